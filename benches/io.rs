@@ -71,6 +71,16 @@ static TEST_SPECS: Lazy<Vec<TestSpec>> = Lazy::new(|| {
             },
             description: String::from("Chunking::Zpaq, Packing::None, Encryption::None"),
         },
+        TestSpec {
+            config: {
+                let mut config = RepoConfig::default();
+                config.chunking = Chunking::FASTCDC;
+                config.packing = Packing::None;
+                config.encryption = Encryption::None;
+                config
+            },
+            description: String::from("Chunking::FastCdc, Packing::None, Encryption::None"),
+        },
         TestSpec {
             config: {
                 let mut config = RepoConfig::default();
@@ -95,6 +105,32 @@ static TEST_SPECS: Lazy<Vec<TestSpec>> = Lazy::new(|| {
                 "Chunking::Zpaq, Packing::Fixed, Encryption::XChaCha20Poly1305",
             ),
         },
+        TestSpec {
+            config: {
+                let mut config = RepoConfig::default();
+                config.chunking = Chunking::FIXED;
+                config.packing = Packing::None;
+                config.encryption = Encryption::None;
+                config.verify_reads = true;
+                config
+            },
+            description: String::from(
+                "Chunking::Fixed, Packing::None, Encryption::None, verify_reads",
+            ),
+        },
+        TestSpec {
+            config: {
+                let mut config = RepoConfig::default();
+                config.chunking = Chunking::FIXED;
+                config.packing = Packing::FIXED;
+                config.encryption = Encryption::XChaCha20Poly1305;
+                config.buffer_pool_size = 16 * 1024 * 1024;
+                config
+            },
+            description: String::from(
+                "Chunking::Fixed, Packing::Fixed, Encryption::XChaCha20Poly1305, buffer pool",
+            ),
+        },
     ]
 });
 
@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::rngs::SmallRng;
+use rand::{RngCore, SeedableRng};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{OpenMode, OpenOptions};
+use acid_store::store::MemoryConfig;
+
+/// The criterion sample size to use.
+const SAMPLE_SIZE: usize = 100;
+
+/// The criterion measurement time.
+const MEASUREMENT_TIME: Duration = Duration::from_secs(10);
+
+/// The size of the object which is removed.
+const OBJECT_SIZE: usize = 1024 * 1024;
+
+/// The numbers of other objects in the repository to benchmark with.
+const REPO_SIZES: [usize; 3] = [1_000, 100_000, 1_000_000];
+
+/// The key of the object which is copied and then removed.
+const SOURCE_KEY: &str = "source";
+
+/// The key of the copy of the object which is removed.
+const TARGET_KEY: &str = "target";
+
+/// Create a repository containing `num_objects` small objects and one larger object.
+fn create_repo(num_objects: usize) -> KeyRepo<String> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())
+        .unwrap();
+
+    // Copies share the chunks of the original, so this is a cheap way to create many objects which
+    // each reference a chunk.
+    let mut object = repo.insert(String::from("object-0"));
+    object.write_all(b"small object").unwrap();
+    object.commit().unwrap();
+    drop(object);
+    for index in 1..num_objects {
+        repo.copy("object-0", format!("object-{}", index));
+    }
+
+    let mut data = vec![0u8; OBJECT_SIZE];
+    SmallRng::from_entropy().fill_bytes(&mut data);
+    let mut object = repo.insert(String::from(SOURCE_KEY));
+    object.write_all(&data).unwrap();
+    object.commit().unwrap();
+    drop(object);
+
+    repo
+}
+
+pub fn remove_object(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("Remove an object");
+
+    group.sample_size(SAMPLE_SIZE);
+    group.measurement_time(MEASUREMENT_TIME);
+
+    for num_objects in REPO_SIZES {
+        let mut repo = create_repo(num_objects);
+        group.bench_function(format!("{} other objects", num_objects), |bencher| {
+            bencher.iter(|| {
+                // Copying is cheap and shares the chunks of the source object, so this measures
+                // the cost of removing an object which shares its chunks with another object.
+                repo.copy(SOURCE_KEY, String::from(TARGET_KEY));
+                repo.remove(TARGET_KEY)
+            });
+        });
+    }
+}
+
+criterion_group!(remove, remove_object);
+criterion_main!(remove);
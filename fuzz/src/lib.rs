@@ -0,0 +1,123 @@
+//! The code behind each fuzz target.
+//!
+//! Each fuzz target in `fuzz_targets/` is a thin wrapper around a function in this crate so that
+//! the same code can be exercised by the smoke tests in `tests/`.
+
+use std::io::{Read, Write};
+
+use acid_store::repo::fuzzing;
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{peek_info, Commit, Compression, OpenMode, OpenOptions};
+use acid_store::store::{BlockKey, BlockType, DataStore, MemoryConfig, OpenStore};
+use arbitrary::{Result, Unstructured};
+
+/// The maximum number of bytes a decompressed buffer can be in the `decompress` target.
+const MAX_DECOMPRESSED_SIZE: usize = 1024 * 1024;
+
+/// Create a repository in a new memory store with an object and a commit.
+///
+/// Encryption is disabled so that the fuzzer can reach the header decoding and deserialization
+/// code without having to get past ciphertext verification.
+fn template_store(compression: Compression) -> MemoryConfig {
+    let store_config = MemoryConfig::new();
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .compression(compression)
+        .mode(OpenMode::CreateNew)
+        .open(&store_config)
+        .expect("Could not create the template repository.");
+
+    let mut object = repo.insert(String::from("object"));
+    object
+        .write_all(b"Hello, world!")
+        .expect("Could not write to the template repository.");
+    object
+        .commit()
+        .expect("Could not commit the template object.");
+    drop(object);
+    repo.commit()
+        .expect("Could not commit the template repository.");
+
+    store_config
+}
+
+/// Replace blocks in the template repository with fuzz-provided bytes.
+fn corrupt_store(store_config: &MemoryConfig, input: &mut Unstructured) -> Result<()> {
+    let version: Option<Vec<u8>> = input.arbitrary()?;
+    let superblock: Option<Vec<u8>> = input.arbitrary()?;
+    let header = input.take_rest();
+
+    let mut store = store_config
+        .open()
+        .expect("Could not open the memory store.");
+    if let Some(version) = version {
+        store.write_block(BlockKey::Version, &version).unwrap();
+    }
+    if let Some(superblock) = superblock {
+        store.write_block(BlockKey::Super, &superblock).unwrap();
+    }
+    for header_id in store.list_blocks(BlockType::Header).unwrap() {
+        store
+            .write_block(BlockKey::Header(header_id), header)
+            .unwrap();
+    }
+
+    Ok(())
+}
+
+/// Open a repository whose version, metadata, and header blocks are provided by the fuzzer.
+///
+/// The repository must fail to open with an error or open successfully; it must never panic. If it
+/// opens, every object in it is verified and read.
+pub fn open_repo(data: &[u8]) {
+    let mut input = Unstructured::new(data);
+    let compression = match input.arbitrary::<bool>() {
+        Ok(true) => Compression::Lz4 { level: 1 },
+        Ok(false) => Compression::None,
+        Err(_) => return,
+    };
+
+    let store_config = template_store(compression);
+    if corrupt_store(&store_config, &mut input).is_err() {
+        return;
+    }
+
+    let _ = peek_info(&store_config);
+
+    let repo: KeyRepo<String> = match OpenOptions::new().mode(OpenMode::Open).open(&store_config) {
+        Ok(repo) => repo,
+        Err(_) => return,
+    };
+
+    let _ = repo.verify();
+    for key in repo.keys() {
+        if let Some(mut object) = repo.object(key) {
+            let mut contents = Vec::new();
+            let _ = object.read_to_end(&mut contents);
+        }
+    }
+}
+
+/// Deserialize a repository header and repository metadata from arbitrary bytes.
+pub fn deserialize_header(data: &[u8]) {
+    let _ = fuzzing::deserialize_header(data);
+    let _ = fuzzing::deserialize_metadata(data);
+}
+
+/// Decompress arbitrary bytes using each compression method with a size limit.
+///
+/// Decompression must never return more bytes than the limit.
+pub fn decompress(data: &[u8]) {
+    let mut input = Unstructured::new(data);
+    let limit = match input.int_in_range(0..=MAX_DECOMPRESSED_SIZE) {
+        Ok(limit) => limit,
+        Err(_) => return,
+    };
+    let compressed = input.take_rest();
+
+    for compression in [Compression::None, Compression::Lz4 { level: 1 }] {
+        if let Ok(output) = fuzzing::decompress(&compression, compressed, limit) {
+            assert!(output.len() <= limit);
+        }
+        let _ = fuzzing::decompress_default(&compression, compressed);
+    }
+}
@@ -0,0 +1,45 @@
+//! Short smoke runs of each fuzz target using random inputs.
+//!
+//! These don't replace running the fuzz targets with `cargo fuzz`, but they check that the targets
+//! run and catch shallow bugs without needing a nightly toolchain. They're ignored by default; run
+//! them with `cargo test -p acid-store-fuzz -- --ignored`.
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// The number of random inputs to pass to each target.
+const ITERATIONS: usize = 2000;
+
+/// The maximum size of each random input.
+const MAX_INPUT_SIZE: usize = 512;
+
+/// Pass `ITERATIONS` random inputs to the given fuzz `target`.
+fn smoke_run(target: fn(&[u8])) {
+    // Use a fixed seed so that failures are reproducible.
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut input = Vec::with_capacity(MAX_INPUT_SIZE);
+
+    for _ in 0..ITERATIONS {
+        input.resize(rng.gen_range(0..=MAX_INPUT_SIZE), 0);
+        rng.fill(input.as_mut_slice());
+        target(&input);
+    }
+}
+
+#[test]
+#[ignore]
+fn smoke_open_repo() {
+    smoke_run(acid_store_fuzz::open_repo);
+}
+
+#[test]
+#[ignore]
+fn smoke_deserialize_header() {
+    smoke_run(acid_store_fuzz::deserialize_header);
+}
+
+#[test]
+#[ignore]
+fn smoke_decompress() {
+    smoke_run(acid_store_fuzz::decompress);
+}
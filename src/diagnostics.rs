@@ -0,0 +1,160 @@
+//! Diagnostic information about the current process.
+//!
+//! This module exposes a read-only view of the repositories which are currently open in this
+//! process. This is meant for things like diagnostics endpoints and debugging, and it never exposes
+//! any key material or repository contents.
+//!
+//! A repository is listed by [`open_repositories`] from the time it is opened until it is dropped.
+//! Repositories opened with [`OpenOptions::open_metadata`] are listed as well. You can attach a
+//! human-readable label to a repository when it is opened using [`OpenOptions::label`].
+//!
+//! # Examples
+//! ```
+//! use acid_store::diagnostics::open_repositories;
+//! use acid_store::repo::{OpenOptions, OpenMode, key::KeyRepo};
+//! use acid_store::store::MemoryConfig;
+//!
+//! let repo: KeyRepo<String> = OpenOptions::new()
+//!     .mode(OpenMode::CreateNew)
+//!     .label("project-a")
+//!     .open(&MemoryConfig::new())
+//!     .unwrap();
+//!
+//! assert!(open_repositories()
+//!     .iter()
+//!     .any(|info| info.label() == Some("project-a")));
+//! ```
+//!
+//! [`OpenOptions::open_metadata`]: crate::repo::OpenOptions::open_metadata
+//! [`OpenOptions::label`]: crate::repo::OpenOptions::label
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use once_cell::sync::Lazy;
+
+use crate::repo::RepoId;
+
+/// The process-global table of open repositories.
+///
+/// This lock is never held while acquiring any other lock, so it is always safe to acquire.
+static OPEN_REPOS: Lazy<Mutex<HashMap<u64, OpenRepoInfo>>> = Lazy::new(Default::default);
+
+/// The source of unique keys for entries in `OPEN_REPOS`.
+static NEXT_ENTRY: AtomicU64 = AtomicU64::new(0);
+
+/// Lock the table of open repositories.
+///
+/// The table is only ever modified by inserting or removing whole entries, so a panic while
+/// holding the lock can't leave it in an inconsistent state. Because of this, we ignore poisoning.
+fn open_repos() -> MutexGuard<'static, HashMap<u64, OpenRepoInfo>> {
+    OPEN_REPOS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The kind of lock a repository holds on its data store.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[non_exhaustive]
+pub enum LockKind {
+    /// An exclusive lock, which prevents any other client from opening the repository.
+    Exclusive,
+}
+
+/// Information about a repository which is currently open in this process.
+///
+/// This is returned by [`open_repositories`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OpenRepoInfo {
+    id: RepoId,
+    label: Option<String>,
+    opened: SystemTime,
+    lock_kind: Option<LockKind>,
+}
+
+impl OpenRepoInfo {
+    /// The ID of the repository.
+    pub fn id(&self) -> RepoId {
+        self.id
+    }
+
+    /// The label attached to the repository with [`OpenOptions::label`], if any.
+    ///
+    /// [`OpenOptions::label`]: crate::repo::OpenOptions::label
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// The time at which the repository was opened and its lock was acquired.
+    pub fn opened(&self) -> SystemTime {
+        self.opened
+    }
+
+    /// How long the repository has been open.
+    ///
+    /// This returns `Duration::ZERO` if the system clock has moved backwards since the repository
+    /// was opened.
+    pub fn open_duration(&self) -> Duration {
+        self.opened.elapsed().unwrap_or(Duration::ZERO)
+    }
+
+    /// The kind of lock the repository holds on its data store.
+    ///
+    /// This returns `None` if the repository's lock has been released with [`Unlock::unlock`] or
+    /// if the repository was opened with [`OpenMode::ReadOnly`], which doesn't acquire a lock.
+    /// This does not reflect locks which were removed by another client via a lock handler; use
+    /// [`Unlock::is_locked`] to check that.
+    ///
+    /// [`Unlock::unlock`]: crate::repo::Unlock::unlock
+    /// [`Unlock::is_locked`]: crate::repo::Unlock::is_locked
+    /// [`OpenMode::ReadOnly`]: crate::repo::OpenMode::ReadOnly
+    pub fn lock_kind(&self) -> Option<LockKind> {
+        self.lock_kind
+    }
+}
+
+/// Return a list of the repositories which are currently open in this process.
+///
+/// The returned list is a snapshot; repositories may be opened or dropped concurrently. The list is
+/// sorted by the time each repository was opened, oldest first.
+pub fn open_repositories() -> Vec<OpenRepoInfo> {
+    let mut repos = open_repos().values().cloned().collect::<Vec<_>>();
+    repos.sort_by_key(|info| info.opened);
+    repos
+}
+
+/// An entry in the table of open repositories.
+///
+/// The entry is removed when this value is dropped.
+#[derive(Debug)]
+pub(crate) struct Registration(u64);
+
+impl Registration {
+    /// Add a repository which has just acquired a `lock_kind` lock to the table.
+    ///
+    /// If `lock_kind` is `None`, the repository was opened without acquiring a lock.
+    pub(crate) fn new(id: RepoId, label: Option<String>, lock_kind: Option<LockKind>) -> Self {
+        let entry = NEXT_ENTRY.fetch_add(1, Ordering::Relaxed);
+        let info = OpenRepoInfo {
+            id,
+            label,
+            opened: SystemTime::now(),
+            lock_kind,
+        };
+        open_repos().insert(entry, info);
+        Self(entry)
+    }
+
+    /// Record that this repository has released its lock.
+    pub(crate) fn unlocked(&self) {
+        if let Some(info) = open_repos().get_mut(&self.0) {
+            info.lock_kind = None;
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        open_repos().remove(&self.0);
+    }
+}
@@ -31,6 +31,14 @@ pub enum Error {
     #[error("A resource is not locked.")]
     NotLocked,
 
+    /// The repository is still being used by another thread.
+    #[error("The repository is still being used by another thread.")]
+    InUse,
+
+    /// The repository or data store is read-only.
+    #[error("The repository or data store is read-only.")]
+    ReadOnly,
+
     /// The repository is corrupt.
     #[error("The repository is corrupt.")]
     Corrupt,
@@ -39,6 +47,10 @@ pub enum Error {
     #[error("This data store is an unsupported format.")]
     UnsupportedStore,
 
+    /// This is not a data store.
+    #[error("This is not a data store.")]
+    InvalidStore,
+
     /// This repository is an unsupported format.
     #[error("This repository is an unsupported format.")]
     UnsupportedRepo,
@@ -87,6 +99,78 @@ pub enum Error {
     #[error("Ciphertext verification failed or data is otherwise invalid.")]
     InvalidData,
 
+    /// The repository header is larger than the configured maximum size.
+    #[error("The repository header is {size} bytes, which exceeds the limit of {limit} bytes.")]
+    HeaderTooLarge {
+        /// The size of the encoded header in bytes.
+        size: u64,
+
+        /// The configured maximum size of the header in bytes.
+        limit: u64,
+    },
+
+    /// An option is not supported by the repository's compatibility target.
+    ///
+    /// See [`RepoConfig::compatibility_target`] for details.
+    ///
+    /// [`RepoConfig::compatibility_target`]: crate::repo::RepoConfig::compatibility_target
+    #[error(
+        "The option `{option}` is not supported by version {target} of the repository format."
+    )]
+    Incompatible {
+        /// The name of the option or feature which is not supported.
+        option: &'static str,
+
+        /// The compatibility target of the repository.
+        target: crate::repo::FormatVersion,
+    },
+
+    /// Committing would perform a destructive operation which was not authorized.
+    ///
+    /// See [`DestructivePolicy`] for details.
+    ///
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    #[error("Destructive operation not authorized: {scope} ({objects} objects, {bytes} bytes).")]
+    DestructiveNotAuthorized {
+        /// The kind of destructive operation which was not authorized.
+        scope: crate::repo::DestructiveScope,
+
+        /// The number of objects removed by the operation since the last commit.
+        objects: u64,
+
+        /// The number of bytes removed by the operation since the last commit.
+        bytes: u64,
+    },
+
+    /// The operation was cancelled.
+    #[error("The operation was cancelled.")]
+    Cancelled,
+
+    /// A block in the data store doesn't match the checksum it was written with.
+    ///
+    /// This is returned by [`VerifyingStore`] when the underlying storage has corrupted a block.
+    ///
+    /// [`VerifyingStore`]: crate::store::VerifyingStore
+    #[error("The block {key:?} does not match its checksum.")]
+    ChecksumMismatch {
+        /// The key of the corrupt block.
+        key: crate::store::BlockKey,
+    },
+
+    /// A chunk read from the repository doesn't match its hash.
+    ///
+    /// This is returned when [`RepoConfig::verify_reads`] is enabled.
+    ///
+    /// [`RepoConfig::verify_reads`]: crate::repo::RepoConfig::verify_reads
+    #[error("A chunk in the block {block_id:?} does not match its hash.")]
+    ChunkMismatch {
+        /// The BLAKE3 hash which the chunk was written with.
+        hash: [u8; blake3::OUT_LEN],
+
+        /// The ID of the block which the chunk was read from.
+        block_id: crate::store::BlockId,
+    },
+
     /// An I/O error occurred.
     #[error("{0}")]
     Io(io::Error),
@@ -8,6 +8,12 @@
 //!
 //! - [`KeyRepo`][crate::repo::key] is an object store which maps keys to seekable binary blobs.
 //! - [`ValueRepo`][crate::repo::value] is a persistent, heterogeneous, map-like collection.
+//! - [`VersionRepo`][crate::repo::version] is an object store which stores a history of versions
+//! of each object.
+//! - [`ContentRepo`][crate::repo::content] is a content-addressable storage which stores data
+//! keyed by its cryptographic hash.
+//! - [`ForkRepo`][crate::repo::fork] is an object store which maps each key to multiple named
+//! streams of data.
 //! - [`FileRepo`] is a virtual file system which can be mounted via FUSE and supports file
 //! metadata, special files, sparse files, hard links, and importing and exporting files to the
 //! local file system.
@@ -19,10 +25,23 @@
 //! be found in the [`crate::store`] module.
 //!
 //! - [`DirectoryStore`] stores data in a directory in the local file system.
+//! - [`FileStore`] stores data in a single archive file in the local file system.
+//! - [`DeviceStore`] stores data directly on a raw block device.
 //! - [`SqliteStore`] stores data in a SQLite database.
+//! - [`SledStore`] stores data in a sled database.
+//! - [`RocksdbStore`] stores data in a RocksDB database.
+//! - [`PostgresStore`] stores data in a PostgreSQL database.
 //! - [`RedisStore`] stores data on a Redis server.
 //! - [`S3Store`] stores data in an Amazon S3 bucket.
+//! - [`AzureStore`] stores data in an Azure Blob Storage container.
+//! - [`GcsStore`] stores data in a Google Cloud Storage bucket.
+//! - [`B2Store`] stores data in a Backblaze B2 bucket.
+//! - [`DropboxStore`] stores data in a user's Dropbox.
+//! - [`GoogleDriveStore`] stores data in a Google Drive folder.
+//! - [`IpfsStore`] stores data on an IPFS node.
 //! - [`SftpStore`] stores data on an SFTP server.
+//! - [`FtpStore`] stores data on an FTP or FTPS server.
+//! - [`HttpStore`] reads data from a static HTTP server.
 //! - [`RcloneStore`] stores data in a varity of cloud storage backends using
 //! [rclone].
 //! - [`MemoryStore`] stores data in memory.
@@ -75,27 +94,46 @@
 //! Feature        | Description
 //! ---            | ---
 //! `repo-value`   | Use the [`ValueRepo`] repository type
+//! `repo-version` | Use the [`VersionRepo`] repository type
+//! `repo-content` | Use the [`ContentRepo`] repository type
+//! `repo-fork`    | Use the [`ForkRepo`] repository type
 //! `repo-file`    | Use the [`FileRepo`] repository type
 //!
 //! These features enable different [`DataStore`] implementations.
 //!
-//! Feature           | Description
-//! ---               | ---
-//! `store-directory` | Store data in a directory in the local file system
-//! `store-sqlite`    | Store data in a SQLite database
-//! `store-redis`     | Store data on a Redis server
-//! `store-s3`        | Store data in an Amazon S3 bucket
-//! `store-sftp`      | Store data on an SFTP server
-//! `store-rclone`    | Store data in cloud storage via [rclone]
+//! Feature              | Description
+//! ---                  | ---
+//! `store-directory`    | Store data in a directory in the local file system
+//! `store-file`         | Store data in a single archive file in the local file system
+//! `store-device`       | Store data directly on a raw block device
+//! `store-sqlite`       | Store data in a SQLite database
+//! `store-sled`         | Store data in a sled database
+//! `store-rocksdb`      | Store data in a RocksDB database
+//! `store-postgres`     | Store data in a PostgreSQL database
+//! `store-redis`        | Store data on a Redis server
+//! `store-s3`           | Store data in an Amazon S3 bucket
+//! `store-azure`        | Store data in an Azure Blob Storage container
+//! `store-gcs`          | Store data in a Google Cloud Storage bucket
+//! `store-b2`           | Store data in a Backblaze B2 bucket
+//! `store-dropbox`      | Store data in a user's Dropbox
+//! `store-google-drive` | Store data in a Google Drive folder
+//! `store-ipfs`         | Store data on an IPFS node
+//! `store-sftp`         | Store data on an SFTP server
+//! `store-ftp`          | Store data on an FTP or FTPS server
+//! `store-http`         | Read data from a static HTTP server
+//! `store-rclone`       | Store data in cloud storage via [rclone]
 //!
 //! These features enable additional functionality.
 //!
-//! Feature           | Description
-//! ---               | ---
-//! `encryption`      | Encrypt repositories
-//! `compression`     | Compress repositories
-//! `file-metadata`   | Store file metadata and special file types in [`FileRepo`]
-//! `fuse-mount`      | Mount a [`FileRepo`] as a FUSE file system
+//! Feature                | Description
+//! ---                    | ---
+//! `encryption`           | Encrypt repositories
+//! `compression`          | Compress repositories
+//! `file-metadata`        | Store file metadata and special file types in [`FileRepo`]
+//! `fuse-mount`           | Mount a [`FileRepo`] as a FUSE file system
+//! `store-recording`      | Record and replay data store operations for debugging
+//! `store-directory-mmap` | Read large blocks from a [`DirectoryStore`] using memory mapping
+//! `export`               | Export repositories to plaintext tar archives
 //!
 //! These features have native dependencies. This table shows their package names on Ubuntu.
 //!
@@ -103,29 +141,50 @@
 //! ---             | ---                          | ---
 //! `file-metadata` | `libacl1-dev`                | `acl`
 //! `fuse-mount`    | `libfuse3-dev`, `pkg-config` | `fuse3`
+//! `store-rocksdb` | `libclang-dev`               |
 //!
 //! [rclone]: https://rclone.org/
 //!
 //! [`KeyRepo`]: crate::repo::key
 //! [`FileRepo`]: crate::repo::file
 //! [`ValueRepo`]: crate::repo::value
+//! [`VersionRepo`]: crate::repo::version
+//! [`ContentRepo`]: crate::repo::content
+//! [`ForkRepo`]: crate::repo::fork
 //! [`StateRepo`]: crate::repo::state
 //!
 //! [`DataStore`]: crate::store::DataStore
 //! [`DirectoryStore`]: crate::store::DirectoryStore
+//! [`FileStore`]: crate::store::FileStore
+//! [`DeviceStore`]: crate::store::DeviceStore
 //! [`SqliteStore`]: crate::store::SqliteStore
+//! [`SledStore`]: crate::store::SledStore
+//! [`RocksdbStore`]: crate::store::RocksdbStore
+//! [`PostgresStore`]: crate::store::PostgresStore
 //! [`RedisStore`]: crate::store::RedisStore
 //! [`S3Store`]: crate::store::S3Store
+//! [`AzureStore`]: crate::store::AzureStore
+//! [`GcsStore`]: crate::store::GcsStore
+//! [`B2Store`]: crate::store::B2Store
+//! [`DropboxStore`]: crate::store::DropboxStore
+//! [`GoogleDriveStore`]: crate::store::GoogleDriveStore
+//! [`IpfsStore`]: crate::store::IpfsStore
 //! [`SftpStore`]: crate::store::SftpStore
+//! [`FtpStore`]: crate::store::FtpStore
+//! [`HttpStore`]: crate::store::HttpStore
 //! [`RcloneStore`]: crate::store::RcloneStore
 //! [`MemoryStore`]: crate::store::MemoryStore
 
-#![forbid(unsafe_code)]
+// Memory mapping can't be done safely, so the feature which uses it only allows `unsafe` where it
+// opts in explicitly.
+#![cfg_attr(not(feature = "store-directory-mmap"), forbid(unsafe_code))]
+#![cfg_attr(feature = "store-directory-mmap", deny(unsafe_code))]
 
 pub use uuid;
 
 pub use error::{Error, Result};
 
+pub mod diagnostics;
 mod error;
 mod id;
 pub mod repo;
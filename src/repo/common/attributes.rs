@@ -0,0 +1,356 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::handle::ObjectHandle;
+use super::key::Key;
+use super::snapshot::SnapshotId;
+
+/// The metadata and tags attached to an object.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyAttributes {
+    /// The serialized metadata of the object.
+    pub metadata: Option<Vec<u8>>,
+
+    /// The tags of the object.
+    pub tags: BTreeSet<String>,
+
+    /// The time at which the object expires.
+    #[serde(default)]
+    pub expires: Option<SystemTime>,
+
+    /// The snapshots of the object and their handles, from oldest to newest.
+    #[serde(default)]
+    pub snapshots: Vec<(SnapshotId, ObjectHandle)>,
+}
+
+impl KeyAttributes {
+    /// Return whether this object has no metadata, tags, expiration time, or snapshots.
+    fn is_empty(&self) -> bool {
+        self.metadata.is_none()
+            && self.tags.is_empty()
+            && self.expires.is_none()
+            && self.snapshots.is_empty()
+    }
+}
+
+/// The metadata and tags of the objects in an instance and an index of their tags.
+///
+/// Only the map of keys to their attributes is persisted. The index of tags is rebuilt when the
+/// table is read.
+#[derive(Debug, Clone)]
+pub struct AttributeTable<K> {
+    /// A map of object keys to their metadata and tags.
+    entries: HashMap<K, KeyAttributes>,
+
+    /// A map of tags to the keys of objects which have them.
+    index: HashMap<String, HashSet<K>>,
+}
+
+impl<K> Default for AttributeTable<K> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            index: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Key> AttributeTable<K> {
+    /// Return a new `AttributeTable` containing the given `entries`.
+    pub fn from_entries(entries: HashMap<K, KeyAttributes>) -> Self {
+        let mut index = HashMap::<String, HashSet<K>>::new();
+        for (key, attributes) in &entries {
+            for tag in &attributes.tags {
+                index.entry(tag.clone()).or_default().insert(key.clone());
+            }
+        }
+        Self { entries, index }
+    }
+
+    /// The map of object keys to their metadata and tags.
+    pub fn entries(&self) -> &HashMap<K, KeyAttributes> {
+        &self.entries
+    }
+
+    /// Return whether no object has any metadata or tags.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the serialized metadata of the object with the given `key`.
+    pub fn metadata<Q>(&self, key: &Q) -> Option<&[u8]>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.get(key)?.metadata.as_deref()
+    }
+
+    /// Set the serialized `metadata` of the object with the given `key`.
+    pub fn set_metadata(&mut self, key: K, metadata: Vec<u8>) {
+        self.entries.entry(key).or_default().metadata = Some(metadata);
+    }
+
+    /// Remove the metadata of the object with the given `key`.
+    ///
+    /// This returns whether the object had metadata.
+    pub fn remove_metadata<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let attributes = match self.entries.get_mut(key) {
+            Some(attributes) => attributes,
+            None => return false,
+        };
+        let removed = attributes.metadata.take().is_some();
+        if attributes.is_empty() {
+            self.entries.remove(key);
+        }
+        removed
+    }
+
+    /// Return the tags of the object with the given `key`.
+    pub fn tags<Q>(&self, key: &Q) -> impl Iterator<Item = &str>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries
+            .get(key)
+            .into_iter()
+            .flat_map(|attributes| attributes.tags.iter().map(String::as_str))
+    }
+
+    /// Add a `tag` to the object with the given `key`.
+    ///
+    /// This returns whether the object didn't already have the tag.
+    pub fn add_tag(&mut self, key: K, tag: String) -> bool {
+        let attributes = self.entries.entry(key.clone()).or_default();
+        if !attributes.tags.insert(tag.clone()) {
+            return false;
+        }
+        self.index.entry(tag).or_default().insert(key);
+        true
+    }
+
+    /// Remove a `tag` from the object with the given `key`.
+    ///
+    /// This returns whether the object had the tag.
+    pub fn remove_tag<Q>(&mut self, key: &Q, tag: &str) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let attributes = match self.entries.get_mut(key) {
+            Some(attributes) => attributes,
+            None => return false,
+        };
+        if !attributes.tags.remove(tag) {
+            return false;
+        }
+        if attributes.is_empty() {
+            self.entries.remove(key);
+        }
+        self.unindex(key, tag);
+        true
+    }
+
+    /// Return an iterator over the keys of objects which have the given `tag`.
+    pub fn keys_with_tag(&self, tag: &str) -> impl Iterator<Item = &K> {
+        self.index.get(tag).into_iter().flatten()
+    }
+
+    /// Return the time at which the object with the given `key` expires.
+    pub fn expires<Q>(&self, key: &Q) -> Option<SystemTime>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.get(key)?.expires
+    }
+
+    /// Set the time at which the object with the given `key` expires.
+    pub fn set_expires(&mut self, key: K, expires: SystemTime) {
+        self.entries.entry(key).or_default().expires = Some(expires);
+    }
+
+    /// Remove the expiration time of the object with the given `key`.
+    pub fn remove_expires<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(attributes) = self.entries.get_mut(key) {
+            attributes.expires = None;
+            if attributes.is_empty() {
+                self.entries.remove(key);
+            }
+        }
+    }
+
+    /// Return the keys of objects which expire at or before `now`.
+    pub fn expired(&self, now: SystemTime) -> Vec<K> {
+        self.entries
+            .iter()
+            .filter(|(_, attributes)| matches!(attributes.expires, Some(expires) if expires <= now))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Return the IDs of the snapshots of the object with the given `key`.
+    pub fn snapshots<Q>(&self, key: &Q) -> impl Iterator<Item = SnapshotId> + '_
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries
+            .get(key)
+            .into_iter()
+            .flat_map(|attributes| attributes.snapshots.iter().map(|(id, _)| *id))
+    }
+
+    /// Return the handle of the snapshot of the object with the given `key` and `id`.
+    pub fn snapshot<Q>(&self, key: &Q, id: SnapshotId) -> Option<&ObjectHandle>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries
+            .get(key)?
+            .snapshots
+            .iter()
+            .find(|(snapshot_id, _)| *snapshot_id == id)
+            .map(|(_, handle)| handle)
+    }
+
+    /// Add a snapshot with the given `id` and `handle` to the object with the given `key`.
+    pub fn add_snapshot(&mut self, key: K, id: SnapshotId, handle: ObjectHandle) {
+        self.entries
+            .entry(key)
+            .or_default()
+            .snapshots
+            .push((id, handle));
+    }
+
+    /// Remove the snapshot with the given `id` from the object with the given `key`.
+    ///
+    /// This returns the handle of the snapshot, which must be removed from the repository.
+    pub fn remove_snapshot<Q>(&mut self, key: &Q, id: SnapshotId) -> Option<ObjectHandle>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let attributes = self.entries.get_mut(key)?;
+        let index = attributes
+            .snapshots
+            .iter()
+            .position(|(snapshot_id, _)| *snapshot_id == id)?;
+        let (_, handle) = attributes.snapshots.remove(index);
+        if attributes.is_empty() {
+            self.entries.remove(key);
+        }
+        Some(handle)
+    }
+
+    /// Remove the attributes of the object with the given `key`.
+    ///
+    /// This returns the handles of the object's snapshots, which must be removed from the
+    /// repository.
+    pub fn remove<Q>(&mut self, key: &Q) -> Vec<ObjectHandle>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.entries.remove(key) {
+            Some(attributes) => {
+                for tag in &attributes.tags {
+                    self.unindex(key, tag);
+                }
+                attributes
+                    .snapshots
+                    .into_iter()
+                    .map(|(_, handle)| handle)
+                    .collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Move the metadata, tags, and expiration time of the object at `source` to `dest`.
+    pub fn rename<Q>(&mut self, source: &Q, dest: K)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(attributes) = self.take(source) {
+            self.put(dest, attributes);
+        }
+    }
+
+    /// Exchange the attributes of the objects at `first` and `second`.
+    pub fn swap(&mut self, first: &K, second: &K) {
+        let first_attributes = self.take(first);
+        let second_attributes = self.take(second);
+        if let Some(attributes) = second_attributes {
+            self.put(first.clone(), attributes);
+        }
+        if let Some(attributes) = first_attributes {
+            self.put(second.clone(), attributes);
+        }
+    }
+
+    /// Remove the attributes of every object.
+    ///
+    /// This returns the handles of every snapshot, which must be removed from the repository.
+    pub fn clear(&mut self) -> Vec<ObjectHandle> {
+        self.index.clear();
+        self.entries
+            .drain()
+            .flat_map(|(_, attributes)| attributes.snapshots)
+            .map(|(_, handle)| handle)
+            .collect()
+    }
+
+    /// Remove and return the attributes of the object with the given `key` without discarding them.
+    fn take<Q>(&mut self, key: &Q) -> Option<KeyAttributes>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let attributes = self.entries.remove(key)?;
+        for tag in &attributes.tags {
+            self.unindex(key, tag);
+        }
+        Some(attributes)
+    }
+
+    /// Set the attributes of the object with the given `key`, indexing its tags.
+    fn put(&mut self, key: K, attributes: KeyAttributes) {
+        for tag in &attributes.tags {
+            self.index
+                .entry(tag.clone())
+                .or_default()
+                .insert(key.clone());
+        }
+        self.entries.insert(key, attributes);
+    }
+
+    /// Remove the object with the given `key` from the index for `tag`.
+    fn unindex<Q>(&mut self, key: &Q, tag: &str)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(keys) = self.index.get_mut(tag) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.index.remove(tag);
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use rmp::Marker;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+use crate::store::{BlockKey, BlockType, DataStore, OpenStore};
+
+use super::encryption::Encryption;
+use super::metadata::peek_info_store;
+
+/// The minimum size of a block for its byte distribution to be checked.
+///
+/// The distribution of bytes in very small blocks isn't meaningful enough to draw conclusions from.
+const MIN_DISTRIBUTION_SIZE: usize = 128;
+
+/// The chi-squared statistic above which a block's byte distribution is considered non-uniform.
+///
+/// For uniformly distributed bytes, this statistic has 255 degrees of freedom, a mean of 255, and
+/// a standard deviation of about 22.6, so this threshold is more than ten standard deviations above
+/// the mean. Ciphertext should practically never exceed it.
+const MAX_CHI_SQUARED: f64 = 512.0;
+
+/// The magic number at the start of an LZ4 frame.
+const LZ4_MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+
+/// The magic number at the start of a Zstandard frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The reason a block was reported by [`audit_encryption`].
+///
+/// [`audit_encryption`]: crate::repo::audit_encryption
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SuspectReason {
+    /// The repository is not configured to use encryption.
+    EncryptionDisabled,
+
+    /// The block is too small to contain the framing added by the configured encryption method.
+    TooShort,
+
+    /// The block starts with a magic number or contains a serialized structure which is only
+    /// present in unencrypted data.
+    PlaintextMarker,
+
+    /// The bytes in the block are not uniformly distributed like ciphertext should be.
+    NonUniform,
+}
+
+/// A block which was reported by [`audit_encryption`] as likely being unencrypted.
+///
+/// [`audit_encryption`]: crate::repo::audit_encryption
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SuspectBlock {
+    key: BlockKey,
+    size: usize,
+    reason: SuspectReason,
+}
+
+impl SuspectBlock {
+    /// The key of the block in the data store.
+    pub fn key(&self) -> BlockKey {
+        self.key
+    }
+
+    /// The size of the block in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Why the block looks like it is unencrypted.
+    pub fn reason(&self) -> SuspectReason {
+        self.reason
+    }
+}
+
+/// The results of auditing a repository with [`audit_encryption`].
+///
+/// [`audit_encryption`]: crate::repo::audit_encryption
+#[derive(Debug, Clone)]
+pub struct EncryptionAudit {
+    encryption: Encryption,
+    total_blocks: u64,
+    scanned_blocks: u64,
+    suspect_blocks: Vec<SuspectBlock>,
+}
+
+impl EncryptionAudit {
+    /// The encryption method the repository is configured to use.
+    pub fn encryption(&self) -> &Encryption {
+        &self.encryption
+    }
+
+    /// The number of blocks in the data store which could have been scanned.
+    pub fn total_blocks(&self) -> u64 {
+        self.total_blocks
+    }
+
+    /// The number of blocks which were actually scanned.
+    pub fn scanned_blocks(&self) -> u64 {
+        self.scanned_blocks
+    }
+
+    /// The fraction of blocks in the data store which were scanned.
+    ///
+    /// This is a number between `0.0` and `1.0`. If there are no blocks in the data store, this is
+    /// `1.0`.
+    pub fn sampled_fraction(&self) -> f64 {
+        if self.total_blocks == 0 {
+            1.0
+        } else {
+            self.scanned_blocks as f64 / self.total_blocks as f64
+        }
+    }
+
+    /// The blocks which look like they are unencrypted.
+    pub fn suspect_blocks(&self) -> &[SuspectBlock] {
+        &self.suspect_blocks
+    }
+
+    /// Return whether none of the scanned blocks look like they are unencrypted.
+    pub fn is_clean(&self) -> bool {
+        self.suspect_blocks.is_empty()
+    }
+}
+
+/// Return whether `data` is a complete MessagePack map.
+///
+/// About one in sixteen random bytes looks like the header of a MessagePack map, so we only
+/// consider data to be a map if it decodes as exactly one map with no bytes left over.
+fn is_messagepack_map(data: &[u8]) -> bool {
+    let is_map_header = matches!(
+        data.first().map(|&byte| Marker::from_u8(byte)),
+        Some(Marker::FixMap(_) | Marker::Map16 | Marker::Map32)
+    );
+    if !is_map_header {
+        return false;
+    }
+
+    let mut remaining = data;
+    let is_decoded =
+        IgnoredAny::deserialize(&mut rmp_serde::Deserializer::new(&mut remaining)).is_ok();
+    is_decoded && remaining.is_empty()
+}
+
+/// Return the chi-squared statistic of the byte distribution of `data` against a uniform one.
+fn chi_squared(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let expected = data.len() as f64 / 256.0;
+    counts
+        .iter()
+        .map(|&count| {
+            let difference = count as f64 - expected;
+            difference * difference / expected
+        })
+        .sum()
+}
+
+/// Check whether the given `data` looks like it was encrypted with `encryption`.
+///
+/// This returns the reason the data looks unencrypted or `None` if it looks encrypted.
+fn check_block(data: &[u8], encryption: &Encryption) -> Option<SuspectReason> {
+    if *encryption == Encryption::None {
+        return Some(SuspectReason::EncryptionDisabled);
+    }
+
+    if data.len() < encryption.overhead() {
+        return Some(SuspectReason::TooShort);
+    }
+
+    if data.starts_with(&LZ4_MAGIC) || data.starts_with(&ZSTD_MAGIC) || is_messagepack_map(data) {
+        return Some(SuspectReason::PlaintextMarker);
+    }
+
+    if data.len() >= MIN_DISTRIBUTION_SIZE && chi_squared(data) > MAX_CHI_SQUARED {
+        return Some(SuspectReason::NonUniform);
+    }
+
+    None
+}
+
+/// Audit the encryption of the repository in the given `store`.
+fn audit_encryption_store(
+    store: &mut impl DataStore,
+    sample_fraction: f64,
+) -> crate::Result<EncryptionAudit> {
+    // The super block is never encrypted, so we can read the configuration without a password.
+    let encryption = peek_info_store(store)?.config().encryption.clone();
+
+    let sample_fraction = sample_fraction.clamp(0.0, 1.0);
+
+    let mut keys = Vec::new();
+    for block_id in store
+        .list_blocks(BlockType::Header)
+        .map_err(crate::Error::Store)?
+    {
+        keys.push(BlockKey::Header(block_id));
+    }
+    for block_id in store
+        .list_blocks(BlockType::Lock)
+        .map_err(crate::Error::Store)?
+    {
+        keys.push(BlockKey::Lock(block_id));
+    }
+    for block_id in store
+        .list_blocks(BlockType::Data)
+        .map_err(crate::Error::Store)?
+    {
+        keys.push(BlockKey::Data(block_id));
+    }
+
+    let mut scanned_blocks = 0u64;
+    let mut suspect_blocks = Vec::new();
+
+    for (index, key) in keys.iter().enumerate() {
+        // Sample blocks evenly so that the results are deterministic and so that every type of
+        // block is represented in the sample.
+        let is_sampled = ((index + 1) as f64 * sample_fraction).floor()
+            > (index as f64 * sample_fraction).floor();
+        if !is_sampled {
+            continue;
+        }
+
+        // A block may have been removed since we listed it, in which case we skip it.
+        let data = match store.read_block(*key).map_err(crate::Error::Store)? {
+            Some(data) => data,
+            None => continue,
+        };
+        scanned_blocks += 1;
+
+        if let Some(reason) = check_block(&data, &encryption) {
+            suspect_blocks.push(SuspectBlock {
+                key: *key,
+                size: data.len(),
+                reason,
+            });
+        }
+    }
+
+    Ok(EncryptionAudit {
+        encryption,
+        total_blocks: keys.len() as u64,
+        scanned_blocks,
+        suspect_blocks,
+    })
+}
+
+/// Check whether the blocks in a repository look like they are encrypted.
+///
+/// This accepts the `config` used to open the data store and the fraction of blocks to scan as
+/// `sample_fraction`, which is a number between `0.0` and `1.0`. Blocks are sampled evenly across
+/// the data store, and a `sample_fraction` of `1.0` scans every block.
+///
+/// This does not require the repository's password and does not acquire a lock on the repository.
+/// Each scanned data, header, and lock block is checked for the framing of the repository's
+/// configured [`Encryption`] method, for magic numbers and MessagePack maps which only appear in
+/// unencrypted data, and for a byte distribution which is uniform like ciphertext. The super block
+/// and version block are never encrypted, so they are not scanned. If the repository is not
+/// configured to use encryption, every scanned block is reported.
+///
+/// This check is a heuristic. It can't prove that data is encrypted without the encryption key,
+/// and unencrypted data which is already uniformly distributed (like data which was compressed or
+/// encrypted before being written to the repository) will not be reported. A clean audit means
+/// that no scanned block looks like unencrypted data, not that every block is encrypted.
+///
+/// # Errors
+/// - `Error::NotFound`: There is no repository in the data store.
+/// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+/// - `Error::UnsupportedStore`: The data store is an unsupported format. This can happen if
+/// the serialized data format changed or if the storage represented by this value does not
+/// contain a valid data store.
+/// - `Error::Store`: An error occurred with the data store.
+/// - `Error::Io`: An I/O error occurred.
+///
+/// [`Encryption`]: crate::repo::Encryption
+pub fn audit_encryption(
+    config: &impl OpenStore,
+    sample_fraction: f64,
+) -> crate::Result<EncryptionAudit> {
+    let mut store = config.open()?;
+    audit_encryption_store(&mut store, sample_fraction)
+}
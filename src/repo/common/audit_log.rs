@@ -0,0 +1,86 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::destructive::DestructivePolicy;
+use super::encryption::ResourceLimit;
+
+/// A security-relevant operation recorded in a repository's audit log.
+///
+/// See [`KeyRepo::audit_log`] for details.
+///
+/// [`KeyRepo::audit_log`]: crate::repo::key::KeyRepo::audit_log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum AuditOperation {
+    /// The repository was created.
+    Created,
+
+    /// The password was changed.
+    ///
+    /// This includes changes which only change the parameters of the key derivation function.
+    PasswordChanged {
+        /// The new memory limit of the key derivation function.
+        memory_limit: ResourceLimit,
+
+        /// The new operations limit of the key derivation function.
+        operations_limit: ResourceLimit,
+    },
+
+    /// The [`DestructivePolicy`] was changed.
+    ///
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    DestructivePolicyChanged {
+        /// The new policy.
+        policy: DestructivePolicy,
+    },
+}
+
+/// An entry in a repository's audit log.
+///
+/// See [`KeyRepo::audit_log`] for details.
+///
+/// [`KeyRepo::audit_log`]: crate::repo::key::KeyRepo::audit_log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct AuditEntry {
+    /// The position of this entry in the log.
+    ///
+    /// Entries are numbered consecutively starting at zero, so a gap in the sequence numbers or a
+    /// log which doesn't start at zero means that entries were removed.
+    pub sequence: u64,
+
+    /// The operation which was performed.
+    pub operation: AuditOperation,
+
+    /// The time the operation was performed.
+    pub time: SystemTime,
+
+    /// The writer ID of the client which performed the operation, if one was provided.
+    ///
+    /// See [`OpenOptions::audit_writer`] for details.
+    ///
+    /// [`OpenOptions::audit_writer`]: crate::repo::OpenOptions::audit_writer
+    pub writer: Option<String>,
+}
+
+/// Append an entry for `operation` performed now by `writer` to the end of the audit `log`.
+pub fn record_audit(log: &mut Vec<AuditEntry>, operation: AuditOperation, writer: Option<String>) {
+    append_audit(log, operation, SystemTime::now(), writer);
+}
+
+/// Append an entry for `operation` performed at `time` by `writer` to the end of the audit `log`.
+pub fn append_audit(
+    log: &mut Vec<AuditEntry>,
+    operation: AuditOperation,
+    time: SystemTime,
+    writer: Option<String>,
+) {
+    let sequence = log.last().map_or(0, |entry| entry.sequence + 1);
+    log.push(AuditEntry {
+        sequence,
+        operation,
+        time,
+        writer,
+    });
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::mem;
+
+use super::handle::ObjectHandle;
+use super::key::Key;
+use super::repository::KeyRepo;
+
+/// An operation in a `Batch`.
+#[derive(Debug)]
+enum BatchOp<K> {
+    /// Add an object with the data which was written to the given handle.
+    Insert(K, ObjectHandle),
+
+    /// Remove an object.
+    Remove(K),
+
+    /// Copy an object from `source` to `dest`.
+    Copy { source: K, dest: K },
+}
+
+/// A group of changes to a [`KeyRepo`] which are applied atomically.
+///
+/// Changes are recorded in the batch and don't affect the repository until [`apply`] is called,
+/// at which point they are applied in the order they were recorded. Either every change in the
+/// batch is applied or none of them are. This can be used to keep several objects consistent with
+/// each other, like an index object and the objects it refers to.
+///
+/// The data for objects inserted into the batch is written to the data store as soon as it is
+/// added to the batch, but it is not associated with a key until the batch is applied. If the
+/// batch is dropped without being applied, that data is discarded.
+///
+/// Like other changes to the repository, changes applied from a batch are not persisted to the
+/// data store until they are committed.
+///
+/// This value is created by [`KeyRepo::batch`].
+///
+/// [`KeyRepo`]: crate::repo::key::KeyRepo
+/// [`KeyRepo::batch`]: crate::repo::key::KeyRepo::batch
+/// [`apply`]: crate::repo::key::Batch::apply
+#[derive(Debug)]
+pub struct Batch<'a, K: Key> {
+    repo: &'a mut KeyRepo<K>,
+    ops: Vec<BatchOp<K>>,
+}
+
+impl<'a, K: Key> Batch<'a, K> {
+    /// Create a new empty batch of changes to `repo`.
+    pub(super) fn new(repo: &'a mut KeyRepo<K>) -> Self {
+        Self {
+            repo,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Add an object with the given `key` containing `data`.
+    ///
+    /// If another object with the same `key` exists when the batch is applied, it is replaced.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn insert(&mut self, key: K, data: &[u8]) -> crate::Result<&mut Self> {
+        let handle = self.repo.write_new_handle(data)?;
+        self.ops.push(BatchOp::Insert(key, handle));
+        Ok(self)
+    }
+
+    /// Remove the object with the given `key`.
+    ///
+    /// If there is no object with the given `key` when the batch is applied, this does nothing.
+    pub fn remove(&mut self, key: K) -> &mut Self {
+        self.ops.push(BatchOp::Remove(key));
+        self
+    }
+
+    /// Copy the object at `source` to `dest`.
+    ///
+    /// If another object already exists at `dest` when the batch is applied, it is replaced.
+    pub fn copy(&mut self, source: K, dest: K) -> &mut Self {
+        self.ops.push(BatchOp::Copy { source, dest });
+        self
+    }
+
+    /// Apply the changes in this batch to the repository.
+    ///
+    /// If this returns `Err`, no changes are applied and the batch is discarded.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: The source of a copy doesn't exist at the point in the batch where it
+    /// is copied.
+    pub fn apply(mut self) -> crate::Result<()> {
+        // Check that every change can be applied before applying any of them.
+        let mut exists = HashMap::<&K, bool>::new();
+        for op in &self.ops {
+            match op {
+                BatchOp::Insert(key, _) => {
+                    exists.insert(key, true);
+                }
+                BatchOp::Remove(key) => {
+                    exists.insert(key, false);
+                }
+                BatchOp::Copy { source, dest } => {
+                    let source_exists = exists
+                        .get(source)
+                        .copied()
+                        .unwrap_or_else(|| self.repo.contains(source));
+                    if !source_exists {
+                        return Err(crate::Error::NotFound);
+                    }
+                    exists.insert(dest, true);
+                }
+            }
+        }
+
+        for op in mem::take(&mut self.ops) {
+            match op {
+                BatchOp::Insert(key, handle) => self.repo.insert_handle(key, handle),
+                BatchOp::Remove(key) => {
+                    self.repo.remove(&key);
+                }
+                BatchOp::Copy { source, dest } => {
+                    self.repo.copy(&source, dest);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, K: Key> Drop for Batch<'a, K> {
+    fn drop(&mut self) {
+        // Discard the data for objects which were never inserted.
+        for op in mem::take(&mut self.ops) {
+            if let BatchOp::Insert(_, handle) = op {
+                self.repo.remove_handle(&handle);
+            }
+        }
+    }
+}
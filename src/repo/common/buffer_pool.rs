@@ -0,0 +1,284 @@
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Mutex;
+
+/// The number of size classes larger than the one requested which are searched for a free buffer.
+///
+/// This limits how much larger than requested a rented buffer can be.
+const MAX_CLASS_DISTANCE: usize = 1;
+
+/// Statistics about a [`BufferPool`].
+///
+/// [`BufferPool`]: crate::repo::BufferPool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    rented: u64,
+    reused: u64,
+    retained: u64,
+    high_water_mark: u64,
+}
+
+impl PoolStats {
+    /// The number of buffers which were rented from the pool.
+    pub fn rented(&self) -> u64 {
+        self.rented
+    }
+
+    /// The number of rented buffers which were reused from the pool rather than newly allocated.
+    pub fn reused(&self) -> u64 {
+        self.reused
+    }
+
+    /// The total capacity in bytes of the buffers currently held by the pool.
+    pub fn retained(&self) -> u64 {
+        self.retained
+    }
+
+    /// The largest total capacity in bytes of the buffers held by the pool at any one time.
+    pub fn high_water_mark(&self) -> u64 {
+        self.high_water_mark
+    }
+}
+
+/// The mutable state of a `BufferPool`.
+#[derive(Debug, Default)]
+struct PoolState {
+    /// The free buffers in the pool, indexed by size class.
+    ///
+    /// A buffer in size class `n` has a capacity of at least `2^n` bytes and less than `2^(n + 1)`
+    /// bytes. Every buffer is filled with zeroes up to its capacity.
+    classes: Vec<Vec<Vec<u8>>>,
+
+    /// The total capacity of the buffers in `classes`.
+    retained: u64,
+
+    high_water_mark: u64,
+    rented: u64,
+    reused: u64,
+}
+
+/// A pool of reusable buffers for encoding and decoding chunks which can be shared between
+/// repositories.
+///
+/// Reading and writing data allocates and frees a buffer for each chunk at each stage of encoding
+/// and decoding. In long-running processes, this can fragment the heap. A `BufferPool` keeps
+/// buffers which are no longer needed and hands them out again, grouped by size class, up to a
+/// budget of bytes.
+///
+/// Every repository uses a pool. By default, a repository has its own pool which retains up to
+/// [`RepoConfig::buffer_pool_size`] bytes. A pool can also be passed to
+/// [`OpenOptions::buffer_pool`] when opening a repository, and the same pool can be shared between
+/// any number of repositories, including from multiple threads. A pool with a capacity of zero
+/// never retains any buffers.
+///
+/// Buffers are zeroed when they are returned to the pool, so data from one object is never
+/// visible in a buffer which is reused for another object, even between repositories with
+/// different encryption keys.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+///
+/// use acid_store::repo::{BufferPool, OpenOptions, OpenMode, key::KeyRepo};
+/// use acid_store::store::MemoryConfig;
+///
+/// // Share up to 16 MiB of buffers between repositories.
+/// let pool = Arc::new(BufferPool::new(16 * 1024 * 1024));
+///
+/// let repo: KeyRepo<String> = OpenOptions::new()
+///     .mode(OpenMode::CreateNew)
+///     .buffer_pool(Arc::clone(&pool))
+///     .open(&MemoryConfig::new())
+///     .unwrap();
+/// ```
+///
+/// [`RepoConfig::buffer_pool_size`]: crate::repo::RepoConfig::buffer_pool_size
+/// [`OpenOptions::buffer_pool`]: crate::repo::OpenOptions::buffer_pool
+pub struct BufferPool {
+    capacity: u64,
+    state: Mutex<PoolState>,
+}
+
+impl BufferPool {
+    /// Create a new empty pool which retains at most `capacity` bytes of buffers.
+    ///
+    /// If `capacity` is zero, pooling is disabled.
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(PoolState::default()),
+        }
+    }
+
+    /// The maximum number of bytes of buffers this pool retains.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Return statistics about this pool.
+    pub fn stats(&self) -> PoolStats {
+        let state = self.state.lock().unwrap();
+        PoolStats {
+            rented: state.rented,
+            reused: state.reused,
+            retained: state.retained,
+            high_water_mark: state.high_water_mark,
+        }
+    }
+
+    /// Free all the buffers held by this pool.
+    ///
+    /// This does not reset the other statistics.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        for class in &mut state.classes {
+            class.clear();
+        }
+        state.retained = 0;
+    }
+
+    /// Return an empty buffer with a capacity of at least `min_capacity` bytes.
+    ///
+    /// The buffer should be returned with `release` once it's no longer needed.
+    pub(crate) fn rent(&self, min_capacity: usize) -> Vec<u8> {
+        let mut state = self.state.lock().unwrap();
+        state.rented += 1;
+
+        if self.capacity == 0 {
+            return Vec::with_capacity(min_capacity);
+        }
+
+        // Round up so that every buffer in the class we take from is large enough.
+        let class = size_class(min_capacity.max(1).next_power_of_two());
+        for class in class..=(class + MAX_CLASS_DISTANCE) {
+            if let Some(mut buffer) = state.classes.get_mut(class).and_then(Vec::pop) {
+                state.reused += 1;
+                state.retained -= buffer.capacity() as u64;
+                buffer.clear();
+                return buffer;
+            }
+        }
+
+        // Allocate buffers in powers of two so that they can be reused for more sizes.
+        Vec::with_capacity(min_capacity.max(1).next_power_of_two())
+    }
+
+    /// Return the given `buffer` to the pool so that it can be rented again.
+    ///
+    /// If the pool is full, the buffer is freed instead.
+    pub(crate) fn release(&self, mut buffer: Vec<u8>) {
+        let capacity = buffer.capacity();
+        if capacity == 0 || capacity as u64 > self.capacity {
+            return;
+        }
+
+        // Zero the whole buffer, not just its contents, before anyone else can rent it. We do this
+        // before acquiring the lock so we don't block other threads.
+        buffer.clear();
+        buffer.resize(capacity, 0);
+
+        let mut state = self.state.lock().unwrap();
+        if state.retained + capacity as u64 > self.capacity {
+            return;
+        }
+        state.retained += capacity as u64;
+        state.high_water_mark = state.high_water_mark.max(state.retained);
+
+        // The size classes are allocated lazily so that a pool which is never used doesn't
+        // allocate.
+        let class = size_class(capacity);
+        if state.classes.len() <= class {
+            state.classes.resize_with(class + 1, Vec::new);
+        }
+        state.classes[class].push(buffer);
+    }
+}
+
+impl Debug for BufferPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("capacity", &self.capacity)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Return the size class of a buffer with the given nonzero `capacity`.
+fn size_class(capacity: usize) -> usize {
+    (usize::BITS - 1 - capacity.leading_zeros()) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_are_reused() {
+        let pool = BufferPool::new(1024 * 1024);
+        let buffer = pool.rent(1000);
+        let capacity = buffer.capacity();
+        pool.release(buffer);
+
+        assert_eq!(pool.stats().retained(), capacity as u64);
+
+        let buffer = pool.rent(600);
+        assert_eq!(buffer.capacity(), capacity);
+        assert_eq!(pool.stats().rented(), 2);
+        assert_eq!(pool.stats().reused(), 1);
+        assert_eq!(pool.stats().retained(), 0);
+        assert_eq!(pool.stats().high_water_mark(), capacity as u64);
+    }
+
+    #[test]
+    fn buffers_are_zeroed_before_reuse() {
+        let pool = BufferPool::new(1024 * 1024);
+        let mut buffer = pool.rent(4096);
+        buffer.extend_from_slice(&[0xff; 4096]);
+        buffer.truncate(10);
+        pool.release(buffer);
+
+        // Check the buffer while it's in the pool, because the bytes past the length of a rented
+        // buffer can't be read.
+        {
+            let state = pool.state.lock().unwrap();
+            let retained = state.classes.iter().flatten().collect::<Vec<_>>();
+            assert_eq!(retained.len(), 1);
+            assert_eq!(retained[0].len(), retained[0].capacity());
+            assert!(retained[0].iter().all(|&byte| byte == 0));
+        }
+
+        assert!(pool.rent(4096).is_empty());
+    }
+
+    #[test]
+    fn small_buffers_are_not_used_for_large_requests() {
+        let pool = BufferPool::new(1024 * 1024);
+        pool.release(Vec::with_capacity(100));
+
+        let buffer = pool.rent(1000);
+        assert!(buffer.capacity() >= 1000);
+        assert_eq!(pool.stats().reused(), 0);
+    }
+
+    #[test]
+    fn pool_does_not_exceed_capacity() {
+        let pool = BufferPool::new(4096);
+        let buffers = (0..4).map(|_| pool.rent(2048)).collect::<Vec<_>>();
+        for buffer in buffers {
+            pool.release(buffer);
+        }
+
+        assert_eq!(pool.stats().retained(), 4096);
+        assert_eq!(pool.stats().high_water_mark(), 4096);
+    }
+
+    #[test]
+    fn disabled_pool_retains_nothing() {
+        let pool = BufferPool::new(0);
+        let buffer = pool.rent(1000);
+        pool.release(buffer);
+
+        assert_eq!(pool.stats().rented(), 1);
+        assert_eq!(pool.stats().retained(), 0);
+        assert!(pool.rent(1000).capacity() >= 1000);
+    }
+}
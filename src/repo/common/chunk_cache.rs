@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Mutex;
+
+use super::handle::{chunk_hash, Chunk};
+
+/// Statistics about a [`ChunkCache`].
+///
+/// [`ChunkCache`]: crate::repo::ChunkCache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    hits: u64,
+    misses: u64,
+    size: u64,
+    entries: usize,
+}
+
+impl CacheStats {
+    /// The number of chunks which were read from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of chunks which were not in the cache and had to be read from a data store.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// The total size in bytes of the chunks currently in the cache.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The number of chunks currently in the cache.
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+}
+
+/// A chunk stored in the cache.
+#[derive(Debug)]
+struct CacheEntry {
+    /// The decoded contents of the chunk.
+    data: Vec<u8>,
+
+    /// The last time this entry was used, which is its key in `CacheState::recency`.
+    last_used: u64,
+}
+
+/// The mutable state of a `ChunkCache`.
+#[derive(Debug, Default)]
+struct CacheState {
+    /// The chunks in the cache.
+    entries: HashMap<Chunk, CacheEntry>,
+
+    /// The chunks in the cache ordered from least recently used to most recently used.
+    recency: BTreeMap<u64, Chunk>,
+
+    /// A counter used to order entries by when they were last used.
+    clock: u64,
+
+    /// The total size of the chunks in the cache.
+    size: u64,
+
+    hits: u64,
+    misses: u64,
+}
+
+impl CacheState {
+    /// Mark the given `chunk` as the most recently used and return its new timestamp.
+    fn touch(&mut self, chunk: Chunk, last_used: Option<u64>) -> u64 {
+        if let Some(last_used) = last_used {
+            self.recency.remove(&last_used);
+        }
+        self.clock += 1;
+        self.recency.insert(self.clock, chunk);
+        self.clock
+    }
+
+    /// Evict the least recently used entries until `additional` bytes fit within `capacity`.
+    fn evict(&mut self, capacity: u64, additional: u64) {
+        while self.size + additional > capacity {
+            let chunk = match self.recency.pop_first() {
+                Some((_, chunk)) => chunk,
+                None => break,
+            };
+            if let Some(entry) = self.entries.remove(&chunk) {
+                self.size -= entry.data.len() as u64;
+            }
+        }
+    }
+}
+
+/// A cache of decoded chunks which can be shared between repositories.
+///
+/// By default, each object only keeps the chunk it most recently read in memory. A `ChunkCache`
+/// keeps recently read chunks in memory up to a budget of bytes, evicting the least recently used
+/// chunks first. A cache can be passed to [`OpenOptions::chunk_cache`] when opening a repository,
+/// and the same cache can be shared between any number of repositories, including from multiple
+/// threads.
+///
+/// Chunks are identified by the hash of their contents, so repositories which contain the same data
+/// share entries in the cache, even if they use different encryption keys. A chunk is only added to
+/// the cache after its contents have been checked against its hash, so the data in the cache can
+/// always be trusted.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+///
+/// use acid_store::repo::{ChunkCache, OpenOptions, OpenMode, key::KeyRepo};
+/// use acid_store::store::MemoryConfig;
+///
+/// // Share 64 MiB of cached chunks between repositories.
+/// let cache = Arc::new(ChunkCache::new(64 * 1024 * 1024));
+///
+/// let repo: KeyRepo<String> = OpenOptions::new()
+///     .mode(OpenMode::CreateNew)
+///     .chunk_cache(Arc::clone(&cache))
+///     .open(&MemoryConfig::new())
+///     .unwrap();
+/// ```
+///
+/// [`OpenOptions::chunk_cache`]: crate::repo::OpenOptions::chunk_cache
+pub struct ChunkCache {
+    capacity: u64,
+    state: Mutex<CacheState>,
+}
+
+impl ChunkCache {
+    /// Create a new empty cache which holds at most `capacity` bytes of chunks.
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// The maximum number of bytes of chunks this cache holds.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Return statistics about this cache.
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap();
+        CacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            size: state.size,
+            entries: state.entries.len(),
+        }
+    }
+
+    /// Remove all chunks from this cache.
+    ///
+    /// This does not reset the hit and miss counts.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.recency.clear();
+        state.size = 0;
+    }
+
+    /// Return the contents of the given `chunk` if it is in the cache.
+    pub(crate) fn get(&self, chunk: Chunk) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        let last_used = match state.entries.get(&chunk) {
+            Some(entry) => entry.last_used,
+            None => {
+                state.misses += 1;
+                return None;
+            }
+        };
+        state.hits += 1;
+        let last_used = state.touch(chunk, Some(last_used));
+        let entry = state.entries.get_mut(&chunk).unwrap();
+        entry.last_used = last_used;
+        Some(entry.data.clone())
+    }
+
+    /// Add the contents of the given `chunk` to the cache.
+    ///
+    /// If `data` does not match the size and hash of `chunk`, it is not added to the cache.
+    pub(crate) fn insert(&self, chunk: Chunk, data: &[u8]) {
+        let size = data.len() as u64;
+        if size > self.capacity || data.len() != chunk.size as usize {
+            return;
+        }
+
+        // Hash the data before acquiring the lock so we don't block other readers.
+        if chunk_hash(data) != chunk.hash {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.entries.contains_key(&chunk) {
+            return;
+        }
+        state.evict(self.capacity, size);
+        let last_used = state.touch(chunk, None);
+        state.size += size;
+        state.entries.insert(
+            chunk,
+            CacheEntry {
+                data: data.to_vec(),
+                last_used,
+            },
+        );
+    }
+}
+
+impl Debug for ChunkCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkCache")
+            .field("capacity", &self.capacity)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
@@ -1,14 +1,30 @@
 use std::cmp::min;
 use std::collections::HashSet;
+use std::io::{self, Read};
 
 use uuid::Uuid;
 
+use super::compression::Compression;
+use super::encryption::Encryption;
+use super::format::{
+    decode_chunk, decode_chunk_reader, decode_pack, decode_packed_chunk, encode_chunk, encode_pack,
+    encode_packed_chunk,
+};
 use super::handle::HandleId;
 use super::handle::{chunk_hash, Chunk};
 use super::packing::Packing;
 use super::state::{ChunkInfo, Pack, PackIndex, RepoState};
 use crate::store::{BlockId, BlockKey};
 
+/// Chunks larger than this many bytes are decoded as a stream when packing is disabled.
+///
+/// This avoids holding both the encoded and decoded contents of very large chunks in memory at
+/// once.
+pub const STREAMING_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+
+/// The size of the buffer used when hashing a chunk as a stream.
+const STREAMING_BUFFER_SIZE: usize = 64 * 1024;
+
 /// Encode and decode blocks of data.
 pub trait EncodeBlock {
     /// Compress and encrypt the given `data` and return it.
@@ -18,28 +34,28 @@ pub trait EncodeBlock {
     fn decode_data(&self, data: &[u8]) -> crate::Result<Vec<u8>>;
 }
 
+// This is used for headers, which store the dictionaries, so it never uses a dictionary.
 impl EncodeBlock for RepoState {
     fn encode_data(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
-        let compressed_data = self.metadata.config.compression.compress(data)?;
-
-        Ok(self
-            .metadata
-            .config
-            .encryption
-            .encrypt(compressed_data.as_slice(), &self.master_key))
+        encode_chunk(
+            data,
+            &self.metadata.config.compression,
+            &[],
+            &self.metadata.config.encryption,
+            &self.master_key,
+            &self.buffer_pool,
+        )
     }
 
     fn decode_data(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
-        let decrypted_data = self
-            .metadata
-            .config
-            .encryption
-            .decrypt(data, &self.master_key)?;
-
-        self.metadata
-            .config
-            .compression
-            .decompress(decrypted_data.as_slice())
+        decode_chunk(
+            data,
+            &self.metadata.config.compression,
+            &[],
+            &self.metadata.config.encryption,
+            &self.master_key,
+            &self.buffer_pool,
+        )
     }
 }
 
@@ -61,9 +77,18 @@ pub trait WriteBlock: ReadBlock {
     fn write_block(&mut self, id: BlockId, data: &[u8]) -> crate::Result<()>;
 }
 
+/// Return the compression method which was used to encode the chunk with the given `chunk_info`.
+fn chunk_compression<'a>(repo_state: &'a RepoState, chunk_info: &'a ChunkInfo) -> &'a Compression {
+    chunk_info
+        .compression
+        .as_ref()
+        .unwrap_or(&repo_state.metadata.config.compression)
+}
+
 struct PackingBlockReader<'a> {
     repo_state: &'a RepoState,
     store_state: &'a mut StoreState,
+    compression: &'a Compression,
 }
 
 impl<'a> ReadBlock for PackingBlockReader<'a> {
@@ -73,8 +98,9 @@ impl<'a> ReadBlock for PackingBlockReader<'a> {
             None => return Err(crate::Error::InvalidData),
         };
 
-        let block_size: u32 = index_list.iter().map(|index| index.size).sum();
-        let mut block_buffer = Vec::with_capacity(block_size as usize);
+        // We don't pre-allocate the buffer using the sizes in the pack index because they may be
+        // corrupt.
+        let mut block_buffer = Vec::new();
 
         // A block can be spread across multiple packs. Get the data from each pack and concatenate
         // them.
@@ -94,12 +120,12 @@ impl<'a> ReadBlock for PackingBlockReader<'a> {
                         .read_block(BlockKey::Data(pack_index.id))
                         .map_err(crate::Error::Store)?
                         .ok_or(crate::Error::InvalidData)?;
-                    let pack_buffer = self
-                        .repo_state
-                        .metadata
-                        .config
-                        .encryption
-                        .decrypt(encoded_pack_buffer.as_slice(), &self.repo_state.master_key)?;
+                    let pack_buffer = decode_pack(
+                        encoded_pack_buffer.as_slice(),
+                        &self.repo_state.metadata.config.encryption,
+                        &self.repo_state.master_key,
+                    )?;
+                    self.repo_state.buffer_pool.release(encoded_pack_buffer);
                     let pack = Pack {
                         id: pack_index.id,
                         buffer: pack_buffer,
@@ -109,23 +135,31 @@ impl<'a> ReadBlock for PackingBlockReader<'a> {
                 }
             };
 
-            // Get the slice of the pack containing the block data.
+            // Get the slice of the pack containing the block data. If the pack index is corrupt, this
+            // range may not be within the pack.
             let start = pack_index.offset as usize;
-            let end = (pack_index.offset + pack_index.size) as usize;
-            block_buffer.extend_from_slice(&pack_buffer[start..end]);
+            let block_data = start
+                .checked_add(pack_index.size as usize)
+                .and_then(|end| pack_buffer.get(start..end))
+                .ok_or(crate::Error::InvalidData)?;
+            block_buffer.extend_from_slice(block_data);
         }
 
-        self.repo_state
-            .metadata
-            .config
-            .compression
-            .decompress(block_buffer.as_slice())
+        let data = decode_packed_chunk(
+            block_buffer.as_slice(),
+            self.compression,
+            &self.repo_state.dictionaries,
+            &self.repo_state.buffer_pool,
+        );
+        self.repo_state.buffer_pool.release(block_buffer);
+        data
     }
 }
 
 struct PackingBlockWriter<'a> {
     repo_state: &'a mut RepoState,
     store_state: &'a mut StoreState,
+    compression: &'a Compression,
     pack_size: u32,
 }
 
@@ -134,6 +168,7 @@ impl<'a> ReadBlock for PackingBlockWriter<'a> {
         let mut reader = PackingBlockReader {
             repo_state: self.repo_state,
             store_state: self.store_state,
+            compression: self.compression,
         };
         reader.read_block(id)
     }
@@ -141,6 +176,18 @@ impl<'a> ReadBlock for PackingBlockWriter<'a> {
 
 impl<'a> WriteBlock for PackingBlockWriter<'a> {
     fn write_block(&mut self, id: BlockId, data: &[u8]) -> crate::Result<()> {
+        // The pack we're about to write to may have already been read into the read buffer. Once
+        // we write more data to it, the copy in the read buffer will be stale, so we need to
+        // discard it.
+        if let (Some(read_pack), Some(write_pack)) = (
+            &self.store_state.read_buffer,
+            &self.store_state.write_buffer,
+        ) {
+            if read_pack.id == write_pack.id {
+                self.store_state.read_buffer = None;
+            }
+        }
+
         let pack_size = self.pack_size;
         let current_pack = self
             .store_state
@@ -152,7 +199,12 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
         // a fixed size, as different data may compress with a different compression ratio. The size
         // of the compressed pack would leak metadata about the contents of the pack, as unlike
         // with encryption, the size of the compressed pack would be based on its contents.
-        let compressed_data = self.repo_state.metadata.config.compression.compress(data)?;
+        let compressed_data = encode_packed_chunk(
+            data,
+            self.compression,
+            &self.repo_state.dictionaries,
+            &self.repo_state.buffer_pool,
+        )?;
 
         // The block's offset from the start of the current pack.
         let mut current_offset = current_pack.buffer.len() as u32;
@@ -175,6 +227,10 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
         // The list packs which store the current block and where it's located in those packs.
         let mut new_packs_indices = Vec::new();
 
+        // The encrypted packs which need to be written to the data store. These are written
+        // together in a single batch once all the `compressed_data` has been packed.
+        let mut encrypted_packs = Vec::new();
+
         loop {
             // Fill the current pack with the provided `compressed_data`.
             remaining_space = self.pack_size as usize - current_pack.buffer.len();
@@ -213,18 +269,13 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
                 // the blocks in the data store may not be exactly equal to the pack size. However,
                 // this isn't a problem because the size of the encrypted messages don't vary based
                 // on the contents of the message.
-                let encrypted_pack = self
-                    .repo_state
-                    .metadata
-                    .config
-                    .encryption
-                    .encrypt(current_pack.buffer.as_slice(), &self.repo_state.master_key);
-                self.repo_state
-                    .store
-                    .lock()
-                    .unwrap()
-                    .write_block(BlockKey::Data(current_pack.id), encrypted_pack.as_slice())
-                    .map_err(crate::Error::Store)?;
+                let encrypted_pack = encode_pack(
+                    current_pack.buffer.as_slice(),
+                    self.pack_size,
+                    &self.repo_state.metadata.config.encryption,
+                    &self.repo_state.master_key,
+                );
+                encrypted_packs.push((BlockKey::Data(current_pack.id), encrypted_pack));
 
                 // We're starting a new pack, so these need to be reset.
                 current_offset = 0;
@@ -242,19 +293,29 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
                 // clone of this pack buffered in memory though, so we can write more data to the
                 // pack and overwrite it in the data store in the future. This way, we don't have a
                 // bunch of half-empty packs in the data store.
-                let padded_pack = current_pack.padded(self.pack_size);
-                let encrypted_pack = self
+                let encrypted_pack = encode_pack(
+                    current_pack.buffer.as_slice(),
+                    self.pack_size,
+                    &self.repo_state.metadata.config.encryption,
+                    &self.repo_state.master_key,
+                );
+                encrypted_packs.push((BlockKey::Data(current_pack.id), encrypted_pack));
+
+                let blocks = encrypted_packs
+                    .iter()
+                    .map(|(key, pack)| (*key, pack.as_slice()))
+                    .collect::<Vec<_>>();
+                let result = self
                     .repo_state
-                    .metadata
-                    .config
-                    .encryption
-                    .encrypt(padded_pack.as_slice(), &self.repo_state.master_key);
-                self.repo_state
                     .store
                     .lock()
                     .unwrap()
-                    .write_block(BlockKey::Data(current_pack.id), encrypted_pack.as_slice())
-                    .map_err(crate::Error::Store)?;
+                    .write_blocks(&blocks)
+                    .map_err(crate::Error::Store);
+                for (_, encrypted_pack) in encrypted_packs {
+                    self.repo_state.buffer_pool.release(encrypted_pack);
+                }
+                result?;
 
                 // We need to update the pack map in the repository state after all data has been
                 // written to the data store. If this method fails early, we can't have the pack map
@@ -267,6 +328,7 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
                 // from the data store at this point in case the repository is rolled back, but we
                 // do need to replace the pack indices in the pack map, which we do here.
                 self.repo_state.packs.insert(id, new_packs_indices);
+                self.repo_state.buffer_pool.release(compressed_data);
 
                 return Ok(());
             }
@@ -276,6 +338,7 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
 
 struct DirectBlockWriter<'a> {
     state: &'a RepoState,
+    compression: &'a Compression,
 }
 
 impl<'a> ReadBlock for DirectBlockWriter<'a> {
@@ -288,19 +351,38 @@ impl<'a> ReadBlock for DirectBlockWriter<'a> {
             .read_block(BlockKey::Data(id))
             .map_err(crate::Error::Store)?
             .ok_or(crate::Error::InvalidData)?;
-        self.state.decode_data(encoded_block.as_slice())
+        let data = decode_chunk(
+            encoded_block.as_slice(),
+            self.compression,
+            &self.state.dictionaries,
+            &self.state.metadata.config.encryption,
+            &self.state.master_key,
+            &self.state.buffer_pool,
+        );
+        self.state.buffer_pool.release(encoded_block);
+        data
     }
 }
 
 impl<'a> WriteBlock for DirectBlockWriter<'a> {
     fn write_block(&mut self, id: BlockId, data: &[u8]) -> crate::Result<()> {
-        let encoded_block = self.state.encode_data(data)?;
-        self.state
+        let encoded_block = encode_chunk(
+            data,
+            self.compression,
+            &self.state.dictionaries,
+            &self.state.metadata.config.encryption,
+            &self.state.master_key,
+            &self.state.buffer_pool,
+        )?;
+        let result = self
+            .state
             .store
             .lock()
             .unwrap()
             .write_block(BlockKey::Data(id), encoded_block.as_slice())
-            .map_err(crate::Error::Store)
+            .map_err(crate::Error::Store);
+        self.state.buffer_pool.release(encoded_block);
+        result
     }
 }
 
@@ -312,6 +394,9 @@ pub struct StoreState {
 
     /// The pack which is currently being written to.
     write_buffer: Option<Pack>,
+
+    /// The compression method to use for new chunks instead of the repository's.
+    compression: Option<Compression>,
 }
 
 impl StoreState {
@@ -320,6 +405,17 @@ impl StoreState {
         StoreState {
             read_buffer: None,
             write_buffer: None,
+            compression: None,
+        }
+    }
+
+    /// Create a new empty `StoreState` which writes new chunks with the given `compression`.
+    ///
+    /// If this is `None`, the repository's compression method is used.
+    pub fn with_compression(compression: Option<Compression>) -> Self {
+        StoreState {
+            compression,
+            ..Self::new()
         }
     }
 }
@@ -359,27 +455,303 @@ impl<'a> StoreReader<'a> {
 
 impl<'a> ReadBlock for StoreReader<'a> {
     fn read_block(&mut self, id: BlockId) -> crate::Result<Vec<u8>> {
+        let repo_state = self.repo_state;
+        self.read_block_with(id, &repo_state.metadata.config.compression)
+    }
+}
+
+impl<'a> StoreReader<'a> {
+    /// Return the bytes of the block with the given `id`, which was compressed with `compression`.
+    ///
+    /// This is like `read_block`, but for blocks which may not use the repository's compression.
+    pub fn read_block_with(
+        &mut self,
+        id: BlockId,
+        compression: &Compression,
+    ) -> crate::Result<Vec<u8>> {
         let mut read_block: Box<dyn ReadBlock> = match &self.repo_state.metadata.config.packing {
             Packing::None => Box::new(DirectBlockWriter {
                 state: self.repo_state,
+                compression,
             }),
             Packing::Fixed(_) => Box::new(PackingBlockReader {
                 repo_state: self.repo_state,
                 store_state: self.store_state,
+                compression,
             }),
         };
         read_block.read_block(id)
     }
-}
 
-impl<'a> ReadChunk for StoreReader<'a> {
-    fn read_chunk(&mut self, chunk: Chunk) -> crate::Result<Vec<u8>> {
+    /// Read the given `chunk` from the data store without going through the chunk cache.
+    ///
+    /// This is used when verifying data, where we need to check what is actually stored.
+    pub fn read_chunk_uncached(&mut self, chunk: Chunk) -> crate::Result<Vec<u8>> {
+        let repo_state = self.repo_state;
+        let chunk_info = repo_state
+            .chunks
+            .get(&chunk)
+            .ok_or(crate::Error::InvalidData)?;
+        let data = if self.is_streamed(chunk) {
+            self.with_chunk_reader(chunk, |reader| {
+                let mut data = Vec::with_capacity(chunk.size as usize);
+                // Read one byte past the expected size so we can tell if the chunk is too large.
+                reader.take(chunk.size as u64 + 1).read_to_end(&mut data)?;
+                Ok(data)
+            })?
+        } else {
+            self.read_block_with(
+                chunk_info.block_id,
+                chunk_compression(repo_state, chunk_info),
+            )?
+        };
+        self.check_chunk(chunk, chunk_info.block_id, &data)?;
+        Ok(data)
+    }
+
+    /// Return the bytes of each of the given `chunks` in the same order.
+    ///
+    /// When packing is disabled, the blocks containing the chunks are read from the data store in
+    /// a single batch. Chunks which are cached or decoded as a stream are read individually.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn read_chunks(&mut self, chunks: &[Chunk]) -> crate::Result<Vec<Vec<u8>>> {
+        let repo_state = self.repo_state;
+        let mut contents = vec![None; chunks.len()];
+
+        // The indices of the chunks in `chunks` which are read as part of the batch.
+        let mut batch = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let cached = repo_state
+                .chunk_cache
+                .as_ref()
+                .filter(|_| repo_state.chunks.contains_key(chunk))
+                .and_then(|cache| cache.get(*chunk));
+            if let Some(data) = cached {
+                contents[index] = Some(data);
+            } else if repo_state.metadata.config.packing == Packing::None
+                && !self.is_streamed(*chunk)
+            {
+                batch.push(index);
+            } else {
+                contents[index] = Some(self.read_chunk(*chunk)?);
+            }
+        }
+
+        let keys = batch
+            .iter()
+            .map(|&index| {
+                repo_state
+                    .chunks
+                    .get(&chunks[index])
+                    .map(|chunk_info| BlockKey::Data(chunk_info.block_id))
+                    .ok_or(crate::Error::InvalidData)
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        let blocks = repo_state
+            .store
+            .lock()
+            .unwrap()
+            .read_blocks(&keys)
+            .map_err(crate::Error::Store)?;
+
+        for (index, block) in batch.into_iter().zip(blocks) {
+            let chunk = chunks[index];
+            let chunk_info = repo_state
+                .chunks
+                .get(&chunk)
+                .ok_or(crate::Error::InvalidData)?;
+            let encoded_block = block.ok_or(crate::Error::InvalidData)?;
+            let data = decode_chunk(
+                encoded_block.as_slice(),
+                chunk_compression(repo_state, chunk_info),
+                &repo_state.dictionaries,
+                &repo_state.metadata.config.encryption,
+                &repo_state.master_key,
+                &repo_state.buffer_pool,
+            );
+            repo_state.buffer_pool.release(encoded_block);
+            let data = data?;
+            self.check_chunk(chunk, chunk_info.block_id, &data)?;
+            if let Some(cache) = &repo_state.chunk_cache {
+                cache.insert(chunk, &data);
+            }
+            contents[index] = Some(data);
+        }
+
+        Ok(contents.into_iter().map(Option::unwrap).collect())
+    }
+
+    /// Return an error if the given `data` read for `chunk` from `block_id` is not what we expected.
+    fn check_chunk(&self, chunk: Chunk, block_id: BlockId, data: &[u8]) -> crate::Result<()> {
+        // Readers slice chunks based on their expected size, so we always check it, even when we
+        // aren't verifying reads.
+        if data.len() != chunk.size as usize {
+            return Err(crate::Error::InvalidData);
+        }
+
+        // Objects cache the most recently read chunk, so verifying chunks here means that cached
+        // data has always been verified.
+        if self.repo_state.metadata.config.verify_reads && chunk_hash(data) != chunk.hash {
+            return Err(crate::Error::ChunkMismatch {
+                hash: chunk.hash,
+                block_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Return whether the given `chunk` is decoded as a stream rather than all at once.
+    fn is_streamed(&self, chunk: Chunk) -> bool {
+        self.repo_state.metadata.config.packing == Packing::None
+            && chunk.size > STREAMING_CHUNK_SIZE
+    }
+
+    /// Call `f` with a reader which decodes the given `chunk` as it is read from the data store.
+    ///
+    /// This is only valid when packing is disabled.
+    fn with_chunk_reader<T>(
+        &self,
+        chunk: Chunk,
+        f: impl FnOnce(&mut dyn Read) -> crate::Result<T>,
+    ) -> crate::Result<T> {
         let chunk_info = self
             .repo_state
             .chunks
             .get(&chunk)
             .ok_or(crate::Error::InvalidData)?;
-        self.read_block(chunk_info.block_id)
+        let mut store = self.repo_state.store.lock().unwrap();
+        let block = store
+            .read_block_streaming(BlockKey::Data(chunk_info.block_id))
+            .map_err(crate::Error::Store)?
+            .ok_or(crate::Error::InvalidData)?;
+        let mut reader = decode_chunk_reader(
+            block,
+            chunk_compression(self.repo_state, chunk_info),
+            &self.repo_state.dictionaries,
+            &self.repo_state.metadata.config.encryption,
+            &self.repo_state.master_key,
+        )?;
+        f(&mut reader)
+    }
+
+    /// Return whether the given `chunk` is stored intact in the data store.
+    ///
+    /// Large chunks are hashed as they are decoded, so they are never held in memory in full.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn verify_chunk(&mut self, chunk: Chunk) -> crate::Result<bool> {
+        let result = if self.is_streamed(chunk) {
+            self.with_chunk_reader(chunk, |reader| {
+                let mut hasher = blake3::Hasher::new();
+                let mut buffer = vec![0u8; STREAMING_BUFFER_SIZE];
+                let mut size = 0u64;
+                loop {
+                    let bytes_read = match reader.read(&mut buffer) {
+                        Ok(0) => break,
+                        Ok(bytes_read) => bytes_read,
+                        Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(error) => return Err(error.into()),
+                    };
+                    hasher.update(&buffer[..bytes_read]);
+                    size += bytes_read as u64;
+                    if size > chunk.size as u64 {
+                        return Ok(false);
+                    }
+                }
+                Ok(size == chunk.size as u64 && hasher.finalize() == chunk.hash)
+            })
+        } else {
+            self.read_chunk_uncached(chunk)
+                .map(|data| data.len() == chunk.size as usize && chunk_hash(&data) == chunk.hash)
+        };
+
+        match result {
+            // Ciphertext or hash verification failed. No need to check the hash again.
+            Err(crate::Error::InvalidData | crate::Error::ChunkMismatch { .. }) => Ok(false),
+            result => result,
+        }
+    }
+
+    /// Return whether bytes can be read from the middle of the given `chunk` without decoding
+    /// the whole chunk.
+    ///
+    /// This is only possible for large chunks which are neither packed, compressed, nor encrypted,
+    /// and only when reads are not being verified, because verifying a chunk requires hashing all
+    /// of it.
+    pub fn supports_partial_reads(&self, chunk: Chunk) -> bool {
+        let config = &self.repo_state.metadata.config;
+        let compression = self
+            .repo_state
+            .chunks
+            .get(&chunk)
+            .map(|chunk_info| chunk_compression(self.repo_state, chunk_info));
+        self.is_streamed(chunk)
+            && compression == Some(&Compression::None)
+            && matches!(config.encryption, Encryption::None)
+            && !config.verify_reads
+    }
+
+    /// Return up to `size` bytes of the given `chunk` starting at `offset`.
+    ///
+    /// This must only be called when `supports_partial_reads` returns `true` for this `chunk`.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: The chunk is smaller than expected.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn read_chunk_range(
+        &mut self,
+        chunk: Chunk,
+        offset: u64,
+        size: usize,
+    ) -> crate::Result<Vec<u8>> {
+        let size = min(size as u64, (chunk.size as u64).saturating_sub(offset));
+        let data = self.with_chunk_reader(chunk, |reader| {
+            let skipped = io::copy(&mut (&mut *reader).take(offset), &mut io::sink())?;
+            if skipped != offset {
+                return Err(crate::Error::InvalidData);
+            }
+            let mut data = Vec::with_capacity(size as usize);
+            reader.take(size).read_to_end(&mut data)?;
+            Ok(data)
+        })?;
+
+        if data.len() as u64 != size {
+            return Err(crate::Error::InvalidData);
+        }
+
+        Ok(data)
+    }
+}
+
+impl<'a> ReadChunk for StoreReader<'a> {
+    fn read_chunk(&mut self, chunk: Chunk) -> crate::Result<Vec<u8>> {
+        let repo_state = self.repo_state;
+        let cache = match &repo_state.chunk_cache {
+            Some(cache) => cache,
+            None => return self.read_chunk_uncached(chunk),
+        };
+
+        // The cache may be shared with other repositories, so only return chunks which are
+        // actually in this repository.
+        if !repo_state.chunks.contains_key(&chunk) {
+            return Err(crate::Error::InvalidData);
+        }
+
+        if let Some(data) = cache.get(chunk) {
+            return Ok(data);
+        }
+
+        let data = self.read_chunk_uncached(chunk)?;
+        cache.insert(chunk, &data);
+        Ok(data)
     }
 }
 
@@ -397,28 +769,43 @@ impl<'a> StoreWriter<'a> {
             store_state,
         }
     }
-}
 
-impl<'a> ReadBlock for StoreWriter<'a> {
-    fn read_block(&mut self, id: BlockId) -> crate::Result<Vec<u8>> {
-        let mut chunk_reader = StoreReader {
+    /// Return the bytes of the block with the given `id`, which was compressed with `compression`.
+    ///
+    /// This is like `read_block`, but for blocks which may not use the repository's compression.
+    pub fn read_block_with(
+        &mut self,
+        id: BlockId,
+        compression: &Compression,
+    ) -> crate::Result<Vec<u8>> {
+        let mut block_reader = StoreReader {
             repo_state: self.repo_state,
             store_state: self.store_state,
         };
-        chunk_reader.read_block(id)
+        block_reader.read_block_with(id, compression)
     }
-}
 
-impl<'a> WriteBlock for StoreWriter<'a> {
-    fn write_block(&mut self, id: BlockId, data: &[u8]) -> crate::Result<()> {
+    /// Write the given `data` as a block with the given `id`, compressing it with `compression`.
+    ///
+    /// This is like `write_block`, but for blocks which may not use the repository's compression.
+    pub fn write_block_with(
+        &mut self,
+        id: BlockId,
+        data: &[u8],
+        compression: &Compression,
+    ) -> crate::Result<()> {
+        self.repo_state.check_writable()?;
+
         let mut block_writer: Box<dyn WriteBlock> =
             match self.repo_state.metadata.config.packing.clone() {
                 Packing::None => Box::new(DirectBlockWriter {
                     state: self.repo_state,
+                    compression,
                 }),
                 Packing::Fixed(pack_size) => Box::new(PackingBlockWriter {
                     repo_state: self.repo_state,
                     store_state: self.store_state,
+                    compression,
                     pack_size,
                 }),
             };
@@ -426,6 +813,23 @@ impl<'a> WriteBlock for StoreWriter<'a> {
     }
 }
 
+impl<'a> ReadBlock for StoreWriter<'a> {
+    fn read_block(&mut self, id: BlockId) -> crate::Result<Vec<u8>> {
+        let mut chunk_reader = StoreReader {
+            repo_state: self.repo_state,
+            store_state: self.store_state,
+        };
+        chunk_reader.read_block(id)
+    }
+}
+
+impl<'a> WriteBlock for StoreWriter<'a> {
+    fn write_block(&mut self, id: BlockId, data: &[u8]) -> crate::Result<()> {
+        let compression = self.repo_state.metadata.config.compression.clone();
+        self.write_block_with(id, data, &compression)
+    }
+}
+
 impl<'a> ReadChunk for StoreWriter<'a> {
     fn read_chunk(&mut self, chunk: Chunk) -> crate::Result<Vec<u8>> {
         let mut chunk_reader = StoreReader {
@@ -455,8 +859,19 @@ impl<'a> WriteChunk for StoreWriter<'a> {
             return Ok(chunk);
         }
 
+        // Only record the compression method for this chunk if it differs from the repository's.
+        let config_compression = &self.repo_state.metadata.config.compression;
+        let compression = self
+            .store_state
+            .compression
+            .clone()
+            .filter(|compression| compression != config_compression);
+
         let block_id = Uuid::new_v4().into();
-        self.write_block(block_id, data)?;
+        let block_compression = compression
+            .clone()
+            .unwrap_or_else(|| config_compression.clone());
+        self.write_block_with(block_id, data, &block_compression)?;
 
         // Add the chunk to the header.
         let chunk_info = ChunkInfo {
@@ -466,6 +881,7 @@ impl<'a> WriteChunk for StoreWriter<'a> {
                 id_set.insert(id);
                 id_set
             },
+            compression,
         };
         self.repo_state.chunks.insert(chunk, chunk_info);
 
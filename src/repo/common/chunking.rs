@@ -1,9 +1,15 @@
+use std::cmp::min;
 use std::fmt::{Debug, Formatter};
 use std::io::{self, Write};
 
 use cdchunking::{ChunkerImpl, ZPAQ};
 use serde::{Deserialize, Serialize};
 
+use super::chunk_store::STREAMING_CHUNK_SIZE;
+
+/// The smallest buffer to use when streaming data into an object.
+const MIN_STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
 /// A method for chunking data in a repository.
 ///
 /// Data is deduplicated, read into memory, and written to the data store in chunks. This value
@@ -34,6 +40,27 @@ pub enum Chunking {
         /// (2^20 = 1048576).
         bits: u32,
     },
+
+    /// Split data using the FastCDC content-defined chunking algorithm.
+    ///
+    /// This chunking method provides content-defined deduplication like `Zpaq`, but it is
+    /// typically much faster. Chunk sizes are normalized toward `avg_size`, so chunks vary less in
+    /// size than with `Zpaq`.
+    ///
+    /// If `min_size` is greater than `avg_size` or `max_size` is less than `avg_size`, they are
+    /// treated as equal to `avg_size`.
+    FastCdc {
+        /// The minimum chunk size in bytes.
+        ///
+        /// Only the last chunk of an object can be smaller than this.
+        min_size: u32,
+
+        /// The target average chunk size in bytes.
+        avg_size: u32,
+
+        /// The maximum chunk size in bytes.
+        max_size: u32,
+    },
 }
 
 impl Chunking {
@@ -43,13 +70,59 @@ impl Chunking {
     /// A reasonable default value of `Chunking::Zpaq`.
     pub const ZPAQ: Self = Self::Zpaq { bits: 18 };
 
+    /// A reasonable default value of `Chunking::FastCdc`.
+    pub const FASTCDC: Self = Self::FastCdc {
+        min_size: 64 * 1024,
+        avg_size: 256 * 1024,
+        max_size: 1024 * 1024,
+    };
+
     /// Return a chunker for this chunking method.
     pub(super) fn to_chunker(&self) -> Box<dyn ChunkerImpl + Send + Sync> {
         match self {
             Chunking::Fixed { size } => Box::new(FixedChunker::new(*size as usize)),
             Chunking::Zpaq { bits } => Box::new(ZPAQ::new(*bits as usize)),
+            Chunking::FastCdc {
+                min_size,
+                avg_size,
+                max_size,
+            } => Box::new(FastCdcChunker::new(
+                *min_size as usize,
+                *avg_size as usize,
+                *max_size as usize,
+            )),
         }
     }
+
+    /// Return a chunker for this chunking method which keeps chunk sizes within the given bounds.
+    pub(super) fn to_bounded_chunker(
+        &self,
+        min_size: Option<u32>,
+        max_size: Option<u32>,
+    ) -> Box<dyn ChunkerImpl + Send + Sync> {
+        let chunker = self.to_chunker();
+        if min_size.is_none() && max_size.is_none() {
+            return chunker;
+        }
+        Box::new(BoundedChunker::new(
+            chunker,
+            min_size.unwrap_or(0) as usize,
+            max_size.map_or(usize::MAX, |size| size as usize),
+        ))
+    }
+
+    /// The size of the buffer to use when streaming data into an object with this chunking method.
+    ///
+    /// This is the average chunk size, so that each buffer produces about one chunk, within limits
+    /// which keep the number of reads and memory usage reasonable.
+    pub(super) fn stream_buffer_size(&self) -> usize {
+        let chunk_size = match self {
+            Chunking::Fixed { size } => *size as usize,
+            Chunking::Zpaq { bits } => 1usize.checked_shl(*bits).unwrap_or(usize::MAX),
+            Chunking::FastCdc { avg_size, .. } => *avg_size as usize,
+        };
+        chunk_size.clamp(MIN_STREAM_BUFFER_SIZE, STREAMING_CHUNK_SIZE as usize)
+    }
 }
 
 /// A `ChunkerImpl` which chunks data into fixed-size chunks.
@@ -84,6 +157,175 @@ impl ChunkerImpl for FixedChunker {
     }
 }
 
+/// A `ChunkerImpl` which keeps the chunks produced by another `ChunkerImpl` within size bounds.
+///
+/// Boundaries which would produce a chunk smaller than the minimum size are ignored, and a boundary
+/// is added whenever a chunk reaches the maximum size.
+pub struct BoundedChunker {
+    inner: Box<dyn ChunkerImpl + Send + Sync>,
+    min_size: usize,
+    max_size: usize,
+    bytes_read: usize,
+}
+
+impl BoundedChunker {
+    /// Return a new instance which bounds the chunks produced by `inner`.
+    ///
+    /// If `max_size` is less than `min_size`, `min_size` is used as the maximum size.
+    pub fn new(
+        inner: Box<dyn ChunkerImpl + Send + Sync>,
+        min_size: usize,
+        max_size: usize,
+    ) -> Self {
+        BoundedChunker {
+            inner,
+            min_size,
+            max_size: max_size.max(min_size).max(1),
+            bytes_read: 0,
+        }
+    }
+}
+
+impl ChunkerImpl for BoundedChunker {
+    fn find_boundary(&mut self, data: &[u8]) -> Option<usize> {
+        let mut offset = 0;
+
+        while offset < data.len() {
+            // Never pass the inner chunker more data than it takes to reach the maximum size.
+            let window_size = min(data.len() - offset, self.max_size - self.bytes_read);
+            let window = &data[offset..(offset + window_size)];
+
+            match self.inner.find_boundary(window) {
+                Some(index) if self.bytes_read + index >= self.min_size && index > 0 => {
+                    return Some(offset + index);
+                }
+                Some(index) => {
+                    // This chunk would be too small, so ignore the boundary and keep looking. The
+                    // inner chunker needs to be reset after it finds a boundary.
+                    let index = index.max(1);
+                    self.bytes_read += index;
+                    offset += index;
+                    self.inner.reset();
+                }
+                None => {
+                    self.bytes_read += window.len();
+                    offset += window.len();
+                }
+            }
+
+            if self.bytes_read >= self.max_size {
+                return Some(offset);
+            }
+        }
+
+        None
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.bytes_read = 0;
+    }
+}
+
+/// The table of random values used by the gear hash in `FastCdcChunker`.
+///
+/// This determines where chunk boundaries fall, so changing it would keep new data from being
+/// deduplicated against existing data. It must never change.
+const GEAR_TABLE: [u64; 256] = gear_table();
+
+/// Generate `GEAR_TABLE` deterministically using the SplitMix64 generator.
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut index = 0;
+    while index < table.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[index] = value ^ (value >> 31);
+        index += 1;
+    }
+    table
+}
+
+/// Return a mask which selects the `bits` most significant bits of a `u64`.
+///
+/// The gear hash shifts older bytes toward the most significant bits, so those bits depend on the
+/// most bytes.
+fn high_bits_mask(bits: u32) -> u64 {
+    match bits {
+        0 => 0,
+        bits => u64::MAX << (u64::BITS - bits.min(u64::BITS)),
+    }
+}
+
+/// A `ChunkerImpl` which chunks data using the FastCDC algorithm.
+pub struct FastCdcChunker {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+
+    /// The mask used before reaching `avg_size`, which makes a boundary less likely.
+    small_mask: u64,
+
+    /// The mask used after reaching `avg_size`, which makes a boundary more likely.
+    large_mask: u64,
+
+    hash: u64,
+    bytes_read: usize,
+}
+
+impl FastCdcChunker {
+    /// Return a new instance which chunks data using the given chunk sizes.
+    pub fn new(min_size: usize, avg_size: usize, max_size: usize) -> Self {
+        let avg_size = avg_size.max(1);
+        let min_size = min_size.clamp(1, avg_size);
+        let max_size = max_size.max(avg_size);
+        let bits = avg_size.ilog2();
+        FastCdcChunker {
+            min_size,
+            avg_size,
+            max_size,
+            small_mask: high_bits_mask(bits + 2),
+            large_mask: high_bits_mask(bits.saturating_sub(2)),
+            hash: 0,
+            bytes_read: 0,
+        }
+    }
+}
+
+impl ChunkerImpl for FastCdcChunker {
+    fn find_boundary(&mut self, data: &[u8]) -> Option<usize> {
+        for (index, byte) in data.iter().enumerate() {
+            self.bytes_read += 1;
+
+            // There can't be a boundary before the minimum chunk size, so we don't need to hash.
+            if self.bytes_read < self.min_size {
+                continue;
+            }
+
+            self.hash = (self.hash << 1).wrapping_add(GEAR_TABLE[*byte as usize]);
+
+            let mask = if self.bytes_read < self.avg_size {
+                self.small_mask
+            } else {
+                self.large_mask
+            };
+
+            if self.hash & mask == 0 || self.bytes_read >= self.max_size {
+                return Some(index + 1);
+            }
+        }
+        None
+    }
+
+    fn reset(&mut self) {
+        self.hash = 0;
+        self.bytes_read = 0;
+    }
+}
+
 /// A chunker which partitions data written to it into chunks.
 pub struct IncrementalChunker {
     chunker: Box<dyn ChunkerImpl + Send + Sync>,
@@ -1,4 +1,120 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
 use static_assertions::assert_obj_safe;
+use uuid::Uuid;
+
+use crate::store::BlockId;
+
+/// An identifier for a commit of a repository.
+///
+/// Each commit which writes to the data store is assigned a new `CommitId`. Commit IDs consist of
+/// a sequence number, which is incremented with each commit, and a random UUID, which
+/// distinguishes commits with the same sequence number in different copies of a repository.
+/// Commit IDs are ordered by their sequence number, so a later commit of a repository always has
+/// a greater commit ID than an earlier one.
+///
+/// Commit IDs can be serialized so that they can be stored alongside state outside the
+/// repository. The commit ID of a repository can be read without the password using
+/// [`peek_commit_id`].
+///
+/// A repository whose [`RepoConfig::compatibility_target`] is version 0.14 doesn't store its
+/// commit ID, so it always has the default commit ID when it's opened.
+///
+/// [`peek_commit_id`]: crate::repo::peek_commit_id
+/// [`RepoConfig::compatibility_target`]: crate::repo::RepoConfig::compatibility_target
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct CommitId {
+    pub(super) sequence: u64,
+    pub(super) uuid: Uuid,
+}
+
+impl CommitId {
+    /// Return the commit ID which follows this one.
+    pub(super) fn next(&self) -> Self {
+        CommitId {
+            sequence: self.sequence + 1,
+            uuid: Uuid::new_v4(),
+        }
+    }
+
+    /// The number of commits which came before this one.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The random UUID of this commit.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+/// Information about a commit which is kept in the commit history of a repository.
+///
+/// This is returned by [`KeyRepo::list_commits`].
+///
+/// [`KeyRepo::list_commits`]: crate::repo::key::KeyRepo::list_commits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CommitInfo {
+    pub(super) id: CommitId,
+    pub(super) time: SystemTime,
+    pub(super) header_id: BlockId,
+}
+
+impl CommitInfo {
+    /// The ID of this commit.
+    pub fn id(&self) -> CommitId {
+        self.id
+    }
+
+    /// The time this commit was made.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+}
+
+/// A summary of what was written to the data store by a commit.
+///
+/// This is returned by [`KeyRepo::commit_with_report`].
+///
+/// [`KeyRepo::commit_with_report`]: crate::repo::key::KeyRepo::commit_with_report
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CommitReport {
+    pub(super) header_written: bool,
+    pub(super) metadata_written: bool,
+    pub(super) commit_id: CommitId,
+}
+
+impl CommitReport {
+    /// The ID of the repository's latest commit.
+    ///
+    /// If the commit wrote nothing to the data store, this is the ID of the previous commit.
+    pub fn commit_id(&self) -> CommitId {
+        self.commit_id
+    }
+
+    /// Whether a new repository header was written to the data store.
+    ///
+    /// This is `false` if nothing has changed since the last commit.
+    pub fn header_written(&self) -> bool {
+        self.header_written
+    }
+
+    /// Whether the repository metadata was written to the data store.
+    ///
+    /// The metadata is written whenever a new header is written, but it may also be written on its
+    /// own if only the metadata has changed, like when the password is changed.
+    pub fn metadata_written(&self) -> bool {
+        self.metadata_written
+    }
+
+    /// Whether the commit wrote nothing to the data store.
+    pub fn is_empty(&self) -> bool {
+        !self.header_written && !self.metadata_written
+    }
+}
 
 /// A repository which supports committing and rolling back changes.
 pub trait Commit {
@@ -17,13 +133,23 @@ pub trait Commit {
     ///
     /// This method commits changes for all instances of the repository.
     ///
+    /// If nothing has changed since the last commit, this method does not write anything to the
+    /// data store.
+    ///
     /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
     /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
     /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::DestructiveNotAuthorized`: The changes include destructive operations which were
+    /// not authorized. See [`DestructivePolicy`].
+    /// - `Error::Incompatible`: The repository uses an option or feature which its compatibility
+    /// target doesn't support. See [`RepoConfig::compatibility_target`].
     /// - `Error::Store`: An error occurred with the data store.
     /// - `Error::Io`: An I/O error occurred.
     ///
     /// [`clean`]: crate::repo::Commit::clean
+    /// [`RepoConfig::compatibility_target`]: crate::repo::RepoConfig::compatibility_target
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
     fn commit(&mut self) -> crate::Result<()>;
 
     /// Roll back all changes made since the last commit.
@@ -35,7 +161,8 @@ pub trait Commit {
     /// If this method returns `Ok`, changes have been rolled back. If this method returns `Err`,
     /// the repository is unchanged.
     ///
-    /// This method rolls back changes for all instances of the repository.
+    /// This method rolls back changes for all instances of the repository. This includes changes
+    /// to the repository metadata, like a new password or [`DestructivePolicy`].
     ///
     /// Rolling back changes invalidates all [`Object`] and [`ReadOnlyObject`] instances associated
     /// with the repository.
@@ -46,6 +173,7 @@ pub trait Commit {
     /// - `Error::Store`: An error occurred with the data store.
     /// - `Error::Io`: An I/O error occurred.
     ///
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
     /// [`Object`]: crate::repo::Object
     /// [`ReadOnlyObject`]: crate::repo::ReadOnlyObject
     fn rollback(&mut self) -> crate::Result<()>;
@@ -55,11 +183,29 @@ pub trait Commit {
     /// When data in a repository is deleted, the space is not reclaimed in the backing data store
     /// until those changes are committed and this method is called.
     ///
+    /// If the data store has [`Consistency::Eventual`], this only removes unreferenced data which
+    /// is older than [`RepoConfig::gc_grace_period`], so some space may not be reclaimed until this
+    /// method is called again after the grace period has passed.
+    ///
+    /// If the data store has a [`retention`] period, this only removes unreferenced data which is
+    /// older than the retention period and leaves the rest to be removed by a later call to this
+    /// method. The size of the data which is awaiting the expiry of its retention period is
+    /// reported by [`RepoStats::retained_size`].
+    ///
+    /// This method does not commit any changes, and it does not remove any data which is needed
+    /// to roll back uncommitted changes.
+    ///
     /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
     /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
     /// - `Error::InvalidData`: Ciphertext verification failed.
     /// - `Error::Store`: An error occurred with the data store.
     /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`Consistency::Eventual`]: crate::store::Consistency::Eventual
+    /// [`RepoConfig::gc_grace_period`]: crate::repo::RepoConfig::gc_grace_period
+    /// [`retention`]: crate::store::DataStore::retention
+    /// [`RepoStats::retained_size`]: crate::repo::RepoStats::retained_size
     fn clean(&mut self) -> crate::Result<()>;
 }
 
@@ -0,0 +1,216 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::chunking::Chunking;
+use super::config::RepoConfig;
+
+/// A version of this library whose repository format a repository can be kept compatible with.
+///
+/// Newer versions of this library can read repositories written by older versions, but older
+/// versions can't read repositories written by newer versions. If a repository is created with a
+/// [`RepoConfig::compatibility_target`], it's always written in a format which that version can
+/// read, and options which that version doesn't support are rejected.
+///
+/// Version 0.14 doesn't support:
+///
+/// - [`RepoConfig::max_header_size`]
+/// - [`RepoConfig::verify_reads`]
+/// - [`RepoConfig::gc_grace_period`]
+/// - [`RepoConfig::buffer_pool_size`]
+/// - [`RepoConfig::commit_history`], so no commit history is kept
+/// - [`Chunking::FastCdc`]
+/// - [`RepoConfig::min_chunk_size`] and [`RepoConfig::max_chunk_size`]
+/// - Per-object compression with [`ObjectOptions::compression`]
+/// - [`Compression::Zstd`] and dictionaries trained with [`KeyRepo::train_dictionary`]
+/// - The [`DestructivePolicy`]
+/// - The audit log, so no audit log is kept
+/// - The trash
+/// - Object metadata, tags, expiration times, and snapshots
+/// - Rechunking
+/// - Commit IDs and commit payloads, so the commit ID is reset each time the repository is opened
+///
+/// [`RepoConfig::compatibility_target`]: crate::repo::RepoConfig::compatibility_target
+/// [`RepoConfig::max_header_size`]: crate::repo::RepoConfig::max_header_size
+/// [`RepoConfig::verify_reads`]: crate::repo::RepoConfig::verify_reads
+/// [`RepoConfig::gc_grace_period`]: crate::repo::RepoConfig::gc_grace_period
+/// [`RepoConfig::buffer_pool_size`]: crate::repo::RepoConfig::buffer_pool_size
+/// [`RepoConfig::commit_history`]: crate::repo::RepoConfig::commit_history
+/// [`Chunking::FastCdc`]: crate::repo::Chunking::FastCdc
+/// [`RepoConfig::min_chunk_size`]: crate::repo::RepoConfig::min_chunk_size
+/// [`RepoConfig::max_chunk_size`]: crate::repo::RepoConfig::max_chunk_size
+/// [`ObjectOptions::compression`]: crate::repo::ObjectOptions::compression
+/// [`Compression::Zstd`]: crate::repo::Compression::Zstd
+/// [`KeyRepo::train_dictionary`]: crate::repo::key::KeyRepo::train_dictionary
+/// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum FormatVersion {
+    /// Version 0.14.
+    V0_14,
+
+    /// Version 0.15.
+    ///
+    /// This is the current version.
+    V0_15,
+}
+
+impl FormatVersion {
+    /// The version of this library.
+    pub const CURRENT: FormatVersion = FormatVersion::V0_15;
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatVersion::V0_14 => f.write_str("0.14"),
+            FormatVersion::V0_15 => f.write_str("0.15"),
+        }
+    }
+}
+
+/// A part of the repository format which isn't supported by every `FormatVersion`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    MaxHeaderSize,
+    VerifyReads,
+    GcGracePeriod,
+    BufferPoolSize,
+    DestructivePolicy,
+    AuditLog,
+    Trash,
+    Tags,
+    Rechunk,
+    CommitPayload,
+    CommitHistory,
+    FastCdc,
+    MinChunkSize,
+    MaxChunkSize,
+    ObjectCompression,
+    Zstd,
+    ZstdDictionary,
+}
+
+impl Capability {
+    /// The first version which supports this capability.
+    fn introduced_in(self) -> FormatVersion {
+        match self {
+            Capability::MaxHeaderSize => FormatVersion::V0_15,
+            Capability::VerifyReads => FormatVersion::V0_15,
+            Capability::GcGracePeriod => FormatVersion::V0_15,
+            Capability::BufferPoolSize => FormatVersion::V0_15,
+            Capability::DestructivePolicy => FormatVersion::V0_15,
+            Capability::AuditLog => FormatVersion::V0_15,
+            Capability::Trash => FormatVersion::V0_15,
+            Capability::Tags => FormatVersion::V0_15,
+            Capability::Rechunk => FormatVersion::V0_15,
+            Capability::CommitPayload => FormatVersion::V0_15,
+            Capability::CommitHistory => FormatVersion::V0_15,
+            Capability::FastCdc => FormatVersion::V0_15,
+            Capability::MinChunkSize => FormatVersion::V0_15,
+            Capability::MaxChunkSize => FormatVersion::V0_15,
+            Capability::ObjectCompression => FormatVersion::V0_15,
+            Capability::Zstd => FormatVersion::V0_15,
+            Capability::ZstdDictionary => FormatVersion::V0_15,
+        }
+    }
+
+    /// The name of the option or feature which requires this capability.
+    fn option(self) -> &'static str {
+        match self {
+            Capability::MaxHeaderSize => "max_header_size",
+            Capability::VerifyReads => "verify_reads",
+            Capability::GcGracePeriod => "gc_grace_period",
+            Capability::BufferPoolSize => "buffer_pool_size",
+            Capability::DestructivePolicy => "destructive_policy",
+            Capability::AuditLog => "audit_log",
+            Capability::Trash => "trash",
+            Capability::Tags => "tags",
+            Capability::Rechunk => "rechunk",
+            Capability::CommitPayload => "commit_payload",
+            Capability::CommitHistory => "commit_history",
+            Capability::FastCdc => "chunking",
+            Capability::MinChunkSize => "min_chunk_size",
+            Capability::MaxChunkSize => "max_chunk_size",
+            Capability::ObjectCompression => "object_compression",
+            Capability::Zstd => "compression",
+            Capability::ZstdDictionary => "zstd_dictionary",
+        }
+    }
+
+    /// Return whether a repository with the given compatibility `target` can use this capability.
+    pub fn is_supported_by(self, target: Option<FormatVersion>) -> bool {
+        match target {
+            Some(target) => target >= self.introduced_in(),
+            None => true,
+        }
+    }
+
+    /// Return an error if a repository with the given compatibility `target` can't use this
+    /// capability.
+    ///
+    /// # Errors
+    /// - `Error::Incompatible`: The `target` doesn't support this capability.
+    pub fn check(self, target: Option<FormatVersion>) -> crate::Result<()> {
+        match target {
+            Some(target) if !self.is_supported_by(Some(target)) => {
+                Err(crate::Error::Incompatible {
+                    option: self.option(),
+                    target,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Return an error if `config` uses an option which its compatibility target doesn't support.
+///
+/// # Errors
+/// - `Error::Incompatible`: The compatibility target doesn't support an option in `config`.
+pub fn check_config(config: &RepoConfig) -> crate::Result<()> {
+    let default = RepoConfig::default();
+    let options = [
+        (
+            Capability::MaxHeaderSize,
+            config.max_header_size != default.max_header_size,
+        ),
+        (
+            Capability::VerifyReads,
+            config.verify_reads != default.verify_reads,
+        ),
+        (
+            Capability::GcGracePeriod,
+            config.gc_grace_period != default.gc_grace_period,
+        ),
+        (
+            Capability::BufferPoolSize,
+            config.buffer_pool_size != default.buffer_pool_size,
+        ),
+        (
+            Capability::CommitHistory,
+            config.commit_history != default.commit_history,
+        ),
+        (
+            Capability::FastCdc,
+            matches!(config.chunking, Chunking::FastCdc { .. }),
+        ),
+        (
+            Capability::MinChunkSize,
+            config.min_chunk_size != default.min_chunk_size,
+        ),
+        (
+            Capability::MaxChunkSize,
+            config.max_chunk_size != default.max_chunk_size,
+        ),
+        (Capability::Zstd, config.compression.is_zstd()),
+    ];
+
+    for (capability, used) in options {
+        if used {
+            capability.check(config.compatibility_target)?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,11 +1,74 @@
+use std::io::Read;
+
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "compression")]
 use {
     lz4::{Decoder as Lz4Decoder, EncoderBuilder as Lz4EncoderBuilder},
-    std::io::{Read, Write},
+    std::io::{BufReader, Cursor, Write},
+    zstd::bulk::{Compressor as ZstdCompressor, Decompressor as ZstdDecompressor},
+    zstd::stream::read::Decoder as ZstdDecoder,
+    zstd::zstd_safe,
 };
 
+/// The maximum ratio of decompressed size to compressed size that LZ4 can produce.
+///
+/// LZ4 encodes long matches using one additional byte for every 255 bytes of output, so valid
+/// compressed data can never expand by more than this factor.
+#[cfg(feature = "compression")]
+const MAX_LZ4_RATIO: usize = 255;
+
+/// The maximum ratio of decompressed size to compressed size that Zstandard can produce.
+///
+/// Every Zstandard block has a 3-byte header and decompresses to at most 128 KiB, so valid
+/// compressed data can never expand by more than this factor.
+#[cfg(feature = "compression")]
+const MAX_ZSTD_RATIO: usize = 128 * 1024 / 3 + 1;
+
+/// The maximum size of a Zstandard frame header, which records the ID of its dictionary.
+#[cfg(feature = "compression")]
+const ZSTD_FRAME_HEADER_MAX_SIZE: u64 = 18;
+
+/// A Zstandard dictionary trained from the chunks in a repository.
+///
+/// Data compressed with a dictionary records the ID of the dictionary, so it can only be
+/// decompressed when that dictionary is available.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Dictionary(#[serde(with = "serde_bytes")] Vec<u8>);
+
+impl Dictionary {
+    /// Create a new `Dictionary` with the given contents.
+    #[cfg(feature = "compression")]
+    pub fn new(data: Vec<u8>) -> Self {
+        Dictionary(data)
+    }
+
+    /// The size of this dictionary in bytes.
+    pub fn size(&self) -> u64 {
+        self.0.len() as u64
+    }
+
+    /// Return the dictionary in `dictionaries` which the Zstandard frame at the start of `data`
+    /// was compressed with, or `None` if it wasn't compressed with a dictionary.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: The frame was compressed with a dictionary which isn't available.
+    #[cfg(feature = "compression")]
+    fn for_frame<'a>(
+        data: &[u8],
+        dictionaries: &'a [Dictionary],
+    ) -> crate::Result<Option<&'a Self>> {
+        match zstd_safe::get_dict_id_from_frame(data) {
+            Some(id) => dictionaries
+                .iter()
+                .find(|dictionary| zstd_safe::get_dict_id_from_dict(&dictionary.0) == Some(id))
+                .map(Some)
+                .ok_or(crate::Error::InvalidData),
+            None => Ok(None),
+        }
+    }
+}
+
 /// A data compression method.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
@@ -23,37 +86,238 @@ pub enum Compression {
         /// highest compression ratio.
         level: u32,
     },
+
+    /// Compress data using the Zstandard compression algorithm.
+    ///
+    /// If the repository has a dictionary trained with [`KeyRepo::train_dictionary`], new chunks
+    /// are compressed with it.
+    ///
+    /// [`KeyRepo::train_dictionary`]: crate::repo::key::KeyRepo::train_dictionary
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    Zstd {
+        /// The compression level to use.
+        ///
+        /// This is a number in the range 1-22, where 1 gives the fastest compression and 22 gives
+        /// the highest compression ratio. Negative levels are faster still.
+        level: i32,
+    },
 }
 
 impl Compression {
     /// Compresses the given `data` and returns it.
     pub(crate) fn compress(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(data.len());
+        self.compress_into(data, &mut output)?;
+        Ok(output)
+    }
+
+    /// Compresses the given `data` and appends it to `output`.
+    pub(crate) fn compress_into(&self, data: &[u8], output: &mut Vec<u8>) -> crate::Result<()> {
+        self.compress_into_with_dictionaries(data, &[], output)
+    }
+
+    /// Compresses the given `data` and appends it to `output`.
+    ///
+    /// If this compression method supports dictionaries, the last of the given `dictionaries` is
+    /// used.
+    #[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+    pub(crate) fn compress_into_with_dictionaries(
+        &self,
+        data: &[u8],
+        dictionaries: &[Dictionary],
+        output: &mut Vec<u8>,
+    ) -> crate::Result<()> {
         match self {
-            Compression::None => Ok(data.to_vec()),
+            Compression::None => {
+                output.extend_from_slice(data);
+                Ok(())
+            }
             #[cfg(feature = "compression")]
             Compression::Lz4 { level } => {
-                let mut output = Vec::with_capacity(data.len());
-                let mut encoder = Lz4EncoderBuilder::new().level(*level).build(&mut output)?;
+                let mut encoder = Lz4EncoderBuilder::new().level(*level).build(output)?;
                 encoder.write_all(data)?;
                 let (_, result) = encoder.finish();
                 result?;
-                Ok(output)
+                Ok(())
             }
+            #[cfg(feature = "compression")]
+            Compression::Zstd { level } => {
+                let mut compressor = match dictionaries.last() {
+                    Some(dictionary) => ZstdCompressor::with_dictionary(*level, &dictionary.0)?,
+                    None => ZstdCompressor::new(*level)?,
+                };
+                output.extend_from_slice(&compressor.compress(data)?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Return whether this is a Zstandard compression method.
+    pub(crate) fn is_zstd(&self) -> bool {
+        #[cfg(feature = "compression")]
+        if let Compression::Zstd { .. } = self {
+            return true;
         }
+        false
     }
 
-    /// Wraps the given `reader` to decompress its bytes using this compression method.
+    /// Decompresses the given `data` and returns it.
+    ///
+    /// This fails with `Error::InvalidData` if the data would decompress to more than the maximum
+    /// size that valid compressed data of its length could produce.
     pub(crate) fn decompress(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(data.len());
+        self.decompress_into(data, &mut output)?;
+        Ok(output)
+    }
+
+    /// Decompresses the given `data` and appends it to `output`.
+    ///
+    /// This has the same size limit as `decompress`.
+    pub(crate) fn decompress_into(&self, data: &[u8], output: &mut Vec<u8>) -> crate::Result<()> {
+        self.decompress_into_with_dictionaries(data, &[], output)
+    }
+
+    /// Decompresses the given `data`, which may have been compressed with one of the given
+    /// `dictionaries`, and appends it to `output`.
+    ///
+    /// This has the same size limit as `decompress`.
+    pub(crate) fn decompress_into_with_dictionaries(
+        &self,
+        data: &[u8],
+        dictionaries: &[Dictionary],
+        output: &mut Vec<u8>,
+    ) -> crate::Result<()> {
+        let limit = match self {
+            Compression::None => data.len(),
+            #[cfg(feature = "compression")]
+            Compression::Lz4 { .. } => data.len().saturating_mul(MAX_LZ4_RATIO),
+            #[cfg(feature = "compression")]
+            Compression::Zstd { .. } => data.len().saturating_mul(MAX_ZSTD_RATIO),
+        };
+        self.decompress_limited_into_with_dictionaries(data, limit, dictionaries, output)
+    }
+
+    /// Decompresses the given `data` and returns it, reading at most `limit` bytes of output.
+    ///
+    /// This fails with `Error::InvalidData` if the data decompresses to more than `limit` bytes,
+    /// which protects against maliciously crafted data which decompresses to a huge size.
+    pub(crate) fn decompress_limited(&self, data: &[u8], limit: usize) -> crate::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(data.len().min(limit));
+        self.decompress_limited_into(data, limit, &mut output)?;
+        Ok(output)
+    }
+
+    /// Decompresses the given `data` and appends it to `output`, reading at most `limit` bytes of
+    /// output.
+    ///
+    /// If this returns `Err`, some data may have been appended to `output`.
+    pub(crate) fn decompress_limited_into(
+        &self,
+        data: &[u8],
+        limit: usize,
+        output: &mut Vec<u8>,
+    ) -> crate::Result<()> {
+        self.decompress_limited_into_with_dictionaries(data, limit, &[], output)
+    }
+
+    /// Decompresses the given `data`, which may have been compressed with one of the given
+    /// `dictionaries`, and appends it to `output`, reading at most `limit` bytes of output.
+    ///
+    /// If this returns `Err`, some data may have been appended to `output`.
+    #[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+    fn decompress_limited_into_with_dictionaries(
+        &self,
+        data: &[u8],
+        limit: usize,
+        dictionaries: &[Dictionary],
+        output: &mut Vec<u8>,
+    ) -> crate::Result<()> {
         match self {
-            Compression::None => Ok(data.to_vec()),
+            Compression::None => {
+                if data.len() > limit {
+                    return Err(crate::Error::InvalidData);
+                }
+                output.extend_from_slice(data);
+                Ok(())
+            }
             #[cfg(feature = "compression")]
             Compression::Lz4 { .. } => {
-                let mut output = Vec::with_capacity(data.len());
+                let start = output.len();
                 let mut decoder = Lz4Decoder::new(data)?;
-                decoder.read_to_end(&mut output)?;
+                // Read one byte past the limit so we can tell if the limit was exceeded.
+                (&mut decoder).take(limit as u64 + 1).read_to_end(output)?;
+                if output.len() - start > limit {
+                    return Err(crate::Error::InvalidData);
+                }
                 let (_, result) = decoder.finish();
                 result?;
-                Ok(output)
+                Ok(())
+            }
+            #[cfg(feature = "compression")]
+            Compression::Zstd { .. } => {
+                // Frames are always written with their decompressed size, so we can check the
+                // limit before decompressing anything.
+                let size = match zstd_safe::get_frame_content_size(data) {
+                    Ok(Some(size)) if size <= limit as u64 => size as usize,
+                    _ => return Err(crate::Error::InvalidData),
+                };
+                let mut decompressor = match Dictionary::for_frame(data, dictionaries)? {
+                    Some(dictionary) => ZstdDecompressor::with_dictionary(&dictionary.0)?,
+                    None => ZstdDecompressor::new()?,
+                };
+                let decompressed = decompressor.decompress(data, size)?;
+                if decompressed.len() != size {
+                    return Err(crate::Error::InvalidData);
+                }
+                output.extend_from_slice(&decompressed);
+                Ok(())
+            }
+        }
+    }
+
+    /// Return a reader which decompresses the data read from `reader`.
+    ///
+    /// Unlike `decompress`, this does not limit the size of the output, so callers must limit how
+    /// much they read.
+    pub(crate) fn decompress_reader<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> crate::Result<Box<dyn Read + 'a>> {
+        self.decompress_reader_with_dictionaries(reader, &[])
+    }
+
+    /// Return a reader which decompresses the data read from `reader`, which may have been
+    /// compressed with one of the given `dictionaries`.
+    ///
+    /// This has the same caveats as `decompress_reader`.
+    #[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+    pub(crate) fn decompress_reader_with_dictionaries<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+        dictionaries: &[Dictionary],
+    ) -> crate::Result<Box<dyn Read + 'a>> {
+        match self {
+            Compression::None => Ok(reader),
+            #[cfg(feature = "compression")]
+            Compression::Lz4 { .. } => Ok(Box::new(Lz4Decoder::new(reader)?)),
+            #[cfg(feature = "compression")]
+            Compression::Zstd { .. } => {
+                // Read the frame header first to find out which dictionary the frame needs.
+                let mut reader = reader;
+                let mut header = Vec::new();
+                (&mut reader)
+                    .take(ZSTD_FRAME_HEADER_MAX_SIZE)
+                    .read_to_end(&mut header)?;
+                let dictionary = Dictionary::for_frame(&header, dictionaries)?;
+                let reader = BufReader::new(Cursor::new(header).chain(reader));
+                Ok(match dictionary {
+                    Some(dictionary) => {
+                        Box::new(ZstdDecoder::with_dictionary(reader, &dictionary.0)?)
+                    }
+                    None => Box::new(ZstdDecoder::with_buffer(reader)?),
+                })
             }
         }
     }
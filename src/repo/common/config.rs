@@ -1,6 +1,11 @@
-use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use cdchunking::ChunkerImpl;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 
 use super::chunking::Chunking;
+use super::compatibility::FormatVersion;
 use super::compression::Compression;
 use super::encryption::{Encryption, ResourceLimit};
 use super::packing::Packing;
@@ -9,7 +14,7 @@ use super::packing::Packing;
 ///
 /// This type is used to configure a repository when it is created. This type implements `Default`
 /// to provide a reasonable default configuration.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[non_exhaustive]
 pub struct RepoConfig {
     /// The chunking method to use in the repository.
@@ -41,6 +46,204 @@ pub struct RepoConfig {
     ///
     /// The default value is `ResourceLimit::Interactive`.
     pub operations_limit: ResourceLimit,
+
+    /// The maximum size of the encoded repository header in bytes.
+    ///
+    /// If committing changes would write a header larger than this, the commit fails with
+    /// `Error::HeaderTooLarge`. If this is `None`, the size of the header is not limited. Unlike
+    /// other options, this can be changed after the repository is created with
+    /// [`KeyRepo::set_max_header_size`].
+    ///
+    /// The default value is `None`.
+    ///
+    /// [`KeyRepo::set_max_header_size`]: crate::repo::key::KeyRepo::set_max_header_size
+    #[serde(default)]
+    pub max_header_size: Option<u64>,
+
+    /// Whether to verify the hash of every chunk which is read from the repository.
+    ///
+    /// If this is `true`, every chunk which is read from the data store is hashed and compared to
+    /// its expected hash, and reading fails with `Error::ChunkMismatch` if they don't match. This
+    /// detects corrupt data at the point it is read even when encryption is disabled, at the cost
+    /// of hashing all data which is read. Unlike other options, this can be changed after the
+    /// repository is created with [`KeyRepo::set_verify_reads`].
+    ///
+    /// The default value is `false`.
+    ///
+    /// [`KeyRepo::set_verify_reads`]: crate::repo::key::KeyRepo::set_verify_reads
+    #[serde(default)]
+    pub verify_reads: bool,
+
+    /// How old an unreferenced block must be before it can be removed from the data store.
+    ///
+    /// This only applies when the data store has [`Consistency::Eventual`]. With such stores, a
+    /// block which was written recently may have been written by another client whose header is
+    /// not visible yet, so [`Commit::clean`] only removes unreferenced blocks which were written
+    /// longer ago than this. Blocks whose modification time the data store can't report are never
+    /// removed. Unlike other options, this can be changed after the repository is created with
+    /// [`KeyRepo::set_gc_grace_period`].
+    ///
+    /// The default value is one hour.
+    ///
+    /// [`Consistency::Eventual`]: crate::store::Consistency::Eventual
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    /// [`KeyRepo::set_gc_grace_period`]: crate::repo::key::KeyRepo::set_gc_grace_period
+    #[serde(default = "default_gc_grace_period")]
+    pub gc_grace_period: Duration,
+
+    /// The maximum number of bytes of buffers to keep for reuse when encoding and decoding chunks.
+    ///
+    /// Keeping buffers for reuse rather than freeing them reduces heap fragmentation in
+    /// long-running processes. If this is `0`, buffers are not reused. This only applies when the
+    /// repository isn't opened with a shared pool; see [`BufferPool`] for details.
+    ///
+    /// The default value is `0`.
+    ///
+    /// [`BufferPool`]: crate::repo::BufferPool
+    #[serde(default)]
+    pub buffer_pool_size: u64,
+
+    /// The oldest version of this library which must be able to read the repository.
+    ///
+    /// If this is `Some`, the repository is always written in a format which that version can
+    /// read, and creating the repository fails with `Error::Incompatible` if any other option
+    /// requires a newer version. See [`FormatVersion`] for what each version supports. If this is
+    /// `None`, the repository is written in the current format.
+    ///
+    /// A repository which was written by version 0.14 of this library keeps that version as its
+    /// compatibility target when it's opened by a newer version. Unlike other options, this can be
+    /// changed after the repository is created with [`KeyRepo::set_compatibility_target`].
+    ///
+    /// The default value is `None`.
+    ///
+    /// [`FormatVersion`]: crate::repo::FormatVersion
+    /// [`KeyRepo::set_compatibility_target`]: crate::repo::key::KeyRepo::set_compatibility_target
+    #[serde(default)]
+    pub compatibility_target: Option<FormatVersion>,
+
+    /// The number of previous commits to keep in the commit history.
+    ///
+    /// The headers of previous commits are kept in the data store, along with the data they
+    /// reference, so that the repository can be returned to one of those commits with
+    /// [`KeyRepo::rollback_to_commit`] even after changes have been committed and
+    /// [`Commit::clean`] has been called. Keeping more commits uses more space in the data store.
+    /// If this is `0`, no commit history is kept. Unlike other options, this can be changed after
+    /// the repository is created with [`KeyRepo::set_commit_history`].
+    ///
+    /// The default value is `0`.
+    ///
+    /// [`KeyRepo::rollback_to_commit`]: crate::repo::key::KeyRepo::rollback_to_commit
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    /// [`KeyRepo::set_commit_history`]: crate::repo::key::KeyRepo::set_commit_history
+    #[serde(default)]
+    pub commit_history: u32,
+
+    /// The minimum size of a chunk in bytes.
+    ///
+    /// If this is `Some`, chunk boundaries found by the [`chunking`] method are ignored until a
+    /// chunk is at least this large, so only the last chunk of an object can be smaller. This keeps
+    /// pathological data from producing many tiny chunks, which deduplicate poorly and are slow to
+    /// store. This applies to every chunking method, including when objects are rechunked.
+    ///
+    /// The default value is `None`.
+    ///
+    /// [`chunking`]: crate::repo::RepoConfig::chunking
+    #[serde(default)]
+    pub min_chunk_size: Option<u32>,
+
+    /// The maximum size of a chunk in bytes.
+    ///
+    /// If this is `Some`, a chunk boundary is added whenever a chunk reaches this size, even if the
+    /// [`chunking`] method didn't find one. This keeps pathological data from producing enormous
+    /// chunks, which must be held in memory when they're read. If this is less than
+    /// [`min_chunk_size`], [`min_chunk_size`] is used instead.
+    ///
+    /// The default value is `None`.
+    ///
+    /// [`chunking`]: crate::repo::RepoConfig::chunking
+    /// [`min_chunk_size`]: crate::repo::RepoConfig::min_chunk_size
+    #[serde(default)]
+    pub max_chunk_size: Option<u32>,
+}
+
+impl RepoConfig {
+    /// Return a chunker for the chunking method and chunk size bounds of this config.
+    pub(super) fn to_chunker(&self) -> Box<dyn ChunkerImpl + Send + Sync> {
+        self.chunking
+            .to_bounded_chunker(self.min_chunk_size, self.max_chunk_size)
+    }
+}
+
+// Options which were added after the repository format was stabilized are omitted while they have
+// their default value, so they don't change how existing repositories are serialized. Because the
+// config is serialized as an array, an option can only be omitted if every option after it is
+// omitted as well, which `#[serde(skip_serializing_if)]` can't express.
+//
+// The config is destructured without a rest pattern so that a new option can't be added without
+// also being serialized here.
+impl Serialize for RepoConfig {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let RepoConfig {
+            chunking,
+            packing,
+            compression,
+            encryption,
+            memory_limit,
+            operations_limit,
+            max_header_size,
+            verify_reads,
+            gc_grace_period,
+            buffer_pool_size,
+            compatibility_target,
+            commit_history,
+            min_chunk_size,
+            max_chunk_size,
+        } = self;
+
+        let trailing_options = [
+            *commit_history != 0,
+            min_chunk_size.is_some(),
+            max_chunk_size.is_some(),
+        ];
+        let trailing_len = trailing_options
+            .iter()
+            .rposition(|is_set| *is_set)
+            .map_or(0, |index| index + 1);
+
+        let mut state = serializer.serialize_struct("RepoConfig", 11 + trailing_len)?;
+        state.serialize_field("chunking", chunking)?;
+        state.serialize_field("packing", packing)?;
+        state.serialize_field("compression", compression)?;
+        state.serialize_field("encryption", encryption)?;
+        state.serialize_field("memory_limit", memory_limit)?;
+        state.serialize_field("operations_limit", operations_limit)?;
+        state.serialize_field("max_header_size", max_header_size)?;
+        state.serialize_field("verify_reads", verify_reads)?;
+        state.serialize_field("gc_grace_period", gc_grace_period)?;
+        state.serialize_field("buffer_pool_size", buffer_pool_size)?;
+        state.serialize_field("compatibility_target", compatibility_target)?;
+        if trailing_len > 0 {
+            state.serialize_field("commit_history", commit_history)?;
+        } else {
+            state.skip_field("commit_history")?;
+        }
+        if trailing_len > 1 {
+            state.serialize_field("min_chunk_size", min_chunk_size)?;
+        } else {
+            state.skip_field("min_chunk_size")?;
+        }
+        if trailing_len > 2 {
+            state.serialize_field("max_chunk_size", max_chunk_size)?;
+        } else {
+            state.skip_field("max_chunk_size")?;
+        }
+        state.end()
+    }
+}
+
+/// The default value of `RepoConfig::gc_grace_period`.
+fn default_gc_grace_period() -> Duration {
+    Duration::from_secs(60 * 60)
 }
 
 impl Default for RepoConfig {
@@ -52,6 +255,14 @@ impl Default for RepoConfig {
             encryption: Encryption::None,
             memory_limit: ResourceLimit::Interactive,
             operations_limit: ResourceLimit::Interactive,
+            max_header_size: None,
+            verify_reads: false,
+            gc_grace_period: default_gc_grace_period(),
+            buffer_pool_size: 0,
+            compatibility_target: None,
+            commit_history: 0,
+            min_chunk_size: None,
+            max_chunk_size: None,
         }
     }
 }
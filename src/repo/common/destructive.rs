@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// A policy which requires destructive operations to be authorized before they are committed.
+///
+/// When this policy is enabled, committing changes which remove more objects or more bytes than
+/// the configured thresholds, clear an instance, or purge objects from the trash fails with
+/// `Error::DestructiveNotAuthorized` unless the operation was authorized in the same session with
+/// [`KeyRepo::authorize_destructive`].
+///
+/// This policy is stored in the repository metadata and can only be changed with
+/// [`KeyRepo::set_destructive_policy`], which requires the repository's password. It is meant to
+/// protect against accidents, like running a cleanup script against the wrong repository. It is
+/// not a security mechanism; anyone who can open the repository can authorize destructive
+/// operations.
+///
+/// This type implements `Default` to provide a policy which is disabled.
+///
+/// [`KeyRepo::authorize_destructive`]: crate::repo::key::KeyRepo::authorize_destructive
+/// [`KeyRepo::set_destructive_policy`]: crate::repo::key::KeyRepo::set_destructive_policy
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct DestructivePolicy {
+    /// Whether destructive operations must be authorized.
+    ///
+    /// The default value is `false`.
+    pub enabled: bool,
+
+    /// The maximum number of objects which can be removed between commits without authorization.
+    ///
+    /// If this is `None`, the number of objects is not limited.
+    ///
+    /// The default value is `None`.
+    pub max_removed_objects: Option<u64>,
+
+    /// The maximum number of bytes which can be removed between commits without authorization.
+    ///
+    /// If this is `None`, the number of bytes is not limited.
+    ///
+    /// The default value is `None`.
+    pub max_removed_bytes: Option<u64>,
+}
+
+/// A kind of destructive operation which can be authorized.
+///
+/// See [`DestructivePolicy`] for details.
+///
+/// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DestructiveScope {
+    /// Removing more objects or bytes than allowed by the policy.
+    Remove,
+
+    /// Clearing an instance of the repository.
+    Clear,
+
+    /// Purging objects from the trash.
+    PurgeTrash,
+}
+
+impl fmt::Display for DestructiveScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DestructiveScope::Remove => f.write_str("removing objects"),
+            DestructiveScope::Clear => f.write_str("clearing an instance"),
+            DestructiveScope::PurgeTrash => f.write_str("purging the trash"),
+        }
+    }
+}
+
+/// The number of objects and bytes removed by a kind of destructive operation.
+#[derive(Debug, Default, Clone, Copy)]
+struct Tally {
+    objects: u64,
+    bytes: u64,
+}
+
+/// The destructive operations performed since the last commit and the authorizations for them.
+///
+/// This state is kept in memory and is never persisted.
+#[derive(Debug, Default)]
+pub struct Interlock {
+    /// The destructive operations performed since the last commit.
+    tallies: HashMap<DestructiveScope, Tally>,
+
+    /// A map of authorized scopes to when their authorization expires.
+    ///
+    /// An authorization with no expiration time never expires.
+    authorizations: HashMap<DestructiveScope, Option<Instant>>,
+}
+
+impl Interlock {
+    /// Record that an object of `size` bytes was removed by an operation in the given `scope`.
+    pub fn record(&mut self, scope: DestructiveScope, size: u64) {
+        let tally = self.tallies.entry(scope).or_default();
+        tally.objects += 1;
+        tally.bytes += size;
+    }
+
+    /// Authorize operations in the given `scope` for the next `ttl`.
+    pub fn authorize(&mut self, scope: DestructiveScope, ttl: Duration) {
+        self.authorizations
+            .insert(scope, Instant::now().checked_add(ttl));
+    }
+
+    /// Return whether operations in the given `scope` are currently authorized.
+    fn is_authorized(&self, scope: DestructiveScope) -> bool {
+        match self.authorizations.get(&scope) {
+            Some(Some(expires)) => Instant::now() < *expires,
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// Check that the operations performed since the last commit are allowed by the `policy`.
+    ///
+    /// # Errors
+    /// - `Error::DestructiveNotAuthorized`: An operation exceeds the `policy` and was not
+    /// authorized.
+    pub fn check(&self, policy: &DestructivePolicy) -> crate::Result<()> {
+        if !policy.enabled {
+            return Ok(());
+        }
+
+        for scope in [
+            DestructiveScope::Remove,
+            DestructiveScope::Clear,
+            DestructiveScope::PurgeTrash,
+        ] {
+            let tally = match self.tallies.get(&scope) {
+                Some(tally) => *tally,
+                None => continue,
+            };
+
+            let exceeds_policy = match scope {
+                DestructiveScope::Remove => {
+                    policy
+                        .max_removed_objects
+                        .is_some_and(|limit| tally.objects > limit)
+                        || policy
+                            .max_removed_bytes
+                            .is_some_and(|limit| tally.bytes > limit)
+                }
+                DestructiveScope::Clear | DestructiveScope::PurgeTrash => true,
+            };
+
+            if exceeds_policy && !self.is_authorized(scope) {
+                return Err(crate::Error::DestructiveNotAuthorized {
+                    scope,
+                    objects: tally.objects,
+                    bytes: tally.bytes,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forget the destructive operations performed since the last commit.
+    ///
+    /// This does not revoke any authorizations.
+    pub fn reset(&mut self) {
+        self.tallies.clear();
+    }
+}
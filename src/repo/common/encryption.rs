@@ -8,7 +8,7 @@ use {
     rand::rngs::OsRng,
     rand::RngCore,
     sodiumoxide::crypto::aead::xchacha20poly1305_ietf::{
-        gen_nonce, open, seal, Key as ChaChaKey, Nonce, KEYBYTES, NONCEBYTES,
+        gen_nonce, open, seal, Key as ChaChaKey, Nonce, KEYBYTES, NONCEBYTES, TAGBYTES,
     },
     sodiumoxide::crypto::pwhash::argon2id13::{
         derive_key, gen_salt, MemLimit, OpsLimit, Salt, MEMLIMIT_INTERACTIVE, MEMLIMIT_MODERATE,
@@ -107,8 +107,14 @@ impl Encryption {
         match self {
             Encryption::None => Ok(ciphertext.to_vec()),
             Encryption::XChaCha20Poly1305 => {
-                let nonce = Nonce::from_slice(&ciphertext[..NONCEBYTES]).unwrap();
-                let chacha_key = ChaChaKey::from_slice(key.expose_secret()).unwrap();
+                // The ciphertext and key may have come from an untrusted source, so we can't assume
+                // they're the correct size.
+                let nonce = ciphertext
+                    .get(..NONCEBYTES)
+                    .and_then(Nonce::from_slice)
+                    .ok_or(crate::Error::InvalidData)?;
+                let chacha_key =
+                    ChaChaKey::from_slice(key.expose_secret()).ok_or(crate::Error::InvalidData)?;
                 open(&ciphertext[NONCEBYTES..], None, &nonce, &chacha_key)
                     .map_err(|_| crate::Error::InvalidData)
             }
@@ -135,6 +141,17 @@ impl Encryption {
             Encryption::XChaCha20Poly1305 => KEYBYTES,
         }
     }
+
+    /// The number of bytes this encryption method adds to each message it encrypts.
+    ///
+    /// This is the size of the framing (nonce and authentication tag) around each ciphertext.
+    pub(crate) fn overhead(&self) -> usize {
+        match self {
+            Encryption::None => 0,
+            #[cfg(feature = "encryption")]
+            Encryption::XChaCha20Poly1305 => NONCEBYTES + TAGBYTES,
+        }
+    }
 }
 
 /// Salt for deriving an encryption `Key`.
@@ -161,6 +178,23 @@ impl KeySalt {
     pub fn generate() -> Self {
         panic!("The `encryption` cargo feature is not enabled.")
     }
+
+    /// Create a `KeySalt` containing the given `bytes`.
+    #[cfg(test)]
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        KeySalt(bytes)
+    }
+
+    /// Return whether this salt is the correct size to derive a key from.
+    #[cfg(feature = "encryption")]
+    pub fn is_valid(&self) -> bool {
+        Salt::from_slice(self.0.as_slice()).is_some()
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn is_valid(&self) -> bool {
+        true
+    }
 }
 
 /// An secret encryption key.
@@ -0,0 +1,105 @@
+use std::collections::hash_map;
+use std::sync::{Arc, RwLock};
+
+use super::handle::{HandleIdTable, ObjectHandle};
+use super::key::Key;
+use super::key_index::KeyIndex;
+use super::object::Object;
+use super::state::RepoState;
+
+/// A view into a single object in a [`KeyRepo`], which may or may not exist.
+///
+/// This is like [`std::collections::hash_map::Entry`]. It can be used to get an object or insert
+/// it if it doesn't exist without looking up its key more than once.
+///
+/// This value is created by [`KeyRepo::entry`].
+///
+/// [`KeyRepo`]: crate::repo::key::KeyRepo
+/// [`KeyRepo::entry`]: crate::repo::key::KeyRepo::entry
+#[derive(Debug)]
+pub enum Entry<'a, K: Key> {
+    /// An object which exists.
+    Occupied(OccupiedEntry<'a, K>),
+
+    /// An object which doesn't exist.
+    Vacant(VacantEntry<'a, K>),
+}
+
+impl<'a, K: Key> Entry<'a, K> {
+    /// Return the key of this entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Return the object in this entry, inserting a new empty object if it doesn't exist.
+    pub fn or_insert(self) -> Object {
+        match self {
+            Entry::Occupied(entry) => entry.into_object(),
+            Entry::Vacant(entry) => entry.insert(),
+        }
+    }
+}
+
+/// A view into an object in a [`KeyRepo`] which exists.
+///
+/// This is part of the [`Entry`] enum.
+///
+/// [`KeyRepo`]: crate::repo::key::KeyRepo
+/// [`Entry`]: crate::repo::key::Entry
+#[derive(Debug)]
+pub struct OccupiedEntry<'a, K: Key> {
+    pub(super) state: &'a Arc<RwLock<RepoState>>,
+    pub(super) entry: hash_map::OccupiedEntry<'a, K, Arc<RwLock<ObjectHandle>>>,
+}
+
+impl<'a, K: Key> OccupiedEntry<'a, K> {
+    /// Return the key of this entry.
+    pub fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    /// Return the object in this entry.
+    pub fn object(&self) -> Object {
+        Object::new(self.state, self.entry.get())
+    }
+
+    /// Consume this entry and return its object.
+    pub fn into_object(self) -> Object {
+        Object::new(self.state, self.entry.get())
+    }
+}
+
+/// A view into an object in a [`KeyRepo`] which doesn't exist.
+///
+/// This is part of the [`Entry`] enum.
+///
+/// [`KeyRepo`]: crate::repo::key::KeyRepo
+/// [`Entry`]: crate::repo::key::Entry
+#[derive(Debug)]
+pub struct VacantEntry<'a, K: Key> {
+    pub(super) state: &'a Arc<RwLock<RepoState>>,
+    pub(super) handle_table: &'a mut HandleIdTable,
+    pub(super) key_index: &'a mut KeyIndex<K>,
+    pub(super) entry: hash_map::VacantEntry<'a, K, Arc<RwLock<ObjectHandle>>>,
+}
+
+impl<'a, K: Key> VacantEntry<'a, K> {
+    /// Return the key of this entry.
+    pub fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    /// Insert a new empty object with the key of this entry and return it.
+    pub fn insert(self) -> Object {
+        let handle = ObjectHandle {
+            id: self.handle_table.next(),
+            extents: Vec::new(),
+        };
+        self.key_index.touch(self.entry.key());
+        let handle = self.entry.insert(Arc::new(RwLock::new(handle)));
+        Object::new(self.state, handle)
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// The default path of the manifest in an exported archive.
+const DEFAULT_MANIFEST_PATH: &str = "MANIFEST.json";
+
+/// The permissions of regular files in an exported archive.
+const FILE_MODE: u32 = 0o644;
+
+/// The permissions of directories in an exported archive.
+const DIRECTORY_MODE: u32 = 0o755;
+
+/// The progress of an export.
+///
+/// This is passed to the progress hook of [`ExportOptions`] after each entry is exported.
+///
+/// [`ExportOptions`]: crate::repo::ExportOptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExportProgress {
+    /// The number of entries which have been exported so far.
+    pub entries: u64,
+
+    /// The number of bytes of object contents which have been exported so far.
+    pub bytes: u64,
+
+    /// The total number of entries in the repository, including those which are filtered out.
+    pub total_entries: u64,
+}
+
+/// Options for exporting the contents of a repository to a tar archive.
+///
+/// This is used with [`KeyRepo::export_plaintext`] and [`FileRepo::export_plaintext`]. The type
+/// parameter `K` is the type which is passed to the filter, which is the key type for a `KeyRepo`
+/// and `RelativePath` for a `FileRepo`.
+///
+/// [`KeyRepo::export_plaintext`]: crate::repo::key::KeyRepo::export_plaintext
+/// [`FileRepo::export_plaintext`]: crate::repo::file::FileRepo::export_plaintext
+pub struct ExportOptions<'a, K: ?Sized> {
+    manifest: Option<PathBuf>,
+    filter: Option<Box<dyn FnMut(&K) -> bool + 'a>>,
+    progress: Option<Box<dyn FnMut(ExportProgress) -> bool + 'a>>,
+}
+
+impl<'a, K: ?Sized> fmt::Debug for ExportOptions<'a, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExportOptions")
+            .field("manifest", &self.manifest)
+            .field("filter", &self.filter.is_some())
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl<'a, K: ?Sized> Default for ExportOptions<'a, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, K: ?Sized> ExportOptions<'a, K> {
+    /// Create a new `ExportOptions` with default settings.
+    ///
+    /// By default, every entry is exported, a manifest is written to `MANIFEST.json` at the root
+    /// of the archive, and there is no progress hook.
+    pub fn new() -> Self {
+        Self {
+            manifest: Some(PathBuf::from(DEFAULT_MANIFEST_PATH)),
+            filter: None,
+            progress: None,
+        }
+    }
+
+    /// The `path` in the archive to write the manifest to.
+    ///
+    /// The manifest is a JSON array with an element for each exported entry. Each element contains
+    /// the entry's `path` in the archive, its `key`, its `size`, the `blake3` hash of its contents
+    /// as a hex string, and its `attributes`. The hash is computed from the exported contents, so
+    /// it is not the same as the object's [`ContentId`]. The manifest is written after all the
+    /// entries, so it can be used to verify that the archive is complete.
+    ///
+    /// [`ContentId`]: crate::repo::ContentId
+    pub fn manifest(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.manifest = Some(path.into());
+        self
+    }
+
+    /// Do not write a manifest to the archive.
+    pub fn no_manifest(&mut self) -> &mut Self {
+        self.manifest = None;
+        self
+    }
+
+    /// A `filter` which returns whether an entry should be exported.
+    pub fn filter(&mut self, filter: impl FnMut(&K) -> bool + 'a) -> &mut Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// A `hook` which is called with the progress of the export after each entry is exported.
+    ///
+    /// If the hook returns `false`, the export is cancelled and returns `Error::Cancelled`.
+    pub fn progress(&mut self, hook: impl FnMut(ExportProgress) -> bool + 'a) -> &mut Self {
+        self.progress = Some(Box::new(hook));
+        self
+    }
+}
+
+/// An entry in the manifest of an exported archive.
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    path: PathBuf,
+    key: serde_json::Value,
+    size: u64,
+    blake3: Option<String>,
+    attributes: Option<serde_json::Value>,
+}
+
+/// A reader which computes a hash of the data read from it.
+struct HashingReader<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..bytes_read]);
+        Ok(bytes_read)
+    }
+}
+
+/// Convert a `value` to JSON for the manifest.
+pub fn to_json(value: &(impl Serialize + ?Sized)) -> crate::Result<serde_json::Value> {
+    serde_json::to_value(value).map_err(|_| crate::Error::Serialize)
+}
+
+/// A writer which exports entries to a tar archive.
+pub struct Exporter<'o, 'a, W: Write, K: ?Sized> {
+    builder: tar::Builder<W>,
+    options: &'o mut ExportOptions<'a, K>,
+    manifest: Vec<ManifestEntry>,
+    progress: ExportProgress,
+}
+
+impl<'o, 'a, W: Write, K: ?Sized> Exporter<'o, 'a, W, K> {
+    /// Create a new `Exporter` which writes to `dest` given the number of entries in the repo.
+    pub fn new(dest: W, options: &'o mut ExportOptions<'a, K>, total_entries: u64) -> Self {
+        Self {
+            builder: tar::Builder::new(dest),
+            options,
+            manifest: Vec::new(),
+            progress: ExportProgress {
+                entries: 0,
+                bytes: 0,
+                total_entries,
+            },
+        }
+    }
+
+    /// Return whether the entry with the given `key` should be exported.
+    pub fn includes(&mut self, key: &K) -> bool {
+        match &mut self.options.filter {
+            Some(filter) => filter(key),
+            None => true,
+        }
+    }
+
+    /// Record an entry in the manifest and report the progress.
+    ///
+    /// # Errors
+    /// - `Error::Cancelled`: The progress hook cancelled the export.
+    fn finish_entry(&mut self, entry: ManifestEntry) -> crate::Result<()> {
+        self.progress.entries += 1;
+        self.progress.bytes += entry.size;
+
+        if self.options.manifest.is_some() {
+            self.manifest.push(entry);
+        }
+
+        match &mut self.options.progress {
+            Some(hook) if !hook(self.progress) => Err(crate::Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stream the `size` bytes in `contents` to a regular file at `path` in the archive.
+    ///
+    /// # Errors
+    /// - `Error::Cancelled`: The progress hook cancelled the export.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn append_file(
+        &mut self,
+        path: &Path,
+        key: serde_json::Value,
+        size: u64,
+        contents: impl Read,
+        attributes: Option<serde_json::Value>,
+    ) -> crate::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(size);
+        header.set_mode(FILE_MODE);
+        header.set_mtime(0);
+
+        let mut reader = HashingReader {
+            inner: contents,
+            hasher: blake3::Hasher::new(),
+        };
+        self.builder.append_data(&mut header, path, &mut reader)?;
+
+        self.finish_entry(ManifestEntry {
+            path: path.to_owned(),
+            key,
+            size,
+            blake3: Some(reader.hasher.finalize().to_hex().to_string()),
+            attributes,
+        })
+    }
+
+    /// Add a directory at `path` in the archive.
+    ///
+    /// # Errors
+    /// - `Error::Cancelled`: The progress hook cancelled the export.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn append_directory(
+        &mut self,
+        path: &Path,
+        key: serde_json::Value,
+        attributes: Option<serde_json::Value>,
+    ) -> crate::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(DIRECTORY_MODE);
+        header.set_mtime(0);
+        self.builder.append_data(&mut header, path, io::empty())?;
+
+        self.finish_entry(ManifestEntry {
+            path: path.to_owned(),
+            key,
+            size: 0,
+            blake3: None,
+            attributes,
+        })
+    }
+
+    /// Record an entry at `path` which only appears in the manifest.
+    ///
+    /// # Errors
+    /// - `Error::Cancelled`: The progress hook cancelled the export.
+    pub fn append_manifest_only(
+        &mut self,
+        path: &Path,
+        key: serde_json::Value,
+        attributes: Option<serde_json::Value>,
+    ) -> crate::Result<()> {
+        self.finish_entry(ManifestEntry {
+            path: path.to_owned(),
+            key,
+            size: 0,
+            blake3: None,
+            attributes,
+        })
+    }
+
+    /// Write the manifest and finish the archive, returning the underlying writer.
+    ///
+    /// # Errors
+    /// - `Error::Serialize`: The manifest could not be serialized.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn finish(mut self) -> crate::Result<W> {
+        if let Some(manifest_path) = &self.options.manifest {
+            let manifest =
+                serde_json::to_vec_pretty(&self.manifest).map_err(|_| crate::Error::Serialize)?;
+
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_size(manifest.len() as u64);
+            header.set_mode(FILE_MODE);
+            header.set_mtime(0);
+            self.builder
+                .append_data(&mut header, manifest_path, manifest.as_slice())?;
+        }
+
+        Ok(self.builder.into_inner()?)
+    }
+}
@@ -0,0 +1,1178 @@
+//! The format of the data which a repository writes to its data store.
+//!
+//! All knowledge of how repository data is laid out in a data store lives in this module, and the
+//! rest of the crate goes through these functions to encode and decode blocks. Any change to the
+//! output of these functions is a backwards-incompatible change to the repository format and
+//! requires changing [`FORMAT_VERSION`]. The tests in this module check these functions against
+//! golden files in `tests/golden/` so that unintentional changes to the format are caught.
+//!
+//! # Blocks
+//! A repository stores the following blocks in its data store.
+//!
+//! - `BlockKey::Version` contains the 16 bytes of [`FORMAT_VERSION`]. This block is written last
+//! when a repository is created, so a data store without it does not contain a repository.
+//! - `BlockKey::Super` contains the repository metadata, which is serialized but never compressed
+//! or encrypted so that it can be read without the password.
+//! - `BlockKey::Header` contains a serialized repository header, encoded like a chunk. The
+//! metadata contains the ID of the current header; other header blocks are left over from previous
+//! commits.
+//! - `BlockKey::Data` contains either a single chunk or a pack, depending on the packing method.
+//! - `BlockKey::Lock` contains the context of a lock on the repository, which is encrypted but not
+//! compressed.
+//!
+//! # Serialization
+//! The metadata and the header are serialized with MessagePack in the format produced by
+//! `rmp-serde`. Structs are encoded as arrays of their fields in declaration order. Unit enum
+//! variants are encoded as their name, and other enum variants are encoded as a map with a single
+//! entry from their name to their value. UUIDs are encoded as 16-byte binary values, byte vectors
+//! and hashes are encoded as arrays of integers, `None` is encoded as nil, and durations are
+//! encoded as an array of seconds and nanoseconds. Zstandard dictionaries are encoded as binary
+//! values.
+//!
+//! # Chunks
+//! Chunks are identified by their size and their BLAKE3 hash. A chunk is encoded by compressing it
+//! and then encrypting it. With `Compression::Lz4`, data is compressed using the LZ4 frame format.
+//! With `Compression::Zstd`, data is compressed as a single Zstandard frame which records its
+//! decompressed size. Chunks are compressed with the most recent dictionary in the header if there
+//! is one, in which case the frame records the ID of the dictionary. Headers are never compressed
+//! with a dictionary.
+//! With `Encryption::XChaCha20Poly1305`, the encoded data is a random 24-byte nonce followed by the
+//! ciphertext and its 16-byte authentication tag, as produced by libsodium's
+//! `crypto_aead_xchacha20poly1305_ietf_encrypt` with no additional data.
+//!
+//! # Packs
+//! With `Packing::Fixed`, each chunk is compressed and the compressed chunks are concatenated into
+//! packs of exactly the configured pack size. A chunk may be split across multiple packs. The last
+//! pack is padded with zeroes, and each pack is then encrypted as a whole. The header maps the ID
+//! of each chunk's block to the list of pack slices which make up that block.
+//!
+//! # Keys
+//! All data in a repository is encrypted with a random master key. The master key is stored in the
+//! metadata, encrypted with a key which is derived from the password with Argon2id using the salt
+//! and the resource limits in the metadata.
+//!
+//! # Compatibility
+//! Fields which were added to the metadata and the header after version 0.14 of this library are
+//! appended to the end of their structs, so this version can read repositories written by 0.14.
+//! However, 0.14 rejects structs with more fields than it knows about. A repository whose
+//! compatibility target is 0.14 is written without those fields, and metadata which 0.14 can read
+//! is decoded as having a compatibility target of 0.14.
+//!
+//! # Well-known IDs
+//! Besides [`FORMAT_VERSION`], the following UUIDs have a fixed meaning in the repository format.
+//!
+//! - `ea978302-bfd8-11ea-b92b-031a9ad75c07` is the ID of the default instance.
+//! - `989a6a76-9d8b-46b7-9c05-d1c5e0d9471a` is the version ID of a `KeyRepo` instance.
+//! - `bb93f91a-ce4a-11eb-9c6b-b78939b5b629` is the version ID of a `StateRepo` instance.
+//! - `4db4c84c-cfc7-11eb-9e06-77121c3277f7` is the version ID of a `ValueRepo` instance.
+//! - `57ac9d00-fde6-11eb-82cd-1f2bdd384d98` is the version ID of a `FileRepo` instance.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+use rmp_serde::{from_slice, to_vec};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use uuid::{uuid, Uuid};
+
+use super::buffer_pool::BufferPool;
+use super::chunking::Chunking;
+use super::commit::CommitId;
+use super::compatibility::{check_config, Capability, FormatVersion};
+use super::compression::{Compression, Dictionary};
+use super::config::RepoConfig;
+use super::destructive::DestructivePolicy;
+use super::encryption::{Encryption, EncryptionKey, KeySalt, ResourceLimit};
+use super::handle::{Chunk, HandleIdTable, ObjectHandle};
+use super::metadata::{Header, RepoId, RepoMetadata};
+use super::open_repo::VersionId;
+use super::packing::Packing;
+use super::state::{ChunkInfo, InstanceId, PackIndex};
+use crate::store::BlockId;
+
+/// The current repository format version ID.
+///
+/// This must be changed any time a backwards-incompatible change is made to the repository
+/// format.
+pub const FORMAT_VERSION: Uuid = uuid!("44253e72-f08f-11eb-a2a3-a701701f8601");
+
+/// Return the contents of the version block.
+pub fn encode_version() -> &'static [u8] {
+    FORMAT_VERSION.as_bytes()
+}
+
+/// Check the contents of the version block.
+///
+/// # Errors
+/// - `Error::Corrupt`: The version block does not contain a version ID.
+/// - `Error::UnsupportedRepo`: The repository uses a different format version.
+pub fn decode_version(data: &[u8]) -> crate::Result<()> {
+    let version = Uuid::from_slice(data).map_err(|_| crate::Error::Corrupt)?;
+    if version != FORMAT_VERSION {
+        return Err(crate::Error::UnsupportedRepo);
+    }
+    Ok(())
+}
+
+/// The repository metadata in the format read by version 0.14.
+#[derive(Serialize, Deserialize)]
+struct MetadataV0_14 {
+    id: RepoId,
+    config: ConfigV0_14,
+    master_key: Vec<u8>,
+    salt: KeySalt,
+    header_id: BlockId,
+}
+
+/// The repository config in the format read by version 0.14.
+#[derive(Serialize, Deserialize)]
+struct ConfigV0_14 {
+    chunking: Chunking,
+    packing: Packing,
+    compression: Compression,
+    encryption: Encryption,
+    memory_limit: ResourceLimit,
+    operations_limit: ResourceLimit,
+}
+
+/// The repository header in the format read by version 0.14.
+#[derive(Serialize)]
+struct HeaderV0_14<'a> {
+    chunks: &'a HashMap<Chunk, ChunkInfo>,
+    packs: &'a HashMap<BlockId, Vec<PackIndex>>,
+    instances: HashMap<InstanceId, InstanceInfoV0_14<'a>>,
+    handle_table: &'a HandleIdTable,
+}
+
+/// Information about an instance in the format read by version 0.14.
+#[derive(Serialize)]
+struct InstanceInfoV0_14<'a> {
+    version_id: &'a VersionId,
+    objects: &'a ObjectHandle,
+}
+
+/// Return the contents of the superblock for the given `metadata`.
+///
+/// If the repository has a compatibility target, the metadata is encoded in the format which that
+/// version reads.
+///
+/// # Errors
+/// - `Error::Incompatible`: The metadata uses an option its compatibility target doesn't support.
+pub fn encode_metadata(metadata: &RepoMetadata) -> crate::Result<Vec<u8>> {
+    let config = &metadata.config;
+    let target = config.compatibility_target;
+
+    check_config(config)?;
+    if metadata.destructive_policy != DestructivePolicy::default() {
+        Capability::DestructivePolicy.check(target)?;
+    }
+    if !metadata.audit_log.is_empty() {
+        Capability::AuditLog.check(target)?;
+    }
+    if !metadata.commit_payload.is_empty() {
+        Capability::CommitPayload.check(target)?;
+    }
+    if !metadata.commit_history.is_empty() {
+        Capability::CommitHistory.check(target)?;
+    }
+
+    let serialized = match target {
+        Some(FormatVersion::V0_14) => to_vec(&MetadataV0_14 {
+            id: metadata.id,
+            config: ConfigV0_14 {
+                chunking: config.chunking.clone(),
+                packing: config.packing.clone(),
+                compression: config.compression.clone(),
+                encryption: config.encryption.clone(),
+                memory_limit: config.memory_limit,
+                operations_limit: config.operations_limit,
+            },
+            master_key: metadata.master_key.clone(),
+            salt: metadata.salt.clone(),
+            header_id: metadata.header_id,
+        }),
+        Some(FormatVersion::V0_15) | None => to_vec(metadata),
+    };
+
+    Ok(serialized.expect("Could not serialize repository metadata."))
+}
+
+/// Deserialize metadata from the contents of the superblock.
+///
+/// # Errors
+/// - `Error::Corrupt`: The metadata could not be deserialized.
+pub fn decode_metadata(data: &[u8]) -> crate::Result<RepoMetadata> {
+    // We use `from_slice` rather than `from_read` because these bytes are untrusted, and
+    // `from_read` allocates buffers based on lengths encoded in the data.
+
+    // Newer versions always write fields which version 0.14 doesn't know about, so if 0.14 can
+    // read the metadata, it was written to be compatible with 0.14.
+    if let Ok(legacy) = from_slice::<MetadataV0_14>(data) {
+        return Ok(RepoMetadata {
+            id: legacy.id,
+            config: RepoConfig {
+                chunking: legacy.config.chunking,
+                packing: legacy.config.packing,
+                compression: legacy.config.compression,
+                encryption: legacy.config.encryption,
+                memory_limit: legacy.config.memory_limit,
+                operations_limit: legacy.config.operations_limit,
+                compatibility_target: Some(FormatVersion::V0_14),
+                ..RepoConfig::default()
+            },
+            master_key: legacy.master_key,
+            salt: legacy.salt,
+            header_id: legacy.header_id,
+            destructive_policy: DestructivePolicy::default(),
+            audit_log: Vec::new(),
+            commit_id: CommitId::default(),
+            commit_payload: Vec::new(),
+            commit_history: Vec::new(),
+        });
+    }
+
+    from_slice(data).map_err(|_| crate::Error::Corrupt)
+}
+
+/// Serialize the given `header` for a repository with the given compatibility `target`.
+///
+/// The serialized header must be encoded with [`encode_chunk`] before it is written to the data
+/// store.
+///
+/// # Errors
+/// - `Error::Incompatible`: The header uses a feature the compatibility `target` doesn't support.
+pub fn serialize_header(header: &Header, target: Option<FormatVersion>) -> crate::Result<Vec<u8>> {
+    if header.rechunk.is_some() {
+        Capability::Rechunk.check(target)?;
+    }
+    if header.instances.values().any(|info| info.trash.is_some()) {
+        Capability::Trash.check(target)?;
+    }
+    if header
+        .instances
+        .values()
+        .any(|info| info.attributes.is_some())
+    {
+        Capability::Tags.check(target)?;
+    }
+    if header
+        .chunks
+        .values()
+        .any(|info| info.compression.is_some())
+    {
+        Capability::ObjectCompression.check(target)?;
+    }
+    if !header.dictionaries.is_empty() {
+        Capability::ZstdDictionary.check(target)?;
+    }
+
+    let serialized = match target {
+        Some(FormatVersion::V0_14) => to_vec(&HeaderV0_14 {
+            chunks: &header.chunks,
+            packs: &header.packs,
+            instances: header
+                .instances
+                .iter()
+                .map(|(id, info)| {
+                    let info = InstanceInfoV0_14 {
+                        version_id: &info.version_id,
+                        objects: &info.objects,
+                    };
+                    (*id, info)
+                })
+                .collect(),
+            handle_table: &header.handle_table,
+        }),
+        Some(FormatVersion::V0_15) | None => to_vec(header),
+    };
+
+    Ok(serialized.expect("Could not serialize the repository header."))
+}
+
+/// Deserialize a header from the bytes of a decoded header block.
+///
+/// # Errors
+/// - `Error::Corrupt`: The header could not be deserialized.
+pub fn deserialize_header(data: &[u8]) -> crate::Result<Header> {
+    // We use `from_slice` rather than `from_read` because these bytes are untrusted, and
+    // `from_read` allocates buffers based on lengths encoded in the data.
+    from_slice(data).map_err(|_| crate::Error::Corrupt)
+}
+
+/// Compress and encrypt the given `data` to be written as a block.
+///
+/// This is used for chunks when packing is disabled and for headers. Intermediate buffers are
+/// rented from `pool`, and the returned buffer can be released to `pool` once it's written.
+pub fn encode_chunk(
+    data: &[u8],
+    compression: &Compression,
+    dictionaries: &[Dictionary],
+    encryption: &Encryption,
+    key: &EncryptionKey,
+    pool: &BufferPool,
+) -> crate::Result<Vec<u8>> {
+    let mut compressed_data = pool.rent(data.len());
+    compression.compress_into_with_dictionaries(data, dictionaries, &mut compressed_data)?;
+
+    // Without encryption, the compressed data is the encoded data.
+    if let Encryption::None = encryption {
+        return Ok(compressed_data);
+    }
+
+    let encoded_data = encryption.encrypt(compressed_data.as_slice(), key);
+    pool.release(compressed_data);
+    Ok(encoded_data)
+}
+
+/// Decrypt and decompress a block which was encoded with [`encode_chunk`].
+///
+/// Intermediate buffers are rented from `pool`, and the returned buffer can be released to `pool`
+/// once it's no longer needed.
+///
+/// # Errors
+/// - `Error::InvalidData`: Ciphertext verification failed or the data is otherwise invalid.
+/// - `Error::Io`: The data could not be decompressed.
+pub fn decode_chunk(
+    data: &[u8],
+    compression: &Compression,
+    dictionaries: &[Dictionary],
+    encryption: &Encryption,
+    key: &EncryptionKey,
+    pool: &BufferPool,
+) -> crate::Result<Vec<u8>> {
+    let mut decoded_data = pool.rent(data.len());
+
+    // Without encryption, we can decompress the data directly.
+    if let Encryption::None = encryption {
+        compression.decompress_into_with_dictionaries(data, dictionaries, &mut decoded_data)?;
+        return Ok(decoded_data);
+    }
+
+    let decrypted_data = encryption.decrypt(data, key)?;
+    compression.decompress_into_with_dictionaries(
+        decrypted_data.as_slice(),
+        dictionaries,
+        &mut decoded_data,
+    )?;
+    pool.release(decrypted_data);
+    Ok(decoded_data)
+}
+
+/// Return a reader which decrypts and decompresses a block encoded with [`encode_chunk`].
+///
+/// Because each chunk is encrypted as a single message, an encrypted block must be read and
+/// authenticated in full before any of it can be decrypted; only decompression is streamed.
+/// Without encryption, the block is decoded as it is read from `block`.
+///
+/// The returned reader does not limit the size of the decoded data, so callers must limit how much
+/// they read.
+///
+/// # Errors
+/// - `Error::InvalidData`: Ciphertext verification failed or the data is otherwise invalid.
+/// - `Error::Io`: An I/O error occurred.
+pub fn decode_chunk_reader<'a>(
+    mut block: Box<dyn Read + 'a>,
+    compression: &Compression,
+    dictionaries: &[Dictionary],
+    encryption: &Encryption,
+    key: &EncryptionKey,
+) -> crate::Result<Box<dyn Read + 'a>> {
+    if let Encryption::None = encryption {
+        return compression.decompress_reader_with_dictionaries(block, dictionaries);
+    }
+
+    let mut encrypted_data = Vec::new();
+    block.read_to_end(&mut encrypted_data)?;
+    let decrypted_data = encryption.decrypt(encrypted_data.as_slice(), key)?;
+    drop(encrypted_data);
+    compression
+        .decompress_reader_with_dictionaries(Box::new(Cursor::new(decrypted_data)), dictionaries)
+}
+
+/// Compress the given `data` to be written to a pack.
+///
+/// Chunks are compressed before they are packed so that packs are always a fixed size. The
+/// returned buffer is rented from `pool`.
+pub fn encode_packed_chunk(
+    data: &[u8],
+    compression: &Compression,
+    dictionaries: &[Dictionary],
+    pool: &BufferPool,
+) -> crate::Result<Vec<u8>> {
+    let mut compressed_data = pool.rent(data.len());
+    compression.compress_into_with_dictionaries(data, dictionaries, &mut compressed_data)?;
+    Ok(compressed_data)
+}
+
+/// Decompress a chunk which was read from one or more packs.
+///
+/// The returned buffer is rented from `pool`.
+///
+/// # Errors
+/// - `Error::InvalidData`: The data is invalid.
+/// - `Error::Io`: The data could not be decompressed.
+pub fn decode_packed_chunk(
+    data: &[u8],
+    compression: &Compression,
+    dictionaries: &[Dictionary],
+    pool: &BufferPool,
+) -> crate::Result<Vec<u8>> {
+    let mut decompressed_data = pool.rent(data.len());
+    compression.decompress_into_with_dictionaries(data, dictionaries, &mut decompressed_data)?;
+    Ok(decompressed_data)
+}
+
+/// Pad the given pack `buffer` to `pack_size` with zeroes and encrypt it.
+///
+/// # Panics
+/// - The `buffer` is larger than `pack_size`.
+pub fn encode_pack(
+    buffer: &[u8],
+    pack_size: u32,
+    encryption: &Encryption,
+    key: &EncryptionKey,
+) -> Vec<u8> {
+    assert!(
+        buffer.len() <= pack_size as usize,
+        "The size of the current pack has exceeded the configured pack size.",
+    );
+
+    if buffer.len() == pack_size as usize {
+        return encryption.encrypt(buffer, key);
+    }
+    let mut padded = buffer.to_vec();
+    padded.resize(pack_size as usize, 0u8);
+    encryption.encrypt(padded.as_slice(), key)
+}
+
+/// Decrypt a pack which was encoded with [`encode_pack`].
+///
+/// # Errors
+/// - `Error::InvalidData`: Ciphertext verification failed or the data is otherwise invalid.
+pub fn decode_pack(
+    data: &[u8],
+    encryption: &Encryption,
+    key: &EncryptionKey,
+) -> crate::Result<Vec<u8>> {
+    encryption.decrypt(data, key)
+}
+
+/// Encrypt the `master_key` with the `user_key` derived from the password.
+pub fn encode_master_key(
+    master_key: &EncryptionKey,
+    encryption: &Encryption,
+    user_key: &EncryptionKey,
+) -> Vec<u8> {
+    encryption.encrypt(master_key.expose_secret(), user_key)
+}
+
+/// Decrypt a master key which was encoded with [`encode_master_key`].
+///
+/// # Errors
+/// - `Error::Password`: The `user_key` is not the key the master key was encrypted with.
+/// - `Error::Corrupt`: The decrypted master key is the wrong size.
+pub fn decode_master_key(
+    data: &[u8],
+    encryption: &Encryption,
+    user_key: &EncryptionKey,
+) -> crate::Result<EncryptionKey> {
+    let master_key = encryption
+        .decrypt(data, user_key)
+        .map_err(|_| crate::Error::Password)?;
+
+    if master_key.len() != encryption.key_size() {
+        return Err(crate::Error::Corrupt);
+    }
+
+    Ok(EncryptionKey::new(master_key))
+}
+
+/// Encrypt the given lock `context` to be written as a lock block.
+pub fn encode_lock(context: &[u8], encryption: &Encryption, key: &EncryptionKey) -> Vec<u8> {
+    encryption.encrypt(context, key)
+}
+
+/// Decrypt the context of a lock block which was encoded with [`encode_lock`].
+///
+/// # Errors
+/// - `Error::InvalidData`: Ciphertext verification failed or the data is otherwise invalid.
+pub fn decode_lock(
+    data: &[u8],
+    encryption: &Encryption,
+    key: &EncryptionKey,
+) -> crate::Result<Vec<u8>> {
+    encryption.decrypt(data, key)
+}
+
+#[cfg(all(test, feature = "encryption", feature = "compression"))]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use spectral::prelude::*;
+    use uuid::uuid;
+
+    use super::*;
+    use crate::repo::common::audit_log::{append_audit, AuditOperation};
+    use crate::repo::common::chunking::Chunking;
+    use crate::repo::common::config::RepoConfig;
+    use crate::repo::common::destructive::DestructivePolicy;
+    use crate::repo::common::encryption::{KeySalt, ResourceLimit};
+    use crate::repo::common::handle::{chunk_hash, Chunk, Extent, HandleIdTable, ObjectHandle};
+    use crate::repo::common::open_options::DEFAULT_INSTANCE;
+    use crate::repo::common::open_repo::OpenRepo;
+    use crate::repo::common::packing::Packing;
+    use crate::repo::common::rechunk::RechunkProgress;
+    use crate::repo::common::repository::KeyRepo;
+    use crate::repo::common::state::{ChunkInfo, InstanceInfo, PackIndex};
+
+    // The golden files in `tests/golden/` were produced from these inputs. The files containing
+    // encrypted data were produced with libsodium using the fixed `NONCE`, since encrypting data
+    // in this crate always uses a random nonce.
+
+    /// The master key used to encrypt the golden files.
+    const MASTER_KEY: [u8; 32] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+        0x1e, 0x1f,
+    ];
+
+    /// The password used to encrypt the master key in `metadata.bin`.
+    const PASSWORD: &[u8] = b"golden password";
+
+    /// The salt used to derive a key from `PASSWORD`.
+    const SALT: [u8; 16] = [
+        0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d, 0x6e,
+        0x6f,
+    ];
+
+    /// The nonce used to encrypt the encrypted golden files, other than the master key.
+    const NONCE: [u8; 24] = [
+        0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e,
+        0x4f, 0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57,
+    ];
+
+    /// The application payload stored in `metadata.bin`.
+    const COMMIT_PAYLOAD: &[u8] = b"golden payload";
+
+    /// The context stored in `lock-xchacha.bin`.
+    const LOCK_CONTEXT: &[u8] = b"golden lock context";
+
+    /// The pack size of the pack in `pack-xchacha.bin`.
+    const PACK_SIZE: u32 = 256;
+
+    /// The sizes of the two compressed chunks stored in `pack-xchacha.bin`.
+    const PACKED_SIZES: [usize; 2] = [49, 50];
+
+    /// The data stored in the chunk golden files.
+    fn chunk_data() -> Vec<u8> {
+        b"The quick brown fox jumps over the lazy dog. ".repeat(4)
+    }
+
+    /// The data stored in the two chunks in `pack-xchacha.bin`.
+    fn packed_data() -> [Vec<u8>; 2] {
+        [
+            b"first packed chunk ".repeat(3),
+            b"second packed chunk ".repeat(3),
+        ]
+    }
+
+    fn master_key() -> EncryptionKey {
+        EncryptionKey::new(MASTER_KEY.to_vec())
+    }
+
+    /// The commit ID stored in `metadata.bin`.
+    fn commit_id() -> CommitId {
+        CommitId {
+            sequence: 7,
+            uuid: uuid!("d4e5f6a7-b8c9-4dae-9f0a-1b2c3d4e5f60"),
+        }
+    }
+
+    fn lz4() -> Compression {
+        Compression::Lz4 { level: 4 }
+    }
+
+    fn zstd() -> Compression {
+        Compression::Zstd { level: 3 }
+    }
+
+    /// A Zstandard dictionary trained from records similar to `chunk_data`.
+    fn dictionary() -> Dictionary {
+        let samples = (0..1000)
+            .map(|i| format!("The quick brown fox number {i} jumps over lazy dog number {i}. "))
+            .collect::<Vec<_>>();
+        Dictionary::new(zstd::dict::from_samples(&samples, 4096).unwrap())
+    }
+
+    /// A buffer pool which never reuses buffers.
+    fn no_pool() -> BufferPool {
+        BufferPool::new(0)
+    }
+
+    /// The metadata stored in `metadata.bin`.
+    fn golden_metadata() -> RepoMetadata {
+        let mut config = RepoConfig::default();
+        config.chunking = Chunking::Zpaq { bits: 20 };
+        config.packing = Packing::Fixed(PACK_SIZE);
+        config.compression = lz4();
+        config.encryption = Encryption::XChaCha20Poly1305;
+        config.memory_limit = ResourceLimit::Interactive;
+        config.operations_limit = ResourceLimit::Interactive;
+        config.max_header_size = Some(1024 * 1024);
+        config.verify_reads = true;
+        config.gc_grace_period = Duration::from_secs(60 * 60);
+        config.buffer_pool_size = 4 * 1024 * 1024;
+
+        // The master key was encrypted with a different nonce than the other golden files, so we
+        // take it from the golden file rather than encrypting it again.
+        let golden = decode_metadata(include_bytes!("../../../tests/golden/metadata.bin")).unwrap();
+
+        let mut audit_log = Vec::new();
+        append_audit(
+            &mut audit_log,
+            AuditOperation::Created,
+            UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            Some(String::from("golden")),
+        );
+        append_audit(
+            &mut audit_log,
+            AuditOperation::PasswordChanged {
+                memory_limit: ResourceLimit::Interactive,
+                operations_limit: ResourceLimit::Interactive,
+            },
+            UNIX_EPOCH + Duration::from_secs(1_600_000_060),
+            None,
+        );
+
+        RepoMetadata {
+            id: uuid!("b7c5b2a4-3a8e-4c64-9f6b-1d2e3f405162").into(),
+            config,
+            master_key: golden.master_key,
+            salt: KeySalt::from_bytes(SALT.to_vec()),
+            header_id: uuid!("c1d2e3f4-a5b6-4c7d-8e9f-a0b1c2d3e4f5").into(),
+            destructive_policy: DestructivePolicy {
+                enabled: true,
+                max_removed_objects: Some(100),
+                max_removed_bytes: None,
+            },
+            audit_log,
+            commit_id: commit_id(),
+            commit_payload: COMMIT_PAYLOAD.to_vec(),
+            commit_history: Vec::new(),
+        }
+    }
+
+    /// The header stored in `header.bin`.
+    fn golden_header() -> Header {
+        let mut handle_table = HandleIdTable::new();
+        let chunk_handle_id = handle_table.next();
+        let object_map_handle_id = handle_table.next();
+
+        let chunk = Chunk {
+            size: chunk_data().len() as u32,
+            hash: chunk_hash(&chunk_data()),
+        };
+        let block_id = uuid!("3f5a1c2e-8b7d-4e6f-9a0b-1c2d3e4f5a6b").into();
+
+        let mut chunks = HashMap::new();
+        chunks.insert(
+            chunk,
+            ChunkInfo {
+                block_id,
+                references: [chunk_handle_id].into_iter().collect::<HashSet<_>>(),
+                compression: None,
+            },
+        );
+
+        let mut packs = HashMap::new();
+        packs.insert(
+            block_id,
+            vec![PackIndex {
+                id: uuid!("7e8f9a0b-1c2d-4e3f-8a5b-6c7d8e9f0a1b").into(),
+                offset: 0,
+                size: 75,
+            }],
+        );
+
+        let mut instances = HashMap::new();
+        instances.insert(
+            DEFAULT_INSTANCE,
+            InstanceInfo {
+                version_id: KeyRepo::<String>::VERSION_ID,
+                objects: ObjectHandle {
+                    id: object_map_handle_id,
+                    extents: vec![Extent::Chunk(chunk), Extent::Hole { size: 4096 }],
+                },
+                trash: None,
+                attributes: None,
+            },
+        );
+
+        Header {
+            chunks,
+            packs,
+            instances,
+            handle_table,
+            rechunk: None,
+            dictionaries: Vec::new(),
+        }
+    }
+
+    /// Encrypt `data` with the fixed `NONCE`, like the encrypted golden files.
+    fn encrypt_with_fixed_nonce(data: &[u8]) -> Vec<u8> {
+        use sodiumoxide::crypto::aead::xchacha20poly1305_ietf::{seal, Key, Nonce};
+
+        sodiumoxide::init().unwrap();
+        let nonce = Nonce::from_slice(&NONCE).unwrap();
+        let key = Key::from_slice(&MASTER_KEY).unwrap();
+        let mut output = NONCE.to_vec();
+        output.extend(seal(data, None, &nonce, &key));
+        output
+    }
+
+    #[test]
+    fn well_known_ids_are_unchanged() {
+        assert_that!(FORMAT_VERSION).is_equal_to(uuid!("44253e72-f08f-11eb-a2a3-a701701f8601"));
+        assert_that!(*DEFAULT_INSTANCE.as_ref())
+            .is_equal_to(uuid!("ea978302-bfd8-11ea-b92b-031a9ad75c07"));
+        assert_that!(*KeyRepo::<String>::VERSION_ID.as_ref())
+            .is_equal_to(uuid!("989a6a76-9d8b-46b7-9c05-d1c5e0d9471a"));
+    }
+
+    #[test]
+    fn version_matches_golden() {
+        let golden = include_bytes!("../../../tests/golden/version.bin");
+        assert_that!(encode_version()).is_equal_to(&golden[..]);
+        assert_that!(decode_version(golden)).is_ok();
+    }
+
+    #[test]
+    fn other_versions_are_unsupported() {
+        let other_version = uuid!("00000000-0000-0000-0000-000000000000");
+        assert!(matches!(
+            decode_version(other_version.as_bytes()),
+            Err(crate::Error::UnsupportedRepo)
+        ));
+        assert!(matches!(
+            decode_version(b"not a uuid"),
+            Err(crate::Error::Corrupt)
+        ));
+    }
+
+    #[test]
+    fn metadata_matches_golden() {
+        let golden = include_bytes!("../../../tests/golden/metadata.bin");
+        assert_that!(encode_metadata(&golden_metadata()).unwrap()).is_equal_to(golden.to_vec());
+        assert_that!(decode_metadata(golden).unwrap()).is_equal_to(golden_metadata());
+    }
+
+    #[test]
+    fn trailing_config_options_round_trip() {
+        let mut metadata = golden_metadata();
+        metadata.config.max_chunk_size = Some(4096);
+        let encoded = encode_metadata(&metadata).unwrap();
+        assert_that!(decode_metadata(&encoded).unwrap()).is_equal_to(&metadata);
+
+        metadata.config.min_chunk_size = Some(1024);
+        metadata.config.max_chunk_size = None;
+        let encoded = encode_metadata(&metadata).unwrap();
+        assert_that!(decode_metadata(&encoded).unwrap()).is_equal_to(&metadata);
+    }
+
+    #[test]
+    fn legacy_metadata_matches_golden() {
+        let golden = include_bytes!("../../../tests/golden/metadata-v0_14.bin");
+        let mut metadata = golden_metadata();
+        let mut config = RepoConfig::default();
+        config.chunking = Chunking::Zpaq { bits: 20 };
+        config.packing = Packing::Fixed(PACK_SIZE);
+        config.compression = lz4();
+        config.encryption = Encryption::XChaCha20Poly1305;
+        config.memory_limit = ResourceLimit::Interactive;
+        config.operations_limit = ResourceLimit::Interactive;
+        config.compatibility_target = Some(FormatVersion::V0_14);
+        metadata.config = config;
+        metadata.destructive_policy = DestructivePolicy::default();
+        metadata.audit_log = Vec::new();
+        metadata.commit_id = CommitId::default();
+        metadata.commit_payload = Vec::new();
+
+        assert_that!(encode_metadata(&metadata).unwrap()).is_equal_to(golden.to_vec());
+        assert_that!(decode_metadata(golden).unwrap()).is_equal_to(metadata);
+    }
+
+    #[test]
+    fn legacy_metadata_rejects_new_options() {
+        let mut metadata = golden_metadata();
+        metadata.config.compatibility_target = Some(FormatVersion::V0_14);
+        assert!(matches!(
+            encode_metadata(&metadata),
+            Err(crate::Error::Incompatible {
+                option: "max_header_size",
+                target: FormatVersion::V0_14,
+            })
+        ));
+
+        metadata.config = RepoConfig::default();
+        metadata.config.compatibility_target = Some(FormatVersion::V0_14);
+        assert!(matches!(
+            encode_metadata(&metadata),
+            Err(crate::Error::Incompatible {
+                option: "destructive_policy",
+                ..
+            })
+        ));
+
+        metadata.destructive_policy = DestructivePolicy::default();
+        assert!(matches!(
+            encode_metadata(&metadata),
+            Err(crate::Error::Incompatible {
+                option: "audit_log",
+                ..
+            })
+        ));
+
+        metadata.audit_log = Vec::new();
+        assert!(matches!(
+            encode_metadata(&metadata),
+            Err(crate::Error::Incompatible {
+                option: "commit_payload",
+                ..
+            })
+        ));
+
+        // The commit ID is dropped rather than rejected, since every repository has one.
+        metadata.commit_payload = Vec::new();
+        assert_that!(encode_metadata(&metadata)).is_ok();
+    }
+
+    #[test]
+    fn master_key_in_golden_metadata_can_be_decrypted() {
+        let metadata = golden_metadata();
+        let master_key = metadata.decrypt_master_key(PASSWORD).unwrap();
+        assert_that!(master_key.expose_secret()).is_equal_to(&MASTER_KEY.to_vec());
+    }
+
+    #[test]
+    fn derived_key_matches_golden() {
+        let golden = include_bytes!("../../../tests/golden/derived-key.bin");
+        let user_key = EncryptionKey::derive(
+            PASSWORD,
+            &KeySalt::from_bytes(SALT.to_vec()),
+            Encryption::XChaCha20Poly1305.key_size(),
+            ResourceLimit::Interactive,
+            ResourceLimit::Interactive,
+        );
+        assert_that!(user_key.expose_secret()).is_equal_to(&golden.to_vec());
+    }
+
+    #[test]
+    fn master_key_round_trips() {
+        let user_key = EncryptionKey::new(vec![0xaa; 32]);
+        let encoded = encode_master_key(&master_key(), &Encryption::XChaCha20Poly1305, &user_key);
+        let decoded =
+            decode_master_key(&encoded, &Encryption::XChaCha20Poly1305, &user_key).unwrap();
+        assert_that!(decoded.expose_secret()).is_equal_to(&MASTER_KEY.to_vec());
+
+        let wrong_key = EncryptionKey::new(vec![0xbb; 32]);
+        assert!(matches!(
+            decode_master_key(&encoded, &Encryption::XChaCha20Poly1305, &wrong_key),
+            Err(crate::Error::Password)
+        ));
+    }
+
+    #[test]
+    fn header_matches_golden() {
+        let golden = include_bytes!("../../../tests/golden/header.bin");
+        assert_that!(serialize_header(&golden_header(), None).unwrap())
+            .is_equal_to(golden.to_vec());
+
+        // `Header` doesn't implement `PartialEq`, so we check that it serializes back to the same
+        // bytes.
+        let header = deserialize_header(golden).unwrap();
+        assert_that!(serialize_header(&header, None).unwrap()).is_equal_to(golden.to_vec());
+    }
+
+    #[test]
+    fn legacy_header_matches_golden() {
+        let golden = include_bytes!("../../../tests/golden/header-v0_14.bin");
+        let target = Some(FormatVersion::V0_14);
+        assert_that!(serialize_header(&golden_header(), target).unwrap())
+            .is_equal_to(golden.to_vec());
+
+        let header = deserialize_header(golden).unwrap();
+        assert_that!(header.rechunk.is_none()).is_true();
+        assert_that!(serialize_header(&header, None).unwrap())
+            .is_equal_to(include_bytes!("../../../tests/golden/header.bin").to_vec());
+    }
+
+    #[test]
+    fn legacy_header_rejects_new_features() {
+        let target = Some(FormatVersion::V0_14);
+
+        let mut header = golden_header();
+        header.rechunk = Some(RechunkProgress::new(Chunking::Fixed { size: 256 }));
+        assert!(matches!(
+            serialize_header(&header, target),
+            Err(crate::Error::Incompatible {
+                option: "rechunk",
+                ..
+            })
+        ));
+
+        let mut header = golden_header();
+        let trash_id = header.handle_table.next();
+        for info in header.instances.values_mut() {
+            info.trash = Some(ObjectHandle {
+                id: trash_id,
+                extents: Vec::new(),
+            });
+        }
+        assert!(matches!(
+            serialize_header(&header, target),
+            Err(crate::Error::Incompatible {
+                option: "trash",
+                ..
+            })
+        ));
+        assert_that!(serialize_header(&header, None)).is_ok();
+
+        let mut header = golden_header();
+        let attributes_id = header.handle_table.next();
+        for info in header.instances.values_mut() {
+            info.attributes = Some(ObjectHandle {
+                id: attributes_id,
+                extents: Vec::new(),
+            });
+        }
+        assert!(matches!(
+            serialize_header(&header, target),
+            Err(crate::Error::Incompatible { option: "tags", .. })
+        ));
+        assert_that!(serialize_header(&header, None)).is_ok();
+    }
+
+    #[test]
+    fn chunk_hash_matches_golden() {
+        let golden = include_bytes!("../../../tests/golden/chunk-hash.bin");
+        assert_that!(chunk_hash(&chunk_data())).is_equal_to(*golden);
+    }
+
+    #[test]
+    fn unencoded_chunk_is_unchanged() {
+        let key = EncryptionKey::new(Vec::new());
+        let encoded = encode_chunk(
+            &chunk_data(),
+            &Compression::None,
+            &[],
+            &Encryption::None,
+            &key,
+            &no_pool(),
+        )
+        .unwrap();
+        assert_that!(encoded).is_equal_to(chunk_data());
+    }
+
+    #[test]
+    fn compressed_chunk_decodes_golden() {
+        let golden = include_bytes!("../../../tests/golden/chunk-lz4.bin");
+        let key = EncryptionKey::new(Vec::new());
+        let decoded =
+            decode_chunk(golden, &lz4(), &[], &Encryption::None, &key, &no_pool()).unwrap();
+        assert_that!(decoded).is_equal_to(chunk_data());
+    }
+
+    #[test]
+    fn encrypted_chunk_matches_golden() {
+        let golden = include_bytes!("../../../tests/golden/chunk-xchacha.bin");
+        let decoded = decode_chunk(
+            golden,
+            &Compression::None,
+            &[],
+            &Encryption::XChaCha20Poly1305,
+            &master_key(),
+            &no_pool(),
+        )
+        .unwrap();
+        assert_that!(decoded).is_equal_to(chunk_data());
+        assert_that!(encrypt_with_fixed_nonce(&chunk_data())).is_equal_to(golden.to_vec());
+    }
+
+    #[test]
+    fn compressed_and_encrypted_chunk_decodes_golden() {
+        let golden = include_bytes!("../../../tests/golden/chunk-lz4-xchacha.bin");
+        let decoded = decode_chunk(
+            golden,
+            &lz4(),
+            &[],
+            &Encryption::XChaCha20Poly1305,
+            &master_key(),
+            &no_pool(),
+        )
+        .unwrap();
+        assert_that!(decoded).is_equal_to(chunk_data());
+    }
+
+    #[test]
+    fn chunk_round_trips() {
+        let encoded = encode_chunk(
+            &chunk_data(),
+            &lz4(),
+            &[],
+            &Encryption::XChaCha20Poly1305,
+            &master_key(),
+            &no_pool(),
+        )
+        .unwrap();
+        let decoded = decode_chunk(
+            &encoded,
+            &lz4(),
+            &[],
+            &Encryption::XChaCha20Poly1305,
+            &master_key(),
+            &no_pool(),
+        )
+        .unwrap();
+        assert_that!(decoded).is_equal_to(chunk_data());
+    }
+
+    #[test]
+    fn chunk_round_trips_with_reused_buffers() {
+        let pool = BufferPool::new(1024 * 1024);
+        for _ in 0..2 {
+            let encoded = encode_chunk(
+                &chunk_data(),
+                &lz4(),
+                &[],
+                &Encryption::XChaCha20Poly1305,
+                &master_key(),
+                &pool,
+            )
+            .unwrap();
+            let decoded = decode_chunk(
+                &encoded,
+                &lz4(),
+                &[],
+                &Encryption::XChaCha20Poly1305,
+                &master_key(),
+                &pool,
+            )
+            .unwrap();
+            assert_that!(decoded).is_equal_to(chunk_data());
+            pool.release(encoded);
+            pool.release(decoded);
+        }
+        assert_that!(pool.stats().reused()).is_greater_than(0);
+    }
+
+    #[test]
+    fn zstd_chunk_round_trips() {
+        let encoded = encode_chunk(
+            &chunk_data(),
+            &zstd(),
+            &[],
+            &Encryption::XChaCha20Poly1305,
+            &master_key(),
+            &no_pool(),
+        )
+        .unwrap();
+        let decoded = decode_chunk(
+            &encoded,
+            &zstd(),
+            &[dictionary()],
+            &Encryption::XChaCha20Poly1305,
+            &master_key(),
+            &no_pool(),
+        )
+        .unwrap();
+        assert_that!(decoded).is_equal_to(chunk_data());
+    }
+
+    #[test]
+    fn zstd_chunk_round_trips_with_dictionary() {
+        let dictionaries = [dictionary()];
+        let encoded =
+            encode_packed_chunk(&chunk_data(), &zstd(), &dictionaries, &no_pool()).unwrap();
+        let decoded = decode_packed_chunk(&encoded, &zstd(), &dictionaries, &no_pool()).unwrap();
+        assert_that!(decoded).is_equal_to(chunk_data());
+
+        let mut reader = decode_chunk_reader(
+            Box::new(encoded.as_slice()),
+            &zstd(),
+            &dictionaries,
+            &Encryption::None,
+            &master_key(),
+        )
+        .unwrap();
+        let mut streamed = Vec::new();
+        reader.read_to_end(&mut streamed).unwrap();
+        assert_that!(streamed).is_equal_to(chunk_data());
+    }
+
+    #[test]
+    fn zstd_chunk_without_its_dictionary_is_invalid() {
+        let encoded =
+            encode_packed_chunk(&chunk_data(), &zstd(), &[dictionary()], &no_pool()).unwrap();
+        assert!(matches!(
+            decode_packed_chunk(&encoded, &zstd(), &[], &no_pool()),
+            Err(crate::Error::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn dictionaries_require_v0_15() {
+        let mut header = golden_header();
+        header.dictionaries.push(dictionary());
+        assert!(matches!(
+            serialize_header(&header, Some(FormatVersion::V0_14)),
+            Err(crate::Error::Incompatible {
+                option: "zstd_dictionary",
+                ..
+            })
+        ));
+
+        let serialized = serialize_header(&header, None).unwrap();
+        let deserialized = deserialize_header(&serialized).unwrap();
+        assert_that!(deserialized.dictionaries).is_equal_to(header.dictionaries);
+    }
+
+    #[test]
+    fn pack_decodes_golden() {
+        let golden = include_bytes!("../../../tests/golden/pack-xchacha.bin");
+        let pack = decode_pack(golden, &Encryption::XChaCha20Poly1305, &master_key()).unwrap();
+        assert_that!(pack.len()).is_equal_to(PACK_SIZE as usize);
+
+        let [first_size, second_size] = PACKED_SIZES;
+        let [first_data, second_data] = packed_data();
+        let first = decode_packed_chunk(&pack[..first_size], &lz4(), &[], &no_pool()).unwrap();
+        let second = decode_packed_chunk(
+            &pack[first_size..first_size + second_size],
+            &lz4(),
+            &[],
+            &no_pool(),
+        )
+        .unwrap();
+        let padding = &pack[first_size + second_size..];
+
+        assert_that!(first).is_equal_to(first_data);
+        assert_that!(second).is_equal_to(second_data);
+        assert_that!(padding.iter().all(|&byte| byte == 0)).is_true();
+    }
+
+    #[test]
+    fn pack_is_padded_to_pack_size() {
+        let key = EncryptionKey::new(Vec::new());
+        let encoded = encode_pack(b"data", PACK_SIZE, &Encryption::None, &key);
+        let mut expected = b"data".to_vec();
+        expected.resize(PACK_SIZE as usize, 0);
+        assert_that!(encoded).is_equal_to(expected);
+    }
+
+    #[test]
+    fn lock_decodes_golden() {
+        let golden = include_bytes!("../../../tests/golden/lock-xchacha.bin");
+        let context = decode_lock(golden, &Encryption::XChaCha20Poly1305, &master_key()).unwrap();
+        assert_that!(context).is_equal_to(LOCK_CONTEXT.to_vec());
+    }
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "fuzzing")]
+
+//! Entry points for fuzzing code paths which aren't reachable through the public API.
+//!
+//! This module is only available with the `fuzzing` feature. It is not part of the public API and
+//! may change at any time.
+
+use super::compression::Compression;
+use super::format;
+
+/// Deserialize a repository header from the given `data`, which has already been decoded.
+pub fn deserialize_header(data: &[u8]) -> crate::Result<()> {
+    format::deserialize_header(data).map(drop)
+}
+
+/// Deserialize repository metadata from the given `data`, which is the contents of a superblock.
+pub fn deserialize_metadata(data: &[u8]) -> crate::Result<()> {
+    format::decode_metadata(data).map(drop)
+}
+
+/// Decompress the given `data` using `compression`, reading at most `limit` bytes of output.
+pub fn decompress(compression: &Compression, data: &[u8], limit: usize) -> crate::Result<Vec<u8>> {
+    compression.decompress_limited(data, limit)
+}
+
+/// Decompress the given `data` using `compression` and its default size limit.
+pub fn decompress_default(compression: &Compression, data: &[u8]) -> crate::Result<Vec<u8>> {
+    compression.decompress(data)
+}
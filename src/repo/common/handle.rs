@@ -1,9 +1,11 @@
 use std::cmp::min;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::ops::Range;
 
+use cdchunking::ChunkerImpl;
 use serde::{Deserialize, Serialize};
 
+use super::chunking::IncrementalChunker;
 use super::metadata::RepoId;
 
 id_table! {
@@ -87,6 +89,36 @@ impl ObjectHandle {
             Extent::Hole { .. } => None,
         })
     }
+
+    /// Return an `ObjectStats` containing statistics about the object.
+    pub fn stats(&self) -> ObjectStats {
+        let mut current_position = 0u64;
+        let mut actual_size = 0u64;
+        let mut apparent_size = 0u64;
+        let mut chunks = 0usize;
+        let mut holes = Vec::new();
+
+        for extent in &self.extents {
+            match extent {
+                Extent::Chunk(_) => {
+                    actual_size += extent.size();
+                    chunks += 1;
+                }
+                Extent::Hole { .. } => {
+                    holes.push(current_position..(current_position + extent.size()));
+                }
+            }
+            current_position += extent.size();
+            apparent_size += extent.size();
+        }
+
+        ObjectStats {
+            apparent_size,
+            actual_size,
+            chunks,
+            holes,
+        }
+    }
 }
 
 /// A value that represents the identity of an object.
@@ -165,6 +197,9 @@ pub struct ContentId {
 /// The maximum number of bytes which will be read when comparing contents against a hole.
 const HOLE_BUFFER: usize = 4096;
 
+/// The number of bytes to read from a reader at once when chunking it for comparison.
+const MATCH_BUFFER: usize = 64 * 1024;
+
 impl ContentId {
     /// The size of the contents represented by this content ID in bytes.
     pub fn size(&self) -> u64 {
@@ -248,6 +283,48 @@ impl ContentId {
 
         Ok(true)
     }
+
+    /// Return whether chunking the data in `reader` with `chunker` produces the same chunks.
+    ///
+    /// This stops reading from `reader` as soon as it produces a chunk which doesn't match. A
+    /// hole never matches, because data read from `reader` is always chunked.
+    pub(super) fn matches_chunks(
+        &self,
+        chunker: Box<dyn ChunkerImpl + Send + Sync>,
+        mut reader: impl Read,
+    ) -> crate::Result<bool> {
+        let mut chunker = IncrementalChunker::new(chunker);
+        let mut expected = self.extents.iter();
+        let mut buffer = vec![0u8; MATCH_BUFFER];
+
+        loop {
+            let bytes_read = match reader.read(&mut buffer) {
+                Ok(bytes_read) => bytes_read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            };
+
+            if bytes_read == 0 {
+                chunker.flush()?;
+            } else {
+                chunker.write_all(&buffer[..bytes_read])?;
+            }
+
+            for data in chunker.chunks() {
+                match expected.next() {
+                    Some(Extent::Chunk(chunk))
+                        if chunk.size as usize == data.len() && chunk.hash == chunk_hash(&data) => {
+                    }
+                    _ => return Ok(false),
+                }
+            }
+
+            if bytes_read == 0 {
+                // Handle the case where `reader` is shorter than this content ID.
+                return Ok(expected.next().is_none());
+            }
+        }
+    }
 }
 
 /// Statistics about an [`Object`] or [`ReadOnlyObject`].
@@ -258,6 +335,7 @@ impl ContentId {
 pub struct ObjectStats {
     pub(super) apparent_size: u64,
     pub(super) actual_size: u64,
+    pub(super) chunks: usize,
     pub(super) holes: Vec<Range<u64>>,
 }
 
@@ -283,13 +361,45 @@ impl ObjectStats {
         self.actual_size
     }
 
+    /// The number of chunks which make up the object.
+    ///
+    /// Sparse holes are not stored as chunks, so they don't count towards this number.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks
+    }
+
     /// The locations of sparse holes in the object.
     ///
     /// This returns a slice of the ranges of bytes which are sparse holes created with
-    /// [`Object::set_len`].
+    /// [`Object::set_len`] or [`Object::punch_hole`].
     ///
     /// [`Object::set_len`]: crate::repo::Object::set_len
+    /// [`Object::punch_hole`]: crate::repo::Object::punch_hole
     pub fn holes(&self) -> &[Range<u64>] {
         &self.holes
     }
+
+    /// The locations of data in the object.
+    ///
+    /// This returns the ranges of bytes which are not sparse holes, in order. This is the
+    /// complement of [`holes`].
+    ///
+    /// [`holes`]: crate::repo::ObjectStats::holes
+    pub fn allocated_ranges(&self) -> Vec<Range<u64>> {
+        let mut ranges = Vec::new();
+        let mut current_position = 0u64;
+
+        for hole in &self.holes {
+            if hole.start > current_position {
+                ranges.push(current_position..hole.start);
+            }
+            current_position = hole.end;
+        }
+
+        if self.apparent_size > current_position {
+            ranges.push(current_position..self.apparent_size);
+        }
+
+        ranges
+    }
 }
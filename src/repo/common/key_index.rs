@@ -0,0 +1,101 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::{Bound, RangeBounds};
+use std::sync::Mutex;
+
+use super::key::Key;
+
+/// The state of a `KeyIndex` once it has been built.
+#[derive(Debug)]
+struct OrderedKeys<K> {
+    /// The keys of objects in the current instance in sorted order.
+    keys: BTreeSet<K>,
+
+    /// The keys which have been added or removed since the index was last brought up to date.
+    dirty: HashSet<K>,
+}
+
+/// An ordered index of the keys of objects in an instance.
+///
+/// Because not every `Key` is `Ord`, the index is not persisted. It is built the first time a
+/// range of keys is requested, and then kept up to date incrementally by recording which keys
+/// have changed since the last query. Until the index is built, changing keys costs nothing.
+#[derive(Debug)]
+pub struct KeyIndex<K> {
+    ordered: Mutex<Option<OrderedKeys<K>>>,
+}
+
+impl<K> Default for KeyIndex<K> {
+    fn default() -> Self {
+        Self {
+            ordered: Mutex::new(None),
+        }
+    }
+}
+
+impl<K: Key> KeyIndex<K> {
+    /// Record that an object with the given `key` may have been added or removed.
+    pub fn touch(&mut self, key: &K) {
+        if let Some(ordered) = self.ordered.get_mut().unwrap() {
+            ordered.dirty.insert(key.clone());
+        }
+    }
+
+    /// Discard the index so that it is rebuilt the next time it is needed.
+    ///
+    /// This should be called when the set of keys is replaced wholesale.
+    pub fn reset(&mut self) {
+        *self.ordered.get_mut().unwrap() = None;
+    }
+
+    /// Return the keys in `objects` which fall within `range` in sorted order.
+    ///
+    /// The returned references borrow from `objects`.
+    pub fn range<'a, Q, R, V>(&self, objects: &'a HashMap<K, V>, range: R) -> Vec<&'a K>
+    where
+        K: Ord + Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.with_keys(objects, |keys| {
+            keys.range::<Q, _>(range)
+                .map(|key| objects.get_key_value(key).unwrap().0)
+                .collect()
+        })
+    }
+
+    /// Return the keys in `objects` which start with `prefix` in sorted order.
+    ///
+    /// The returned references borrow from `objects`.
+    pub fn with_prefix<'a, V>(&self, objects: &'a HashMap<K, V>, prefix: &str) -> Vec<&'a K>
+    where
+        K: Ord + Borrow<str>,
+    {
+        self.with_keys(objects, |keys| {
+            keys.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+                .take_while(|key| Borrow::<str>::borrow(*key).starts_with(prefix))
+                .map(|key| objects.get_key_value(key).unwrap().0)
+                .collect()
+        })
+    }
+
+    /// Bring the index up to date with `objects` and pass the sorted keys to `f`.
+    fn with_keys<V, T>(&self, objects: &HashMap<K, V>, f: impl FnOnce(&BTreeSet<K>) -> T) -> T
+    where
+        K: Ord,
+    {
+        let mut guard = self.ordered.lock().unwrap();
+        let ordered = guard.get_or_insert_with(|| OrderedKeys {
+            keys: objects.keys().cloned().collect(),
+            dirty: HashSet::new(),
+        });
+        for key in ordered.dirty.drain() {
+            if objects.contains_key(&key) {
+                ordered.keys.insert(key);
+            } else {
+                ordered.keys.remove(&key);
+            }
+        }
+        f(&ordered.keys)
+    }
+}
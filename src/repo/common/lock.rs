@@ -6,6 +6,7 @@ use uuid::Uuid;
 use weak_table::WeakHashSet;
 
 use super::encryption::{Encryption, EncryptionKey};
+use super::format::{decode_lock, encode_lock};
 use crate::store::{BlockId, BlockKey, BlockType, DataStore};
 
 /// A lock acquired on a resource.
@@ -96,6 +97,7 @@ pub trait Unlock {
     /// loss.
     ///
     /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only, so it holds no lock.
     /// - `Error::Store`: An error occurred with the data store.
     ///
     /// [`OpenOptions::locking`]: crate::repo::OpenOptions::locking
@@ -139,7 +141,7 @@ pub fn lock_store<'a>(
                 .map_err(crate::Error::Store)?
                 .ok_or(crate::Error::Locked)?;
             let existing_lock_context =
-                encryption.decrypt(&encrypted_existing_lock_context, key)?;
+                decode_lock(&encrypted_existing_lock_context, encryption, key)?;
 
             // Invoke the lock handler with the existing lock's lock ID to see if it should be
             // removed.
@@ -159,7 +161,7 @@ pub fn lock_store<'a>(
     }
 
     // Acquire a lock on the repository.
-    let encrypted_current_lock_context = encryption.encrypt(context, key);
+    let encrypted_current_lock_context = encode_lock(context, encryption, key);
     store
         .write_block(
             BlockKey::Lock(current_lock_id),
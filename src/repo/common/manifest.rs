@@ -0,0 +1,301 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use super::chunking::Chunking;
+use super::handle::{Chunk, ChunkHash, Extent, ObjectHandle};
+use super::key::Key;
+use super::metadata::RepoId;
+use super::state::InstanceId;
+
+/// Which state of a repository a [`Manifest`] describes.
+///
+/// [`Manifest`]: crate::repo::Manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ManifestSource {
+    /// The state of the repository as of the last commit.
+    Committed,
+
+    /// The current state of the repository, including uncommitted changes.
+    Staged,
+}
+
+/// A fixed-size digest which identifies the contents of an object.
+///
+/// Unlike a [`ContentId`], this is cheap to store and compare, even for large objects. Two objects
+/// with the same contents have the same digest as long as they were written with the same
+/// chunking method, so digests from repositories with different chunking methods can't be
+/// compared.
+///
+/// [`ContentId`]: crate::repo::ContentId
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ContentDigest(ChunkHash);
+
+impl ContentDigest {
+    /// Compute the digest of an object made up of the given `extents`.
+    fn new(extents: &[Extent]) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        for extent in extents {
+            match extent {
+                Extent::Chunk(chunk) => {
+                    hasher.update(&[0]);
+                    hasher.update(&chunk.size.to_le_bytes());
+                    hasher.update(&chunk.hash);
+                }
+                Extent::Hole { size } => {
+                    hasher.update(&[1]);
+                    hasher.update(&size.to_le_bytes());
+                }
+            }
+        }
+        Self(hasher.finalize().into())
+    }
+
+    /// The bytes of the digest.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The digest as a lowercase hex string.
+    pub fn to_hex(&self) -> String {
+        blake3::Hash::from(self.0).to_hex().to_string()
+    }
+}
+
+/// An entry for an object in a [`Manifest`].
+///
+/// [`Manifest`]: crate::repo::Manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ManifestEntry {
+    /// The apparent size of the object in bytes, including any sparse holes.
+    pub size: u64,
+
+    /// The number of bytes of data in the object, not including any sparse holes.
+    pub actual_size: u64,
+
+    /// A digest of the contents of the object.
+    pub content: ContentDigest,
+}
+
+impl ManifestEntry {
+    /// Create an entry for the object with the given `handle`.
+    fn new(handle: &ObjectHandle) -> Self {
+        let actual_size = handle.chunks().map(|chunk| chunk.size as u64).sum();
+        Self {
+            size: handle.size(),
+            actual_size,
+            content: ContentDigest::new(&handle.extents),
+        }
+    }
+}
+
+/// A serializable snapshot of the objects in a repository.
+///
+/// This is created by [`KeyRepo::manifest`].
+///
+/// [`KeyRepo::manifest`]: crate::repo::key::KeyRepo::manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound(serialize = "K: Key", deserialize = "K: Key"))]
+pub struct Manifest<K: Key> {
+    repo_id: RepoId,
+    instance: InstanceId,
+    source: ManifestSource,
+    chunking: Chunking,
+    apparent_size: u64,
+    actual_size: u64,
+    // Maps can only be serialized with string keys in some formats, so we serialize the entries as
+    // a list of pairs instead.
+    #[serde(with = "entry_list")]
+    entries: HashMap<K, ManifestEntry>,
+}
+
+impl<K: Key> Manifest<K> {
+    /// The ID of the repository this manifest describes.
+    pub fn repo_id(&self) -> RepoId {
+        self.repo_id
+    }
+
+    /// The instance of the repository this manifest describes.
+    pub fn instance(&self) -> InstanceId {
+        self.instance
+    }
+
+    /// Whether this manifest describes the committed or staged state of the repository.
+    pub fn source(&self) -> ManifestSource {
+        self.source
+    }
+
+    /// The chunking method of the repository.
+    ///
+    /// The [`ContentDigest`] of an object depends on the chunking method.
+    ///
+    /// [`ContentDigest`]: crate::repo::ContentDigest
+    pub fn chunking(&self) -> &Chunking {
+        &self.chunking
+    }
+
+    /// The sum of the apparent sizes of all the objects in the manifest.
+    pub fn apparent_size(&self) -> u64 {
+        self.apparent_size
+    }
+
+    /// The number of bytes of data in the objects in the manifest, after deduplication.
+    pub fn actual_size(&self) -> u64 {
+        self.actual_size
+    }
+
+    /// The number of objects in the manifest.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no objects in the manifest.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the entry for the object with the given `key`, or `None` if there is none.
+    pub fn get<Q>(&self, key: &Q) -> Option<&ManifestEntry>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.get(key)
+    }
+
+    /// Return an iterator over the keys of the objects in the manifest and their entries.
+    pub fn entries(&self) -> impl Iterator<Item = (&K, &ManifestEntry)> {
+        self.entries.iter()
+    }
+
+    /// Return the changes needed to go from this manifest to `other`.
+    ///
+    /// An object is changed if its size or contents are different. Because contents are compared
+    /// using their [`ContentDigest`], every object with data is reported as changed if the two
+    /// manifests have different chunking methods.
+    ///
+    /// [`ContentDigest`]: crate::repo::ContentDigest
+    pub fn diff(&self, other: &Manifest<K>) -> ManifestDiff<K> {
+        let mut diff = ManifestDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+
+        for (key, entry) in &self.entries {
+            match other.entries.get(key) {
+                None => diff.removed.push(key.clone()),
+                Some(other_entry) if other_entry != entry => diff.changed.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+
+        for key in other.entries.keys() {
+            if !self.entries.contains_key(key) {
+                diff.added.push(key.clone());
+            }
+        }
+
+        diff
+    }
+}
+
+/// The differences between two [`Manifest`] values.
+///
+/// This is returned by [`Manifest::diff`].
+///
+/// [`Manifest`]: crate::repo::Manifest
+/// [`Manifest::diff`]: crate::repo::Manifest::diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ManifestDiff<K> {
+    /// The keys of objects which are only in the new manifest.
+    pub added: Vec<K>,
+
+    /// The keys of objects which are only in the old manifest.
+    pub removed: Vec<K>,
+
+    /// The keys of objects which are in both manifests but have different sizes or contents.
+    pub changed: Vec<K>,
+}
+
+impl<K> ManifestDiff<K> {
+    /// Whether the two manifests describe the same objects.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A builder for a `Manifest` which adds one object at a time.
+pub struct ManifestBuilder<K: Key> {
+    manifest: Manifest<K>,
+    chunks: HashSet<Chunk>,
+}
+
+impl<K: Key> ManifestBuilder<K> {
+    /// Start building an empty manifest.
+    pub fn new(
+        repo_id: RepoId,
+        instance: InstanceId,
+        source: ManifestSource,
+        chunking: Chunking,
+    ) -> Self {
+        Self {
+            manifest: Manifest {
+                repo_id,
+                instance,
+                source,
+                chunking,
+                apparent_size: 0,
+                actual_size: 0,
+                entries: HashMap::new(),
+            },
+            chunks: HashSet::new(),
+        }
+    }
+
+    /// Add the object with the given `key` and `handle` to the manifest.
+    pub fn insert(&mut self, key: K, handle: &ObjectHandle) {
+        for chunk in handle.chunks() {
+            if self.chunks.insert(chunk) {
+                self.manifest.actual_size += chunk.size as u64;
+            }
+        }
+        self.manifest.apparent_size += handle.size();
+        self.manifest
+            .entries
+            .insert(key, ManifestEntry::new(handle));
+    }
+
+    /// Return the finished manifest.
+    pub fn finish(self) -> Manifest<K> {
+        self.manifest
+    }
+}
+
+/// Serialize and deserialize a map of manifest entries as a list of pairs.
+mod entry_list {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{Key, ManifestEntry};
+
+    pub fn serialize<K: Key, S: Serializer>(
+        entries: &HashMap<K, ManifestEntry>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(entries.iter())
+    }
+
+    pub fn deserialize<'de, K: Key, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<K, ManifestEntry>, D::Error> {
+        Ok(Vec::<(K, ManifestEntry)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
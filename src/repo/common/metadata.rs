@@ -1,13 +1,20 @@
 use std::collections::HashMap;
+use std::time::SystemTime;
 
-use rmp_serde::from_read;
 use serde::{Deserialize, Serialize};
 
+use super::audit_log::{record_audit, AuditEntry, AuditOperation};
+use super::commit::{CommitId, CommitInfo};
+use super::compatibility::Capability;
+use super::compression::Dictionary;
 use super::config::RepoConfig;
-use super::encryption::{EncryptionKey, KeySalt};
+use super::destructive::DestructivePolicy;
+use super::encryption::{Encryption, EncryptionKey, KeySalt, ResourceLimit};
+use super::format::{decode_master_key, decode_metadata, encode_master_key};
 use super::handle::{Chunk, HandleIdTable};
+use super::rechunk::RechunkProgress;
 use super::state::{ChunkInfo, InstanceId, InstanceInfo, PackIndex};
-use crate::store::{BlockId, BlockKey, DataStore, OpenStore};
+use crate::store::{BlockId, BlockKey, DataStore, OpenStore, StoreCapabilities};
 
 /// The repository state which is persisted to the data store on each commit.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +30,14 @@ pub struct Header {
 
     /// The table of object handle IDs.
     pub handle_table: HandleIdTable,
+
+    /// The progress of an unfinished rechunk operation, if there is one.
+    #[serde(default)]
+    pub rechunk: Option<RechunkProgress>,
+
+    /// The Zstandard dictionaries trained for this repository, from oldest to newest.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dictionaries: Vec<Dictionary>,
 }
 
 /// Metadata for a repository.
@@ -42,6 +57,29 @@ pub struct RepoMetadata {
 
     /// The ID of the chunk which stores the repository header.
     pub header_id: BlockId,
+
+    /// The policy for authorizing destructive operations.
+    #[serde(default)]
+    pub destructive_policy: DestructivePolicy,
+
+    /// The log of security-relevant operations performed on the repository.
+    #[serde(default)]
+    pub audit_log: Vec<AuditEntry>,
+
+    /// The ID of the commit which wrote this metadata.
+    #[serde(default)]
+    pub commit_id: CommitId,
+
+    /// The application payload which was stored with the latest commit.
+    #[serde(default)]
+    pub commit_payload: Vec<u8>,
+
+    /// The latest commits of the repository, from oldest to newest.
+    ///
+    /// If the commit history is enabled, this includes the current commit followed by up to
+    /// `config.commit_history` previous commits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commit_history: Vec<CommitInfo>,
 }
 
 impl RepoMetadata {
@@ -49,7 +87,12 @@ impl RepoMetadata {
     ///
     /// # Errors
     /// - `Error::Password`: The password provided is invalid.
+    /// - `Error::Corrupt`: The salt or the master key is the wrong size.
     pub fn decrypt_master_key(&self, password: &[u8]) -> crate::Result<EncryptionKey> {
+        if !self.salt.is_valid() {
+            return Err(crate::Error::Corrupt);
+        }
+
         let user_key = EncryptionKey::derive(
             password,
             &self.salt,
@@ -57,21 +100,90 @@ impl RepoMetadata {
             self.config.memory_limit,
             self.config.operations_limit,
         );
-        Ok(EncryptionKey::new(
-            self.config
-                .encryption
-                .decrypt(&self.master_key, &user_key)
-                .map_err(|_| crate::Error::Password)?,
-        ))
+        decode_master_key(&self.master_key, &self.config.encryption, &user_key)
+    }
+
+    /// Encrypt the `master_key` with a key derived from `new_password` and record it in the audit
+    /// log as performed by `writer`.
+    ///
+    /// The `memory_limit` and `operations_limit` are the parameters of the key derivation
+    /// function. If encryption is disabled, this does nothing.
+    pub fn change_password(
+        &mut self,
+        master_key: &EncryptionKey,
+        new_password: &[u8],
+        memory_limit: ResourceLimit,
+        operations_limit: ResourceLimit,
+        writer: Option<String>,
+    ) {
+        if self.config.encryption == Encryption::None {
+            return;
+        }
+
+        let salt = KeySalt::generate();
+        let user_key = EncryptionKey::derive(
+            new_password,
+            &salt,
+            self.config.encryption.key_size(),
+            memory_limit,
+            operations_limit,
+        );
+
+        self.master_key = encode_master_key(master_key, &self.config.encryption, &user_key);
+        self.salt = salt;
+        self.config.memory_limit = memory_limit;
+        self.config.operations_limit = operations_limit;
+
+        self.record_audit(
+            AuditOperation::PasswordChanged {
+                memory_limit,
+                operations_limit,
+            },
+            writer,
+        );
     }
 }
 
 impl RepoMetadata {
-    /// Create a `RepoInfo` using the metadata in this struct.
-    pub fn to_info(&self) -> RepoInfo {
+    /// Append an entry for `operation` performed now by `writer` to the audit log.
+    ///
+    /// Nothing is recorded if the repository's compatibility target doesn't support an audit log.
+    pub fn record_audit(&mut self, operation: AuditOperation, writer: Option<String>) {
+        if Capability::AuditLog.is_supported_by(self.config.compatibility_target) {
+            record_audit(&mut self.audit_log, operation, writer);
+        }
+    }
+
+    /// Append the current commit to the commit history, discarding commits which are too old.
+    ///
+    /// The current commit must point to the header which was written for it.
+    pub fn record_commit(&mut self) {
+        let retained = self.config.commit_history as usize;
+        if retained == 0
+            || !Capability::CommitHistory.is_supported_by(self.config.compatibility_target)
+        {
+            self.commit_history.clear();
+            return;
+        }
+
+        self.commit_history.push(CommitInfo {
+            id: self.commit_id,
+            time: SystemTime::now(),
+            header_id: self.header_id,
+        });
+
+        // Keep the current commit and `retained` commits before it.
+        let excess = self.commit_history.len().saturating_sub(retained + 1);
+        self.commit_history.drain(..excess);
+    }
+
+    /// Create a `RepoInfo` using the metadata in this struct and the `capabilities` of the store.
+    pub fn to_info(&self, capabilities: StoreCapabilities) -> RepoInfo {
         RepoInfo {
             id: self.id,
             config: self.config.clone(),
+            commit_id: self.commit_id,
+            capabilities,
         }
     }
 }
@@ -86,10 +198,9 @@ pub fn peek_info_store(store: &mut impl DataStore) -> crate::Result<RepoInfo> {
         Some(data) => data,
         None => return Err(crate::Error::NotFound),
     };
-    let metadata: RepoMetadata =
-        from_read(serialized_metadata.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+    let metadata = decode_metadata(serialized_metadata.as_slice())?;
 
-    Ok(metadata.to_info())
+    Ok(metadata.to_info(store.probe()))
 }
 
 /// Return information about the repository in a data store without opening it.
@@ -110,6 +221,23 @@ pub fn peek_info(config: &impl OpenStore) -> crate::Result<RepoInfo> {
     peek_info_store(&mut store)
 }
 
+/// Return the ID of the latest commit of the repository in a data store without opening it.
+///
+/// This accepts the `config` used to open the data store. This doesn't require the password, so
+/// it can be used to cheaply check whether a repository has changed.
+///
+/// # Errors
+/// - `Error::NotFound`: There is no repository in the data store.
+/// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+/// - `Error::UnsupportedStore`: The data store is an unsupported format. This can happen if
+/// the serialized data format changed or if the storage represented by this value does not
+/// contain a valid data store.
+/// - `Error::Store`: An error occurred with the data store.
+/// - `Error::Io`: An I/O error occurred.
+pub fn peek_commit_id(config: &impl OpenStore) -> crate::Result<CommitId> {
+    Ok(peek_info(config)?.commit_id())
+}
+
 uuid_type! {
     /// A UUID which uniquely identifies a repository.
     ///
@@ -123,6 +251,8 @@ uuid_type! {
 pub struct RepoInfo {
     id: RepoId,
     config: RepoConfig,
+    commit_id: CommitId,
+    capabilities: StoreCapabilities,
 }
 
 impl RepoInfo {
@@ -135,6 +265,20 @@ impl RepoInfo {
     pub fn config(&self) -> &RepoConfig {
         &self.config
     }
+
+    /// The ID of the latest commit of this repository.
+    pub fn commit_id(&self) -> CommitId {
+        self.commit_id
+    }
+
+    /// The capabilities of the data store which backs this repository.
+    ///
+    /// See [`DataStore::probe`] for details.
+    ///
+    /// [`DataStore::probe`]: crate::store::DataStore::probe
+    pub fn store_capabilities(&self) -> StoreCapabilities {
+        self.capabilities
+    }
 }
 
 /// Statistics about a repository.
@@ -143,6 +287,8 @@ pub struct RepoStats {
     pub(super) apparent_size: u64,
     pub(super) actual_size: u64,
     pub(super) repo_size: u64,
+    pub(super) trash_size: u64,
+    pub(super) retained_size: u64,
 }
 
 impl RepoStats {
@@ -178,4 +324,34 @@ impl RepoStats {
     pub fn repo_size(&self) -> u64 {
         self.repo_size
     }
+
+    /// The apparent size of the objects in the trash for the current instance.
+    ///
+    /// This is the sum of the apparent sizes of all the objects which have been moved to the trash
+    /// in the current instance of the repository. Objects in the trash don't count towards the
+    /// [`apparent_size`] or [`actual_size`], but they do count towards the [`repo_size`].
+    ///
+    /// [`apparent_size`]: crate::repo::RepoStats::apparent_size
+    /// [`actual_size`]: crate::repo::RepoStats::actual_size
+    /// [`repo_size`]: crate::repo::RepoStats::repo_size
+    pub fn trash_size(&self) -> u64 {
+        self.trash_size
+    }
+
+    /// The number of bytes in the data store which are awaiting the expiry of a retention period.
+    ///
+    /// This is the size of the unreferenced blocks which [`Commit::clean`] could not remove
+    /// because the data store's [`retention`] period has not passed since they were written. These
+    /// blocks are removed by a later call to `clean` once their retention period expires. Unlike
+    /// the other sizes, this is the number of bytes stored in the backing data store, and it does
+    /// not count towards the [`repo_size`].
+    ///
+    /// This is updated each time `clean` is called, so it is zero until the repository is cleaned.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    /// [`retention`]: crate::store::DataStore::retention
+    /// [`repo_size`]: crate::repo::RepoStats::repo_size
+    pub fn retained_size(&self) -> u64 {
+        self.retained_size
+    }
 }
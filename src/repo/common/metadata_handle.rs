@@ -0,0 +1,217 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Mutex;
+
+use crate::diagnostics::Registration;
+use crate::store::{BlockId, BlockKey, DataStore};
+
+use super::audit_log::{append_audit, AuditEntry};
+use super::encryption::{EncryptionKey, ResourceLimit};
+use super::format::{decode_lock, decode_metadata, encode_lock, encode_metadata};
+use super::lock::{unlock_store, Unlock};
+use super::metadata::{RepoInfo, RepoMetadata};
+
+/// A handle for performing maintenance on a repository's metadata.
+///
+/// This is returned by [`OpenOptions::open_metadata`]. Unlike a repository, this handle never
+/// reads the repository header, so it only supports operations which touch the repository's
+/// metadata, like changing its password. Changes made through this handle do not take effect until
+/// [`commit_metadata`] is called.
+///
+/// This handle holds a lock on the repository, which is released when it is dropped.
+///
+/// [`OpenOptions::open_metadata`]: crate::repo::OpenOptions::open_metadata
+/// [`commit_metadata`]: crate::repo::MetadataHandle::commit_metadata
+pub struct MetadataHandle {
+    /// The data store which backs the repository.
+    store: Mutex<Box<dyn DataStore>>,
+
+    /// The metadata for the repository, including any uncommitted changes.
+    metadata: RepoMetadata,
+
+    /// The master encryption key for the repository.
+    master_key: EncryptionKey,
+
+    /// The `BlockId` of the key which stores the lock on the repository.
+    lock_id: BlockId,
+
+    /// The entry for this repository in the table of open repositories.
+    registration: Registration,
+
+    /// The writer ID to record in audit log entries.
+    audit_writer: Option<String>,
+
+    /// The number of entries in the audit log which have already been committed.
+    committed_audit_len: usize,
+}
+
+impl MetadataHandle {
+    /// Create a new `MetadataHandle` for a repository which has already been locked.
+    pub(super) fn new(
+        store: Box<dyn DataStore>,
+        metadata: RepoMetadata,
+        master_key: EncryptionKey,
+        lock_id: BlockId,
+        registration: Registration,
+        audit_writer: Option<String>,
+    ) -> Self {
+        let committed_audit_len = metadata.audit_log.len();
+        Self {
+            store: Mutex::new(store),
+            metadata,
+            master_key,
+            lock_id,
+            registration,
+            audit_writer,
+            committed_audit_len,
+        }
+    }
+
+    /// Change the password for this repository.
+    ///
+    /// This replaces the existing password with `new_password`. This also accepts the
+    /// `memory_limit` and the `operations_limit`, which affect the amount of memory and the number
+    /// of computations respectively which will be used by the key derivation function. To only
+    /// change the parameters of the key derivation function, pass the existing password.
+    ///
+    /// Changing the password does not require re-encrypting any data. The change does not take
+    /// effect until [`commit_metadata`] is called.
+    ///
+    /// If encryption is disabled, this method does nothing.
+    ///
+    /// [`commit_metadata`]: crate::repo::MetadataHandle::commit_metadata
+    pub fn change_password(
+        &mut self,
+        new_password: &[u8],
+        memory_limit: ResourceLimit,
+        operations_limit: ResourceLimit,
+    ) {
+        self.metadata.change_password(
+            &self.master_key,
+            new_password,
+            memory_limit,
+            operations_limit,
+            self.audit_writer.clone(),
+        );
+    }
+
+    /// Atomically write the changes made through this handle to the data store.
+    ///
+    /// This only writes the repository's metadata. The metadata in the data store is read again
+    /// immediately before it is written, and only the fields which this handle can change are
+    /// replaced. This means that committing the metadata never replaces the header of the
+    /// repository, even if another client committed changes to the repository in the meantime.
+    /// Audit log entries recorded through this handle are appended to the audit log in the data
+    /// store.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: The repository no longer exists in the data store.
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::Store`: An error occurred with the data store.
+    pub fn commit_metadata(&mut self) -> crate::Result<()> {
+        let mut store = self.store.lock().unwrap();
+
+        let serialized_metadata = store
+            .read_block(BlockKey::Super)
+            .map_err(crate::Error::Store)?
+            .ok_or(crate::Error::NotFound)?;
+        let mut current_metadata = decode_metadata(serialized_metadata.as_slice())?;
+
+        // Keep everything from the data store except the fields which this handle can change, so
+        // we don't undo changes which another client committed since this handle was opened.
+        current_metadata.salt = self.metadata.salt.clone();
+        current_metadata.master_key = self.metadata.master_key.clone();
+        current_metadata.config.memory_limit = self.metadata.config.memory_limit;
+        current_metadata.config.operations_limit = self.metadata.config.operations_limit;
+
+        // Append the audit log entries recorded through this handle to the log in the data store.
+        let pending = self.metadata.audit_log.split_off(self.committed_audit_len);
+        self.metadata = current_metadata;
+        self.committed_audit_len = self.metadata.audit_log.len();
+        for entry in pending {
+            append_audit(
+                &mut self.metadata.audit_log,
+                entry.operation,
+                entry.time,
+                entry.writer,
+            );
+        }
+
+        let serialized_metadata = encode_metadata(&self.metadata)?;
+        store
+            .write_block(BlockKey::Super, &serialized_metadata)
+            .map_err(crate::Error::Store)
+    }
+
+    /// Return information about the repository.
+    ///
+    /// This includes any uncommitted changes made through this handle.
+    pub fn info(&self) -> RepoInfo {
+        self.metadata.to_info(self.store.lock().unwrap().probe())
+    }
+
+    /// Return the audit log of security-relevant operations performed on the repository.
+    ///
+    /// This includes any uncommitted entries recorded through this handle. See
+    /// [`KeyRepo::audit_log`] for details.
+    ///
+    /// [`KeyRepo::audit_log`]: crate::repo::key::KeyRepo::audit_log
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.metadata.audit_log.clone()
+    }
+}
+
+impl Unlock for MetadataHandle {
+    fn unlock(&self) -> crate::Result<()> {
+        let mut store = self.store.lock().unwrap();
+        unlock_store(&mut *store, self.lock_id)?;
+        self.registration.unlocked();
+        Ok(())
+    }
+
+    fn is_locked(&self) -> crate::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        store
+            .read_block(BlockKey::Lock(self.lock_id))
+            .map_err(crate::Error::Store)
+            .map(|result| result.is_some())
+    }
+
+    fn context(&self) -> crate::Result<Vec<u8>> {
+        let mut store = self.store.lock().unwrap();
+        let encrypted_context = store
+            .read_block(BlockKey::Lock(self.lock_id))
+            .map_err(crate::Error::Store)?
+            .ok_or(crate::Error::NotLocked)?;
+        decode_lock(
+            &encrypted_context,
+            &self.metadata.config.encryption,
+            &self.master_key,
+        )
+    }
+
+    fn update_context(&self, context: &[u8]) -> crate::Result<()> {
+        let mut store = self.store.lock().unwrap();
+        let encrypted_context =
+            encode_lock(context, &self.metadata.config.encryption, &self.master_key);
+        store
+            .write_block(BlockKey::Lock(self.lock_id), &encrypted_context)
+            .map_err(crate::Error::Store)
+    }
+}
+
+impl Drop for MetadataHandle {
+    fn drop(&mut self) {
+        // Attempt to release the lock on the repository. This may fail.
+        let mut store = self.store.lock().unwrap();
+        unlock_store(&mut *store, self.lock_id).ok();
+    }
+}
+
+impl Debug for MetadataHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetadataHandle")
+            .field("metadata", &self.metadata)
+            .field("lock_id", &self.lock_id)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,88 @@
+use crate::store::{BlockId, BlockKey, BlockType, DataStore};
+
+/// The progress of moving a repository to a new data store.
+///
+/// This is passed to the progress hook of [`KeyRepo::migrate_store`] after each block is copied.
+///
+/// [`KeyRepo::migrate_store`]: crate::repo::key::KeyRepo::migrate_store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MigrateProgress {
+    /// The number of blocks which have been copied so far.
+    pub blocks: u64,
+
+    /// The number of bytes which have been copied so far.
+    pub bytes: u64,
+
+    /// The total number of blocks to copy.
+    pub total_blocks: u64,
+}
+
+/// Copy every block of the repository in `source` to `target`.
+///
+/// This copies every data block and header, the lock with the given `lock_id`, the version block,
+/// and the superblock. Other locks are not copied. The superblock is copied last so that `target`
+/// doesn't contain a repository which can be opened until every other block has been copied.
+///
+/// # Errors
+/// - `Error::AlreadyExists`: `target` already contains a repository.
+/// - `Error::Cancelled`: The `progress` hook cancelled the migration.
+/// - `Error::Corrupt`: A block which the repository needs is missing from `source`.
+/// - `Error::Store`: An error occurred with either data store.
+pub fn copy_blocks(
+    source: &mut impl DataStore,
+    target: &mut impl DataStore,
+    lock_id: Option<BlockId>,
+    mut progress: impl FnMut(MigrateProgress) -> bool,
+) -> crate::Result<()> {
+    if target
+        .read_block(BlockKey::Super)
+        .map_err(crate::Error::Store)?
+        .is_some()
+    {
+        return Err(crate::Error::AlreadyExists);
+    }
+
+    let mut keys = Vec::new();
+    keys.extend(
+        source
+            .list_blocks(BlockType::Data)
+            .map_err(crate::Error::Store)?
+            .into_iter()
+            .map(BlockKey::Data),
+    );
+    keys.extend(
+        source
+            .list_blocks(BlockType::Header)
+            .map_err(crate::Error::Store)?
+            .into_iter()
+            .map(BlockKey::Header),
+    );
+    keys.extend(lock_id.map(BlockKey::Lock));
+    keys.push(BlockKey::Version);
+    keys.push(BlockKey::Super);
+
+    let mut current = MigrateProgress {
+        blocks: 0,
+        bytes: 0,
+        total_blocks: keys.len() as u64,
+    };
+
+    for key in keys {
+        let data = source
+            .read_block(key)
+            .map_err(crate::Error::Store)?
+            .ok_or(crate::Error::Corrupt)?;
+        target
+            .write_block(key, &data)
+            .map_err(crate::Error::Store)?;
+
+        current.blocks += 1;
+        current.bytes += data.len() as u64;
+        if !progress(current) {
+            return Err(crate::Error::Cancelled);
+        }
+    }
+
+    Ok(())
+}
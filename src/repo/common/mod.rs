@@ -1,35 +1,74 @@
+pub use self::audit::{audit_encryption, EncryptionAudit, SuspectBlock, SuspectReason};
+pub use self::audit_log::{AuditEntry, AuditOperation};
+pub use self::batch::Batch;
+pub use self::buffer_pool::{BufferPool, PoolStats};
+pub use self::chunk_cache::{CacheStats, ChunkCache};
 pub use self::chunking::Chunking;
-pub use self::commit::Commit;
+pub use self::commit::{Commit, CommitId, CommitInfo, CommitReport};
+pub use self::compatibility::FormatVersion;
 pub use self::compression::Compression;
 pub use self::config::RepoConfig;
+pub use self::destructive::{DestructivePolicy, DestructiveScope};
 pub use self::encryption::{Encryption, ResourceLimit};
+pub use self::entry::{Entry, OccupiedEntry, VacantEntry};
+#[cfg(feature = "export")]
+pub use self::export::{ExportOptions, ExportProgress};
 pub use self::handle::{ContentId, ObjectId, ObjectStats};
 pub use self::key::{Key, Keys};
 pub use self::lock::Unlock;
-pub use self::metadata::{peek_info, RepoId, RepoInfo, RepoStats};
+pub use self::manifest::{ContentDigest, Manifest, ManifestDiff, ManifestEntry, ManifestSource};
+pub use self::metadata::{peek_commit_id, peek_info, RepoId, RepoInfo, RepoStats};
+pub use self::metadata_handle::MetadataHandle;
+pub use self::migrate::MigrateProgress;
 pub use self::object::{Object, ReadOnlyObject};
+pub use self::object_options::ObjectOptions;
 pub use self::open_options::{OpenMode, OpenOptions, DEFAULT_INSTANCE};
 pub use self::open_repo::{OpenRepo, SwitchInstance, VersionId};
 pub use self::packing::Packing;
+pub use self::raw_key::RawKey;
+pub use self::rechunk::{RechunkOptions, RechunkReport};
 pub use self::repository::KeyRepo;
 pub use self::savepoint::{Restore, RestoreSavepoint, Savepoint};
+pub use self::snapshot::SnapshotId;
 pub use self::state::InstanceId;
 
+mod attributes;
+mod audit;
+mod audit_log;
+mod batch;
+mod buffer_pool;
+mod chunk_cache;
 mod chunk_store;
 mod chunking;
 mod commit;
+mod compatibility;
 mod compression;
 mod config;
+mod destructive;
 mod encryption;
+mod entry;
+#[cfg(feature = "export")]
+pub(crate) mod export;
+mod format;
+pub mod fuzzing;
 mod handle;
 mod key;
+mod key_index;
 mod lock;
+mod manifest;
 mod metadata;
+mod metadata_handle;
+mod migrate;
 mod object;
+mod object_options;
 mod object_store;
 mod open_options;
 mod open_repo;
 mod packing;
+mod raw_key;
+mod rechunk;
 mod repository;
 mod savepoint;
+mod snapshot;
 mod state;
+mod trash;
@@ -1,13 +1,15 @@
 use std::convert::TryFrom;
 use std::fmt::Debug;
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{self, IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, RwLock, Weak};
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use static_assertions::assert_impl_all;
 
+use super::chunk_store::StoreState;
 use super::handle::{ContentId, ObjectHandle, ObjectId, ObjectStats};
+use super::object_options::ObjectOptions;
 use super::object_store::ObjectStore;
 use super::state::{ObjectState, RepoState};
 
@@ -32,6 +34,16 @@ use super::state::{ObjectState, RepoState};
 /// committed. You can use [`object_id`] to determine if two `Object` or [`ReadOnlyObject`]
 /// instances refer to the same underlying object.
 ///
+/// # Concurrency
+///
+/// `Object` and [`ReadOnlyObject`] are `Send` and `Sync`, and each instance has its own seek
+/// position and read buffer. Reading only takes a shared lock on the repository and the object,
+/// so instances for the same or different objects can be read from separate threads in parallel.
+/// Decoding, decompressing, and decrypting chunks happen in parallel; only the calls into the
+/// [`DataStore`] are serialized, since a data store requires exclusive access. Writing to an object
+/// takes an exclusive lock on the repository, so writes are not performed in parallel with other
+/// reads or writes.
+///
 /// # Invalidation
 ///
 /// An object can be invalidated, in which case methods of `Object` and [`ReadOnlyObject`] will
@@ -67,12 +79,19 @@ use super::state::{ObjectState, RepoState};
 /// Because `Object` internally buffers data when reading, there's no need to use a buffered reader
 /// like `BufReader`.
 ///
+/// An object normally buffers the whole chunk it is reading from. Chunks larger than 16 MiB are
+/// instead buffered 16 MiB at a time when the repository uses no packing, compression, or
+/// encryption and [`RepoConfig::verify_reads`] is disabled. Otherwise, the whole chunk must be
+/// decoded before any of it can be read.
+///
 /// # Errors
 ///
 /// The methods of `Read`, `Write`, and `Seek` return `io::Result`, but the returned `io::Error` can
 /// be converted `Into` a [`crate::Error`] to be consistent with the rest of the library.
 ///
 /// [`commit`]: crate::repo::Object::commit
+/// [`DataStore`]: crate::store::DataStore
+/// [`RepoConfig::verify_reads`]: crate::repo::RepoConfig::verify_reads
 /// [`Commit::clean`]: crate::repo::Commit::clean
 /// [`Error::TransactionInProgress`]: crate::Error::TransactionInProgress
 /// [`ReadOnlyObject`]: crate::repo::ReadOnlyObject
@@ -103,7 +122,30 @@ impl Object {
         handle: &Arc<RwLock<ObjectHandle>>,
     ) -> Self {
         let metadata = &repo_state.read().unwrap().metadata;
-        let object_state = ObjectState::new(metadata.config.chunking.to_chunker());
+        let object_state = ObjectState::new(metadata.config.to_chunker());
+        Self {
+            repo_state: Arc::downgrade(repo_state),
+            handle: Arc::downgrade(handle),
+            object_state,
+        }
+    }
+
+    /// Create a new `Object` which writes data using the given `options`.
+    pub(super) fn with_options(
+        repo_state: &Arc<RwLock<RepoState>>,
+        handle: &Arc<RwLock<ObjectHandle>>,
+        options: &ObjectOptions,
+    ) -> Self {
+        let config = &repo_state.read().unwrap().metadata.config;
+        let chunker = match options.chunking_method() {
+            Some(chunking) => {
+                chunking.to_bounded_chunker(config.min_chunk_size, config.max_chunk_size)
+            }
+            None => config.to_chunker(),
+        };
+        let mut object_state = ObjectState::new(chunker);
+        object_state.store_state =
+            StoreState::with_compression(options.compression_method().cloned());
         Self {
             repo_state: Arc::downgrade(repo_state),
             handle: Arc::downgrade(handle),
@@ -151,6 +193,51 @@ impl Object {
             .content_id()
     }
 
+    /// Return whether the data in `reader` is the same as the contents of this object.
+    ///
+    /// This chunks the data in `reader` the same way it would be chunked if it were written to this
+    /// object and compares the checksums of the chunks against the chunks which make up the object.
+    /// This never reads, decrypts, or decompresses data from the data store, and it never writes
+    /// anything to the data store, so it's much cheaper than writing the data to the object to see
+    /// if its contents change. This makes it useful for skipping data which has already been
+    /// stored, such as files which haven't changed since they were last archived.
+    ///
+    /// This stops reading from `reader` as soon as it finds data which doesn't match, so `reader`
+    /// may not be read in its entirety.
+    ///
+    /// This may return `false` for data which is the same as the contents of this object if the
+    /// object wasn't written in a single pass from start to finish, because its chunk boundaries
+    /// may differ from the ones `reader` would produce. Like with [`ContentId`], a sparse hole
+    /// created with [`set_len`] never matches data read from `reader`, even if it contains only
+    /// null bytes. To compare contents without these limitations, use
+    /// [`ContentId::compare_contents`].
+    ///
+    /// The data in `reader` is always chunked using the repository's chunking method, because the
+    /// chunking method an object was written with isn't stored. This means an object written with
+    /// a different chunking method using [`insert_with_options`] won't match `reader` unless its
+    /// chunk boundaries happen to be the same.
+    ///
+    /// # Errors
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`ContentId`]: crate::repo::ContentId
+    /// [`set_len`]: crate::repo::Object::set_len
+    /// [`ContentId::compare_contents`]: crate::repo::ContentId::compare_contents
+    /// [`insert_with_options`]: crate::repo::key::KeyRepo::insert_with_options
+    pub fn matches_reader(&mut self, reader: impl Read) -> crate::Result<bool> {
+        // Don't hold the locks on the repository while reading from `reader`, since that could
+        // take a long time.
+        let (content_id, chunker) = {
+            let store = ObjectStore::new(&self.repo_state, &self.handle)?;
+            let guard = store.info_guard(&self.object_state);
+            let info = guard.info();
+            (info.content_id()?, info.chunker())
+        };
+        content_id.matches_chunks(chunker, reader)
+    }
+
     /// Return statistics about the object.
     ///
     /// The returned `ObjectStats` represents the contents of the object at the time this method was
@@ -195,6 +282,7 @@ impl Object {
     /// This method starts a new transaction and commits the transaction before it returns.
     ///
     /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
     /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
     /// - `Error::InvalidObject`: The object has been invalidated.
     /// - `Error::InvalidData`: Ciphertext verification failed.
@@ -207,6 +295,187 @@ impl Object {
             .set_len(size)
     }
 
+    /// Replace `len` bytes of the object starting at `offset` with a sparse hole.
+    ///
+    /// The bytes in the hole will read as null bytes, but no space is used in the backing data
+    /// store to store them. Any part of the range which is past the end of the object is ignored,
+    /// so this never changes the size of the object. The seek position is not changed.
+    ///
+    /// The locations of holes and data in the object can be found with [`ObjectStats::holes`] and
+    /// [`ObjectStats::allocated_ranges`].
+    ///
+    /// This method starts a new transaction and commits the transaction before it returns.
+    ///
+    /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`ObjectStats::holes`]: crate::repo::ObjectStats::holes
+    /// [`ObjectStats::allocated_ranges`]: crate::repo::ObjectStats::allocated_ranges
+    pub fn punch_hole(&mut self, offset: u64, len: u64) -> crate::Result<()> {
+        ObjectStore::new(&self.repo_state, &self.handle)?
+            .writer_guard(&mut self.object_state)
+            .writer()
+            .punch_hole(offset, len)
+    }
+
+    /// Write the data from `reader` to the object at the current position.
+    ///
+    /// This reads from `reader` until it reaches EOF or, if `len` is `Some`, until `len` bytes have
+    /// been read, and returns the number of bytes written. This is like [`io::copy`], except it
+    /// reads data in pieces sized for the repository's [`Chunking`], which avoids extra copying for
+    /// large objects.
+    ///
+    /// Like writing to the object via `Write`, this starts a new transaction if one isn't already in
+    /// progress, and changes aren't visible until [`commit`] is called. If this returns `Err`, the
+    /// data which was already written is kept in the transaction.
+    ///
+    /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
+    /// - `Error::TransactionInProgress`: A transaction is in progress for another instance of
+    /// this object.
+    /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred, including while reading from `reader`.
+    ///
+    /// [`io::copy`]: std::io::copy
+    /// [`Chunking`]: crate::repo::Chunking
+    /// [`commit`]: crate::repo::Object::commit
+    pub fn write_from<R: Read>(&mut self, reader: R, len: Option<u64>) -> crate::Result<u64> {
+        let buffer_size = self.stream_buffer_size()?;
+        let mut reader = reader.take(len.unwrap_or(u64::MAX));
+        let mut buffer = vec![0u8; buffer_size];
+        let mut bytes_written = 0u64;
+
+        loop {
+            let bytes_read = match reader.read(&mut buffer) {
+                Ok(0) => return Ok(bytes_written),
+                Ok(bytes_read) => bytes_read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            };
+
+            // Reading from `reader` may be slow, so we only lock the repository while writing.
+            ObjectStore::new(&self.repo_state, &self.handle)?
+                .writer_guard(&mut self.object_state)
+                .writer()
+                .write_all(&buffer[..bytes_read])?;
+            bytes_written += bytes_read as u64;
+        }
+    }
+
+    /// Read the data in the object from the current position to the end into `writer`.
+    ///
+    /// This returns the number of bytes written to `writer`. This is like [`io::copy`], except it
+    /// reads data in pieces sized for the repository's [`Chunking`], so throughput doesn't depend
+    /// on choosing a buffer size.
+    ///
+    /// # Errors
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred, including while writing to `writer`.
+    ///
+    /// [`io::copy`]: std::io::copy
+    /// [`Chunking`]: crate::repo::Chunking
+    pub fn read_into<W: Write>(&mut self, mut writer: W) -> crate::Result<u64> {
+        let buffer_size = self.stream_buffer_size()?;
+        let mut buffer = vec![0u8; buffer_size];
+        let mut bytes_written = 0u64;
+
+        loop {
+            // Writing to `writer` may be slow, so we only lock the repository while reading.
+            let bytes_read = ObjectStore::new(&self.repo_state, &self.handle)?
+                .reader_guard(&mut self.object_state)
+                .reader()
+                .read(&mut buffer)?;
+            if bytes_read == 0 {
+                return Ok(bytes_written);
+            }
+            writer.write_all(&buffer[..bytes_read])?;
+            bytes_written += bytes_read as u64;
+        }
+    }
+
+    /// Read data from the object starting at `offset` into `buf`.
+    ///
+    /// This returns the number of bytes read, which may be less than the length of `buf`. It
+    /// returns `0` if `offset` is at or past the end of the object. Unlike reading via `Read`, this
+    /// does not change the seek position of the object.
+    ///
+    /// # Errors
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> crate::Result<usize> {
+        if self.object_state.transaction_lock.is_some() {
+            return Err(crate::Error::TransactionInProgress);
+        }
+
+        // Read using a separate seek position and read buffer so that the state of this object
+        // is left untouched.
+        let store = ObjectStore::new(&self.repo_state, &self.handle)?;
+        let mut object_state = store.new_object_state();
+        let mut guard = store.reader_guard(&mut object_state);
+        let mut reader = guard.reader();
+
+        reader.seek(SeekFrom::Start(offset))?;
+        Ok(reader.read(buf)?)
+    }
+
+    /// Write all of `buf` to the object starting at `offset`.
+    ///
+    /// If `offset` is past the end of the object, the object is first extended to `offset` with a
+    /// sparse hole, like [`set_len`]. Unlike writing via `Write`, this does not change the seek
+    /// position of the object.
+    ///
+    /// This method starts a new transaction and commits the transaction before it returns. If
+    /// this returns `Err`, the data which was already written is kept in the transaction.
+    ///
+    /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`set_len`]: crate::repo::Object::set_len
+    pub fn write_at(&mut self, offset: u64, buf: &[u8]) -> crate::Result<()> {
+        let store = ObjectStore::new(&self.repo_state, &self.handle)?;
+        let mut guard = store.writer_guard(&mut self.object_state);
+        let mut writer = guard.writer();
+
+        let original_position = writer.seek(SeekFrom::Current(0))?;
+        if offset > writer.seek(SeekFrom::End(0))? {
+            writer.set_len(offset)?;
+        }
+        writer.seek(SeekFrom::Start(offset))?;
+        writer.write_all(buf)?;
+        writer.commit()?;
+        writer.seek(SeekFrom::Start(original_position))?;
+
+        Ok(())
+    }
+
+    /// Return the size of the buffer to use when streaming data into or out of this object.
+    fn stream_buffer_size(&self) -> crate::Result<usize> {
+        let repo_state = self
+            .repo_state
+            .upgrade()
+            .ok_or(crate::Error::InvalidObject)?;
+        let state = repo_state.read().unwrap();
+        Ok(state.metadata.config.chunking.stream_buffer_size())
+    }
+
     /// Serialize the given `value` and write it to the object.
     ///
     /// This is a convenience function that serializes the `value` using a space-efficient binary
@@ -217,6 +486,7 @@ impl Object {
     ///
     /// # Errors
     /// - `Error::Serialize`: The given value could not be serialized.
+    /// - `Error::ReadOnly`: The repository was opened read-only.
     /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
     /// - `Error::InvalidObject`: The object has been invalidated.
     /// - `Error::InvalidData`: Ciphertext verification failed.
@@ -275,6 +545,35 @@ impl Object {
             .commit()
     }
 
+    /// Write buffered data to the data store and ask the data store to durably persist it.
+    ///
+    /// Data written via `Write` is buffered in memory until a chunk boundary is reached. Calling
+    /// `Write::flush` writes the buffered data to the data store as a chunk without committing the
+    /// transaction, which bounds the amount of data held in memory while streaming a large object.
+    /// This method does the same and then calls [`DataStore::sync`] so the data store durably
+    /// persists everything which has been written to it.
+    ///
+    /// Flushed data is still not visible to other instances of this object until [`commit`] is
+    /// called, and it is not reachable after the repository is closed or the process crashes until
+    /// [`Commit::commit`] is called. Flushing often may reduce deduplication, since chunk boundaries
+    /// are no longer determined only by the contents of the object.
+    ///
+    /// # Errors
+    /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`DataStore::sync`]: crate::store::DataStore::sync
+    /// [`commit`]: crate::repo::Object::commit
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    pub fn sync_data(&mut self) -> crate::Result<()> {
+        ObjectStore::new(&self.repo_state, &self.handle)?
+            .writer_guard(&mut self.object_state)
+            .writer()
+            .sync_data()
+    }
+
     /// Return whether this object is valid.
     pub fn is_valid(&self) -> bool {
         ObjectStore::new(&self.repo_state, &self.handle).is_ok()
@@ -288,6 +587,24 @@ impl Read for Object {
             .reader()
             .read(buf)
     }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let store = ObjectStore::new(&self.repo_state, &self.handle)?;
+        let mut guard = store.reader_guard(&mut self.object_state);
+        let mut reader = guard.reader();
+
+        // Fill each buffer in turn under a single lock, stopping at the first short read.
+        let mut bytes_read = 0;
+        for buf in bufs.iter_mut() {
+            let buf_bytes_read = reader.read(buf)?;
+            bytes_read += buf_bytes_read;
+            if buf_bytes_read < buf.len() {
+                break;
+            }
+        }
+
+        Ok(bytes_read)
+    }
 }
 
 impl Seek for Object {
@@ -307,6 +624,23 @@ impl Write for Object {
             .write(buf)
     }
 
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let store = ObjectStore::new(&self.repo_state, &self.handle)?;
+        let mut guard = store.writer_guard(&mut self.object_state);
+        let mut writer = guard.writer();
+
+        let mut bytes_written = 0;
+        for buf in bufs {
+            let buf_bytes_written = writer.write(buf)?;
+            bytes_written += buf_bytes_written;
+            if buf_bytes_written < buf.len() {
+                break;
+            }
+        }
+
+        Ok(bytes_written)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         ObjectStore::new(&self.repo_state, &self.handle)?
             .writer_guard(&mut self.object_state)
@@ -353,6 +687,33 @@ impl ReadOnlyObject {
         self.0.content_id()
     }
 
+    /// Return whether the data in `reader` is the same as the contents of this object.
+    ///
+    /// See [`Object::matches_reader`] for details.
+    ///
+    /// [`Object::matches_reader`]: crate::repo::Object::matches_reader
+    pub fn matches_reader(&mut self, reader: impl Read) -> crate::Result<bool> {
+        self.0.matches_reader(reader)
+    }
+
+    /// Read the data in the object from the current position to the end into `writer`.
+    ///
+    /// See [`Object::read_into`] for details.
+    ///
+    /// [`Object::read_into`]: crate::repo::Object::read_into
+    pub fn read_into<W: Write>(&mut self, writer: W) -> crate::Result<u64> {
+        self.0.read_into(writer)
+    }
+
+    /// Read data from the object starting at `offset` into `buf`.
+    ///
+    /// See [`Object::read_at`] for details.
+    ///
+    /// [`Object::read_at`]: crate::repo::Object::read_at
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> crate::Result<usize> {
+        self.0.read_at(offset, buf)
+    }
+
     /// Return statistics about the object.
     ///
     /// See [`Object::stats`] for details.
@@ -405,6 +766,10 @@ impl Read for ReadOnlyObject {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.0.read(buf)
     }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.0.read_vectored(bufs)
+    }
 }
 
 impl Seek for ReadOnlyObject {
@@ -0,0 +1,65 @@
+use super::chunking::Chunking;
+use super::compression::Compression;
+
+/// Options for writing an individual object.
+///
+/// This is used with [`KeyRepo::insert_with_options`] to write an object with a different
+/// compression or chunking method than the rest of the repository. For example, data which is
+/// already compressed, like images or video, gains nothing from being compressed again.
+///
+/// These options only apply to data written through the [`Object`] they are used to create.
+///
+/// [`KeyRepo::insert_with_options`]: crate::repo::key::KeyRepo::insert_with_options
+/// [`Object`]: crate::repo::Object
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ObjectOptions {
+    compression: Option<Compression>,
+    chunking: Option<Chunking>,
+}
+
+impl ObjectOptions {
+    /// Create a new `ObjectOptions` which uses the repository's settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compress the object's data with the given `method` instead of the repository's.
+    ///
+    /// The compression method is recorded for each chunk, so the data can still be read after the
+    /// repository is closed and reopened. Chunks which are shared with other objects keep the
+    /// compression method they were first written with.
+    ///
+    /// This requires [`FormatVersion::V0_15`] or newer if it differs from the repository's
+    /// compression method. Objects which are rewritten by [`KeyRepo::rechunk`] use the
+    /// repository's compression method.
+    ///
+    /// [`FormatVersion::V0_15`]: crate::repo::FormatVersion::V0_15
+    /// [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
+    pub fn compression(&mut self, method: Compression) -> &mut Self {
+        self.compression = Some(method);
+        self
+    }
+
+    /// Split the object's data into chunks with the given `method` instead of the repository's.
+    ///
+    /// The chunking method is not stored in the repository, so it only affects how data is
+    /// written, not how it is read. The repository's [`RepoConfig::min_chunk_size`] and
+    /// [`RepoConfig::max_chunk_size`] still apply.
+    ///
+    /// [`RepoConfig::min_chunk_size`]: crate::repo::RepoConfig::min_chunk_size
+    /// [`RepoConfig::max_chunk_size`]: crate::repo::RepoConfig::max_chunk_size
+    pub fn chunking(&mut self, method: Chunking) -> &mut Self {
+        self.chunking = Some(method);
+        self
+    }
+
+    /// The compression method to use instead of the repository's, if any.
+    pub(super) fn compression_method(&self) -> Option<&Compression> {
+        self.compression.as_ref()
+    }
+
+    /// The chunking method to use instead of the repository's, if any.
+    pub(super) fn chunking_method(&self) -> Option<&Chunking> {
+        self.chunking.as_ref()
+    }
+}
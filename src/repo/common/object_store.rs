@@ -1,16 +1,23 @@
-use std::cmp::{min, Ordering};
+use std::cmp::{max, min, Ordering};
 use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 
+use cdchunking::ChunkerImpl;
 use rmp_serde::{from_read, to_vec};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use super::chunk_store::{ReadChunk, StoreReader, StoreWriter, WriteChunk};
-use super::handle::{chunk_hash, ContentId, Extent, ObjectHandle, ObjectStats};
+use super::chunk_store::{ReadChunk, StoreReader, StoreWriter, WriteChunk, STREAMING_CHUNK_SIZE};
+use super::handle::{ContentId, Extent, ObjectHandle, ObjectStats};
 use super::state::{ExtentLocation, ObjectState, RepoState, SeekPosition};
 use crate::repo::ObjectId;
 
+/// The number of bytes to read at once when reading part of a large chunk.
+///
+/// See `StoreReader::supports_partial_reads` for when this applies.
+const PARTIAL_READ_SIZE: usize = STREAMING_CHUNK_SIZE as usize;
+
 pub struct ObjectStore {
     repo_state: Arc<RwLock<RepoState>>,
     handle: Arc<RwLock<ObjectHandle>>,
@@ -27,6 +34,11 @@ impl ObjectStore {
         })
     }
 
+    /// Return a new `ObjectState` for this object which has its own seek position and buffers.
+    pub fn new_object_state(&self) -> ObjectState {
+        ObjectState::new(self.repo_state.read().unwrap().metadata.config.to_chunker())
+    }
+
     pub fn info_guard<'a>(&'a self, object_state: &'a ObjectState) -> ObjectInfoGuard<'a> {
         ObjectInfoGuard {
             repo_state: self.repo_state.read().unwrap(),
@@ -132,34 +144,17 @@ impl<'a> ObjectInfo<'a> {
         })
     }
 
+    /// Return a chunker which partitions data the same way as when it is written to the object.
+    pub fn chunker(&self) -> Box<dyn ChunkerImpl + Send + Sync> {
+        self.repo_state.metadata.config.to_chunker()
+    }
+
     /// Return an `ObjectStats` containing statistics about the object.
     pub fn stats(&self) -> crate::Result<ObjectStats> {
         if self.object_state.transaction_lock.is_some() {
             return Err(crate::Error::TransactionInProgress);
         }
-        let mut current_position = 0u64;
-        let mut actual_size = 0u64;
-        let mut apparent_size = 0u64;
-        let mut holes = Vec::new();
-
-        for extent in &self.handle.extents {
-            match extent {
-                Extent::Chunk(_) => {
-                    actual_size += extent.size();
-                }
-                Extent::Hole { .. } => {
-                    holes.push(current_position..(current_position + extent.size()));
-                }
-            }
-            current_position += extent.size();
-            apparent_size += extent.size();
-        }
-
-        Ok(ObjectStats {
-            apparent_size,
-            actual_size,
-            holes,
-        })
+        Ok(self.handle.stats())
     }
 }
 
@@ -197,15 +192,8 @@ impl<'a> ObjectReader<'a> {
         let expected_chunks = self.handle.chunks().collect::<Vec<_>>();
 
         for chunk in expected_chunks {
-            match self.store_reader().read_chunk(chunk) {
-                Ok(data) => {
-                    if data.len() != chunk.size as usize || chunk_hash(&data) != chunk.hash {
-                        return Ok(false);
-                    }
-                }
-                // Ciphertext verification failed. No need to check the hash.
-                Err(crate::Error::InvalidData) => return Ok(false),
-                Err(error) => return Err(error),
+            if !self.store_reader().verify_chunk(chunk)? {
+                return Ok(false);
             }
         }
 
@@ -258,15 +246,33 @@ impl<'a> ObjectReader<'a> {
 
         match current_location.extent {
             Extent::Chunk(chunk) => {
-                // If we're reading from a new chunk, read the contents of that chunk into the read
-                // buffer.
-                if Some(chunk) != self.object_state.buffered_chunk {
+                let position = current_location.relative_position();
+                let buffer_start = self.object_state.buffered_offset;
+                let buffer_end = buffer_start + self.object_state.read_buffer.len() as u64;
+                let is_buffered = Some(chunk) == self.object_state.buffered_chunk
+                    && position >= buffer_start
+                    && position < buffer_end;
+
+                // If the data we need isn't in the read buffer, read it into the read buffer. For
+                // most chunks, we read the whole chunk. For large chunks which can be read
+                // partially, we only read the next part of the chunk.
+                if !is_buffered {
+                    self.object_state.buffered_chunk = None;
+                    let mut store_reader = self.store_reader();
+                    let (offset, data) = if store_reader.supports_partial_reads(chunk) {
+                        let data =
+                            store_reader.read_chunk_range(chunk, position, PARTIAL_READ_SIZE)?;
+                        (position, data)
+                    } else {
+                        (0, store_reader.read_chunk(chunk)?)
+                    };
                     self.object_state.buffered_chunk = Some(chunk);
-                    self.object_state.read_buffer = self.store_reader().read_chunk(chunk)?;
+                    self.object_state.buffered_offset = offset;
+                    self.object_state.read_buffer = data;
                 }
 
-                let start = current_location.relative_position() as usize;
-                let end = min(start + size, chunk.size as usize);
+                let start = (position - self.object_state.buffered_offset) as usize;
+                let end = min(start + size, self.object_state.read_buffer.len());
                 Ok(&self.object_state.read_buffer[start..end])
             }
             Extent::Hole { size: hole_size } => {
@@ -434,6 +440,8 @@ impl<'a> ObjectWriter<'a> {
 
     /// Set the length of the object.
     pub fn set_len(&mut self, size: u64) -> crate::Result<()> {
+        self.repo_state.check_writable()?;
+
         // Because this modifies the object, we need to start a new transaction.
         match self.object_state.transaction_lock {
             None => match self.repo_state.transactions.acquire_lock(self.handle.id) {
@@ -456,6 +464,98 @@ impl<'a> ObjectWriter<'a> {
         Ok(())
     }
 
+    /// Replace the bytes in the given range of the object with a sparse hole.
+    ///
+    /// The range is clamped to the size of the object.
+    pub fn punch_hole(&mut self, offset: u64, len: u64) -> crate::Result<()> {
+        self.repo_state.check_writable()?;
+
+        // Because this modifies the object, we need to start a new transaction.
+        match self.object_state.transaction_lock {
+            None => match self.repo_state.transactions.acquire_lock(self.handle.id) {
+                None => return Err(crate::Error::TransactionInProgress),
+                Some(lock) => {
+                    self.object_state.transaction_lock = Some(lock);
+                }
+            },
+            Some(_) => return Err(crate::Error::TransactionInProgress),
+        }
+
+        let end = min(offset.saturating_add(len), self.handle.size());
+        let result = if offset < end {
+            self.replace_with_hole(offset, end)
+        } else {
+            Ok(())
+        };
+
+        self.object_state.transaction_lock = None;
+
+        result
+    }
+
+    /// Replace the extents between `start` and `end` with a hole.
+    ///
+    /// If this returns `Err`, the object is unchanged.
+    fn replace_with_hole(&mut self, start: u64, end: u64) -> crate::Result<()> {
+        let mut new_extents = Vec::new();
+        let mut extent_start = 0u64;
+
+        for extent in self.handle.extents.clone() {
+            let extent_end = extent_start + extent.size();
+
+            if extent_end <= start || extent_start >= end {
+                new_extents.push(extent);
+            } else {
+                // Chunks can't be modified in-place, so the parts of a chunk which are outside the
+                // hole must be read and written back as new chunks.
+                if extent_start < start {
+                    new_extents.push(self.slice_extent(extent, 0..(start - extent_start))?);
+                }
+                new_extents.push(Extent::Hole {
+                    size: min(end, extent_end) - max(start, extent_start),
+                });
+                if extent_end > end {
+                    new_extents
+                        .push(self.slice_extent(extent, (end - extent_start)..extent.size())?);
+                }
+            }
+
+            extent_start = extent_end;
+        }
+
+        // Merge adjacent holes so that the object doesn't accumulate extents.
+        let mut merged_extents: Vec<Extent> = Vec::with_capacity(new_extents.len());
+        for extent in new_extents {
+            match (merged_extents.last_mut(), extent) {
+                (Some(Extent::Hole { size }), Extent::Hole { size: next_size }) => {
+                    *size += next_size;
+                }
+                _ => merged_extents.push(extent),
+            }
+        }
+
+        self.handle.extents = merged_extents;
+
+        Ok(())
+    }
+
+    /// Return an extent containing the bytes in the given `range` of `extent`.
+    fn slice_extent(&mut self, extent: Extent, range: Range<u64>) -> crate::Result<Extent> {
+        match extent {
+            Extent::Chunk(chunk) => {
+                let chunk_data = self.store_writer().read_chunk(chunk)?;
+                let handle_id = self.handle.id;
+                Ok(Extent::Chunk(self.store_writer().write_chunk(
+                    &chunk_data[range.start as usize..range.end as usize],
+                    handle_id,
+                )?))
+            }
+            Extent::Hole { .. } => Ok(Extent::Hole {
+                size: range.end - range.start,
+            }),
+        }
+    }
+
     /// Write chunks stored in the chunker to the repository.
     fn write_chunks(&mut self) -> crate::Result<()> {
         for chunk_data in self.object_state.chunker.chunks() {
@@ -476,6 +576,17 @@ impl<'a> ObjectWriter<'a> {
         Ok(())
     }
 
+    /// Flush buffered data and ask the data store to durably persist it.
+    pub fn sync_data(&mut self) -> crate::Result<()> {
+        self.flush()?;
+        self.repo_state
+            .store
+            .lock()
+            .unwrap()
+            .sync()
+            .map_err(crate::Error::Store)
+    }
+
     /// Commit change to the data store.
     pub fn commit(&mut self) -> crate::Result<()> {
         if self.object_state.transaction_lock.is_none() {
@@ -564,6 +675,9 @@ impl<'a> ObjectWriter<'a> {
 // the user needs to explicitly call `commit` when they're done writing data.
 impl<'a> Write for ObjectWriter<'a> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Fail before buffering any data rather than when the first chunk is written.
+        self.repo_state.check_writable()?;
+
         // Attempt to acquire a transaction lock if one has not already been acquired.
         let first_write = match self.object_state.transaction_lock {
             None => match self.repo_state.transactions.acquire_lock(self.handle.id) {
@@ -606,6 +720,17 @@ impl<'a> Write for ObjectWriter<'a> {
     }
 
     fn flush(&mut self) -> io::Result<()> {
+        if self.object_state.transaction_lock.is_none() {
+            // There is no buffered data to write.
+            return Ok(());
+        }
+
+        // Write the data buffered in the chunker to the repository as a chunk even though we
+        // haven't reached a chunk boundary. This doesn't update the object handle; the chunks are
+        // only added to the object once the transaction is committed.
+        self.object_state.chunker.flush()?;
+        self.write_chunks()?;
+
         Ok(())
     }
 }
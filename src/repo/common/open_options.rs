@@ -1,20 +1,33 @@
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
-use rmp_serde::{from_read, to_vec};
-use secrecy::ExposeSecret;
 use uuid::{uuid, Uuid};
 
+use crate::diagnostics::{LockKind, Registration};
 use crate::store::{BlockKey, DataStore, OpenStore};
 
+use super::attributes::AttributeTable;
+use super::audit_log::AuditOperation;
+use super::buffer_pool::BufferPool;
+use super::chunk_cache::ChunkCache;
 use super::chunking::Chunking;
+use super::commit::CommitId;
+use super::compatibility::check_config;
 use super::compression::Compression;
 use super::config::RepoConfig;
+use super::destructive::{DestructivePolicy, Interlock};
 use super::encryption::{Encryption, EncryptionKey, KeySalt, ResourceLimit};
+use super::format::{
+    decode_chunk, decode_metadata, decode_version, deserialize_header, encode_chunk,
+    encode_master_key, encode_metadata, encode_version, serialize_header,
+};
 use super::handle::HandleIdTable;
-use super::lock::{lock_store, LockTable};
+use super::key_index::KeyIndex;
+use super::lock::{lock_store, unlock_store, LockTable};
 use super::metadata::{Header, RepoMetadata};
+use super::metadata_handle::MetadataHandle;
 use super::open_repo::OpenRepo;
 use super::packing::Packing;
 use super::repository::KeyRepo;
@@ -28,12 +41,6 @@ use super::state::{InstanceId, RepoState};
 pub const DEFAULT_INSTANCE: InstanceId =
     InstanceId::new(uuid!("ea978302-bfd8-11ea-b92b-031a9ad75c07"));
 
-/// The current repository format version ID.
-///
-/// This must be changed any time a backwards-incompatible change is made to the repository
-/// format.
-const VERSION_ID: Uuid = uuid!("44253e72-f08f-11eb-a2a3-a701701f8601");
-
 /// The mode to use to open a repository.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum OpenMode {
@@ -45,6 +52,24 @@ pub enum OpenMode {
 
     /// Create a new repository, failing if it already exists.
     CreateNew,
+
+    /// Open an existing repository without writing anything to the data store, failing if it
+    /// doesn't exist.
+    ///
+    /// A repository opened this way doesn't acquire a lock, so it can be opened from a data store
+    /// which is read-only, like a directory store on a read-only file system. Because it isn't
+    /// locked, another client may modify the repository while it's open.
+    ///
+    /// Objects can be read, verified, and exported, but any operation which would write to the
+    /// data store fails early with `Error::ReadOnly`. This includes writing to objects, committing
+    /// changes, cleaning the repository, and switching to or opening an instance which doesn't
+    /// exist yet.
+    ///
+    /// To guarantee that nothing is written to a data store which is otherwise writable, wrap its
+    /// configuration in a [`ReadOnlyConfig`].
+    ///
+    /// [`ReadOnlyConfig`]: crate::store::ReadOnlyConfig
+    ReadOnly,
 }
 
 type BoxLockHandler<'a> = Box<dyn FnMut(&[u8]) -> bool + 'a>;
@@ -110,6 +135,10 @@ pub struct OpenOptions<'a> {
     instance: InstanceId,
     lock_context: &'a [u8],
     lock_handler: BoxLockHandler<'a>,
+    label: Option<String>,
+    audit_writer: Option<String>,
+    chunk_cache: Option<Arc<ChunkCache>>,
+    buffer_pool: Option<Arc<BufferPool>>,
 }
 
 impl<'a> Default for OpenOptions<'a> {
@@ -128,6 +157,10 @@ impl<'a> OpenOptions<'a> {
             instance: DEFAULT_INSTANCE,
             lock_context: &[],
             lock_handler: Box::new(|_| false),
+            label: None,
+            audit_writer: None,
+            chunk_cache: None,
+            buffer_pool: None,
         }
     }
 
@@ -214,6 +247,39 @@ impl<'a> OpenOptions<'a> {
         self
     }
 
+    /// Overwrite the maximum header size specified in [`RepoConfig::max_header_size`].
+    ///
+    /// This is only applicable when creating a new repository. This is ignored when opening an
+    /// existing repository.
+    ///
+    /// [`RepoConfig::max_header_size`]: crate::repo::RepoConfig::max_header_size
+    pub fn max_header_size(&mut self, limit: Option<u64>) -> &mut Self {
+        self.config.max_header_size = limit;
+        self
+    }
+
+    /// Overwrite whether to verify reads as specified in [`RepoConfig::verify_reads`].
+    ///
+    /// This is only applicable when creating a new repository. This is ignored when opening an
+    /// existing repository.
+    ///
+    /// [`RepoConfig::verify_reads`]: crate::repo::RepoConfig::verify_reads
+    pub fn verify_reads(&mut self, verify: bool) -> &mut Self {
+        self.config.verify_reads = verify;
+        self
+    }
+
+    /// Overwrite the grace period specified in [`RepoConfig::gc_grace_period`].
+    ///
+    /// This is only applicable when creating a new repository. This is ignored when opening an
+    /// existing repository.
+    ///
+    /// [`RepoConfig::gc_grace_period`]: crate::repo::RepoConfig::gc_grace_period
+    pub fn gc_grace_period(&mut self, grace_period: Duration) -> &mut Self {
+        self.config.gc_grace_period = grace_period;
+        self
+    }
+
     /// Use the given `password`.
     ///
     /// This is required when encryption is enabled for the repository.
@@ -281,27 +347,82 @@ impl<'a> OpenOptions<'a> {
         self
     }
 
-    /// Open the repository, failing if it doesn't exist.
-    fn open_repo<R: OpenRepo>(&mut self, mut store: impl DataStore + 'static) -> crate::Result<R> {
+    /// Attach a human-readable `label` to the repository once it is opened.
+    ///
+    /// This label is only used to identify the repository in [`diagnostics::open_repositories`].
+    /// It is not stored in the repository.
+    ///
+    /// [`diagnostics::open_repositories`]: crate::diagnostics::open_repositories
+    pub fn label(&mut self, label: impl Into<String>) -> &mut Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Record the given writer `id` in audit log entries for operations performed by this client.
+    ///
+    /// Unlike a [`label`], this ID is stored in the repository in plaintext as part of the
+    /// [`audit_log`], so it should not contain sensitive information. By default, entries do not
+    /// record a writer ID.
+    ///
+    /// [`label`]: crate::repo::OpenOptions::label
+    /// [`audit_log`]: crate::repo::key::KeyRepo::audit_log
+    pub fn audit_writer(&mut self, id: impl Into<String>) -> &mut Self {
+        self.audit_writer = Some(id.into());
+        self
+    }
+
+    /// Cache decoded chunks in the given `cache`.
+    ///
+    /// The same [`ChunkCache`] can be shared between multiple repositories. If this is not
+    /// specified, the repository does not use a shared cache.
+    ///
+    /// [`ChunkCache`]: crate::repo::ChunkCache
+    pub fn chunk_cache(&mut self, cache: Arc<ChunkCache>) -> &mut Self {
+        self.chunk_cache = Some(cache);
+        self
+    }
+
+    /// Rent buffers for encoding and decoding chunks from the given `pool`.
+    ///
+    /// The same [`BufferPool`] can be shared between multiple repositories. If this is not
+    /// specified, the repository uses its own pool which retains up to
+    /// [`RepoConfig::buffer_pool_size`] bytes.
+    ///
+    /// [`BufferPool`]: crate::repo::BufferPool
+    /// [`RepoConfig::buffer_pool_size`]: crate::repo::RepoConfig::buffer_pool_size
+    pub fn buffer_pool(&mut self, pool: Arc<BufferPool>) -> &mut Self {
+        self.buffer_pool = Some(pool);
+        self
+    }
+
+    /// Return the buffer pool to use for a repository with the given `config`.
+    fn buffer_pool_for(&self, config: &RepoConfig) -> Arc<BufferPool> {
+        self.buffer_pool
+            .clone()
+            .unwrap_or_else(|| Arc::new(BufferPool::new(config.buffer_pool_size)))
+    }
+
+    /// Read the metadata of an existing repository from the given `store`.
+    ///
+    /// This checks that the repository is a compatible version before reading its metadata.
+    fn read_metadata(store: &mut impl DataStore) -> crate::Result<RepoMetadata> {
         // Read the repository version to see if this is a compatible repository.
         let serialized_version = store
             .read_block(BlockKey::Version)
             .map_err(crate::Error::Store)?
             .ok_or(crate::Error::NotFound)?;
-        let version =
-            Uuid::from_slice(serialized_version.as_slice()).map_err(|_| crate::Error::Corrupt)?;
-        if version != VERSION_ID {
-            return Err(crate::Error::UnsupportedRepo);
-        }
+        decode_version(serialized_version.as_slice())?;
 
         // Read the repository metadata from the super block.
         let serialized_metadata = store
             .read_block(BlockKey::Super)
             .map_err(crate::Error::Store)?
             .ok_or(crate::Error::Corrupt)?;
-        let metadata: RepoMetadata =
-            from_read(serialized_metadata.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        decode_metadata(serialized_metadata.as_slice())
+    }
 
+    /// Decrypt the master key in the given `metadata` using the configured password.
+    fn decrypt_master_key(&self, metadata: &RepoMetadata) -> crate::Result<EncryptionKey> {
         let password = match self.password {
             Some(password) if metadata.config.encryption != Encryption::None => Some(password),
             // Return an error if a password was required but not provided.
@@ -312,19 +433,31 @@ impl<'a> OpenOptions<'a> {
         };
 
         // Decrypt the master key for the repository.
-        let master_key = match password {
-            Some(password_bytes) => metadata.decrypt_master_key(password_bytes)?,
-            None => EncryptionKey::new(Vec::new()),
-        };
+        match password {
+            Some(password_bytes) => metadata.decrypt_master_key(password_bytes),
+            None => Ok(EncryptionKey::new(Vec::new())),
+        }
+    }
 
-        // Attempt to acquire a lock on the repository.
-        let lock_id = lock_store(
-            &mut store,
-            &metadata.config.encryption,
-            &master_key,
-            self.lock_context,
-            &mut self.lock_handler,
-        )?;
+    /// Open the repository, failing if it doesn't exist.
+    fn open_repo<R: OpenRepo>(&mut self, mut store: impl DataStore + 'static) -> crate::Result<R> {
+        let metadata = Self::read_metadata(&mut store)?;
+        let master_key = self.decrypt_master_key(&metadata)?;
+
+        // Attempt to acquire a lock on the repository. Acquiring a lock requires writing to the data
+        // store, so read-only repositories don't hold one.
+        let read_only = self.mode == OpenMode::ReadOnly;
+        let lock_id = if read_only {
+            None
+        } else {
+            Some(lock_store(
+                &mut store,
+                &metadata.config.encryption,
+                &master_key,
+                self.lock_context,
+                &mut self.lock_handler,
+            )?)
+        };
 
         // We read the metadata again after acquiring a lock but before getting the header ID to
         // avoid a race condition. We don't have to worry about decrypting the master encryption key
@@ -333,39 +466,53 @@ impl<'a> OpenOptions<'a> {
             .read_block(BlockKey::Super)
             .map_err(crate::Error::Store)?
             .ok_or(crate::Error::Corrupt)?;
-        let metadata: RepoMetadata =
-            from_read(serialized_metadata.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        let metadata = decode_metadata(serialized_metadata.as_slice())?;
 
         // Read, decrypt, decompress, and deserialize the repository header.
         let encrypted_header = store
             .read_block(BlockKey::Header(metadata.header_id))
             .map_err(crate::Error::Store)?
             .ok_or(crate::Error::Corrupt)?;
-        let compressed_header = metadata
-            .config
-            .encryption
-            .decrypt(&encrypted_header, &master_key)
-            .map_err(|_| crate::Error::Corrupt)?;
-        let serialized_header = metadata
-            .config
-            .compression
-            .decompress(&compressed_header)
-            .map_err(|_| crate::Error::Corrupt)?;
-        let header = from_read(serialized_header.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        let buffer_pool = self.buffer_pool_for(&metadata.config);
+        let serialized_header = decode_chunk(
+            &encrypted_header,
+            &metadata.config.compression,
+            &[],
+            &metadata.config.encryption,
+            &master_key,
+            &buffer_pool,
+        )
+        .map_err(|_| crate::Error::Corrupt)?;
+        let header = deserialize_header(serialized_header.as_slice())?;
 
         let Header {
             chunks,
             packs,
             instances,
             handle_table,
+            rechunk,
+            dictionaries,
         } = header;
 
+        let lock_kind = (!read_only).then_some(LockKind::Exclusive);
+        let registration = Registration::new(metadata.id, self.label.clone(), lock_kind);
+
         let state = Arc::new(RwLock::new(RepoState {
             store: Mutex::new(Box::new(store)),
             metadata,
             chunks,
             packs,
             transactions: LockTable::new(),
+            registration,
+            chunk_cache: self.chunk_cache.clone(),
+            buffer_pool,
+            committed_header: None,
+            committed_metadata: None,
+            interlock: Interlock::default(),
+            audit_writer: self.audit_writer.clone(),
+            retained_blocks: HashMap::new(),
+            rechunk,
+            dictionaries,
             master_key,
             lock_id,
         }));
@@ -374,6 +521,9 @@ impl<'a> OpenOptions<'a> {
             state,
             instance_id: self.instance,
             objects: HashMap::new(),
+            trash: HashMap::new(),
+            attributes: AttributeTable::default(),
+            key_index: KeyIndex::default(),
             instances,
             handle_table,
             transaction_id: Arc::new(Uuid::new_v4()),
@@ -387,6 +537,10 @@ impl<'a> OpenOptions<'a> {
         &mut self,
         mut store: impl DataStore + 'static,
     ) -> crate::Result<R> {
+        // Check this before writing anything so that an incompatible config never leaves a
+        // partially created repository behind.
+        check_config(&self.config)?;
+
         let password = match self.password {
             Some(password) if self.config.encryption != Encryption::None => Some(password),
             // Return an error if a password was required but not provided.
@@ -435,9 +589,7 @@ impl<'a> OpenOptions<'a> {
                     self.config.memory_limit,
                     self.config.operations_limit,
                 );
-                self.config
-                    .encryption
-                    .encrypt(master_key.expose_secret(), &user_key)
+                encode_master_key(&master_key, &self.config.encryption, &user_key)
             }
             None => Vec::new(),
         };
@@ -448,32 +600,46 @@ impl<'a> OpenOptions<'a> {
             packs: HashMap::new(),
             instances: HashMap::new(),
             handle_table: HandleIdTable::new(),
+            rechunk: None,
+            dictionaries: Vec::new(),
         };
 
         // Serialize, encode, and write the header to the data store.
-        let serialized_header =
-            to_vec(&header).expect("Could not serialize the repository header.");
-        let compressed_header = self.config.compression.compress(&serialized_header)?;
-        let encrypted_header = self
-            .config
-            .encryption
-            .encrypt(&compressed_header, &master_key);
+        let serialized_header = serialize_header(&header, self.config.compatibility_target)?;
+        let buffer_pool = self.buffer_pool_for(&self.config);
+        let encrypted_header = encode_chunk(
+            &serialized_header,
+            &self.config.compression,
+            &[],
+            &self.config.encryption,
+            &master_key,
+            &buffer_pool,
+        )?;
         let header_id = Uuid::new_v4().into();
         store
             .write_block(BlockKey::Header(header_id), &encrypted_header)
             .map_err(crate::Error::Store)?;
 
         // Create the repository metadata with the header block references.
-        let metadata = RepoMetadata {
+        let mut metadata = RepoMetadata {
             id: Uuid::new_v4().into(),
             config: self.config.clone(),
             master_key: encrypted_master_key,
             salt,
             header_id,
+            destructive_policy: DestructivePolicy::default(),
+            audit_log: Vec::new(),
+            commit_id: CommitId {
+                sequence: 0,
+                uuid: Uuid::new_v4(),
+            },
+            commit_payload: Vec::new(),
+            commit_history: Vec::new(),
         };
+        metadata.record_audit(AuditOperation::Created, self.audit_writer.clone());
 
         // Write the repository metadata.
-        let serialized_metadata = to_vec(&metadata).expect("Could not serialize metadata.");
+        let serialized_metadata = encode_metadata(&metadata)?;
         store
             .write_block(BlockKey::Super, &serialized_metadata)
             .map_err(crate::Error::Store)?;
@@ -481,7 +647,7 @@ impl<'a> OpenOptions<'a> {
         // Write the repository version. We do this last because this signifies that the repository
         // is done being created.
         store
-            .write_block(BlockKey::Version, VERSION_ID.as_bytes())
+            .write_block(BlockKey::Version, encode_version())
             .map_err(crate::Error::Store)?;
 
         let Header {
@@ -489,22 +655,40 @@ impl<'a> OpenOptions<'a> {
             packs,
             instances,
             handle_table,
+            rechunk,
+            dictionaries,
         } = header;
 
+        let registration =
+            Registration::new(metadata.id, self.label.clone(), Some(LockKind::Exclusive));
+
         let state = Arc::new(RwLock::new(RepoState {
             store: Mutex::new(Box::new(store)),
             metadata,
             chunks,
             packs,
             transactions: LockTable::new(),
+            registration,
+            chunk_cache: self.chunk_cache.clone(),
+            buffer_pool,
+            committed_header: None,
+            committed_metadata: None,
+            interlock: Interlock::default(),
+            audit_writer: self.audit_writer.clone(),
+            retained_blocks: HashMap::new(),
+            rechunk,
+            dictionaries,
             master_key,
-            lock_id,
+            lock_id: Some(lock_id),
         }));
 
         let repo: KeyRepo<R::Key> = KeyRepo {
             state,
             instance_id: self.instance,
             objects: HashMap::new(),
+            trash: HashMap::new(),
+            attributes: AttributeTable::default(),
+            key_index: KeyIndex::default(),
             instances,
             handle_table,
             transaction_id: Arc::new(Uuid::new_v4()),
@@ -516,10 +700,16 @@ impl<'a> OpenOptions<'a> {
     /// Open or create the repository.
     ///
     /// # Errors
-    /// - `Error::NotFound`: There is no repository in the data store and `OpenMode::Open` was
-    /// specified.
+    /// - `Error::NotFound`: There is no repository in the data store and `OpenMode::Open` or
+    /// `OpenMode::ReadOnly` was specified.
     /// - `Error::AlreadyExists`: A repository already exists in the data store and
     /// `OpenMode::CreateNew` was specified.
+    /// - `Error::ReadOnly`: The data store is read-only and `OpenMode::ReadOnly` was not
+    /// specified.
+    /// - `Error::Incompatible`: A repository was being created and the config uses an option which
+    /// its compatibility target doesn't support.
+    /// - `Error::ReadOnly`: `OpenMode::ReadOnly` was specified and the configured instance doesn't
+    /// exist.
     /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
     /// - `Error::Locked`: The repository is locked.
     /// - `Error::Password`: The password provided is invalid.
@@ -541,8 +731,14 @@ impl<'a> OpenOptions<'a> {
     {
         let mut store = config.open()?;
 
+        // Check this before touching the data store so that we fail early rather than when the
+        // data store refuses a write.
+        if store.is_read_only() && self.mode != OpenMode::ReadOnly {
+            return Err(crate::Error::ReadOnly);
+        }
+
         match self.mode {
-            OpenMode::Open => self.open_repo(store),
+            OpenMode::Open | OpenMode::ReadOnly => self.open_repo(store),
             OpenMode::Create => {
                 if store
                     .read_block(BlockKey::Version)
@@ -557,6 +753,74 @@ impl<'a> OpenOptions<'a> {
             OpenMode::CreateNew => self.create_repo(store),
         }
     }
+
+    /// Open an existing repository for metadata-only maintenance.
+    ///
+    /// This authenticates the configured password against the repository's master key and acquires
+    /// a lock on the repository like [`open`], but it never reads the repository header. This
+    /// makes it much cheaper than [`open`] for operations which only touch the repository's
+    /// metadata, like changing its password, on repositories with a large header.
+    ///
+    /// The configured [`OpenMode`] and repository config are ignored; this always opens an
+    /// existing repository.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no repository in the data store.
+    /// - `Error::ReadOnly`: The data store is read-only.
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::Locked`: The repository is locked.
+    /// - `Error::Password`: The password provided is invalid.
+    /// - `Error::Password`: A password was required but not provided.
+    /// - `Error::UnsupportedRepo`: The repository is an unsupported format.
+    /// - `Error::UnsupportedStore`: The data store is an unsupported format.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`open`]: crate::repo::OpenOptions::open
+    /// [`OpenMode`]: crate::repo::OpenMode
+    pub fn open_metadata(&mut self, config: &impl OpenStore) -> crate::Result<MetadataHandle> {
+        let mut store = config.open()?;
+
+        // Changing the metadata requires a lock, which can't be acquired in a read-only store.
+        if store.is_read_only() {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        let metadata = Self::read_metadata(&mut store)?;
+        let master_key = self.decrypt_master_key(&metadata)?;
+
+        // Attempt to acquire a lock on the repository.
+        let lock_id = lock_store(
+            &mut store,
+            &metadata.config.encryption,
+            &master_key,
+            self.lock_context,
+            &mut self.lock_handler,
+        )?;
+
+        // Read the metadata again after acquiring a lock to avoid a race condition, like when
+        // opening the full repository.
+        let metadata = match Self::read_metadata(&mut store) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                unlock_store(&mut store, lock_id).ok();
+                return Err(error);
+            }
+        };
+
+        let registration =
+            Registration::new(metadata.id, self.label.clone(), Some(LockKind::Exclusive));
+
+        Ok(MetadataHandle::new(
+            Box::new(store),
+            metadata,
+            master_key,
+            lock_id,
+            registration,
+            self.audit_writer.clone(),
+        ))
+    }
 }
 
 impl<'a> Debug for OpenOptions<'a> {
@@ -567,6 +831,10 @@ impl<'a> Debug for OpenOptions<'a> {
             .field("password", &self.password)
             .field("instance", &self.instance)
             .field("lock_context", &self.lock_context)
+            .field("label", &self.label)
+            .field("audit_writer", &self.audit_writer)
+            .field("chunk_cache", &self.chunk_cache)
+            .field("buffer_pool", &self.buffer_pool)
             .finish_non_exhaustive()
     }
 }
@@ -0,0 +1,235 @@
+use std::fmt;
+
+use rmp_serde::{from_slice, to_vec};
+use serde::de::{self, DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A value in the serialized data format, which can hold any serialized key.
+///
+/// This mirrors the MessagePack data model so that any key can be deserialized into it and then
+/// serialized again without knowing the original key type.
+#[derive(Debug, PartialEq, Clone)]
+enum RawValue {
+    Nil,
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<RawValue>),
+    Map(Vec<(RawValue, RawValue)>),
+}
+
+impl Serialize for RawValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            RawValue::Nil => serializer.serialize_unit(),
+            RawValue::Bool(value) => serializer.serialize_bool(*value),
+            RawValue::Unsigned(value) => serializer.serialize_u64(*value),
+            RawValue::Signed(value) => serializer.serialize_i64(*value),
+            RawValue::F32(value) => serializer.serialize_f32(*value),
+            RawValue::F64(value) => serializer.serialize_f64(*value),
+            RawValue::String(value) => serializer.serialize_str(value),
+            RawValue::Binary(value) => serializer.serialize_bytes(value),
+            RawValue::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            RawValue::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct RawValueVisitor;
+
+impl<'de> Visitor<'de> for RawValueVisitor {
+    type Value = RawValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any serialized value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(RawValue::Nil)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(RawValue::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        RawValue::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(RawValue::Bool(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(RawValue::Unsigned(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        // Normalize non-negative integers so that every integer has exactly one representation.
+        match u64::try_from(value) {
+            Ok(unsigned) => Ok(RawValue::Unsigned(unsigned)),
+            Err(_) => Ok(RawValue::Signed(value)),
+        }
+    }
+
+    fn visit_f32<E: de::Error>(self, value: f32) -> Result<Self::Value, E> {
+        Ok(RawValue::F32(value))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(RawValue::F64(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(RawValue::String(value.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(RawValue::String(value))
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(RawValue::Binary(value.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(RawValue::Binary(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(RawValue::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(RawValue::Map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(RawValueVisitor)
+    }
+}
+
+/// A key in a [`KeyRepo`] which has not been deserialized.
+///
+/// A `RawKey` holds the serialized bytes of a key of any type. Opening a [`KeyRepo<RawKey>`] never
+/// fails because of the repository's key type, which makes it possible to access the objects in a
+/// repository after the key type used to create it has changed or is no longer available.
+///
+/// You can iterate over the keys in a `KeyRepo<RawKey>` and read objects like you would in any other
+/// [`KeyRepo`]. Use [`deserialize`] to convert a `RawKey` into a key of a concrete type, or
+/// [`from_key`] to look up an object using a key of a concrete type.
+///
+/// # Examples
+/// Reading the objects in a repository which was created with `String` keys.
+/// ```
+/// use std::io::Read;
+/// use acid_store::repo::{OpenOptions, OpenMode, Commit, key::{KeyRepo, RawKey}};
+/// use acid_store::store::MemoryConfig;
+///
+/// let store = MemoryConfig::new();
+/// let mut repo: KeyRepo<String> = OpenOptions::new()
+///     .mode(OpenMode::CreateNew)
+///     .open(&store)
+///     .unwrap();
+/// repo.insert(String::from("Key"));
+/// repo.commit().unwrap();
+/// drop(repo);
+///
+/// let raw_repo: KeyRepo<RawKey> = OpenOptions::new().open(&store).unwrap();
+/// for raw_key in raw_repo.keys() {
+///     let key: String = raw_key.deserialize().unwrap();
+///     assert_eq!(key, "Key");
+/// }
+/// ```
+///
+/// [`KeyRepo`]: crate::repo::key::KeyRepo
+/// [`KeyRepo<RawKey>`]: crate::repo::key::KeyRepo
+/// [`deserialize`]: crate::repo::key::RawKey::deserialize
+/// [`from_key`]: crate::repo::key::RawKey::from_key
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct RawKey(Vec<u8>);
+
+impl RawKey {
+    /// Encode a `RawValue` as a `RawKey`.
+    fn from_value(value: &RawValue) -> crate::Result<Self> {
+        Ok(RawKey(to_vec(value).map_err(|_| crate::Error::Serialize)?))
+    }
+
+    /// Return the `RawKey` which is equal to the given `key` once it is stored in a repository.
+    ///
+    /// # Errors
+    /// - `Error::Serialize`: The key could not be serialized.
+    pub fn from_key<K: Serialize>(key: &K) -> crate::Result<Self> {
+        let serialized = to_vec(key).map_err(|_| crate::Error::Serialize)?;
+
+        // Round-trip the key through `RawValue` so that it has the same encoding as keys read from
+        // the repository.
+        let value: RawValue = from_slice(&serialized).map_err(|_| crate::Error::Serialize)?;
+        Self::from_value(&value)
+    }
+
+    /// Deserialize this key as a key of type `K`.
+    ///
+    /// # Errors
+    /// - `Error::Deserialize`: This key could not be deserialized as a value of type `K`.
+    pub fn deserialize<K: DeserializeOwned>(&self) -> crate::Result<K> {
+        from_slice(&self.0).map_err(|_| crate::Error::Deserialize)
+    }
+
+    /// The serialized bytes of this key.
+    ///
+    /// Keys are serialized using MessagePack.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for RawKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match from_slice::<RawValue>(&self.0) {
+            Ok(value) => f.debug_tuple("RawKey").field(&value).finish(),
+            Err(_) => f.debug_tuple("RawKey").field(&self.0).finish(),
+        }
+    }
+}
+
+impl Serialize for RawKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value: RawValue = from_slice(&self.0).map_err(serde::ser::Error::custom)?;
+        value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = RawValue::deserialize(deserializer)?;
+        Self::from_value(&value).map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::chunking::Chunking;
+use super::handle::HandleId;
+
+/// The default number of objects to rechunk between commits.
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 100;
+
+/// Options for rewriting the objects in a repository with a new chunking method.
+///
+/// This is used with [`KeyRepo::rechunk`].
+///
+/// [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RechunkOptions {
+    checkpoint_interval: Option<u64>,
+}
+
+impl Default for RechunkOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RechunkOptions {
+    /// Create a new `RechunkOptions` with default settings.
+    ///
+    /// By default, changes are committed after every 100 objects which are rechunked.
+    pub fn new() -> Self {
+        Self {
+            checkpoint_interval: Some(DEFAULT_CHECKPOINT_INTERVAL),
+        }
+    }
+
+    /// Commit changes after every `objects` objects which are rechunked.
+    ///
+    /// Progress is only saved when changes are committed, so this is the maximum number of
+    /// objects which will need to be rechunked again if the operation is interrupted. Committing
+    /// more often means less work is lost, but each commit writes a new header.
+    ///
+    /// # Panics
+    /// - `objects` is zero.
+    pub fn checkpoint_interval(&mut self, objects: u64) -> &mut Self {
+        assert!(objects > 0, "The checkpoint interval must be nonzero.");
+        self.checkpoint_interval = Some(objects);
+        self
+    }
+
+    /// Do not commit changes while rechunking.
+    ///
+    /// Nothing is committed, including the new chunking method, until [`Commit::commit`] is
+    /// called. If the operation is interrupted, it must start over from the beginning.
+    ///
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    pub fn no_checkpoints(&mut self) -> &mut Self {
+        self.checkpoint_interval = None;
+        self
+    }
+
+    /// The number of objects to rechunk between commits, or `None` if checkpoints are disabled.
+    pub(super) fn interval(&self) -> Option<u64> {
+        self.checkpoint_interval
+    }
+}
+
+/// A summary of the work done by [`KeyRepo::rechunk`].
+///
+/// [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct RechunkReport {
+    pub(super) objects_rechunked: u64,
+    pub(super) objects_skipped: u64,
+    pub(super) bytes_written: u64,
+    pub(super) bytes_superseded: u64,
+}
+
+impl RechunkReport {
+    /// The number of objects which were rewritten with the new chunking method.
+    pub fn objects_rechunked(&self) -> u64 {
+        self.objects_rechunked
+    }
+
+    /// The number of objects which were skipped because a previous invocation already rewrote
+    /// them.
+    pub fn objects_skipped(&self) -> u64 {
+        self.objects_skipped
+    }
+
+    /// The number of bytes in new chunks which were written to the data store.
+    ///
+    /// This doesn't include chunks which were deduplicated against existing data. The space used
+    /// in the data store grows by roughly this much until the old chunks are reclaimed.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// The number of bytes in old chunks which are no longer referenced by any object.
+    ///
+    /// This space isn't reclaimed in the data store until changes are committed and
+    /// [`Commit::clean`] is called.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn bytes_superseded(&self) -> u64 {
+        self.bytes_superseded
+    }
+}
+
+/// The progress of an unfinished rechunk operation, which is persisted in the header.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RechunkProgress {
+    /// The chunking method objects are being rewritten with.
+    pub chunking: Chunking,
+
+    /// The IDs of object handles which have already been rewritten with `chunking`.
+    pub rechunked: HashSet<HandleId>,
+}
+
+impl RechunkProgress {
+    /// Start rechunking a repository with the given `chunking` method.
+    pub fn new(chunking: Chunking) -> Self {
+        Self {
+            chunking,
+            rechunked: HashSet::new(),
+        }
+    }
+}
@@ -1,32 +1,229 @@
 use std::borrow::Borrow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{hash_map, HashMap, HashSet};
 use std::hash::Hash;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::mem;
+use std::ops::RangeBounds;
+#[cfg(feature = "export")]
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 
 use rmp_serde::{from_read, to_vec};
-use secrecy::ExposeSecret;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use static_assertions::assert_impl_all;
 use uuid::{uuid, Uuid};
 
-use crate::store::{BlockKey, BlockType, DataStore};
-
-use super::chunk_store::{
-    EncodeBlock, ReadBlock, ReadChunk, StoreReader, StoreState, StoreWriter, WriteBlock,
+use crate::store::{BlockId, BlockKey, BlockType, Consistency, DataStore};
+
+use super::attributes::{AttributeTable, KeyAttributes};
+use super::audit_log::{AuditEntry, AuditOperation};
+use super::batch::Batch;
+use super::buffer_pool::PoolStats;
+use super::chunk_store::{EncodeBlock, ReadChunk, StoreReader, StoreState, StoreWriter};
+use super::chunking::Chunking;
+use super::commit::{Commit, CommitId, CommitInfo, CommitReport};
+use super::compatibility::{Capability, FormatVersion};
+#[cfg(feature = "compression")]
+use super::compression::{Compression, Dictionary};
+use super::destructive::{DestructivePolicy, DestructiveScope};
+use super::encryption::{Encryption, ResourceLimit};
+use super::entry::{Entry, OccupiedEntry, VacantEntry};
+#[cfg(feature = "export")]
+use super::export::{self, ExportOptions, Exporter};
+use super::format::{
+    self, decode_lock, decode_metadata, deserialize_header, encode_lock, encode_metadata,
+};
+use super::handle::{
+    chunk_hash, Chunk, Extent, HandleId, HandleIdTable, ObjectHandle, ObjectStats,
 };
-use super::commit::Commit;
-use super::encryption::{Encryption, EncryptionKey, KeySalt, ResourceLimit};
-use super::handle::{chunk_hash, HandleIdTable, ObjectHandle};
 use super::key::{Key, Keys};
+use super::key_index::KeyIndex;
 use super::lock::{unlock_store, Unlock};
-use super::metadata::{Header, RepoInfo, RepoStats};
+use super::manifest::{Manifest, ManifestBuilder, ManifestDiff, ManifestSource};
+use super::metadata::{Header, RepoInfo, RepoMetadata, RepoStats};
+use super::migrate::{copy_blocks, MigrateProgress};
 use super::object::Object;
+use super::object_options::ObjectOptions;
 use super::object_store::{ObjectReader, ObjectWriter};
 use super::open_repo::OpenRepo;
 use super::open_repo::VersionId;
 use super::packing::Packing;
+use super::rechunk::{RechunkOptions, RechunkProgress, RechunkReport};
 use super::savepoint::{KeyRestore, RestoreSavepoint, Savepoint};
-use super::state::{InstanceId, InstanceInfo, ObjectState, RepoState};
+use super::snapshot::SnapshotId;
+use super::state::{ChunkInfo, InstanceId, InstanceInfo, ObjectState, RepoState};
+use super::trash::TrashEntry;
+
+/// The estimated size of a chunk and its information in a serialized header.
+const ESTIMATED_CHUNK_SIZE: u64 = 96;
+
+/// The estimated size of a pack entry, not including its indices, in a serialized header.
+const ESTIMATED_PACK_SIZE: u64 = 24;
+
+/// The estimated size of each pack index in a serialized header.
+const ESTIMATED_PACK_INDEX_SIZE: u64 = 32;
+
+/// The estimated size of an instance, not including its extents, in a serialized header.
+const ESTIMATED_INSTANCE_SIZE: u64 = 64;
+
+/// The estimated size of each extent of an object handle in a serialized header.
+const ESTIMATED_EXTENT_SIZE: u64 = 64;
+
+/// The number of bytes of chunks to sample for each byte of a trained dictionary.
+#[cfg(feature = "compression")]
+const DICTIONARY_SAMPLE_RATIO: usize = 100;
+
+/// Whether an unreferenced block can be removed from the data store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Removal {
+    /// The block can be removed.
+    Allowed,
+
+    /// The block is younger than the grace period for stores with eventual consistency.
+    GracePeriod,
+
+    /// The block is younger than the retention period of the store.
+    Retention,
+}
+
+/// Return whether the block with the given `key` is old enough to be removed from the `store`.
+///
+/// When the data store has eventual consistency, a block is only removed once it is older than the
+/// `grace_period`. When the data store has a retention period, a block is only removed once it is
+/// older than the retention period. In either case, a block with an unknown modification time is
+/// never removed.
+fn block_removal(
+    store: &mut dyn DataStore,
+    key: BlockKey,
+    grace_period: Duration,
+) -> crate::Result<Removal> {
+    let grace_period = match store.consistency() {
+        Consistency::Strong => None,
+        Consistency::Eventual => Some(grace_period),
+    };
+    let retention = store.retention();
+    if grace_period.is_none() && retention.is_none() {
+        return Ok(Removal::Allowed);
+    }
+
+    let modified_time = store
+        .block_modified_time(key)
+        .map_err(crate::Error::Store)?;
+    // The age is `None` if the modification time is unknown or in the future.
+    let age = modified_time.and_then(|time| time.elapsed().ok());
+    let is_younger_than = |period: Option<Duration>| match (period, age) {
+        (None, _) => false,
+        (Some(period), Some(age)) => age < period,
+        (Some(_), None) => true,
+    };
+
+    Ok(if is_younger_than(retention) {
+        Removal::Retention
+    } else if is_younger_than(grace_period) {
+        Removal::GracePeriod
+    } else {
+        Removal::Allowed
+    })
+}
+
+/// Read and decode the header with the given `header_id` from the data store.
+fn read_header(state: &RepoState, header_id: BlockId) -> crate::Result<Header> {
+    let encoded_header = state
+        .store
+        .lock()
+        .unwrap()
+        .read_block(BlockKey::Header(header_id))
+        .map_err(crate::Error::Store)?
+        .ok_or(crate::Error::Corrupt)?;
+    let serialized_header = state.decode_data(encoded_header.as_slice())?;
+    deserialize_header(serialized_header.as_slice())
+}
+
+/// A tracker for unreferenced blocks which cannot be removed until their retention period expires.
+struct RetentionTracker {
+    /// The sizes of blocks which were retained the last time the repository was cleaned.
+    known: HashMap<BlockKey, u64>,
+
+    /// The sizes of blocks which have been retained so far.
+    retained: HashMap<BlockKey, u64>,
+
+    /// The grace period for stores with eventual consistency.
+    grace_period: Duration,
+}
+
+impl RetentionTracker {
+    /// Return whether the unreferenced block with the given `key` can be removed from the `store`.
+    ///
+    /// If the block can't be removed because of the store's retention period, this records it.
+    fn is_removable(&mut self, store: &mut dyn DataStore, key: BlockKey) -> crate::Result<bool> {
+        match block_removal(store, key, self.grace_period)? {
+            Removal::Allowed => Ok(true),
+            Removal::GracePeriod => Ok(false),
+            Removal::Retention => {
+                // Avoid reading the block just to get its size. If the store doesn't know its size,
+                // it doesn't count toward the retained size.
+                let size = match self.known.get(&key) {
+                    Some(size) => *size,
+                    None => store
+                        .block_size(key)
+                        .map_err(crate::Error::Store)?
+                        .unwrap_or(0),
+                };
+                self.retained.insert(key, size);
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// The parts of a repository's working state which are changed by removing expired objects.
+///
+/// This is used to put expired objects back if the commit which removed them fails.
+struct ExpiryBackup<K> {
+    objects: HashMap<K, Arc<RwLock<ObjectHandle>>>,
+    attributes: AttributeTable<K>,
+    instances: HashMap<InstanceId, InstanceInfo>,
+    handle_table: HandleIdTable,
+    chunks: HashMap<Chunk, ChunkInfo>,
+    rechunk: Option<RechunkProgress>,
+}
+
+/// Write the contents of the object with the `source` handle to the `dest` handle, splitting it
+/// into chunks with the given `chunking` method.
+///
+/// Chunks are read from `source` one at a time, so the object does not need to fit in memory.
+fn write_rechunked(
+    state: &mut RepoState,
+    source: &ObjectHandle,
+    dest: &mut ObjectHandle,
+    chunking: &Chunking,
+) -> crate::Result<()> {
+    let config = &state.metadata.config;
+    let mut object_state =
+        ObjectState::new(chunking.to_bounded_chunker(config.min_chunk_size, config.max_chunk_size));
+    let mut store_state = StoreState::new();
+
+    for extent in &source.extents {
+        match extent {
+            Extent::Chunk(chunk) => {
+                let data = StoreReader::new(state, &mut store_state).read_chunk(*chunk)?;
+                ObjectWriter::new(state, &mut object_state, dest).write_all(&data)?;
+            }
+            Extent::Hole { size } => {
+                // Holes don't contain any chunks, so we copy them as-is by extending the object.
+                ObjectWriter::new(state, &mut object_state, dest).commit()?;
+                let new_size = dest.size() + size;
+                let mut writer = ObjectWriter::new(state, &mut object_state, dest);
+                writer.set_len(new_size)?;
+                writer.seek(SeekFrom::End(0))?;
+            }
+        }
+    }
+
+    ObjectWriter::new(state, &mut object_state, dest).commit()
+}
 
 /// An object store which maps keys to seekable binary blobs.
 ///
@@ -42,6 +239,15 @@ pub struct KeyRepo<K: Key> {
     /// A map of object keys to their object handles for the current instance.
     pub(super) objects: HashMap<K, Arc<RwLock<ObjectHandle>>>,
 
+    /// A map of object keys to objects which have been moved to the trash for the current instance.
+    pub(super) trash: HashMap<K, TrashEntry>,
+
+    /// The metadata and tags of objects in the current instance.
+    pub(super) attributes: AttributeTable<K>,
+
+    /// An ordered index of the keys of objects in the current instance.
+    pub(super) key_index: KeyIndex<K>,
+
     /// A map of instance IDs to information about those instances.
     pub(super) instances: HashMap<InstanceId, InstanceInfo>,
 
@@ -94,26 +300,142 @@ impl<K: Key> KeyRepo<K> {
         self.objects.contains_key(key)
     }
 
+    /// Return the number of objects in this repository.
+    ///
+    /// Objects in the trash are not counted.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Return whether there are no objects in this repository.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Return statistics about the object with the given `key`.
+    ///
+    /// This is the same as [`Object::stats`], but it doesn't require creating an `Object`. If the
+    /// object is being written to, this returns its statistics as of the last time it was
+    /// flushed. This returns `None` if there is no object with the given `key`.
+    ///
+    /// [`Object::stats`]: crate::repo::Object::stats
+    pub fn object_stats<Q>(&self, key: &Q) -> Option<ObjectStats>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let handle = self.objects.get(key)?;
+        let stats = handle.read().unwrap().stats();
+        Some(stats)
+    }
+
     /// Add a new object with the given `key` to the repository and return it.
     ///
-    /// If another object with the same `key` already exists, it is replaced.
+    /// If another object with the same `key` already exists, it is replaced. Replacing an object
+    /// does not count towards the thresholds of the [`DestructivePolicy`]. If the replaced object
+    /// had an expiration time, the new object does not.
+    ///
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
     pub fn insert(&mut self, key: K) -> Object {
-        self.remove(&key);
+        let handle = self.insert_empty_handle(key);
+        Object::new(&self.state, &handle)
+    }
+
+    /// Add a new empty object handle with the given `key`, replacing any existing object.
+    fn insert_empty_handle(&mut self, key: K) -> Arc<RwLock<ObjectHandle>> {
+        if let Some(handle) = self.objects.remove(&key) {
+            self.remove_handle(&handle.read().unwrap());
+        }
+        self.attributes.remove_expires(&key);
         let handle_id = self.handle_table.next();
         let handle = ObjectHandle {
             id: handle_id,
             extents: Vec::new(),
         };
         assert!(!self.objects.contains_key(&key));
-        let handle = self
-            .objects
-            .entry(key)
-            .or_insert_with(|| Arc::new(RwLock::new(handle)));
-        Object::new(&self.state, handle)
+        self.key_index.touch(&key);
+        Arc::clone(
+            self.objects
+                .entry(key)
+                .or_insert_with(|| Arc::new(RwLock::new(handle))),
+        )
+    }
+
+    /// Add a new object with the given `key` which is written using `options` and return it.
+    ///
+    /// This is like [`insert`], except data written through the returned object uses the
+    /// compression and chunking methods in `options` instead of the repository's. Other `Object`
+    /// instances for the same object, including ones returned by [`object`], use the repository's
+    /// settings.
+    ///
+    /// # Errors
+    /// - `Error::Incompatible`: The compression method in `options` differs from the
+    /// repository's and the repository's [`RepoConfig::compatibility_target`] doesn't support
+    /// per-object compression.
+    ///
+    /// [`insert`]: crate::repo::key::KeyRepo::insert
+    /// [`object`]: crate::repo::key::KeyRepo::object
+    /// [`RepoConfig::compatibility_target`]: crate::repo::RepoConfig::compatibility_target
+    pub fn insert_with_options(
+        &mut self,
+        key: K,
+        options: &ObjectOptions,
+    ) -> crate::Result<Object> {
+        {
+            let state = self.state.read().unwrap();
+            let config = &state.metadata.config;
+            if options
+                .compression_method()
+                .is_some_and(|compression| *compression != config.compression)
+            {
+                Capability::ObjectCompression.check(config.compatibility_target)?;
+            }
+        }
+
+        let handle = self.insert_empty_handle(key);
+        Ok(Object::with_options(&self.state, &handle, options))
+    }
+
+    /// Return the entry for the object with the given `key` for in-place manipulation.
+    ///
+    /// This can be used to get an object or insert it if it doesn't exist while only looking up
+    /// its key once.
+    pub fn entry(&mut self, key: K) -> Entry<K> {
+        match self.objects.entry(key) {
+            hash_map::Entry::Occupied(entry) => Entry::Occupied(OccupiedEntry {
+                state: &self.state,
+                entry,
+            }),
+            hash_map::Entry::Vacant(entry) => Entry::Vacant(VacantEntry {
+                state: &self.state,
+                handle_table: &mut self.handle_table,
+                key_index: &mut self.key_index,
+                entry,
+            }),
+        }
+    }
+
+    /// Add a new object with the given `key` which expires after `ttl` and return it.
+    ///
+    /// This is like [`insert`], except the object is removed by [`expire`] once `ttl` has passed.
+    /// Expired objects are also removed automatically when changes are committed. Until then, an
+    /// expired object can still be accessed like any other object.
+    ///
+    /// If `ttl` is so large that the expiration time can't be represented, the object never
+    /// expires.
+    ///
+    /// [`insert`]: crate::repo::key::KeyRepo::insert
+    /// [`expire`]: crate::repo::key::KeyRepo::expire
+    pub fn insert_with_ttl(&mut self, key: K, ttl: Duration) -> Object {
+        let object = self.insert(key.clone());
+        if let Some(expires) = SystemTime::now().checked_add(ttl) {
+            self.attributes.set_expires(key, expires);
+        }
+        object
     }
 
     /// Remove the given object `handle` from the repository.
-    fn remove_handle(&mut self, handle: &ObjectHandle) {
+    pub(super) fn remove_handle(&mut self, handle: &ObjectHandle) {
         let mut state = self.state.write().unwrap();
         for chunk in handle.chunks() {
             let chunk_info = state
@@ -125,9 +447,48 @@ impl<K: Key> KeyRepo<K> {
                 state.chunks.remove(&chunk);
             }
         }
+
+        // The ID may be recycled for an object which hasn't been rechunked yet.
+        if let Some(progress) = &mut state.rechunk {
+            progress.rechunked.remove(&handle.id);
+        }
+
         self.handle_table.recycle(handle.id);
     }
 
+    /// Remove the attributes of the object with the given `key`, including its snapshots.
+    fn remove_attributes<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        for handle in self.attributes.remove(key) {
+            self.remove_handle(&handle);
+        }
+    }
+
+    /// Return a new handle which shares the contents of `handle`.
+    ///
+    /// This is a cheap operation which does not require copying the bytes in the object.
+    fn copy_handle(&mut self, handle: &ObjectHandle) -> ObjectHandle {
+        let new_handle = ObjectHandle {
+            id: self.handle_table.next(),
+            extents: handle.extents.clone(),
+        };
+
+        // Update the chunk map to include the new handle in the list of references for each chunk.
+        let mut state = self.state.write().unwrap();
+        for chunk in new_handle.chunks() {
+            let chunk_info = state
+                .chunks
+                .get_mut(&chunk)
+                .expect("This chunk was not found in the repository.");
+            chunk_info.references.insert(new_handle.id);
+        }
+
+        new_handle
+    }
+
     /// Remove the object with the given `key` from the repository.
     ///
     /// This returns `true` if the object was removed or `false` if it didn't exist.
@@ -135,21 +496,335 @@ impl<K: Key> KeyRepo<K> {
     /// The space used by the given object isn't reclaimed in the backing data store until changes
     /// are committed and [`Commit::clean`] is called.
     ///
+    /// Removed objects count towards the thresholds of the [`DestructivePolicy`]. The metadata,
+    /// tags, and snapshots of the object are removed as well.
+    ///
     /// [`Commit::clean`]: crate::repo::Commit::clean
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
     pub fn remove<Q>(&mut self, key: &Q) -> bool
     where
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
-        let handle = match self.objects.remove(key) {
-            Some(handle) => handle,
+        let (key, handle) = match self.objects.remove_entry(key) {
+            Some(entry) => entry,
             None => return false,
         };
+        self.remove_attributes(&key);
+        self.key_index.touch(&key);
         let handle_guard = handle.read().unwrap();
         self.remove_handle(&handle_guard);
+        self.state
+            .write()
+            .unwrap()
+            .interlock
+            .record(DestructiveScope::Remove, handle_guard.size());
         true
     }
 
+    /// Move the object with the given `key` to the trash.
+    ///
+    /// This returns `true` if the object was moved to the trash or `false` if it didn't exist.
+    ///
+    /// Unlike [`remove`], the data in the object is retained, and the object can be restored with
+    /// [`restore_from_trash`] until it is purged with [`purge_trash`]. Objects in the trash are not
+    /// visible to [`contains`], [`object`], or [`keys`]. If an object with the same `key` is
+    /// already in the trash, it is purged and replaced. The metadata, tags, and snapshots of the
+    /// object are not retained.
+    ///
+    /// Any existing `Object` instances for this object are invalidated.
+    ///
+    /// # Errors
+    /// - `Error::Incompatible`: The repository's compatibility target doesn't support the trash.
+    ///
+    /// [`remove`]: crate::repo::key::KeyRepo::remove
+    /// [`restore_from_trash`]: crate::repo::key::KeyRepo::restore_from_trash
+    /// [`purge_trash`]: crate::repo::key::KeyRepo::purge_trash
+    /// [`contains`]: crate::repo::key::KeyRepo::contains
+    /// [`object`]: crate::repo::key::KeyRepo::object
+    /// [`keys`]: crate::repo::key::KeyRepo::keys
+    pub fn remove_to_trash<Q>(&mut self, key: &Q) -> crate::Result<bool>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        Capability::Trash.check(
+            self.state
+                .read()
+                .unwrap()
+                .metadata
+                .config
+                .compatibility_target,
+        )?;
+
+        let (key, handle) = match self.objects.remove_entry(key) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+        self.remove_attributes(&key);
+        self.key_index.touch(&key);
+        let handle = handle.read().unwrap().clone();
+
+        let entry = TrashEntry {
+            handle,
+            deleted: SystemTime::now(),
+        };
+        if let Some(old_entry) = self.trash.insert(key, entry) {
+            self.remove_handle(&old_entry.handle);
+        }
+
+        Ok(true)
+    }
+
+    /// Restore the object with the given `key` from the trash.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key` in the trash.
+    /// - `Error::AlreadyExists`: There is already an object with the given `key` in the
+    /// repository.
+    pub fn restore_from_trash<Q>(&mut self, key: &Q) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if !self.trash.contains_key(key) {
+            return Err(crate::Error::NotFound);
+        }
+        if self.objects.contains_key(key) {
+            return Err(crate::Error::AlreadyExists);
+        }
+        let (key, entry) = self.trash.remove_entry(key).unwrap();
+        self.key_index.touch(&key);
+        self.objects
+            .insert(key, Arc::new(RwLock::new(entry.handle)));
+        Ok(())
+    }
+
+    /// Permanently remove objects which were moved to the trash before `older_than`.
+    ///
+    /// This returns the number of objects which were purged. To purge every object in the trash,
+    /// pass [`SystemTime::now`].
+    ///
+    /// The space used by purged objects isn't reclaimed in the backing data store until changes
+    /// are committed and [`Commit::clean`] is called.
+    ///
+    /// If the [`DestructivePolicy`] is enabled, purging objects must be authorized with
+    /// [`authorize_destructive`] before changes can be committed.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    /// [`authorize_destructive`]: crate::repo::key::KeyRepo::authorize_destructive
+    pub fn purge_trash(&mut self, older_than: SystemTime) -> usize {
+        let purged_entries = self
+            .trash
+            .iter()
+            .filter(|(_, entry)| entry.deleted <= older_than)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|key| self.trash.remove(&key))
+            .collect::<Vec<_>>();
+        for entry in &purged_entries {
+            self.remove_handle(&entry.handle);
+            self.state
+                .write()
+                .unwrap()
+                .interlock
+                .record(DestructiveScope::PurgeTrash, entry.handle.size());
+        }
+        purged_entries.len()
+    }
+
+    /// Return an iterator over the keys of objects in the trash and the times they were moved to
+    /// the trash.
+    pub fn list_trash(&self) -> impl Iterator<Item = (&K, SystemTime)> {
+        self.trash.iter().map(|(key, entry)| (key, entry.deleted))
+    }
+
+    /// Attach serializable metadata to the object with the given `key`.
+    ///
+    /// Each object can have one metadata value, which replaces any existing metadata. Metadata is
+    /// stored separately from the contents of the object, and it's kept when the object is
+    /// replaced with [`insert`]. It's removed along with the object.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key` in the repository.
+    /// - `Error::Serialize`: The `value` could not be serialized.
+    ///
+    /// [`insert`]: crate::repo::key::KeyRepo::insert
+    pub fn set_metadata<Q, V>(&mut self, key: &Q, value: &V) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: Serialize + ?Sized,
+    {
+        let (key, _) = self
+            .objects
+            .get_key_value(key)
+            .ok_or(crate::Error::NotFound)?;
+        let metadata = to_vec(value).map_err(|_| crate::Error::Serialize)?;
+        self.attributes.set_metadata(key.clone(), metadata);
+        Ok(())
+    }
+
+    /// Return the metadata attached to the object with the given `key`.
+    ///
+    /// This returns `None` if the object has no metadata.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key` in the repository.
+    /// - `Error::Deserialize`: The metadata could not be deserialized as a value of type `V`.
+    pub fn get_metadata<Q, V>(&self, key: &Q) -> crate::Result<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: DeserializeOwned,
+    {
+        if !self.objects.contains_key(key) {
+            return Err(crate::Error::NotFound);
+        }
+        self.attributes
+            .metadata(key)
+            .map(|metadata| from_read(metadata).map_err(|_| crate::Error::Deserialize))
+            .transpose()
+    }
+
+    /// Remove the metadata attached to the object with the given `key`.
+    ///
+    /// This returns `true` if the metadata was removed or `false` if there was none.
+    pub fn remove_metadata<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.attributes.remove_metadata(key)
+    }
+
+    /// Add a `tag` to the object with the given `key`.
+    ///
+    /// Objects can have any number of tags, and you can efficiently find the objects with a given
+    /// tag using [`keys_with_tag`]. Like metadata, tags are kept when the object is replaced with
+    /// [`insert`], and they're removed along with the object.
+    ///
+    /// This returns `true` if the tag was added or `false` if the object already had it.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key` in the repository.
+    ///
+    /// [`keys_with_tag`]: crate::repo::key::KeyRepo::keys_with_tag
+    /// [`insert`]: crate::repo::key::KeyRepo::insert
+    pub fn add_tag<Q>(&mut self, key: &Q, tag: impl Into<String>) -> crate::Result<bool>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (key, _) = self
+            .objects
+            .get_key_value(key)
+            .ok_or(crate::Error::NotFound)?;
+        Ok(self.attributes.add_tag(key.clone(), tag.into()))
+    }
+
+    /// Remove a `tag` from the object with the given `key`.
+    ///
+    /// This returns `true` if the tag was removed or `false` if the object didn't have it.
+    pub fn remove_tag<Q>(&mut self, key: &Q, tag: &str) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.attributes.remove_tag(key, tag)
+    }
+
+    /// Return an iterator over the tags of the object with the given `key`.
+    ///
+    /// Tags are returned in sorted order. If there is no object with the given `key`, this returns
+    /// an empty iterator.
+    pub fn tags<Q>(&self, key: &Q) -> impl Iterator<Item = &str>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.attributes.tags(key)
+    }
+
+    /// Return an iterator over the keys of objects which have the given `tag`.
+    ///
+    /// This uses an index of tags, so it doesn't need to check every object in the repository.
+    pub fn keys_with_tag(&self, tag: &str) -> impl Iterator<Item = &K> {
+        self.attributes.keys_with_tag(tag)
+    }
+
+    /// Return the time at which the object with the given `key` expires.
+    ///
+    /// This returns `None` if the object doesn't exist or if it was not inserted with
+    /// [`insert_with_ttl`].
+    ///
+    /// [`insert_with_ttl`]: crate::repo::key::KeyRepo::insert_with_ttl
+    pub fn expires_at<Q>(&self, key: &Q) -> Option<SystemTime>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.attributes.expires(key)
+    }
+
+    /// Remove every object which has expired.
+    ///
+    /// This returns the number of objects which were removed. This is called automatically when
+    /// changes are committed, so it only needs to be called directly to remove expired objects
+    /// before then.
+    ///
+    /// Removing expired objects does not count towards the thresholds of the
+    /// [`DestructivePolicy`]. The space used by expired objects isn't reclaimed in the backing data
+    /// store until changes are committed and [`Commit::clean`] is called.
+    ///
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn expire(&mut self) -> usize {
+        let expired_keys = self.attributes.expired(SystemTime::now());
+        self.remove_expired(&expired_keys);
+        expired_keys.len()
+    }
+
+    /// Remove the objects with the given `expired_keys`.
+    fn remove_expired(&mut self, expired_keys: &[K]) {
+        for key in expired_keys {
+            self.remove_attributes(key);
+            self.key_index.touch(key);
+            if let Some(handle) = self.objects.remove(key) {
+                self.remove_handle(&handle.read().unwrap());
+            }
+        }
+    }
+
+    /// Return a backup of the working state which is changed by removing expired objects.
+    fn expiry_backup(&self) -> ExpiryBackup<K> {
+        let state = self.state.read().unwrap();
+        ExpiryBackup {
+            objects: self.objects.clone(),
+            attributes: self.attributes.clone(),
+            instances: self.instances.clone(),
+            handle_table: self.handle_table.clone(),
+            chunks: state.chunks.clone(),
+            rechunk: state.rechunk.clone(),
+        }
+    }
+
+    /// Put back the objects with the given `expired_keys` from `backup`.
+    fn restore_expired(&mut self, backup: ExpiryBackup<K>, expired_keys: &[K]) {
+        let mut state = self.state.write().unwrap();
+        self.objects = backup.objects;
+        self.attributes = backup.attributes;
+        self.instances = backup.instances;
+        self.handle_table = backup.handle_table;
+        state.chunks = backup.chunks;
+        state.rechunk = backup.rechunk;
+        for key in expired_keys {
+            self.key_index.touch(key);
+        }
+    }
+
     /// Return an object for reading and writing the object with the given `key`.
     ///
     /// This returns `None` if there is no object with the given `key` in the repository.
@@ -167,46 +842,482 @@ impl<K: Key> KeyRepo<K> {
         Keys(self.objects.keys())
     }
 
+    /// Return an iterator over the keys of objects in this repository which fall within `range`.
+    ///
+    /// The keys are returned in sorted order. This uses an ordered index of keys, so it doesn't
+    /// need to check every object in the repository. The index is kept in memory; it is built the
+    /// first time it is needed and kept up to date as objects are added and removed.
+    pub fn range<Q, R>(&self, range: R) -> impl Iterator<Item = &K>
+    where
+        K: Ord + Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.key_index.range(&self.objects, range).into_iter()
+    }
+
+    /// Return an iterator over the keys of objects in this repository which start with `prefix`.
+    ///
+    /// The keys are returned in sorted order. This is useful for listing path-like keys
+    /// hierarchically. Like [`range`], this uses an ordered index of keys.
+    ///
+    /// [`range`]: crate::repo::key::KeyRepo::range
+    pub fn keys_with_prefix(&self, prefix: &str) -> impl Iterator<Item = &K>
+    where
+        K: Ord + Borrow<str>,
+    {
+        self.key_index
+            .with_prefix(&self.objects, prefix)
+            .into_iter()
+    }
+
     /// Copy the object at `source` to `dest`.
     ///
     /// If another object already exists at `dest`, it is replaced.
     ///
     /// This returns `true` if the object was copied or `false` if there was no object at source.
     ///
-    /// This is a cheap operation which does not require copying the bytes in the object.
+    /// This is a cheap operation which does not require copying the bytes in the object. The copy
+    /// shares its chunks with the original, but the two objects are independent; writing to one
+    /// of them writes new chunks rather than modifying shared ones, so it never changes the other.
+    /// Use [`shared_chunk_count`] to see how many chunks two objects share.
+    ///
+    /// [`shared_chunk_count`]: crate::repo::key::KeyRepo::shared_chunk_count
     pub fn copy<Q>(&mut self, source: &Q, dest: K) -> bool
     where
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
-        let source_chunks = match self.objects.get(source) {
-            Some(handle) => handle.read().unwrap().extents.clone(),
+        let source_handle = match self.objects.get(source) {
+            Some(handle) => handle.read().unwrap().clone(),
             None => return false,
         };
 
         self.remove(dest.borrow());
 
-        let dest_handle = ObjectHandle {
-            id: self.handle_table.next(),
-            extents: source_chunks,
-        };
-
-        // Update the chunk map to include the new handle in the list of references for each chunk.
-        let mut state = self.state.write().unwrap();
-        for chunk in dest_handle.chunks() {
-            let chunk_info = state
-                .chunks
-                .get_mut(&chunk)
-                .expect("This chunk was not found in the repository.");
-            chunk_info.references.insert(dest_handle.id);
-        }
+        let dest_handle = self.copy_handle(&source_handle);
 
+        self.key_index.touch(&dest);
         self.objects
             .insert(dest, Arc::new(RwLock::new(dest_handle)));
 
         true
     }
 
+    /// Return the number of chunks which are shared by the objects at `first` and `second`.
+    ///
+    /// Objects share chunks when one is a [`copy`] of the other or when they contain the same data,
+    /// since identical chunks are only stored once. Each distinct chunk is counted once, even if it
+    /// appears more than once in either object.
+    ///
+    /// This returns `None` if there is no object at `first` or `second`.
+    ///
+    /// [`copy`]: crate::repo::key::KeyRepo::copy
+    pub fn shared_chunk_count<Q>(&self, first: &Q, second: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let first_handle = self.objects.get(first)?;
+        let second_handle = self.objects.get(second)?;
+        let first_chunks = first_handle
+            .read()
+            .unwrap()
+            .chunks()
+            .collect::<HashSet<_>>();
+        let shared_chunks = second_handle
+            .read()
+            .unwrap()
+            .chunks()
+            .filter(|chunk| first_chunks.contains(chunk))
+            .collect::<HashSet<_>>();
+        Some(shared_chunks.len())
+    }
+
+    /// Move the object at `source` to `dest`.
+    ///
+    /// The object keeps its contents, metadata, tags, and expiration time. This is a cheap
+    /// operation which does not require copying the bytes in the object, and unlike a [`copy`]
+    /// followed by a [`remove`], there is never a point where the object exists at both keys.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object at `source`.
+    /// - `Error::AlreadyExists`: There is already an object at `dest`.
+    ///
+    /// [`copy`]: crate::repo::key::KeyRepo::copy
+    /// [`remove`]: crate::repo::key::KeyRepo::remove
+    pub fn rename<Q>(&mut self, source: &Q, dest: K) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if !self.objects.contains_key(source) {
+            return Err(crate::Error::NotFound);
+        }
+        if self.objects.contains_key(&dest) {
+            return Err(crate::Error::AlreadyExists);
+        }
+        let (source, handle) = self.objects.remove_entry(source).unwrap();
+        self.attributes.rename(&source, dest.clone());
+        self.key_index.touch(&source);
+        self.key_index.touch(&dest);
+        self.objects.insert(dest, handle);
+        Ok(())
+    }
+
+    /// Exchange the objects at `first` and `second`.
+    ///
+    /// Each object keeps its contents, metadata, tags, expiration time, and snapshots, so this is
+    /// like renaming each object to the other's key in one step. This is a cheap operation which
+    /// does not require copying the bytes in either object.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object at `first` or `second`.
+    pub fn swap<Q>(&mut self, first: &Q, second: &Q) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if !self.objects.contains_key(first) || !self.objects.contains_key(second) {
+            return Err(crate::Error::NotFound);
+        }
+        if first == second {
+            return Ok(());
+        }
+        let (first, first_handle) = self.objects.remove_entry(first).unwrap();
+        let (second, second_handle) = self.objects.remove_entry(second).unwrap();
+        self.attributes.swap(&first, &second);
+        self.objects.insert(first, second_handle);
+        self.objects.insert(second, first_handle);
+        Ok(())
+    }
+
+    /// Replace the object at `key` with the object at `source`.
+    ///
+    /// The object at `source` is moved to `key`, keeping its contents, metadata, tags, expiration
+    /// time, and snapshots, and the object which was at `key` is removed. Unlike a [`remove`]
+    /// followed by a [`rename`], there is never a point where there is no object at `key`. This can
+    /// be used to write an object to a temporary key and then move it over the original once it's
+    /// complete.
+    ///
+    /// Like replacing an object with [`insert`], this does not count towards the thresholds of the
+    /// [`DestructivePolicy`].
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object at `key` or `source`.
+    ///
+    /// [`remove`]: crate::repo::key::KeyRepo::remove
+    /// [`rename`]: crate::repo::key::KeyRepo::rename
+    /// [`insert`]: crate::repo::key::KeyRepo::insert
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    pub fn replace<Q>(&mut self, key: &Q, source: &Q) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if !self.objects.contains_key(key) || !self.objects.contains_key(source) {
+            return Err(crate::Error::NotFound);
+        }
+        if key == source {
+            return Ok(());
+        }
+        let (source, source_handle) = self.objects.remove_entry(source).unwrap();
+        self.remove_attributes(key);
+        let (key, old_handle) = self.objects.remove_entry(key).unwrap();
+        self.remove_handle(&old_handle.read().unwrap());
+        self.attributes.rename(&source, key.clone());
+        self.key_index.touch(&source);
+        self.objects.insert(key, source_handle);
+        Ok(())
+    }
+
+    /// Take a snapshot of the current contents of the object with the given `key`.
+    ///
+    /// The object can later be returned to the contents it had when the snapshot was taken with
+    /// [`restore_snapshot`]. Changes to the object which haven't been committed with
+    /// [`Object::commit`] are not included in the snapshot.
+    ///
+    /// This is a cheap operation which does not require copying the bytes in the object. Snapshots
+    /// are removed along with the object.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key` in the repository.
+    ///
+    /// [`restore_snapshot`]: crate::repo::key::KeyRepo::restore_snapshot
+    /// [`Object::commit`]: crate::repo::Object::commit
+    pub fn snapshot<Q>(&mut self, key: &Q) -> crate::Result<SnapshotId>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (key, handle) = self
+            .objects
+            .get_key_value(key)
+            .ok_or(crate::Error::NotFound)?;
+        let key = key.clone();
+        let handle = handle.read().unwrap().clone();
+        let snapshot_handle = self.copy_handle(&handle);
+        let id = SnapshotId::new();
+        self.attributes.add_snapshot(key, id, snapshot_handle);
+        Ok(id)
+    }
+
+    /// Return the contents of the object with the given `key` to the snapshot with the given `id`.
+    ///
+    /// Existing `Object` instances for this object see the restored contents. The snapshot itself
+    /// is not removed, so it can be restored again.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no snapshot of the object with the given `key` with the given
+    /// `id`.
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    pub fn restore_snapshot<Q>(&mut self, key: &Q, id: SnapshotId) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let handle = Arc::clone(self.objects.get(key).ok_or(crate::Error::NotFound)?);
+        let snapshot_handle = self
+            .attributes
+            .snapshot(key, id)
+            .ok_or(crate::Error::NotFound)?
+            .clone();
+
+        // Hold a transaction lock on the object so that no other `Object` can modify it while its
+        // contents are replaced.
+        let handle_id = handle.read().unwrap().id;
+        let _transaction_lock = self
+            .state
+            .write()
+            .unwrap()
+            .transactions
+            .acquire_lock(handle_id)
+            .ok_or(crate::Error::TransactionInProgress)?;
+
+        let new_handle = self.copy_handle(&snapshot_handle);
+        let old_handle = mem::replace(&mut *handle.write().unwrap(), new_handle);
+        self.remove_handle(&old_handle);
+
+        Ok(())
+    }
+
+    /// Remove the snapshot of the object with the given `key` with the given `id`.
+    ///
+    /// This returns `true` if the snapshot was removed or `false` if it didn't exist.
+    pub fn remove_snapshot<Q>(&mut self, key: &Q, id: SnapshotId) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.attributes.remove_snapshot(key, id) {
+            Some(handle) => {
+                self.remove_handle(&handle);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return an iterator over the IDs of the snapshots of the object with the given `key`.
+    ///
+    /// Snapshots are returned from oldest to newest. If the object doesn't exist, this returns an
+    /// empty iterator.
+    pub fn snapshots<Q>(&self, key: &Q) -> impl Iterator<Item = SnapshotId> + '_
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.attributes.snapshots(key)
+    }
+
+    /// Return a new [`Batch`] for making several changes to this repository atomically.
+    ///
+    /// See [`Batch`] for details.
+    ///
+    /// [`Batch`]: crate::repo::key::Batch
+    pub fn batch(&mut self) -> Batch<K> {
+        Batch::new(self)
+    }
+
+    /// Associate the given `handle` with `key`, replacing any existing object.
+    pub(super) fn insert_handle(&mut self, key: K, handle: ObjectHandle) {
+        if let Some(old_handle) = self.objects.remove(&key) {
+            self.remove_handle(&old_handle.read().unwrap());
+        }
+        self.attributes.remove_expires(&key);
+        self.key_index.touch(&key);
+        self.objects.insert(key, Arc::new(RwLock::new(handle)));
+    }
+
+    /// Write `data` to a new object handle which is not yet associated with a key.
+    ///
+    /// If this fails, any data which was written is removed from the repository.
+    pub(super) fn write_new_handle(&mut self, data: &[u8]) -> crate::Result<ObjectHandle> {
+        let handle = Arc::new(RwLock::new(ObjectHandle {
+            id: self.handle_table.next(),
+            extents: Vec::new(),
+        }));
+
+        let mut object = Object::new(&self.state, &handle);
+        let result = object
+            .write_all(data)
+            .map_err(crate::Error::from)
+            .and_then(|_| object.commit());
+        drop(object);
+
+        let handle = Arc::try_unwrap(handle)
+            .expect("The temporary object handle is still referenced.")
+            .into_inner()
+            .unwrap();
+
+        match result {
+            Ok(()) => Ok(handle),
+            Err(error) => {
+                self.remove_handle(&handle);
+                Err(error)
+            }
+        }
+    }
+
+    /// Atomically replace the contents of the object with the given `handle` using `f`.
+    ///
+    /// `f` is passed the current contents of the object, or `None` if `handle` is `None`. The new
+    /// contents are written to a new object handle, and the object's handle is only replaced once
+    /// they have been written successfully. This returns the new handle if `handle` is `None`.
+    fn update_handle<F>(
+        &mut self,
+        handle: Option<&Arc<RwLock<ObjectHandle>>>,
+        f: F,
+    ) -> crate::Result<Option<ObjectHandle>>
+    where
+        F: FnOnce(Option<Vec<u8>>) -> crate::Result<Vec<u8>>,
+    {
+        let handle = match handle {
+            Some(handle) => handle,
+            None => {
+                let new_data = f(None)?;
+                return self.write_new_handle(&new_data).map(Some);
+            }
+        };
+
+        // Hold a transaction lock on the object for the duration of the update so that no other
+        // `Object` can modify it in the meantime.
+        let handle_id = handle.read().unwrap().id;
+        let _transaction_lock = self
+            .state
+            .write()
+            .unwrap()
+            .transactions
+            .acquire_lock(handle_id)
+            .ok_or(crate::Error::TransactionInProgress)?;
+
+        let mut old_data = Vec::new();
+        Object::new(&self.state, handle).read_to_end(&mut old_data)?;
+
+        let new_data = f(Some(old_data))?;
+        let new_handle = self.write_new_handle(&new_data)?;
+
+        // Swap the new handle into place so that existing `Object` instances see the new contents,
+        // and then release the old data.
+        let old_handle = mem::replace(&mut *handle.write().unwrap(), new_handle);
+        self.remove_handle(&old_handle);
+
+        Ok(None)
+    }
+
+    /// Atomically replace the contents of the object with the given `key` using `f`.
+    ///
+    /// `f` is passed the current contents of the object and returns the new contents. The object is
+    /// overwritten and truncated to the length of the new contents.
+    ///
+    /// The new contents are written separately from the existing contents of the object, and the
+    /// object is only changed once they have been written successfully. If `f` returns an error or
+    /// writing the new contents fails, the object is left unchanged and the error is returned.
+    ///
+    /// This does not commit changes to the repository.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key` in the repository.
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    /// - Any error returned by `f`.
+    pub fn update<Q, F>(&mut self, key: &Q, f: F) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        F: FnOnce(Vec<u8>) -> crate::Result<Vec<u8>>,
+    {
+        let handle = Arc::clone(self.objects.get(key).ok_or(crate::Error::NotFound)?);
+        self.update_handle(Some(&handle), |data| {
+            f(data.expect("The object has no contents."))
+        })?;
+        Ok(())
+    }
+
+    /// Atomically replace the contents of the object with the given `key` using `f`, creating the
+    /// object if it doesn't exist.
+    ///
+    /// This is like [`update`], except `f` is passed `None` if there is no object with the given
+    /// `key`, in which case a new object is created with the contents returned by `f`.
+    ///
+    /// This does not commit changes to the repository.
+    ///
+    /// # Errors
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    /// - Any error returned by `f`.
+    ///
+    /// [`update`]: crate::repo::key::KeyRepo::update
+    pub fn upsert<F>(&mut self, key: K, f: F) -> crate::Result<()>
+    where
+        F: FnOnce(Option<Vec<u8>>) -> crate::Result<Vec<u8>>,
+    {
+        let handle = self.objects.get(&key).map(Arc::clone);
+        if let Some(new_handle) = self.update_handle(handle.as_ref(), f)? {
+            self.key_index.touch(&key);
+            self.objects.insert(key, Arc::new(RwLock::new(new_handle)));
+        }
+        Ok(())
+    }
+
+    /// Atomically replace the value stored in the object with the given `key` using `f`.
+    ///
+    /// This is a convenience function which deserializes the value in the object like
+    /// [`Object::deserialize`], passes it to `f`, and serializes the value returned by `f` back to
+    /// the object like [`Object::serialize`]. It has the same guarantees as [`update`].
+    ///
+    /// This does not commit changes to the repository.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key` in the repository.
+    /// - `Error::Deserialize`: The data could not be deserialized as a value of type `T`.
+    /// - `Error::Serialize`: The new value could not be serialized.
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    /// - Any error returned by `f`.
+    ///
+    /// [`Object::deserialize`]: crate::repo::Object::deserialize
+    /// [`Object::serialize`]: crate::repo::Object::serialize
+    /// [`update`]: crate::repo::key::KeyRepo::update
+    pub fn update_value<Q, T, F>(&mut self, key: &Q, f: F) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(T) -> crate::Result<T>,
+    {
+        self.update(key, |data| {
+            let value = from_read(data.as_slice()).map_err(|_| crate::Error::Deserialize)?;
+            to_vec(&f(value)?).map_err(|_| crate::Error::Serialize)
+        })
+    }
+
     /// Write the map of objects for the current instance to the data store.
     pub(super) fn write_object_map(&mut self) -> crate::Result<()> {
         let mut state = self.state.write().unwrap();
@@ -217,9 +1328,42 @@ impl<K: Key> KeyRepo<K> {
             .expect("There is no instance with the given ID.")
             .objects;
 
-        let mut object_state = ObjectState::new(state.metadata.config.chunking.to_chunker());
+        let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
         let mut writer = ObjectWriter::new(&mut state, &mut object_state, handle);
-        writer.serialize(&self.objects)
+        writer.serialize(&self.objects)?;
+
+        let instance_info = self
+            .instances
+            .get_mut(&self.instance_id)
+            .expect("There is no instance with the given ID.");
+
+        // Don't allocate an object for the trash until an object has been moved to the trash.
+        if !self.trash.is_empty() || instance_info.trash.is_some() {
+            let handle = instance_info.trash.get_or_insert_with(|| ObjectHandle {
+                id: self.handle_table.next(),
+                extents: Vec::new(),
+            });
+
+            let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
+            let mut writer = ObjectWriter::new(&mut state, &mut object_state, handle);
+            writer.serialize(&self.trash)?;
+        }
+
+        // Don't allocate an object for the attributes until an object has metadata or tags.
+        if !self.attributes.is_empty() || instance_info.attributes.is_some() {
+            let handle = instance_info
+                .attributes
+                .get_or_insert_with(|| ObjectHandle {
+                    id: self.handle_table.next(),
+                    extents: Vec::new(),
+                });
+
+            let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
+            let mut writer = ObjectWriter::new(&mut state, &mut object_state, handle);
+            writer.serialize(self.attributes.entries())?;
+        }
+
+        Ok(())
     }
 
     /// Read the object map for the current instance from the data store and return it.
@@ -232,8 +1376,7 @@ impl<K: Key> KeyRepo<K> {
         let state = self.state.read().unwrap();
         match self.instances.get(&self.instance_id) {
             Some(instance_info) => {
-                let mut object_state =
-                    ObjectState::new(state.metadata.config.chunking.to_chunker());
+                let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
                 let mut reader =
                     ObjectReader::new(&state, &mut object_state, &instance_info.objects);
                 reader.deserialize()
@@ -247,6 +1390,47 @@ impl<K: Key> KeyRepo<K> {
         }
     }
 
+    /// Read the trash for the current instance from the data store and return it.
+    ///
+    /// This does not commit or roll back changes.
+    pub(super) fn read_trash(&self) -> crate::Result<HashMap<K, TrashEntry>> {
+        let state = self.state.read().unwrap();
+        match self
+            .instances
+            .get(&self.instance_id)
+            .and_then(|instance_info| instance_info.trash.as_ref())
+        {
+            Some(handle) => {
+                let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
+                let mut reader = ObjectReader::new(&state, &mut object_state, handle);
+                reader.deserialize()
+            }
+            // No object has ever been moved to the trash in this instance.
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Read the metadata and tags for the current instance from the data store and return them.
+    ///
+    /// This does not commit or roll back changes.
+    pub(super) fn read_attributes(&self) -> crate::Result<AttributeTable<K>> {
+        let state = self.state.read().unwrap();
+        match self
+            .instances
+            .get(&self.instance_id)
+            .and_then(|instance_info| instance_info.attributes.as_ref())
+        {
+            Some(handle) => {
+                let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
+                let mut reader = ObjectReader::new(&state, &mut object_state, handle);
+                let entries: HashMap<K, KeyAttributes> = reader.deserialize()?;
+                Ok(AttributeTable::from_entries(entries))
+            }
+            // No object has ever had metadata or tags in this instance.
+            None => Ok(AttributeTable::default()),
+        }
+    }
+
     /// Set the current instance of the repository.
     ///
     /// This does not write the object map for the current instance before switching to the new
@@ -257,7 +1441,12 @@ impl<K: Key> KeyRepo<K> {
     ) -> crate::Result<R> {
         let is_new_instance = !self.instances.contains_key(&instance_id);
 
-        let new_objects = if is_new_instance {
+        // Creating an instance writes its object map to the data store.
+        if is_new_instance {
+            self.state.read().unwrap().check_writable()?;
+        }
+
+        let (new_objects, new_trash, new_attributes) = if is_new_instance {
             // Create the object handle for the object which will store the object map for the new
             // instance.
             let mut handle = ObjectHandle {
@@ -270,7 +1459,7 @@ impl<K: Key> KeyRepo<K> {
 
             // Write an empty object map to the object.
             let mut state = self.state.write().unwrap();
-            let mut object_state = ObjectState::new(state.metadata.config.chunking.to_chunker());
+            let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
             let mut writer = ObjectWriter::new(&mut state, &mut object_state, &mut handle);
             writer.serialize(&objects)?;
 
@@ -278,10 +1467,12 @@ impl<K: Key> KeyRepo<K> {
             let instance_info = InstanceInfo {
                 version_id: R::VERSION_ID,
                 objects: handle,
+                trash: None,
+                attributes: None,
             };
             self.instances.insert(instance_id, instance_info);
 
-            objects
+            (objects, HashMap::new(), AttributeTable::default())
         } else {
             let instance_info = self.instances.get_mut(&instance_id).unwrap();
 
@@ -289,17 +1480,38 @@ impl<K: Key> KeyRepo<K> {
                 return Err(crate::Error::UnsupportedRepo);
             }
 
-            // Deserialize the object map for this instance.
+            // Deserialize the object map, the trash, and the attributes for this instance.
             let state = self.state.read().unwrap();
-            let mut object_state = ObjectState::new(state.metadata.config.chunking.to_chunker());
+            let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
             let mut reader = ObjectReader::new(&state, &mut object_state, &instance_info.objects);
-            reader.deserialize()?
+            let objects = reader.deserialize()?;
+            let trash = match &instance_info.trash {
+                Some(handle) => {
+                    let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
+                    let mut reader = ObjectReader::new(&state, &mut object_state, handle);
+                    reader.deserialize()?
+                }
+                None => HashMap::new(),
+            };
+            let attributes = match &instance_info.attributes {
+                Some(handle) => {
+                    let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
+                    let mut reader = ObjectReader::new(&state, &mut object_state, handle);
+                    AttributeTable::from_entries(reader.deserialize()?)
+                }
+                None => AttributeTable::default(),
+            };
+
+            (objects, trash, attributes)
         };
 
         let repo = KeyRepo {
             state: self.state,
             instance_id,
             objects: new_objects,
+            trash: new_trash,
+            attributes: new_attributes,
+            key_index: KeyIndex::default(),
             instances: self.instances,
             handle_table: self.handle_table,
             transaction_id: self.transaction_id,
@@ -313,31 +1525,137 @@ impl<K: Key> KeyRepo<K> {
     }
 
     /// Atomically encode and write the given serialized `header` to the data store.
-    fn write_serialized_header(&mut self, serialized_header: &[u8]) -> crate::Result<()> {
+    ///
+    /// If the header is identical to the one which was last written, no new header is written. If
+    /// the repository metadata is also unchanged, nothing is written at all.
+    ///
+    /// If `new_commit` is `true` and anything is written, the repository is assigned a new commit
+    /// ID, which is recorded in the commit history. Otherwise, the current commit in the commit
+    /// history is updated to point to the new header.
+    fn write_serialized_header(
+        &mut self,
+        serialized_header: &[u8],
+        new_commit: bool,
+    ) -> crate::Result<CommitReport> {
         let mut state = self.state.write().unwrap();
-        // Encode the serialized header.
-        let encoded_header = state.encode_data(serialized_header)?;
 
-        // Write the new header to a new block.
-        let header_id = Uuid::new_v4().into();
-        state
-            .store
-            .lock()
-            .unwrap()
-            .write_block(BlockKey::Header(header_id), encoded_header.as_slice())
-            .map_err(crate::Error::Store)?;
-        state.metadata.header_id = header_id;
+        let header_hash = chunk_hash(serialized_header);
+        let header_written = state.committed_header != Some(header_hash);
+        let previous_header_id = state.metadata.header_id;
 
-        // Atomically write the new repository metadata containing the new header ID.
-        let serialized_metadata =
-            to_vec(&state.metadata).expect("Could not serialize repository metadata.");
-        state
+        if header_written {
+            // Encode the serialized header.
+            let encoded_header = state.encode_data(serialized_header)?;
+
+            // Check the size of the header before we write anything to the data store so that the
+            // repository is left unchanged if the header is too large.
+            if let Some(limit) = state.metadata.config.max_header_size {
+                let size = encoded_header.len() as u64;
+                if size > limit {
+                    return Err(crate::Error::HeaderTooLarge { size, limit });
+                }
+            }
+
+            // Write the new header to a new block.
+            let header_id = Uuid::new_v4().into();
+            state
+                .store
+                .lock()
+                .unwrap()
+                .write_block(BlockKey::Header(header_id), encoded_header.as_slice())
+                .map_err(crate::Error::Store)?;
+            state.metadata.header_id = header_id;
+        }
+
+        // Atomically write the new repository metadata containing the new header ID. Metadata-only
+        // changes, like changing the password, still need to be written even if the header is
+        // unchanged.
+        let mut serialized_metadata = encode_metadata(&state.metadata)?;
+        let metadata_written = header_written
+            || state.committed_metadata.as_deref() != Some(serialized_metadata.as_slice());
+
+        if metadata_written {
+            // The commit ID only advances if the new metadata is written.
+            let previous_commit_id = state.metadata.commit_id;
+            let previous_history = state.metadata.commit_history.clone();
+            if new_commit {
+                state.metadata.commit_id = previous_commit_id.next();
+                state.metadata.record_commit();
+            } else {
+                let header_id = state.metadata.header_id;
+                for info in &mut state.metadata.commit_history {
+                    if info.header_id == previous_header_id {
+                        info.header_id = header_id;
+                    }
+                }
+            }
+
+            let result = encode_metadata(&state.metadata).and_then(|serialized| {
+                state
+                    .store
+                    .lock()
+                    .unwrap()
+                    .write_block(BlockKey::Super, &serialized)
+                    .map_err(crate::Error::Store)?;
+                Ok(serialized)
+            });
+
+            match result {
+                Ok(serialized) => serialized_metadata = serialized,
+                Err(error) => {
+                    state.metadata.commit_id = previous_commit_id;
+                    state.metadata.commit_history = previous_history;
+                    return Err(error);
+                }
+            }
+        }
+
+        state.committed_header = Some(header_hash);
+        state.committed_metadata = Some(serialized_metadata);
+
+        Ok(CommitReport {
+            header_written,
+            metadata_written,
+            commit_id: state.metadata.commit_id,
+        })
+    }
+
+    /// Atomically write the given serialized `header` to the data store without committing
+    /// changes to the repository metadata.
+    ///
+    /// This writes the metadata from the previous commit, pointing to the new header, rather than
+    /// the current metadata. Uncommitted changes to the metadata, like a new password, are kept in
+    /// memory so that they can still be committed later.
+    fn write_header_only(&mut self, serialized_header: &[u8]) -> crate::Result<()> {
+        let committed_metadata = self.read_committed_metadata()?;
+        let staged_metadata = mem::replace(
+            &mut self.state.write().unwrap().metadata,
+            committed_metadata,
+        );
+
+        let result = self.write_serialized_header(serialized_header, false);
+
+        // Put the uncommitted metadata back, keeping the pointer to the header which is now in the
+        // data store.
+        let mut state = self.state.write().unwrap();
+        let committed_metadata = mem::replace(&mut state.metadata, staged_metadata);
+        state.metadata.header_id = committed_metadata.header_id;
+        state.metadata.commit_history = committed_metadata.commit_history;
+
+        result.map(|_| ())
+    }
+
+    /// Read the repository metadata from the previous commit from the data store.
+    fn read_committed_metadata(&self) -> crate::Result<RepoMetadata> {
+        let state = self.state.read().unwrap();
+        let serialized_metadata = state
             .store
             .lock()
             .unwrap()
-            .write_block(BlockKey::Super, &serialized_metadata)
-            .map_err(crate::Error::Store)?;
-        Ok(())
+            .read_block(BlockKey::Super)
+            .map_err(crate::Error::Store)?
+            .ok_or(crate::Error::Corrupt)?;
+        decode_metadata(serialized_metadata.as_slice())
     }
 
     /// Return a cloned `Header` representing the current state of the repository.
@@ -348,13 +1666,18 @@ impl<K: Key> KeyRepo<K> {
             packs: state.packs.clone(),
             instances: self.instances.clone(),
             handle_table: self.handle_table.clone(),
+            rechunk: state.rechunk.clone(),
+            dictionaries: state.dictionaries.clone(),
         }
     }
 
     /// Return a serialized `Header` representing the current state of the repository.
     ///
     /// The returned data is not encoded.
-    fn serialize_header(&mut self) -> Vec<u8> {
+    ///
+    /// # Errors
+    /// - `Error::Incompatible`: The header uses a feature the compatibility target doesn't support.
+    fn serialize_header(&mut self) -> crate::Result<Vec<u8>> {
         let mut state = self.state.write().unwrap();
         // Temporarily replace the values in the repository which need to be serialized so we can
         // put them into the `Header`. This avoids the need to clone them. We'll put them back
@@ -364,11 +1687,13 @@ impl<K: Key> KeyRepo<K> {
             packs: std::mem::take(&mut state.packs),
             instances: std::mem::take(&mut self.instances),
             handle_table: std::mem::take(&mut self.handle_table),
+            rechunk: state.rechunk.take(),
+            dictionaries: std::mem::take(&mut state.dictionaries),
         };
 
         // Serialize the header so we can write it to the data store.
         let serialized_header =
-            to_vec(&header).expect("Could not serialize the repository header.");
+            format::serialize_header(&header, state.metadata.config.compatibility_target);
 
         // Unpack the values from the `Header` and put them back where they originally were.
         let Header {
@@ -376,11 +1701,15 @@ impl<K: Key> KeyRepo<K> {
             packs,
             instances,
             handle_table,
+            rechunk,
+            dictionaries,
         } = header;
         state.chunks = chunks;
         state.packs = packs;
         self.instances = instances;
         self.handle_table = handle_table;
+        state.rechunk = rechunk;
+        state.dictionaries = dictionaries;
 
         serialized_header
     }
@@ -392,11 +1721,15 @@ impl<K: Key> KeyRepo<K> {
         let old_packs = mem::replace(&mut state.packs, header.packs);
         let old_instances = mem::replace(&mut self.instances, header.instances);
         let old_handle_table = mem::replace(&mut self.handle_table, header.handle_table);
+        let old_rechunk = mem::replace(&mut state.rechunk, header.rechunk);
+        let old_dictionaries = mem::replace(&mut state.dictionaries, header.dictionaries);
         Header {
             chunks: old_chunks,
             packs: old_packs,
             instances: old_instances,
             handle_table: old_handle_table,
+            rechunk: old_rechunk,
+            dictionaries: old_dictionaries,
         }
     }
     /// Atomically restore the repository's state from the given `header`.
@@ -410,10 +1743,16 @@ impl<K: Key> KeyRepo<K> {
         // We need to restore the repository state before we can read the object map.
         let old_header = self.replace_header(header);
 
-        // Restore the object map from the old header.
-        match self.read_object_map() {
-            Ok(objects) => {
+        // Restore the object map, the trash, and the attributes from the old header.
+        match self
+            .read_object_map()
+            .and_then(|objects| Ok((objects, self.read_trash()?, self.read_attributes()?)))
+        {
+            Ok((objects, trash, attributes)) => {
                 self.objects = objects;
+                self.trash = trash;
+                self.attributes = attributes;
+                self.key_index.reset();
                 Ok(())
             }
             Err(error) => {
@@ -423,6 +1762,84 @@ impl<K: Key> KeyRepo<K> {
         }
     }
 
+    /// Return the contents of the objects with the given `keys`.
+    ///
+    /// This returns a list with the contents of each object in the same order as `keys`. If there
+    /// is no object with a given key, its entry is `None`.
+    ///
+    /// This is meant for reading many small objects at once. The chunks of all the requested
+    /// objects are read from the data store together, and each chunk is only read and decoded once,
+    /// even if it is shared by several of the objects. Because the contents of every object are
+    /// read into memory, this is not suitable for large objects; use [`object`] instead.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`object`]: crate::repo::key::KeyRepo::object
+    pub fn get_many<Q>(&self, keys: &[&Q]) -> crate::Result<Vec<Option<Vec<u8>>>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let state = self.state.read().unwrap();
+
+        let handles = keys
+            .iter()
+            .map(|key| {
+                self.objects
+                    .get(*key)
+                    .map(|handle| handle.read().unwrap().clone())
+            })
+            .collect::<Vec<_>>();
+
+        // Get the set of distinct chunks which are needed. When packing is enabled, we read them in
+        // the order they are stored in packs so that chunks in the same pack are read together.
+        let mut needed_chunks = handles
+            .iter()
+            .flatten()
+            .flat_map(|handle| handle.chunks())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        needed_chunks.sort_by_key(|chunk| {
+            state
+                .chunks
+                .get(chunk)
+                .and_then(|info| state.packs.get(&info.block_id))
+                .and_then(|indices| indices.first())
+                .map(|index| (*index.id.as_ref(), index.offset))
+        });
+
+        let mut store_state = StoreState::new();
+        let mut store_reader = StoreReader::new(&state, &mut store_state);
+        let chunk_data = needed_chunks
+            .iter()
+            .copied()
+            .zip(store_reader.read_chunks(&needed_chunks)?)
+            .collect::<HashMap<_, _>>();
+
+        // Assemble the contents of each object from its chunks.
+        let contents = handles
+            .into_iter()
+            .map(|handle| {
+                handle.map(|handle| {
+                    let mut data = Vec::new();
+                    for extent in &handle.extents {
+                        match extent {
+                            Extent::Chunk(chunk) => data.extend_from_slice(&chunk_data[chunk]),
+                            Extent::Hole { size } => data.resize(data.len() + *size as usize, 0),
+                        }
+                    }
+                    data
+                })
+            })
+            .collect();
+
+        Ok(contents)
+    }
+
     /// Verify the integrity of all the data in the current instance of the repository.
     ///
     /// This returns the set of keys of objects in the current instance which are corrupt.
@@ -447,18 +1864,9 @@ impl<K: Key> KeyRepo<K> {
         let mut store_state = StoreState::new();
         let mut store_reader = StoreReader::new(&state, &mut store_state);
         for chunk in expected_chunks {
-            match store_reader.read_chunk(chunk) {
-                Ok(data) => {
-                    if data.len() != chunk.size as usize || chunk_hash(&data) != chunk.hash {
-                        corrupt_chunks.insert(chunk.hash);
-                    }
-                }
-                Err(crate::Error::InvalidData) => {
-                    // Ciphertext verification failed. No need to check the hash.
-                    corrupt_chunks.insert(chunk.hash);
-                }
-                Err(error) => return Err(error),
-            };
+            if !store_reader.verify_chunk(chunk)? {
+                corrupt_chunks.insert(chunk.hash);
+            }
         }
 
         // If there are no corrupt chunks, there are no corrupt objects.
@@ -477,73 +1885,708 @@ impl<K: Key> KeyRepo<K> {
             }
         }
 
-        Ok(corrupt_keys)
+        Ok(corrupt_keys)
+    }
+
+    /// Export the contents of the current instance of the repository to a tar archive.
+    ///
+    /// This writes each object as a regular file to `dest` at the path returned by `key_to_path`,
+    /// which must be a relative path without any `..` components. The exported data is plaintext,
+    /// so it can be read with standard tools without this library, the repository's password, or
+    /// the repository's configuration. This is meant for escrow and for migrating away from
+    /// `acid-store`.
+    ///
+    /// The contents of each object are streamed to `dest` as they are read from the data store, so
+    /// objects do not need to fit in memory. The [`ExportOptions`] can be used to filter which
+    /// objects are exported, report progress or cancel the export, and configure the manifest
+    /// which is written to the archive.
+    ///
+    /// This returns `dest` once the archive is complete.
+    ///
+    /// # Security
+    /// The archive is not encrypted, even if the repository is.
+    ///
+    /// # Errors
+    /// - `Error::Cancelled`: The progress hook cancelled the export.
+    /// - `Error::Serialize`: A key could not be serialized for the manifest.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`ExportOptions`]: crate::repo::ExportOptions
+    #[cfg(feature = "export")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "export")))]
+    pub fn export_plaintext<W, F>(
+        &self,
+        dest: W,
+        mut key_to_path: F,
+        options: &mut ExportOptions<K>,
+    ) -> crate::Result<W>
+    where
+        W: Write,
+        F: FnMut(&K) -> PathBuf,
+    {
+        let mut exporter = Exporter::new(dest, options, self.objects.len() as u64);
+
+        for (key, handle) in &self.objects {
+            if !exporter.includes(key) {
+                continue;
+            }
+
+            let mut object = Object::new(&self.state, handle);
+            let size = object.size()?;
+            exporter.append_file(
+                &key_to_path(key),
+                export::to_json(key)?,
+                size,
+                &mut object,
+                None,
+            )?;
+        }
+
+        exporter.finish()
+    }
+
+    /// Train a Zstandard dictionary from the chunks in this repository.
+    ///
+    /// Chunks are compressed independently of each other, so compressing many small chunks which
+    /// are similar to each other, like small files of the same type, gains little. A dictionary
+    /// captures the content which is common to those chunks so that it doesn't need to be stored
+    /// in each of them. Once a dictionary is trained, new chunks which are compressed with
+    /// [`Compression::Zstd`] are compressed with it. Existing chunks are not recompressed.
+    ///
+    /// The dictionary is at most `size` bytes, and it is trained from a sample of up to 100 times
+    /// that much data from existing chunks. A size of around 100 KiB is usually a good choice.
+    /// Training again replaces the dictionary used for new chunks, but older dictionaries are kept
+    /// so that chunks which were compressed with them can still be read.
+    ///
+    /// Dictionaries are stored in the repository header, so they count towards
+    /// [`RepoConfig::max_header_size`]. Changes are not persisted until they are committed.
+    ///
+    /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
+    /// - `Error::Incompatible`: The repository's compatibility target doesn't support
+    /// dictionaries.
+    /// - `Error::UnsupportedRepo`: The repository doesn't use [`Compression::Zstd`], so the
+    /// dictionary would never be used.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: There isn't enough data in the repository to train a dictionary, or an I/O
+    /// error occurred.
+    ///
+    /// [`Compression::Zstd`]: crate::repo::Compression::Zstd
+    /// [`RepoConfig::max_header_size`]: crate::repo::RepoConfig::max_header_size
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub fn train_dictionary(&mut self, size: usize) -> crate::Result<()> {
+        let mut state = self.state.write().unwrap();
+        state.check_writable()?;
+        Capability::ZstdDictionary.check(state.metadata.config.compatibility_target)?;
+        if !matches!(state.metadata.config.compression, Compression::Zstd { .. }) {
+            return Err(crate::Error::UnsupportedRepo);
+        }
+
+        // Sample chunks in an arbitrary order until we have enough data to train with.
+        let sample_limit = size.saturating_mul(DICTIONARY_SAMPLE_RATIO);
+        let mut sample_size = 0usize;
+        let mut sampled_chunks = Vec::new();
+        for chunk in state.chunks.keys() {
+            if sample_size >= sample_limit {
+                break;
+            }
+            sample_size += chunk.size as usize;
+            sampled_chunks.push(*chunk);
+        }
+
+        let mut store_state = StoreState::new();
+        let samples = StoreReader::new(&state, &mut store_state).read_chunks(&sampled_chunks)?;
+        let dictionary = zstd::dict::from_samples(&samples, size)?;
+        state.dictionaries.push(Dictionary::new(dictionary));
+
+        Ok(())
+    }
+
+    /// Rewrite every object in the current instance using the given `chunking` method.
+    ///
+    /// The chunking method of a repository is chosen when it is created. This method changes it by
+    /// streaming the contents of each object through the new chunker, one object at a time, so
+    /// objects do not need to fit in memory. New chunks are deduplicated against the existing data
+    /// in the repository, including the chunks of objects which have already been rechunked.
+    /// Objects in the trash are rechunked as well.
+    ///
+    /// The repository's chunking method, as reported by [`info`], is only changed once every
+    /// object has been rechunked. Until then, new data is still written with the old chunking
+    /// method.
+    ///
+    /// Changes are committed periodically as configured by `options`, and the progress of the
+    /// operation is committed along with them. If this method is interrupted, calling it again
+    /// with the same `chunking` skips the objects which were already rechunked and committed.
+    /// Calling it with a different `chunking` starts over. Unless checkpoints are disabled, changes
+    /// are committed when this method returns successfully. Committing a checkpoint also commits
+    /// any other uncommitted changes in the repository.
+    ///
+    /// The space used by the old chunks isn't reclaimed until changes are committed and
+    /// [`Commit::clean`] is called, so the space used in the data store temporarily grows by up to
+    /// the size of the data in the repository. The returned [`RechunkReport`] reports how much
+    /// data was written and how much can be reclaimed.
+    ///
+    /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
+    /// - `Error::Incompatible`: The repository's compatibility target doesn't support rechunking.
+    /// - `Error::UnsupportedRepo`: The repository has instances other than the current one, which
+    /// can't be rechunked without knowing their key types.
+    /// - `Error::TransactionInProgress`: A transaction is currently in progress for an object.
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::DestructiveNotAuthorized`: A checkpoint couldn't be committed because the
+    /// repository contains destructive changes which were not authorized.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`info`]: crate::repo::key::KeyRepo::info
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    /// [`RechunkReport`]: crate::repo::RechunkReport
+    pub fn rechunk(
+        &mut self,
+        chunking: Chunking,
+        options: &RechunkOptions,
+    ) -> crate::Result<RechunkReport> {
+        {
+            let state = self.state.read().unwrap();
+            state.check_writable()?;
+            Capability::Rechunk.check(state.metadata.config.compatibility_target)?;
+        }
+
+        if self.instances.keys().any(|id| *id != self.instance_id) {
+            return Err(crate::Error::UnsupportedRepo);
+        }
+
+        let mut report = RechunkReport::default();
+
+        {
+            let mut state = self.state.write().unwrap();
+            match &state.rechunk {
+                Some(progress) if progress.chunking == chunking => {}
+                None if state.metadata.config.chunking == chunking => return Ok(report),
+                _ => state.rechunk = Some(RechunkProgress::new(chunking.clone())),
+            }
+        }
+
+        let mut uncommitted_objects = 0u64;
+
+        let object_keys = self.objects.keys().cloned().collect::<Vec<_>>();
+        for key in object_keys {
+            let handle = Arc::clone(&self.objects[&key]);
+
+            // Hold a transaction lock on the object while it's rechunked so that no other `Object`
+            // can modify it in the meantime.
+            let handle_id = handle.read().unwrap().id;
+            let _transaction_lock = self
+                .state
+                .write()
+                .unwrap()
+                .transactions
+                .acquire_lock(handle_id)
+                .ok_or(crate::Error::TransactionInProgress)?;
+
+            if self.is_rechunked(handle_id) {
+                report.objects_skipped += 1;
+                continue;
+            }
+
+            let old_handle = handle.read().unwrap().clone();
+            let new_handle = self.rechunk_handle(&old_handle, &chunking, &mut report)?;
+            let new_id = new_handle.id;
+
+            // Swap the new handle into place so that existing `Object` instances see the new
+            // chunks, and then release the old ones.
+            *handle.write().unwrap() = new_handle;
+            self.release_rechunked(&old_handle, new_id, &mut report);
+            self.rechunk_checkpoint(options, &mut uncommitted_objects)?;
+        }
+
+        let trash_keys = self.trash.keys().cloned().collect::<Vec<_>>();
+        for key in trash_keys {
+            let old_handle = self.trash[&key].handle.clone();
+
+            if self.is_rechunked(old_handle.id) {
+                report.objects_skipped += 1;
+                continue;
+            }
+
+            let new_handle = self.rechunk_handle(&old_handle, &chunking, &mut report)?;
+            let new_id = new_handle.id;
+            self.trash.get_mut(&key).unwrap().handle = new_handle;
+            self.release_rechunked(&old_handle, new_id, &mut report);
+            self.rechunk_checkpoint(options, &mut uncommitted_objects)?;
+        }
+
+        // Every object has been rechunked, so new data can be written with the new chunking method.
+        // The object map is rewritten with the new chunking method when changes are committed.
+        {
+            let mut state = self.state.write().unwrap();
+            state.metadata.config.chunking = chunking;
+            state.rechunk = None;
+        }
+
+        if options.interval().is_some() {
+            self.commit()?;
+        }
+
+        Ok(report)
+    }
+
+    /// Return whether the object handle with the given `id` has already been rechunked.
+    fn is_rechunked(&self, id: HandleId) -> bool {
+        let state = self.state.read().unwrap();
+        match &state.rechunk {
+            Some(progress) => progress.rechunked.contains(&id),
+            None => false,
+        }
+    }
+
+    /// Write the contents of the object with the given `handle` to a new handle using `chunking`.
+    ///
+    /// This returns the new handle without replacing `handle`. If this fails, any data which was
+    /// written is removed from the repository.
+    fn rechunk_handle(
+        &mut self,
+        handle: &ObjectHandle,
+        chunking: &Chunking,
+        report: &mut RechunkReport,
+    ) -> crate::Result<ObjectHandle> {
+        let mut new_handle = ObjectHandle {
+            id: self.handle_table.next(),
+            extents: Vec::new(),
+        };
+
+        let result = write_rechunked(
+            &mut self.state.write().unwrap(),
+            handle,
+            &mut new_handle,
+            chunking,
+        );
+        if let Err(error) = result {
+            // Chunks which were written before the error may reference the new handle even if
+            // they were never added to it, so we need to remove its ID from every chunk.
+            let mut state = self.state.write().unwrap();
+            state.chunks.retain(|_, chunk_info| {
+                chunk_info.references.remove(&new_handle.id);
+                !chunk_info.references.is_empty()
+            });
+            drop(state);
+            self.handle_table.recycle(new_handle.id);
+            return Err(error);
+        }
+
+        // Chunks which are only referenced by the new handle were written to the data store rather
+        // than being deduplicated against existing data.
+        let state = self.state.read().unwrap();
+        report.bytes_written += new_handle
+            .chunks()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter(|chunk| state.chunks[chunk].references.len() == 1)
+            .map(|chunk| chunk.size as u64)
+            .sum::<u64>();
+
+        Ok(new_handle)
+    }
+
+    /// Release the `old_handle` of an object which was rechunked into the handle with `new_id`.
+    fn release_rechunked(
+        &mut self,
+        old_handle: &ObjectHandle,
+        new_id: HandleId,
+        report: &mut RechunkReport,
+    ) {
+        self.remove_handle(old_handle);
+
+        let mut state = self.state.write().unwrap();
+        report.objects_rechunked += 1;
+        report.bytes_superseded += old_handle
+            .chunks()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter(|chunk| !state.chunks.contains_key(chunk))
+            .map(|chunk| chunk.size as u64)
+            .sum::<u64>();
+
+        if let Some(progress) = &mut state.rechunk {
+            progress.rechunked.insert(new_id);
+        }
+    }
+
+    /// Commit changes if enough objects have been rechunked since the last checkpoint.
+    fn rechunk_checkpoint(
+        &mut self,
+        options: &RechunkOptions,
+        uncommitted_objects: &mut u64,
+    ) -> crate::Result<()> {
+        *uncommitted_objects += 1;
+        if options.interval() == Some(*uncommitted_objects) {
+            self.commit()?;
+            *uncommitted_objects = 0;
+        }
+        Ok(())
+    }
+
+    /// Delete all data in the current instance of the repository.
+    ///
+    /// This does not delete data from other instances of the repository.
+    ///
+    /// This does not commit changes to the repository.
+    ///
+    /// No data is reclaimed in the backing data store until changes are committed and
+    /// [`Commit::clean`] is called.
+    ///
+    /// If the [`DestructivePolicy`] is enabled, clearing a non-empty instance must be authorized
+    /// with [`authorize_destructive`] before changes can be committed.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    /// [`authorize_destructive`]: crate::repo::key::KeyRepo::authorize_destructive
+    pub fn clear_instance(&mut self) {
+        let handles = self
+            .objects
+            .drain()
+            .map(|(_, handle)| handle.read().unwrap().clone())
+            .collect::<Vec<_>>();
+
+        // This also empties the trash.
+        let trash_handles = self.trash.drain().map(|(_, entry)| entry.handle);
+        let snapshot_handles = self.attributes.clear();
+        self.key_index.reset();
+
+        let handles = handles.into_iter().chain(trash_handles).collect::<Vec<_>>();
+        for handle in handles {
+            self.remove_handle(&handle);
+            self.state
+                .write()
+                .unwrap()
+                .interlock
+                .record(DestructiveScope::Clear, handle.size());
+        }
+        for handle in snapshot_handles {
+            self.remove_handle(&handle);
+        }
+    }
+
+    /// Change the password for this repository.
+    ///
+    /// This replaces the existing password with `new_password`. This also accepts the
+    /// `memory_limit` and the `operations_limit`, which affect the amount of memory and the number
+    /// of computations respectively which will be used by the key derivation function.
+    ///
+    /// Changing the password does not require re-encrypting any data. The change does not take
+    /// effect until [`Commit::commit`] is called; if the changes are rolled back or the repository
+    /// is dropped first, the old password keeps working.
+    ///
+    /// If encryption is disabled, this method does nothing.
+    ///
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    pub fn change_password(
+        &mut self,
+        new_password: &[u8],
+        memory_limit: ResourceLimit,
+        operations_limit: ResourceLimit,
+    ) {
+        let mut state = self.state.write().unwrap();
+        let state = &mut *state;
+        state.metadata.change_password(
+            &state.master_key,
+            new_password,
+            memory_limit,
+            operations_limit,
+            state.audit_writer.clone(),
+        );
+    }
+
+    /// Return the policy for authorizing destructive operations in this repository.
+    pub fn destructive_policy(&self) -> DestructivePolicy {
+        self.state.read().unwrap().metadata.destructive_policy
+    }
+
+    /// Change the policy for authorizing destructive operations in this repository.
+    ///
+    /// This replaces the [`DestructivePolicy`] stored in the repository metadata. Because the
+    /// policy is meant to guard against accidental data loss, this requires the repository's
+    /// `password`. If encryption is disabled, the password is not checked.
+    ///
+    /// The new policy applies to the next call to [`Commit::commit`], but it is only persisted
+    /// once a commit succeeds.
+    ///
+    /// # Errors
+    /// - `Error::Password`: The `password` is invalid.
+    /// - `Error::Corrupt`: The repository metadata is corrupt.
+    /// - `Error::Incompatible`: The repository's compatibility target doesn't support a
+    /// destructive policy.
+    ///
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    pub fn set_destructive_policy(
+        &mut self,
+        password: &[u8],
+        policy: DestructivePolicy,
+    ) -> crate::Result<()> {
+        let mut state = self.state.write().unwrap();
+        Capability::DestructivePolicy.check(state.metadata.config.compatibility_target)?;
+        if state.metadata.config.encryption != Encryption::None {
+            state.metadata.decrypt_master_key(password)?;
+        }
+        state.metadata.destructive_policy = policy;
+
+        let writer = state.audit_writer.clone();
+        state
+            .metadata
+            .record_audit(AuditOperation::DestructivePolicyChanged { policy }, writer);
+
+        Ok(())
+    }
+
+    /// Return a serializable snapshot of the objects in the current instance of the repository.
+    ///
+    /// The `source` determines whether the manifest describes the repository as of the last commit
+    /// or its current state, including uncommitted changes. The manifest contains the size and a
+    /// [`ContentDigest`] of each object as well as information about the repository. Two
+    /// manifests can be compared with [`Manifest::diff`] to find which objects were added, removed,
+    /// or changed.
+    ///
+    /// The manifest is a consistent snapshot; it's built while holding a lock on the repository
+    /// state. Building a manifest of the staged state doesn't read anything from the data store,
+    /// but building a manifest of the committed state requires reading the committed object map.
+    ///
+    /// Objects in the trash are not included in the manifest.
+    ///
+    /// # Errors
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`ContentDigest`]: crate::repo::ContentDigest
+    /// [`Manifest::diff`]: crate::repo::Manifest::diff
+    pub fn manifest(&self, source: ManifestSource) -> crate::Result<Manifest<K>> {
+        let state = self.state.read().unwrap();
+        let mut builder = ManifestBuilder::new(
+            state.metadata.id,
+            self.instance_id,
+            source,
+            state.metadata.config.chunking.clone(),
+        );
+
+        match source {
+            ManifestSource::Staged => {
+                for (key, handle) in &self.objects {
+                    builder.insert(key.clone(), &handle.read().unwrap());
+                }
+            }
+            ManifestSource::Committed => {
+                let header = read_header(&state, state.metadata.header_id)?;
+
+                // If the current instance isn't in the committed header, it has no objects.
+                if let Some(instance_info) = header.instances.get(&self.instance_id) {
+                    let mut object_state = ObjectState::new(state.metadata.config.to_chunker());
+                    let objects: HashMap<K, ObjectHandle> =
+                        ObjectReader::new(&state, &mut object_state, &instance_info.objects)
+                            .deserialize()?;
+                    for (key, handle) in objects {
+                        builder.insert(key, &handle);
+                    }
+                }
+            }
+        }
+
+        Ok(builder.finish())
     }
 
-    /// Delete all data in the current instance of the repository.
+    /// Return which objects in the current instance have changed since the last commit.
     ///
-    /// This does not delete data from other instances of the repository.
+    /// This compares the committed and staged manifests of the repository, so an object is only
+    /// reported as changed if its contents are different from when it was committed. Objects in the
+    /// trash are treated as removed. This can be used to report which changes would be lost if
+    /// the repository were dropped without being committed.
     ///
-    /// This does not commit changes to the repository.
+    /// # Errors
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn pending_changes(&self) -> crate::Result<ManifestDiff<K>> {
+        let committed = self.manifest(ManifestSource::Committed)?;
+        let staged = self.manifest(ManifestSource::Staged)?;
+        Ok(committed.diff(&staged))
+    }
+
+    /// Return whether the repository has changes which have not been committed.
     ///
-    /// No data is reclaimed in the backing data store until changes are committed and
-    /// [`Commit::clean`] is called.
+    /// This returns `true` if any objects in the current instance have changed since the last
+    /// commit, as reported by [`pending_changes`], or if the repository metadata, like the
+    /// password or the configuration, has changed.
     ///
-    /// [`Commit::clean`]: crate::repo::Commit::clean
-    pub fn clear_instance(&mut self) {
-        let handles = self
-            .objects
-            .drain()
-            .map(|(_, handle)| handle)
-            .collect::<Vec<_>>();
-        for handle in handles {
-            self.remove_handle(&handle.read().unwrap());
+    /// # Errors
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Incompatible`: The repository metadata uses an option or feature which its
+    /// compatibility target doesn't support.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`pending_changes`]: crate::repo::key::KeyRepo::pending_changes
+    pub fn is_dirty(&self) -> crate::Result<bool> {
+        if !self.pending_changes()?.is_empty() {
+            return Ok(true);
         }
+
+        let committed_metadata = encode_metadata(&self.read_committed_metadata()?)?;
+        let staged_metadata = encode_metadata(&self.state.read().unwrap().metadata)?;
+        Ok(committed_metadata != staged_metadata)
     }
 
-    /// Change the password for this repository.
+    /// Return the audit log of security-relevant operations performed on this repository.
     ///
-    /// This replaces the existing password with `new_password`. This also accepts the
-    /// `memory_limit` and the `operations_limit`, which affect the amount of memory and the number
-    /// of computations respectively which will be used by the key derivation function.
+    /// Creating the repository, changing its password, and changing its [`DestructivePolicy`] each
+    /// append an [`AuditEntry`] to the log. Each entry records the operation, the time it was
+    /// performed, and the writer ID set with [`OpenOptions::audit_writer`]. An entry is persisted
+    /// by the same commit which persists its operation, so an operation which is rolled back or
+    /// never committed does not appear in the log once the repository is reopened.
     ///
-    /// Changing the password does not require re-encrypting any data. The change does not take
-    /// effect until [`Commit::commit`] is called.
+    /// Entries are numbered consecutively starting at zero, so entries which were removed from the
+    /// log can be detected by checking their [`sequence`] numbers. The log is stored in the
+    /// repository metadata, which is not encrypted, so it never contains secret material like
+    /// passwords or keys. Repositories created before the audit log was introduced start with an
+    /// empty log.
     ///
-    /// If encryption is disabled, this method does nothing.
+    /// This includes entries which have not been committed yet.
+    ///
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    /// [`AuditEntry`]: crate::repo::AuditEntry
+    /// [`OpenOptions::audit_writer`]: crate::repo::OpenOptions::audit_writer
+    /// [`sequence`]: crate::repo::AuditEntry::sequence
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.state.read().unwrap().metadata.audit_log.clone()
+    }
+
+    /// Authorize destructive operations in the given `scope` for the next `ttl`.
+    ///
+    /// If the [`DestructivePolicy`] is enabled, changes which include destructive operations in
+    /// `scope` can only be committed while they are authorized. Authorizations are kept in memory
+    /// for as long as the repository is open; they are never persisted, and they are not revoked
+    /// by committing changes.
+    ///
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    pub fn authorize_destructive(&mut self, scope: DestructiveScope, ttl: Duration) {
+        let mut state = self.state.write().unwrap();
+        state.interlock.authorize(scope, ttl);
+    }
+
+    /// Return an estimate of the size of the repository header in bytes.
+    ///
+    /// The header stores information about every chunk in the repository, so its size grows with
+    /// the amount of data stored in the repository. This value is computed from the number of
+    /// entries in the header and is only a rough estimate; it is not exact and does not account
+    /// for compression. It is cheap to compute, so it can be used to check whether a commit is
+    /// likely to exceed [`RepoConfig::max_header_size`] before attempting it.
+    ///
+    /// [`RepoConfig::max_header_size`]: crate::repo::RepoConfig::max_header_size
+    pub fn estimated_header_size(&self) -> u64 {
+        let state = self.state.read().unwrap();
+
+        let chunks_size = state.chunks.len() as u64 * ESTIMATED_CHUNK_SIZE;
+        let packs_size = state
+            .packs
+            .values()
+            .map(|indices| ESTIMATED_PACK_SIZE + indices.len() as u64 * ESTIMATED_PACK_INDEX_SIZE)
+            .sum::<u64>();
+        let instances_size = self
+            .instances
+            .values()
+            .map(|info| {
+                let trash_extents = info.trash.as_ref().map_or(0, |handle| handle.extents.len());
+                let attributes_extents = info
+                    .attributes
+                    .as_ref()
+                    .map_or(0, |handle| handle.extents.len());
+                let extents = info.objects.extents.len() + trash_extents + attributes_extents;
+                ESTIMATED_INSTANCE_SIZE + extents as u64 * ESTIMATED_EXTENT_SIZE
+            })
+            .sum::<u64>();
+
+        let dictionaries_size = state
+            .dictionaries
+            .iter()
+            .map(|dictionary| dictionary.size())
+            .sum::<u64>();
+
+        chunks_size + packs_size + instances_size + dictionaries_size
+    }
+
+    /// Change the maximum size of the repository header.
     ///
+    /// This replaces the value of [`RepoConfig::max_header_size`] for this repository. The change
+    /// applies to the next call to [`Commit::commit`], but it is only persisted once a commit
+    /// succeeds.
+    ///
+    /// [`RepoConfig::max_header_size`]: crate::repo::RepoConfig::max_header_size
     /// [`Commit::commit`]: crate::repo::Commit::commit
-    pub fn change_password(
-        &mut self,
-        new_password: &[u8],
-        memory_limit: ResourceLimit,
-        operations_limit: ResourceLimit,
-    ) {
+    pub fn set_max_header_size(&mut self, limit: Option<u64>) {
         let mut state = self.state.write().unwrap();
+        state.metadata.config.max_header_size = limit;
+    }
 
-        if state.metadata.config.encryption == Encryption::None {
-            return;
-        }
+    /// Change whether the hash of every chunk which is read from the repository is verified.
+    ///
+    /// This replaces the value of [`RepoConfig::verify_reads`] for this repository. The change
+    /// takes effect immediately, but it is only persisted once a call to [`Commit::commit`]
+    /// succeeds.
+    ///
+    /// [`RepoConfig::verify_reads`]: crate::repo::RepoConfig::verify_reads
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    pub fn set_verify_reads(&mut self, verify: bool) {
+        let mut state = self.state.write().unwrap();
+        state.metadata.config.verify_reads = verify;
+    }
 
-        let salt = KeySalt::generate();
-        let user_key = EncryptionKey::derive(
-            new_password,
-            &salt,
-            state.metadata.config.encryption.key_size(),
-            memory_limit,
-            operations_limit,
-        );
+    /// Change how old an unreferenced block must be before it can be removed.
+    ///
+    /// This replaces the value of [`RepoConfig::gc_grace_period`] for this repository. The change
+    /// takes effect immediately, but it is only persisted once a call to [`Commit::commit`]
+    /// succeeds.
+    ///
+    /// [`RepoConfig::gc_grace_period`]: crate::repo::RepoConfig::gc_grace_period
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    pub fn set_gc_grace_period(&mut self, grace_period: Duration) {
+        let mut state = self.state.write().unwrap();
+        state.metadata.config.gc_grace_period = grace_period;
+    }
 
-        let encrypted_master_key = state
-            .metadata
-            .config
-            .encryption
-            .encrypt(state.master_key.expose_secret(), &user_key);
+    /// Change the number of previous commits to keep in the commit history.
+    ///
+    /// This replaces the value of [`RepoConfig::commit_history`] for this repository. The change
+    /// applies to the next call to [`Commit::commit`], but it is only persisted once a commit
+    /// succeeds. Commits which no longer fit in the commit history are discarded, and the data
+    /// they reference is removed the next time the repository is cleaned.
+    ///
+    /// [`RepoConfig::commit_history`]: crate::repo::RepoConfig::commit_history
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    pub fn set_commit_history(&mut self, commits: u32) {
+        let mut state = self.state.write().unwrap();
+        state.metadata.config.commit_history = commits;
+    }
 
-        state.metadata.salt = salt;
-        state.metadata.master_key = encrypted_master_key;
-        state.metadata.config.memory_limit = memory_limit;
-        state.metadata.config.operations_limit = operations_limit;
+    /// Change the oldest version of this library which must be able to read the repository.
+    ///
+    /// This replaces the value of [`RepoConfig::compatibility_target`] for this repository. This
+    /// can be used to upgrade a repository written by an older version of this library so that it
+    /// can use newer features. The change takes effect immediately, but it is only persisted once
+    /// a call to [`Commit::commit`] succeeds. Committing fails with `Error::Incompatible` if the
+    /// repository uses an option or feature which the new target doesn't support.
+    ///
+    /// [`RepoConfig::compatibility_target`]: crate::repo::RepoConfig::compatibility_target
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    pub fn set_compatibility_target(&mut self, target: Option<FormatVersion>) {
+        let mut state = self.state.write().unwrap();
+        state.metadata.config.compatibility_target = target;
     }
 
     /// Return this repository's current instance ID.
@@ -568,9 +2611,15 @@ impl<K: Key> KeyRepo<K> {
         let metadata_handles = self
             .instances
             .values()
-            .map(|info| info.objects.id)
+            .flat_map(|info| {
+                std::iter::once(info.objects.id)
+                    .chain(info.trash.as_ref().map(|handle| handle.id))
+                    .chain(info.attributes.as_ref().map(|handle| handle.id))
+            })
             .collect::<HashSet<_>>();
 
+        let trash_size = self.trash.values().map(|entry| entry.handle.size()).sum();
+
         for handle_lock in self.objects.values() {
             let handle = handle_lock.read().unwrap();
             apparent_size += handle.size();
@@ -589,16 +2638,294 @@ impl<K: Key> KeyRepo<K> {
             }
         }
 
+        let retained_size = state.retained_blocks.values().sum();
+
         RepoStats {
             apparent_size,
             actual_size,
             repo_size,
+            trash_size,
+            retained_size,
+        }
+    }
+
+    /// Return statistics about the pool of buffers used to encode and decode chunks.
+    ///
+    /// If the pool is shared with other repositories, this includes buffers used by every
+    /// repository which shares it.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.state.read().unwrap().buffer_pool.stats()
+    }
+
+    /// Consume this repository and return the data store which backs it.
+    ///
+    /// This releases the lock on the repository and discards any uncommitted changes, just like
+    /// dropping the repository. The returned data store contains the repository as of the last
+    /// commit, and opening a repository from it will not see any uncommitted changes.
+    ///
+    /// Data which was written to the data store since the last commit is not removed. It remains
+    /// in the data store as unreferenced blocks, which are ignored when the repository is opened
+    /// again and are removed the next time [`Commit::clean`] is called.
+    ///
+    /// Objects and read-only objects which outlive the repository become invalid.
+    ///
+    /// # Errors
+    /// - `Error::InUse`: An object is being read or written by another thread.
+    /// - `Error::Store`: An error occurred with the data store.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn into_store(self) -> crate::Result<Box<dyn DataStore>> {
+        // Objects only hold weak references to the repository state, but they hold a strong
+        // reference while an operation on them is in progress.
+        let state = match Arc::try_unwrap(self.state) {
+            Ok(state) => state.into_inner().unwrap(),
+            Err(_) => return Err(crate::Error::InUse),
+        };
+
+        if let Some(lock_id) = state.lock_id {
+            let mut store = state.store.lock().unwrap();
+            unlock_store(&mut *store, lock_id)?;
+        }
+        state.registration.unlocked();
+
+        Ok(state.into_store())
+    }
+
+    /// Move this repository to `new_store`.
+    ///
+    /// This copies every block of the repository from its current data store to `new_store`,
+    /// including blocks which were written since the last commit, and then switches the
+    /// repository to `new_store`. The repository keeps working without being reopened, and
+    /// uncommitted changes are preserved. This allows moving a repository between data stores,
+    /// like from a local directory to an S3 bucket, without knowing how it's laid out in them.
+    ///
+    /// The `progress` hook is called with the progress of the migration after each block is
+    /// copied. If it returns `false`, the migration is cancelled. If the migration is cancelled or
+    /// fails, the repository keeps using its current data store, and `new_store` is left with some
+    /// blocks but no repository which can be opened.
+    ///
+    /// The lock on the repository is moved to `new_store`. This returns the old data store, which
+    /// still contains the repository as of the last commit. It should be discarded, because
+    /// changes which are committed from now on are only written to `new_store`.
+    ///
+    /// # Errors
+    /// - `Error::ReadOnly`: The repository was opened read-only.
+    /// - `Error::AlreadyExists`: `new_store` already contains a repository.
+    /// - `Error::Cancelled`: The progress hook cancelled the migration.
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::Store`: An error occurred with either data store.
+    pub fn migrate_store(
+        &mut self,
+        mut new_store: impl DataStore + 'static,
+        progress: impl FnMut(MigrateProgress) -> bool,
+    ) -> crate::Result<Box<dyn DataStore>> {
+        // Hold the state lock so that nothing is written to the old data store while its blocks
+        // are being copied.
+        let state = self.state.write().unwrap();
+        state.check_writable()?;
+        let mut store = state.store.lock().unwrap();
+
+        copy_blocks(&mut *store, &mut new_store, state.lock_id, progress)?;
+
+        let mut old_store = mem::replace(&mut *store, Box::new(new_store));
+        if let Some(lock_id) = state.lock_id {
+            unlock_store(&mut old_store, lock_id)?;
+        }
+
+        Ok(old_store)
+    }
+
+    /// Commit changes which have been made to the repository and report what was written.
+    ///
+    /// This is the same as [`Commit::commit`], except it returns a [`CommitReport`] describing what
+    /// was written to the data store. If nothing has changed since the last commit, nothing is
+    /// written to the data store. If only the repository metadata has changed, like when the
+    /// password is changed with [`change_password`], only the metadata is written.
+    ///
+    /// Changes are detected by comparing against the header which was last written by this
+    /// repository, so the first commit after the repository is opened always writes a new header.
+    ///
+    /// # Errors
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::HeaderTooLarge`: The header is larger than [`RepoConfig::max_header_size`].
+    /// - `Error::DestructiveNotAuthorized`: The changes include destructive operations which were
+    /// not authorized. See [`DestructivePolicy`].
+    /// - `Error::Incompatible`: The repository uses an option or feature which its compatibility
+    /// target doesn't support.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    /// [`CommitReport`]: crate::repo::CommitReport
+    /// [`change_password`]: crate::repo::key::KeyRepo::change_password
+    /// [`RepoConfig::max_header_size`]: crate::repo::RepoConfig::max_header_size
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    pub fn commit_with_report(&mut self) -> crate::Result<CommitReport> {
+        // Check that any destructive operations have been authorized before writing anything.
+        {
+            let state = self.state.read().unwrap();
+            state.check_writable()?;
+            state.interlock.check(&state.metadata.destructive_policy)?;
+
+            // Check that the metadata can be written for the repository's compatibility target.
+            encode_metadata(&state.metadata)?;
+        }
+
+        // Remove expired objects so they aren't committed. If the commit fails, they're put back so
+        // that they aren't silently dropped from the uncommitted state.
+        let expired_keys = self.attributes.expired(SystemTime::now());
+        let backup = if expired_keys.is_empty() {
+            None
+        } else {
+            Some(self.expiry_backup())
+        };
+        self.remove_expired(&expired_keys);
+
+        let result = self.write_object_map().and_then(|_| {
+            // Serialize the header.
+            let serialized_header = self.serialize_header()?;
+
+            // Write the serialized header to the data store, atomically completing the commit. If
+            // this completes successfully, changes have been committed and this method MUST return
+            // `Ok`.
+            self.write_serialized_header(serialized_header.as_slice(), true)
+        });
+        let report = match (result, backup) {
+            (Ok(report), _) => report,
+            (Err(error), Some(backup)) => {
+                self.restore_expired(backup, &expired_keys);
+                return Err(error);
+            }
+            (Err(error), None) => return Err(error),
+        };
+
+        // Now that the commit has succeeded, we must invalidate all savepoints associated with this
+        // repository.
+        self.transaction_id = Arc::new(Uuid::new_v4());
+        self.state.write().unwrap().interlock.reset();
+
+        Ok(report)
+    }
+
+    /// Commit changes which have been made to the repository along with an application `payload`.
+    ///
+    /// This is the same as [`commit_with_report`], except it atomically stores `payload` in the
+    /// repository metadata as part of the commit. This can be used to keep state outside the
+    /// repository consistent with it, like a checkpoint token for an external index. The payload
+    /// of the latest commit can be retrieved with [`committed_payload`].
+    ///
+    /// The payload is kept until it's replaced by another call to this method, so commits made
+    /// with [`Commit::commit`] don't change it. Because the payload is stored in the metadata, it
+    /// should be small, and it is not encrypted.
+    ///
+    /// If this method returns `Err`, changes have not been committed and the payload is unchanged.
+    ///
+    /// # Errors
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::HeaderTooLarge`: The header is larger than [`RepoConfig::max_header_size`].
+    /// - `Error::DestructiveNotAuthorized`: The changes include destructive operations which were
+    /// not authorized. See [`DestructivePolicy`].
+    /// - `Error::Incompatible`: The repository uses an option or feature which its compatibility
+    /// target doesn't support, including a nonempty `payload`.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`commit_with_report`]: crate::repo::key::KeyRepo::commit_with_report
+    /// [`committed_payload`]: crate::repo::key::KeyRepo::committed_payload
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    /// [`RepoConfig::max_header_size`]: crate::repo::RepoConfig::max_header_size
+    /// [`DestructivePolicy`]: crate::repo::DestructivePolicy
+    pub fn commit_with_payload(&mut self, payload: &[u8]) -> crate::Result<CommitReport> {
+        let previous_payload = mem::replace(
+            &mut self.state.write().unwrap().metadata.commit_payload,
+            payload.to_vec(),
+        );
+
+        let result = self.commit_with_report();
+
+        if result.is_err() {
+            self.state.write().unwrap().metadata.commit_payload = previous_payload;
         }
+
+        result
+    }
+
+    /// Return the application payload which was stored by the latest call to
+    /// [`commit_with_payload`].
+    ///
+    /// This returns an empty payload if no payload has been stored.
+    ///
+    /// [`commit_with_payload`]: crate::repo::key::KeyRepo::commit_with_payload
+    pub fn committed_payload(&self) -> Vec<u8> {
+        self.state.read().unwrap().metadata.commit_payload.clone()
+    }
+
+    /// Return the ID of the latest commit of this repository.
+    ///
+    /// This is the ID of the commit this repository was opened at until changes are committed.
+    /// Use [`peek_commit_id`] to get the commit ID of a repository without opening it.
+    ///
+    /// [`peek_commit_id`]: crate::repo::peek_commit_id
+    pub fn current_commit_id(&self) -> CommitId {
+        self.state.read().unwrap().metadata.commit_id
+    }
+
+    /// Return the commits in this repository's commit history, from oldest to newest.
+    ///
+    /// The newest commit is the latest commit of this repository. This returns an empty list if
+    /// [`RepoConfig::commit_history`] is `0` or no commits have been made since it was set.
+    ///
+    /// [`RepoConfig::commit_history`]: crate::repo::RepoConfig::commit_history
+    pub fn list_commits(&self) -> Vec<CommitInfo> {
+        self.state.read().unwrap().metadata.commit_history.clone()
+    }
+
+    /// Restore the repository to the state it was in after the commit with the given `commit_id`.
+    ///
+    /// The commit must be in the list returned by [`list_commits`]. This restores the objects in
+    /// every instance of the repository, but it does not change the repository metadata, like the
+    /// password or the commit history. Like other changes, this is not persisted to the data store
+    /// until the repository is committed, and it can be undone with [`Commit::rollback`].
+    ///
+    /// If this returns `Err`, the repository is unchanged.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no commit with the given `commit_id` in the commit history.
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`list_commits`]: crate::repo::key::KeyRepo::list_commits
+    /// [`Commit::rollback`]: crate::repo::Commit::rollback
+    pub fn rollback_to_commit(&mut self, commit_id: CommitId) -> crate::Result<()> {
+        let state = self.state.read().unwrap();
+        let header_id = state
+            .metadata
+            .commit_history
+            .iter()
+            .find(|info| info.id == commit_id)
+            .ok_or(crate::Error::NotFound)?
+            .header_id;
+        let mut header = read_header(&state, header_id)?;
+
+        // Blocks may have been repacked since this commit was made, in which case the pack map in
+        // its header is out of date. Blocks in the current pack map are always where it says.
+        header
+            .packs
+            .extend(state.packs.iter().map(|(id, packs)| (*id, packs.clone())));
+        drop(state);
+
+        self.restore_header(header)
     }
 
     /// Return information about the repository.
     pub fn info(&self) -> RepoInfo {
-        self.state.read().unwrap().metadata.to_info()
+        let state = self.state.read().unwrap();
+        let capabilities = state.store.lock().unwrap().probe();
+        state.metadata.to_info(capabilities)
     }
 }
 
@@ -625,9 +2952,14 @@ impl<K: Key> RestoreSavepoint for KeyRepo<K> {
 
         let old_header = self.replace_header((*savepoint.header).clone());
 
-        match self.read_object_map() {
-            Ok(objects) => Ok(KeyRestore {
+        match self
+            .read_object_map()
+            .and_then(|objects| Ok((objects, self.read_trash()?, self.read_attributes()?)))
+        {
+            Ok((objects, trash, attributes)) => Ok(KeyRestore {
                 objects,
+                trash,
+                attributes,
                 header: self.replace_header(old_header),
                 transaction_id: savepoint.transaction_id.clone(),
                 instance_id: self.instance_id,
@@ -652,6 +2984,9 @@ impl<K: Key> RestoreSavepoint for KeyRepo<K> {
 
         self.replace_header(restore.header);
         self.objects = restore.objects;
+        self.trash = restore.trash;
+        self.attributes = restore.attributes;
+        self.key_index.reset();
 
         true
     }
@@ -659,56 +2994,45 @@ impl<K: Key> RestoreSavepoint for KeyRepo<K> {
 
 impl<K: Key> Commit for KeyRepo<K> {
     fn commit(&mut self) -> crate::Result<()> {
-        // Write the map of objects for the current instance.
-        self.write_object_map()?;
-
-        // Serialize the header.
-        let serialized_header = self.serialize_header();
-
-        // Write the serialized header to the data store, atomically completing the commit. If this
-        // completes successfully, changes have been committed and this method MUST return `Ok`.
-        self.write_serialized_header(serialized_header.as_slice())?;
-
-        // Now that the commit has succeeded, we must invalidate all savepoints associated with this
-        // repository.
-        self.transaction_id = Arc::new(Uuid::new_v4());
-
-        Ok(())
+        self.commit_with_report().map(|_| ())
     }
 
     fn rollback(&mut self) -> crate::Result<()> {
         let state = self.state.read().unwrap();
         // Read the header from the previous commit from the data store.
-        let encoded_header = state
-            .store
-            .lock()
-            .unwrap()
-            .read_block(BlockKey::Header(state.metadata.header_id))
-            .map_err(crate::Error::Store)?
-            .ok_or(crate::Error::Corrupt)?;
-        let serialized_header = state.decode_data(encoded_header.as_slice())?;
-        let header: Header =
-            from_read(serialized_header.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        let header = read_header(&state, state.metadata.header_id)?;
         drop(state);
 
+        // Uncommitted changes to the metadata, like a new password, are rolled back as well.
+        let committed_metadata = self.read_committed_metadata()?;
+
         // Atomically restore from the deserialized header.
-        self.restore_header(header)
+        self.restore_header(header)?;
+        let mut state = self.state.write().unwrap();
+        state.metadata = committed_metadata;
+        state.interlock.reset();
+        Ok(())
     }
 
     fn clean(&mut self) -> crate::Result<()> {
         let mut state = self.state.write().unwrap();
+        state.check_writable()?;
 
         // Read the header from the previous commit.
-        let encoded_header = state
-            .store
-            .lock()
-            .unwrap()
-            .read_block(BlockKey::Header(state.metadata.header_id))
-            .map_err(crate::Error::Store)?
-            .ok_or(crate::Error::Corrupt)?;
-        let serialized_header = state.decode_data(encoded_header.as_slice())?;
-        let previous_header: Header =
-            from_read(serialized_header.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        let previous_header = read_header(&state, state.metadata.header_id)?;
+
+        // Read the headers of older commits in the commit history, which must stay restorable.
+        let history_header_ids = state
+            .metadata
+            .commit_history
+            .iter()
+            .map(|info| info.header_id)
+            .filter(|&header_id| header_id != state.metadata.header_id)
+            .collect::<HashSet<_>>();
+        let history_headers = history_header_ids
+            .iter()
+            .map(|&header_id| read_header(&state, header_id))
+            .collect::<crate::Result<Vec<_>>>()?;
 
         // We need to find the set of blocks which are either currently referenced by the repository
         // or were referenced after the previous commit. It's important that we don't clean up
@@ -722,6 +3046,15 @@ impl<K: Key> Commit for KeyRepo<K> {
             .collect::<HashSet<_>>();
         let previous_referenced_blocks = previous_header.chunks.values().map(|info| info.block_id);
         referenced_blocks.extend(previous_referenced_blocks);
+        for header in &history_headers {
+            referenced_blocks.extend(header.chunks.values().map(|info| info.block_id));
+        }
+
+        let mut tracker = RetentionTracker {
+            known: state.retained_blocks.clone(),
+            retained: HashMap::new(),
+            grace_period: state.metadata.config.gc_grace_period,
+        };
 
         // Remove all blocks from the data store which are unreferenced.
         match &state.metadata.config.packing {
@@ -737,13 +3070,18 @@ impl<K: Key> Commit for KeyRepo<K> {
                         .map_err(crate::Error::Store)?;
 
                     let mut store = state.store.lock().unwrap();
+                    let mut keys_to_remove = Vec::new();
                     for block_id in block_ids {
-                        if !referenced_blocks.contains(&block_id) {
-                            store
-                                .remove_block(BlockKey::Data(block_id))
-                                .map_err(crate::Error::Store)?;
+                        let key = BlockKey::Data(block_id);
+                        if !referenced_blocks.contains(&block_id)
+                            && tracker.is_removable(&mut **store, key)?
+                        {
+                            keys_to_remove.push(key);
                         }
                     }
+                    store
+                        .remove_blocks(&keys_to_remove)
+                        .map_err(crate::Error::Store)?;
                 }
 
                 drop(state);
@@ -753,7 +3091,15 @@ impl<K: Key> Commit for KeyRepo<K> {
                 // blocks.
 
                 // Get an iterator of block IDs and the list of packs they're contained in.
-                let blocks_to_packs = state.packs.iter().chain(previous_header.packs.iter());
+                let blocks_to_packs = state
+                    .packs
+                    .iter()
+                    .chain(previous_header.packs.iter())
+                    .chain(
+                        history_headers
+                            .iter()
+                            .flat_map(|header| header.packs.iter()),
+                    );
 
                 // Get a map of pack IDs to the set of blocks contained in them.
                 let mut packs_to_blocks = HashMap::new();
@@ -773,6 +3119,10 @@ impl<K: Key> Commit for KeyRepo<K> {
                 // are contained in packs which contain at least one unreferenced block.
                 let mut blocks_to_repack = Vec::new();
 
+                // The set of IDs of packs which contain unreferenced blocks but are too new to be
+                // removed yet.
+                let mut packs_to_keep = HashSet::new();
+
                 // Iterate over the IDs of packs which are contained in the data store.
                 let data_blocks = state
                     .store
@@ -780,6 +3130,10 @@ impl<K: Key> Commit for KeyRepo<K> {
                     .unwrap()
                     .list_blocks(BlockType::Data)
                     .map_err(crate::Error::Store)?;
+                let mut is_removable = |pack_id: BlockId| {
+                    let mut store = state.store.lock().unwrap();
+                    tracker.is_removable(&mut **store, BlockKey::Data(pack_id))
+                };
                 for pack_id in data_blocks {
                     match packs_to_blocks.get(&pack_id) {
                         Some(contained_blocks) => {
@@ -787,38 +3141,76 @@ impl<K: Key> Commit for KeyRepo<K> {
                                 .iter()
                                 .any(|block_id| !referenced_blocks.contains(block_id));
                             if contains_unreferenced_blocks {
-                                let contained_referenced_blocks =
-                                    contained_blocks.intersection(&referenced_blocks).copied();
-                                packs_to_remove.push(pack_id);
-                                blocks_to_repack.extend(contained_referenced_blocks);
+                                if is_removable(pack_id)? {
+                                    let contained_referenced_blocks =
+                                        contained_blocks.intersection(&referenced_blocks).copied();
+                                    packs_to_remove.push(pack_id);
+                                    blocks_to_repack.extend(contained_referenced_blocks);
+                                } else {
+                                    packs_to_keep.insert(pack_id);
+                                }
                             }
                         }
                         // This pack does not contain any blocks that we know about. We can remove
                         // it.
-                        None => packs_to_remove.push(pack_id),
+                        None => {
+                            if is_removable(pack_id)? {
+                                packs_to_remove.push(pack_id);
+                            }
+                        }
                     }
                 }
 
                 // For each block that needs repacking, read it from its current pack and write it
                 // to a new one.
                 {
+                    // Blocks which were written by an object with its own compression method need
+                    // to be decoded and re-encoded with that method.
+                    let block_compression = state
+                        .chunks
+                        .values()
+                        .chain(previous_header.chunks.values())
+                        .chain(
+                            history_headers
+                                .iter()
+                                .flat_map(|header| header.chunks.values()),
+                        )
+                        .filter_map(|chunk_info| {
+                            chunk_info
+                                .compression
+                                .clone()
+                                .map(|compression| (chunk_info.block_id, compression))
+                        })
+                        .collect::<HashMap<_, _>>();
+                    let default_compression = state.metadata.config.compression.clone();
                     let mut store_state = StoreState::new();
                     let mut store_writer = StoreWriter::new(&mut state, &mut store_state);
                     for block_id in blocks_to_repack {
-                        let block_data = store_writer.read_block(block_id)?;
-                        store_writer.write_block(block_id, block_data.as_slice())?;
+                        let compression = block_compression
+                            .get(&block_id)
+                            .unwrap_or(&default_compression);
+                        let block_data = store_writer.read_block_with(block_id, compression)?;
+                        store_writer.write_block_with(
+                            block_id,
+                            block_data.as_slice(),
+                            compression,
+                        )?;
                     }
                 }
 
                 // Once all the referenced blocks have been written to new packs, remove the old
                 // packs from the data store.
                 {
-                    let mut store = state.store.lock().unwrap();
-                    for pack_id in packs_to_remove {
-                        store
-                            .remove_block(BlockKey::Data(pack_id))
-                            .map_err(crate::Error::Store)?;
-                    }
+                    let keys_to_remove = packs_to_remove
+                        .into_iter()
+                        .map(BlockKey::Data)
+                        .collect::<Vec<_>>();
+                    state
+                        .store
+                        .lock()
+                        .unwrap()
+                        .remove_blocks(&keys_to_remove)
+                        .map_err(crate::Error::Store)?;
                 }
 
                 // Once old packs have been removed from the data store, all unreferenced blocks
@@ -829,10 +3221,14 @@ impl<K: Key> Commit for KeyRepo<K> {
                 // and it will consume additional memory. For this reason, it's beneficial to remove
                 // nonexistent blocks from the pack map, but if this method returns early or panics
                 // before this step can complete, the repository will not be in an inconsistent
-                // state.
-                state
-                    .packs
-                    .retain(|block_id, _| referenced_blocks.contains(block_id));
+                // state. Unreferenced blocks in packs which we did not remove are kept so that those
+                // packs can be removed once they are old enough.
+                state.packs.retain(|block_id, index_list| {
+                    referenced_blocks.contains(block_id)
+                        || index_list
+                            .iter()
+                            .any(|pack_index| packs_to_keep.contains(&pack_index.id))
+                });
 
                 // Next we need to write the updated pack map to the data store. To do this, we have
                 // to write the entire header. Because this method does not commit any changes, it's
@@ -844,33 +3240,50 @@ impl<K: Key> Commit for KeyRepo<K> {
                     // serialize it. Once we're done, move it back. This avoids needing the clone
                     // the pack map.
                     previous_header.packs = std::mem::take(&mut state.packs);
-                    let serialized_header = to_vec(&previous_header)
-                        .expect("Could not serialize the repository header.");
+                    let serialized_header = format::serialize_header(
+                        &previous_header,
+                        state.metadata.config.compatibility_target,
+                    );
                     mem::swap(&mut previous_header.packs, &mut state.packs);
                     drop(previous_header);
+                    let serialized_header = serialized_header?;
 
-                    // Encode the serialized header and write it to the data store.
-                    let encoded_header = state.encode_data(serialized_header.as_slice())?;
+                    // Write the serialized header to the data store. It is encoded when it is
+                    // written. This must not commit any changes to the metadata either.
                     drop(state);
-                    self.write_serialized_header(encoded_header.as_slice())?;
+                    self.write_header_only(serialized_header.as_slice())?;
                 }
             }
         }
 
         // Remove old unreferenced headers from the data store.
         {
-            let state = self.state.read().unwrap();
-            let mut store = state.store.lock().unwrap();
-            let unreferenced_headers = store
-                .list_blocks(BlockType::Header)
-                .map_err(crate::Error::Store)?
-                .into_iter()
-                .filter(|&block_id| block_id != state.metadata.header_id);
-            for block_id in unreferenced_headers {
+            let mut state = self.state.write().unwrap();
+            {
+                let mut store = state.store.lock().unwrap();
+                let unreferenced_headers = store
+                    .list_blocks(BlockType::Header)
+                    .map_err(crate::Error::Store)?
+                    .into_iter()
+                    .filter(|&block_id| {
+                        block_id != state.metadata.header_id
+                            && !history_header_ids.contains(&block_id)
+                    });
+                let mut keys_to_remove = Vec::new();
+                for block_id in unreferenced_headers {
+                    let key = BlockKey::Header(block_id);
+                    if tracker.is_removable(&mut **store, key)? {
+                        keys_to_remove.push(key);
+                    }
+                }
                 store
-                    .remove_block(BlockKey::Header(block_id))
+                    .remove_blocks(&keys_to_remove)
                     .map_err(crate::Error::Store)?;
             }
+
+            // Remember which blocks are awaiting the expiry of their retention period so that they
+            // can be reported in the repository stats.
+            state.retained_blocks = tracker.retained;
         }
 
         Ok(())
@@ -880,43 +3293,54 @@ impl<K: Key> Commit for KeyRepo<K> {
 impl<K: Key> Unlock for KeyRepo<K> {
     fn unlock(&self) -> crate::Result<()> {
         let state = self.state.read().unwrap();
-        let mut store = state.store.lock().unwrap();
-        unlock_store(&mut *store, state.lock_id)
+        // A read-only repository has no lock to release.
+        if let Some(lock_id) = state.lock_id {
+            let mut store = state.store.lock().unwrap();
+            unlock_store(&mut *store, lock_id)?;
+        }
+        state.registration.unlocked();
+        Ok(())
     }
 
     fn is_locked(&self) -> crate::Result<bool> {
         let state = self.state.read().unwrap();
+        let lock_id = match state.lock_id {
+            Some(lock_id) => lock_id,
+            None => return Ok(false),
+        };
         let mut store = state.store.lock().unwrap();
         store
-            .read_block(BlockKey::Lock(state.lock_id))
+            .read_block(BlockKey::Lock(lock_id))
             .map_err(crate::Error::Store)
             .map(|result| result.is_some())
     }
 
     fn context(&self) -> crate::Result<Vec<u8>> {
         let state = self.state.read().unwrap();
+        let lock_id = state.lock_id.ok_or(crate::Error::NotLocked)?;
         let mut store = state.store.lock().unwrap();
         let encrypted_context = store
-            .read_block(BlockKey::Lock(state.lock_id))
+            .read_block(BlockKey::Lock(lock_id))
             .map_err(crate::Error::Store)?
             .ok_or(crate::Error::NotLocked)?;
-        state
-            .metadata
-            .config
-            .encryption
-            .decrypt(&encrypted_context, &state.master_key)
+        decode_lock(
+            &encrypted_context,
+            &state.metadata.config.encryption,
+            &state.master_key,
+        )
     }
 
     fn update_context(&self, context: &[u8]) -> crate::Result<()> {
         let state = self.state.read().unwrap();
+        let lock_id = state.lock_id.ok_or(crate::Error::ReadOnly)?;
         let mut store = state.store.lock().unwrap();
-        let encrypted_context = state
-            .metadata
-            .config
-            .encryption
-            .encrypt(context, &state.master_key);
+        let encrypted_context = encode_lock(
+            context,
+            &state.metadata.config.encryption,
+            &state.master_key,
+        );
         store
-            .write_block(BlockKey::Lock(state.lock_id), &encrypted_context)
+            .write_block(BlockKey::Lock(lock_id), &encrypted_context)
             .map_err(crate::Error::Store)
     }
 }
@@ -4,9 +4,11 @@ use std::sync::{Arc, RwLock, Weak};
 use static_assertions::assert_impl_all;
 use uuid::Uuid;
 
+use super::attributes::AttributeTable;
 use super::handle::ObjectHandle;
 use super::metadata::Header;
 use super::state::InstanceId;
+use super::trash::TrashEntry;
 
 /// A target for rolling back changes in a repository.
 ///
@@ -190,6 +192,8 @@ pub trait RestoreSavepoint {
 #[derive(Debug, Clone)]
 pub struct KeyRestore<K> {
     pub(super) objects: HashMap<K, Arc<RwLock<ObjectHandle>>>,
+    pub(super) trash: HashMap<K, TrashEntry>,
+    pub(super) attributes: AttributeTable<K>,
     pub(super) header: Header,
     pub(super) transaction_id: Weak<Uuid>,
     // We need to store the instance ID because it should not be possible to complete this restore
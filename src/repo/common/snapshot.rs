@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// An identifier for a snapshot of an object in a [`KeyRepo`].
+///
+/// This is returned by [`KeyRepo::snapshot`]. Snapshot IDs are unique, so an ID for a snapshot
+/// which has been removed never refers to another snapshot.
+///
+/// [`KeyRepo`]: crate::repo::key::KeyRepo
+/// [`KeyRepo::snapshot`]: crate::repo::key::KeyRepo::snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SnapshotId(Uuid);
+
+impl SnapshotId {
+    /// Return a new unique `SnapshotId`.
+    pub(super) fn new() -> Self {
+        SnapshotId(Uuid::new_v4())
+    }
+}
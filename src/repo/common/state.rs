@@ -1,19 +1,26 @@
 use std::collections::{HashMap, HashSet};
-use std::sync::Mutex;
+use std::mem;
+use std::sync::{Arc, Mutex};
 
 use cdchunking::ChunkerImpl;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::store::{BlockId, DataStore};
+use crate::diagnostics::Registration;
+use crate::store::{BlockId, BlockKey, DataStore, MemoryConfig, OpenStore};
 
+use super::buffer_pool::BufferPool;
+use super::chunk_cache::ChunkCache;
 use super::chunk_store::StoreState;
 use super::chunking::IncrementalChunker;
+use super::compression::{Compression, Dictionary};
+use super::destructive::Interlock;
 use super::encryption::EncryptionKey;
-use super::handle::{Chunk, Extent, HandleId, ObjectHandle};
+use super::handle::{Chunk, ChunkHash, Extent, HandleId, ObjectHandle};
 use super::lock::{unlock_store, Lock, LockTable};
 use super::metadata::RepoMetadata;
 use super::open_repo::VersionId;
+use super::rechunk::RechunkProgress;
 
 /// Information about a chunk in a repository.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -23,6 +30,10 @@ pub struct ChunkInfo {
 
     /// The IDs of objects which reference this chunk.
     pub references: HashSet<HandleId>,
+
+    /// The compression method used for this chunk if it differs from the repository's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
 }
 
 /// The location of a block in a pack.
@@ -56,17 +67,6 @@ impl Pack {
             buffer: Vec::with_capacity(pack_size as usize),
         }
     }
-
-    /// Return a clone of this buffer padded to `pack_size` with zeroes.
-    pub fn padded(&mut self, pack_size: u32) -> Vec<u8> {
-        assert!(
-            self.buffer.len() <= pack_size as usize,
-            "The size of the current pack has exceeded the configured pack size.",
-        );
-        let mut padded = self.buffer.clone();
-        padded.resize(pack_size as usize, 0u8);
-        padded
-    }
 }
 
 uuid_type! {
@@ -88,6 +88,20 @@ pub struct InstanceInfo {
     /// This object handle contains a serialized map of object IDs to object handles for that
     /// instance.
     pub objects: ObjectHandle,
+
+    /// The object handle used to store the serialized trash for that instance.
+    ///
+    /// This is `None` if no object has ever been moved to the trash in that instance.
+    #[serde(default)]
+    pub trash: Option<ObjectHandle>,
+
+    /// The object handle used to store the serialized metadata and tags of objects in that
+    /// instance.
+    ///
+    /// This is `None` if no object has ever had metadata or tags in that instance. This is skipped
+    /// when it's `None` so that headers without it are serialized the same way as before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<ObjectHandle>,
 }
 
 /// The state associated with a `KeyRepo`.
@@ -113,15 +127,76 @@ pub struct RepoState {
 
     /// The `BlockId` of the key which stores the lock on the repository.
     ///
-    /// This is used to release the lock when the repository is dropped.
-    pub lock_id: BlockId,
+    /// This is used to release the lock when the repository is dropped. This is `None` if the
+    /// repository was opened read-only, in which case it holds no lock.
+    pub lock_id: Option<BlockId>,
+
+    /// The entry for this repository in the table of open repositories.
+    pub registration: Registration,
+
+    /// The cache of decoded chunks, which may be shared with other repositories.
+    pub chunk_cache: Option<Arc<ChunkCache>>,
+
+    /// The pool of reusable buffers, which may be shared with other repositories.
+    pub buffer_pool: Arc<BufferPool>,
+
+    /// The hash of the serialized header which was last written to the data store.
+    ///
+    /// This is `None` if no header has been written since the repository was opened.
+    pub committed_header: Option<ChunkHash>,
+
+    /// The serialized metadata which was last written to the data store.
+    ///
+    /// This is `None` if no metadata has been written since the repository was opened.
+    pub committed_metadata: Option<Vec<u8>>,
+
+    /// The destructive operations performed since the last commit and the authorizations for them.
+    pub interlock: Interlock,
+
+    /// The writer ID to record in audit log entries.
+    pub audit_writer: Option<String>,
+
+    /// The sizes of unreferenced blocks which could not be removed the last time the repository
+    /// was cleaned because their retention period has not expired.
+    pub retained_blocks: HashMap<BlockKey, u64>,
+
+    /// The progress of an unfinished rechunk operation, if there is one.
+    pub rechunk: Option<RechunkProgress>,
+
+    /// The Zstandard dictionaries for compressing chunks, from oldest to newest.
+    pub dictionaries: Vec<Dictionary>,
+}
+
+impl RepoState {
+    /// Return `Error::ReadOnly` if the repository was opened read-only.
+    pub fn check_writable(&self) -> crate::Result<()> {
+        match self.lock_id {
+            Some(_) => Ok(()),
+            None => Err(crate::Error::ReadOnly),
+        }
+    }
+
+    /// Consume this state and return the data store without releasing the lock on it.
+    ///
+    /// The caller is responsible for releasing the lock.
+    pub fn into_store(mut self) -> Box<dyn DataStore> {
+        // We can't move the data store out of a type which implements `Drop`, so we swap in an
+        // empty in-memory data store instead. Releasing the lock on it when this is dropped is a
+        // no-op.
+        let placeholder = MemoryConfig::new()
+            .open()
+            .expect("Could not open an in-memory data store.");
+        mem::replace(self.store.get_mut().unwrap(), Box::new(placeholder))
+    }
 }
 
 impl Drop for RepoState {
     fn drop(&mut self) {
         // Attempt to release the lock on the repository. This may fail.
-        let mut store = self.store.lock().unwrap();
-        unlock_store(&mut *store, self.lock_id).ok();
+        if let Some(lock_id) = self.lock_id {
+            let mut store = self.store.lock().unwrap();
+            unlock_store(&mut *store, lock_id).ok();
+        }
     }
 }
 
@@ -181,7 +256,14 @@ pub struct ObjectState {
     /// If no data has been read, this is `None`.
     pub buffered_chunk: Option<Chunk>,
 
+    /// The offset in `buffered_chunk` of the data in `read_buffer`.
+    ///
+    /// This is only nonzero when part of a large chunk was read.
+    pub buffered_offset: u64,
+
     /// The contents of the chunk which was most recently read from.
+    ///
+    /// This may only contain part of the chunk, starting at `buffered_offset`.
     pub read_buffer: Vec<u8>,
 
     /// A pre-allocated buffer of null bytes to read from when reading a hole.
@@ -203,6 +285,7 @@ impl ObjectState {
             start_position: SeekPosition::Empty,
             position: 0,
             buffered_chunk: None,
+            buffered_offset: 0,
             read_buffer: Vec::new(),
             hole_buffer: Vec::new(),
             transaction_lock: None,
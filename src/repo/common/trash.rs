@@ -0,0 +1,17 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::handle::ObjectHandle;
+
+/// An object which has been moved to the trash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// The handle of the object.
+    ///
+    /// The chunks referenced by this handle are retained until the object is purged.
+    pub handle: ObjectHandle,
+
+    /// The time the object was moved to the trash.
+    pub deleted: SystemTime,
+}
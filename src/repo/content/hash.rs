@@ -0,0 +1,119 @@
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A cryptographic hash algorithm which is used to identify data in a [`ContentRepo`].
+///
+/// The default is [`HashAlgorithm::Blake3`].
+///
+/// [`ContentRepo`]: crate::repo::content::ContentRepo
+/// [`HashAlgorithm::Blake3`]: crate::repo::content::HashAlgorithm::Blake3
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum HashAlgorithm {
+    /// BLAKE3 with a 256-bit output.
+    #[default]
+    Blake3,
+
+    /// SHA-256.
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Return a new hasher which uses this algorithm.
+    pub(super) fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+}
+
+/// The cryptographic hash of some data in a [`ContentRepo`].
+///
+/// A `HashId` includes the [`HashAlgorithm`] which was used to compute it, so hashes computed
+/// with different algorithms are never equal.
+///
+/// The `Display` implementation formats the hash as a lowercase hexadecimal string.
+///
+/// [`ContentRepo`]: crate::repo::content::ContentRepo
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct HashId {
+    algorithm: HashAlgorithm,
+    digest: Vec<u8>,
+}
+
+impl HashId {
+    /// Return a new `HashId` from an existing `digest` computed with the given `algorithm`.
+    pub fn new(algorithm: HashAlgorithm, digest: impl Into<Vec<u8>>) -> Self {
+        HashId {
+            algorithm,
+            digest: digest.into(),
+        }
+    }
+
+    /// The algorithm which was used to compute this hash.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// The bytes of this hash.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.digest
+    }
+}
+
+impl Display for HashId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for byte in &self.digest {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// A hasher for one of the supported hash algorithms.
+pub enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    /// Add `data` to the hash.
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Return the hash of the data which has been added.
+    pub fn finalize(self) -> HashId {
+        match self {
+            Hasher::Blake3(hasher) => {
+                HashId::new(HashAlgorithm::Blake3, hasher.finalize().as_bytes().to_vec())
+            }
+            Hasher::Sha256(hasher) => {
+                HashId::new(HashAlgorithm::Sha256, hasher.finalize().to_vec())
+            }
+        }
+    }
+}
+
+/// A reader which computes a hash of the data read from it.
+pub struct HashingReader<R> {
+    pub inner: R,
+    pub hasher: Hasher,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..bytes_read]);
+        Ok(bytes_read)
+    }
+}
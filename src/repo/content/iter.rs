@@ -0,0 +1,30 @@
+use std::collections::hash_map;
+use std::iter::{ExactSizeIterator, FusedIterator};
+
+use super::hash::HashId;
+use crate::repo::state::ObjectKey;
+
+/// An iterator over the hashes of data in a [`ContentRepo`].
+///
+/// This value is created by [`ContentRepo::hashes`].
+///
+/// [`ContentRepo`]: crate::repo::content::ContentRepo
+/// [`ContentRepo::hashes`]: crate::repo::content::ContentRepo::hashes
+#[derive(Debug, Clone)]
+pub struct Hashes<'a>(pub(super) hash_map::Keys<'a, HashId, ObjectKey>);
+
+impl<'a> Iterator for Hashes<'a> {
+    type Item = &'a HashId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> FusedIterator for Hashes<'a> {}
+
+impl<'a> ExactSizeIterator for Hashes<'a> {}
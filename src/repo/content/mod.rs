@@ -0,0 +1,25 @@
+//! A content-addressable storage.
+//!
+//! This module contains the [`ContentRepo`] repository type.
+//!
+//! This is a repository which stores data keyed by its cryptographic hash. Putting data in the
+//! repository returns its [`HashId`], which can be used to read it back later. Putting the same
+//! data in the repository more than once only stores it once. The hash algorithm which is used can
+//! be chosen with [`HashAlgorithm`], and it can be changed at any time.
+//!
+//! Like other repositories, changes made to the repository are not persisted to the data store
+//! until [`Commit::commit`] is called. For details about deduplication, compression, encryption,
+//! and locking, see the module-level documentation for [`crate::repo`].
+//!
+//! [`ContentRepo`]: crate::repo::content::ContentRepo
+//! [`HashId`]: crate::repo::content::HashId
+//! [`HashAlgorithm`]: crate::repo::content::HashAlgorithm
+//! [`Commit::commit`]: crate::repo::Commit::commit
+
+pub use self::hash::{HashAlgorithm, HashId};
+pub use self::iter::Hashes;
+pub use self::repository::ContentRepo;
+
+mod hash;
+mod iter;
+mod repository;
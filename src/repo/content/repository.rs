@@ -0,0 +1,418 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use uuid::uuid;
+
+use super::hash::{HashAlgorithm, HashId, HashingReader};
+use super::iter::Hashes;
+use crate::repo::{
+    key::KeyRepo,
+    state::{ObjectKey, StateRepo},
+    AuditEntry, Chunking, Commit, CommitId, CommitInfo, CommitReport, DestructivePolicy,
+    DestructiveScope, InstanceId, MigrateProgress, OpenRepo, PoolStats, ReadOnlyObject,
+    RechunkOptions, RechunkReport, RepoInfo, RepoStats, ResourceLimit, RestoreSavepoint, Savepoint,
+    Unlock, VersionId,
+};
+use crate::store::DataStore;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoState {
+    /// The hash algorithm which is currently used to identify data.
+    pub algorithm: HashAlgorithm,
+
+    /// A map of the hashes of data to the objects which store it.
+    pub objects: HashMap<HashId, ObjectKey>,
+}
+
+/// A content-addressable storage.
+///
+/// See [`crate::repo::content`] for more information.
+#[derive(Debug)]
+pub struct ContentRepo(StateRepo<RepoState>);
+
+impl OpenRepo for ContentRepo {
+    type Key = <StateRepo<RepoState> as OpenRepo>::Key;
+
+    const VERSION_ID: VersionId = VersionId::new(uuid!("2c5e4d0e-c96a-11f1-a2f4-3b8e61c9d017"));
+
+    fn open_repo(repo: KeyRepo<Self::Key>) -> crate::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(StateRepo::open_repo(repo)?))
+    }
+
+    fn create_repo(repo: KeyRepo<Self::Key>) -> crate::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(StateRepo::create_repo(repo)?))
+    }
+
+    fn into_repo(self) -> crate::Result<KeyRepo<Self::Key>> {
+        self.0.into_repo()
+    }
+}
+
+impl ContentRepo {
+    /// Return whether there is data with the given `hash` in this repository.
+    pub fn contains(&self, hash: &HashId) -> bool {
+        self.0.state().objects.contains_key(hash)
+    }
+
+    /// Read data from `reader`, add it to the repository, and return its hash.
+    ///
+    /// The data is hashed using the current [`algorithm`]. If the data is already in the
+    /// repository, it is not stored again.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`algorithm`]: crate::repo::content::ContentRepo::algorithm
+    pub fn put(&mut self, reader: impl Read) -> crate::Result<HashId> {
+        let object_id = self.0.create();
+        let mut object = self.0.object(object_id).unwrap();
+        let mut reader = HashingReader {
+            inner: reader,
+            hasher: self.0.state().algorithm.hasher(),
+        };
+        let result = io::copy(&mut reader, &mut object)
+            .map_err(crate::Error::from)
+            .and_then(|_| object.commit());
+        drop(object);
+        if let Err(error) = result {
+            self.0.remove(object_id);
+            return Err(error);
+        }
+
+        let hash = reader.hasher.finalize();
+        if self.0.state().objects.contains_key(&hash) {
+            self.0.remove(object_id);
+        } else {
+            self.0.state_mut().objects.insert(hash.clone(), object_id);
+        }
+
+        Ok(hash)
+    }
+
+    /// Return an object for reading the data with the given `hash`.
+    ///
+    /// This returns `None` if there is no data with the given `hash` in the repository. Data in
+    /// the repository can't be modified, because that would change its hash.
+    pub fn get(&self, hash: &HashId) -> Option<ReadOnlyObject> {
+        let object_id = self.0.state().objects.get(hash)?;
+        // A new `Object` never has a transaction in progress, so this conversion can't fail.
+        ReadOnlyObject::try_from(self.0.object(*object_id)?).ok()
+    }
+
+    /// Remove the data with the given `hash` from the repository.
+    ///
+    /// This returns `true` if the data was removed or `false` if it didn't exist.
+    ///
+    /// The space used by the given data isn't reclaimed in the backing data store until changes
+    /// are committed and [`Commit::clean`] is called.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn remove(&mut self, hash: &HashId) -> bool {
+        match self.0.state_mut().objects.remove(hash) {
+            Some(object_id) => {
+                self.0.remove(object_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return an iterator of the hashes of all the data in this repository.
+    pub fn hashes(&self) -> Hashes {
+        Hashes(self.0.state().objects.keys())
+    }
+
+    /// Return the hash algorithm currently used by this repository.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.0.state().algorithm
+    }
+
+    /// Change the hash algorithm used by this repository to `algorithm`.
+    ///
+    /// This rehashes all the data in the repository using the new algorithm, which requires
+    /// reading all of it from the data store. Afterwards, the data in the repository can only be
+    /// accessed using the new hashes. If this returns an error, the repository is left unchanged.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn change_algorithm(&mut self, algorithm: HashAlgorithm) -> crate::Result<()> {
+        if algorithm == self.0.state().algorithm {
+            return Ok(());
+        }
+
+        let mut objects = HashMap::with_capacity(self.0.state().objects.len());
+        for object_id in self.0.state().objects.values() {
+            let mut reader = HashingReader {
+                inner: self.0.object(*object_id).unwrap(),
+                hasher: algorithm.hasher(),
+            };
+            io::copy(&mut reader, &mut io::sink())?;
+            objects.insert(reader.hasher.finalize(), *object_id);
+        }
+
+        let state = self.0.state_mut();
+        state.algorithm = algorithm;
+        state.objects = objects;
+
+        Ok(())
+    }
+
+    /// Verify the integrity of all the data in the repository.
+    ///
+    /// This returns the set of hashes of data which is corrupt.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn verify(&self) -> crate::Result<HashSet<&HashId>> {
+        let corrupt_keys = self.0.verify()?;
+        Ok(self
+            .0
+            .state()
+            .objects
+            .iter()
+            .filter(|(_, object_id)| corrupt_keys.contains(*object_id))
+            .map(|(hash, _)| hash)
+            .collect())
+    }
+
+    /// Delete all data in the current instance of the repository.
+    ///
+    /// See [`KeyRepo::clear_instance`] for details.
+    ///
+    /// [`KeyRepo::clear_instance`]: crate::repo::key::KeyRepo::clear_instance
+    pub fn clear_instance(&mut self) {
+        self.0.clear_instance()
+    }
+
+    /// Change the password for this repository.
+    ///
+    /// See [`KeyRepo::change_password`] for details.
+    ///
+    /// [`KeyRepo::change_password`]: crate::repo::key::KeyRepo::change_password
+    pub fn change_password(
+        &mut self,
+        new_password: &[u8],
+        memory_limit: ResourceLimit,
+        operations_limit: ResourceLimit,
+    ) {
+        self.0
+            .change_password(new_password, memory_limit, operations_limit);
+    }
+
+    /// Return the policy for authorizing destructive operations in this repository.
+    pub fn destructive_policy(&self) -> DestructivePolicy {
+        self.0.destructive_policy()
+    }
+
+    /// Change the policy for authorizing destructive operations in this repository.
+    ///
+    /// See [`KeyRepo::set_destructive_policy`] for details.
+    ///
+    /// [`KeyRepo::set_destructive_policy`]: crate::repo::key::KeyRepo::set_destructive_policy
+    pub fn set_destructive_policy(
+        &mut self,
+        password: &[u8],
+        policy: DestructivePolicy,
+    ) -> crate::Result<()> {
+        self.0.set_destructive_policy(password, policy)
+    }
+
+    /// Return the audit log of security-relevant operations performed on this repository.
+    ///
+    /// See [`KeyRepo::audit_log`] for details.
+    ///
+    /// [`KeyRepo::audit_log`]: crate::repo::key::KeyRepo::audit_log
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.0.audit_log()
+    }
+
+    /// Rewrite every object in the repository using the given `chunking` method.
+    ///
+    /// See [`KeyRepo::rechunk`] for details.
+    ///
+    /// [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
+    pub fn rechunk(
+        &mut self,
+        chunking: Chunking,
+        options: &RechunkOptions,
+    ) -> crate::Result<RechunkReport> {
+        self.0.rechunk(chunking, options)
+    }
+
+    /// Move this repository to `new_store`.
+    ///
+    /// See [`KeyRepo::migrate_store`] for details.
+    ///
+    /// [`KeyRepo::migrate_store`]: crate::repo::key::KeyRepo::migrate_store
+    pub fn migrate_store(
+        &mut self,
+        new_store: impl DataStore + 'static,
+        progress: impl FnMut(MigrateProgress) -> bool,
+    ) -> crate::Result<Box<dyn DataStore>> {
+        self.0.migrate_store(new_store, progress)
+    }
+
+    /// Authorize destructive operations in the given `scope` for the next `ttl`.
+    ///
+    /// See [`KeyRepo::authorize_destructive`] for details.
+    ///
+    /// [`KeyRepo::authorize_destructive`]: crate::repo::key::KeyRepo::authorize_destructive
+    pub fn authorize_destructive(&mut self, scope: DestructiveScope, ttl: Duration) {
+        self.0.authorize_destructive(scope, ttl)
+    }
+
+    /// Return this repository's instance ID.
+    pub fn instance(&self) -> InstanceId {
+        self.0.instance()
+    }
+
+    /// Compute statistics about the repository.
+    ///
+    /// See [`KeyRepo::stats`] for details.
+    ///
+    /// [`KeyRepo::stats`]: crate::repo::key::KeyRepo::stats
+    pub fn stats(&self) -> RepoStats {
+        self.0.stats()
+    }
+
+    /// Return statistics about the pool of buffers used to encode and decode chunks.
+    ///
+    /// See [`KeyRepo::pool_stats`] for details.
+    ///
+    /// [`KeyRepo::pool_stats`]: crate::repo::key::KeyRepo::pool_stats
+    pub fn pool_stats(&self) -> PoolStats {
+        self.0.pool_stats()
+    }
+
+    /// Commit changes which have been made to the repository along with an application `payload`.
+    ///
+    /// See [`KeyRepo::commit_with_payload`] for details.
+    ///
+    /// [`KeyRepo::commit_with_payload`]: crate::repo::key::KeyRepo::commit_with_payload
+    pub fn commit_with_payload(&mut self, payload: &[u8]) -> crate::Result<CommitReport> {
+        self.0.commit_with_payload(payload)
+    }
+
+    /// Return the application payload which was stored by the latest commit.
+    ///
+    /// See [`KeyRepo::committed_payload`] for details.
+    ///
+    /// [`KeyRepo::committed_payload`]: crate::repo::key::KeyRepo::committed_payload
+    pub fn committed_payload(&self) -> Vec<u8> {
+        self.0.committed_payload()
+    }
+
+    /// Return the ID of the latest commit of this repository.
+    ///
+    /// See [`KeyRepo::current_commit_id`] for details.
+    ///
+    /// [`KeyRepo::current_commit_id`]: crate::repo::key::KeyRepo::current_commit_id
+    pub fn current_commit_id(&self) -> CommitId {
+        self.0.current_commit_id()
+    }
+
+    /// Return the commits in this repository's commit history, from oldest to newest.
+    ///
+    /// See [`KeyRepo::list_commits`] for details.
+    ///
+    /// [`KeyRepo::list_commits`]: crate::repo::key::KeyRepo::list_commits
+    pub fn list_commits(&self) -> Vec<CommitInfo> {
+        self.0.list_commits()
+    }
+
+    /// Restore the repository to the state it was in after the commit with the given `commit_id`.
+    ///
+    /// See [`KeyRepo::rollback_to_commit`] for details.
+    ///
+    /// [`KeyRepo::rollback_to_commit`]: crate::repo::key::KeyRepo::rollback_to_commit
+    pub fn rollback_to_commit(&mut self, commit_id: CommitId) -> crate::Result<()> {
+        self.0.rollback_to_commit(commit_id)
+    }
+
+    /// Change the number of previous commits to keep in the commit history.
+    ///
+    /// See [`KeyRepo::set_commit_history`] for details.
+    ///
+    /// [`KeyRepo::set_commit_history`]: crate::repo::key::KeyRepo::set_commit_history
+    pub fn set_commit_history(&mut self, commits: u32) {
+        self.0.set_commit_history(commits)
+    }
+
+    /// Train a Zstandard dictionary from the chunks in this repository.
+    ///
+    /// See [`KeyRepo::train_dictionary`] for details.
+    ///
+    /// [`KeyRepo::train_dictionary`]: crate::repo::key::KeyRepo::train_dictionary
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub fn train_dictionary(&mut self, size: usize) -> crate::Result<()> {
+        self.0.train_dictionary(size)
+    }
+
+    /// Return information about the repository.
+    pub fn info(&self) -> RepoInfo {
+        self.0.info()
+    }
+}
+
+impl Commit for ContentRepo {
+    fn commit(&mut self) -> crate::Result<()> {
+        self.0.commit()
+    }
+
+    fn rollback(&mut self) -> crate::Result<()> {
+        self.0.rollback()
+    }
+
+    fn clean(&mut self) -> crate::Result<()> {
+        self.0.clean()
+    }
+}
+
+impl RestoreSavepoint for ContentRepo {
+    type Restore = <StateRepo<RepoState> as RestoreSavepoint>::Restore;
+
+    fn savepoint(&mut self) -> crate::Result<Savepoint> {
+        self.0.savepoint()
+    }
+
+    fn start_restore(&mut self, savepoint: &Savepoint) -> crate::Result<Self::Restore> {
+        self.0.start_restore(savepoint)
+    }
+
+    fn finish_restore(&mut self, restore: Self::Restore) -> bool {
+        self.0.finish_restore(restore)
+    }
+}
+
+impl Unlock for ContentRepo {
+    fn unlock(&self) -> crate::Result<()> {
+        self.0.unlock()
+    }
+
+    fn is_locked(&self) -> crate::Result<bool> {
+        self.0.is_locked()
+    }
+
+    fn context(&self) -> crate::Result<Vec<u8>> {
+        self.0.context()
+    }
+
+    fn update_context(&self, context: &[u8]) -> crate::Result<()> {
+        self.0.update_context(context)
+    }
+}
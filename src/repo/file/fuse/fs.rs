@@ -248,6 +248,17 @@ impl<'a> FuseAdapter<'a> {
 }
 
 impl<'a> Filesystem for FuseAdapter<'a> {
+    fn destroy(&mut self) {
+        // Writes to a file are only committed to the repository when the file is synced, so we
+        // need to commit any remaining changes when the file system is unmounted. There's no way
+        // to report an error here, so if this fails, the repository is rolled back to its last
+        // commit rather than being left with changes that were only partially committed.
+        let result = self.objects.commit_all().and_then(|_| self.repo.commit());
+        if result.is_err() {
+            self.repo.rollback().ok();
+        }
+    }
+
     fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
         let file_name = try_option!(name.to_str(), reply, libc::ENOENT);
         let entry_path = try_option!(self.inodes.path(parent), reply, libc::ENOENT).join(file_name);
@@ -2,8 +2,11 @@ use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::{create_dir, create_dir_all, hard_link, metadata};
 use std::io;
+#[cfg(feature = "export")]
+use std::io::Write;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use once_cell::sync::Lazy;
 use relative_path::{RelativePath, RelativePathBuf};
@@ -12,9 +15,17 @@ use uuid::uuid;
 use walkdir::WalkDir;
 
 use crate::repo::{
-    key::KeyRepo, state::StateRepo, Commit, InstanceId, Object, OpenRepo, RepoInfo, RepoStats,
-    ResourceLimit, RestoreSavepoint, Savepoint, Unlock, VersionId,
+    key::KeyRepo, state::StateRepo, AuditEntry, Chunking, Commit, CommitId, CommitInfo,
+    CommitReport, DestructivePolicy, DestructiveScope, InstanceId, MigrateProgress, Object,
+    OpenRepo, PoolStats, RechunkOptions, RechunkReport, RepoInfo, RepoStats, ResourceLimit,
+    RestoreSavepoint, Savepoint, Unlock, VersionId,
 };
+use crate::store::DataStore;
+
+#[cfg(feature = "export")]
+use crate::repo::common::export::{self, Exporter};
+#[cfg(feature = "export")]
+use crate::repo::ExportOptions;
 
 use super::entry::{Entry, EntryHandle, EntryType, HandleType};
 use super::holes::{archive_file, extract_file};
@@ -1216,6 +1227,73 @@ where
             .collect())
     }
 
+    /// Export the contents of the current instance of the repository to a tar archive.
+    ///
+    /// Each entry is written to `dest` at its path in the repository. Regular files and directories
+    /// are written as regular files and directories in the archive. Special files only appear in
+    /// the manifest. Each entry's type and metadata are serialized to JSON as its attributes in the
+    /// manifest. Entries which are linked are written once for each path.
+    ///
+    /// See [`KeyRepo::export_plaintext`] for details.
+    ///
+    /// # Errors
+    /// - `Error::Cancelled`: The progress hook cancelled the export.
+    /// - `Error::Serialize`: An entry could not be serialized for the manifest.
+    /// - `Error::Deserialize`: The file metadata could not be deserialized.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`KeyRepo::export_plaintext`]: crate::repo::key::KeyRepo::export_plaintext
+    #[cfg(feature = "export")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "export")))]
+    pub fn export_plaintext<W: Write>(
+        &self,
+        dest: W,
+        options: &mut ExportOptions<RelativePath>,
+    ) -> crate::Result<W> {
+        let entries = self
+            .repo
+            .state()
+            .tree
+            .descendants(&*EMPTY_PATH)
+            .unwrap()
+            .map(|(path, entry_handle)| (path, *entry_handle))
+            .collect::<Vec<_>>();
+        let mut exporter = Exporter::new(dest, options, entries.len() as u64);
+
+        for (path, entry_handle) in entries {
+            if !exporter.includes(&path) {
+                continue;
+            }
+
+            let entry: Entry<S, M> = self
+                .repo
+                .object(entry_handle.entry)
+                .unwrap()
+                .deserialize()?;
+            let archive_path = path.to_path("");
+            let key = export::to_json(path.as_str())?;
+            let attributes = Some(export::to_json(&entry)?);
+
+            match entry_handle.kind {
+                HandleType::File(object_id) => {
+                    let mut object = self.repo.object(object_id).unwrap();
+                    let size = object.size()?;
+                    exporter.append_file(&archive_path, key, size, &mut object, attributes)?;
+                }
+                HandleType::Directory => {
+                    exporter.append_directory(&archive_path, key, attributes)?;
+                }
+                HandleType::Special => {
+                    exporter.append_manifest_only(&archive_path, key, attributes)?;
+                }
+            }
+        }
+
+        exporter.finish()
+    }
+
     /// Delete all data in the current instance of the repository.
     ///
     /// See [`KeyRepo::clear_instance`] for details.
@@ -1240,6 +1318,68 @@ where
             .change_password(new_password, memory_limit, operations_limit);
     }
 
+    /// Return the policy for authorizing destructive operations in this repository.
+    pub fn destructive_policy(&self) -> DestructivePolicy {
+        self.repo.destructive_policy()
+    }
+
+    /// Change the policy for authorizing destructive operations in this repository.
+    ///
+    /// See [`KeyRepo::set_destructive_policy`] for details.
+    ///
+    /// [`KeyRepo::set_destructive_policy`]: crate::repo::key::KeyRepo::set_destructive_policy
+    pub fn set_destructive_policy(
+        &mut self,
+        password: &[u8],
+        policy: DestructivePolicy,
+    ) -> crate::Result<()> {
+        self.repo.set_destructive_policy(password, policy)
+    }
+
+    /// Return the audit log of security-relevant operations performed on this repository.
+    ///
+    /// See [`KeyRepo::audit_log`] for details.
+    ///
+    /// [`KeyRepo::audit_log`]: crate::repo::key::KeyRepo::audit_log
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.repo.audit_log()
+    }
+
+    /// Rewrite every object in the repository using the given `chunking` method.
+    ///
+    /// See [`KeyRepo::rechunk`] for details.
+    ///
+    /// [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
+    pub fn rechunk(
+        &mut self,
+        chunking: Chunking,
+        options: &RechunkOptions,
+    ) -> crate::Result<RechunkReport> {
+        self.repo.rechunk(chunking, options)
+    }
+
+    /// Move this repository to `new_store`.
+    ///
+    /// See [`KeyRepo::migrate_store`] for details.
+    ///
+    /// [`KeyRepo::migrate_store`]: crate::repo::key::KeyRepo::migrate_store
+    pub fn migrate_store(
+        &mut self,
+        new_store: impl DataStore + 'static,
+        progress: impl FnMut(MigrateProgress) -> bool,
+    ) -> crate::Result<Box<dyn DataStore>> {
+        self.repo.migrate_store(new_store, progress)
+    }
+
+    /// Authorize destructive operations in the given `scope` for the next `ttl`.
+    ///
+    /// See [`KeyRepo::authorize_destructive`] for details.
+    ///
+    /// [`KeyRepo::authorize_destructive`]: crate::repo::key::KeyRepo::authorize_destructive
+    pub fn authorize_destructive(&mut self, scope: DestructiveScope, ttl: Duration) {
+        self.repo.authorize_destructive(scope, ttl)
+    }
+
     /// Return this repository's instance ID.
     pub fn instance(&self) -> InstanceId {
         self.repo.instance()
@@ -1254,6 +1394,80 @@ where
         self.repo.stats()
     }
 
+    /// Return statistics about the pool of buffers used to encode and decode chunks.
+    ///
+    /// See [`KeyRepo::pool_stats`] for details.
+    ///
+    /// [`KeyRepo::pool_stats`]: crate::repo::key::KeyRepo::pool_stats
+    pub fn pool_stats(&self) -> PoolStats {
+        self.repo.pool_stats()
+    }
+
+    /// Commit changes which have been made to the repository along with an application `payload`.
+    ///
+    /// See [`KeyRepo::commit_with_payload`] for details.
+    ///
+    /// [`KeyRepo::commit_with_payload`]: crate::repo::key::KeyRepo::commit_with_payload
+    pub fn commit_with_payload(&mut self, payload: &[u8]) -> crate::Result<CommitReport> {
+        self.repo.commit_with_payload(payload)
+    }
+
+    /// Return the application payload which was stored by the latest commit.
+    ///
+    /// See [`KeyRepo::committed_payload`] for details.
+    ///
+    /// [`KeyRepo::committed_payload`]: crate::repo::key::KeyRepo::committed_payload
+    pub fn committed_payload(&self) -> Vec<u8> {
+        self.repo.committed_payload()
+    }
+
+    /// Return the ID of the latest commit of this repository.
+    ///
+    /// See [`KeyRepo::current_commit_id`] for details.
+    ///
+    /// [`KeyRepo::current_commit_id`]: crate::repo::key::KeyRepo::current_commit_id
+    pub fn current_commit_id(&self) -> CommitId {
+        self.repo.current_commit_id()
+    }
+
+    /// Return the commits in this repository's commit history, from oldest to newest.
+    ///
+    /// See [`KeyRepo::list_commits`] for details.
+    ///
+    /// [`KeyRepo::list_commits`]: crate::repo::key::KeyRepo::list_commits
+    pub fn list_commits(&self) -> Vec<CommitInfo> {
+        self.repo.list_commits()
+    }
+
+    /// Restore the repository to the state it was in after the commit with the given `commit_id`.
+    ///
+    /// See [`KeyRepo::rollback_to_commit`] for details.
+    ///
+    /// [`KeyRepo::rollback_to_commit`]: crate::repo::key::KeyRepo::rollback_to_commit
+    pub fn rollback_to_commit(&mut self, commit_id: CommitId) -> crate::Result<()> {
+        self.repo.rollback_to_commit(commit_id)
+    }
+
+    /// Change the number of previous commits to keep in the commit history.
+    ///
+    /// See [`KeyRepo::set_commit_history`] for details.
+    ///
+    /// [`KeyRepo::set_commit_history`]: crate::repo::key::KeyRepo::set_commit_history
+    pub fn set_commit_history(&mut self, commits: u32) {
+        self.repo.set_commit_history(commits)
+    }
+
+    /// Train a Zstandard dictionary from the chunks in this repository.
+    ///
+    /// See [`KeyRepo::train_dictionary`] for details.
+    ///
+    /// [`KeyRepo::train_dictionary`]: crate::repo::key::KeyRepo::train_dictionary
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub fn train_dictionary(&mut self, size: usize) -> crate::Result<()> {
+        self.repo.train_dictionary(size)
+    }
+
     /// Return information about the repository.
     pub fn info(&self) -> RepoInfo {
         self.repo.info()
@@ -1312,6 +1526,11 @@ impl FileRepo<UnixSpecial, UnixMetadata> {
     ///
     /// This method does not return until the file system is unmounted.
     ///
+    /// Changes to the file system, like creating or renaming files, are committed to the
+    /// repository as they're made. Writes to a file are buffered and only committed when the file
+    /// is synced, when it was opened with `O_SYNC` or `O_DSYNC`, or when the file system is
+    /// unmounted.
+    ///
     /// # Errors
     /// - `Error::InvalidPath`: The given `root` path is empty.
     /// - `Error::NotFound`: There is no entry at `root`.
@@ -0,0 +1,54 @@
+use std::collections::{hash_map, HashMap};
+use std::iter::{ExactSizeIterator, FusedIterator};
+
+use crate::repo::state::ObjectKey;
+
+/// An iterator over the keys in a [`ForkRepo`].
+///
+/// This value is created by [`ForkRepo::keys`].
+///
+/// [`ForkRepo`]: crate::repo::fork::ForkRepo
+/// [`ForkRepo::keys`]: crate::repo::fork::ForkRepo::keys
+#[derive(Debug, Clone)]
+pub struct Keys<'a, K>(pub(super) hash_map::Keys<'a, K, HashMap<String, ObjectKey>>);
+
+impl<'a, K> Iterator for Keys<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K> FusedIterator for Keys<'a, K> {}
+
+impl<'a, K> ExactSizeIterator for Keys<'a, K> {}
+
+/// An iterator over the names of the forks of a key in a [`ForkRepo`].
+///
+/// This value is created by [`ForkRepo::forks`].
+///
+/// [`ForkRepo`]: crate::repo::fork::ForkRepo
+/// [`ForkRepo::forks`]: crate::repo::fork::ForkRepo::forks
+#[derive(Debug, Clone)]
+pub struct Forks<'a>(pub(super) hash_map::Keys<'a, String, ObjectKey>);
+
+impl<'a> Iterator for Forks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(String::as_str)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> FusedIterator for Forks<'a> {}
+
+impl<'a> ExactSizeIterator for Forks<'a> {}
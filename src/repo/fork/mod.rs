@@ -0,0 +1,25 @@
+//! A repository which stores multiple named streams of data for each key.
+//!
+//! This module contains the [`ForkRepo`] repository type.
+//!
+//! This is a repository like [`KeyRepo`] except each key maps to any number of named objects,
+//! called forks, instead of a single object. For example, a key for an image could have a `data`
+//! fork containing the image itself, a `thumbnail` fork containing a smaller version of the image,
+//! and a `metadata` fork containing information about it. Each fork is an independent [`Object`]
+//! which can be read from and written to separately. A key exists in the repository as long as it
+//! has at least one fork.
+//!
+//! Like other repositories, changes made to the repository are not persisted to the data store
+//! until [`Commit::commit`] is called. For details about deduplication, compression, encryption,
+//! and locking, see the module-level documentation for [`crate::repo`].
+//!
+//! [`ForkRepo`]: crate::repo::fork::ForkRepo
+//! [`KeyRepo`]: crate::repo::key::KeyRepo
+//! [`Object`]: crate::repo::Object
+//! [`Commit::commit`]: crate::repo::Commit::commit
+
+pub use self::iter::{Forks, Keys};
+pub use self::repository::ForkRepo;
+
+mod iter;
+mod repository;
@@ -0,0 +1,450 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::time::Duration;
+
+use uuid::uuid;
+
+use super::iter::{Forks, Keys};
+use crate::repo::{
+    key::{Key, KeyRepo},
+    state::{ObjectKey, StateRepo},
+    AuditEntry, Chunking, Commit, CommitId, CommitInfo, CommitReport, DestructivePolicy,
+    DestructiveScope, InstanceId, MigrateProgress, Object, OpenRepo, PoolStats, RechunkOptions,
+    RechunkReport, RepoInfo, RepoStats, ResourceLimit, RestoreSavepoint, Savepoint, Unlock,
+    VersionId,
+};
+use crate::store::DataStore;
+
+type RepoState<K> = HashMap<K, HashMap<String, ObjectKey>>;
+
+/// A repository which stores multiple named streams of data for each key.
+///
+/// See [`crate::repo::fork`] for more information.
+#[derive(Debug)]
+pub struct ForkRepo<K: Key>(StateRepo<RepoState<K>>);
+
+impl<K: Key> OpenRepo for ForkRepo<K> {
+    type Key = <StateRepo<RepoState<K>> as OpenRepo>::Key;
+
+    const VERSION_ID: VersionId = VersionId::new(uuid!("5b0e8f3a-caf1-11f1-9c3d-7f2a4e6b8d10"));
+
+    fn open_repo(repo: KeyRepo<Self::Key>) -> crate::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(StateRepo::open_repo(repo)?))
+    }
+
+    fn create_repo(repo: KeyRepo<Self::Key>) -> crate::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(StateRepo::create_repo(repo)?))
+    }
+
+    fn into_repo(self) -> crate::Result<KeyRepo<Self::Key>> {
+        self.0.into_repo()
+    }
+}
+
+impl<K: Key> ForkRepo<K> {
+    /// Return whether there is a key with at least one fork in this repository.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.0.state().contains_key(key)
+    }
+
+    /// Return whether the given `key` has a fork with the given `name`.
+    pub fn contains_fork<Q>(&self, key: &Q, name: &str) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.0.state().get(key) {
+            Some(forks) => forks.contains_key(name),
+            None => false,
+        }
+    }
+
+    /// Add a new fork with the given `name` to the given `key` and return it.
+    ///
+    /// If the `key` doesn't exist, it is created. If the `key` already has a fork with the same
+    /// `name`, that fork is replaced. Other forks of the `key` are unaffected.
+    pub fn insert(&mut self, key: K, name: impl Into<String>) -> Object {
+        let object_id = self.0.create();
+        let prev_object_id = self
+            .0
+            .state_mut()
+            .entry(key)
+            .or_default()
+            .insert(name.into(), object_id);
+        if let Some(prev_object_id) = prev_object_id {
+            self.0.remove(prev_object_id);
+        }
+        self.0.object(object_id).unwrap()
+    }
+
+    /// Remove the given `key` and all of its forks from the repository.
+    ///
+    /// This returns `true` if the key was removed or `false` if it didn't exist.
+    ///
+    /// The space used by the forks isn't reclaimed in the backing data store until changes are
+    /// committed and [`Commit::clean`] is called.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.0.state_mut().remove(key) {
+            Some(forks) => {
+                for object_id in forks.into_values() {
+                    self.0.remove(object_id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the fork with the given `name` from the given `key`.
+    ///
+    /// This returns `true` if the fork was removed or `false` if it didn't exist. If this was the
+    /// last fork of the `key`, the `key` is removed as well.
+    ///
+    /// The space used by the fork isn't reclaimed in the backing data store until changes are
+    /// committed and [`Commit::clean`] is called.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn remove_fork<Q>(&mut self, key: &Q, name: &str) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let forks = match self.0.state_mut().get_mut(key) {
+            Some(forks) => forks,
+            None => return false,
+        };
+        let object_id = match forks.remove(name) {
+            Some(object_id) => object_id,
+            None => return false,
+        };
+        if forks.is_empty() {
+            self.0.state_mut().remove(key);
+        }
+        self.0.remove(object_id);
+        true
+    }
+
+    /// Return the fork with the given `name` of the given `key`.
+    ///
+    /// This returns `None` if the fork doesn't exist.
+    pub fn object<Q>(&self, key: &Q, name: &str) -> Option<Object>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let object_id = *self.0.state().get(key)?.get(name)?;
+        self.0.object(object_id)
+    }
+
+    /// Return an iterator of all the keys in this repository.
+    pub fn keys(&self) -> Keys<K> {
+        Keys(self.0.state().keys())
+    }
+
+    /// Return an iterator of the names of the forks of the given `key`.
+    ///
+    /// This returns `None` if the `key` doesn't exist.
+    pub fn forks<Q>(&self, key: &Q) -> Option<Forks>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let forks = self.0.state().get(key)?;
+        Some(Forks(forks.keys()))
+    }
+
+    /// Copy the given `source` key and all of its forks to `dest`.
+    ///
+    /// If `dest` already exists, it and all of its forks are replaced.
+    ///
+    /// This returns `true` if the key was copied or `false` if `source` didn't exist.
+    ///
+    /// This is a cheap operation which does not require copying the bytes in the forks.
+    pub fn copy<Q>(&mut self, source: &Q, dest: K) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let source_forks = match self.0.state().get(source) {
+            Some(forks) => forks.clone(),
+            None => return false,
+        };
+        let dest_forks = source_forks
+            .into_iter()
+            .map(|(name, object_id)| (name, self.0.copy(object_id).unwrap()))
+            .collect();
+        if let Some(prev_forks) = self.0.state_mut().insert(dest, dest_forks) {
+            for object_id in prev_forks.into_values() {
+                self.0.remove(object_id);
+            }
+        }
+        true
+    }
+
+    /// Verify the integrity of all the data in the repository.
+    ///
+    /// This returns the set of keys which have a corrupt fork.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn verify(&self) -> crate::Result<HashSet<&K>> {
+        let corrupt_keys = self.0.verify()?;
+        Ok(self
+            .0
+            .state()
+            .iter()
+            .filter(|(_, forks)| {
+                forks
+                    .values()
+                    .any(|object_id| corrupt_keys.contains(object_id))
+            })
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Delete all data in the current instance of the repository.
+    ///
+    /// See [`KeyRepo::clear_instance`] for details.
+    ///
+    /// [`KeyRepo::clear_instance`]: crate::repo::key::KeyRepo::clear_instance
+    pub fn clear_instance(&mut self) {
+        self.0.clear_instance()
+    }
+
+    /// Change the password for this repository.
+    ///
+    /// See [`KeyRepo::change_password`] for details.
+    ///
+    /// [`KeyRepo::change_password`]: crate::repo::key::KeyRepo::change_password
+    pub fn change_password(
+        &mut self,
+        new_password: &[u8],
+        memory_limit: ResourceLimit,
+        operations_limit: ResourceLimit,
+    ) {
+        self.0
+            .change_password(new_password, memory_limit, operations_limit);
+    }
+
+    /// Return the policy for authorizing destructive operations in this repository.
+    pub fn destructive_policy(&self) -> DestructivePolicy {
+        self.0.destructive_policy()
+    }
+
+    /// Change the policy for authorizing destructive operations in this repository.
+    ///
+    /// See [`KeyRepo::set_destructive_policy`] for details.
+    ///
+    /// [`KeyRepo::set_destructive_policy`]: crate::repo::key::KeyRepo::set_destructive_policy
+    pub fn set_destructive_policy(
+        &mut self,
+        password: &[u8],
+        policy: DestructivePolicy,
+    ) -> crate::Result<()> {
+        self.0.set_destructive_policy(password, policy)
+    }
+
+    /// Return the audit log of security-relevant operations performed on this repository.
+    ///
+    /// See [`KeyRepo::audit_log`] for details.
+    ///
+    /// [`KeyRepo::audit_log`]: crate::repo::key::KeyRepo::audit_log
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.0.audit_log()
+    }
+
+    /// Rewrite every object in the repository using the given `chunking` method.
+    ///
+    /// See [`KeyRepo::rechunk`] for details.
+    ///
+    /// [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
+    pub fn rechunk(
+        &mut self,
+        chunking: Chunking,
+        options: &RechunkOptions,
+    ) -> crate::Result<RechunkReport> {
+        self.0.rechunk(chunking, options)
+    }
+
+    /// Move this repository to `new_store`.
+    ///
+    /// See [`KeyRepo::migrate_store`] for details.
+    ///
+    /// [`KeyRepo::migrate_store`]: crate::repo::key::KeyRepo::migrate_store
+    pub fn migrate_store(
+        &mut self,
+        new_store: impl DataStore + 'static,
+        progress: impl FnMut(MigrateProgress) -> bool,
+    ) -> crate::Result<Box<dyn DataStore>> {
+        self.0.migrate_store(new_store, progress)
+    }
+
+    /// Authorize destructive operations in the given `scope` for the next `ttl`.
+    ///
+    /// See [`KeyRepo::authorize_destructive`] for details.
+    ///
+    /// [`KeyRepo::authorize_destructive`]: crate::repo::key::KeyRepo::authorize_destructive
+    pub fn authorize_destructive(&mut self, scope: DestructiveScope, ttl: Duration) {
+        self.0.authorize_destructive(scope, ttl)
+    }
+
+    /// Return this repository's instance ID.
+    pub fn instance(&self) -> InstanceId {
+        self.0.instance()
+    }
+
+    /// Compute statistics about the repository.
+    ///
+    /// See [`KeyRepo::stats`] for details.
+    ///
+    /// [`KeyRepo::stats`]: crate::repo::key::KeyRepo::stats
+    pub fn stats(&self) -> RepoStats {
+        self.0.stats()
+    }
+
+    /// Return statistics about the pool of buffers used to encode and decode chunks.
+    ///
+    /// See [`KeyRepo::pool_stats`] for details.
+    ///
+    /// [`KeyRepo::pool_stats`]: crate::repo::key::KeyRepo::pool_stats
+    pub fn pool_stats(&self) -> PoolStats {
+        self.0.pool_stats()
+    }
+
+    /// Commit changes which have been made to the repository along with an application `payload`.
+    ///
+    /// See [`KeyRepo::commit_with_payload`] for details.
+    ///
+    /// [`KeyRepo::commit_with_payload`]: crate::repo::key::KeyRepo::commit_with_payload
+    pub fn commit_with_payload(&mut self, payload: &[u8]) -> crate::Result<CommitReport> {
+        self.0.commit_with_payload(payload)
+    }
+
+    /// Return the application payload which was stored by the latest commit.
+    ///
+    /// See [`KeyRepo::committed_payload`] for details.
+    ///
+    /// [`KeyRepo::committed_payload`]: crate::repo::key::KeyRepo::committed_payload
+    pub fn committed_payload(&self) -> Vec<u8> {
+        self.0.committed_payload()
+    }
+
+    /// Return the ID of the latest commit of this repository.
+    ///
+    /// See [`KeyRepo::current_commit_id`] for details.
+    ///
+    /// [`KeyRepo::current_commit_id`]: crate::repo::key::KeyRepo::current_commit_id
+    pub fn current_commit_id(&self) -> CommitId {
+        self.0.current_commit_id()
+    }
+
+    /// Return the commits in this repository's commit history, from oldest to newest.
+    ///
+    /// See [`KeyRepo::list_commits`] for details.
+    ///
+    /// [`KeyRepo::list_commits`]: crate::repo::key::KeyRepo::list_commits
+    pub fn list_commits(&self) -> Vec<CommitInfo> {
+        self.0.list_commits()
+    }
+
+    /// Restore the repository to the state it was in after the commit with the given `commit_id`.
+    ///
+    /// See [`KeyRepo::rollback_to_commit`] for details.
+    ///
+    /// [`KeyRepo::rollback_to_commit`]: crate::repo::key::KeyRepo::rollback_to_commit
+    pub fn rollback_to_commit(&mut self, commit_id: CommitId) -> crate::Result<()> {
+        self.0.rollback_to_commit(commit_id)
+    }
+
+    /// Change the number of previous commits to keep in the commit history.
+    ///
+    /// See [`KeyRepo::set_commit_history`] for details.
+    ///
+    /// [`KeyRepo::set_commit_history`]: crate::repo::key::KeyRepo::set_commit_history
+    pub fn set_commit_history(&mut self, commits: u32) {
+        self.0.set_commit_history(commits)
+    }
+
+    /// Train a Zstandard dictionary from the chunks in this repository.
+    ///
+    /// See [`KeyRepo::train_dictionary`] for details.
+    ///
+    /// [`KeyRepo::train_dictionary`]: crate::repo::key::KeyRepo::train_dictionary
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub fn train_dictionary(&mut self, size: usize) -> crate::Result<()> {
+        self.0.train_dictionary(size)
+    }
+
+    /// Return information about the repository.
+    pub fn info(&self) -> RepoInfo {
+        self.0.info()
+    }
+}
+
+impl<K: Key> Commit for ForkRepo<K> {
+    fn commit(&mut self) -> crate::Result<()> {
+        self.0.commit()
+    }
+
+    fn rollback(&mut self) -> crate::Result<()> {
+        self.0.rollback()
+    }
+
+    fn clean(&mut self) -> crate::Result<()> {
+        self.0.clean()
+    }
+}
+
+impl<K: Key> RestoreSavepoint for ForkRepo<K> {
+    type Restore = <StateRepo<RepoState<K>> as RestoreSavepoint>::Restore;
+
+    fn savepoint(&mut self) -> crate::Result<Savepoint> {
+        self.0.savepoint()
+    }
+
+    fn start_restore(&mut self, savepoint: &Savepoint) -> crate::Result<Self::Restore> {
+        self.0.start_restore(savepoint)
+    }
+
+    fn finish_restore(&mut self, restore: Self::Restore) -> bool {
+        self.0.finish_restore(restore)
+    }
+}
+
+impl<K: Key> Unlock for ForkRepo<K> {
+    fn unlock(&self) -> crate::Result<()> {
+        self.0.unlock()
+    }
+
+    fn is_locked(&self) -> crate::Result<bool> {
+        self.0.is_locked()
+    }
+
+    fn context(&self) -> crate::Result<Vec<u8>> {
+        self.0.context()
+    }
+
+    fn update_context(&self, context: &[u8]) -> crate::Result<()> {
+        self.0.update_context(context)
+    }
+}
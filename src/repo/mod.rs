@@ -15,7 +15,8 @@
 //! # Deduplication
 //! Data in a repository is transparently deduplicated using either fixed-size chunking (faster) or
 //! contend-defined chunking (better deduplication). The chunk size and chunking method are
-//! configured when you create a repository. See [`Chunking`] for details.
+//! configured when you create a repository, and the existing data in a repository can be rewritten
+//! with a different chunking method using [`KeyRepo::rechunk`]. See [`Chunking`] for details.
 //!
 //! # Locking
 //! A repository cannot be open more than once simultaneously. Once a repository is opened, it is
@@ -84,7 +85,10 @@
 //! Instances of the same repository can be different repository types. This feature allows for
 //! having multiple repositories of different types which are backed by the same [`DataStore`]. For
 //! example, you could have a data store which contains both a [`FileRepo`] and a [`KeyRepo`] by
-//! giving them different instance IDs, and data will still be deduplicated between them.
+//! giving them different instance IDs, and data will still be deduplicated between them. Every
+//! repository type stores the contents of files and objects using the same chunking, compression,
+//! and encryption as a [`KeyRepo`], and stores metadata separately from that content, so identical
+//! content is deduplicated no matter which repository type it was written with.
 //!
 //! This feature can also be used to manage memory usage. The amount of memory used by a repository
 //! while it's open is typically proportional to the number of objects in the repository. If you
@@ -101,6 +105,7 @@
 //! [`KeyRepo`]: crate::repo::key::KeyRepo
 //! [`OpenOptions`]: crate::repo::OpenOptions
 //! [`Chunking`]: crate::repo::Chunking
+//! [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
 //! [`Unlock`]: crate::repo::Unlock
 //! [`OpenOptions::locking`]: crate::repo::OpenOptions::locking
 //! [`Commit::commit`]: crate::repo::Commit::commit
@@ -114,12 +119,20 @@
 //! [`FileRepo`]: crate::repo::file::FileRepo
 
 pub use self::common::{
-    peek_info, Chunking, Commit, Compression, ContentId, Encryption, InstanceId, Object, ObjectId,
-    ObjectStats, OpenMode, OpenOptions, OpenRepo, Packing, ReadOnlyObject, RepoConfig, RepoId,
-    RepoInfo, RepoStats, ResourceLimit, Restore, RestoreSavepoint, Savepoint, SwitchInstance,
-    Unlock, VersionId, DEFAULT_INSTANCE,
+    audit_encryption, peek_commit_id, peek_info, AuditEntry, AuditOperation, BufferPool,
+    CacheStats, ChunkCache, Chunking, Commit, CommitId, CommitInfo, CommitReport, Compression,
+    ContentDigest, ContentId, DestructivePolicy, DestructiveScope, Encryption, EncryptionAudit,
+    FormatVersion, InstanceId, Manifest, ManifestDiff, ManifestEntry, ManifestSource,
+    MetadataHandle, MigrateProgress, Object, ObjectId, ObjectOptions, ObjectStats, OpenMode,
+    OpenOptions, OpenRepo, Packing, PoolStats, ReadOnlyObject, RechunkOptions, RechunkReport,
+    RepoConfig, RepoId, RepoInfo, RepoStats, ResourceLimit, Restore, RestoreSavepoint, Savepoint,
+    SuspectBlock, SuspectReason, SwitchInstance, Unlock, VersionId, DEFAULT_INSTANCE,
 };
 
+#[cfg(feature = "export")]
+#[cfg_attr(docsrs, doc(cfg(feature = "export")))]
+pub use self::common::{ExportOptions, ExportProgress};
+
 /// An object store which maps keys to seekable binary blobs.
 ///
 /// This module contains the [`KeyRepo`] repository type.
@@ -127,26 +140,68 @@ pub use self::common::{
 /// A [`KeyRepo`] maps keys to seekable binary blobs called objects and stores them persistently in
 /// a [`DataStore`]. A key is any type which implements [`Key`].
 ///
+/// If the key type of a repository changes, you can still access its objects by opening it as a
+/// `KeyRepo<RawKey>`. See [`RawKey`] for details.
+///
 /// Like other repositories, changes made to the repository are not persisted to the data store
 /// until [`Commit::commit`] is called. For details about deduplication, compression, encryption,
 /// and locking, see the module-level documentation for [`crate::repo`].
 ///
+/// # Uncommitted changes
+/// Dropping a [`KeyRepo`], calling [`Commit::rollback`], or calling [`Commit::clean`] never
+/// persists uncommitted changes. If a repository is dropped without committing, reopening it
+/// returns the exact state of the last commit: the same objects with the same contents, the same
+/// [`RepoStats`] and [`RepoInfo`], and a repository which passes [`KeyRepo::verify`]. This applies
+/// to every kind of change, including changes to the repository metadata like
+/// [`KeyRepo::change_password`] and [`KeyRepo::set_destructive_policy`]; the old password keeps
+/// working until the new one is committed.
+///
+/// Data written to objects is written to the data store before it is committed, so uncommitted
+/// changes may leave unreferenced data blocks behind. This data is never read, and it is
+/// reclaimed by the next call to [`Commit::clean`] after a commit.
+///
 /// [`KeyRepo`]: crate::repo::key::KeyRepo
 /// [`DataStore`]: crate::store::DataStore
 /// [`Key`]: crate::repo::key::Key
+/// [`RawKey`]: crate::repo::key::RawKey
 /// [`Commit::commit`]: crate::repo::Commit::commit
+/// [`Commit::rollback`]: crate::repo::Commit::rollback
+/// [`Commit::clean`]: crate::repo::Commit::clean
+/// [`RepoStats`]: crate::repo::RepoStats
+/// [`RepoInfo`]: crate::repo::RepoInfo
+/// [`KeyRepo::verify`]: crate::repo::key::KeyRepo::verify
+/// [`KeyRepo::change_password`]: crate::repo::key::KeyRepo::change_password
+/// [`KeyRepo::set_destructive_policy`]: crate::repo::key::KeyRepo::set_destructive_policy
 pub mod key {
-    pub use super::common::{Key, KeyRepo, Keys};
+    pub use super::common::{
+        Batch, Entry, Key, KeyRepo, Keys, OccupiedEntry, RawKey, SnapshotId, VacantEntry,
+    };
 }
 
 mod common;
 
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub use self::common::fuzzing;
+
+#[cfg(feature = "repo-content")]
+#[cfg_attr(docsrs, doc(cfg(feature = "repo-content")))]
+pub mod content;
+
 #[cfg(feature = "repo-file")]
 #[cfg_attr(docsrs, doc(cfg(feature = "repo-file")))]
 pub mod file;
 
+#[cfg(feature = "repo-fork")]
+#[cfg_attr(docsrs, doc(cfg(feature = "repo-fork")))]
+pub mod fork;
+
 pub mod state;
 
 #[cfg(feature = "repo-value")]
 #[cfg_attr(docsrs, doc(cfg(feature = "repo-value")))]
 pub mod value;
+
+#[cfg(feature = "repo-version")]
+#[cfg_attr(docsrs, doc(cfg(feature = "repo-version")))]
+pub mod version;
@@ -2,6 +2,7 @@ use std::borrow::Borrow;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::time::Duration;
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -11,9 +12,12 @@ use super::iter::Keys;
 use crate::repo::{
     key::{Key, KeyRepo},
     state::{ObjectKey, StateRepo},
-    Commit, InstanceId, OpenRepo, RepoInfo, RepoStats, ResourceLimit, RestoreSavepoint, Savepoint,
-    Unlock, VersionId,
+    AuditEntry, Chunking, Commit, CommitId, CommitInfo, CommitReport, DestructivePolicy,
+    DestructiveScope, InstanceId, MigrateProgress, OpenRepo, PoolStats, RechunkOptions,
+    RechunkReport, RepoInfo, RepoStats, ResourceLimit, RestoreSavepoint, Savepoint, Unlock,
+    VersionId,
 };
+use crate::store::DataStore;
 
 type RepoState<K> = HashMap<K, ObjectKey>;
 
@@ -193,6 +197,68 @@ impl<K: Key> ValueRepo<K> {
             .change_password(new_password, memory_limit, operations_limit);
     }
 
+    /// Return the policy for authorizing destructive operations in this repository.
+    pub fn destructive_policy(&self) -> DestructivePolicy {
+        self.0.destructive_policy()
+    }
+
+    /// Change the policy for authorizing destructive operations in this repository.
+    ///
+    /// See [`KeyRepo::set_destructive_policy`] for details.
+    ///
+    /// [`KeyRepo::set_destructive_policy`]: crate::repo::key::KeyRepo::set_destructive_policy
+    pub fn set_destructive_policy(
+        &mut self,
+        password: &[u8],
+        policy: DestructivePolicy,
+    ) -> crate::Result<()> {
+        self.0.set_destructive_policy(password, policy)
+    }
+
+    /// Return the audit log of security-relevant operations performed on this repository.
+    ///
+    /// See [`KeyRepo::audit_log`] for details.
+    ///
+    /// [`KeyRepo::audit_log`]: crate::repo::key::KeyRepo::audit_log
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.0.audit_log()
+    }
+
+    /// Rewrite every object in the repository using the given `chunking` method.
+    ///
+    /// See [`KeyRepo::rechunk`] for details.
+    ///
+    /// [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
+    pub fn rechunk(
+        &mut self,
+        chunking: Chunking,
+        options: &RechunkOptions,
+    ) -> crate::Result<RechunkReport> {
+        self.0.rechunk(chunking, options)
+    }
+
+    /// Move this repository to `new_store`.
+    ///
+    /// See [`KeyRepo::migrate_store`] for details.
+    ///
+    /// [`KeyRepo::migrate_store`]: crate::repo::key::KeyRepo::migrate_store
+    pub fn migrate_store(
+        &mut self,
+        new_store: impl DataStore + 'static,
+        progress: impl FnMut(MigrateProgress) -> bool,
+    ) -> crate::Result<Box<dyn DataStore>> {
+        self.0.migrate_store(new_store, progress)
+    }
+
+    /// Authorize destructive operations in the given `scope` for the next `ttl`.
+    ///
+    /// See [`KeyRepo::authorize_destructive`] for details.
+    ///
+    /// [`KeyRepo::authorize_destructive`]: crate::repo::key::KeyRepo::authorize_destructive
+    pub fn authorize_destructive(&mut self, scope: DestructiveScope, ttl: Duration) {
+        self.0.authorize_destructive(scope, ttl)
+    }
+
     /// Return this repository's instance ID.
     pub fn instance(&self) -> InstanceId {
         self.0.instance()
@@ -207,6 +273,80 @@ impl<K: Key> ValueRepo<K> {
         self.0.stats()
     }
 
+    /// Return statistics about the pool of buffers used to encode and decode chunks.
+    ///
+    /// See [`KeyRepo::pool_stats`] for details.
+    ///
+    /// [`KeyRepo::pool_stats`]: crate::repo::key::KeyRepo::pool_stats
+    pub fn pool_stats(&self) -> PoolStats {
+        self.0.pool_stats()
+    }
+
+    /// Commit changes which have been made to the repository along with an application `payload`.
+    ///
+    /// See [`KeyRepo::commit_with_payload`] for details.
+    ///
+    /// [`KeyRepo::commit_with_payload`]: crate::repo::key::KeyRepo::commit_with_payload
+    pub fn commit_with_payload(&mut self, payload: &[u8]) -> crate::Result<CommitReport> {
+        self.0.commit_with_payload(payload)
+    }
+
+    /// Return the application payload which was stored by the latest commit.
+    ///
+    /// See [`KeyRepo::committed_payload`] for details.
+    ///
+    /// [`KeyRepo::committed_payload`]: crate::repo::key::KeyRepo::committed_payload
+    pub fn committed_payload(&self) -> Vec<u8> {
+        self.0.committed_payload()
+    }
+
+    /// Return the ID of the latest commit of this repository.
+    ///
+    /// See [`KeyRepo::current_commit_id`] for details.
+    ///
+    /// [`KeyRepo::current_commit_id`]: crate::repo::key::KeyRepo::current_commit_id
+    pub fn current_commit_id(&self) -> CommitId {
+        self.0.current_commit_id()
+    }
+
+    /// Return the commits in this repository's commit history, from oldest to newest.
+    ///
+    /// See [`KeyRepo::list_commits`] for details.
+    ///
+    /// [`KeyRepo::list_commits`]: crate::repo::key::KeyRepo::list_commits
+    pub fn list_commits(&self) -> Vec<CommitInfo> {
+        self.0.list_commits()
+    }
+
+    /// Restore the repository to the state it was in after the commit with the given `commit_id`.
+    ///
+    /// See [`KeyRepo::rollback_to_commit`] for details.
+    ///
+    /// [`KeyRepo::rollback_to_commit`]: crate::repo::key::KeyRepo::rollback_to_commit
+    pub fn rollback_to_commit(&mut self, commit_id: CommitId) -> crate::Result<()> {
+        self.0.rollback_to_commit(commit_id)
+    }
+
+    /// Change the number of previous commits to keep in the commit history.
+    ///
+    /// See [`KeyRepo::set_commit_history`] for details.
+    ///
+    /// [`KeyRepo::set_commit_history`]: crate::repo::key::KeyRepo::set_commit_history
+    pub fn set_commit_history(&mut self, commits: u32) {
+        self.0.set_commit_history(commits)
+    }
+
+    /// Train a Zstandard dictionary from the chunks in this repository.
+    ///
+    /// See [`KeyRepo::train_dictionary`] for details.
+    ///
+    /// [`KeyRepo::train_dictionary`]: crate::repo::key::KeyRepo::train_dictionary
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub fn train_dictionary(&mut self, size: usize) -> crate::Result<()> {
+        self.0.train_dictionary(size)
+    }
+
     /// Return information about the repository.
     pub fn info(&self) -> RepoInfo {
         self.0.info()
@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::repo::{state::ObjectKey, ContentId};
+
+/// Information about a version of an object in a [`VersionRepo`].
+///
+/// [`VersionRepo`]: crate::repo::version::VersionRepo
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Version {
+    /// The ID of this version.
+    ///
+    /// Version IDs are unique per key and increase with each new version. The IDs of removed
+    /// versions are never reused.
+    pub id: u32,
+
+    /// The time this version was created.
+    pub created: SystemTime,
+
+    /// The size of the contents of this version in bytes.
+    pub size: u64,
+
+    /// The `ContentId` of the contents of this version.
+    pub content_id: ContentId,
+}
+
+/// A version of an object and the object which stores its contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub version: Version,
+    pub object: ObjectKey,
+}
+
+/// The current object and the versions associated with a key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyInfo {
+    /// The object which stores the current contents.
+    pub object: ObjectKey,
+
+    /// The versions of the object by their ID.
+    pub versions: BTreeMap<u32, VersionInfo>,
+
+    /// The ID to give the next version which is created.
+    pub next_id: u32,
+}
+
+impl KeyInfo {
+    /// Return a new `KeyInfo` for the given current `object` with no versions.
+    pub fn new(object: ObjectKey) -> Self {
+        KeyInfo {
+            object,
+            versions: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+}
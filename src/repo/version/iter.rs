@@ -0,0 +1,62 @@
+use std::collections::{btree_map, hash_map};
+use std::iter::{DoubleEndedIterator, ExactSizeIterator, FusedIterator};
+
+use super::info::{KeyInfo, Version, VersionInfo};
+
+/// An iterator over the keys in a [`VersionRepo`].
+///
+/// This value is created by [`VersionRepo::keys`].
+///
+/// [`VersionRepo`]: crate::repo::version::VersionRepo
+/// [`VersionRepo::keys`]: crate::repo::version::VersionRepo::keys
+#[derive(Debug, Clone)]
+pub struct Keys<'a, K>(pub(super) hash_map::Keys<'a, K, KeyInfo>);
+
+impl<'a, K> Iterator for Keys<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K> FusedIterator for Keys<'a, K> {}
+
+impl<'a, K> ExactSizeIterator for Keys<'a, K> {}
+
+/// An iterator over the versions of an object in a [`VersionRepo`].
+///
+/// Versions are returned from oldest to newest.
+///
+/// This value is created by [`VersionRepo::versions`].
+///
+/// [`VersionRepo`]: crate::repo::version::VersionRepo
+/// [`VersionRepo::versions`]: crate::repo::version::VersionRepo::versions
+#[derive(Debug, Clone)]
+pub struct Versions<'a>(pub(super) btree_map::Values<'a, u32, VersionInfo>);
+
+impl<'a> Iterator for Versions<'a> {
+    type Item = &'a Version;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|info| &info.version)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Versions<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|info| &info.version)
+    }
+}
+
+impl<'a> FusedIterator for Versions<'a> {}
+
+impl<'a> ExactSizeIterator for Versions<'a> {}
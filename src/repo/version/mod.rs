@@ -0,0 +1,26 @@
+//! A repository which stores a history of versions of each object.
+//!
+//! This module contains the [`VersionRepo`] repository type.
+//!
+//! This is a repository like [`KeyRepo`] which also stores a history of versions of each object.
+//! Each key maps to a current object which can be read from and written to, and a list of
+//! immutable versions of that object. Creating a version records the current contents of the
+//! object, and old versions remain readable until they are explicitly removed. Versions are cheap
+//! to create, because the data they share with the current object and with each other is
+//! deduplicated.
+//!
+//! Like other repositories, changes made to the repository are not persisted to the data store
+//! until [`Commit::commit`] is called. For details about deduplication, compression, encryption,
+//! and locking, see the module-level documentation for [`crate::repo`].
+//!
+//! [`VersionRepo`]: crate::repo::version::VersionRepo
+//! [`KeyRepo`]: crate::repo::key::KeyRepo
+//! [`Commit::commit`]: crate::repo::Commit::commit
+
+pub use self::info::Version;
+pub use self::iter::{Keys, Versions};
+pub use self::repository::VersionRepo;
+
+mod info;
+mod iter;
+mod repository;
@@ -0,0 +1,565 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::time::{Duration, SystemTime};
+
+use uuid::uuid;
+
+use super::info::{KeyInfo, Version, VersionInfo};
+use super::iter::{Keys, Versions};
+use crate::repo::{
+    key::{Key, KeyRepo},
+    state::StateRepo,
+    AuditEntry, Chunking, Commit, CommitId, CommitInfo, CommitReport, DestructivePolicy,
+    DestructiveScope, InstanceId, MigrateProgress, Object, OpenRepo, PoolStats, ReadOnlyObject,
+    RechunkOptions, RechunkReport, RepoInfo, RepoStats, ResourceLimit, RestoreSavepoint, Savepoint,
+    Unlock, VersionId,
+};
+use crate::store::DataStore;
+
+type RepoState<K> = HashMap<K, KeyInfo>;
+
+/// A repository which stores a history of versions of each object.
+///
+/// See [`crate::repo::version`] for more information.
+#[derive(Debug)]
+pub struct VersionRepo<K: Key>(StateRepo<RepoState<K>>);
+
+impl<K: Key> OpenRepo for VersionRepo<K> {
+    type Key = <StateRepo<RepoState<K>> as OpenRepo>::Key;
+
+    const VERSION_ID: VersionId = VersionId::new(uuid!("ce0f69bc-c967-11f1-8429-6b1d9e3a7c52"));
+
+    fn open_repo(repo: KeyRepo<Self::Key>) -> crate::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(StateRepo::open_repo(repo)?))
+    }
+
+    fn create_repo(repo: KeyRepo<Self::Key>) -> crate::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(StateRepo::create_repo(repo)?))
+    }
+
+    fn into_repo(self) -> crate::Result<KeyRepo<Self::Key>> {
+        self.0.into_repo()
+    }
+}
+
+impl<K: Key> VersionRepo<K> {
+    /// Return whether there is an object with the given `key` in this repository.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.0.state().contains_key(key)
+    }
+
+    /// Add a new object with the given `key` to the repository and return it.
+    ///
+    /// If another object with the same `key` already exists, the current object is replaced, but
+    /// its versions are kept.
+    pub fn insert(&mut self, key: K) -> Object {
+        let object_id = self.0.create();
+        match self.0.state_mut().get_mut(&key) {
+            Some(info) => {
+                let prev_object_id = std::mem::replace(&mut info.object, object_id);
+                self.0.remove(prev_object_id);
+            }
+            None => {
+                self.0.state_mut().insert(key, KeyInfo::new(object_id));
+            }
+        }
+        self.0.object(object_id).unwrap()
+    }
+
+    /// Remove the object with the given `key` and all of its versions from the repository.
+    ///
+    /// This returns `true` if the object was removed or `false` if it didn't exist.
+    ///
+    /// The space used by the given object isn't reclaimed in the backing data store until changes
+    /// are committed and [`Commit::clean`] is called.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.0.state_mut().remove(key) {
+            Some(info) => {
+                self.0.remove(info.object);
+                for version_info in info.versions.into_values() {
+                    self.0.remove(version_info.object);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return the current object with the given `key`, or `None` if it doesn't exist.
+    pub fn object<Q>(&self, key: &Q) -> Option<Object>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let info = self.0.state().get(key)?;
+        self.0.object(info.object)
+    }
+
+    /// Return an iterator of all the keys in this repository.
+    pub fn keys(&self) -> Keys<K> {
+        Keys(self.0.state().keys())
+    }
+
+    /// Create a new version of the object with the given `key` and return it.
+    ///
+    /// The new version has the current contents of the object. Changes to the object which
+    /// haven't been committed with [`Object::commit`] are not included in the version.
+    ///
+    /// This is a cheap operation which does not require copying the object itself.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key`.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`Object::commit`]: crate::repo::Object::commit
+    pub fn create_version<Q>(&mut self, key: &Q) -> crate::Result<Version>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let object_id = self
+            .0
+            .state()
+            .get(key)
+            .ok_or(crate::Error::NotFound)?
+            .object;
+        let version_object_id = self.0.copy(object_id).unwrap();
+
+        let version_object = self.0.object(version_object_id).unwrap();
+        let contents = version_object
+            .size()
+            .and_then(|size| Ok((size, version_object.content_id()?)));
+        drop(version_object);
+        let (size, content_id) = match contents {
+            Ok(contents) => contents,
+            Err(error) => {
+                self.0.remove(version_object_id);
+                return Err(error);
+            }
+        };
+
+        let info = self.0.state_mut().get_mut(key).unwrap();
+        let version = Version {
+            id: info.next_id,
+            created: SystemTime::now(),
+            size,
+            content_id,
+        };
+        info.next_id += 1;
+        info.versions.insert(
+            version.id,
+            VersionInfo {
+                version: version.clone(),
+                object: version_object_id,
+            },
+        );
+
+        Ok(version)
+    }
+
+    /// Remove the version of the object with the given `key` with the given `id`.
+    ///
+    /// This returns `true` if the version was removed or `false` if it didn't exist.
+    ///
+    /// The space used by the given version isn't reclaimed in the backing data store until changes
+    /// are committed and [`Commit::clean`] is called.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn remove_version<Q>(&mut self, key: &Q, id: u32) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let version_info = match self.0.state_mut().get_mut(key) {
+            Some(info) => match info.versions.remove(&id) {
+                Some(version_info) => version_info,
+                None => return false,
+            },
+            None => return false,
+        };
+        self.0.remove(version_info.object);
+        true
+    }
+
+    /// Remove all but the newest `keep` versions of the object with the given `key`.
+    ///
+    /// This returns the number of versions which were removed.
+    ///
+    /// The space used by the removed versions isn't reclaimed in the backing data store until
+    /// changes are committed and [`Commit::clean`] is called.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no object with the given `key`.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn prune_versions<Q>(&mut self, key: &Q, keep: usize) -> crate::Result<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let info = self
+            .0
+            .state_mut()
+            .get_mut(key)
+            .ok_or(crate::Error::NotFound)?;
+        let num_removed = info.versions.len().saturating_sub(keep);
+        let removed_ids = info
+            .versions
+            .keys()
+            .take(num_removed)
+            .copied()
+            .collect::<Vec<_>>();
+        let removed_objects = removed_ids
+            .iter()
+            .map(|id| info.versions.remove(id).unwrap().object)
+            .collect::<Vec<_>>();
+        for object_id in removed_objects {
+            self.0.remove(object_id);
+        }
+        Ok(num_removed)
+    }
+
+    /// Return the version of the object with the given `key` with the given `id`.
+    ///
+    /// This returns `None` if the version doesn't exist.
+    pub fn get_version<Q>(&self, key: &Q, id: u32) -> Option<&Version>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let info = self.0.state().get(key)?;
+        info.versions
+            .get(&id)
+            .map(|version_info| &version_info.version)
+    }
+
+    /// Return an iterator of the versions of the object with the given `key`.
+    ///
+    /// Versions are returned from oldest to newest. This returns `None` if there is no object with
+    /// the given `key`.
+    pub fn versions<Q>(&self, key: &Q) -> Option<Versions>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let info = self.0.state().get(key)?;
+        Some(Versions(info.versions.values()))
+    }
+
+    /// Return an object for reading the contents of a version.
+    ///
+    /// This returns the contents of the version of the object with the given `key` with the given
+    /// `id`, or `None` if the version doesn't exist.
+    pub fn version_object<Q>(&self, key: &Q, id: u32) -> Option<ReadOnlyObject>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let info = self.0.state().get(key)?;
+        let version_info = info.versions.get(&id)?;
+        // A new `Object` never has a transaction in progress, so this conversion can't fail.
+        ReadOnlyObject::try_from(self.0.object(version_info.object)?).ok()
+    }
+
+    /// Replace the current contents of the object with the given `key` with a version.
+    ///
+    /// This replaces the current object with the contents of the version with the given `id`.
+    /// The version itself is not removed. Any changes to the current object which haven't been
+    /// saved as a version are lost.
+    ///
+    /// This is a cheap operation which does not require copying the object itself.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no version of the object with the given `key` with the given
+    /// `id`.
+    pub fn restore_version<Q>(&mut self, key: &Q, id: u32) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let version_object_id = self
+            .0
+            .state()
+            .get(key)
+            .and_then(|info| info.versions.get(&id))
+            .ok_or(crate::Error::NotFound)?
+            .object;
+        let object_id = self.0.copy(version_object_id).unwrap();
+        let info = self.0.state_mut().get_mut(key).unwrap();
+        let prev_object_id = std::mem::replace(&mut info.object, object_id);
+        self.0.remove(prev_object_id);
+        Ok(())
+    }
+
+    /// Verify the integrity of all the data in the repository.
+    ///
+    /// This returns the set of keys of objects which are corrupt. A key is included if either its
+    /// current object or any of its versions are corrupt.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn verify(&self) -> crate::Result<HashSet<&K>> {
+        let corrupt_keys = self.0.verify()?;
+        Ok(self
+            .0
+            .state()
+            .iter()
+            .filter(|(_, info)| {
+                corrupt_keys.contains(&info.object)
+                    || info
+                        .versions
+                        .values()
+                        .any(|version_info| corrupt_keys.contains(&version_info.object))
+            })
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Delete all data in the current instance of the repository.
+    ///
+    /// See [`KeyRepo::clear_instance`] for details.
+    ///
+    /// [`KeyRepo::clear_instance`]: crate::repo::key::KeyRepo::clear_instance
+    pub fn clear_instance(&mut self) {
+        self.0.clear_instance()
+    }
+
+    /// Change the password for this repository.
+    ///
+    /// See [`KeyRepo::change_password`] for details.
+    ///
+    /// [`KeyRepo::change_password`]: crate::repo::key::KeyRepo::change_password
+    pub fn change_password(
+        &mut self,
+        new_password: &[u8],
+        memory_limit: ResourceLimit,
+        operations_limit: ResourceLimit,
+    ) {
+        self.0
+            .change_password(new_password, memory_limit, operations_limit);
+    }
+
+    /// Return the policy for authorizing destructive operations in this repository.
+    pub fn destructive_policy(&self) -> DestructivePolicy {
+        self.0.destructive_policy()
+    }
+
+    /// Change the policy for authorizing destructive operations in this repository.
+    ///
+    /// See [`KeyRepo::set_destructive_policy`] for details.
+    ///
+    /// [`KeyRepo::set_destructive_policy`]: crate::repo::key::KeyRepo::set_destructive_policy
+    pub fn set_destructive_policy(
+        &mut self,
+        password: &[u8],
+        policy: DestructivePolicy,
+    ) -> crate::Result<()> {
+        self.0.set_destructive_policy(password, policy)
+    }
+
+    /// Return the audit log of security-relevant operations performed on this repository.
+    ///
+    /// See [`KeyRepo::audit_log`] for details.
+    ///
+    /// [`KeyRepo::audit_log`]: crate::repo::key::KeyRepo::audit_log
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.0.audit_log()
+    }
+
+    /// Rewrite every object in the repository using the given `chunking` method.
+    ///
+    /// See [`KeyRepo::rechunk`] for details.
+    ///
+    /// [`KeyRepo::rechunk`]: crate::repo::key::KeyRepo::rechunk
+    pub fn rechunk(
+        &mut self,
+        chunking: Chunking,
+        options: &RechunkOptions,
+    ) -> crate::Result<RechunkReport> {
+        self.0.rechunk(chunking, options)
+    }
+
+    /// Move this repository to `new_store`.
+    ///
+    /// See [`KeyRepo::migrate_store`] for details.
+    ///
+    /// [`KeyRepo::migrate_store`]: crate::repo::key::KeyRepo::migrate_store
+    pub fn migrate_store(
+        &mut self,
+        new_store: impl DataStore + 'static,
+        progress: impl FnMut(MigrateProgress) -> bool,
+    ) -> crate::Result<Box<dyn DataStore>> {
+        self.0.migrate_store(new_store, progress)
+    }
+
+    /// Authorize destructive operations in the given `scope` for the next `ttl`.
+    ///
+    /// See [`KeyRepo::authorize_destructive`] for details.
+    ///
+    /// [`KeyRepo::authorize_destructive`]: crate::repo::key::KeyRepo::authorize_destructive
+    pub fn authorize_destructive(&mut self, scope: DestructiveScope, ttl: Duration) {
+        self.0.authorize_destructive(scope, ttl)
+    }
+
+    /// Return this repository's instance ID.
+    pub fn instance(&self) -> InstanceId {
+        self.0.instance()
+    }
+
+    /// Compute statistics about the repository.
+    ///
+    /// See [`KeyRepo::stats`] for details.
+    ///
+    /// [`KeyRepo::stats`]: crate::repo::key::KeyRepo::stats
+    pub fn stats(&self) -> RepoStats {
+        self.0.stats()
+    }
+
+    /// Return statistics about the pool of buffers used to encode and decode chunks.
+    ///
+    /// See [`KeyRepo::pool_stats`] for details.
+    ///
+    /// [`KeyRepo::pool_stats`]: crate::repo::key::KeyRepo::pool_stats
+    pub fn pool_stats(&self) -> PoolStats {
+        self.0.pool_stats()
+    }
+
+    /// Commit changes which have been made to the repository along with an application `payload`.
+    ///
+    /// See [`KeyRepo::commit_with_payload`] for details.
+    ///
+    /// [`KeyRepo::commit_with_payload`]: crate::repo::key::KeyRepo::commit_with_payload
+    pub fn commit_with_payload(&mut self, payload: &[u8]) -> crate::Result<CommitReport> {
+        self.0.commit_with_payload(payload)
+    }
+
+    /// Return the application payload which was stored by the latest commit.
+    ///
+    /// See [`KeyRepo::committed_payload`] for details.
+    ///
+    /// [`KeyRepo::committed_payload`]: crate::repo::key::KeyRepo::committed_payload
+    pub fn committed_payload(&self) -> Vec<u8> {
+        self.0.committed_payload()
+    }
+
+    /// Return the ID of the latest commit of this repository.
+    ///
+    /// See [`KeyRepo::current_commit_id`] for details.
+    ///
+    /// [`KeyRepo::current_commit_id`]: crate::repo::key::KeyRepo::current_commit_id
+    pub fn current_commit_id(&self) -> CommitId {
+        self.0.current_commit_id()
+    }
+
+    /// Return the commits in this repository's commit history, from oldest to newest.
+    ///
+    /// See [`KeyRepo::list_commits`] for details.
+    ///
+    /// [`KeyRepo::list_commits`]: crate::repo::key::KeyRepo::list_commits
+    pub fn list_commits(&self) -> Vec<CommitInfo> {
+        self.0.list_commits()
+    }
+
+    /// Restore the repository to the state it was in after the commit with the given `commit_id`.
+    ///
+    /// See [`KeyRepo::rollback_to_commit`] for details.
+    ///
+    /// [`KeyRepo::rollback_to_commit`]: crate::repo::key::KeyRepo::rollback_to_commit
+    pub fn rollback_to_commit(&mut self, commit_id: CommitId) -> crate::Result<()> {
+        self.0.rollback_to_commit(commit_id)
+    }
+
+    /// Change the number of previous commits to keep in the commit history.
+    ///
+    /// See [`KeyRepo::set_commit_history`] for details.
+    ///
+    /// [`KeyRepo::set_commit_history`]: crate::repo::key::KeyRepo::set_commit_history
+    pub fn set_commit_history(&mut self, commits: u32) {
+        self.0.set_commit_history(commits)
+    }
+
+    /// Train a Zstandard dictionary from the chunks in this repository.
+    ///
+    /// See [`KeyRepo::train_dictionary`] for details.
+    ///
+    /// [`KeyRepo::train_dictionary`]: crate::repo::key::KeyRepo::train_dictionary
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub fn train_dictionary(&mut self, size: usize) -> crate::Result<()> {
+        self.0.train_dictionary(size)
+    }
+
+    /// Return information about the repository.
+    pub fn info(&self) -> RepoInfo {
+        self.0.info()
+    }
+}
+
+impl<K: Key> Commit for VersionRepo<K> {
+    fn commit(&mut self) -> crate::Result<()> {
+        self.0.commit()
+    }
+
+    fn rollback(&mut self) -> crate::Result<()> {
+        self.0.rollback()
+    }
+
+    fn clean(&mut self) -> crate::Result<()> {
+        self.0.clean()
+    }
+}
+
+impl<K: Key> RestoreSavepoint for VersionRepo<K> {
+    type Restore = <StateRepo<RepoState<K>> as RestoreSavepoint>::Restore;
+
+    fn savepoint(&mut self) -> crate::Result<Savepoint> {
+        self.0.savepoint()
+    }
+
+    fn start_restore(&mut self, savepoint: &Savepoint) -> crate::Result<Self::Restore> {
+        self.0.start_restore(savepoint)
+    }
+
+    fn finish_restore(&mut self, restore: Self::Restore) -> bool {
+        self.0.finish_restore(restore)
+    }
+}
+
+impl<K: Key> Unlock for VersionRepo<K> {
+    fn unlock(&self) -> crate::Result<()> {
+        self.0.unlock()
+    }
+
+    fn is_locked(&self) -> crate::Result<bool> {
+        self.0.is_locked()
+    }
+
+    fn context(&self) -> crate::Result<Vec<u8>> {
+        self.0.context()
+    }
+
+    fn update_context(&self, context: &[u8]) -> crate::Result<()> {
+        self.0.update_context(context)
+    }
+}
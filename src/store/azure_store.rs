@@ -0,0 +1,496 @@
+#![cfg(feature = "store-azure")]
+
+use std::env;
+use std::io::Read;
+use std::time::SystemTime;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use ureq::{Agent, AgentBuilder, Response};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+use super::rest::{check_response, join_key, percent_encode, SEPARATOR};
+
+// The names of blobs in the data store.
+const STORE_KEY: &str = "store";
+const DATA_KEY: &str = "data";
+const LOCKS_KEY: &str = "lock";
+const HEADERS_KEY: &str = "header";
+const SUPER_KEY: &str = "super";
+const REPO_VERSION_KEY: &str = "version";
+const STORE_VERSION_KEY: &str = "version";
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("3c6f0b52-8d0e-4b8e-a3a7-5e2f1c9d7b41");
+
+/// The version of the Azure Blob Storage REST API this store uses.
+const API_VERSION: &str = "2021-08-06";
+
+/// The content type of blobs written by this store.
+const CONTENT_TYPE: &str = "application/octet-stream";
+
+/// The environment variable for the storage account access key.
+const ACCOUNT_KEY_ENV: &str = "AZURE_STORAGE_KEY";
+
+/// The environment variable for the shared access signature token.
+const SAS_TOKEN_ENV: &str = "AZURE_STORAGE_SAS_TOKEN";
+
+/// Return the text of each element named `tag` in the XML `document`.
+///
+/// The responses to the List Blobs operation are simple enough that they don't need a full XML
+/// parser.
+fn xml_elements(document: &str, tag: &str) -> Vec<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut elements = Vec::new();
+    let mut rest = document;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let end = match rest.find(&close) {
+            Some(end) => end,
+            None => break,
+        };
+        elements.push(
+            rest[..end]
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&"),
+        );
+        rest = &rest[end + close.len()..];
+    }
+    elements
+}
+
+/// The credentials for an Azure Blob Storage connection.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-azure")))]
+pub enum AzureCredentials {
+    /// A storage account access key.
+    AccountKey {
+        /// The base64-encoded access key, as shown in the Azure portal.
+        key: String,
+    },
+
+    /// A shared access signature (SAS) token.
+    ///
+    /// The token must grant read, write, delete, and list permissions on the container.
+    SasToken {
+        /// The query string of the SAS URL, with or without the leading `?`.
+        token: String,
+    },
+}
+
+impl AzureCredentials {
+    /// Get `AzureCredentials` from environment variables.
+    ///
+    /// This checks the following environment variables in order:
+    /// - `AZURE_STORAGE_KEY`
+    /// - `AZURE_STORAGE_SAS_TOKEN`
+    ///
+    /// This returns `None` if neither environment variable is set.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(key) = env::var(ACCOUNT_KEY_ENV) {
+            return Some(AzureCredentials::AccountKey { key });
+        }
+        env::var(SAS_TOKEN_ENV)
+            .ok()
+            .map(|token| AzureCredentials::SasToken { token })
+    }
+}
+
+/// The configuration for opening an [`AzureStore`].
+///
+/// The container must already exist.
+///
+/// [`AzureStore`]: crate::store::AzureStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-azure")))]
+pub struct AzureConfig {
+    /// The name of the storage account.
+    pub account: String,
+
+    /// The name of the blob container.
+    pub container: String,
+
+    /// The credentials to connect with.
+    pub credentials: AzureCredentials,
+
+    /// The prefix to prepend to blob names in the store.
+    ///
+    /// While blob names are a flat namespace, you can think of this like the directory of the
+    /// container to create the store in. To create the store in the container root, use an empty
+    /// string.
+    pub prefix: String,
+
+    /// The URL of the blob service, if it isn't the default for the account.
+    ///
+    /// By default, this is `https://<account>.blob.core.windows.net`. This is useful for
+    /// connecting to a local emulator like Azurite, which uses URLs like
+    /// `http://127.0.0.1:10000/<account>`.
+    pub endpoint: Option<String>,
+}
+
+impl AzureConfig {
+    /// Return a new config for the container in the given `account` with the default endpoint.
+    pub fn new(
+        account: impl Into<String>,
+        container: impl Into<String>,
+        credentials: AzureCredentials,
+    ) -> Self {
+        AzureConfig {
+            account: account.into(),
+            container: container.into(),
+            credentials,
+            prefix: String::new(),
+            endpoint: None,
+        }
+    }
+
+    /// Return the URL of the blob service.
+    fn service_url(&self) -> String {
+        match &self.endpoint {
+            Some(endpoint) => endpoint.trim_end_matches('/').to_owned(),
+            None => format!("https://{}.blob.core.windows.net", self.account),
+        }
+    }
+}
+
+impl OpenStore for AzureConfig {
+    type Store = AzureStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let auth = match &self.credentials {
+            AzureCredentials::AccountKey { key } => Auth::SharedKey(
+                BASE64
+                    .decode(key)
+                    .map_err(|error| crate::Error::Store(super::Error::from(error)))?,
+            ),
+            AzureCredentials::SasToken { token } => {
+                Auth::SasToken(token.trim_start_matches('?').to_owned())
+            }
+        };
+        let store = AzureStore {
+            agent: AgentBuilder::new().build(),
+            container_url: format!(
+                "{}/{}",
+                self.service_url(),
+                percent_encode(&self.container, b"")
+            ),
+            account: self.account.clone(),
+            auth,
+            prefix: self.prefix.trim_matches('/').to_owned(),
+        };
+
+        let version_key = join_key(&[&store.prefix, STORE_VERSION_KEY]);
+        match store.get(&version_key).map_err(crate::Error::Store)? {
+            None => store
+                .put(&version_key, CURRENT_VERSION.as_bytes())
+                .map_err(crate::Error::Store)?,
+            Some(response) => {
+                let mut version = Vec::new();
+                response
+                    .into_reader()
+                    .read_to_end(&mut version)
+                    .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+                let version =
+                    Uuid::from_slice(&version).map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// How requests to the blob service are authorized.
+#[derive(Debug)]
+enum Auth {
+    /// Requests are signed with the decoded account key.
+    SharedKey(Vec<u8>),
+
+    /// The SAS token is appended to the URL of each request.
+    SasToken(String),
+}
+
+/// A `DataStore` which stores data in an Azure Blob Storage container.
+///
+/// You can use [`AzureConfig`] to open a data store of this type.
+///
+/// [`AzureConfig`]: crate::store::AzureConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-azure")))]
+pub struct AzureStore {
+    agent: Agent,
+    container_url: String,
+    account: String,
+    auth: Auth,
+    prefix: String,
+}
+
+impl AzureStore {
+    /// Return the name of the blob for the block with the given `key`.
+    fn block_path(&self, key: BlockKey) -> String {
+        match key {
+            BlockKey::Data(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                DATA_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Lock(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                LOCKS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Header(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                HEADERS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Super => join_key(&[&self.prefix, STORE_KEY, SUPER_KEY]),
+            BlockKey::Version => join_key(&[&self.prefix, STORE_KEY, REPO_VERSION_KEY]),
+        }
+    }
+
+    /// Return the `Authorization` header for a request signed with the account `key`.
+    ///
+    /// See the Azure documentation on authorizing with Shared Key for the format of the string
+    /// which is signed.
+    fn authorization(
+        &self,
+        key: &[u8],
+        method: &str,
+        url: &str,
+        query: &[(&str, &str)],
+        body: Option<&[u8]>,
+        date: &str,
+    ) -> String {
+        let content_length = match body {
+            Some(body) if !body.is_empty() => body.len().to_string(),
+            _ => String::new(),
+        };
+        let content_type = if body.is_some() { CONTENT_TYPE } else { "" };
+        let blob_type_header = if body.is_some() {
+            "x-ms-blob-type:BlockBlob\n"
+        } else {
+            ""
+        };
+
+        // The canonicalized resource uses the path of the URL without the scheme and host.
+        let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let path = without_scheme
+            .find('/')
+            .map_or("/", |start| &without_scheme[start..]);
+
+        let mut string_to_sign = format!(
+            "{}\n\n\n{}\n\n{}\n\n\n\n\n\n\n{}x-ms-date:{}\nx-ms-version:{}\n/{}{}",
+            method,
+            content_length,
+            content_type,
+            blob_type_header,
+            date,
+            API_VERSION,
+            self.account,
+            path
+        );
+        let mut query = query.to_vec();
+        query.sort_unstable();
+        for (name, value) in query {
+            string_to_sign.push_str(&format!("\n{}:{}", name, value));
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(string_to_sign.as_bytes());
+        format!(
+            "SharedKey {}:{}",
+            self.account,
+            BASE64.encode(mac.finalize().into_bytes())
+        )
+    }
+
+    /// Send a request with the given `method` for the given `blob` or the container itself.
+    ///
+    /// If `body` is `Some`, it's uploaded as a block blob. This returns `None` if the blob or
+    /// container does not exist.
+    fn send(
+        &self,
+        method: &str,
+        blob: Option<&str>,
+        query: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> super::Result<Option<Response>> {
+        let path_url = match blob {
+            Some(blob) => format!("{}/{}", self.container_url, percent_encode(blob, b"/")),
+            None => self.container_url.clone(),
+        };
+
+        let mut url = path_url.clone();
+        let mut separator = '?';
+        if let Auth::SasToken(token) = &self.auth {
+            url.push(separator);
+            url.push_str(token);
+            separator = '&';
+        }
+        for (name, value) in query {
+            url.push(separator);
+            url.push_str(name);
+            url.push('=');
+            url.push_str(&percent_encode(value, b""));
+            separator = '&';
+        }
+
+        let date = httpdate::fmt_http_date(SystemTime::now());
+        let mut request = self
+            .agent
+            .request(method, &url)
+            .set("x-ms-date", &date)
+            .set("x-ms-version", API_VERSION);
+        if body.is_some() {
+            request = request
+                .set("x-ms-blob-type", "BlockBlob")
+                .set("Content-Type", CONTENT_TYPE);
+        }
+        if let Auth::SharedKey(key) = &self.auth {
+            let authorization = self.authorization(key, method, &path_url, query, body, &date);
+            request = request.set("Authorization", &authorization);
+        }
+
+        let result = match body {
+            Some(body) => request.send_bytes(body),
+            None => request.call(),
+        };
+
+        check_response("Azure Blob Storage", result)
+    }
+
+    /// Return the response to getting the given `blob` or `None` if it does not exist.
+    fn get(&self, blob: &str) -> super::Result<Option<Response>> {
+        self.send("GET", Some(blob), &[], None)
+    }
+
+    /// Upload `data` as the given `blob`.
+    fn put(&self, blob: &str, data: &[u8]) -> super::Result<()> {
+        self.send("PUT", Some(blob), &[], Some(data))?
+            .ok_or_else(|| super::Error::msg("The container does not exist."))?;
+        Ok(())
+    }
+
+    /// Return the names of all blobs whose names start with `prefix`.
+    fn list(&self, prefix: &str) -> super::Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut marker = String::new();
+
+        loop {
+            let mut query = vec![
+                ("restype", "container"),
+                ("comp", "list"),
+                ("prefix", prefix),
+            ];
+            if !marker.is_empty() {
+                query.push(("marker", marker.as_str()));
+            }
+            let response = self
+                .send("GET", None, &query, None)?
+                .ok_or_else(|| super::Error::msg("The container does not exist."))?;
+            let mut document = String::new();
+            response.into_reader().read_to_string(&mut document)?;
+
+            names.extend(xml_elements(&document, "Name"));
+
+            // The results are paginated, and the last page has an empty marker.
+            match xml_elements(&document, "NextMarker").into_iter().next() {
+                Some(next_marker) if !next_marker.is_empty() => marker = next_marker,
+                _ => break,
+            }
+        }
+
+        Ok(names)
+    }
+}
+
+impl DataStore for AzureStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let block_path = self.block_path(key);
+        self.put(&block_path, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let block_path = self.block_path(key);
+        match self.get(&block_path)? {
+            Some(response) => {
+                let mut data = Vec::new();
+                response.into_reader().read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        let block_path = self.block_path(key);
+        Ok(self
+            .get(&block_path)?
+            .map(|response| Box::new(response.into_reader()) as Box<dyn Read>))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let block_path = self.block_path(key);
+        self.send("DELETE", Some(&block_path), &[], None)?;
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let blocks_key = match kind {
+            BlockType::Data => join_key(&[&self.prefix, STORE_KEY, DATA_KEY]) + SEPARATOR,
+            BlockType::Lock => join_key(&[&self.prefix, STORE_KEY, LOCKS_KEY]) + SEPARATOR,
+            BlockType::Header => join_key(&[&self.prefix, STORE_KEY, HEADERS_KEY]) + SEPARATOR,
+        };
+        let block_ids = self
+            .list(&blocks_key)?
+            .iter()
+            .map(|name| Uuid::parse_str(name.trim_start_matches(&blocks_key)).map(|id| id.into()))
+            .collect::<Result<Vec<BlockId>, _>>()?;
+        Ok(block_ids)
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        let block_path = self.block_path(key);
+        let response = match self.send("HEAD", Some(&block_path), &[], None)? {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+        match response.header("Last-Modified") {
+            Some(last_modified) => Ok(Some(httpdate::parse_http_date(last_modified)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let block_path = self.block_path(key);
+        let response = match self.send("HEAD", Some(&block_path), &[], None)? {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+        Ok(response
+            .header("Content-Length")
+            .and_then(|length| length.parse().ok()))
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            // This is the maximum size of a blob uploaded in a single request.
+            max_block_size: Some(5000 * 1024 * 1024),
+            ..StoreCapabilities::default()
+        }
+    }
+}
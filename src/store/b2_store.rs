@@ -0,0 +1,582 @@
+#![cfg(feature = "store-b2")]
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::Read;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use ureq::{Agent, AgentBuilder, Response};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::open_store::OpenStore;
+use super::rest::{check_response, join_key, percent_encode, SEPARATOR};
+use super::ErrorKind;
+
+// The names of objects in the data store.
+const STORE_KEY: &str = "store";
+const DATA_KEY: &str = "data";
+const LOCKS_KEY: &str = "lock";
+const HEADERS_KEY: &str = "header";
+const SUPER_KEY: &str = "super";
+const REPO_VERSION_KEY: &str = "version";
+const STORE_VERSION_KEY: &str = "version";
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("b7e3c5a1-0d4f-4e92-a6b8-3c1f9d2e7a40");
+
+/// The default URL of the B2 API.
+const DEFAULT_ENDPOINT: &str = "https://api.backblazeb2.com";
+
+/// The HTTP status code for a request with a missing or expired authorization token.
+const UNAUTHORIZED_CODE: u16 = 401;
+
+/// The HTTP status code returned when an upload URL is too busy to accept an upload.
+const SERVICE_UNAVAILABLE_CODE: u16 = 503;
+
+/// The maximum number of file names to request in each page of a listing.
+const MAX_FILE_COUNT: u32 = 1000;
+
+/// The name of the service in error messages.
+const SERVICE_NAME: &str = "Backblaze B2";
+
+/// The response to authorizing an account.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Authorization {
+    account_id: String,
+    authorization_token: String,
+    api_url: String,
+    download_url: String,
+    recommended_part_size: u64,
+    allowed: Allowed,
+}
+
+/// What an application key is allowed to access.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Allowed {
+    bucket_id: Option<String>,
+    bucket_name: Option<String>,
+    name_prefix: Option<String>,
+}
+
+/// The response to listing buckets.
+#[derive(Debug, Deserialize)]
+struct BucketList {
+    buckets: Vec<Bucket>,
+}
+
+/// A bucket in a `BucketList`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Bucket {
+    bucket_id: String,
+    bucket_name: String,
+}
+
+/// A URL to upload a file or a part of a large file to.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadUrl {
+    upload_url: String,
+    authorization_token: String,
+}
+
+/// A file which has been uploaded or a large file which has been started.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileInfo {
+    file_id: String,
+    file_name: String,
+}
+
+/// A page of the response to listing file names or file versions.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileList {
+    files: Vec<FileInfo>,
+    next_file_name: Option<String>,
+    #[serde(default)]
+    next_file_id: Option<String>,
+}
+
+/// The configuration for opening a [`B2Store`].
+///
+/// The bucket must already exist. The application key may be restricted to the bucket and to a
+/// prefix of file names, in which case `prefix` must be within the prefix the key is restricted
+/// to.
+///
+/// [`B2Store`]: crate::store::B2Store
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-b2")))]
+pub struct B2Config {
+    /// The ID of the application key.
+    pub key_id: String,
+
+    /// The application key.
+    pub application_key: String,
+
+    /// The name of the bucket.
+    pub bucket: String,
+
+    /// The prefix to prepend to file names in the store.
+    ///
+    /// While file names are a flat namespace, you can think of this like the directory of the
+    /// bucket to create the store in. To create the store in the bucket root, use an empty string.
+    pub prefix: String,
+
+    /// The URL of the B2 API, if it isn't the default.
+    ///
+    /// By default, this is `https://api.backblazeb2.com`.
+    pub endpoint: Option<String>,
+}
+
+impl B2Config {
+    /// Return a new config for the given `bucket` with the default endpoint.
+    pub fn new(
+        key_id: impl Into<String>,
+        application_key: impl Into<String>,
+        bucket: impl Into<String>,
+    ) -> Self {
+        B2Config {
+            key_id: key_id.into(),
+            application_key: application_key.into(),
+            bucket: bucket.into(),
+            prefix: String::new(),
+            endpoint: None,
+        }
+    }
+
+    /// Authorize the account with the application key.
+    fn authorize(&self, agent: &Agent) -> super::Result<Authorization> {
+        let endpoint = self
+            .endpoint
+            .as_deref()
+            .unwrap_or(DEFAULT_ENDPOINT)
+            .trim_end_matches('/');
+        let credentials = BASE64.encode(format!("{}:{}", self.key_id, self.application_key));
+        let response = check_response(
+            SERVICE_NAME,
+            agent
+                .get(&format!("{}/b2api/v2/b2_authorize_account", endpoint))
+                .set("Authorization", &format!("Basic {}", credentials))
+                .call(),
+        )?
+        .ok_or_else(|| super::Error::msg("The authorization endpoint does not exist."))?;
+        Ok(serde_json::from_reader(response.into_reader())?)
+    }
+}
+
+impl OpenStore for B2Config {
+    type Store = B2Store;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let agent = AgentBuilder::new().build();
+        let authorization = self.authorize(&agent).map_err(crate::Error::Store)?;
+        let prefix = self.prefix.trim_matches('/').to_owned();
+
+        // Application keys can be restricted to a prefix of file names in a bucket. Check that the
+        // store is within it now rather than failing on the first write.
+        if let Some(name_prefix) = &authorization.allowed.name_prefix {
+            if !join_key(&[&prefix, STORE_VERSION_KEY]).starts_with(name_prefix.as_str()) {
+                return Err(crate::Error::Store(
+                    super::Error::msg("The application key does not have access to the prefix.")
+                        .with_kind(ErrorKind::PermissionDenied),
+                ));
+            }
+        }
+
+        let mut store = B2Store {
+            agent,
+            config: self.clone(),
+            authorization,
+            bucket_id: String::new(),
+            upload_url: None,
+            prefix,
+        };
+
+        store.bucket_id = match &store.authorization.allowed.bucket_id {
+            Some(bucket_id) => {
+                if store.authorization.allowed.bucket_name.as_deref() != Some(&self.bucket) {
+                    return Err(crate::Error::Store(
+                        super::Error::msg(
+                            "The application key does not have access to the bucket.",
+                        )
+                        .with_kind(ErrorKind::PermissionDenied),
+                    ));
+                }
+                bucket_id.clone()
+            }
+            None => {
+                let account_id = store.authorization.account_id.clone();
+                let bucket_list: BucketList = store
+                    .api(
+                        "b2_list_buckets",
+                        json!({ "accountId": account_id, "bucketName": self.bucket }),
+                    )
+                    .map_err(crate::Error::Store)?;
+                bucket_list
+                    .buckets
+                    .into_iter()
+                    .find(|bucket| bucket.bucket_name == self.bucket)
+                    .ok_or_else(|| {
+                        crate::Error::Store(
+                            super::Error::msg("The bucket does not exist.")
+                                .with_kind(ErrorKind::NotFound),
+                        )
+                    })?
+                    .bucket_id
+            }
+        };
+
+        let version_key = join_key(&[&store.prefix, STORE_VERSION_KEY]);
+        match store.get(&version_key).map_err(crate::Error::Store)? {
+            None => store
+                .put(&version_key, CURRENT_VERSION.as_bytes())
+                .map_err(crate::Error::Store)?,
+            Some(version) => {
+                let version =
+                    Uuid::from_slice(&version).map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// A `DataStore` which stores data in a Backblaze B2 bucket using the native B2 API.
+///
+/// Blocks which are larger than the part size recommended by B2 are uploaded as large files in
+/// several parts. B2 keeps old versions of files when they are overwritten or hidden, so this
+/// store deletes every other version of a file when it's written or removed.
+///
+/// Authorization tokens expire after a day, so the account is authorized again when a token is
+/// rejected.
+///
+/// You can use [`B2Config`] to open a data store of this type.
+///
+/// [`B2Config`]: crate::store::B2Config
+#[cfg_attr(docsrs, doc(cfg(feature = "store-b2")))]
+pub struct B2Store {
+    agent: Agent,
+    config: B2Config,
+    authorization: Authorization,
+    bucket_id: String,
+    upload_url: Option<UploadUrl>,
+    prefix: String,
+}
+
+impl Debug for B2Store {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("B2Store")
+            .field("bucket", &self.config.bucket)
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Return the hex-encoded SHA-1 checksum of `data`, which B2 requires for uploads.
+fn sha1_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
+
+impl B2Store {
+    /// Return the name of the file for the block with the given `key`.
+    fn block_path(&self, key: BlockKey) -> String {
+        match key {
+            BlockKey::Data(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                DATA_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Lock(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                LOCKS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Header(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                HEADERS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Super => join_key(&[&self.prefix, STORE_KEY, SUPER_KEY]),
+            BlockKey::Version => join_key(&[&self.prefix, STORE_KEY, REPO_VERSION_KEY]),
+        }
+    }
+
+    /// Send the request made by `request`, authorizing the account again if the token expired.
+    ///
+    /// This returns `None` if the file does not exist.
+    fn send(
+        &mut self,
+        request: impl Fn(&Agent, &Authorization) -> Result<Response, ureq::Error>,
+    ) -> super::Result<Option<Response>> {
+        let result = match request(&self.agent, &self.authorization) {
+            Err(ureq::Error::Status(UNAUTHORIZED_CODE, _)) => {
+                self.authorization = self.config.authorize(&self.agent)?;
+                request(&self.agent, &self.authorization)
+            }
+            result => result,
+        };
+        check_response(SERVICE_NAME, result)
+    }
+
+    /// Call the API `operation` with the given JSON `body` and return its response.
+    fn api<T: DeserializeOwned>(&mut self, operation: &str, body: Value) -> super::Result<T> {
+        let body = body.to_string();
+        let response = self
+            .send(|agent, authorization| {
+                agent
+                    .post(&format!("{}/b2api/v2/{}", authorization.api_url, operation))
+                    .set("Authorization", &authorization.authorization_token)
+                    .set("Content-Type", "application/json")
+                    .send_string(&body)
+            })?
+            .ok_or_else(|| super::Error::msg(format!("The {} operation failed.", operation)))?;
+        Ok(serde_json::from_reader(response.into_reader())?)
+    }
+
+    /// Return a response with the contents of the file with the given `name`.
+    ///
+    /// This returns `None` if the file does not exist.
+    fn download(&mut self, name: &str) -> super::Result<Option<Response>> {
+        let path = format!(
+            "file/{}/{}",
+            percent_encode(&self.config.bucket, b""),
+            percent_encode(name, b"/")
+        );
+        self.send(|agent, authorization| {
+            agent
+                .get(&format!("{}/{}", authorization.download_url, path))
+                .set("Authorization", &authorization.authorization_token)
+                .call()
+        })
+    }
+
+    /// Return the contents of the file with the given `name` or `None` if it does not exist.
+    fn get(&mut self, name: &str) -> super::Result<Option<Vec<u8>>> {
+        match self.download(name)? {
+            Some(response) => {
+                let mut data = Vec::new();
+                response.into_reader().read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Upload `data` to the given upload `url` and return the response.
+    fn upload(
+        &self,
+        url: &UploadUrl,
+        headers: &[(&str, &str)],
+        data: &[u8],
+    ) -> Result<Response, ureq::Error> {
+        let mut request = self
+            .agent
+            .post(&url.upload_url)
+            .set("Authorization", &url.authorization_token)
+            .set("Content-Length", &data.len().to_string())
+            .set("X-Bz-Content-Sha1", &sha1_hex(data));
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        request.send_bytes(data)
+    }
+
+    /// Upload `data` as a file with the given `name` in a single request and return its ID.
+    fn put_small(&mut self, name: &str, data: &[u8]) -> super::Result<String> {
+        let encoded_name = percent_encode(name, b"/");
+        let headers = [
+            ("X-Bz-File-Name", encoded_name.as_str()),
+            ("Content-Type", "application/octet-stream"),
+        ];
+
+        // Upload URLs can be reused until they expire or are too busy, in which case we need to
+        // get a new one.
+        let url = match self.upload_url.take() {
+            Some(url) => url,
+            None => self.api("b2_get_upload_url", json!({ "bucketId": self.bucket_id }))?,
+        };
+        let (url, result) = match self.upload(&url, &headers, data) {
+            Err(ureq::Error::Status(UNAUTHORIZED_CODE | SERVICE_UNAVAILABLE_CODE, _)) => {
+                let url: UploadUrl =
+                    self.api("b2_get_upload_url", json!({ "bucketId": self.bucket_id }))?;
+                let result = self.upload(&url, &headers, data);
+                (url, result)
+            }
+            result => (url, result),
+        };
+
+        let response = check_response(SERVICE_NAME, result)?
+            .ok_or_else(|| super::Error::msg("The bucket does not exist."))?;
+        self.upload_url = Some(url);
+        let file: FileInfo = serde_json::from_reader(response.into_reader())?;
+        Ok(file.file_id)
+    }
+
+    /// Upload `data` as a large file with the given `name` in parts of `part_size` bytes and
+    /// return its ID.
+    fn put_large(&mut self, name: &str, data: &[u8], part_size: usize) -> super::Result<String> {
+        let file: FileInfo = self.api(
+            "b2_start_large_file",
+            json!({
+                "bucketId": self.bucket_id,
+                "fileName": name,
+                "contentType": "application/octet-stream",
+            }),
+        )?;
+
+        let result = self.upload_parts(&file.file_id, data, part_size);
+        if result.is_err() {
+            // Unfinished large files take up space until they're cancelled. The original error is
+            // more useful than an error cancelling it.
+            let _ = self.api::<Value>("b2_cancel_large_file", json!({ "fileId": file.file_id }));
+        }
+        result?;
+
+        Ok(file.file_id)
+    }
+
+    /// Upload the parts of the large file with the given `file_id` and finish it.
+    fn upload_parts(&mut self, file_id: &str, data: &[u8], part_size: usize) -> super::Result<()> {
+        let url: UploadUrl = self.api("b2_get_upload_part_url", json!({ "fileId": file_id }))?;
+        let mut checksums = Vec::new();
+
+        for (index, part) in data.chunks(part_size).enumerate() {
+            let part_number = (index + 1).to_string();
+            let headers = [("X-Bz-Part-Number", part_number.as_str())];
+            check_response(SERVICE_NAME, self.upload(&url, &headers, part))?
+                .ok_or_else(|| super::Error::msg("The large file does not exist."))?;
+            checksums.push(sha1_hex(part));
+        }
+
+        self.api::<Value>(
+            "b2_finish_large_file",
+            json!({ "fileId": file_id, "partSha1Array": checksums }),
+        )?;
+        Ok(())
+    }
+
+    /// Upload `data` as a file with the given `name`, replacing any existing versions.
+    fn put(&mut self, name: &str, data: &[u8]) -> super::Result<()> {
+        let part_size = self.authorization.recommended_part_size as usize;
+
+        // A large file needs at least two parts.
+        let file_id = if data.len() > part_size {
+            self.put_large(name, data, part_size)?
+        } else {
+            self.put_small(name, data)?
+        };
+
+        self.delete_versions(name, Some(&file_id))
+    }
+
+    /// Delete every version of the file with the given `name` except the one with `keep_id`.
+    fn delete_versions(&mut self, name: &str, keep_id: Option<&str>) -> super::Result<()> {
+        let mut start_id: Option<String> = None;
+
+        loop {
+            let mut body = json!({
+                "bucketId": self.bucket_id,
+                "startFileName": name,
+                "prefix": name,
+                "maxFileCount": MAX_FILE_COUNT,
+            });
+            if let Some(start_id) = &start_id {
+                body["startFileId"] = json!(start_id);
+            }
+            let page: FileList = self.api("b2_list_file_versions", body)?;
+
+            for file in page.files {
+                if file.file_name == name && Some(file.file_id.as_str()) != keep_id {
+                    self.api::<Value>(
+                        "b2_delete_file_version",
+                        json!({ "fileName": file.file_name, "fileId": file.file_id }),
+                    )?;
+                }
+            }
+
+            // Versions of other files with this name as a prefix come after all versions of this
+            // file, so we can stop once we reach them.
+            match (page.next_file_name, page.next_file_id) {
+                (Some(next_name), Some(next_id)) if next_name == name => start_id = Some(next_id),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Return the names of all files whose names start with `prefix`.
+    fn list(&mut self, prefix: &str) -> super::Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut start_name: Option<String> = None;
+
+        loop {
+            let mut body = json!({
+                "bucketId": self.bucket_id,
+                "prefix": prefix,
+                "maxFileCount": MAX_FILE_COUNT,
+            });
+            if let Some(start_name) = &start_name {
+                body["startFileName"] = json!(start_name);
+            }
+            let page: FileList = self.api("b2_list_file_names", body)?;
+
+            names.extend(page.files.into_iter().map(|file| file.file_name));
+
+            match page.next_file_name {
+                Some(next_name) => start_name = Some(next_name),
+                None => return Ok(names),
+            }
+        }
+    }
+}
+
+impl DataStore for B2Store {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let block_path = self.block_path(key);
+        self.put(&block_path, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let block_path = self.block_path(key);
+        self.get(&block_path)
+    }
+
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        let block_path = self.block_path(key);
+        Ok(self
+            .download(&block_path)?
+            .map(|response| Box::new(response.into_reader()) as Box<dyn Read>))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let block_path = self.block_path(key);
+        self.delete_versions(&block_path, None)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let blocks_key = match kind {
+            BlockType::Data => join_key(&[&self.prefix, STORE_KEY, DATA_KEY]) + SEPARATOR,
+            BlockType::Lock => join_key(&[&self.prefix, STORE_KEY, LOCKS_KEY]) + SEPARATOR,
+            BlockType::Header => join_key(&[&self.prefix, STORE_KEY, HEADERS_KEY]) + SEPARATOR,
+        };
+        let block_ids = self
+            .list(&blocks_key)?
+            .iter()
+            .map(|name| Uuid::parse_str(name.trim_start_matches(&blocks_key)).map(|id| id.into()))
+            .collect::<Result<Vec<BlockId>, _>>()?;
+        Ok(block_ids)
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use uuid::Uuid;
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// The name of the directory which contains cached blocks.
+const BLOCKS_DIRECTORY: &str = "blocks";
+
+/// The name of the directory which contains blocks which haven't been written to the inner store.
+const PENDING_DIRECTORY: &str = "pending";
+
+/// The name of the directory for files which are being written.
+const STAGING_DIRECTORY: &str = "stage";
+
+/// Return the name of the cache file for the block with the given `key`.
+///
+/// Only data blocks and headers are cached, because blocks with these keys are never overwritten.
+fn file_name(key: BlockKey) -> Option<String> {
+    match key {
+        BlockKey::Data(id) => Some(format!("data-{}", id.as_ref().as_hyphenated())),
+        BlockKey::Header(id) => Some(format!("header-{}", id.as_ref().as_hyphenated())),
+        _ => None,
+    }
+}
+
+/// Return the key of the block with the given cache file `name`.
+fn parse_file_name(name: &str) -> Option<BlockKey> {
+    let (kind, id) = name.split_once('-')?;
+    let id = BlockId::from(Uuid::parse_str(id).ok()?);
+    match kind {
+        "data" => Some(BlockKey::Data(id)),
+        "header" => Some(BlockKey::Header(id)),
+        _ => None,
+    }
+}
+
+/// The configuration for opening a [`CachedStore`].
+///
+/// [`CachedStore`]: crate::store::CachedStore
+#[derive(Debug, Clone)]
+pub struct CachedConfig<C: OpenStore> {
+    /// The configuration for the data store to cache blocks from.
+    pub inner: C,
+
+    /// The path of the directory to cache blocks in.
+    ///
+    /// This directory is created if it doesn't exist. It must not be shared with any other
+    /// `CachedStore`.
+    pub path: PathBuf,
+
+    /// The maximum number of bytes of blocks to keep in the cache.
+    pub capacity: u64,
+
+    /// Whether to write data blocks to the inner data store in batches.
+    ///
+    /// See [`CachedStore`] for details.
+    ///
+    /// [`CachedStore`]: crate::store::CachedStore
+    pub write_back: bool,
+}
+
+impl<C: OpenStore> OpenStore for CachedConfig<C> {
+    type Store = CachedStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        CachedStore::new(
+            self.inner.open()?,
+            &self.path,
+            self.capacity,
+            self.write_back,
+        )
+    }
+}
+
+/// A cached block.
+#[derive(Debug, Clone, Copy)]
+struct CacheEntry {
+    /// The size of the block in bytes.
+    size: u64,
+
+    /// When the block was last used, where higher values are more recent.
+    last_used: u64,
+}
+
+/// A `DataStore` which caches blocks from another data store in a local directory.
+///
+/// This is useful for data stores which are slow to read from, like network storage. Data blocks
+/// and headers are cached when they are read or written, and they're read from the cache when
+/// possible. When the cache is full, the least recently used blocks are removed from it. Other
+/// blocks are always read from the inner data store, because they may be changed by other clients.
+///
+/// If write-back is enabled, data blocks are only written to the cache at first, and they're
+/// written to the inner data store in a batch when anything other than a data block is written,
+/// when the cache fills up with pending blocks, when [`flush`] is called, or when this value is
+/// dropped. Because repositories write data blocks before the metadata which references them,
+/// changes are still only visible in the inner data store once they're committed. Pending blocks
+/// which were never written to the inner data store are discarded when the cache is reopened.
+///
+/// The cache persists across instances, so it should only be used with a single inner data store.
+///
+/// You can use [`CachedConfig`] to open a data store of this type.
+///
+/// [`flush`]: crate::store::CachedStore::flush
+/// [`CachedConfig`]: crate::store::CachedConfig
+#[derive(Debug)]
+pub struct CachedStore<S: DataStore> {
+    inner: S,
+    path: PathBuf,
+    capacity: u64,
+    write_back: bool,
+    entries: HashMap<BlockKey, CacheEntry>,
+    lru: BTreeMap<u64, BlockKey>,
+    pending: HashSet<BlockKey>,
+    cached_size: u64,
+    clock: u64,
+}
+
+impl<S: DataStore> CachedStore<S> {
+    /// Cache blocks from the `inner` data store in the directory at `path`.
+    ///
+    /// The cache holds up to `capacity` bytes of blocks. If `write_back` is `true`, data blocks are
+    /// written to the inner data store in batches.
+    ///
+    /// # Errors
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn new(
+        inner: S,
+        path: impl AsRef<Path>,
+        capacity: u64,
+        write_back: bool,
+    ) -> crate::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        for directory in [PENDING_DIRECTORY, STAGING_DIRECTORY] {
+            match fs::remove_dir_all(path.join(directory)) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        }
+        for directory in [BLOCKS_DIRECTORY, PENDING_DIRECTORY, STAGING_DIRECTORY] {
+            fs::create_dir_all(path.join(directory))?;
+        }
+
+        // When blocks were last used isn't persisted, so order them by when they were cached.
+        let mut cached = Vec::new();
+        for entry in fs::read_dir(path.join(BLOCKS_DIRECTORY))? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            match entry.file_name().to_str().and_then(parse_file_name) {
+                Some(key) => cached.push((metadata.modified()?, key, metadata.len())),
+                None => fs::remove_file(entry.path())?,
+            }
+        }
+        cached.sort_by_key(|(modified, ..)| *modified);
+
+        let mut store = CachedStore {
+            inner,
+            path,
+            capacity,
+            write_back,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            pending: HashSet::new(),
+            cached_size: 0,
+            clock: 0,
+        };
+        for (_, key, size) in cached {
+            store.track(key, size);
+        }
+        store.evict()?;
+
+        Ok(store)
+    }
+
+    /// The number of bytes of blocks currently in the cache.
+    pub fn cached_size(&self) -> u64 {
+        self.cached_size
+    }
+
+    /// Write any pending data blocks to the inner data store.
+    ///
+    /// This does nothing if write-back is disabled.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with the inner data store or the cache.
+    pub fn flush(&mut self) -> crate::Result<()> {
+        self.flush_pending().map_err(crate::Error::Store)
+    }
+
+    /// Return the path of the cache file for the block with the given `key`.
+    fn block_path(&self, key: BlockKey) -> Option<PathBuf> {
+        let directory = if self.pending.contains(&key) {
+            PENDING_DIRECTORY
+        } else {
+            BLOCKS_DIRECTORY
+        };
+        file_name(key).map(|name| self.path.join(directory).join(name))
+    }
+
+    /// Write `data` to the file at `path` atomically.
+    fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let staging_path = self
+            .path
+            .join(STAGING_DIRECTORY)
+            .join(Uuid::new_v4().to_string());
+        let mut file = File::create(&staging_path)?;
+        file.write_all(data)?;
+        fs::rename(&staging_path, path)
+    }
+
+    /// Record that the block with the given `key` and `size` is in the cache and was just used.
+    fn track(&mut self, key: BlockKey, size: u64) {
+        self.untrack(key);
+        self.clock += 1;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                size,
+                last_used: self.clock,
+            },
+        );
+        self.lru.insert(self.clock, key);
+        self.cached_size += size;
+    }
+
+    /// Record that the block with the given `key` is no longer in the cache.
+    fn untrack(&mut self, key: BlockKey) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.lru.remove(&entry.last_used);
+            self.cached_size -= entry.size;
+        }
+    }
+
+    /// Record that the block with the given `key` was just used.
+    fn touch(&mut self, key: BlockKey) {
+        if let Some(entry) = self.entries.get(&key).copied() {
+            self.track(key, entry.size);
+        }
+    }
+
+    /// Remove the least recently used blocks until the cache is within its capacity.
+    ///
+    /// Pending blocks are never removed.
+    fn evict(&mut self) -> io::Result<()> {
+        let mut evictable = self
+            .lru
+            .values()
+            .copied()
+            .filter(|key| !self.pending.contains(key))
+            .collect::<Vec<_>>()
+            .into_iter();
+        while self.cached_size > self.capacity {
+            let key = match evictable.next() {
+                Some(key) => key,
+                None => break,
+            };
+            if let Some(path) = self.block_path(key) {
+                match fs::remove_file(path) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                    _ => {}
+                }
+            }
+            self.untrack(key);
+        }
+        Ok(())
+    }
+
+    /// Add `data` to the cache as the block with the given `key` if it fits.
+    ///
+    /// Failing to cache a block isn't an error, because the block can still be read from the
+    /// inner data store.
+    fn cache(&mut self, key: BlockKey, data: &[u8]) {
+        if data.len() as u64 > self.capacity {
+            return;
+        }
+        if let Some(path) = self.block_path(key) {
+            if self.write_file(&path, data).is_ok() {
+                self.track(key, data.len() as u64);
+                self.evict().ok();
+            }
+        }
+    }
+
+    /// Write all pending blocks to the inner data store and move them into the cache.
+    fn flush_pending(&mut self) -> super::Result<()> {
+        let mut pending = self.pending.iter().copied().collect::<Vec<_>>();
+        pending.sort_by_key(|key| self.entries.get(key).map(|entry| entry.last_used));
+
+        for key in pending {
+            let pending_path = self.block_path(key).unwrap();
+            let data = fs::read(&pending_path)?;
+            self.inner.write_block(key, &data)?;
+            self.pending.remove(&key);
+            fs::rename(&pending_path, self.block_path(key).unwrap())?;
+        }
+
+        self.evict()?;
+        Ok(())
+    }
+}
+
+impl<S: DataStore> DataStore for CachedStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        if self.write_back && matches!(key, BlockKey::Data(_)) {
+            if data.len() as u64 > self.capacity {
+                self.inner.write_block(key, data)?;
+                return Ok(());
+            }
+            self.untrack(key);
+            self.pending.insert(key);
+            let path = self.block_path(key).unwrap();
+            if let Err(error) = self.write_file(&path, data) {
+                self.pending.remove(&key);
+                return Err(error.into());
+            }
+            self.track(key, data.len() as u64);
+            if self.cached_size > self.capacity {
+                self.flush_pending()?;
+            }
+            return Ok(());
+        }
+
+        // Blocks which reference data blocks must not be written before those data blocks.
+        self.flush_pending()?;
+        self.inner.write_block(key, data)?;
+        self.cache(key, data);
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        if self.entries.contains_key(&key) {
+            if let Ok(data) = fs::read(self.block_path(key).unwrap()) {
+                self.touch(key);
+                return Ok(Some(data));
+            }
+            if self.pending.contains(&key) {
+                return Err(super::Error::msg("A pending block could not be read."));
+            }
+            self.untrack(key);
+        }
+
+        let data = self.inner.read_block(key)?;
+        if let Some(data) = &data {
+            self.cache(key, data);
+        }
+        Ok(data)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        if let Some(path) = self.block_path(key) {
+            match fs::remove_file(path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        }
+        self.untrack(key);
+        self.pending.remove(&key);
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let mut block_ids = self.inner.list_blocks(kind)?;
+        if kind == BlockType::Data {
+            block_ids.extend(self.pending.iter().filter_map(|key| match key {
+                BlockKey::Data(id) => Some(*id),
+                _ => None,
+            }));
+        }
+        Ok(block_ids)
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.inner.block_modified_time(key)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.inner.block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.flush_pending()?;
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        self.inner.probe()
+    }
+}
+
+impl<S: DataStore> Drop for CachedStore<S> {
+    fn drop(&mut self) {
+        // Attempt to write any pending blocks. This may fail.
+        self.flush_pending().ok();
+    }
+}
@@ -0,0 +1,198 @@
+#![cfg(feature = "compression")]
+
+use std::time::{Duration, SystemTime};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+use crate::repo::Compression;
+
+/// The tag at the start of a block which is stored uncompressed.
+const UNCOMPRESSED_TAG: u8 = 0;
+
+/// The tag at the start of a block which is compressed with LZ4.
+const LZ4_TAG: u8 = 1;
+
+/// The tag at the start of a block which is compressed with Zstandard.
+const ZSTD_TAG: u8 = 2;
+
+/// The configuration for opening a [`CompressedStore`].
+///
+/// [`CompressedStore`]: crate::store::CompressedStore
+#[derive(Debug, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub struct CompressedConfig<C: OpenStore> {
+    /// The configuration for the data store to write compressed blocks to.
+    pub inner: C,
+
+    /// The compression method to use for new blocks.
+    pub compression: Compression,
+}
+
+impl<C: OpenStore> OpenStore for CompressedConfig<C> {
+    type Store = CompressedStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(CompressedStore::new(
+            self.inner.open()?,
+            self.compression.clone(),
+        ))
+    }
+}
+
+/// A `DataStore` which compresses blocks before writing them to another data store.
+///
+/// This is independent of any compression done by the repository, so it can be used to make
+/// blocks smaller in storage while keeping compression off of the clients which write to the
+/// repository, like when the inner data store runs on a server. Compressing blocks which the
+/// repository has already encrypted doesn't make them any smaller.
+///
+/// Each block starts with a tag recording how it was compressed, so the compression method can be
+/// changed without affecting existing blocks. Blocks which don't get smaller when compressed are
+/// stored uncompressed. Every block in the inner data store must have been written by a
+/// `CompressedStore`.
+///
+/// You can use [`CompressedConfig`] to open a data store of this type.
+///
+/// [`CompressedConfig`]: crate::store::CompressedConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub struct CompressedStore<S: DataStore> {
+    inner: S,
+    compression: Compression,
+}
+
+impl<S: DataStore> CompressedStore<S> {
+    /// Compress blocks written to the given `inner` data store with the given `compression`.
+    pub fn new(inner: S, compression: Compression) -> Self {
+        Self { inner, compression }
+    }
+
+    /// The data store which compressed blocks are written to.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// The compression method used for new blocks.
+    pub fn compression(&self) -> &Compression {
+        &self.compression
+    }
+
+    /// Return the tagged contents of a block containing `data`.
+    fn encode(&self, data: &[u8]) -> super::Result<Vec<u8>> {
+        let tag = match self.compression {
+            Compression::None => UNCOMPRESSED_TAG,
+            Compression::Lz4 { .. } => LZ4_TAG,
+            Compression::Zstd { .. } => ZSTD_TAG,
+        };
+
+        if tag != UNCOMPRESSED_TAG {
+            let mut output = vec![tag];
+            self.compression
+                .compress_into(data, &mut output)
+                .map_err(super::Error::new)?;
+            if output.len() <= data.len() {
+                return Ok(output);
+            }
+        }
+
+        let mut output = Vec::with_capacity(data.len() + 1);
+        output.push(UNCOMPRESSED_TAG);
+        output.extend_from_slice(data);
+        Ok(output)
+    }
+}
+
+/// Return the data in the block with the given tagged `contents`.
+fn decode(contents: &[u8]) -> super::Result<Vec<u8>> {
+    match contents.split_first() {
+        Some((&UNCOMPRESSED_TAG, data)) => Ok(data.to_vec()),
+        Some((&LZ4_TAG, data)) => Compression::Lz4 { level: 1 }
+            .decompress(data)
+            .map_err(super::Error::new),
+        Some((&ZSTD_TAG, data)) => Compression::Zstd { level: 1 }
+            .decompress(data)
+            .map_err(super::Error::new),
+        _ => Err(super::Error::new(crate::Error::InvalidData)),
+    }
+}
+
+impl<S: DataStore> DataStore for CompressedStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let contents = self.encode(data)?;
+        self.inner.write_block(key, &contents)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        match self.inner.read_block(key)? {
+            Some(contents) => Ok(Some(decode(&contents)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        let contents = blocks
+            .iter()
+            .map(|(key, data)| Ok((*key, self.encode(data)?)))
+            .collect::<super::Result<Vec<_>>>()?;
+        let inner_blocks = contents
+            .iter()
+            .map(|(key, data)| (*key, data.as_slice()))
+            .collect::<Vec<_>>();
+        self.inner.write_blocks(&inner_blocks)
+    }
+
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        self.inner
+            .read_blocks(keys)?
+            .into_iter()
+            .map(|contents| contents.map(|contents| decode(&contents)).transpose())
+            .collect()
+    }
+
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        self.inner.remove_blocks(keys)
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.inner.block_modified_time(key)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.inner.block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        let capabilities = self.inner.probe();
+        StoreCapabilities {
+            // Each block is prefixed with a tag, and blocks which don't compress are stored as-is.
+            max_block_size: capabilities
+                .max_block_size
+                .map(|size| size.saturating_sub(1)),
+            ..capabilities
+        }
+    }
+}
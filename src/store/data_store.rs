@@ -1,5 +1,8 @@
 use std::fmt::{self, Debug, Formatter};
+use std::io::{Cursor, Read};
+use std::time::{Duration, SystemTime};
 
+use serde::{Deserialize, Serialize};
 use static_assertions::assert_obj_safe;
 
 uuid_type! {
@@ -12,7 +15,7 @@ uuid_type! {
 /// A key for accessing a block in a [`DataStore`].
 ///
 /// [`DataStore`]: crate::store::DataStore
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BlockKey {
     Data(BlockId),
     Lock(BlockId),
@@ -24,13 +27,111 @@ pub enum BlockKey {
 /// A type of block in a [`DataStore`].
 ///
 /// [`DataStore`]: crate::store::DataStore
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BlockType {
     Data,
     Lock,
     Header,
 }
 
+/// The consistency guarantees a [`DataStore`] makes when listing blocks.
+///
+/// [`DataStore`]: crate::store::DataStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Consistency {
+    /// Blocks are visible to [`DataStore::list_blocks`] as soon as they are written, and removed
+    /// blocks are never listed.
+    ///
+    /// [`DataStore::list_blocks`]: crate::store::DataStore::list_blocks
+    Strong,
+
+    /// Blocks may take some time to become visible to [`DataStore::list_blocks`] after they are
+    /// written.
+    ///
+    /// When a data store has eventual consistency, repositories only remove blocks which are older
+    /// than a grace period. See [`RepoConfig::gc_grace_period`] for details.
+    ///
+    /// [`DataStore::list_blocks`]: crate::store::DataStore::list_blocks
+    /// [`RepoConfig::gc_grace_period`]: crate::repo::RepoConfig::gc_grace_period
+    Eventual,
+}
+
+/// How long operations on a [`DataStore`] are expected to take.
+///
+/// [`DataStore`]: crate::store::DataStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LatencyClass {
+    /// Blocks are stored in memory.
+    Memory,
+
+    /// Blocks are stored on local storage, like a disk or an embedded database.
+    Local,
+
+    /// Blocks are stored on a remote server, so each operation requires a round trip over the
+    /// network.
+    Network,
+}
+
+/// The capabilities of a [`DataStore`] as reported by [`DataStore::probe`].
+///
+/// Repositories and other higher layers can use this to adapt their behavior to the data store,
+/// like packing small chunks together when operations are slow.
+///
+/// [`DataStore`]: crate::store::DataStore
+/// [`DataStore::probe`]: crate::store::DataStore::probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StoreCapabilities {
+    /// The largest block the data store can hold in bytes, or `None` if there is no practical
+    /// limit.
+    pub max_block_size: Option<u64>,
+
+    /// Whether writing and removing blocks are atomic operations.
+    ///
+    /// This is `true` for every data store which upholds the contract of [`DataStore`]. A data
+    /// store which can be left with a partially written block if it's interrupted should report
+    /// `false`.
+    ///
+    /// [`DataStore`]: crate::store::DataStore
+    pub atomic_writes: bool,
+
+    /// How long operations on the data store are expected to take.
+    pub latency: LatencyClass,
+
+    /// Whether the data store accepts writes.
+    pub writable: bool,
+}
+
+impl StoreCapabilities {
+    /// Return the capabilities of a store which uses both `self` and `other` to store blocks.
+    ///
+    /// This doesn't combine [`writable`], because that depends on how the store uses them.
+    ///
+    /// [`writable`]: crate::store::StoreCapabilities::writable
+    pub(super) fn combine(self, other: Self) -> Self {
+        let max_block_size = match (self.max_block_size, other.max_block_size) {
+            (Some(first), Some(second)) => Some(first.min(second)),
+            (first, second) => first.or(second),
+        };
+        Self {
+            max_block_size,
+            atomic_writes: self.atomic_writes && other.atomic_writes,
+            latency: self.latency.max(other.latency),
+            writable: self.writable,
+        }
+    }
+}
+
+impl Default for StoreCapabilities {
+    fn default() -> Self {
+        Self {
+            max_block_size: None,
+            atomic_writes: true,
+            latency: LatencyClass::Network,
+            writable: true,
+        }
+    }
+}
+
 /// A persistent store for blocks of data.
 ///
 /// A `DataStore` persistently stores blocks of data uniquely identified by [`BlockKey`] values.
@@ -55,6 +156,21 @@ pub trait DataStore: Send {
     /// If there is no block with the given `key`, return `None`.
     fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>>;
 
+    /// Return a reader for the bytes of the block with the given `key`.
+    ///
+    /// If there is no block with the given `key`, return `None`.
+    ///
+    /// Repositories use this to read very large blocks without holding the whole block in memory.
+    /// Implementations which can read a block incrementally, like from a file, should override
+    /// this method.
+    ///
+    /// The default implementation reads the whole block with `read_block`.
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        Ok(self
+            .read_block(key)?
+            .map(|data| Box::new(Cursor::new(data)) as Box<dyn Read>))
+    }
+
     /// Remove the block with the given `key` from the store.
     ///
     /// If this method returns `Ok`, the given `key` is no longer stored persistently and any space
@@ -68,6 +184,144 @@ pub trait DataStore: Send {
 
     /// Return a list of IDs of blocks of the given `kind` in the store.
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>>;
+
+    /// Write each of the given `blocks`, which are pairs of keys and data.
+    ///
+    /// Each block is written atomically as if by `write_block`, but the batch as a whole is not
+    /// atomic. If this method returns `Err`, some of the blocks may have been written.
+    ///
+    /// Repositories use this to write several blocks at once. Implementations which can write a
+    /// batch of blocks more efficiently than one at a time, like with fewer network round trips,
+    /// should override this method.
+    ///
+    /// The default implementation calls `write_block` for each block in order.
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        for (key, data) in blocks {
+            self.write_block(*key, data)?;
+        }
+        Ok(())
+    }
+
+    /// Return the bytes of each of the blocks with the given `keys`.
+    ///
+    /// This returns a list in the same order as `keys`. If there is no block with a given key, its
+    /// entry is `None`.
+    ///
+    /// Implementations which can read a batch of blocks more efficiently than one at a time should
+    /// override this method.
+    ///
+    /// The default implementation calls `read_block` for each key in order.
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        keys.iter().map(|key| self.read_block(*key)).collect()
+    }
+
+    /// Remove each of the blocks with the given `keys` from the store.
+    ///
+    /// Each block is removed atomically as if by `remove_block`, but the batch as a whole is not
+    /// atomic. If this method returns `Err`, some of the blocks may have been removed.
+    ///
+    /// Implementations which can remove a batch of blocks more efficiently than one at a time
+    /// should override this method.
+    ///
+    /// The default implementation calls `remove_block` for each key in order.
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        for key in keys {
+            self.remove_block(*key)?;
+        }
+        Ok(())
+    }
+
+    /// Durably persist all the blocks which have been written to this store.
+    ///
+    /// Once this returns `Ok`, blocks written before it was called should survive a crash or power
+    /// loss. Stores which buffer writes should flush them here, and stores which wrap other stores
+    /// should forward this call to them.
+    ///
+    /// The default implementation does nothing, which is correct for stores which persist each
+    /// block before `write_block` returns.
+    fn sync(&mut self) -> super::Result<()> {
+        Ok(())
+    }
+
+    /// Return the consistency guarantees this store makes when listing blocks.
+    ///
+    /// The default implementation returns `Consistency::Strong`.
+    fn consistency(&self) -> Consistency {
+        Consistency::Strong
+    }
+
+    /// Return the time the block with the given `key` was last written.
+    ///
+    /// If there is no block with the given `key` or its modification time is unknown, return
+    /// `None`. When this store has eventual consistency, a block with an unknown modification time
+    /// is never removed by a repository.
+    ///
+    /// The default implementation always returns `None`.
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        let _ = key;
+        Ok(None)
+    }
+
+    /// Return the size in bytes of the block with the given `key` without reading it.
+    ///
+    /// If there is no block with the given `key` or its size is unknown, return `None`.
+    /// Repositories use this to report the size of blocks which they can't remove yet because of
+    /// the store's [`retention`] period. Stores which return `Some` from [`retention`] should also
+    /// implement this method.
+    ///
+    /// The default implementation always returns `None`.
+    ///
+    /// [`retention`]: crate::store::DataStore::retention
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let _ = key;
+        Ok(None)
+    }
+
+    /// Return the period after a block is written during which it cannot be removed, if any.
+    ///
+    /// Some backends, like S3 buckets with Object Lock enabled, refuse to remove blocks until a
+    /// retention period has passed since they were written. When this returns `Some`, repositories
+    /// only attempt to remove blocks which are older than the retention period according to
+    /// [`block_modified_time`], and blocks with an unknown modification time are never removed.
+    /// Stores which return `Some` should also implement [`block_modified_time`].
+    ///
+    /// The default implementation returns `None`.
+    ///
+    /// [`block_modified_time`]: crate::store::DataStore::block_modified_time
+    fn retention(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Return whether this store refuses all writes.
+    ///
+    /// Repositories can only be opened in a read-only store with [`OpenMode::ReadOnly`], which
+    /// never writes to the store. Opening a repository in a read-only store with any other mode
+    /// fails with `Error::ReadOnly` before anything is written.
+    ///
+    /// The default implementation returns `false`.
+    ///
+    /// [`OpenMode::ReadOnly`]: crate::repo::OpenMode::ReadOnly
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// Return the capabilities of this store.
+    ///
+    /// This should be cheap to call and should not access the underlying storage.
+    ///
+    /// The default implementation returns the default [`StoreCapabilities`], which assumes a remote
+    /// store with no limit on the size of blocks, except that [`StoreCapabilities::writable`] is
+    /// the opposite of [`is_read_only`].
+    ///
+    /// [`StoreCapabilities`]: crate::store::StoreCapabilities
+    /// [`StoreCapabilities::writable`]: crate::store::StoreCapabilities::writable
+    /// [`is_read_only`]: crate::store::DataStore::is_read_only
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            writable: !self.is_read_only(),
+            ..StoreCapabilities::default()
+        }
+    }
 }
 
 assert_obj_safe!(DataStore);
@@ -81,6 +335,10 @@ impl DataStore for Box<dyn DataStore> {
         self.as_mut().read_block(key)
     }
 
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        self.as_mut().read_block_streaming(key)
+    }
+
     fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
         self.as_mut().remove_block(key)
     }
@@ -88,6 +346,46 @@ impl DataStore for Box<dyn DataStore> {
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
         self.as_mut().list_blocks(kind)
     }
+
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        self.as_mut().write_blocks(blocks)
+    }
+
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        self.as_mut().read_blocks(keys)
+    }
+
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        self.as_mut().remove_blocks(keys)
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.as_mut().sync()
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.as_ref().consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.as_mut().block_modified_time(key)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.as_mut().block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.as_ref().retention()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.as_ref().is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        self.as_ref().probe()
+    }
 }
 
 impl Debug for dyn DataStore {
@@ -0,0 +1,925 @@
+#![cfg(feature = "store-device")]
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, LatencyClass, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// The magic number at the start of a formatted device.
+const MAGIC: [u8; 8] = *b"ACIDDEV\0";
+
+/// A UUID which acts as the version ID of the device format.
+const CURRENT_VERSION: Uuid = uuid!("5b0e7c3a-92d4-4f61-8a2e-c7f13d9b6e08");
+
+/// The size of the superblock, which is the magic number, the version ID, the format ID, the unit
+/// size, the unit count, the size of each block table, the size of the journal, and a checksum.
+const SUPERBLOCK_SIZE: usize = 8 + 16 + 16 + 4 + 8 + 8 + 8 + 8;
+
+/// The size of the header of a block table, which is a generation number and an entry count.
+const TABLE_HEADER_SIZE: usize = 8 + 8;
+
+/// The size of an entry in the block table, which is a tag, a block ID, the first unit, the length
+/// in bytes, and a checksum of the contents.
+const ENTRY_SIZE: usize = 1 + 16 + 8 + 8 + 8;
+
+/// The size of a journal entry, which is a generation number, an entry, and a checksum.
+const JOURNAL_ENTRY_SIZE: usize = 8 + ENTRY_SIZE + 8;
+
+/// The size of a checksum.
+const CHECKSUM_SIZE: usize = 8;
+
+/// The smallest allowed allocation unit.
+const MIN_UNIT_SIZE: u32 = 512;
+
+// The tags which identify the kind of each block.
+const DATA_TAG: u8 = 0;
+const LOCK_TAG: u8 = 1;
+const HEADER_TAG: u8 = 2;
+const SUPER_TAG: u8 = 3;
+const VERSION_TAG: u8 = 4;
+
+/// The bit which is set in the tag of a journal entry that removes a block.
+const REMOVED_BIT: u8 = 0x80;
+
+/// The options for formatting a device with [`DeviceStore::format`].
+///
+/// [`DeviceStore::format`]: crate::store::DeviceStore::format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-device")))]
+pub struct DeviceFormat {
+    /// The size of the units which space on the device is allocated in.
+    ///
+    /// This must be a power of two which is at least 512 bytes. Each block takes up a whole number
+    /// of units, so this should usually match the sector size of the device.
+    ///
+    /// The default is 4096 bytes.
+    pub unit_size: u32,
+
+    /// The maximum number of blocks the device can hold.
+    ///
+    /// Space for a table of this many blocks is reserved when the device is formatted.
+    ///
+    /// The default is 65536.
+    pub max_blocks: u64,
+
+    /// The number of changes which are recorded in the journal before the block table is rewritten.
+    ///
+    /// A larger journal means the block table is rewritten less often, but it takes longer to open
+    /// the store.
+    ///
+    /// The default is 4096.
+    pub journal_entries: u64,
+}
+
+impl Default for DeviceFormat {
+    fn default() -> Self {
+        Self {
+            unit_size: 4096,
+            max_blocks: 65536,
+            journal_entries: 4096,
+        }
+    }
+}
+
+/// The results of checking a device with [`DeviceStore::check`] or [`DeviceStore::repair`].
+///
+/// [`DeviceStore::check`]: crate::store::DeviceStore::check
+/// [`DeviceStore::repair`]: crate::store::DeviceStore::repair
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-device")))]
+pub struct DeviceReport {
+    /// The number of blocks on the device.
+    pub blocks: u64,
+
+    /// The number of bytes of the data region which are allocated to blocks.
+    pub used_bytes: u64,
+
+    /// The number of bytes of the data region which are available for new blocks.
+    pub free_bytes: u64,
+
+    /// The blocks whose space is outside the data region or overlaps the space of another block.
+    pub misplaced_blocks: HashSet<BlockKey>,
+
+    /// The blocks whose contents don't match their checksums.
+    pub corrupt_blocks: HashSet<BlockKey>,
+}
+
+impl DeviceReport {
+    /// Return whether no problems were found.
+    pub fn is_consistent(&self) -> bool {
+        self.misplaced_blocks.is_empty() && self.corrupt_blocks.is_empty()
+    }
+}
+
+/// The location of a block's contents on the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Extent {
+    /// The first unit of the block.
+    start: u64,
+
+    /// The length of the block in bytes.
+    len: u64,
+
+    /// A checksum of the contents of the block.
+    checksum: u64,
+}
+
+/// The layout of a formatted device.
+///
+/// The device starts with the superblock in the first unit, followed by two copies of the block
+/// table, followed by the journal, followed by the data region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    /// A random ID which is generated each time the device is formatted.
+    ///
+    /// This is included in every checksum so that stale metadata from a previous format is never
+    /// mistaken for valid metadata.
+    format_id: Uuid,
+    unit_size: u64,
+    unit_count: u64,
+    table_units: u64,
+    journal_units: u64,
+}
+
+impl Layout {
+    /// Return the layout for a device of `device_len` bytes formatted with `format`.
+    fn new(device_len: u64, format: &DeviceFormat) -> crate::Result<Self> {
+        if !format.unit_size.is_power_of_two() || format.unit_size < MIN_UNIT_SIZE {
+            return Err(invalid_format(
+                "The unit size must be a power of two of at least 512.",
+            ));
+        }
+        if format.max_blocks == 0 || format.journal_entries == 0 {
+            return Err(invalid_format(
+                "The maximum number of blocks and journal entries must not be zero.",
+            ));
+        }
+
+        let unit_size = format.unit_size as u64;
+        let table_size =
+            TABLE_HEADER_SIZE as u64 + format.max_blocks * ENTRY_SIZE as u64 + CHECKSUM_SIZE as u64;
+        let journal_size = format.journal_entries * JOURNAL_ENTRY_SIZE as u64;
+        let layout = Layout {
+            format_id: Uuid::new_v4(),
+            unit_size,
+            unit_count: device_len / unit_size,
+            table_units: (table_size + unit_size - 1) / unit_size,
+            journal_units: (journal_size + unit_size - 1) / unit_size,
+        };
+
+        if layout.data_start() >= layout.unit_count {
+            return Err(invalid_format("The device is too small for this format."));
+        }
+
+        Ok(layout)
+    }
+
+    /// Decode the layout from the given `superblock`.
+    fn decode(superblock: &[u8; SUPERBLOCK_SIZE]) -> crate::Result<Self> {
+        if superblock[..8] != MAGIC {
+            return Err(crate::Error::InvalidStore);
+        }
+        if Uuid::from_slice(&superblock[8..24]).unwrap() != CURRENT_VERSION {
+            return Err(crate::Error::UnsupportedStore);
+        }
+
+        let layout = Layout {
+            format_id: Uuid::from_slice(&superblock[24..40]).unwrap(),
+            unit_size: u32::from_le_bytes(superblock[40..44].try_into().unwrap()) as u64,
+            unit_count: read_u64(&superblock[44..52]),
+            table_units: read_u64(&superblock[52..60]),
+            journal_units: read_u64(&superblock[60..68]),
+        };
+
+        let body_size = SUPERBLOCK_SIZE - CHECKSUM_SIZE;
+        if read_u64(&superblock[body_size..]) != layout.checksum(&[&superblock[..body_size]])
+            || layout.unit_size < MIN_UNIT_SIZE as u64
+            || !layout.unit_size.is_power_of_two()
+            || layout.data_start() >= layout.unit_count
+        {
+            return Err(crate::Error::InvalidStore);
+        }
+
+        Ok(layout)
+    }
+
+    /// Encode this layout as a superblock.
+    fn encode(&self) -> Vec<u8> {
+        let mut superblock = Vec::with_capacity(SUPERBLOCK_SIZE);
+        superblock.extend_from_slice(&MAGIC);
+        superblock.extend_from_slice(CURRENT_VERSION.as_bytes());
+        superblock.extend_from_slice(self.format_id.as_bytes());
+        superblock.extend_from_slice(&(self.unit_size as u32).to_le_bytes());
+        superblock.extend_from_slice(&self.unit_count.to_le_bytes());
+        superblock.extend_from_slice(&self.table_units.to_le_bytes());
+        superblock.extend_from_slice(&self.journal_units.to_le_bytes());
+        let checksum = self.checksum(&[&superblock]);
+        superblock.extend_from_slice(&checksum.to_le_bytes());
+        superblock
+    }
+
+    /// Return the offset of the block table in the given `slot`.
+    fn table_offset(&self, slot: usize) -> u64 {
+        (1 + slot as u64 * self.table_units) * self.unit_size
+    }
+
+    /// Return the offset of the journal.
+    fn journal_offset(&self) -> u64 {
+        (1 + 2 * self.table_units) * self.unit_size
+    }
+
+    /// Return the first unit of the data region.
+    fn data_start(&self) -> u64 {
+        1 + 2 * self.table_units + self.journal_units
+    }
+
+    /// Return the number of entries which fit in a block table.
+    fn table_capacity(&self) -> u64 {
+        (self.table_units * self.unit_size - (TABLE_HEADER_SIZE + CHECKSUM_SIZE) as u64)
+            / ENTRY_SIZE as u64
+    }
+
+    /// Return the number of entries which fit in the journal.
+    fn journal_capacity(&self) -> u64 {
+        self.journal_units * self.unit_size / JOURNAL_ENTRY_SIZE as u64
+    }
+
+    /// Return the number of units needed to store `len` bytes.
+    fn units_for(&self, len: u64) -> u64 {
+        (len + self.unit_size - 1) / self.unit_size
+    }
+
+    /// Return the checksum of `parts`.
+    fn checksum(&self, parts: &[&[u8]]) -> u64 {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.format_id.as_bytes());
+        for part in parts {
+            hasher.update(part);
+        }
+        read_u64(&hasher.finalize().as_bytes()[..8])
+    }
+}
+
+/// Return an error for a device which can't be formatted with the given options.
+fn invalid_format(message: &'static str) -> crate::Error {
+    crate::Error::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+/// Decode a little-endian `u64` from `bytes`.
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes.try_into().unwrap())
+}
+
+/// Return the tag and ID for the block with the given `key`.
+fn key_to_tag(key: BlockKey) -> (u8, Uuid) {
+    match key {
+        BlockKey::Data(id) => (DATA_TAG, id.into()),
+        BlockKey::Lock(id) => (LOCK_TAG, id.into()),
+        BlockKey::Header(id) => (HEADER_TAG, id.into()),
+        BlockKey::Super => (SUPER_TAG, Uuid::nil()),
+        BlockKey::Version => (VERSION_TAG, Uuid::nil()),
+    }
+}
+
+/// Return the key of the block with the given `tag` and `id`.
+fn tag_to_key(tag: u8, id: Uuid) -> Option<BlockKey> {
+    match tag {
+        DATA_TAG => Some(BlockKey::Data(id.into())),
+        LOCK_TAG => Some(BlockKey::Lock(id.into())),
+        HEADER_TAG => Some(BlockKey::Header(id.into())),
+        SUPER_TAG => Some(BlockKey::Super),
+        VERSION_TAG => Some(BlockKey::Version),
+        _ => None,
+    }
+}
+
+/// Encode an entry for the block with the given `key`.
+///
+/// If `extent` is `None`, this is an entry which removes the block.
+fn encode_entry(key: BlockKey, extent: Option<Extent>) -> [u8; ENTRY_SIZE] {
+    let (tag, id) = key_to_tag(key);
+    let (tag, extent) = match extent {
+        Some(extent) => (tag, extent),
+        None => (tag | REMOVED_BIT, Extent::default()),
+    };
+
+    let mut entry = [0u8; ENTRY_SIZE];
+    entry[0] = tag;
+    entry[1..17].copy_from_slice(id.as_bytes());
+    entry[17..25].copy_from_slice(&extent.start.to_le_bytes());
+    entry[25..33].copy_from_slice(&extent.len.to_le_bytes());
+    entry[33..41].copy_from_slice(&extent.checksum.to_le_bytes());
+    entry
+}
+
+/// Decode an entry which was encoded with `encode_entry`.
+fn decode_entry(entry: &[u8]) -> Option<(BlockKey, Option<Extent>)> {
+    let key = tag_to_key(
+        entry[0] & !REMOVED_BIT,
+        Uuid::from_slice(&entry[1..17]).unwrap(),
+    )?;
+    if entry[0] & REMOVED_BIT != 0 {
+        return Some((key, None));
+    }
+    let extent = Extent {
+        start: read_u64(&entry[17..25]),
+        len: read_u64(&entry[25..33]),
+        checksum: read_u64(&entry[33..41]),
+    };
+    Some((key, Some(extent)))
+}
+
+/// Read the block table in the given `slot`.
+///
+/// This returns the generation number and the blocks in the table, or `None` if the table is
+/// invalid.
+fn read_table(
+    device: &mut File,
+    layout: &Layout,
+    slot: usize,
+) -> io::Result<Option<(u64, HashMap<BlockKey, Extent>)>> {
+    let mut header = [0u8; TABLE_HEADER_SIZE];
+    device.seek(SeekFrom::Start(layout.table_offset(slot)))?;
+    device.read_exact(&mut header)?;
+    let generation = read_u64(&header[..8]);
+    let count = read_u64(&header[8..]);
+    if count > layout.table_capacity() {
+        return Ok(None);
+    }
+
+    let mut body = vec![0u8; count as usize * ENTRY_SIZE + CHECKSUM_SIZE];
+    device.read_exact(&mut body)?;
+    let (entries, checksum) = body.split_at(body.len() - CHECKSUM_SIZE);
+    if read_u64(checksum) != layout.checksum(&[&header, entries]) {
+        return Ok(None);
+    }
+
+    let mut index = HashMap::with_capacity(count as usize);
+    for entry in entries.chunks_exact(ENTRY_SIZE) {
+        match decode_entry(entry) {
+            Some((key, Some(extent))) => index.insert(key, extent),
+            _ => return Ok(None),
+        };
+    }
+
+    Ok(Some((generation, index)))
+}
+
+/// Write the blocks in `index` to the block table in the given `slot` and sync it to disk.
+fn write_table(
+    device: &mut File,
+    layout: &Layout,
+    slot: usize,
+    generation: u64,
+    index: &HashMap<BlockKey, Extent>,
+) -> io::Result<()> {
+    let mut table =
+        Vec::with_capacity(TABLE_HEADER_SIZE + index.len() * ENTRY_SIZE + CHECKSUM_SIZE);
+    table.extend_from_slice(&generation.to_le_bytes());
+    table.extend_from_slice(&(index.len() as u64).to_le_bytes());
+    for (key, extent) in index {
+        table.extend_from_slice(&encode_entry(*key, Some(*extent)));
+    }
+    let checksum = layout.checksum(&[&table]);
+    table.extend_from_slice(&checksum.to_le_bytes());
+
+    device.seek(SeekFrom::Start(layout.table_offset(slot)))?;
+    device.write_all(&table)?;
+    device.sync_data()
+}
+
+/// The metadata which was read from a device.
+#[derive(Debug)]
+struct Metadata {
+    layout: Layout,
+    generation: u64,
+    active_slot: usize,
+    index: HashMap<BlockKey, Extent>,
+    journal_len: u64,
+}
+
+impl Metadata {
+    /// Read the metadata from `device`, replaying the journal.
+    ///
+    /// # Errors
+    /// - `Error::InvalidStore`: The device is not formatted.
+    /// - `Error::UnsupportedStore`: The device was formatted with an incompatible version.
+    /// - `Error::Corrupt`: Neither copy of the block table is valid.
+    /// - `Error::Io`: An I/O error occurred.
+    fn read(device: &mut File) -> crate::Result<Self> {
+        let mut superblock = [0u8; SUPERBLOCK_SIZE];
+        device.seek(SeekFrom::Start(0))?;
+        match device.read_exact(&mut superblock) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(crate::Error::InvalidStore)
+            }
+            result => result?,
+        }
+        let layout = Layout::decode(&superblock)?;
+
+        let mut newest = None;
+        for slot in 0..2 {
+            if let Some((generation, index)) = read_table(device, &layout, slot)? {
+                if newest.as_ref().map_or(true, |(newest_generation, _, _)| {
+                    generation > *newest_generation
+                }) {
+                    newest = Some((generation, slot, index));
+                }
+            }
+        }
+        let (generation, active_slot, mut index) = newest.ok_or(crate::Error::Corrupt)?;
+
+        let mut journal = vec![0u8; (layout.journal_units * layout.unit_size) as usize];
+        device.seek(SeekFrom::Start(layout.journal_offset()))?;
+        device.read_exact(&mut journal)?;
+
+        let mut journal_len = 0;
+        for journal_entry in journal.chunks_exact(JOURNAL_ENTRY_SIZE) {
+            let (body, checksum) = journal_entry.split_at(JOURNAL_ENTRY_SIZE - CHECKSUM_SIZE);
+            if read_u64(&body[..8]) != generation || read_u64(checksum) != layout.checksum(&[body])
+            {
+                break;
+            }
+            match decode_entry(&body[8..]) {
+                Some((key, Some(extent))) => index.insert(key, extent),
+                Some((key, None)) => index.remove(&key),
+                None => break,
+            };
+            journal_len += 1;
+        }
+
+        Ok(Metadata {
+            layout,
+            generation,
+            active_slot,
+            index,
+            journal_len,
+        })
+    }
+
+    /// Return the blocks whose space is outside the data region or overlaps another block.
+    fn misplaced_blocks(&self) -> HashSet<BlockKey> {
+        let mut extents = self
+            .index
+            .iter()
+            .filter(|(_, extent)| extent.len > 0)
+            .collect::<Vec<_>>();
+        extents.sort_by_key(|(_, extent)| extent.start);
+
+        let mut misplaced = HashSet::new();
+        let mut next_free = self.layout.data_start();
+        for (key, extent) in extents {
+            let end = extent
+                .start
+                .checked_add(self.layout.units_for(extent.len))
+                .unwrap_or(u64::MAX);
+            if extent.start < next_free || end > self.layout.unit_count {
+                misplaced.insert(*key);
+            } else {
+                next_free = end;
+            }
+        }
+        misplaced
+    }
+
+    /// Return an allocator for the space which isn't used by any block in the index.
+    ///
+    /// This assumes that no blocks are misplaced.
+    fn allocator(&self) -> Allocator {
+        let mut allocator = Allocator::default();
+        allocator.release(
+            self.layout.data_start(),
+            self.layout.unit_count - self.layout.data_start(),
+        );
+        for extent in self.index.values() {
+            allocator.reserve(extent.start, self.layout.units_for(extent.len));
+        }
+        allocator
+    }
+}
+
+/// An allocator for ranges of units on the device.
+#[derive(Debug, Default)]
+struct Allocator {
+    /// The number of units in each free range, indexed by the first unit in the range.
+    free: BTreeMap<u64, u64>,
+}
+
+impl Allocator {
+    /// Allocate a range of `units` units and return its first unit.
+    ///
+    /// This uses the first free range which is large enough.
+    fn allocate(&mut self, units: u64) -> Option<u64> {
+        let (start, len) = self
+            .free
+            .iter()
+            .map(|(start, len)| (*start, *len))
+            .find(|(_, len)| *len >= units)?;
+        self.free.remove(&start);
+        if len > units {
+            self.free.insert(start + units, len - units);
+        }
+        Some(start)
+    }
+
+    /// Mark the range of `units` units starting at `start` as used.
+    ///
+    /// The range must be free.
+    fn reserve(&mut self, start: u64, units: u64) {
+        if units == 0 {
+            return;
+        }
+        let (free_start, free_len) = match self.free.range(..=start).next_back() {
+            Some((free_start, free_len)) => (*free_start, *free_len),
+            None => return,
+        };
+        self.free.remove(&free_start);
+        if start > free_start {
+            self.free.insert(free_start, start - free_start);
+        }
+        if free_start + free_len > start + units {
+            self.free
+                .insert(start + units, free_start + free_len - start - units);
+        }
+    }
+
+    /// Mark the range of `units` units starting at `start` as free.
+    fn release(&mut self, mut start: u64, mut units: u64) {
+        if units == 0 {
+            return;
+        }
+        if let Some((previous_start, previous_len)) = self.free.range(..start).next_back() {
+            if previous_start + previous_len == start {
+                start = *previous_start;
+                units += previous_len;
+            }
+        }
+        if let Some(next_len) = self.free.remove(&(start + units)) {
+            units += next_len;
+        }
+        self.free.insert(start, units);
+    }
+
+    /// Return the total number of free units.
+    fn free_units(&self) -> u64 {
+        self.free.values().sum()
+    }
+}
+
+/// The configuration for opening a [`DeviceStore`].
+///
+/// The device must already be formatted with [`DeviceStore::format`].
+///
+/// [`DeviceStore`]: crate::store::DeviceStore
+/// [`DeviceStore::format`]: crate::store::DeviceStore::format
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-device")))]
+pub struct DeviceConfig {
+    /// The path of the block device or file.
+    pub path: PathBuf,
+}
+
+impl DeviceConfig {
+    /// Create a new `DeviceConfig` for the device at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl OpenStore for DeviceConfig {
+    type Store = DeviceStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        DeviceStore::open(&self.path)
+    }
+}
+
+/// A `DataStore` which stores data directly on a raw block device.
+///
+/// This store manages the space on a block device or a fixed-size file itself, without a file
+/// system, which is useful for embedded systems and appliances. The device must be formatted with
+/// [`format`] before it can be opened, which reserves space for a table of blocks and a journal and
+/// leaves the rest of the device for the contents of blocks.
+///
+/// Each change is written to the journal and synced to disk before it completes, so the store
+/// remains consistent if the process is interrupted. When the journal is full, the block table is
+/// rewritten to whichever of its two copies is older, so a table which was only partially
+/// written is never used.
+///
+/// Use [`check`] to look for problems on a device, like after a hardware failure, and [`repair`]
+/// to remove the blocks which are affected.
+///
+/// The device must not be open in more than one store at a time.
+///
+/// You can use [`DeviceConfig`] to open a data store of this type.
+///
+/// [`format`]: crate::store::DeviceStore::format
+/// [`check`]: crate::store::DeviceStore::check
+/// [`repair`]: crate::store::DeviceStore::repair
+/// [`DeviceConfig`]: crate::store::DeviceConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-device")))]
+pub struct DeviceStore {
+    device: File,
+    layout: Layout,
+    index: HashMap<BlockKey, Extent>,
+    allocator: Allocator,
+    generation: u64,
+    active_slot: usize,
+    journal_len: u64,
+}
+
+impl DeviceStore {
+    /// Format the block device or file at `path` with the given `format`.
+    ///
+    /// This destroys any existing data on the device. The whole device is used, so if `path` is a
+    /// file, it must already have the desired size.
+    ///
+    /// # Errors
+    /// - `Error::Io`: The device is too small for `format`, `format` is invalid, or an I/O error
+    /// occurred.
+    pub fn format(path: impl AsRef<Path>, format: &DeviceFormat) -> crate::Result<()> {
+        let mut device = OpenOptions::new().read(true).write(true).open(path)?;
+        let device_len = device.seek(SeekFrom::End(0))?;
+        let layout = Layout::new(device_len, format)?;
+
+        // Write the superblock last so that a device which was only partially formatted is never
+        // mistaken for a valid store.
+        write_table(&mut device, &layout, 0, 1, &HashMap::new())?;
+        device.seek(SeekFrom::Start(0))?;
+        device.write_all(&layout.encode())?;
+        device.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Open the store on the block device or file at `path`.
+    ///
+    /// # Errors
+    /// - `Error::InvalidStore`: The device is not formatted.
+    /// - `Error::UnsupportedStore`: The device was formatted with an incompatible version.
+    /// - `Error::Corrupt`: The metadata on the device is inconsistent. Use [`check`] and
+    /// [`repair`] to fix it.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`check`]: crate::store::DeviceStore::check
+    /// [`repair`]: crate::store::DeviceStore::repair
+    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let mut device = OpenOptions::new().read(true).write(true).open(path)?;
+        let metadata = Metadata::read(&mut device)?;
+        if !metadata.misplaced_blocks().is_empty() {
+            return Err(crate::Error::Corrupt);
+        }
+
+        Ok(DeviceStore {
+            device,
+            layout: metadata.layout,
+            allocator: metadata.allocator(),
+            index: metadata.index,
+            generation: metadata.generation,
+            active_slot: metadata.active_slot,
+            journal_len: metadata.journal_len,
+        })
+    }
+
+    /// Check the store on the block device or file at `path` for problems.
+    ///
+    /// This reads the contents of every block to verify its checksum, but doesn't change the
+    /// device. The store must not be open while this is running.
+    ///
+    /// # Errors
+    /// - `Error::InvalidStore`: The device is not formatted.
+    /// - `Error::UnsupportedStore`: The device was formatted with an incompatible version.
+    /// - `Error::Corrupt`: Neither copy of the block table is valid.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn check(path: impl AsRef<Path>) -> crate::Result<DeviceReport> {
+        let mut device = File::open(path)?;
+        let metadata = Metadata::read(&mut device)?;
+        check_metadata(&mut device, &metadata)
+    }
+
+    /// Check the store on the block device or file at `path` and remove any blocks with problems.
+    ///
+    /// Blocks which are misplaced or corrupt are removed from the block table, which makes their
+    /// space available again. This returns the problems which were found before they were fixed.
+    /// The store must not be open while this is running.
+    ///
+    /// # Errors
+    /// - `Error::InvalidStore`: The device is not formatted.
+    /// - `Error::UnsupportedStore`: The device was formatted with an incompatible version.
+    /// - `Error::Corrupt`: Neither copy of the block table is valid.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn repair(path: impl AsRef<Path>) -> crate::Result<DeviceReport> {
+        let mut device = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut metadata = Metadata::read(&mut device)?;
+        let report = check_metadata(&mut device, &metadata)?;
+
+        if !report.is_consistent() {
+            for key in report
+                .misplaced_blocks
+                .iter()
+                .chain(report.corrupt_blocks.iter())
+            {
+                metadata.index.remove(key);
+            }
+            write_table(
+                &mut device,
+                &metadata.layout,
+                1 - metadata.active_slot,
+                metadata.generation + 1,
+                &metadata.index,
+            )?;
+        }
+
+        Ok(report)
+    }
+
+    /// The number of bytes on the device which are available for the contents of blocks.
+    pub fn capacity(&self) -> u64 {
+        (self.layout.unit_count - self.layout.data_start()) * self.layout.unit_size
+    }
+
+    /// The number of bytes on the device which are available for new blocks.
+    pub fn free_space(&self) -> u64 {
+        self.allocator.free_units() * self.layout.unit_size
+    }
+
+    /// Read the contents of the block at `extent`.
+    fn read_extent(&mut self, extent: Extent) -> io::Result<Vec<u8>> {
+        read_extent(&mut self.device, &self.layout, extent)
+    }
+
+    /// Write `data` to the space at `extent` and sync it to disk.
+    fn write_extent(&mut self, extent: Extent, data: &[u8]) -> io::Result<()> {
+        self.device
+            .seek(SeekFrom::Start(extent.start * self.layout.unit_size))?;
+        self.device.write_all(data)?;
+        self.device.sync_data()
+    }
+
+    /// Record a change to the block with the given `key` in the journal.
+    ///
+    /// If `extent` is `None`, the block is removed. If the journal is full, the block table is
+    /// rewritten first.
+    fn log(&mut self, key: BlockKey, extent: Option<Extent>) -> io::Result<()> {
+        if self.journal_len >= self.layout.journal_capacity() {
+            self.checkpoint()?;
+        }
+
+        let mut journal_entry = Vec::with_capacity(JOURNAL_ENTRY_SIZE);
+        journal_entry.extend_from_slice(&self.generation.to_le_bytes());
+        journal_entry.extend_from_slice(&encode_entry(key, extent));
+        let checksum = self.layout.checksum(&[&journal_entry]);
+        journal_entry.extend_from_slice(&checksum.to_le_bytes());
+
+        let offset = self.layout.journal_offset() + self.journal_len * JOURNAL_ENTRY_SIZE as u64;
+        self.device.seek(SeekFrom::Start(offset))?;
+        self.device.write_all(&journal_entry)?;
+        self.device.sync_data()?;
+        self.journal_len += 1;
+
+        Ok(())
+    }
+
+    /// Write the current blocks to the older copy of the block table and clear the journal.
+    fn checkpoint(&mut self) -> io::Result<()> {
+        let slot = 1 - self.active_slot;
+        write_table(
+            &mut self.device,
+            &self.layout,
+            slot,
+            self.generation + 1,
+            &self.index,
+        )?;
+
+        // Entries in the journal from the previous generation are ignored, so there's no need to
+        // overwrite them.
+        self.active_slot = slot;
+        self.generation += 1;
+        self.journal_len = 0;
+
+        Ok(())
+    }
+}
+
+/// Read the contents of the block at `extent` from `device`.
+fn read_extent(device: &mut File, layout: &Layout, extent: Extent) -> io::Result<Vec<u8>> {
+    let mut data = vec![0u8; extent.len as usize];
+    device.seek(SeekFrom::Start(extent.start * layout.unit_size))?;
+    device.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Check the blocks in `metadata` for problems.
+fn check_metadata(device: &mut File, metadata: &Metadata) -> crate::Result<DeviceReport> {
+    let layout = &metadata.layout;
+    let misplaced_blocks = metadata.misplaced_blocks();
+
+    let mut corrupt_blocks = HashSet::new();
+    let mut used_units = 0;
+    for (key, extent) in &metadata.index {
+        if misplaced_blocks.contains(key) {
+            continue;
+        }
+        used_units += layout.units_for(extent.len);
+        let data = read_extent(device, layout, *extent)?;
+        if layout.checksum(&[&data]) != extent.checksum {
+            corrupt_blocks.insert(*key);
+        }
+    }
+
+    let data_units = layout.unit_count - layout.data_start();
+    Ok(DeviceReport {
+        blocks: metadata.index.len() as u64,
+        used_bytes: used_units * layout.unit_size,
+        free_bytes: (data_units - used_units) * layout.unit_size,
+        misplaced_blocks,
+        corrupt_blocks,
+    })
+}
+
+impl DataStore for DeviceStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        if !self.index.contains_key(&key) && self.index.len() as u64 >= self.layout.table_capacity()
+        {
+            return Err(super::Error::msg("The device cannot hold any more blocks."));
+        }
+
+        let units = self.layout.units_for(data.len() as u64);
+        let start = if units == 0 {
+            self.layout.data_start()
+        } else {
+            self.allocator
+                .allocate(units)
+                .ok_or_else(|| super::Error::msg("There is not enough free space on the device."))?
+        };
+        let extent = Extent {
+            start,
+            len: data.len() as u64,
+            checksum: self.layout.checksum(&[data]),
+        };
+
+        // The old contents of the block aren't freed until the change is in the journal, so they
+        // are never overwritten while they may still be read after a crash.
+        if let Err(error) = self
+            .write_extent(extent, data)
+            .and_then(|_| self.log(key, Some(extent)))
+        {
+            self.allocator.release(start, units);
+            return Err(error.into());
+        }
+        if let Some(old_extent) = self.index.insert(key, extent) {
+            self.allocator
+                .release(old_extent.start, self.layout.units_for(old_extent.len));
+        }
+
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        match self.index.get(&key).copied() {
+            Some(extent) => Ok(Some(self.read_extent(extent)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let extent = match self.index.get(&key).copied() {
+            Some(extent) => extent,
+            None => return Ok(()),
+        };
+        self.log(key, None)?;
+        self.index.remove(&key);
+        self.allocator
+            .release(extent.start, self.layout.units_for(extent.len));
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        Ok(self
+            .index
+            .keys()
+            .filter_map(|key| match (kind, key) {
+                (BlockType::Data, BlockKey::Data(id)) => Some(*id),
+                (BlockType::Lock, BlockKey::Lock(id)) => Some(*id),
+                (BlockType::Header, BlockKey::Header(id)) => Some(*id),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            max_block_size: Some(self.capacity()),
+            latency: LatencyClass::Local,
+            ..StoreCapabilities::default()
+        }
+    }
+}
@@ -1,12 +1,16 @@
 #![cfg(feature = "store-directory")]
 
-use std::fs::{create_dir_all, read_dir, remove_file, rename, File};
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::fs::{create_dir_all, metadata, read_dir, remove_file, rename, File};
+#[cfg(feature = "store-directory-mmap")]
+use std::io::Cursor;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use uuid::Uuid;
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, LatencyClass, StoreCapabilities};
 use super::open_store::OpenStore;
 
 /// A UUID which acts as the version ID of the directory store format.
@@ -17,6 +21,12 @@ const STORE_DIRECTORY: &str = "store";
 const STAGING_DIRECTORY: &str = "stage";
 const VERSION_FILE: &str = "version";
 
+/// The size in bytes at which blocks are memory mapped instead of read when memory mapping is on.
+///
+/// Mapping a file has a fixed cost which outweighs the cost of copying small blocks.
+#[cfg(feature = "store-directory-mmap")]
+const MMAP_THRESHOLD: u64 = 64 * 1024;
+
 fn type_path(kind: BlockType) -> PathBuf {
     match kind {
         BlockType::Data => [STORE_DIRECTORY, "data"].iter().collect(),
@@ -48,55 +58,96 @@ fn block_path(key: BlockKey) -> PathBuf {
 
 /// The configuration for opening a [`DirectoryStore`].
 ///
+/// Opening this config opens the store like [`DirectoryStore::open_or_create`], creating a new
+/// store if there is nothing at `path` or if `path` is an empty directory. If `read_only` is
+/// `true`, it opens the store like [`DirectoryStore::open_read_only`] instead. If `memory_map` is
+/// `true`, the store reads blocks like [`DirectoryStore::set_memory_map`] describes.
+///
+/// This struct is non-exhaustive so that options can be added without breaking changes. Create it
+/// with [`DirectoryConfig::new`] or [`DirectoryConfig::new_read_only`] and then set any other
+/// fields you need.
+///
 /// [`DirectoryStore`]: crate::store::DirectoryStore
+/// [`DirectoryConfig::new`]: crate::store::DirectoryConfig::new
+/// [`DirectoryConfig::new_read_only`]: crate::store::DirectoryConfig::new_read_only
+/// [`DirectoryStore::open_or_create`]: crate::store::DirectoryStore::open_or_create
+/// [`DirectoryStore::open_read_only`]: crate::store::DirectoryStore::open_read_only
+/// [`DirectoryStore::set_memory_map`]: crate::store::DirectoryStore::set_memory_map
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(docsrs, doc(cfg(feature = "store-directory")))]
+#[non_exhaustive]
 pub struct DirectoryConfig {
     /// The path of the directory store.
     pub path: PathBuf,
+
+    /// Whether to open an existing store without ever modifying it.
+    pub read_only: bool,
+
+    /// Whether to read large blocks by mapping them into memory.
+    ///
+    /// This is ignored unless the `store-directory-mmap` feature is enabled.
+    pub memory_map: bool,
+}
+
+impl DirectoryConfig {
+    /// Create a new `DirectoryConfig` for a directory store at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            read_only: false,
+            memory_map: false,
+        }
+    }
+
+    /// Create a new `DirectoryConfig` for an existing directory store at `path` which is opened
+    /// read-only.
+    pub fn new_read_only(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            read_only: true,
+            memory_map: false,
+        }
+    }
 }
 
 impl OpenStore for DirectoryConfig {
     type Store = DirectoryStore;
 
     fn open(&self) -> crate::Result<Self::Store> {
-        create_dir_all(&self.path)
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-        create_dir_all(self.path.join(STORE_DIRECTORY))
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-        create_dir_all(self.path.join(STAGING_DIRECTORY))
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-        create_dir_all(self.path.join(type_path(BlockType::Data)))
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-        create_dir_all(self.path.join(type_path(BlockType::Lock)))
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-        create_dir_all(self.path.join(type_path(BlockType::Header)))
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-
-        let version_path = self.path.join(VERSION_FILE);
-
-        if version_path.exists() {
-            // Read the version ID file.
-            let mut version_file = File::open(&version_path)
-                .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-            let mut version_id = String::new();
-            version_file.read_to_string(&mut version_id)?;
-
-            // Verify the version ID.
-            if version_id != CURRENT_VERSION {
-                return Err(crate::Error::UnsupportedStore);
-            }
+        let mut store = if self.read_only {
+            DirectoryStore::open_read_only(&self.path)?
         } else {
-            // Write the version ID file.
-            let mut version_file = File::create(&version_path)
-                .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-            version_file.write_all(CURRENT_VERSION.as_bytes())?;
-        }
+            DirectoryStore::open_or_create(&self.path)?
+        };
+        store.set_memory_map(self.memory_map);
+        Ok(store)
+    }
+}
 
-        Ok(DirectoryStore {
-            path: self.path.clone(),
-        })
+/// Create the directories used by the store at `path` if they don't already exist.
+fn create_store_directories(path: &Path) -> crate::Result<()> {
+    for directory in [
+        PathBuf::from(STORE_DIRECTORY),
+        PathBuf::from(STAGING_DIRECTORY),
+        type_path(BlockType::Data),
+        type_path(BlockType::Lock),
+        type_path(BlockType::Header),
+    ] {
+        create_dir_all(path.join(directory))
+            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
     }
+    Ok(())
+}
+
+/// Return whether there is nothing at `path` or `path` is an empty directory.
+fn is_missing_or_empty(path: &Path) -> crate::Result<bool> {
+    if !path.exists() {
+        return Ok(true);
+    }
+    if !path.is_dir() {
+        return Ok(false);
+    }
+    Ok(read_dir(path)?.next().is_none())
 }
 
 /// A `DataStore` which stores data in a directory in the local file system.
@@ -109,9 +160,165 @@ impl OpenStore for DirectoryConfig {
 pub struct DirectoryStore {
     /// The path of the store's root directory.
     path: PathBuf,
+
+    /// Whether this store refuses to modify any files.
+    read_only: bool,
+
+    /// Whether to read large blocks by mapping them into memory.
+    memory_map: bool,
+
+    /// The paths of the blocks which have been written since the store was last synced.
+    unsynced_blocks: HashSet<PathBuf>,
+
+    /// The directories whose entries have changed since the store was last synced.
+    unsynced_directories: HashSet<PathBuf>,
 }
 
 impl DirectoryStore {
+    /// Open the existing directory store at `path`.
+    ///
+    /// A directory store is identified by a version file in its root directory, which is written
+    /// when the store is created and records the layout of the store.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is nothing at `path`.
+    /// - `Error::InvalidStore`: There is a file or directory at `path`, but it is not a directory
+    /// store.
+    /// - `Error::UnsupportedStore`: The directory store uses an incompatible layout version.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::open_existing(path.as_ref(), false)
+    }
+
+    /// Open the existing directory store at `path` without ever modifying it.
+    ///
+    /// This never creates, modifies, or removes any files or directories, so it can be used to read
+    /// a store on a read-only file system, like a snapshot or a recovery mount. Attempting to
+    /// write to or remove blocks from the returned store fails with `Error::ReadOnly`.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is nothing at `path`.
+    /// - `Error::InvalidStore`: There is a file or directory at `path`, but it is not a directory
+    /// store.
+    /// - `Error::UnsupportedStore`: The directory store uses an incompatible layout version.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn open_read_only(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::open_existing(path.as_ref(), true)
+    }
+
+    /// Open the existing directory store at `path`.
+    fn open_existing(path: &Path, read_only: bool) -> crate::Result<Self> {
+        if !path.exists() {
+            return Err(crate::Error::NotFound);
+        }
+
+        let version_path = path.join(VERSION_FILE);
+        if !version_path.is_file() {
+            return Err(crate::Error::InvalidStore);
+        }
+
+        // Read and verify the version ID.
+        let mut version_file = File::open(&version_path)?;
+        let mut version_id = String::new();
+        version_file.read_to_string(&mut version_id)?;
+        if version_id != CURRENT_VERSION {
+            return Err(crate::Error::UnsupportedStore);
+        }
+
+        // Some directories may have been removed if they were empty, like when the store was
+        // copied with a tool that doesn't preserve empty directories. A read-only store can't
+        // create them, so it treats missing directories as empty instead.
+        if !read_only {
+            create_store_directories(path)?;
+        }
+
+        Ok(DirectoryStore {
+            path: path.to_path_buf(),
+            read_only,
+            memory_map: false,
+            unsynced_blocks: HashSet::new(),
+            unsynced_directories: HashSet::new(),
+        })
+    }
+
+    /// Create a new directory store at `path`.
+    ///
+    /// The directory at `path` and its parents are created if they don't exist. If `path` is an
+    /// existing empty directory, the store is created in it.
+    ///
+    /// # Errors
+    /// - `Error::AlreadyExists`: There is already a file or non-empty directory at `path`.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn create_new(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+
+        if !is_missing_or_empty(path)? {
+            return Err(crate::Error::AlreadyExists);
+        }
+
+        create_store_directories(path)?;
+
+        // Write the version ID file last so that a partially created store is not mistaken for a
+        // valid one.
+        let mut version_file = File::create(path.join(VERSION_FILE))
+            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+        version_file.write_all(CURRENT_VERSION.as_bytes())?;
+
+        Ok(DirectoryStore {
+            path: path.to_path_buf(),
+            read_only: false,
+            memory_map: false,
+            unsynced_blocks: HashSet::new(),
+            unsynced_directories: HashSet::new(),
+        })
+    }
+
+    /// Open the directory store at `path`, creating it if it doesn't exist.
+    ///
+    /// A new store is created if there is nothing at `path` or if `path` is an empty directory.
+    /// Otherwise, this opens the existing store like [`open`].
+    ///
+    /// # Errors
+    /// - `Error::InvalidStore`: There is a file or non-empty directory at `path`, but it is not a
+    /// directory store.
+    /// - `Error::UnsupportedStore`: The directory store uses an incompatible layout version.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`open`]: crate::store::DirectoryStore::open
+    pub fn open_or_create(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        if is_missing_or_empty(path)? {
+            Self::create_new(path)
+        } else {
+            Self::open(path)
+        }
+    }
+
+    /// Whether this store reads large blocks by mapping them into memory.
+    ///
+    /// This is always `false` unless the `store-directory-mmap` feature is enabled.
+    pub fn memory_map(&self) -> bool {
+        self.memory_map && cfg!(feature = "store-directory-mmap")
+    }
+
+    /// Set whether this store reads large blocks by mapping them into memory.
+    ///
+    /// Memory mapping avoids copying the contents of large blocks through an intermediate buffer,
+    /// which can greatly improve read throughput on fast local storage. Small blocks are always
+    /// read normally.
+    ///
+    /// Blocks are never modified in place, so a mapped block doesn't change while it's being read.
+    /// However, if another program truncates or modifies a block file while it's mapped, the
+    /// process may crash, so only turn this on if nothing else modifies the store.
+    ///
+    /// This does nothing unless the `store-directory-mmap` feature is enabled.
+    pub fn set_memory_map(&mut self, memory_map: bool) {
+        self.memory_map = memory_map;
+    }
+
     /// Return the path where a block with the given `key` will be stored.
     fn block_path(&self, key: BlockKey) -> PathBuf {
         self.path.join(block_path(key))
@@ -122,20 +329,59 @@ impl DirectoryStore {
         let uuid_str = Uuid::new_v4().as_hyphenated().to_string();
         self.path.join(STAGING_DIRECTORY).join(uuid_str)
     }
+
+    /// Return an error if this store was opened read-only.
+    fn check_writable(&self) -> super::Result<()> {
+        if self.read_only {
+            Err(super::Error::new(crate::Error::ReadOnly))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "store-directory-mmap")]
+impl DirectoryStore {
+    /// Map the block `file` into memory if memory mapping is on and the block is large enough.
+    fn map_block(&self, file: &File) -> io::Result<Option<memmap2::Mmap>> {
+        if !self.memory_map || file.metadata()?.len() < MMAP_THRESHOLD {
+            return Ok(None);
+        }
+        map_file(file).map(Some)
+    }
+}
+
+/// Map the contents of `file` into memory.
+#[cfg(feature = "store-directory-mmap")]
+#[allow(unsafe_code)]
+fn map_file(file: &File) -> io::Result<memmap2::Mmap> {
+    // SAFETY: Block files are written to a staging file which is atomically renamed into place,
+    // so they're never modified while they're mapped unless another program modifies the store,
+    // which is documented in `DirectoryStore::set_memory_map`.
+    unsafe { memmap2::Mmap::map(file) }
 }
 
 impl DataStore for DirectoryStore {
     fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.check_writable()?;
+
         let staging_path = self.staging_path();
         let block_path = self.block_path(key);
+        let block_directory = block_path.parent().unwrap().to_path_buf();
 
         // If this is the first block its sub-directory, the directory needs to be created.
-        create_dir_all(block_path.parent().unwrap())?;
+        if !block_directory.exists() {
+            create_dir_all(&block_directory)?;
+            self.unsynced_directories
+                .insert(block_directory.parent().unwrap().to_path_buf());
+        }
 
         // Write to a staging file and then atomically move it to its final destination.
         let mut staging_file = File::create(&staging_path)?;
         staging_file.write_all(data)?;
         rename(&staging_path, &block_path)?;
+        self.unsynced_blocks.insert(block_path);
+        self.unsynced_directories.insert(block_directory);
 
         // Remove any unused staging files.
         for entry in read_dir(self.path.join(STAGING_DIRECTORY))? {
@@ -150,6 +396,10 @@ impl DataStore for DirectoryStore {
 
         if block_path.exists() {
             let mut file = File::open(block_path)?;
+            #[cfg(feature = "store-directory-mmap")]
+            if let Some(map) = self.map_block(&file)? {
+                return Ok(Some(map.to_vec()));
+            }
             let mut buffer = Vec::with_capacity(file.metadata()?.len() as usize);
             file.read_to_end(&mut buffer)?;
             Ok(Some(buffer))
@@ -158,11 +408,31 @@ impl DataStore for DirectoryStore {
         }
     }
 
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        let block_path = self.block_path(key);
+
+        if block_path.exists() {
+            let file = File::open(block_path)?;
+            #[cfg(feature = "store-directory-mmap")]
+            if let Some(map) = self.map_block(&file)? {
+                return Ok(Some(Box::new(Cursor::new(map))));
+            }
+            Ok(Some(Box::new(file)))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.check_writable()?;
+
         let block_path = self.block_path(key);
 
         if block_path.exists() {
-            remove_file(self.block_path(key))?;
+            remove_file(&block_path)?;
+            self.unsynced_directories
+                .insert(block_path.parent().unwrap().to_path_buf());
+            self.unsynced_blocks.remove(&block_path);
         }
 
         Ok(())
@@ -171,6 +441,11 @@ impl DataStore for DirectoryStore {
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
         let mut block_ids = Vec::new();
 
+        // A read-only store may be missing directories which were empty.
+        if !self.path.join(type_path(kind)).exists() {
+            return Ok(block_ids);
+        }
+
         match kind {
             BlockType::Data => {
                 for directory_entry in read_dir(self.path.join(type_path(kind)))? {
@@ -204,4 +479,54 @@ impl DataStore for DirectoryStore {
 
         Ok(block_ids)
     }
+
+    fn sync(&mut self) -> super::Result<()> {
+        for block_path in &self.unsynced_blocks {
+            // The block may have been replaced or removed by another store since it was written.
+            match File::open(block_path) {
+                Ok(block_file) => block_file.sync_all()?,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        self.unsynced_blocks.clear();
+
+        // Creating, renaming, or removing a file is only durable once its directory is synced.
+        // Directories can't be opened as files on every platform.
+        #[cfg(unix)]
+        for directory in &self.unsynced_directories {
+            File::open(directory)?.sync_all()?;
+        }
+        self.unsynced_directories.clear();
+
+        Ok(())
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        match metadata(self.block_path(key)) {
+            Ok(block_metadata) => Ok(block_metadata.modified().ok()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        match metadata(self.block_path(key)) {
+            Ok(block_metadata) => Ok(Some(block_metadata.len())),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            latency: LatencyClass::Local,
+            writable: !self.read_only,
+            ..StoreCapabilities::default()
+        }
+    }
 }
@@ -0,0 +1,389 @@
+#![cfg(feature = "store-dropbox")]
+
+use std::io::Read;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use ureq::{AgentBuilder, Response};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::oauth::{OAuthCredentials, TokenSource};
+use super::open_store::OpenStore;
+use super::rest::{check_response, join_key, SEPARATOR};
+
+// The names of files in the data store.
+const STORE_KEY: &str = "store";
+const DATA_KEY: &str = "data";
+const LOCKS_KEY: &str = "lock";
+const HEADERS_KEY: &str = "header";
+const SUPER_KEY: &str = "super";
+const REPO_VERSION_KEY: &str = "version";
+const STORE_VERSION_KEY: &str = "version";
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("5c2e8b71-4a9d-4f3e-b0c6-9e1d7a3f5b28");
+
+/// The URL of the endpoint for refreshing access tokens.
+const TOKEN_URL: &str = "https://api.dropboxapi.com/oauth2/token";
+
+/// The base URL for RPC endpoints, which take and return JSON.
+const API_URL: &str = "https://api.dropboxapi.com/2";
+
+/// The base URL for content endpoints, which upload and download file contents.
+const CONTENT_URL: &str = "https://content.dropboxapi.com/2";
+
+/// The HTTP status code Dropbox uses for errors specific to an endpoint, like a missing file.
+const ENDPOINT_ERROR_CODE: u16 = 409;
+
+/// The largest file in bytes which can be uploaded in a single request.
+const UPLOAD_LIMIT: usize = 150 * 1024 * 1024;
+
+/// The size in bytes of each request when uploading a large file in an upload session.
+const SESSION_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+/// The name of the service to use in error messages.
+const SERVICE_NAME: &str = "Dropbox";
+
+/// An error specific to a Dropbox endpoint.
+#[derive(Debug, Deserialize)]
+struct EndpointError {
+    error_summary: String,
+}
+
+/// A page of the response to listing a folder.
+#[derive(Debug, Deserialize)]
+struct FolderListing {
+    entries: Vec<FolderEntry>,
+    cursor: String,
+    has_more: bool,
+}
+
+/// An entry in a `FolderListing`.
+#[derive(Debug, Deserialize)]
+struct FolderEntry {
+    #[serde(rename = ".tag")]
+    tag: String,
+    name: String,
+}
+
+/// The response to starting an upload session.
+#[derive(Debug, Deserialize)]
+struct UploadSession {
+    session_id: String,
+}
+
+/// The configuration for opening a [`DropboxStore`].
+///
+/// [`DropboxStore`]: crate::store::DropboxStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-dropbox")))]
+pub struct DropboxConfig {
+    /// The credentials for accessing the user's Dropbox account.
+    pub credentials: OAuthCredentials,
+
+    /// The path of the folder in the user's Dropbox to store blocks in.
+    ///
+    /// If your app has app folder access, this is relative to the app folder.
+    pub root: String,
+}
+
+impl DropboxConfig {
+    /// Return a new config for a store in the folder at `root`.
+    pub fn new(credentials: OAuthCredentials, root: impl Into<String>) -> Self {
+        DropboxConfig {
+            credentials,
+            root: root.into(),
+        }
+    }
+}
+
+impl OpenStore for DropboxConfig {
+    type Store = DropboxStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let agent = AgentBuilder::new().build();
+        let mut store = DropboxStore {
+            tokens: TokenSource::new(agent, SERVICE_NAME, TOKEN_URL, self.credentials.clone()),
+            root: self.root.trim_matches('/').to_owned(),
+        };
+
+        let version_path = store.path(&[STORE_VERSION_KEY]);
+        match store.get(&version_path).map_err(crate::Error::Store)? {
+            None => store
+                .put(&version_path, CURRENT_VERSION.as_bytes())
+                .map_err(crate::Error::Store)?,
+            Some(version) => {
+                let version =
+                    Uuid::from_slice(&version).map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// A `DataStore` which stores data in a user's Dropbox.
+///
+/// Blocks are stored as files in a folder in the user's Dropbox using the Dropbox HTTP API, which
+/// is authorized with [`OAuthCredentials`]. Uploads are atomic, so blocks are written in place.
+/// Blocks which are too large to upload in a single request are uploaded in an upload session.
+///
+/// You can use [`DropboxConfig`] to open a data store of this type.
+///
+/// [`OAuthCredentials`]: crate::store::OAuthCredentials
+/// [`DropboxConfig`]: crate::store::DropboxConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-dropbox")))]
+pub struct DropboxStore {
+    tokens: TokenSource,
+    root: String,
+}
+
+/// Serialize `value` for the `Dropbox-API-Arg` header, which must only contain ASCII characters.
+fn api_arg(value: &Value) -> String {
+    let mut encoded = String::new();
+    for character in value.to_string().chars() {
+        if character.is_ascii() {
+            encoded.push(character);
+        } else {
+            for unit in character.encode_utf16(&mut [0; 2]) {
+                encoded.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    encoded
+}
+
+/// Convert the `result` of a request into a store result.
+///
+/// This returns `None` if the file or folder does not exist.
+fn check_endpoint_response(
+    result: Result<Response, ureq::Error>,
+) -> super::Result<Option<Response>> {
+    match result {
+        Err(ureq::Error::Status(ENDPOINT_ERROR_CODE, response)) => {
+            let error: EndpointError = serde_json::from_reader(response.into_reader())?;
+            if error.error_summary.contains("not_found") {
+                Ok(None)
+            } else {
+                Err(super::Error::msg(format!(
+                    "{} request failed: {}",
+                    SERVICE_NAME, error.error_summary
+                )))
+            }
+        }
+        result => check_response(SERVICE_NAME, result),
+    }
+}
+
+impl DropboxStore {
+    /// Return the Dropbox path of the file with the given path `segments` under the root.
+    fn path(&self, segments: &[&str]) -> String {
+        let mut all_segments = vec![self.root.as_str()];
+        all_segments.extend_from_slice(segments);
+        format!("{}{}", SEPARATOR, join_key(&all_segments))
+    }
+
+    /// Return the Dropbox path of the file for the block with the given `key`.
+    fn block_path(&self, key: BlockKey) -> String {
+        match key {
+            BlockKey::Data(id) => self.path(&[
+                STORE_KEY,
+                DATA_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Lock(id) => self.path(&[
+                STORE_KEY,
+                LOCKS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Header(id) => self.path(&[
+                STORE_KEY,
+                HEADERS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Super => self.path(&[STORE_KEY, SUPER_KEY]),
+            BlockKey::Version => self.path(&[STORE_KEY, REPO_VERSION_KEY]),
+        }
+    }
+
+    /// Call the RPC `endpoint` with the given JSON `body`.
+    ///
+    /// This returns `None` if the file or folder does not exist.
+    fn rpc(&mut self, endpoint: &str, body: Value) -> super::Result<Option<Response>> {
+        let url = format!("{}/{}", API_URL, endpoint);
+        let body = body.to_string();
+        check_endpoint_response(self.tokens.send(|agent, authorization| {
+            agent
+                .post(&url)
+                .set("Authorization", authorization)
+                .set("Content-Type", "application/json")
+                .send_string(&body)
+        })?)
+    }
+
+    /// Call the content `endpoint` with the given `arg` and uploaded `data`, if any.
+    ///
+    /// This returns `None` if the file does not exist.
+    fn content(
+        &mut self,
+        endpoint: &str,
+        arg: Value,
+        data: Option<&[u8]>,
+    ) -> super::Result<Option<Response>> {
+        let url = format!("{}/{}", CONTENT_URL, endpoint);
+        let arg = api_arg(&arg);
+        check_endpoint_response(self.tokens.send(|agent, authorization| {
+            let request = agent
+                .post(&url)
+                .set("Authorization", authorization)
+                .set("Dropbox-API-Arg", &arg);
+            match data {
+                Some(data) => request
+                    .set("Content-Type", "application/octet-stream")
+                    .send_bytes(data),
+                None => request.call(),
+            }
+        })?)
+    }
+
+    /// Return a response with the contents of the file at `path`.
+    ///
+    /// This returns `None` if the file does not exist.
+    fn download(&mut self, path: &str) -> super::Result<Option<Response>> {
+        self.content("files/download", json!({ "path": path }), None)
+    }
+
+    /// Return the contents of the file at `path` or `None` if it does not exist.
+    fn get(&mut self, path: &str) -> super::Result<Option<Vec<u8>>> {
+        match self.download(path)? {
+            Some(response) => {
+                let mut data = Vec::new();
+                response.into_reader().read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Upload `data` to the file at `path`, replacing it if it exists.
+    fn put(&mut self, path: &str, data: &[u8]) -> super::Result<()> {
+        let commit = json!({ "path": path, "mode": "overwrite", "mute": true });
+
+        if data.len() <= UPLOAD_LIMIT {
+            self.content("files/upload", commit, Some(data))?
+                .ok_or_else(|| super::Error::msg("The parent folder does not exist."))?;
+            return Ok(());
+        }
+
+        let response = self
+            .content(
+                "files/upload_session/start",
+                json!({ "close": false }),
+                Some(&[][..]),
+            )?
+            .ok_or_else(|| super::Error::msg("Could not start an upload session."))?;
+        let session: UploadSession = serde_json::from_reader(response.into_reader())?;
+
+        let mut offset = 0;
+        for chunk in data.chunks(SESSION_CHUNK_SIZE) {
+            self.content(
+                "files/upload_session/append_v2",
+                json!({
+                    "cursor": { "session_id": session.session_id, "offset": offset },
+                    "close": false,
+                }),
+                Some(chunk),
+            )?
+            .ok_or_else(|| super::Error::msg("The upload session does not exist."))?;
+            offset += chunk.len();
+        }
+
+        self.content(
+            "files/upload_session/finish",
+            json!({
+                "cursor": { "session_id": session.session_id, "offset": offset },
+                "commit": commit,
+            }),
+            Some(&[][..]),
+        )?
+        .ok_or_else(|| super::Error::msg("The upload session does not exist."))?;
+
+        Ok(())
+    }
+
+    /// Remove the file at `path` if it exists.
+    fn delete(&mut self, path: &str) -> super::Result<()> {
+        self.rpc("files/delete_v2", json!({ "path": path }))?;
+        Ok(())
+    }
+
+    /// Return the names of the files in the folder at `path`.
+    fn list(&mut self, path: &str) -> super::Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut response = self.rpc("files/list_folder", json!({ "path": path }))?;
+
+        while let Some(page) = response {
+            let listing: FolderListing = serde_json::from_reader(page.into_reader())?;
+            names.extend(
+                listing
+                    .entries
+                    .into_iter()
+                    .filter(|entry| entry.tag == "file")
+                    .map(|entry| entry.name),
+            );
+            response = if listing.has_more {
+                self.rpc(
+                    "files/list_folder/continue",
+                    json!({ "cursor": listing.cursor }),
+                )?
+            } else {
+                None
+            };
+        }
+
+        Ok(names)
+    }
+}
+
+impl DataStore for DropboxStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let block_path = self.block_path(key);
+        self.put(&block_path, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let block_path = self.block_path(key);
+        self.get(&block_path)
+    }
+
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        let block_path = self.block_path(key);
+        Ok(self
+            .download(&block_path)?
+            .map(|response| Box::new(response.into_reader()) as Box<dyn Read>))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let block_path = self.block_path(key);
+        self.delete(&block_path)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let folder_path = match kind {
+            BlockType::Data => self.path(&[STORE_KEY, DATA_KEY]),
+            BlockType::Lock => self.path(&[STORE_KEY, LOCKS_KEY]),
+            BlockType::Header => self.path(&[STORE_KEY, HEADERS_KEY]),
+        };
+        let block_ids = self
+            .list(&folder_path)?
+            .iter()
+            .map(|name| Uuid::parse_str(name).map(|id| id.into()))
+            .collect::<Result<Vec<BlockId>, _>>()?;
+        Ok(block_ids)
+    }
+}
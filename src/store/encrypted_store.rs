@@ -0,0 +1,372 @@
+#![cfg(feature = "encryption")]
+
+use std::fmt::{self, Debug, Formatter};
+use std::time::{Duration, SystemTime};
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use sodiumoxide::crypto::aead::xchacha20poly1305_ietf::{
+    gen_nonce, open, seal, Key as ChaChaKey, Nonce, ABYTES, KEYBYTES, NONCEBYTES,
+};
+use sodiumoxide::crypto::generichash;
+use uuid::Uuid;
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// The number of rounds of the Feistel network used to obfuscate block IDs.
+const ID_ROUNDS: u8 = 4;
+
+/// The size of the hash used as the round function of the Feistel network.
+///
+/// This is the smallest size supported by BLAKE2b, and only the first half of it is used.
+const ID_HASH_SIZE: usize = 16;
+
+/// A secret key used by an [`EncryptedStore`].
+///
+/// The bytes of the key are zeroed in memory when this value is dropped.
+///
+/// [`EncryptedStore`]: crate::store::EncryptedStore
+#[cfg_attr(docsrs, doc(cfg(feature = "encryption")))]
+pub struct StoreKey(Secret<[u8; StoreKey::SIZE]>);
+
+impl StoreKey {
+    /// The size of a key in bytes.
+    pub const SIZE: usize = KEYBYTES;
+
+    /// Create a key containing the given `bytes`.
+    pub fn new(bytes: [u8; Self::SIZE]) -> Self {
+        Self(Secret::new(bytes))
+    }
+
+    /// Generate a new random key.
+    ///
+    /// This uses bytes retrieved from the operating system's cryptographically secure random number
+    /// generator.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; Self::SIZE];
+        OsRng.fill_bytes(&mut bytes);
+        Self::new(bytes)
+    }
+
+    /// The bytes of this key.
+    ///
+    /// These must be stored somewhere safe to be able to read the store again.
+    pub fn expose_bytes(&self) -> &[u8; Self::SIZE] {
+        self.0.expose_secret()
+    }
+}
+
+impl Clone for StoreKey {
+    fn clone(&self) -> Self {
+        Self::new(*self.expose_bytes())
+    }
+}
+
+impl Debug for StoreKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("StoreKey([REDACTED])")
+    }
+}
+
+/// The configuration for opening an [`EncryptedStore`].
+///
+/// [`EncryptedStore`]: crate::store::EncryptedStore
+#[derive(Debug, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "encryption")))]
+pub struct EncryptedConfig<C: OpenStore> {
+    /// The configuration for the data store to write encrypted blocks to.
+    pub inner: C,
+
+    /// The key to encrypt blocks with.
+    pub key: StoreKey,
+
+    /// Whether to obfuscate the IDs of blocks in the inner data store.
+    pub obfuscate_ids: bool,
+}
+
+impl<C: OpenStore> OpenStore for EncryptedConfig<C> {
+    type Store = EncryptedStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(EncryptedStore::new(
+            self.inner.open()?,
+            self.key.clone(),
+            self.obfuscate_ids,
+        ))
+    }
+}
+
+/// A `DataStore` which encrypts blocks before writing them to another data store.
+///
+/// This encrypts the contents of every block with XChaCha20-Poly1305 using a [`StoreKey`], which is
+/// independent of any encryption done by the repository. This allows for adding a layer of
+/// encryption with a key owned by someone else, like the administrator of the storage. Each block
+/// is authenticated along with its key, so blocks can't be swapped or modified in the inner data
+/// store without reads failing.
+///
+/// If `obfuscate_ids` is `true`, the IDs of blocks are also encrypted before they're passed to the
+/// inner data store, so the inner data store can't correlate them with the block IDs used by
+/// repositories. A store must always be opened with the same value of `obfuscate_ids`.
+///
+/// Reading a block which wasn't encrypted with the same key returns an error wrapping
+/// `Error::InvalidData`.
+///
+/// You can use [`EncryptedConfig`] to open a data store of this type.
+///
+/// [`StoreKey`]: crate::store::StoreKey
+/// [`EncryptedConfig`]: crate::store::EncryptedConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "encryption")))]
+pub struct EncryptedStore<S: DataStore> {
+    inner: S,
+    key: StoreKey,
+    obfuscate_ids: bool,
+}
+
+impl<S: DataStore> EncryptedStore<S> {
+    /// Encrypt blocks written to the given `inner` data store with the given `key`.
+    ///
+    /// If `obfuscate_ids` is `true`, the IDs of blocks are encrypted as well.
+    pub fn new(inner: S, key: StoreKey, obfuscate_ids: bool) -> Self {
+        sodiumoxide::init().expect("Failed to initialize encryption.");
+        Self {
+            inner,
+            key,
+            obfuscate_ids,
+        }
+    }
+
+    /// The data store which encrypted blocks are written to.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Return the output of the round function of the Feistel network for the given `round`.
+    fn round_function(&self, round: u8, half: &[u8]) -> [u8; 8] {
+        let mut state =
+            generichash::State::new(Some(ID_HASH_SIZE), Some(&self.key.expose_bytes()[..]))
+                .expect("Failed to hash block ID.");
+        state.update(b"acid-store block ID").unwrap();
+        state.update(&[round]).unwrap();
+        state.update(half).unwrap();
+        let digest = state.finalize().expect("Failed to hash block ID.");
+        let mut output = [0u8; 8];
+        output.copy_from_slice(&digest.as_ref()[..8]);
+        output
+    }
+
+    /// Return the ID which the block with the given `id` has in the inner data store.
+    fn encrypt_id(&self, id: BlockId) -> BlockId {
+        if !self.obfuscate_ids {
+            return id;
+        }
+
+        // A Feistel network is a keyed permutation, so we can recover the original ID when
+        // listing blocks.
+        let bytes = id.as_ref().as_bytes();
+        let (mut left, mut right) = split(bytes);
+        for round in 0..ID_ROUNDS {
+            let output = self.round_function(round, &right);
+            let new_right = xor(left, output);
+            left = right;
+            right = new_right;
+        }
+        join(left, right)
+    }
+
+    /// Return the ID of the block which has the given `id` in the inner data store.
+    fn decrypt_id(&self, id: BlockId) -> BlockId {
+        if !self.obfuscate_ids {
+            return id;
+        }
+
+        let bytes = id.as_ref().as_bytes();
+        let (mut left, mut right) = split(bytes);
+        for round in (0..ID_ROUNDS).rev() {
+            let output = self.round_function(round, &left);
+            let new_left = xor(right, output);
+            right = left;
+            left = new_left;
+        }
+        join(left, right)
+    }
+
+    /// Return the key which the block with the given `key` has in the inner data store.
+    fn inner_key(&self, key: BlockKey) -> BlockKey {
+        match key {
+            BlockKey::Data(id) => BlockKey::Data(self.encrypt_id(id)),
+            BlockKey::Lock(id) => BlockKey::Lock(self.encrypt_id(id)),
+            BlockKey::Header(id) => BlockKey::Header(self.encrypt_id(id)),
+            BlockKey::Super => BlockKey::Super,
+            BlockKey::Version => BlockKey::Version,
+        }
+    }
+
+    /// Encrypt the contents of the block with the given `key`.
+    fn encrypt(&self, key: BlockKey, data: &[u8]) -> Vec<u8> {
+        let nonce = gen_nonce();
+        let chacha_key = ChaChaKey::from_slice(self.key.expose_bytes()).unwrap();
+        let ciphertext = seal(data, Some(&associated_data(key)), &nonce, &chacha_key);
+        let mut output = nonce.as_ref().to_vec();
+        output.extend_from_slice(&ciphertext);
+        output
+    }
+
+    /// Decrypt the contents of the block with the given `key`.
+    fn decrypt(&self, key: BlockKey, data: &[u8]) -> super::Result<Vec<u8>> {
+        let invalid = || super::Error::new(crate::Error::InvalidData);
+        let nonce = data
+            .get(..NONCEBYTES)
+            .and_then(Nonce::from_slice)
+            .ok_or_else(invalid)?;
+        let chacha_key = ChaChaKey::from_slice(self.key.expose_bytes()).unwrap();
+        open(
+            &data[NONCEBYTES..],
+            Some(&associated_data(key)),
+            &nonce,
+            &chacha_key,
+        )
+        .map_err(|_| invalid())
+    }
+}
+
+/// Return the associated data used to authenticate the block with the given `key`.
+fn associated_data(key: BlockKey) -> Vec<u8> {
+    let (tag, id) = match key {
+        BlockKey::Data(id) => (0u8, Some(id)),
+        BlockKey::Lock(id) => (1, Some(id)),
+        BlockKey::Header(id) => (2, Some(id)),
+        BlockKey::Super => (3, None),
+        BlockKey::Version => (4, None),
+    };
+    let mut data = vec![tag];
+    if let Some(id) = id {
+        data.extend_from_slice(id.as_ref().as_bytes());
+    }
+    data
+}
+
+/// Split the bytes of a UUID into two halves.
+fn split(bytes: &[u8; 16]) -> ([u8; 8], [u8; 8]) {
+    let mut left = [0u8; 8];
+    let mut right = [0u8; 8];
+    left.copy_from_slice(&bytes[..8]);
+    right.copy_from_slice(&bytes[8..]);
+    (left, right)
+}
+
+/// Join two halves into a block ID.
+fn join(left: [u8; 8], right: [u8; 8]) -> BlockId {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&left);
+    bytes[8..].copy_from_slice(&right);
+    BlockId::from(Uuid::from_bytes(bytes))
+}
+
+/// Return the bitwise XOR of `left` and `right`.
+fn xor(mut left: [u8; 8], right: [u8; 8]) -> [u8; 8] {
+    for (left, right) in left.iter_mut().zip(right) {
+        *left ^= right;
+    }
+    left
+}
+
+impl<S: DataStore> DataStore for EncryptedStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let ciphertext = self.encrypt(key, data);
+        self.inner.write_block(self.inner_key(key), &ciphertext)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        match self.inner.read_block(self.inner_key(key))? {
+            Some(ciphertext) => Ok(Some(self.decrypt(key, &ciphertext)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.inner.remove_block(self.inner_key(key))
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        Ok(self
+            .inner
+            .list_blocks(kind)?
+            .into_iter()
+            .map(|id| self.decrypt_id(id))
+            .collect())
+    }
+
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        let ciphertexts = blocks
+            .iter()
+            .map(|(key, data)| (self.inner_key(*key), self.encrypt(*key, data)))
+            .collect::<Vec<_>>();
+        let inner_blocks = ciphertexts
+            .iter()
+            .map(|(key, ciphertext)| (*key, ciphertext.as_slice()))
+            .collect::<Vec<_>>();
+        self.inner.write_blocks(&inner_blocks)
+    }
+
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        let inner_keys = keys
+            .iter()
+            .map(|key| self.inner_key(*key))
+            .collect::<Vec<_>>();
+        self.inner
+            .read_blocks(&inner_keys)?
+            .into_iter()
+            .zip(keys)
+            .map(|(ciphertext, key)| {
+                ciphertext
+                    .map(|ciphertext| self.decrypt(*key, &ciphertext))
+                    .transpose()
+            })
+            .collect()
+    }
+
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        let inner_keys = keys
+            .iter()
+            .map(|key| self.inner_key(*key))
+            .collect::<Vec<_>>();
+        self.inner.remove_blocks(&inner_keys)
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.inner.block_modified_time(self.inner_key(key))
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.inner.block_size(self.inner_key(key))
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        let capabilities = self.inner.probe();
+        StoreCapabilities {
+            max_block_size: capabilities
+                .max_block_size
+                .map(|size| size.saturating_sub((NONCEBYTES + ABYTES) as u64)),
+            ..capabilities
+        }
+    }
+}
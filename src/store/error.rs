@@ -1,16 +1,102 @@
 use std::error::Error as StdError;
 use std::fmt;
+use std::io;
 use std::ops::Deref;
 use std::result;
 
+/// A category of [`Error`].
+///
+/// This allows callers to decide how to handle an error without knowing which data store it came
+/// from, like retrying an operation which failed because of a network outage but not one which
+/// failed because data is corrupt.
+///
+/// [`Error`]: crate::store::Error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A resource the data store depends on, like a bucket or a directory, was not found.
+    NotFound,
+
+    /// The data store doesn't have permission to perform the operation, or it is read-only.
+    PermissionDenied,
+
+    /// The operation failed because of a temporary condition, like a network outage or a server
+    /// which is overloaded, and may succeed if it's retried.
+    Transient,
+
+    /// Data in the data store is corrupt or invalid.
+    Corrupt,
+
+    /// The data store doesn't support the operation or the format of the data.
+    Unsupported,
+
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    /// Return the kind of the given `error` by inspecting it and its chain of sources.
+    fn of(error: &(dyn StdError + 'static)) -> Self {
+        let mut current = Some(error);
+        while let Some(error) = current {
+            if let Some(io_error) = error.downcast_ref::<io::Error>() {
+                return Self::of_io(io_error);
+            }
+            match error.downcast_ref::<crate::Error>() {
+                // This type doesn't expose the errors it wraps as sources.
+                Some(crate::Error::Io(io_error)) => return Self::of_io(io_error),
+                Some(crate::Error::Store(store_error)) => return store_error.kind(),
+                Some(crate::Error::NotFound) => return ErrorKind::NotFound,
+                Some(crate::Error::ReadOnly) => return ErrorKind::PermissionDenied,
+                Some(
+                    crate::Error::Corrupt
+                    | crate::Error::InvalidData
+                    | crate::Error::ChecksumMismatch { .. },
+                ) => return ErrorKind::Corrupt,
+                Some(crate::Error::UnsupportedStore | crate::Error::InvalidStore) => {
+                    return ErrorKind::Unsupported
+                }
+                _ => {}
+            }
+            current = error.source();
+        }
+        ErrorKind::Other
+    }
+
+    /// Return the kind of the given I/O `error`.
+    fn of_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::AddrNotAvailable
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted => ErrorKind::Transient,
+            io::ErrorKind::InvalidData => ErrorKind::Corrupt,
+            io::ErrorKind::Unsupported => ErrorKind::Unsupported,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
 /// An error that occurs in a [`DataStore`].
 ///
-/// This wraps a dynamic error type.
+/// This wraps a dynamic error type along with an [`ErrorKind`] which describes it. When an error is
+/// constructed from another error, its kind is inferred from that error, like from the kind of an
+/// [`std::io::Error`]. Data stores can set the kind explicitly with [`with_kind`].
 ///
 /// [`DataStore`]: crate::store::DataStore
+/// [`ErrorKind`]: crate::store::ErrorKind
+/// [`with_kind`]: crate::store::Error::with_kind
 #[derive(Debug)]
 pub struct Error {
     inner: anyhow::Error,
+    kind: ErrorKind,
 }
 
 impl Error {
@@ -19,14 +105,17 @@ impl Error {
     where
         E: StdError + Send + Sync + 'static,
     {
+        let kind = ErrorKind::of(&error);
         Self {
             inner: anyhow::Error::new(error),
+            kind,
         }
     }
 
     /// Construct a new `Error` from a printable error message.
     ///
-    /// If the argument implements [`std::error::Error`], use [`new`] instead.
+    /// The kind of the returned error is `ErrorKind::Other`. If the argument implements
+    /// [`std::error::Error`], use [`new`] instead.
     ///
     /// [`new`]: crate::store::Error::new
     pub fn msg<M>(message: M) -> Self
@@ -35,8 +124,26 @@ impl Error {
     {
         Self {
             inner: anyhow::Error::msg(message),
+            kind: ErrorKind::Other,
         }
     }
+
+    /// Return this error with its kind set to `kind`.
+    pub fn with_kind(self, kind: ErrorKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    /// The kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Return whether the operation which caused this error may succeed if it's retried.
+    ///
+    /// This is the same as checking whether the kind of this error is `ErrorKind::Transient`.
+    pub fn is_transient(&self) -> bool {
+        self.kind == ErrorKind::Transient
+    }
 }
 
 impl<E> From<E> for Error
@@ -0,0 +1,515 @@
+#![cfg(feature = "store-file")]
+
+use std::collections::HashMap;
+use std::fs::{rename, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, LatencyClass, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("d4a8f1c2-6e3b-4b7d-9c5a-1f0e8b2d7a46");
+
+/// The size of the version ID at the start of the file.
+const VERSION_SIZE: u64 = 16;
+
+/// The size of a record header, which is a tag, a block ID, and a length.
+const RECORD_HEADER_SIZE: u64 = 1 + 16 + 8;
+
+/// The size of the checksum at the end of each record.
+const CHECKSUM_SIZE: u64 = 8;
+
+/// The size of the footer after an index record, which is its offset and a magic number.
+const FOOTER_SIZE: u64 = 8 + 8;
+
+/// The magic number at the end of the footer after an index record.
+const FOOTER_MAGIC: [u8; 8] = *b"ACIDIDX1";
+
+/// The size of an entry in an index record, which is a tag, a block ID, an offset, and a length.
+const INDEX_ENTRY_SIZE: usize = 1 + 16 + 8 + 8;
+
+// The tags which identify the kind of each record.
+const DATA_TAG: u8 = 0;
+const LOCK_TAG: u8 = 1;
+const HEADER_TAG: u8 = 2;
+const SUPER_TAG: u8 = 3;
+const VERSION_TAG: u8 = 4;
+const INDEX_TAG: u8 = 5;
+
+/// The bit which is set in the tag of a record that removes a block.
+const REMOVED_BIT: u8 = 0x80;
+
+/// Return the tag and ID for the block with the given `key`.
+fn key_to_tag(key: BlockKey) -> (u8, Uuid) {
+    match key {
+        BlockKey::Data(id) => (DATA_TAG, id.into()),
+        BlockKey::Lock(id) => (LOCK_TAG, id.into()),
+        BlockKey::Header(id) => (HEADER_TAG, id.into()),
+        BlockKey::Super => (SUPER_TAG, Uuid::nil()),
+        BlockKey::Version => (VERSION_TAG, Uuid::nil()),
+    }
+}
+
+/// Return the key of the block with the given `tag` and `id`.
+fn tag_to_key(tag: u8, id: Uuid) -> Option<BlockKey> {
+    match tag {
+        DATA_TAG => Some(BlockKey::Data(id.into())),
+        LOCK_TAG => Some(BlockKey::Lock(id.into())),
+        HEADER_TAG => Some(BlockKey::Header(id.into())),
+        SUPER_TAG => Some(BlockKey::Super),
+        VERSION_TAG => Some(BlockKey::Version),
+        _ => None,
+    }
+}
+
+/// Return the 64-bit FNV-1a hash of `parts`.
+///
+/// This is only used to detect records which were only partially written, not to protect against
+/// tampering.
+fn checksum(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in *part {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+/// Return the bytes of a record with the given `tag`, `id`, and `data`.
+fn encode_record(tag: u8, id: Uuid, data: &[u8]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(RECORD_HEADER_SIZE as usize);
+    header.push(tag);
+    header.extend_from_slice(id.as_bytes());
+    header.extend_from_slice(&(data.len() as u64).to_le_bytes());
+
+    let mut record = Vec::with_capacity(header.len() + data.len() + CHECKSUM_SIZE as usize);
+    record.extend_from_slice(&header);
+    record.extend_from_slice(data);
+    record.extend_from_slice(&checksum(&[&header, data]).to_le_bytes());
+    record
+}
+
+/// The location of a block's contents in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    offset: u64,
+    len: u64,
+}
+
+/// The configuration for opening a [`FileStore`].
+///
+/// [`FileStore`]: crate::store::FileStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-file")))]
+pub struct FileConfig {
+    /// The path of the archive file.
+    pub path: PathBuf,
+}
+
+impl FileConfig {
+    /// Create a new `FileConfig` for a file store at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl OpenStore for FileConfig {
+    type Store = FileStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        FileStore::open(&self.path)
+    }
+}
+
+/// A `DataStore` which stores data in a single archive file.
+///
+/// Unlike [`DirectoryStore`], which stores each block in a separate file, this store keeps every
+/// block in one append-only file, which makes a repository easy to copy or send as a single file.
+/// Writing or removing a block appends a record to the end of the file, and each record has a
+/// checksum, so a record which was only partially written when the process was interrupted is
+/// discarded the next time the store is opened.
+///
+/// An index of every block is appended to the file when the store is dropped, which makes opening
+/// the store fast. If the store wasn't closed cleanly, the index is rebuilt by reading every
+/// record in the file.
+///
+/// Because the file is append-only, it only grows. Use [`compact`] to rewrite it without the space
+/// used by removed and overwritten blocks.
+///
+/// The file must not be open in more than one store at a time.
+///
+/// You can use [`FileConfig`] to open a data store of this type.
+///
+/// [`DirectoryStore`]: crate::store::DirectoryStore
+/// [`compact`]: crate::store::FileStore::compact
+/// [`FileConfig`]: crate::store::FileConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-file")))]
+pub struct FileStore {
+    /// The path of the archive file.
+    path: PathBuf,
+
+    /// The archive file.
+    file: File,
+
+    /// The location of each block in the file.
+    index: HashMap<BlockKey, Location>,
+
+    /// The size of the file, which is where the next record is written.
+    len: u64,
+
+    /// Whether the file has changed since the last index was written.
+    dirty: bool,
+}
+
+impl FileStore {
+    /// Open the file store at `path`, creating it if it doesn't exist.
+    ///
+    /// # Errors
+    /// - `Error::InvalidStore`: There is a file at `path`, but it is not a file store.
+    /// - `Error::UnsupportedStore`: The file store uses an incompatible format version.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)?;
+        let file_len = file.metadata()?.len();
+
+        if file_len == 0 {
+            file.write_all(CURRENT_VERSION.as_bytes())?;
+            file.sync_all()?;
+            return Ok(FileStore {
+                path: path.to_path_buf(),
+                file,
+                index: HashMap::new(),
+                len: VERSION_SIZE,
+                dirty: true,
+            });
+        }
+
+        if file_len < VERSION_SIZE {
+            return Err(crate::Error::InvalidStore);
+        }
+        let mut version = [0u8; VERSION_SIZE as usize];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut version)?;
+        if Uuid::from_bytes(version) != CURRENT_VERSION {
+            return Err(crate::Error::UnsupportedStore);
+        }
+
+        let mut store = FileStore {
+            path: path.to_path_buf(),
+            file,
+            index: HashMap::new(),
+            len: file_len,
+            dirty: false,
+        };
+
+        if !store.load_index()? {
+            store.scan()?;
+        }
+
+        Ok(store)
+    }
+
+    /// Rewrite the file without the space used by removed and overwritten blocks.
+    ///
+    /// The compacted file is written next to the original and then atomically replaces it. This
+    /// returns the number of bytes which were reclaimed.
+    ///
+    /// # Errors
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn compact(&mut self) -> crate::Result<u64> {
+        let old_len = self.len;
+        let mut compact_path = self.path.clone().into_os_string();
+        compact_path.push(".compact");
+        let compact_path = PathBuf::from(compact_path);
+
+        let mut new_index = HashMap::with_capacity(self.index.len());
+        let mut new_len = VERSION_SIZE;
+        {
+            let mut writer = BufWriter::new(File::create(&compact_path)?);
+            writer.write_all(CURRENT_VERSION.as_bytes())?;
+
+            let mut locations = self
+                .index
+                .iter()
+                .map(|(key, location)| (*key, *location))
+                .collect::<Vec<_>>();
+            locations.sort_by_key(|(_, location)| location.offset);
+            for (key, location) in locations {
+                let data = self.read_at(location)?;
+                let (tag, id) = key_to_tag(key);
+                writer.write_all(&encode_record(tag, id, &data))?;
+                new_index.insert(
+                    key,
+                    Location {
+                        offset: new_len + RECORD_HEADER_SIZE,
+                        len: location.len,
+                    },
+                );
+                new_len += RECORD_HEADER_SIZE + location.len + CHECKSUM_SIZE;
+            }
+
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+
+        rename(&compact_path, &self.path)?;
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.index = new_index;
+        self.len = new_len;
+        self.dirty = true;
+        self.write_index()?;
+
+        Ok(old_len.saturating_sub(self.len))
+    }
+
+    /// Read the contents of the block at `location`.
+    fn read_at(&mut self, location: Location) -> io::Result<Vec<u8>> {
+        let mut data = vec![0u8; location.len as usize];
+        self.file.seek(SeekFrom::Start(location.offset))?;
+        self.file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Append `record` to the file and sync it to disk.
+    ///
+    /// This returns the offset of the record.
+    fn append(&mut self, record: &[u8]) -> io::Result<u64> {
+        let offset = self.len;
+        self.file.seek(SeekFrom::Start(offset))?;
+        if let Err(error) = self
+            .file
+            .write_all(record)
+            .and_then(|_| self.file.sync_data())
+        {
+            // Don't leave a partial record at the end of the file.
+            self.file.set_len(offset).ok();
+            return Err(error);
+        }
+        self.len += record.len() as u64;
+        self.dirty = true;
+        Ok(offset)
+    }
+
+    /// Load the index from the end of the file.
+    ///
+    /// This returns `false` if the file doesn't end with a valid index.
+    fn load_index(&mut self) -> crate::Result<bool> {
+        if self.len < VERSION_SIZE + RECORD_HEADER_SIZE + CHECKSUM_SIZE + FOOTER_SIZE {
+            return Ok(false);
+        }
+
+        let mut footer = [0u8; FOOTER_SIZE as usize];
+        self.file.seek(SeekFrom::Start(self.len - FOOTER_SIZE))?;
+        self.file.read_exact(&mut footer)?;
+        if footer[8..] != FOOTER_MAGIC {
+            return Ok(false);
+        }
+        let index_offset = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        if index_offset < VERSION_SIZE || index_offset >= self.len - FOOTER_SIZE {
+            return Ok(false);
+        }
+
+        self.file.seek(SeekFrom::Start(index_offset))?;
+        let mut reader = BufReader::new(&mut self.file);
+        let (tag, _, data) = match read_record(&mut reader, self.len - index_offset)? {
+            Some(record) => record,
+            None => return Ok(false),
+        };
+        if tag != INDEX_TAG || index_offset + record_size(&data) + FOOTER_SIZE != self.len {
+            return Ok(false);
+        }
+
+        let mut index = HashMap::with_capacity(data.len() / INDEX_ENTRY_SIZE);
+        for entry in data.chunks_exact(INDEX_ENTRY_SIZE) {
+            let id = Uuid::from_slice(&entry[1..17]).unwrap();
+            let key = match tag_to_key(entry[0], id) {
+                Some(key) => key,
+                None => return Ok(false),
+            };
+            let offset = u64::from_le_bytes(entry[17..25].try_into().unwrap());
+            let len = u64::from_le_bytes(entry[25..33].try_into().unwrap());
+            index.insert(key, Location { offset, len });
+        }
+
+        self.index = index;
+        Ok(true)
+    }
+
+    /// Rebuild the index by reading every record in the file.
+    ///
+    /// If the file ends with a partially written record, it is truncated.
+    fn scan(&mut self) -> crate::Result<()> {
+        let mut index = HashMap::new();
+        let mut offset = VERSION_SIZE;
+
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(&mut self.file);
+        while offset < self.len {
+            let (tag, id, data) = match read_record(&mut reader, self.len - offset)? {
+                Some(record) => record,
+                None => break,
+            };
+            let size = record_size(&data);
+
+            if tag == INDEX_TAG {
+                // Skip the footer after the index.
+                if offset + size + FOOTER_SIZE > self.len {
+                    break;
+                }
+                reader.seek_relative(FOOTER_SIZE as i64)?;
+                offset += size + FOOTER_SIZE;
+                continue;
+            }
+
+            let key = tag_to_key(tag & !REMOVED_BIT, id).ok_or(crate::Error::InvalidStore)?;
+            if tag & REMOVED_BIT == 0 {
+                index.insert(
+                    key,
+                    Location {
+                        offset: offset + RECORD_HEADER_SIZE,
+                        len: data.len() as u64,
+                    },
+                );
+            } else {
+                index.remove(&key);
+            }
+            offset += size;
+        }
+
+        if offset < self.len {
+            self.file.set_len(offset)?;
+            self.file.sync_all()?;
+            self.len = offset;
+        }
+        self.index = index;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Append an index of every block to the end of the file.
+    fn write_index(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut entries = Vec::with_capacity(self.index.len() * INDEX_ENTRY_SIZE);
+        for (key, location) in &self.index {
+            let (tag, id) = key_to_tag(*key);
+            entries.push(tag);
+            entries.extend_from_slice(id.as_bytes());
+            entries.extend_from_slice(&location.offset.to_le_bytes());
+            entries.extend_from_slice(&location.len.to_le_bytes());
+        }
+
+        let offset = self.len;
+        let mut record = encode_record(INDEX_TAG, Uuid::nil(), &entries);
+        record.extend_from_slice(&offset.to_le_bytes());
+        record.extend_from_slice(&FOOTER_MAGIC);
+        self.append(&record)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Return the total size of a record containing `data`.
+fn record_size(data: &[u8]) -> u64 {
+    RECORD_HEADER_SIZE + data.len() as u64 + CHECKSUM_SIZE
+}
+
+/// Read a record from `reader`, which has `remaining` bytes left.
+///
+/// This returns `None` if the record is incomplete or its checksum doesn't match.
+fn read_record(reader: &mut impl Read, remaining: u64) -> io::Result<Option<(u8, Uuid, Vec<u8>)>> {
+    if remaining < RECORD_HEADER_SIZE + CHECKSUM_SIZE {
+        return Ok(None);
+    }
+
+    let mut header = [0u8; RECORD_HEADER_SIZE as usize];
+    reader.read_exact(&mut header)?;
+    let len = u64::from_le_bytes(header[17..25].try_into().unwrap());
+    if len > remaining - RECORD_HEADER_SIZE - CHECKSUM_SIZE {
+        return Ok(None);
+    }
+
+    let mut data = vec![0u8; len as usize];
+    reader.read_exact(&mut data)?;
+    let mut expected = [0u8; CHECKSUM_SIZE as usize];
+    reader.read_exact(&mut expected)?;
+    if u64::from_le_bytes(expected) != checksum(&[&header, &data]) {
+        return Ok(None);
+    }
+
+    let id = Uuid::from_slice(&header[1..17]).unwrap();
+    Ok(Some((header[0], id, data)))
+}
+
+impl Drop for FileStore {
+    fn drop(&mut self) {
+        self.write_index().ok();
+    }
+}
+
+impl DataStore for FileStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let (tag, id) = key_to_tag(key);
+        let offset = self.append(&encode_record(tag, id, data))?;
+        self.index.insert(
+            key,
+            Location {
+                offset: offset + RECORD_HEADER_SIZE,
+                len: data.len() as u64,
+            },
+        );
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        match self.index.get(&key).copied() {
+            Some(location) => Ok(Some(self.read_at(location)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        if !self.index.contains_key(&key) {
+            return Ok(());
+        }
+        let (tag, id) = key_to_tag(key);
+        self.append(&encode_record(tag | REMOVED_BIT, id, &[]))?;
+        self.index.remove(&key);
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        Ok(self
+            .index
+            .keys()
+            .filter_map(|key| match (kind, key) {
+                (BlockType::Data, BlockKey::Data(id)) => Some(*id),
+                (BlockType::Lock, BlockKey::Lock(id)) => Some(*id),
+                (BlockType::Header, BlockKey::Header(id)) => Some(*id),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            latency: LatencyClass::Local,
+            ..StoreCapabilities::default()
+        }
+    }
+}
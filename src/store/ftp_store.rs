@@ -0,0 +1,406 @@
+#![cfg(feature = "store-ftp")]
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::io::Cursor;
+use std::sync::{Arc, Mutex, Weak};
+
+use once_cell::sync::Lazy;
+use suppaftp::native_tls::TlsConnector;
+use suppaftp::types::FileType;
+use suppaftp::{FtpError, NativeTlsConnector, NativeTlsFtpStream, Status};
+use uuid::Uuid;
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::open_store::OpenStore;
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: &str = "5f0c2e7a-3b9d-4a61-9c84-e2d71b6f03a9";
+
+// The names of top-level files in the data store.
+const STORE_DIRECTORY: &str = "store";
+const STAGING_DIRECTORY: &str = "stage";
+const VERSION_FILE: &str = "version";
+
+/// The default port for FTP servers.
+const DEFAULT_PORT: u16 = 21;
+
+/// The default maximum number of idle connections to keep open.
+const DEFAULT_MAX_IDLE_CONNECTIONS: usize = 4;
+
+/// The connection pools which are shared between stores, indexed by the server and user.
+static POOLS: Lazy<Mutex<HashMap<PoolKey, Weak<ConnectionPool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Join the given segments of a remote path.
+fn join_path(segments: &[&str]) -> String {
+    segments
+        .iter()
+        .map(|segment| segment.trim_end_matches('/'))
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn type_path(root: &str, kind: BlockType) -> String {
+    match kind {
+        BlockType::Data => join_path(&[root, STORE_DIRECTORY, "data"]),
+        BlockType::Lock => join_path(&[root, STORE_DIRECTORY, "locks"]),
+        BlockType::Header => join_path(&[root, STORE_DIRECTORY, "headers"]),
+    }
+}
+
+fn block_path(root: &str, key: BlockKey) -> String {
+    match key {
+        BlockKey::Data(id) => {
+            let uuid_str = id.as_ref().as_hyphenated().to_string();
+            join_path(&[&type_path(root, BlockType::Data), &uuid_str[..2], &uuid_str])
+        }
+        BlockKey::Lock(id) => {
+            let uuid_str = id.as_ref().as_hyphenated().to_string();
+            join_path(&[&type_path(root, BlockType::Lock), &uuid_str])
+        }
+        BlockKey::Header(id) => {
+            let uuid_str = id.as_ref().as_hyphenated().to_string();
+            join_path(&[&type_path(root, BlockType::Header), &uuid_str])
+        }
+        BlockKey::Super => join_path(&[root, STORE_DIRECTORY, "super"]),
+        BlockKey::Version => join_path(&[root, STORE_DIRECTORY, "version"]),
+    }
+}
+
+/// Return the path where a block will be staged in the store at `root`.
+fn staging_path(root: &str) -> String {
+    let uuid_str = Uuid::new_v4().as_hyphenated().to_string();
+    join_path(&[root, STAGING_DIRECTORY, &uuid_str])
+}
+
+/// Parse the ID of a block from the `name` of its file returned by the server.
+///
+/// Some servers return the full path of each file in a listing and some return only its name.
+fn parse_block_id(name: &str) -> super::Result<BlockId> {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    let id = Uuid::parse_str(file_name)
+        .map_err(|_| super::Error::msg("Block file name is invalid."))?
+        .into();
+    Ok(id)
+}
+
+/// Return whether the given `error` means that a file or directory is unavailable.
+///
+/// Servers return this when a file doesn't exist, but also when a directory already exists.
+fn is_unavailable(error: &FtpError) -> bool {
+    matches!(error, FtpError::UnexpectedResponse(response) if response.status == Status::FileUnavailable)
+}
+
+/// Return whether the given `error` means that the connection can't be used anymore.
+fn is_connection_error(error: &super::Error) -> bool {
+    matches!(
+        error.downcast_ref::<FtpError>(),
+        Some(FtpError::ConnectionError(_) | FtpError::SecureError(_))
+    )
+}
+
+/// Create the directory at `path` if it doesn't already exist.
+fn create_dir(stream: &mut NativeTlsFtpStream, path: &str) -> super::Result<()> {
+    match stream.mkdir(path) {
+        Ok(()) => Ok(()),
+        Err(error) if is_unavailable(&error) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Return the names of the files in the directory at `path`.
+///
+/// If the directory doesn't exist, this returns an empty list.
+fn list_dir(stream: &mut NativeTlsFtpStream, path: &str) -> super::Result<Vec<String>> {
+    match stream.nlst(Some(path)) {
+        Ok(names) => Ok(names),
+        Err(error) if is_unavailable(&error) => Ok(Vec::new()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// The configuration for opening an [`FtpStore`].
+///
+/// Stores opened with the same `host`, `port`, `username`, and `secure` share a pool of
+/// connections to the server. A connection is taken from the pool for each operation and returned
+/// to it afterwards, and up to `max_idle_connections` are kept open between operations.
+///
+/// [`FtpStore`]: crate::store::FtpStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-ftp")))]
+pub struct FtpConfig {
+    /// The hostname of the server.
+    pub host: String,
+
+    /// The port to connect to.
+    ///
+    /// This is `21` by default.
+    pub port: u16,
+
+    /// The username to authenticate with.
+    pub username: String,
+
+    /// The password to authenticate with.
+    pub password: String,
+
+    /// The path of the directory on the server which blocks are stored in.
+    pub path: String,
+
+    /// Whether to secure the connection with explicit TLS (FTPS).
+    ///
+    /// This is `false` by default.
+    pub secure: bool,
+
+    /// The maximum number of idle connections to keep open in the pool.
+    ///
+    /// This is `4` by default.
+    pub max_idle_connections: usize,
+}
+
+impl FtpConfig {
+    /// Create a new config for the store at `path` on the server at `host`.
+    ///
+    /// This uses the default values for the other options.
+    pub fn new(
+        host: impl Into<String>,
+        username: impl Into<String>,
+        password: impl Into<String>,
+        path: impl Into<String>,
+    ) -> Self {
+        Self {
+            host: host.into(),
+            port: DEFAULT_PORT,
+            username: username.into(),
+            password: password.into(),
+            path: path.into(),
+            secure: false,
+            max_idle_connections: DEFAULT_MAX_IDLE_CONNECTIONS,
+        }
+    }
+
+    /// Connect and authenticate to the FTP server.
+    fn connect(&self) -> super::Result<NativeTlsFtpStream> {
+        let mut stream = NativeTlsFtpStream::connect((self.host.as_str(), self.port))?;
+        if self.secure {
+            let connector = NativeTlsConnector::from(TlsConnector::new()?);
+            stream = stream.into_secure(connector, &self.host)?;
+        }
+        stream.login(&self.username, &self.password)?;
+        stream.transfer_type(FileType::Binary)?;
+        Ok(stream)
+    }
+
+    /// Return the connection pool shared with other stores.
+    fn pool(&self) -> Arc<ConnectionPool> {
+        let key = PoolKey {
+            host: self.host.clone(),
+            port: self.port,
+            username: self.username.clone(),
+            secure: self.secure,
+        };
+        let mut pools = POOLS.lock().unwrap();
+
+        // Clean up pools which are no longer in use.
+        pools.retain(|_, pool| pool.strong_count() > 0);
+
+        if let Some(pool) = pools.get(&key).and_then(Weak::upgrade) {
+            return pool;
+        }
+
+        let pool = Arc::new(ConnectionPool {
+            idle: Mutex::new(Vec::new()),
+        });
+        pools.insert(key, Arc::downgrade(&pool));
+        pool
+    }
+}
+
+impl OpenStore for FtpConfig {
+    type Store = FtpStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let store = FtpStore {
+            config: self.clone(),
+            pool: self.pool(),
+        };
+
+        let version_matches = store
+            .with_connection(|stream, root| {
+                // Create the directories if they don't exist.
+                let directories = [
+                    root.to_owned(),
+                    join_path(&[root, STAGING_DIRECTORY]),
+                    join_path(&[root, STORE_DIRECTORY]),
+                    type_path(root, BlockType::Data),
+                    type_path(root, BlockType::Lock),
+                    type_path(root, BlockType::Header),
+                ];
+                for directory in &directories {
+                    create_dir(stream, directory)?;
+                }
+
+                // Read the version ID file, or write it if this is a new store.
+                let version_path = join_path(&[root, VERSION_FILE]);
+                match stream.retr_as_buffer(&version_path) {
+                    Ok(version_id) => Ok(version_id.into_inner() == CURRENT_VERSION.as_bytes()),
+                    Err(error) if is_unavailable(&error) => {
+                        stream.put_file(&version_path, &mut CURRENT_VERSION.as_bytes())?;
+                        Ok(true)
+                    }
+                    Err(error) => Err(error.into()),
+                }
+            })
+            .map_err(crate::Error::Store)?;
+
+        if !version_matches {
+            return Err(crate::Error::UnsupportedStore);
+        }
+
+        Ok(store)
+    }
+}
+
+/// The key which identifies a shared connection pool.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct PoolKey {
+    host: String,
+    port: u16,
+    username: String,
+    secure: bool,
+}
+
+/// A pool of idle connections to an FTP server.
+struct ConnectionPool {
+    idle: Mutex<Vec<NativeTlsFtpStream>>,
+}
+
+/// A `DataStore` which stores data on an FTP server.
+///
+/// This is meant for hosting providers which only offer access over FTP or FTPS. Blocks are stored
+/// as files under the configured directory. Each block is uploaded to a staging directory and then
+/// renamed into place, so the server must allow renaming a file over an existing one for writes to
+/// be atomic.
+///
+/// You can use [`FtpConfig`] to open a data store of this type.
+///
+/// [`FtpConfig`]: crate::store::FtpConfig
+#[cfg_attr(docsrs, doc(cfg(feature = "store-ftp")))]
+pub struct FtpStore {
+    config: FtpConfig,
+    pool: Arc<ConnectionPool>,
+}
+
+impl FtpStore {
+    /// Call `operation` with a connection from the pool and the path of the store.
+    ///
+    /// If a connection from the pool has been closed by the server, the operation is tried again
+    /// once with a new connection. Operations must be safe to repeat.
+    fn with_connection<T>(
+        &self,
+        mut operation: impl FnMut(&mut NativeTlsFtpStream, &str) -> super::Result<T>,
+    ) -> super::Result<T> {
+        let pooled = self.pool.idle.lock().unwrap().pop();
+        let is_pooled = pooled.is_some();
+        let mut stream = match pooled {
+            Some(stream) => stream,
+            None => self.config.connect()?,
+        };
+
+        let mut result = operation(&mut stream, &self.config.path);
+
+        // Idle connections may have timed out, so we try a new connection before giving up.
+        if is_pooled && matches!(&result, Err(error) if is_connection_error(error)) {
+            stream = self.config.connect()?;
+            result = operation(&mut stream, &self.config.path);
+        }
+
+        if !matches!(&result, Err(error) if is_connection_error(error)) {
+            let mut idle = self.pool.idle.lock().unwrap();
+            if idle.len() < self.config.max_idle_connections {
+                idle.push(stream);
+            }
+        }
+
+        result
+    }
+}
+
+impl DataStore for FtpStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.with_connection(|stream, root| {
+            let staging_path = staging_path(root);
+            let block_path = block_path(root, key);
+
+            // If this is the first block its sub-directory, the directory needs to be created.
+            if let BlockKey::Data(_) = key {
+                let (parent, _) = block_path.rsplit_once('/').unwrap();
+                create_dir(stream, parent)?;
+            }
+
+            // Write to a staging file and then move it to its final destination.
+            stream.put_file(&staging_path, &mut Cursor::new(data))?;
+            if let Err(error) = stream.rename(&staging_path, &block_path) {
+                // Don't leave the staging file behind.
+                let _ = stream.rm(&staging_path);
+                return Err(error.into());
+            }
+
+            Ok(())
+        })
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.with_connection(
+            |stream, root| match stream.retr_as_buffer(&block_path(root, key)) {
+                Ok(buffer) => Ok(Some(buffer.into_inner())),
+                Err(error) if is_unavailable(&error) => Ok(None),
+                Err(error) => Err(error.into()),
+            },
+        )
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.with_connection(|stream, root| match stream.rm(&block_path(root, key)) {
+            Ok(()) => Ok(()),
+            Err(error) if is_unavailable(&error) => Ok(()),
+            Err(error) => Err(error.into()),
+        })
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.with_connection(|stream, root| {
+            let type_path = type_path(root, kind);
+            let mut block_ids = Vec::new();
+
+            match kind {
+                BlockType::Data => {
+                    for directory in list_dir(stream, &type_path)? {
+                        let directory_name = directory.rsplit('/').next().unwrap_or(&directory);
+                        let directory_path = join_path(&[&type_path, directory_name]);
+                        for name in list_dir(stream, &directory_path)? {
+                            block_ids.push(parse_block_id(&name)?);
+                        }
+                    }
+                }
+                BlockType::Lock | BlockType::Header => {
+                    for name in list_dir(stream, &type_path)? {
+                        block_ids.push(parse_block_id(&name)?);
+                    }
+                }
+            }
+
+            Ok(block_ids)
+        })
+    }
+}
+
+impl Debug for FtpStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FtpStore")
+            .field("host", &self.config.host)
+            .field("path", &self.config.path)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,424 @@
+#![cfg(feature = "store-gcs")]
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use ureq::{Agent, AgentBuilder, Request, Response};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::open_store::OpenStore;
+use super::rest::{check_response, join_key, percent_encode, SEPARATOR};
+
+// The names of objects in the data store.
+const STORE_KEY: &str = "store";
+const DATA_KEY: &str = "data";
+const LOCKS_KEY: &str = "lock";
+const HEADERS_KEY: &str = "header";
+const SUPER_KEY: &str = "super";
+const REPO_VERSION_KEY: &str = "version";
+const STORE_VERSION_KEY: &str = "version";
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("9a1d5e2c-6b7f-4c3a-8e0d-2f4b6a8c1e35");
+
+/// The default URL of the Cloud Storage API.
+const DEFAULT_ENDPOINT: &str = "https://storage.googleapis.com";
+
+/// The OAuth 2.0 scope which grants read and write access to buckets.
+const READ_WRITE_SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_write";
+
+/// The grant type for exchanging a signed JWT for an access token.
+const JWT_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+/// How long the JWTs used to request access tokens are valid for.
+const JWT_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// How long before an access token expires it should be refreshed.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// The environment variable for the path of the service account key file.
+const CREDENTIALS_ENV: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+
+/// The name of the service in error messages.
+const SERVICE_NAME: &str = "Google Cloud Storage";
+
+/// The fields of a service account key file which are needed to authenticate.
+#[derive(Debug, Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+/// The claims of the JWT used to request an access token.
+#[derive(Debug, Serialize)]
+struct TokenClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+/// The response to an access token request.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// A page of the response to listing objects.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectList {
+    #[serde(default)]
+    items: Vec<ObjectResource>,
+    next_page_token: Option<String>,
+}
+
+/// An object in an `ObjectList`.
+#[derive(Debug, Deserialize)]
+struct ObjectResource {
+    name: String,
+}
+
+/// The credentials for a Google Cloud Storage connection.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-gcs")))]
+pub enum GcsCredentials {
+    /// Anonymous credentials for accessing public buckets or local emulators.
+    Anonymous,
+
+    /// Service account credentials.
+    ///
+    /// These are the fields of the same name in a service account key file. You can use
+    /// [`from_file`] to read them from a key file.
+    ///
+    /// [`from_file`]: crate::store::GcsCredentials::from_file
+    ServiceAccount {
+        /// The email address of the service account.
+        client_email: String,
+        /// The PEM-encoded RSA private key of the service account.
+        private_key: String,
+        /// The URL to request access tokens from.
+        token_uri: String,
+    },
+}
+
+impl GcsCredentials {
+    /// Get `GcsCredentials` from the service account key file at `path`.
+    ///
+    /// This returns `None` if the file could not be read or is not a service account key file.
+    pub fn from_file(path: impl AsRef<Path>) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let key: ServiceAccountKey = serde_json::from_reader(file).ok()?;
+        Some(GcsCredentials::ServiceAccount {
+            client_email: key.client_email,
+            private_key: key.private_key,
+            token_uri: key.token_uri,
+        })
+    }
+
+    /// Get `GcsCredentials` from the key file in the `GOOGLE_APPLICATION_CREDENTIALS`
+    /// environment variable.
+    ///
+    /// This returns `None` if the environment variable is unset or the file could not be read.
+    pub fn from_env() -> Option<Self> {
+        Self::from_file(env::var_os(CREDENTIALS_ENV)?)
+    }
+}
+
+/// The configuration for opening a [`GcsStore`].
+///
+/// The bucket must already exist.
+///
+/// [`GcsStore`]: crate::store::GcsStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-gcs")))]
+pub struct GcsConfig {
+    /// The name of the bucket.
+    pub bucket: String,
+
+    /// The credentials to connect with.
+    pub credentials: GcsCredentials,
+
+    /// The prefix to prepend to object names in the store.
+    ///
+    /// While object names are a flat namespace, you can think of this like the directory of the
+    /// bucket to create the store in. To create the store in the bucket root, use an empty string.
+    pub prefix: String,
+
+    /// The URL of the Cloud Storage API, if it isn't the default.
+    ///
+    /// By default, this is `https://storage.googleapis.com`. This is useful for connecting to a
+    /// local emulator.
+    pub endpoint: Option<String>,
+}
+
+impl GcsConfig {
+    /// Return a new config for the given `bucket` with the default endpoint.
+    pub fn new(bucket: impl Into<String>, credentials: GcsCredentials) -> Self {
+        GcsConfig {
+            bucket: bucket.into(),
+            credentials,
+            prefix: String::new(),
+            endpoint: None,
+        }
+    }
+}
+
+impl OpenStore for GcsConfig {
+    type Store = GcsStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let endpoint = self
+            .endpoint
+            .as_deref()
+            .unwrap_or(DEFAULT_ENDPOINT)
+            .trim_end_matches('/');
+        let bucket = percent_encode(&self.bucket, b"");
+        let mut store = GcsStore {
+            agent: AgentBuilder::new().build(),
+            objects_url: format!("{}/storage/v1/b/{}/o", endpoint, bucket),
+            upload_url: format!("{}/upload/storage/v1/b/{}/o", endpoint, bucket),
+            credentials: self.credentials.clone(),
+            token: None,
+            prefix: self.prefix.trim_matches('/').to_owned(),
+        };
+
+        let version_key = join_key(&[&store.prefix, STORE_VERSION_KEY]);
+        match store.get(&version_key).map_err(crate::Error::Store)? {
+            None => store
+                .put(&version_key, CURRENT_VERSION.as_bytes())
+                .map_err(crate::Error::Store)?,
+            Some(version) => {
+                let version =
+                    Uuid::from_slice(&version).map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// A `DataStore` which stores data in a Google Cloud Storage bucket.
+///
+/// You can use [`GcsConfig`] to open a data store of this type.
+///
+/// Access tokens for service accounts are requested when the store is opened and refreshed
+/// shortly before they expire.
+///
+/// [`GcsConfig`]: crate::store::GcsConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-gcs")))]
+pub struct GcsStore {
+    agent: Agent,
+    objects_url: String,
+    upload_url: String,
+    credentials: GcsCredentials,
+    token: Option<(String, Instant)>,
+    prefix: String,
+}
+
+impl GcsStore {
+    /// Return the name of the object for the block with the given `key`.
+    fn block_path(&self, key: BlockKey) -> String {
+        match key {
+            BlockKey::Data(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                DATA_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Lock(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                LOCKS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Header(id) => join_key(&[
+                &self.prefix,
+                STORE_KEY,
+                HEADERS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Super => join_key(&[&self.prefix, STORE_KEY, SUPER_KEY]),
+            BlockKey::Version => join_key(&[&self.prefix, STORE_KEY, REPO_VERSION_KEY]),
+        }
+    }
+
+    /// Return a valid access token, requesting a new one if necessary.
+    ///
+    /// This returns `None` for anonymous credentials.
+    fn access_token(&mut self) -> super::Result<Option<String>> {
+        let (client_email, private_key, token_uri) = match &self.credentials {
+            GcsCredentials::Anonymous => return Ok(None),
+            GcsCredentials::ServiceAccount {
+                client_email,
+                private_key,
+                token_uri,
+            } => (client_email, private_key, token_uri),
+        };
+
+        if let Some((token, expires)) = &self.token {
+            if Instant::now() + TOKEN_REFRESH_MARGIN < *expires {
+                return Ok(Some(token.clone()));
+            }
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let claims = TokenClaims {
+            iss: client_email,
+            scope: READ_WRITE_SCOPE,
+            aud: token_uri,
+            iat: now,
+            exp: now + JWT_LIFETIME.as_secs(),
+        };
+        let key = EncodingKey::from_rsa_pem(private_key.as_bytes())?;
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)?;
+
+        let requested = Instant::now();
+        let response = check_response(
+            SERVICE_NAME,
+            self.agent
+                .post(token_uri)
+                .send_form(&[("grant_type", JWT_GRANT_TYPE), ("assertion", &assertion)]),
+        )?
+        .ok_or_else(|| super::Error::msg("The token endpoint does not exist."))?;
+        let token: TokenResponse = serde_json::from_reader(response.into_reader())?;
+
+        let expires = requested + Duration::from_secs(token.expires_in);
+        self.token = Some((token.access_token.clone(), expires));
+        Ok(Some(token.access_token))
+    }
+
+    /// Send the given `request`, authorizing it if necessary, and with the given `body`.
+    ///
+    /// This returns `None` if the object or bucket does not exist.
+    fn send(&mut self, request: Request, body: Option<&[u8]>) -> super::Result<Option<Response>> {
+        let request = match self.access_token()? {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        };
+        let result = match body {
+            Some(body) => request
+                .set("Content-Type", "application/octet-stream")
+                .send_bytes(body),
+            None => request.call(),
+        };
+        check_response(SERVICE_NAME, result)
+    }
+
+    /// Return the URL of the object with the given `name`.
+    fn object_url(&self, name: &str) -> String {
+        format!("{}/{}", self.objects_url, percent_encode(name, b""))
+    }
+
+    /// Return the contents of the object with the given `name` or `None` if it does not exist.
+    fn get(&mut self, name: &str) -> super::Result<Option<Vec<u8>>> {
+        let request = self.agent.get(&self.object_url(name)).query("alt", "media");
+        match self.send(request, None)? {
+            Some(response) => {
+                let mut data = Vec::new();
+                response.into_reader().read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Upload `data` as the object with the given `name`.
+    fn put(&mut self, name: &str, data: &[u8]) -> super::Result<()> {
+        let request = self
+            .agent
+            .post(&self.upload_url)
+            .query("uploadType", "media")
+            .query("name", name);
+        self.send(request, Some(data))?
+            .ok_or_else(|| super::Error::msg("The bucket does not exist."))?;
+        Ok(())
+    }
+
+    /// Return the names of all objects whose names start with `prefix`.
+    fn list(&mut self, prefix: &str) -> super::Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut page_token = None;
+
+        loop {
+            let mut request = self
+                .agent
+                .get(&self.objects_url)
+                .query("prefix", prefix)
+                .query("fields", "items(name),nextPageToken");
+            if let Some(page_token) = &page_token {
+                request = request.query("pageToken", page_token);
+            }
+            let response = self
+                .send(request, None)?
+                .ok_or_else(|| super::Error::msg("The bucket does not exist."))?;
+            let page: ObjectList = serde_json::from_reader(response.into_reader())?;
+
+            names.extend(page.items.into_iter().map(|object| object.name));
+
+            match page.next_page_token {
+                Some(next_page_token) => page_token = Some(next_page_token),
+                None => break,
+            }
+        }
+
+        Ok(names)
+    }
+}
+
+impl DataStore for GcsStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let block_path = self.block_path(key);
+        self.put(&block_path, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let block_path = self.block_path(key);
+        self.get(&block_path)
+    }
+
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        let block_path = self.block_path(key);
+        let request = self
+            .agent
+            .get(&self.object_url(&block_path))
+            .query("alt", "media");
+        Ok(self
+            .send(request, None)?
+            .map(|response| Box::new(response.into_reader()) as Box<dyn Read>))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let block_path = self.block_path(key);
+        let request = self.agent.delete(&self.object_url(&block_path));
+        self.send(request, None)?;
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let blocks_key = match kind {
+            BlockType::Data => join_key(&[&self.prefix, STORE_KEY, DATA_KEY]) + SEPARATOR,
+            BlockType::Lock => join_key(&[&self.prefix, STORE_KEY, LOCKS_KEY]) + SEPARATOR,
+            BlockType::Header => join_key(&[&self.prefix, STORE_KEY, HEADERS_KEY]) + SEPARATOR,
+        };
+        let block_ids = self
+            .list(&blocks_key)?
+            .iter()
+            .map(|name| Uuid::parse_str(name.trim_start_matches(&blocks_key)).map(|id| id.into()))
+            .collect::<Result<Vec<BlockId>, _>>()?;
+        Ok(block_ids)
+    }
+}
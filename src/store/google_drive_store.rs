@@ -0,0 +1,512 @@
+#![cfg(feature = "store-google-drive")]
+
+use std::io::Read;
+
+use serde::Deserialize;
+use serde_json::json;
+use ureq::{AgentBuilder, Response};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::oauth::{OAuthCredentials, TokenSource};
+use super::open_store::OpenStore;
+use super::rest::check_response;
+
+// The names of files and folders in the data store.
+const STORE_KEY: &str = "store";
+const DATA_KEY: &str = "data";
+const LOCKS_KEY: &str = "lock";
+const HEADERS_KEY: &str = "header";
+const SUPER_KEY: &str = "super";
+const REPO_VERSION_KEY: &str = "version";
+const STORE_VERSION_KEY: &str = "version";
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("e2a9d4f7-6b3c-4d1e-8f5a-7c0b9e3d2a16");
+
+/// The URL of the endpoint for refreshing access tokens.
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// The base URL for requests which operate on file metadata.
+const API_URL: &str = "https://www.googleapis.com/drive/v3";
+
+/// The base URL for requests which upload file contents.
+const UPLOAD_URL: &str = "https://www.googleapis.com/upload/drive/v3";
+
+/// The MIME type of folders in Google Drive.
+const FOLDER_MIME_TYPE: &str = "application/vnd.google-apps.folder";
+
+/// The HTTP status code for a resumable upload which hasn't received every byte yet.
+const RESUME_INCOMPLETE_CODE: u16 = 308;
+
+/// The size in bytes above which blocks are uploaded with a resumable upload.
+const RESUMABLE_THRESHOLD: usize = 5 * 1024 * 1024;
+
+/// The size in bytes of each request in a resumable upload.
+///
+/// This must be a multiple of 256 KiB.
+const RESUMABLE_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// The maximum number of files to request in each page of a listing.
+const PAGE_SIZE: &str = "1000";
+
+/// The name of the service to use in error messages.
+const SERVICE_NAME: &str = "Google Drive";
+
+/// A page of the response to listing files.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileList {
+    files: Vec<FileInfo>,
+    next_page_token: Option<String>,
+}
+
+/// A file or folder in Google Drive.
+#[derive(Debug, Deserialize)]
+struct FileInfo {
+    id: String,
+    name: String,
+}
+
+/// The configuration for opening a [`GoogleDriveStore`].
+///
+/// [`GoogleDriveStore`]: crate::store::GoogleDriveStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-google-drive")))]
+pub struct GoogleDriveConfig {
+    /// The credentials for accessing the user's Google Drive.
+    ///
+    /// The access token must have a scope which allows reading and writing files in the folder,
+    /// like `https://www.googleapis.com/auth/drive.file` if your app created the folder.
+    pub credentials: OAuthCredentials,
+
+    /// The ID of the folder to store blocks in.
+    ///
+    /// This can be a folder in the user's drive or in a shared drive.
+    pub folder_id: String,
+}
+
+impl GoogleDriveConfig {
+    /// Return a new config for a store in the folder with the given `folder_id`.
+    pub fn new(credentials: OAuthCredentials, folder_id: impl Into<String>) -> Self {
+        GoogleDriveConfig {
+            credentials,
+            folder_id: folder_id.into(),
+        }
+    }
+}
+
+impl OpenStore for GoogleDriveConfig {
+    type Store = GoogleDriveStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let agent = AgentBuilder::new().build();
+        let mut store = GoogleDriveStore {
+            tokens: TokenSource::new(agent, SERVICE_NAME, TOKEN_URL, self.credentials.clone()),
+            store_folder_id: String::new(),
+            data_folder_id: String::new(),
+            locks_folder_id: String::new(),
+            headers_folder_id: String::new(),
+        };
+
+        store.store_folder_id = store
+            .make_folder(&self.folder_id, STORE_KEY)
+            .map_err(crate::Error::Store)?;
+        let store_folder_id = store.store_folder_id.clone();
+        store.data_folder_id = store
+            .make_folder(&store_folder_id, DATA_KEY)
+            .map_err(crate::Error::Store)?;
+        store.locks_folder_id = store
+            .make_folder(&store_folder_id, LOCKS_KEY)
+            .map_err(crate::Error::Store)?;
+        store.headers_folder_id = store
+            .make_folder(&store_folder_id, HEADERS_KEY)
+            .map_err(crate::Error::Store)?;
+
+        match store
+            .get(&self.folder_id, STORE_VERSION_KEY)
+            .map_err(crate::Error::Store)?
+        {
+            None => store
+                .put(
+                    &self.folder_id,
+                    STORE_VERSION_KEY,
+                    CURRENT_VERSION.as_bytes(),
+                )
+                .map_err(crate::Error::Store)?,
+            Some(version) => {
+                let version =
+                    Uuid::from_slice(&version).map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// A `DataStore` which stores data in a folder in Google Drive.
+///
+/// Blocks are stored as files in a folder in the user's drive or a shared drive using the Drive v3
+/// API, which is authorized with [`OAuthCredentials`]. Uploading new contents to a file is atomic,
+/// so blocks are written in place. Blocks which are larger than a few megabytes are uploaded with
+/// a resumable upload in several requests.
+///
+/// Google Drive identifies files by ID rather than by path, so reading or writing a block first
+/// looks up the ID of its file by name. Google Drive allows several files in a folder to have the
+/// same name, so only one client should write to the store at a time, which repositories already
+/// ensure with locks.
+///
+/// You can use [`GoogleDriveConfig`] to open a data store of this type.
+///
+/// [`OAuthCredentials`]: crate::store::OAuthCredentials
+/// [`GoogleDriveConfig`]: crate::store::GoogleDriveConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-google-drive")))]
+pub struct GoogleDriveStore {
+    tokens: TokenSource,
+    store_folder_id: String,
+    data_folder_id: String,
+    locks_folder_id: String,
+    headers_folder_id: String,
+}
+
+/// Escape `value` for use in a string literal in a Drive search query.
+fn escape_query(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+impl GoogleDriveStore {
+    /// Return the ID of the folder and the name of the file for the block with the given `key`.
+    fn block_location(&self, key: BlockKey) -> (String, String) {
+        match key {
+            BlockKey::Data(id) => (
+                self.data_folder_id.clone(),
+                id.as_ref().as_hyphenated().to_string(),
+            ),
+            BlockKey::Lock(id) => (
+                self.locks_folder_id.clone(),
+                id.as_ref().as_hyphenated().to_string(),
+            ),
+            BlockKey::Header(id) => (
+                self.headers_folder_id.clone(),
+                id.as_ref().as_hyphenated().to_string(),
+            ),
+            BlockKey::Super => (self.store_folder_id.clone(), String::from(SUPER_KEY)),
+            BlockKey::Version => (self.store_folder_id.clone(), String::from(REPO_VERSION_KEY)),
+        }
+    }
+
+    /// Send the request made by `request`.
+    ///
+    /// This returns `None` if the file does not exist.
+    fn send(
+        &mut self,
+        request: impl Fn(&ureq::Agent, &str) -> Result<Response, ureq::Error>,
+    ) -> super::Result<Option<Response>> {
+        check_response(SERVICE_NAME, self.tokens.send(request)?)
+    }
+
+    /// Return every file in the folder with the given `parent_id` which matches `query`.
+    fn search(&mut self, parent_id: &str, query: &str) -> super::Result<Vec<FileInfo>> {
+        let query = format!(
+            "'{}' in parents and trashed = false{}",
+            escape_query(parent_id),
+            query
+        );
+        let url = format!("{}/files", API_URL);
+        let mut files = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let response = self
+                .send(|agent, authorization| {
+                    let mut request = agent
+                        .get(&url)
+                        .set("Authorization", authorization)
+                        .query("q", &query)
+                        .query("fields", "nextPageToken,files(id,name)")
+                        .query("pageSize", PAGE_SIZE)
+                        .query("supportsAllDrives", "true")
+                        .query("includeItemsFromAllDrives", "true");
+                    if let Some(page_token) = &page_token {
+                        request = request.query("pageToken", page_token);
+                    }
+                    request.call()
+                })?
+                .ok_or_else(|| super::Error::msg("The folder does not exist."))?;
+            let page: FileList = serde_json::from_reader(response.into_reader())?;
+
+            files.extend(page.files);
+
+            match page.next_page_token {
+                Some(next_token) => page_token = Some(next_token),
+                None => return Ok(files),
+            }
+        }
+    }
+
+    /// Return the IDs of the files with the given `name` in the folder with the given `parent_id`.
+    fn find(&mut self, parent_id: &str, name: &str) -> super::Result<Vec<String>> {
+        let query = format!(" and name = '{}'", escape_query(name));
+        Ok(self
+            .search(parent_id, &query)?
+            .into_iter()
+            .map(|file| file.id)
+            .collect())
+    }
+
+    /// Return the ID of the folder with the given `name` in the folder with the given `parent_id`,
+    /// creating it if it doesn't exist.
+    fn make_folder(&mut self, parent_id: &str, name: &str) -> super::Result<String> {
+        let query = format!(
+            " and name = '{}' and mimeType = '{}'",
+            escape_query(name),
+            FOLDER_MIME_TYPE
+        );
+        if let Some(folder) = self.search(parent_id, &query)?.into_iter().next() {
+            return Ok(folder.id);
+        }
+
+        let url = format!("{}/files", API_URL);
+        let metadata = json!({
+            "name": name,
+            "mimeType": FOLDER_MIME_TYPE,
+            "parents": [parent_id],
+        })
+        .to_string();
+        let response = self
+            .send(|agent, authorization| {
+                agent
+                    .post(&url)
+                    .set("Authorization", authorization)
+                    .query("fields", "id,name")
+                    .query("supportsAllDrives", "true")
+                    .set("Content-Type", "application/json; charset=UTF-8")
+                    .send_string(&metadata)
+            })?
+            .ok_or_else(|| super::Error::msg("The parent folder does not exist."))?;
+        let folder: FileInfo = serde_json::from_reader(response.into_reader())?;
+        Ok(folder.id)
+    }
+
+    /// Return a response with the contents of the file with the given `name` in the folder with
+    /// the given `parent_id`.
+    ///
+    /// This returns `None` if the file does not exist.
+    fn download(&mut self, parent_id: &str, name: &str) -> super::Result<Option<Response>> {
+        let file_id = match self.find(parent_id, name)?.into_iter().next() {
+            Some(file_id) => file_id,
+            None => return Ok(None),
+        };
+        let url = format!("{}/files/{}", API_URL, file_id);
+        self.send(|agent, authorization| {
+            agent
+                .get(&url)
+                .set("Authorization", authorization)
+                .query("alt", "media")
+                .query("supportsAllDrives", "true")
+                .call()
+        })
+    }
+
+    /// Return the contents of the file with the given `name` in the folder with the given
+    /// `parent_id` or `None` if it does not exist.
+    fn get(&mut self, parent_id: &str, name: &str) -> super::Result<Option<Vec<u8>>> {
+        match self.download(parent_id, name)? {
+            Some(response) => {
+                let mut data = Vec::new();
+                response.into_reader().read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Upload `data` to the file with the given `name` in the folder with the given `parent_id`,
+    /// replacing its contents if it exists.
+    fn put(&mut self, parent_id: &str, name: &str, data: &[u8]) -> super::Result<()> {
+        let file_id = self.find(parent_id, name)?.into_iter().next();
+
+        if data.len() > RESUMABLE_THRESHOLD {
+            return self.put_resumable(parent_id, name, file_id.as_deref(), data);
+        }
+
+        match file_id {
+            Some(file_id) => {
+                let url = format!("{}/files/{}", UPLOAD_URL, file_id);
+                self.send(|agent, authorization| {
+                    agent
+                        .request("PATCH", &url)
+                        .set("Authorization", authorization)
+                        .query("uploadType", "media")
+                        .query("supportsAllDrives", "true")
+                        .set("Content-Type", "application/octet-stream")
+                        .send_bytes(data)
+                })?
+                .ok_or_else(|| super::Error::msg("The file does not exist."))?;
+            }
+            None => {
+                // Upload the metadata and the contents of the new file in one request.
+                let boundary = Uuid::new_v4().simple().to_string();
+                let metadata = json!({ "name": name, "parents": [parent_id] });
+                let mut body = Vec::with_capacity(data.len() + 512);
+                body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+                body.extend_from_slice(b"Content-Type: application/json; charset=UTF-8\r\n\r\n");
+                body.extend_from_slice(format!("{}\r\n--{}\r\n", metadata, boundary).as_bytes());
+                body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+                body.extend_from_slice(data);
+                body.extend_from_slice(format!("\r\n--{}--", boundary).as_bytes());
+
+                let url = format!("{}/files", UPLOAD_URL);
+                let content_type = format!("multipart/related; boundary={}", boundary);
+                self.send(|agent, authorization| {
+                    agent
+                        .post(&url)
+                        .set("Authorization", authorization)
+                        .query("uploadType", "multipart")
+                        .query("supportsAllDrives", "true")
+                        .set("Content-Type", &content_type)
+                        .send_bytes(&body)
+                })?
+                .ok_or_else(|| super::Error::msg("The parent folder does not exist."))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Upload `data` to the file with the given `name` in the folder with the given `parent_id`
+    /// using a resumable upload.
+    ///
+    /// If `file_id` is `Some`, this replaces the contents of that file.
+    fn put_resumable(
+        &mut self,
+        parent_id: &str,
+        name: &str,
+        file_id: Option<&str>,
+        data: &[u8],
+    ) -> super::Result<()> {
+        let (method, url, metadata) = match file_id {
+            Some(file_id) => (
+                "PATCH",
+                format!("{}/files/{}", UPLOAD_URL, file_id),
+                json!({}),
+            ),
+            None => (
+                "POST",
+                format!("{}/files", UPLOAD_URL),
+                json!({ "name": name, "parents": [parent_id] }),
+            ),
+        };
+        let metadata = metadata.to_string();
+        let content_length = data.len().to_string();
+
+        let response = self
+            .send(|agent, authorization| {
+                agent
+                    .request(method, &url)
+                    .set("Authorization", authorization)
+                    .query("uploadType", "resumable")
+                    .query("supportsAllDrives", "true")
+                    .set("Content-Type", "application/json; charset=UTF-8")
+                    .set("X-Upload-Content-Type", "application/octet-stream")
+                    .set("X-Upload-Content-Length", &content_length)
+                    .send_string(&metadata)
+            })?
+            .ok_or_else(|| super::Error::msg("The file does not exist."))?;
+        let session_url = response
+            .header("Location")
+            .ok_or_else(|| super::Error::msg("The resumable upload has no session URL."))?
+            .to_owned();
+
+        let mut offset = 0;
+        for chunk in data.chunks(RESUMABLE_CHUNK_SIZE) {
+            let content_range = format!(
+                "bytes {}-{}/{}",
+                offset,
+                offset + chunk.len() - 1,
+                data.len()
+            );
+            let response = self
+                .send(|agent, authorization| {
+                    agent
+                        .put(&session_url)
+                        .set("Authorization", authorization)
+                        .set("Content-Range", &content_range)
+                        .send_bytes(chunk)
+                })?
+                .ok_or_else(|| super::Error::msg("The resumable upload does not exist."))?;
+            offset += chunk.len();
+
+            let complete = response.status() != RESUME_INCOMPLETE_CODE;
+            if complete != (offset == data.len()) {
+                return Err(super::Error::msg(
+                    "The resumable upload did not receive the expected number of bytes.",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove every file with the given `name` in the folder with the given `parent_id`.
+    fn delete(&mut self, parent_id: &str, name: &str) -> super::Result<()> {
+        for file_id in self.find(parent_id, name)? {
+            let url = format!("{}/files/{}", API_URL, file_id);
+            self.send(|agent, authorization| {
+                agent
+                    .delete(&url)
+                    .set("Authorization", authorization)
+                    .query("supportsAllDrives", "true")
+                    .call()
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl DataStore for GoogleDriveStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let (parent_id, name) = self.block_location(key);
+        self.put(&parent_id, &name, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let (parent_id, name) = self.block_location(key);
+        self.get(&parent_id, &name)
+    }
+
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        let (parent_id, name) = self.block_location(key);
+        Ok(self
+            .download(&parent_id, &name)?
+            .map(|response| Box::new(response.into_reader()) as Box<dyn Read>))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let (parent_id, name) = self.block_location(key);
+        self.delete(&parent_id, &name)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let folder_id = match kind {
+            BlockType::Data => self.data_folder_id.clone(),
+            BlockType::Lock => self.locks_folder_id.clone(),
+            BlockType::Header => self.headers_folder_id.clone(),
+        };
+        let mut block_ids = self
+            .search(&folder_id, "")?
+            .iter()
+            .map(|file| Uuid::parse_str(&file.name).map(|id| id.into()))
+            .collect::<Result<Vec<BlockId>, _>>()?;
+
+        // A block may have been written twice concurrently, leaving two files with its name.
+        block_ids.sort_unstable_by_key(|id| *id.as_ref());
+        block_ids.dedup();
+
+        Ok(block_ids)
+    }
+}
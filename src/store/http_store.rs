@@ -0,0 +1,196 @@
+#![cfg(feature = "store-http")]
+
+use std::io::Read;
+
+use ureq::{Agent, AgentBuilder, Response};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::open_store::OpenStore;
+use super::rest::{check_response, join_key};
+use super::ErrorKind;
+
+// The names of files in the data store. These match the layout of a `DirectoryStore`.
+const STORE_KEY: &str = "store";
+const DATA_KEY: &str = "data";
+const LOCKS_KEY: &str = "locks";
+const HEADERS_KEY: &str = "headers";
+const SUPER_KEY: &str = "super";
+const REPO_VERSION_KEY: &str = "version";
+const STORE_VERSION_KEY: &str = "version";
+
+/// The version ID of the `DirectoryStore` format, which is the format this store reads.
+const CURRENT_VERSION: &str = "9ab66f8a-f883-11eb-b994-734187b3c515";
+
+/// The placeholder in a URL pattern which is replaced with the path of a file.
+const PATH_PLACEHOLDER: &str = "{path}";
+
+/// The name of the service to use in error messages.
+const SERVICE_NAME: &str = "HTTP";
+
+/// The configuration for opening an [`HttpStore`].
+///
+/// [`HttpStore`]: crate::store::HttpStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-http")))]
+pub struct HttpConfig {
+    /// The pattern for the URL of each file in the store.
+    ///
+    /// The URL of a file is this pattern with `{path}` replaced by the path of the file relative
+    /// to the root of the store, like `store/super`. This allows for URLs which need a query
+    /// string, like signed CDN URLs.
+    pub url_pattern: String,
+
+    /// Additional headers to send with each request, like an `Authorization` header.
+    pub headers: Vec<(String, String)>,
+}
+
+impl HttpConfig {
+    /// Return a new config for a store published at the given base `url`.
+    pub fn new(url: impl AsRef<str>) -> Self {
+        HttpConfig {
+            url_pattern: format!(
+                "{}/{}",
+                url.as_ref().trim_end_matches('/'),
+                PATH_PLACEHOLDER
+            ),
+            headers: Vec::new(),
+        }
+    }
+}
+
+impl OpenStore for HttpConfig {
+    type Store = HttpStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        if !self.url_pattern.contains(PATH_PLACEHOLDER) {
+            return Err(crate::Error::UnsupportedStore);
+        }
+
+        let store = HttpStore {
+            agent: AgentBuilder::new().build(),
+            config: self.clone(),
+        };
+
+        let version = store
+            .get(STORE_VERSION_KEY)
+            .map_err(crate::Error::Store)?
+            .ok_or(crate::Error::UnsupportedStore)?;
+        if version != CURRENT_VERSION.as_bytes() {
+            return Err(crate::Error::UnsupportedStore);
+        }
+
+        Ok(store)
+    }
+}
+
+/// A read-only `DataStore` which reads blocks from a static HTTP server.
+///
+/// This reads a [`DirectoryStore`] which has been published to a static web server or CDN as-is,
+/// so a repository can be written locally and then opened from anywhere. Each block is fetched
+/// with a `GET` request for its file, and large blocks are streamed rather than buffered.
+///
+/// Writing or removing a block always fails with an error wrapping `Error::ReadOnly`, so
+/// repositories can only be opened in this store with [`OpenMode::ReadOnly`]. Static web servers
+/// can't list files, so listing blocks fails with an error of kind [`ErrorKind::Unsupported`].
+/// This means operations which scan the data store, like verifying a repository, aren't
+/// available.
+///
+/// You can use [`HttpConfig`] to open a data store of this type.
+///
+/// [`DirectoryStore`]: crate::store::DirectoryStore
+/// [`OpenMode::ReadOnly`]: crate::repo::OpenMode::ReadOnly
+/// [`ErrorKind::Unsupported`]: crate::store::ErrorKind::Unsupported
+/// [`HttpConfig`]: crate::store::HttpConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-http")))]
+pub struct HttpStore {
+    agent: Agent,
+    config: HttpConfig,
+}
+
+impl HttpStore {
+    /// Return the path of the file for the block with the given `key`.
+    fn block_path(&self, key: BlockKey) -> String {
+        match key {
+            BlockKey::Data(id) => {
+                let uuid_str = id.as_ref().as_hyphenated().to_string();
+                join_key(&[STORE_KEY, DATA_KEY, &uuid_str[..2], &uuid_str])
+            }
+            BlockKey::Lock(id) => join_key(&[
+                STORE_KEY,
+                LOCKS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Header(id) => join_key(&[
+                STORE_KEY,
+                HEADERS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Super => join_key(&[STORE_KEY, SUPER_KEY]),
+            BlockKey::Version => join_key(&[STORE_KEY, REPO_VERSION_KEY]),
+        }
+    }
+
+    /// Return a response with the contents of the file at `path`.
+    ///
+    /// This returns `None` if the file does not exist.
+    fn download(&self, path: &str) -> super::Result<Option<Response>> {
+        let url = self.config.url_pattern.replace(PATH_PLACEHOLDER, path);
+        let mut request = self.agent.get(&url);
+        for (name, value) in &self.config.headers {
+            request = request.set(name, value);
+        }
+        check_response(SERVICE_NAME, request.call())
+    }
+
+    /// Return the contents of the file at `path` or `None` if it does not exist.
+    fn get(&self, path: &str) -> super::Result<Option<Vec<u8>>> {
+        match self.download(path)? {
+            Some(response) => {
+                let mut data = Vec::new();
+                response.into_reader().read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl DataStore for HttpStore {
+    fn write_block(&mut self, _key: BlockKey, _data: &[u8]) -> super::Result<()> {
+        Err(super::Error::new(crate::Error::ReadOnly))
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.get(&self.block_path(key))
+    }
+
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        Ok(self
+            .download(&self.block_path(key))?
+            .map(|response| Box::new(response.into_reader()) as Box<dyn Read>))
+    }
+
+    fn remove_block(&mut self, _key: BlockKey) -> super::Result<()> {
+        Err(super::Error::new(crate::Error::ReadOnly))
+    }
+
+    fn list_blocks(&mut self, _kind: BlockType) -> super::Result<Vec<BlockId>> {
+        Err(
+            super::Error::msg("Blocks can't be listed on a static HTTP server.")
+                .with_kind(ErrorKind::Unsupported),
+        )
+    }
+
+    fn write_blocks(&mut self, _blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        Err(super::Error::new(crate::Error::ReadOnly))
+    }
+
+    fn remove_blocks(&mut self, _keys: &[BlockKey]) -> super::Result<()> {
+        Err(super::Error::new(crate::Error::ReadOnly))
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,400 @@
+#![cfg(feature = "store-ipfs")]
+
+use std::io::Read;
+
+use serde::Deserialize;
+use ureq::{Agent, AgentBuilder, Response};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::open_store::OpenStore;
+use super::rest::{join_key, SEPARATOR};
+
+// The names of files in the data store.
+const STORE_KEY: &str = "store";
+const DATA_KEY: &str = "data";
+const LOCKS_KEY: &str = "lock";
+const HEADERS_KEY: &str = "header";
+const SUPER_KEY: &str = "super";
+const REPO_VERSION_KEY: &str = "version";
+const STORE_VERSION_KEY: &str = "version";
+const STAGING_KEY: &str = "stage";
+const TEMP_KEY: &str = "tmp";
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("3f8a2c5e-7d1b-4e9f-a6c0-5b2d8e4f1a73");
+
+/// The default URL of the IPFS node's HTTP API.
+const DEFAULT_API_URL: &str = "http://127.0.0.1:5001";
+
+/// The name of the service to use in error messages.
+const SERVICE_NAME: &str = "IPFS";
+
+/// The HTTP status code the IPFS API uses for every failed command.
+const COMMAND_ERROR_CODE: u16 = 500;
+
+/// The error message the IPFS API returns when a file in MFS does not exist.
+const NOT_EXIST_MESSAGE: &str = "does not exist";
+
+/// An error returned by the IPFS API.
+#[derive(Debug, Deserialize)]
+struct CommandError {
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+/// The response to a `files/ls` command.
+#[derive(Debug, Deserialize)]
+struct DirectoryListing {
+    #[serde(rename = "Entries")]
+    entries: Option<Vec<DirectoryEntry>>,
+}
+
+/// An entry in a `DirectoryListing`.
+#[derive(Debug, Deserialize)]
+struct DirectoryEntry {
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+/// The response to a `files/stat` command.
+#[derive(Debug, Deserialize)]
+struct FileStat {
+    #[serde(rename = "Hash")]
+    hash: String,
+}
+
+/// The configuration for opening an [`IpfsStore`].
+///
+/// [`IpfsStore`]: crate::store::IpfsStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-ipfs")))]
+pub struct IpfsConfig {
+    /// The URL of the IPFS node's HTTP API.
+    ///
+    /// By default, this is `http://127.0.0.1:5001`.
+    pub api_url: String,
+
+    /// The path of the directory in the node's mutable file system (MFS) to store blocks in.
+    pub root: String,
+}
+
+impl IpfsConfig {
+    /// Return a new config for a store in the MFS directory at `root` on the local IPFS node.
+    pub fn new(root: impl Into<String>) -> Self {
+        IpfsConfig {
+            api_url: String::from(DEFAULT_API_URL),
+            root: root.into(),
+        }
+    }
+}
+
+impl OpenStore for IpfsConfig {
+    type Store = IpfsStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let mut store = IpfsStore {
+            agent: AgentBuilder::new().build(),
+            api_url: format!("{}/api/v0", self.api_url.trim_end_matches('/')),
+            root: self.root.trim_matches('/').to_owned(),
+        };
+
+        for directory in [DATA_KEY, LOCKS_KEY, HEADERS_KEY] {
+            let path = store.path(&[STORE_KEY, directory]);
+            store.make_directory(&path).map_err(crate::Error::Store)?;
+        }
+        for directory in [STAGING_KEY, TEMP_KEY] {
+            let path = store.path(&[directory]);
+            store.make_directory(&path).map_err(crate::Error::Store)?;
+        }
+
+        let version_path = store.path(&[STORE_VERSION_KEY]);
+        match store.read(&version_path).map_err(crate::Error::Store)? {
+            None => store
+                .write(&version_path, CURRENT_VERSION.as_bytes())
+                .map_err(crate::Error::Store)?,
+            Some(version) => {
+                let version =
+                    Uuid::from_slice(&version).map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// A `DataStore` which stores data on an IPFS node.
+///
+/// Blocks are stored as files in a directory in the node's mutable file system (MFS) using its
+/// HTTP API. MFS maps the path of each block to the content identifier (CID) of its contents, and
+/// files in MFS are never garbage collected by the node, so blocks don't need to be pinned
+/// separately. You can use [`root_cid`] to get the CID of the whole store, for example to pin it on
+/// other nodes.
+///
+/// Blocks are staged before they're moved into place so that writes are atomic, which takes
+/// several API calls for each write.
+///
+/// You can use [`IpfsConfig`] to open a data store of this type.
+///
+/// [`root_cid`]: crate::store::IpfsStore::root_cid
+/// [`IpfsConfig`]: crate::store::IpfsConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-ipfs")))]
+pub struct IpfsStore {
+    agent: Agent,
+    api_url: String,
+    root: String,
+}
+
+impl IpfsStore {
+    /// Return the content identifier (CID) of the store's root directory.
+    ///
+    /// This changes every time the store is modified.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with the IPFS node.
+    pub fn root_cid(&mut self) -> crate::Result<String> {
+        let path = self.path(&[]);
+        let response = self
+            .command("files/stat", &[&path])
+            .map_err(crate::Error::Store)?
+            .ok_or_else(|| crate::Error::Store(super::Error::msg("The store does not exist.")))?;
+        let stat: FileStat = serde_json::from_reader(response.into_reader())
+            .map_err(|error| crate::Error::Store(error.into()))?;
+        Ok(stat.hash)
+    }
+
+    /// Return the absolute MFS path of the file with the given path `segments` in the store.
+    fn path(&self, segments: &[&str]) -> String {
+        let mut all_segments = vec![self.root.as_str()];
+        all_segments.extend_from_slice(segments);
+        format!("{}{}", SEPARATOR, join_key(&all_segments))
+    }
+
+    /// Return the name of the file for the block with the given `key`.
+    fn block_name(key: BlockKey) -> String {
+        match key {
+            BlockKey::Data(id) => format!("{}-{}", DATA_KEY, id.as_ref().as_hyphenated()),
+            BlockKey::Lock(id) => format!("{}-{}", LOCKS_KEY, id.as_ref().as_hyphenated()),
+            BlockKey::Header(id) => format!("{}-{}", HEADERS_KEY, id.as_ref().as_hyphenated()),
+            BlockKey::Super => String::from(SUPER_KEY),
+            BlockKey::Version => String::from(REPO_VERSION_KEY),
+        }
+    }
+
+    /// Return the MFS path of the block with the given `key`.
+    fn block_path(&self, key: BlockKey) -> String {
+        match key {
+            BlockKey::Data(id) => self.path(&[
+                STORE_KEY,
+                DATA_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Lock(id) => self.path(&[
+                STORE_KEY,
+                LOCKS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Header(id) => self.path(&[
+                STORE_KEY,
+                HEADERS_KEY,
+                &id.as_ref().as_hyphenated().to_string(),
+            ]),
+            BlockKey::Super => self.path(&[STORE_KEY, SUPER_KEY]),
+            BlockKey::Version => self.path(&[STORE_KEY, REPO_VERSION_KEY]),
+        }
+    }
+
+    /// Return the MFS path where a complete copy of the block with the given `key` is staged.
+    fn staging_path(&self, key: BlockKey) -> String {
+        self.path(&[STAGING_KEY, &Self::block_name(key)])
+    }
+
+    /// Send the API command with the given `name` and `args` and return its response.
+    ///
+    /// This returns `None` if the command failed because a file does not exist.
+    fn command(&self, name: &str, args: &[&str]) -> super::Result<Option<Response>> {
+        self.send(name, args, &[], None)
+    }
+
+    /// Send the API command with the given `name`, `args`, `options`, and file `body`.
+    ///
+    /// This returns `None` if the command failed because a file does not exist. Errors don't
+    /// include the URL of the request.
+    fn send(
+        &self,
+        name: &str,
+        args: &[&str],
+        options: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> super::Result<Option<Response>> {
+        let mut request = self.agent.post(&format!("{}/{}", self.api_url, name));
+        for arg in args {
+            request = request.query("arg", arg);
+        }
+        for (option, value) in options {
+            request = request.query(option, value);
+        }
+
+        let result = match body {
+            Some(data) => {
+                let boundary = Uuid::new_v4().as_simple().to_string();
+                request
+                    .set(
+                        "Content-Type",
+                        &format!("multipart/form-data; boundary={}", boundary),
+                    )
+                    .send_bytes(&multipart_body(&boundary, data))
+            }
+            None => request.call(),
+        };
+
+        match result {
+            Ok(response) => Ok(Some(response)),
+            Err(ureq::Error::Status(COMMAND_ERROR_CODE, response)) => {
+                let error: CommandError = serde_json::from_reader(response.into_reader())?;
+                if error.message.contains(NOT_EXIST_MESSAGE) {
+                    Ok(None)
+                } else {
+                    Err(super::Error::msg(format!(
+                        "{} command {} failed: {}",
+                        SERVICE_NAME, name, error.message
+                    )))
+                }
+            }
+            Err(ureq::Error::Status(code, response)) => Err(super::Error::msg(format!(
+                "{} request failed with status {}: {}",
+                SERVICE_NAME,
+                code,
+                response.status_text()
+            ))),
+            Err(ureq::Error::Transport(transport)) => Err(super::Error::msg(format!(
+                "{} request failed: {}: {}",
+                SERVICE_NAME,
+                transport.kind(),
+                transport.message().unwrap_or("no details")
+            ))),
+        }
+    }
+
+    /// Create the directory at `path` and its parents if they don't exist.
+    fn make_directory(&self, path: &str) -> super::Result<()> {
+        self.send("files/mkdir", &[path], &[("parents", "true")], None)?;
+        Ok(())
+    }
+
+    /// Return the contents of the file at `path` or `None` if it does not exist.
+    fn read(&self, path: &str) -> super::Result<Option<Vec<u8>>> {
+        match self.command("files/read", &[path])? {
+            Some(response) => {
+                let mut data = Vec::new();
+                response.into_reader().read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Write `data` to the file at `path`, replacing it if it exists.
+    ///
+    /// This is not atomic.
+    fn write(&self, path: &str, data: &[u8]) -> super::Result<()> {
+        self.send(
+            "files/write",
+            &[path],
+            &[("create", "true"), ("truncate", "true")],
+            Some(data),
+        )?
+        .ok_or_else(|| super::Error::msg("The store directory does not exist."))?;
+        Ok(())
+    }
+
+    /// Remove the file at `path` if it exists.
+    fn remove(&self, path: &str) -> super::Result<()> {
+        self.command("files/rm", &[path])?;
+        Ok(())
+    }
+
+    /// Move the file at `source` to `destination`, replacing it if it exists.
+    fn replace(&self, source: &str, destination: &str) -> super::Result<()> {
+        self.remove(destination)?;
+        self.command("files/mv", &[source, destination])?
+            .ok_or_else(|| super::Error::msg("The file to move does not exist."))?;
+        Ok(())
+    }
+}
+
+/// Return a `multipart/form-data` body with the given `boundary` containing `data` as a file.
+fn multipart_body(boundary: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(data.len() + 256);
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"block\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n",
+            boundary
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+impl DataStore for IpfsStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        // Moving a file in MFS is atomic, but replacing one takes two commands. The block is
+        // written to a temporary file and moved to a staging path, so there is always a complete
+        // copy of it in the staging path until it's moved into place. Reads fall back to the
+        // staging path if the block is missing.
+        let temp_path = self.path(&[TEMP_KEY, &Uuid::new_v4().as_hyphenated().to_string()]);
+        let staging_path = self.staging_path(key);
+        let block_path = self.block_path(key);
+
+        self.write(&temp_path, data)?;
+        self.replace(&temp_path, &staging_path)?;
+        self.replace(&staging_path, &block_path)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        match self.read(&self.block_path(key))? {
+            Some(data) => Ok(Some(data)),
+            None => self.read(&self.staging_path(key)),
+        }
+    }
+
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        let response = match self.command("files/read", &[&self.block_path(key)])? {
+            Some(response) => Some(response),
+            None => self.command("files/read", &[&self.staging_path(key)])?,
+        };
+        Ok(response.map(|response| Box::new(response.into_reader()) as Box<dyn Read>))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.remove(&self.staging_path(key))?;
+        self.remove(&self.block_path(key))
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let directory = match kind {
+            BlockType::Data => self.path(&[STORE_KEY, DATA_KEY]),
+            BlockType::Lock => self.path(&[STORE_KEY, LOCKS_KEY]),
+            BlockType::Header => self.path(&[STORE_KEY, HEADERS_KEY]),
+        };
+        let response = self
+            .command("files/ls", &[&directory])?
+            .ok_or_else(|| super::Error::msg("The store directory does not exist."))?;
+        let listing: DirectoryListing = serde_json::from_reader(response.into_reader())?;
+
+        let block_ids = listing
+            .entries
+            .unwrap_or_default()
+            .iter()
+            .map(|entry| Uuid::parse_str(&entry.name).map(|id| id.into()))
+            .collect::<Result<Vec<BlockId>, _>>()?;
+        Ok(block_ids)
+    }
+}
@@ -1,9 +1,25 @@
 use std::collections::HashMap;
+use std::io::{self, Read, Write};
 use std::sync::{Arc, Mutex};
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, LatencyClass, StoreCapabilities};
 use super::open_store::OpenStore;
 
+/// A UUID which acts as the version ID of the format used by `dump` and `load`.
+const DUMP_VERSION: Uuid = uuid!("0f6c2d8e-3a71-4b95-b4e2-9d8a5c17e3f0");
+
+// The tags which identify the kind of each block in a dump.
+const DATA_TAG: u8 = 0;
+const LOCK_TAG: u8 = 1;
+const HEADER_TAG: u8 = 2;
+const SUPER_TAG: u8 = 3;
+const VERSION_TAG: u8 = 4;
+
+/// The tag which marks the end of a dump.
+const END_TAG: u8 = 0xff;
+
 #[derive(Debug, Clone, Default)]
 struct BlockMap {
     data: HashMap<BlockId, Vec<u8>>,
@@ -13,6 +29,99 @@ struct BlockMap {
     version: Option<Vec<u8>>,
 }
 
+impl BlockMap {
+    /// Serialize every block to `writer`.
+    fn dump(&self, mut writer: impl Write) -> crate::Result<()> {
+        writer.write_all(DUMP_VERSION.as_bytes())?;
+
+        let blocks = self
+            .data
+            .iter()
+            .map(|(id, data)| (DATA_TAG, *id.as_ref(), data))
+            .chain(
+                self.locks
+                    .iter()
+                    .map(|(id, data)| (LOCK_TAG, *id.as_ref(), data)),
+            )
+            .chain(
+                self.headers
+                    .iter()
+                    .map(|(id, data)| (HEADER_TAG, *id.as_ref(), data)),
+            )
+            .chain(
+                self.superblock
+                    .iter()
+                    .map(|data| (SUPER_TAG, Uuid::nil(), data)),
+            )
+            .chain(
+                self.version
+                    .iter()
+                    .map(|data| (VERSION_TAG, Uuid::nil(), data)),
+            );
+
+        for (tag, id, data) in blocks {
+            writer.write_all(&[tag])?;
+            writer.write_all(id.as_bytes())?;
+            writer.write_all(&(data.len() as u64).to_le_bytes())?;
+            writer.write_all(data)?;
+        }
+        writer.write_all(&[END_TAG])?;
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Deserialize blocks from `reader` which were serialized with `dump`.
+    fn load(mut reader: impl Read) -> crate::Result<Self> {
+        let mut version = [0u8; 16];
+        read_dump(&mut reader, &mut version)?;
+        if Uuid::from_bytes(version) != DUMP_VERSION {
+            return Err(crate::Error::UnsupportedStore);
+        }
+
+        let mut block_map = BlockMap::default();
+        loop {
+            let mut tag = [0u8; 1];
+            read_dump(&mut reader, &mut tag)?;
+            if tag[0] == END_TAG {
+                break;
+            }
+
+            let mut id = [0u8; 16];
+            let mut len = [0u8; 8];
+            read_dump(&mut reader, &mut id)?;
+            read_dump(&mut reader, &mut len)?;
+            let len = u64::from_le_bytes(len);
+
+            // Don't trust the length enough to allocate it all up front.
+            let mut data = Vec::new();
+            if (&mut reader).take(len).read_to_end(&mut data)? as u64 != len {
+                return Err(crate::Error::InvalidData);
+            }
+
+            let id = BlockId::from(Uuid::from_bytes(id));
+            match tag[0] {
+                DATA_TAG => block_map.data.insert(id, data),
+                LOCK_TAG => block_map.locks.insert(id, data),
+                HEADER_TAG => block_map.headers.insert(id, data),
+                SUPER_TAG => block_map.superblock.replace(data),
+                VERSION_TAG => block_map.version.replace(data),
+                _ => return Err(crate::Error::InvalidData),
+            };
+        }
+
+        Ok(block_map)
+    }
+}
+
+/// Fill `buf` from a dump in `reader`, returning `Error::InvalidData` if the dump ends early.
+fn read_dump(reader: &mut impl Read, buf: &mut [u8]) -> crate::Result<()> {
+    reader.read_exact(buf).map_err(|error| match error.kind() {
+        io::ErrorKind::UnexpectedEof => crate::Error::InvalidData,
+        _ => crate::Error::Io(error),
+    })
+}
+
 /// The configuration for opening a [`MemoryStore`].
 ///
 /// [`MemoryStore`]: crate::store::MemoryStore
@@ -24,6 +133,33 @@ impl MemoryConfig {
     pub fn new() -> Self {
         MemoryConfig(Arc::new(Mutex::new(BlockMap::default())))
     }
+
+    /// Create a new `MemoryConfig` containing the blocks which were serialized to `reader`.
+    ///
+    /// The blocks must have been serialized with [`MemoryConfig::dump`] or [`MemoryStore::dump`].
+    ///
+    /// # Errors
+    /// - `Error::UnsupportedStore`: The blocks were serialized with an incompatible format.
+    /// - `Error::InvalidData`: The serialized blocks are invalid or incomplete.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`MemoryConfig::dump`]: crate::store::MemoryConfig::dump
+    /// [`MemoryStore::dump`]: crate::store::MemoryStore::dump
+    pub fn load(reader: impl Read) -> crate::Result<Self> {
+        Ok(MemoryConfig(Arc::new(Mutex::new(BlockMap::load(reader)?))))
+    }
+
+    /// Serialize the contents of the store to `writer`.
+    ///
+    /// This writes every block in the store, so the store can be restored with [`load`].
+    ///
+    /// # Errors
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`load`]: crate::store::MemoryConfig::load
+    pub fn dump(&self, writer: impl Write) -> crate::Result<()> {
+        self.0.lock().unwrap().dump(writer)
+    }
 }
 
 impl OpenStore for MemoryConfig {
@@ -41,16 +177,36 @@ impl OpenStore for MemoryConfig {
 /// Unlike other `DataStore` implementations, data in a `MemoryStore` is not stored persistently
 /// and is only accessible to the current process. This data store is useful for testing.
 ///
+/// The contents of the store can be persisted manually with [`dump`] and restored with
+/// [`MemoryConfig::load`], which is useful for test fixtures and small repositories.
+///
 /// None of the methods in this data store will ever return `Err`.
 ///
 /// You can use [`MemoryConfig`] to open a data store of this type.
 ///
+/// [`dump`]: crate::store::MemoryStore::dump
+/// [`MemoryConfig::load`]: crate::store::MemoryConfig::load
 /// [`MemoryConfig`]: crate::store::MemoryConfig
 #[derive(Debug)]
 pub struct MemoryStore {
     blocks: Arc<Mutex<BlockMap>>,
 }
 
+impl MemoryStore {
+    /// Serialize the contents of this store to `writer`.
+    ///
+    /// This writes every block in the store, so the store can be restored with
+    /// [`MemoryConfig::load`].
+    ///
+    /// # Errors
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`MemoryConfig::load`]: crate::store::MemoryConfig::load
+    pub fn dump(&self, writer: impl Write) -> crate::Result<()> {
+        self.blocks.lock().unwrap().dump(writer)
+    }
+}
+
 impl DataStore for MemoryStore {
     fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
         let mut block_map = self.blocks.lock().unwrap();
@@ -107,6 +263,18 @@ impl DataStore for MemoryStore {
         Ok(())
     }
 
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let block_map = self.blocks.lock().unwrap();
+        let block = match key {
+            BlockKey::Data(id) => block_map.data.get(&id),
+            BlockKey::Lock(id) => block_map.locks.get(&id),
+            BlockKey::Header(id) => block_map.headers.get(&id),
+            BlockKey::Super => block_map.superblock.as_ref(),
+            BlockKey::Version => block_map.version.as_ref(),
+        };
+        Ok(block.map(|data| data.len() as u64))
+    }
+
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
         let block_map = self.blocks.lock().unwrap();
         Ok(match kind {
@@ -115,4 +283,11 @@ impl DataStore for MemoryStore {
             BlockType::Header => block_map.headers.keys().copied().collect(),
         })
     }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            latency: LatencyClass::Memory,
+            ..StoreCapabilities::default()
+        }
+    }
 }
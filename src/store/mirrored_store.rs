@@ -0,0 +1,304 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// One of the two data stores in a [`MirroredStore`].
+///
+/// [`MirroredStore`]: crate::store::MirroredStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MirrorSide {
+    /// The primary data store, which is read from first.
+    Primary,
+
+    /// The secondary data store.
+    Secondary,
+}
+
+/// The configuration for opening a [`MirroredStore`].
+///
+/// Opening the store fails if either data store can't be opened.
+///
+/// [`MirroredStore`]: crate::store::MirroredStore
+#[derive(Debug, Clone)]
+pub struct MirroredConfig<A: OpenStore, B: OpenStore> {
+    /// The configuration for the primary data store.
+    pub primary: A,
+
+    /// The configuration for the secondary data store.
+    pub secondary: B,
+}
+
+impl<A: OpenStore, B: OpenStore> OpenStore for MirroredConfig<A, B> {
+    type Store = MirroredStore<A::Store, B::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(MirroredStore::new(
+            self.primary.open()?,
+            self.secondary.open()?,
+        ))
+    }
+}
+
+/// A `DataStore` which mirrors every block to two other data stores.
+///
+/// Every block is written to and removed from both data stores, and blocks are read from the
+/// primary data store unless it fails or is stale, in which case they are read from the secondary
+/// data store. This provides redundancy without any changes to repositories.
+///
+/// An operation which changes the store only fails if it fails on both data stores. If it fails on
+/// only one of them, that data store is marked as stale and is no longer preferred for reads. Once
+/// the stale data store is available again, use [`repair`] to bring it back in sync.
+///
+/// Which data store is stale is only tracked for the lifetime of this value. If a data store may
+/// have missed changes before this value was created, use [`repair_from`] to resync it from the
+/// other data store before opening a repository.
+///
+/// You can use [`MirroredConfig`] to open a data store of this type.
+///
+/// [`repair`]: crate::store::MirroredStore::repair
+/// [`repair_from`]: crate::store::MirroredStore::repair_from
+/// [`MirroredConfig`]: crate::store::MirroredConfig
+#[derive(Debug)]
+pub struct MirroredStore<A: DataStore, B: DataStore> {
+    primary: A,
+    secondary: B,
+    primary_stale: bool,
+    secondary_stale: bool,
+}
+
+impl<A: DataStore, B: DataStore> MirroredStore<A, B> {
+    /// Mirror blocks to the given `primary` and `secondary` data stores.
+    pub fn new(primary: A, secondary: B) -> Self {
+        Self {
+            primary,
+            secondary,
+            primary_stale: false,
+            secondary_stale: false,
+        }
+    }
+
+    /// The primary data store.
+    pub fn primary(&self) -> &A {
+        &self.primary
+    }
+
+    /// The secondary data store.
+    pub fn secondary(&self) -> &B {
+        &self.secondary
+    }
+
+    /// Return the data store which has missed changes since this value was created.
+    ///
+    /// If both data stores have missed changes, this returns the primary data store.
+    pub fn stale_side(&self) -> Option<MirrorSide> {
+        if self.primary_stale {
+            Some(MirrorSide::Primary)
+        } else if self.secondary_stale {
+            Some(MirrorSide::Secondary)
+        } else {
+            None
+        }
+    }
+
+    /// Bring the stale data store back in sync with the other data store.
+    ///
+    /// If neither data store is known to be stale, or if both are, the secondary data store is
+    /// synced from the primary data store. This returns the number of blocks which were copied or
+    /// removed.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with either data store.
+    pub fn repair(&mut self) -> crate::Result<u64> {
+        match self.stale_side() {
+            Some(MirrorSide::Primary) if !self.secondary_stale => {
+                self.repair_from(MirrorSide::Secondary)
+            }
+            _ => self.repair_from(MirrorSide::Primary),
+        }
+    }
+
+    /// Make the other data store an exact copy of the `source` data store.
+    ///
+    /// Blocks which are missing from the other data store are copied, blocks which don't exist in
+    /// the `source` data store are removed, and the superblock and version block are overwritten if
+    /// they differ.
+    /// Afterwards, neither data store is considered stale. This returns the number of blocks which
+    /// were copied or removed.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with either data store.
+    pub fn repair_from(&mut self, source: MirrorSide) -> crate::Result<u64> {
+        let count = match source {
+            MirrorSide::Primary => sync(&mut self.primary, &mut self.secondary),
+            MirrorSide::Secondary => sync(&mut self.secondary, &mut self.primary),
+        }
+        .map_err(crate::Error::Store)?;
+        self.primary_stale = false;
+        self.secondary_stale = false;
+        Ok(count)
+    }
+
+    /// Perform a change on both data stores, marking a data store stale if the change fails.
+    ///
+    /// This only fails if the change fails on both data stores.
+    fn change(
+        &mut self,
+        mut primary_op: impl FnMut(&mut A) -> super::Result<()>,
+        mut secondary_op: impl FnMut(&mut B) -> super::Result<()>,
+    ) -> super::Result<()> {
+        let primary_result = primary_op(&mut self.primary);
+        let secondary_result = secondary_op(&mut self.secondary);
+        match (primary_result, secondary_result) {
+            (Ok(()), Ok(())) => Ok(()),
+            (Err(_), Ok(())) => {
+                self.primary_stale = true;
+                Ok(())
+            }
+            (Ok(()), Err(_)) => {
+                self.secondary_stale = true;
+                Ok(())
+            }
+            (Err(error), Err(_)) => Err(error),
+        }
+    }
+}
+
+/// Make `target` an exact copy of `source` and return the number of blocks copied or removed.
+fn sync(source: &mut impl DataStore, target: &mut impl DataStore) -> super::Result<u64> {
+    let mut count = 0;
+
+    for kind in [BlockType::Data, BlockType::Header, BlockType::Lock] {
+        let source_ids = source
+            .list_blocks(kind)?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let target_ids = target
+            .list_blocks(kind)?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let key = |id: BlockId| match kind {
+            BlockType::Data => BlockKey::Data(id),
+            BlockType::Lock => BlockKey::Lock(id),
+            BlockType::Header => BlockKey::Header(id),
+        };
+
+        for id in source_ids.difference(&target_ids) {
+            // The block may have been removed since it was listed.
+            if let Some(data) = source.read_block(key(*id))? {
+                target.write_block(key(*id), &data)?;
+                count += 1;
+            }
+        }
+
+        for id in target_ids.difference(&source_ids) {
+            target.remove_block(key(*id))?;
+            count += 1;
+        }
+    }
+
+    for key in [BlockKey::Version, BlockKey::Super] {
+        let source_data = source.read_block(key)?;
+        if source_data != target.read_block(key)? {
+            match source_data {
+                Some(data) => target.write_block(key, &data)?,
+                None => target.remove_block(key)?,
+            }
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+impl<A: DataStore, B: DataStore> DataStore for MirroredStore<A, B> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.change(
+            |store| store.write_block(key, data),
+            |store| store.write_block(key, data),
+        )
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        // A block which is missing from one data store may exist in the other if it was
+        // unavailable when the block was written.
+        if self.primary_stale {
+            match self.secondary.read_block(key) {
+                Ok(Some(data)) => Ok(Some(data)),
+                Ok(None) | Err(_) => self.primary.read_block(key),
+            }
+        } else {
+            match self.primary.read_block(key) {
+                Ok(Some(data)) => Ok(Some(data)),
+                Ok(None) => Ok(self.secondary.read_block(key).ok().flatten()),
+                Err(error) => self.secondary.read_block(key).map_err(|_| error),
+            }
+        }
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.change(
+            |store| store.remove_block(key),
+            |store| store.remove_block(key),
+        )
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let primary_ids = self.primary.list_blocks(kind);
+        let secondary_ids = self.secondary.list_blocks(kind);
+        match (primary_ids, secondary_ids) {
+            (Ok(primary_ids), Ok(secondary_ids)) => {
+                let mut ids = primary_ids.into_iter().collect::<HashSet<_>>();
+                ids.extend(secondary_ids);
+                Ok(ids.into_iter().collect())
+            }
+            (Ok(ids), Err(_)) | (Err(_), Ok(ids)) => Ok(ids),
+            (Err(error), Err(_)) => Err(error),
+        }
+    }
+
+    fn consistency(&self) -> Consistency {
+        match (self.primary.consistency(), self.secondary.consistency()) {
+            (Consistency::Strong, Consistency::Strong) => Consistency::Strong,
+            _ => Consistency::Eventual,
+        }
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        // Use the latest time so that blocks aren't removed until both copies are old enough.
+        let primary_time = self.primary.block_modified_time(key);
+        let secondary_time = self.secondary.block_modified_time(key);
+        match (primary_time, secondary_time) {
+            (Ok(primary_time), Ok(secondary_time)) => Ok(primary_time.max(secondary_time)),
+            (Ok(time), Err(_)) | (Err(_), Ok(time)) => Ok(time),
+            (Err(error), Err(_)) => Err(error),
+        }
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        match self.primary.block_size(key) {
+            Ok(Some(size)) => Ok(Some(size)),
+            Ok(None) | Err(_) => self.secondary.block_size(key),
+        }
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.primary.retention().max(self.secondary.retention())
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.change(|store| store.sync(), |store| store.sync())
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.primary.is_read_only() && self.secondary.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            writable: !self.is_read_only(),
+            ..self.primary.probe().combine(self.secondary.probe())
+        }
+    }
+}
@@ -17,30 +17,103 @@
 //! [`OpenStore`]: crate::store::OpenStore
 //! [`OpenOptions`]: crate::repo::OpenOptions
 
-pub use self::data_store::{BlockId, BlockKey, BlockType, DataStore};
+#[cfg(feature = "store-azure")]
+pub use self::azure_store::{AzureConfig, AzureCredentials, AzureStore};
+#[cfg(feature = "store-b2")]
+pub use self::b2_store::{B2Config, B2Store};
+pub use self::cached_store::{CachedConfig, CachedStore};
+#[cfg(feature = "compression")]
+pub use self::compressed_store::{CompressedConfig, CompressedStore};
+pub use self::data_store::{
+    BlockId, BlockKey, BlockType, Consistency, DataStore, LatencyClass, StoreCapabilities,
+};
+#[cfg(feature = "store-device")]
+pub use self::device_store::{DeviceConfig, DeviceFormat, DeviceReport, DeviceStore};
 #[cfg(feature = "store-directory")]
 pub use self::directory_store::{DirectoryConfig, DirectoryStore};
-pub use self::error::{Error, Result};
+#[cfg(feature = "store-dropbox")]
+pub use self::dropbox_store::{DropboxConfig, DropboxStore};
+#[cfg(feature = "encryption")]
+pub use self::encrypted_store::{EncryptedConfig, EncryptedStore, StoreKey};
+pub use self::error::{Error, ErrorKind, Result};
+#[cfg(feature = "store-file")]
+pub use self::file_store::{FileConfig, FileStore};
+#[cfg(feature = "store-ftp")]
+pub use self::ftp_store::{FtpConfig, FtpStore};
+#[cfg(feature = "store-gcs")]
+pub use self::gcs_store::{GcsConfig, GcsCredentials, GcsStore};
+#[cfg(feature = "store-google-drive")]
+pub use self::google_drive_store::{GoogleDriveConfig, GoogleDriveStore};
+#[cfg(feature = "store-http")]
+pub use self::http_store::{HttpConfig, HttpStore};
+#[cfg(feature = "store-ipfs")]
+pub use self::ipfs_store::{IpfsConfig, IpfsStore};
 pub use self::memory_store::{MemoryConfig, MemoryStore};
+pub use self::mirrored_store::{MirrorSide, MirroredConfig, MirroredStore};
+#[cfg(any(feature = "store-dropbox", feature = "store-google-drive"))]
+pub use self::oauth::OAuthCredentials;
 pub use self::open_store::OpenStore;
+#[cfg(feature = "store-postgres")]
+pub use self::postgres_store::{PostgresConfig, PostgresStore};
 #[cfg(feature = "store-rclone")]
 pub use self::rclone_store::{RcloneConfig, RcloneStore};
+pub use self::read_only_store::{ReadOnlyConfig, ReadOnlyStore};
+#[cfg(feature = "store-recording")]
+pub use self::recording_store::{
+    read_operation_log, RecordedOperation, RecordingConfig, RecordingStore, ReplayStore,
+    StoreOperation,
+};
 #[cfg(feature = "store-redis")]
 pub use self::redis_store::{RedisAddr, RedisConfig, RedisStore};
+pub use self::retrying_store::{ErrorClass, RetryPolicy, RetryingConfig, RetryingStore};
+#[cfg(feature = "store-rocksdb")]
+pub use self::rocksdb_store::{RocksdbConfig, RocksdbStore};
 #[cfg(feature = "store-s3")]
 pub use self::s3_store::{S3Config, S3Credentials, S3Region, S3Store};
 #[cfg(feature = "store-sftp")]
 pub use self::sftp_store::{SftpAuth, SftpConfig, SftpStore};
+pub use self::sharded_store::{ShardedConfig, ShardedStore};
+#[cfg(feature = "store-sled")]
+pub use self::sled_store::{SledConfig, SledStore};
 #[cfg(feature = "store-sqlite")]
 pub use self::sqlite_store::{SqliteConfig, SqliteStore};
+pub use self::throttled_store::{ThrottleLimits, ThrottledConfig, ThrottledStore};
+pub use self::tiered_store::{Tier, TierPolicy, TieredConfig, TieredStore};
+pub use self::verifying_store::{VerifyingConfig, VerifyingStore};
 
+mod azure_store;
+mod b2_store;
+mod cached_store;
+mod compressed_store;
 mod data_store;
+mod device_store;
 mod directory_store;
+mod dropbox_store;
+mod encrypted_store;
 mod error;
+mod file_store;
+mod ftp_store;
+mod gcs_store;
+mod google_drive_store;
+mod http_store;
+mod ipfs_store;
 mod memory_store;
+mod mirrored_store;
+mod oauth;
 mod open_store;
+mod postgres_store;
 mod rclone_store;
+mod read_only_store;
+mod recording_store;
 mod redis_store;
+mod rest;
+mod retrying_store;
+mod rocksdb_store;
 mod s3_store;
 mod sftp_store;
+mod sharded_store;
+mod sled_store;
 mod sqlite_store;
+mod throttled_store;
+mod tiered_store;
+mod verifying_store;
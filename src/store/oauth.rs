@@ -0,0 +1,153 @@
+#![cfg(any(feature = "store-dropbox", feature = "store-google-drive"))]
+
+//! Helpers for data stores which access a user's account with OAuth 2.0.
+
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use ureq::{Agent, Response};
+
+use super::rest::check_response;
+
+/// The HTTP status code for a request with a missing or expired access token.
+const UNAUTHORIZED_CODE: u16 = 401;
+
+/// How long before an access token expires to refresh it.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// The credentials for accessing a user's account with OAuth 2.0.
+///
+/// Data stores which use these credentials get short-lived access tokens using a long-lived
+/// refresh token, which you can get by having the user authorize your app using the service's
+/// OAuth 2.0 flow with offline access. Access tokens are refreshed automatically when they expire.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(feature = "store-dropbox", feature = "store-google-drive")))
+)]
+pub struct OAuthCredentials {
+    /// The client ID of your app.
+    pub client_id: String,
+
+    /// The client secret of your app, if it has one.
+    ///
+    /// Apps which authorize users using PKCE don't need a client secret.
+    pub client_secret: Option<String>,
+
+    /// The refresh token the user granted your app.
+    pub refresh_token: String,
+}
+
+impl OAuthCredentials {
+    /// Return new credentials for an app without a client secret.
+    pub fn new(client_id: impl Into<String>, refresh_token: impl Into<String>) -> Self {
+        OAuthCredentials {
+            client_id: client_id.into(),
+            client_secret: None,
+            refresh_token: refresh_token.into(),
+        }
+    }
+}
+
+/// The response to refreshing an access token.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// An access token and when it expires.
+#[derive(Debug)]
+struct AccessToken {
+    token: String,
+    expires: Instant,
+}
+
+/// A source of access tokens which refreshes them as they expire.
+#[derive(Debug)]
+pub struct TokenSource {
+    agent: Agent,
+    service: &'static str,
+    token_url: &'static str,
+    credentials: OAuthCredentials,
+    access_token: Option<AccessToken>,
+}
+
+impl TokenSource {
+    /// Return a new token source which gets access tokens for `service` from `token_url`.
+    pub fn new(
+        agent: Agent,
+        service: &'static str,
+        token_url: &'static str,
+        credentials: OAuthCredentials,
+    ) -> Self {
+        TokenSource {
+            agent,
+            service,
+            token_url,
+            credentials,
+            access_token: None,
+        }
+    }
+
+    /// Return the value of the `Authorization` header to send with requests.
+    pub fn authorization(&mut self) -> super::Result<String> {
+        match &self.access_token {
+            Some(access_token) if access_token.expires > Instant::now() => {
+                Ok(format!("Bearer {}", access_token.token))
+            }
+            _ => {
+                let access_token = self.refresh()?;
+                let authorization = format!("Bearer {}", access_token.token);
+                self.access_token = Some(access_token);
+                Ok(authorization)
+            }
+        }
+    }
+
+    /// Get a new access token using the refresh token.
+    fn refresh(&self) -> super::Result<AccessToken> {
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", self.credentials.refresh_token.as_str()),
+            ("client_id", self.credentials.client_id.as_str()),
+        ];
+        if let Some(client_secret) = &self.credentials.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+
+        let requested = Instant::now();
+        let response = check_response(
+            self.service,
+            self.agent.post(self.token_url).send_form(&form),
+        )?
+        .ok_or_else(|| super::Error::msg("The OAuth token endpoint does not exist."))?;
+        let token: TokenResponse = serde_json::from_reader(response.into_reader())?;
+
+        Ok(AccessToken {
+            token: token.access_token,
+            expires: requested
+                + Duration::from_secs(token.expires_in).saturating_sub(EXPIRY_MARGIN),
+        })
+    }
+
+    /// Send the request made by `request`, which is given the value of the `Authorization` header.
+    ///
+    /// If the access token is rejected, because it was revoked or expired early, this gets a new
+    /// one and sends the request again. This returns the result of the request without checking
+    /// its status so that callers can handle service-specific errors.
+    pub fn send(
+        &mut self,
+        request: impl Fn(&Agent, &str) -> Result<Response, ureq::Error>,
+    ) -> super::Result<Result<Response, ureq::Error>> {
+        let authorization = self.authorization()?;
+        match request(&self.agent, &authorization) {
+            Err(ureq::Error::Status(UNAUTHORIZED_CODE, _)) => {
+                self.access_token = None;
+                let authorization = self.authorization()?;
+                Ok(request(&self.agent, &authorization))
+            }
+            result => Ok(result),
+        }
+    }
+}
@@ -1,11 +1,11 @@
 #![cfg(feature = "store-rclone")]
 
-use std::io;
+use std::io::{self, Read};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream, UdpSocket};
 use std::path::Path;
 use std::process::{Child, Command, Stdio};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use rand::distributions::{Alphanumeric, DistString};
 
@@ -35,6 +35,9 @@ const SSH_USERNAME: &str = "rclone";
 /// The amount of time to wait between attempts to connect to the SFTP server.
 const CONNECT_WAIT_TIME: Duration = Duration::from_millis(100);
 
+/// The maximum amount of time to wait for the SFTP server to start.
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Serve the rclone remote over SFTP and return the server process.
 fn serve(port: u16, password: &str, config: &str) -> io::Result<Child> {
     Command::new("rclone")
@@ -57,11 +60,28 @@ fn serve(port: u16, password: &str, config: &str) -> io::Result<Child> {
         .spawn()
 }
 
-/// Wait for a local TCP connection on the given `port` to connect and then drop the connection.
-fn wait_for_connection(port: u16) -> io::Result<()> {
+/// Wait for the rclone `server` to accept a local TCP connection on the given `port` and then drop
+/// the connection.
+///
+/// This fails if the server exits before accepting a connection, like when the remote doesn't
+/// exist, or if it doesn't accept a connection within `START_TIMEOUT`.
+fn wait_for_connection(server: &mut Child, port: u16) -> io::Result<()> {
+    let start = Instant::now();
     loop {
         match TcpStream::connect(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)) {
             Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+                if let Some(status) = server.try_wait()? {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("The rclone server exited before it started ({}).", status),
+                    ));
+                }
+                if start.elapsed() > START_TIMEOUT {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "The rclone server did not start in time.",
+                    ));
+                }
                 sleep(CONNECT_WAIT_TIME);
                 continue;
             }
@@ -73,6 +93,12 @@ fn wait_for_connection(port: u16) -> io::Result<()> {
     Ok(())
 }
 
+/// Kill the rclone `server` and wait for it to exit so it doesn't linger as a zombie process.
+fn stop_server(server: &mut Child) {
+    server.kill().ok();
+    server.wait().ok();
+}
+
 /// The configuration for opening an [`RcloneStore`].
 ///
 /// [`RcloneStore`]: crate::store::RcloneStore
@@ -83,7 +109,8 @@ pub struct RcloneConfig {
     ///
     /// This is a string with the format `<remote>:<path>`, where `<remote>` is the name of the
     /// remote as configured using `rclone config` and `<path>` is the path of the directory on the
-    /// remote to use.
+    /// remote to use. Blocks are stored as files in this directory, so it should be dedicated to
+    /// the store.
     pub config: String,
 }
 
@@ -94,20 +121,29 @@ impl OpenStore for RcloneConfig {
         // Serve the rclone remote over SFTP and wait for the server to start.
         let port = ephemeral_port()?;
         let password = generate_password(PASSWORD_LENGTH);
-        let server_process = serve(port, &password, &self.config)?;
-        wait_for_connection(port)?;
+        let mut server_process = serve(port, &password, &self.config)?;
+        if let Err(error) = wait_for_connection(&mut server_process, port) {
+            stop_server(&mut server_process);
+            return Err(error.into());
+        }
 
-        let sftp_config = SftpConfig {
-            addr: SocketAddrV4::new(Ipv4Addr::LOCALHOST, port).into(),
-            auth: SftpAuth::Password {
+        let sftp_config = SftpConfig::new(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, port).into(),
+            SftpAuth::Password {
                 username: SSH_USERNAME.to_string(),
                 password,
             },
-            path: Path::new("").to_owned(),
+            Path::new(""),
+        );
+
+        let sftp_store = match sftp_config.open() {
+            Ok(store) => store,
+            Err(error) => {
+                stop_server(&mut server_process);
+                return Err(error);
+            }
         };
 
-        let sftp_store = sftp_config.open()?;
-
         Ok(RcloneStore {
             sftp_store,
             server_process,
@@ -142,6 +178,10 @@ impl DataStore for RcloneStore {
         self.sftp_store.read_block(key)
     }
 
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        self.sftp_store.read_block_streaming(key)
+    }
+
     fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
         self.sftp_store.remove_block(key)
     }
@@ -153,6 +193,6 @@ impl DataStore for RcloneStore {
 
 impl Drop for RcloneStore {
     fn drop(&mut self) {
-        self.server_process.kill().ok();
+        stop_server(&mut self.server_process);
     }
 }
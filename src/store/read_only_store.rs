@@ -0,0 +1,126 @@
+use std::io::Read;
+use std::time::{Duration, SystemTime};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// The configuration for opening a [`ReadOnlyStore`].
+///
+/// [`ReadOnlyStore`]: crate::store::ReadOnlyStore
+#[derive(Debug, Clone)]
+pub struct ReadOnlyConfig<C: OpenStore> {
+    /// The configuration for the data store to prevent writes to.
+    pub inner: C,
+}
+
+impl<C: OpenStore> ReadOnlyConfig<C> {
+    /// Create a new `ReadOnlyConfig` which prevents writes to the store opened by `inner`.
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+impl<C: OpenStore> OpenStore for ReadOnlyConfig<C> {
+    type Store = ReadOnlyStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(ReadOnlyStore::new(self.inner.open()?))
+    }
+}
+
+/// A `DataStore` which prevents writes to another data store.
+///
+/// Blocks are read from the inner data store, but writing or removing a block always fails with an
+/// error wrapping `Error::ReadOnly` without touching the inner data store. This is useful for
+/// inspecting a repository on a data store which is shared with other clients or which is on
+/// read-only media, where even acquiring a lock must not modify the store.
+///
+/// Because this store is read-only, repositories can only be opened in it with
+/// [`OpenMode::ReadOnly`]. Opening a repository with any other mode fails with `Error::ReadOnly`.
+///
+/// You can use [`ReadOnlyConfig`] to open a data store of this type.
+///
+/// [`OpenMode::ReadOnly`]: crate::repo::OpenMode::ReadOnly
+/// [`ReadOnlyConfig`]: crate::store::ReadOnlyConfig
+#[derive(Debug)]
+pub struct ReadOnlyStore<S: DataStore> {
+    inner: S,
+}
+
+impl<S: DataStore> ReadOnlyStore<S> {
+    /// Prevent writes to the given `inner` data store.
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    /// The data store which blocks are read from.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume this store and return the inner data store.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: DataStore> DataStore for ReadOnlyStore<S> {
+    fn write_block(&mut self, _key: BlockKey, _data: &[u8]) -> super::Result<()> {
+        Err(super::Error::new(crate::Error::ReadOnly))
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.inner.read_block(key)
+    }
+
+    fn read_block_streaming(&mut self, key: BlockKey) -> super::Result<Option<Box<dyn Read + '_>>> {
+        self.inner.read_block_streaming(key)
+    }
+
+    fn remove_block(&mut self, _key: BlockKey) -> super::Result<()> {
+        Err(super::Error::new(crate::Error::ReadOnly))
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+
+    fn write_blocks(&mut self, _blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        Err(super::Error::new(crate::Error::ReadOnly))
+    }
+
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        self.inner.read_blocks(keys)
+    }
+
+    fn remove_blocks(&mut self, _keys: &[BlockKey]) -> super::Result<()> {
+        Err(super::Error::new(crate::Error::ReadOnly))
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.inner.block_modified_time(key)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.inner.block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            writable: false,
+            ..self.inner.probe()
+        }
+    }
+}
@@ -0,0 +1,494 @@
+#![cfg(feature = "store-recording")]
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use rmp_serde::{from_slice, to_vec};
+use serde::{Deserialize, Serialize};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// The size of the length prefix of each entry in an operation log.
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// The type of operation recorded in an operation log.
+///
+/// Each variant corresponds to a method of [`DataStore`].
+///
+/// [`DataStore`]: crate::store::DataStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-recording")))]
+pub enum StoreOperation {
+    /// A block with the given key was written.
+    Write(BlockKey),
+
+    /// A block with the given key was read.
+    Read(BlockKey),
+
+    /// A block with the given key was removed.
+    Remove(BlockKey),
+
+    /// The blocks of the given type were listed.
+    List(BlockType),
+}
+
+impl StoreOperation {
+    /// Return whether this operation touches a block which stores repository metadata.
+    ///
+    /// Entries for these operations are flushed to the log immediately.
+    fn is_metadata(&self) -> bool {
+        match self {
+            StoreOperation::Write(key)
+            | StoreOperation::Read(key)
+            | StoreOperation::Remove(key) => !matches!(key, BlockKey::Data(_)),
+            StoreOperation::List(kind) => *kind != BlockType::Data,
+        }
+    }
+}
+
+/// An operation which was recorded by a [`RecordingStore`].
+///
+/// [`RecordingStore`]: crate::store::RecordingStore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-recording")))]
+pub struct RecordedOperation {
+    operation: StoreOperation,
+    size: Option<u64>,
+    hash: Option<[u8; blake3::OUT_LEN]>,
+    payload: Option<Vec<u8>>,
+    error: Option<String>,
+    timestamp: SystemTime,
+}
+
+impl RecordedOperation {
+    /// The operation which was performed.
+    pub fn operation(&self) -> StoreOperation {
+        self.operation
+    }
+
+    /// The size of the block which was written or read.
+    ///
+    /// For `List` operations, this is the number of blocks which were listed. This is `None` if
+    /// the operation failed or if a block which was read did not exist.
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// The BLAKE3 hash of the block which was written or read.
+    ///
+    /// This is `None` if the operation failed, if a block which was read did not exist, or if the
+    /// operation doesn't have a payload.
+    pub fn hash(&self) -> Option<&[u8]> {
+        self.hash.as_ref().map(|hash| hash.as_slice())
+    }
+
+    /// The bytes of the block which was written.
+    ///
+    /// This is `None` if the operation was not a `Write` or if the log was recorded with
+    /// redaction.
+    pub fn payload(&self) -> Option<&[u8]> {
+        self.payload.as_deref()
+    }
+
+    /// The error message if the operation failed, or `None` if it succeeded.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// The time at which the operation completed.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+/// Append a length-prefixed `entry` to the given `log`.
+fn write_entry(log: &mut impl Write, entry: &RecordedOperation) -> io::Result<()> {
+    let serialized = to_vec(entry).expect("Could not serialize log entry.");
+    log.write_all(&(serialized.len() as u32).to_be_bytes())?;
+    log.write_all(&serialized)
+}
+
+/// Read the operation log at the given `path`.
+///
+/// If the last entry in the log is incomplete, as can happen if the process which was recording it
+/// crashed, it is ignored.
+///
+/// # Errors
+/// - `Error::Corrupt`: The log is corrupt.
+/// - `Error::Io`: An I/O error occurred.
+#[cfg_attr(docsrs, doc(cfg(feature = "store-recording")))]
+pub fn read_operation_log(path: impl AsRef<Path>) -> crate::Result<Vec<RecordedOperation>> {
+    let mut log = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut log)?;
+
+    let mut operations = Vec::new();
+    let mut remaining = log.as_slice();
+    while remaining.len() >= LENGTH_PREFIX_SIZE {
+        let (prefix, rest) = remaining.split_at(LENGTH_PREFIX_SIZE);
+        let length = u32::from_be_bytes(prefix.try_into().unwrap()) as usize;
+        if rest.len() < length {
+            break;
+        }
+        let (entry, rest) = rest.split_at(length);
+        operations.push(from_slice(entry).map_err(|_| crate::Error::Corrupt)?);
+        remaining = rest;
+    }
+
+    Ok(operations)
+}
+
+/// The configuration for opening a [`RecordingStore`].
+///
+/// [`RecordingStore`]: crate::store::RecordingStore
+#[derive(Debug, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-recording")))]
+pub struct RecordingConfig<C: OpenStore> {
+    /// The configuration for the data store to record operations on.
+    pub inner: C,
+
+    /// The path of the operation log.
+    ///
+    /// If the log already exists, new operations are appended to it.
+    pub log_path: PathBuf,
+
+    /// Whether to omit the bytes of written blocks from the log.
+    ///
+    /// The sizes and hashes of blocks are still recorded. A redacted log can be inspected with
+    /// [`read_operation_log`], but it can't be replayed with a [`ReplayStore`].
+    ///
+    /// [`read_operation_log`]: crate::store::read_operation_log
+    /// [`ReplayStore`]: crate::store::ReplayStore
+    pub redact: bool,
+}
+
+impl<C: OpenStore> OpenStore for RecordingConfig<C> {
+    type Store = RecordingStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        RecordingStore::new(self.inner.open()?, &self.log_path, self.redact)
+    }
+}
+
+/// A `DataStore` which records the operations performed on another data store.
+///
+/// Every operation performed on this data store is performed on the inner data store and then
+/// appended to an operation log. Each entry in the log records the operation, the key of the block,
+/// the size and hash of the block, whether the operation succeeded, and when it completed. Unless
+/// the log is redacted, it also records the bytes of each block which is written, so that the log
+/// can be replayed with a [`ReplayStore`] to reconstruct the state of the data store.
+///
+/// Entries are buffered, but the log is flushed after every operation on a block which isn't a data
+/// block, so the log is always complete up to the last change to the repository's metadata. The
+/// log is also flushed when this data store is dropped.
+///
+/// Failing to write to the log never changes the result of an operation. Instead, the first error
+/// which occurs while writing to the log is returned by the next call to [`flush`].
+///
+/// This data store is meant for debugging.
+///
+/// You can use [`RecordingConfig`] to open a data store of this type.
+///
+/// [`ReplayStore`]: crate::store::ReplayStore
+/// [`RecordingConfig`]: crate::store::RecordingConfig
+/// [`flush`]: crate::store::RecordingStore::flush
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-recording")))]
+pub struct RecordingStore<S: DataStore> {
+    inner: S,
+    log: BufWriter<File>,
+    redact: bool,
+
+    /// The first error which occurred while writing to the log since it was last flushed.
+    log_error: Option<io::Error>,
+}
+
+impl<S: DataStore> RecordingStore<S> {
+    /// Wrap the `inner` data store, appending operations to the log at `log_path`.
+    ///
+    /// If `redact` is `true`, the bytes of written blocks are omitted from the log.
+    ///
+    /// # Errors
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn new(inner: S, log_path: impl AsRef<Path>, redact: bool) -> crate::Result<Self> {
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)?;
+        Ok(Self {
+            inner,
+            log: BufWriter::new(log),
+            redact,
+            log_error: None,
+        })
+    }
+
+    /// Flush any buffered entries to the operation log.
+    ///
+    /// # Errors
+    /// - `Error::Io`: An I/O error occurred, either now or while writing an earlier entry to the
+    /// log. In the latter case, the log is missing entries.
+    pub fn flush(&mut self) -> crate::Result<()> {
+        if let Some(error) = self.log_error.take() {
+            return Err(error.into());
+        }
+        self.log.flush()?;
+        Ok(())
+    }
+
+    /// Append an entry to the log for the given `operation` and its `result`.
+    ///
+    /// If the entry can't be written, the error is saved to be returned by `flush`.
+    fn record<T>(
+        &mut self,
+        operation: StoreOperation,
+        result: &super::Result<T>,
+        size: Option<u64>,
+        data: Option<&[u8]>,
+    ) {
+        let is_write = matches!(operation, StoreOperation::Write(_));
+        let entry = match result {
+            Ok(_) => RecordedOperation {
+                operation,
+                size,
+                hash: data.map(|data| blake3::hash(data).into()),
+                payload: data.filter(|_| is_write && !self.redact).map(Vec::from),
+                error: None,
+                timestamp: SystemTime::now(),
+            },
+            Err(error) => RecordedOperation {
+                operation,
+                size: None,
+                hash: None,
+                payload: None,
+                error: Some(error.to_string()),
+                timestamp: SystemTime::now(),
+            },
+        };
+
+        let mut result = write_entry(&mut self.log, &entry);
+        if result.is_ok() && operation.is_metadata() {
+            result = self.log.flush();
+        }
+
+        if let Err(error) = result {
+            self.log_error.get_or_insert(error);
+        }
+    }
+}
+
+impl<S: DataStore> DataStore for RecordingStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let result = self.inner.write_block(key, data);
+        self.record(
+            StoreOperation::Write(key),
+            &result,
+            Some(data.len() as u64),
+            Some(data),
+        );
+        result
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let result = self.inner.read_block(key);
+        let data = result.as_ref().ok().and_then(|data| data.as_deref());
+        self.record(
+            StoreOperation::Read(key),
+            &result,
+            data.map(|data| data.len() as u64),
+            data,
+        );
+        result
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let result = self.inner.remove_block(key);
+        self.record(StoreOperation::Remove(key), &result, None, None);
+        result
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let result = self.inner.list_blocks(kind);
+        let count = result.as_ref().ok().map(|blocks| blocks.len() as u64);
+        self.record(StoreOperation::List(kind), &result, count, None);
+        result
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.inner.block_modified_time(key)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.inner.block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        self.inner.probe()
+    }
+}
+
+impl<S: DataStore> Drop for RecordingStore<S> {
+    fn drop(&mut self) {
+        // Attempt to flush the log. This may fail.
+        self.log.flush().ok();
+    }
+}
+
+/// A `DataStore` which replays an operation log recorded by a [`RecordingStore`].
+///
+/// This replays the operations in an operation log against an inner data store, which should
+/// initially be empty, to reconstruct the exact state of the recorded data store at any point in
+/// the recording. Only operations which change the data store and which succeeded when they were
+/// recorded are performed; other entries are skipped.
+///
+/// Once operations have been replayed, this data store can be used like the inner data store, or
+/// the inner data store can be recovered with [`into_inner`].
+///
+/// [`RecordingStore`]: crate::store::RecordingStore
+/// [`into_inner`]: crate::store::ReplayStore::into_inner
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-recording")))]
+pub struct ReplayStore<S: DataStore> {
+    inner: S,
+    operations: Vec<RecordedOperation>,
+    position: usize,
+}
+
+impl<S: DataStore> ReplayStore<S> {
+    /// Prepare to replay the operation log at `log_path` against the `inner` data store.
+    ///
+    /// This does not replay any operations.
+    ///
+    /// # Errors
+    /// - `Error::Corrupt`: The log is corrupt.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn new(inner: S, log_path: impl AsRef<Path>) -> crate::Result<Self> {
+        Ok(Self {
+            inner,
+            operations: read_operation_log(log_path)?,
+            position: 0,
+        })
+    }
+
+    /// The operations in the log.
+    pub fn operations(&self) -> &[RecordedOperation] {
+        &self.operations
+    }
+
+    /// The number of entries in the log which have been replayed.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Replay the log up to, but not including, the entry at index `position`.
+    ///
+    /// Operations can only be replayed forward. If `position` is less than the current
+    /// [`position`], this does nothing. If `position` is past the end of the log, the whole log is
+    /// replayed.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: The log was recorded with redaction, so it can't be replayed.
+    /// - `Error::Store`: An error occurred with the data store.
+    ///
+    /// [`position`]: crate::store::ReplayStore::position
+    pub fn replay_to(&mut self, position: usize) -> crate::Result<()> {
+        let end = position.min(self.operations.len());
+        while self.position < end {
+            let entry = &self.operations[self.position];
+            if entry.error.is_none() {
+                match entry.operation {
+                    StoreOperation::Write(key) => {
+                        let payload = entry.payload.as_deref().ok_or(crate::Error::InvalidData)?;
+                        self.inner
+                            .write_block(key, payload)
+                            .map_err(crate::Error::Store)?;
+                    }
+                    StoreOperation::Remove(key) => {
+                        self.inner.remove_block(key).map_err(crate::Error::Store)?;
+                    }
+                    StoreOperation::Read(_) | StoreOperation::List(_) => {}
+                }
+            }
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    /// Replay every remaining operation in the log.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: The log was recorded with redaction, so it can't be replayed.
+    /// - `Error::Store`: An error occurred with the data store.
+    pub fn replay_all(&mut self) -> crate::Result<()> {
+        self.replay_to(self.operations.len())
+    }
+
+    /// Return the inner data store.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: DataStore> DataStore for ReplayStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.inner.block_modified_time(key)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.inner.block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        self.inner.probe()
+    }
+}
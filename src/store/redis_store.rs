@@ -9,7 +9,7 @@ use redis::{
 };
 use uuid::Uuid;
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, StoreCapabilities};
 use super::open_store::OpenStore;
 
 /// A UUID which acts as the version ID of the store format.
@@ -183,4 +183,39 @@ impl DataStore for RedisStore {
 
         Ok(blocks)
     }
+
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        // Each `SET` is atomic, so a pipeline is enough to avoid a round trip for every block.
+        let mut pipeline = redis::pipe();
+        for (key, data) in blocks {
+            pipeline.set(block_key(*key), *data).ignore();
+        }
+        pipeline.query::<()>(&mut self.connection)?;
+        Ok(())
+    }
+
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys = keys.iter().map(|key| block_key(*key)).collect::<Vec<_>>();
+        Ok(redis::cmd("MGET").arg(keys).query(&mut self.connection)?)
+    }
+
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let keys = keys.iter().map(|key| block_key(*key)).collect::<Vec<_>>();
+        self.connection.del(keys)?;
+        Ok(())
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            // This is the maximum size of a string value.
+            max_block_size: Some(512 * 1024 * 1024),
+            ..StoreCapabilities::default()
+        }
+    }
 }
@@ -0,0 +1,82 @@
+#![cfg(any(
+    feature = "store-azure",
+    feature = "store-b2",
+    feature = "store-dropbox",
+    feature = "store-gcs",
+    feature = "store-google-drive",
+    feature = "store-http",
+    feature = "store-ipfs"
+))]
+
+//! Helpers for data stores which talk to a REST API over HTTP.
+
+use ureq::Response;
+
+use super::ErrorKind;
+
+/// The separator to use in object names.
+pub const SEPARATOR: &str = "/";
+
+/// The HTTP status code for a resource which does not exist.
+pub const NOT_FOUND_CODE: u16 = 404;
+
+/// Join the given segments into an object name, skipping empty segments.
+pub fn join_key(segments: &[&str]) -> String {
+    segments
+        .iter()
+        .filter(|segment| !segment.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(SEPARATOR)
+}
+
+/// Percent-encode `value` for use in a URL, leaving the characters in `keep` unencoded.
+pub fn percent_encode(value: &str, keep: &[u8]) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) || keep.contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Return the kind of error which corresponds to the HTTP status `code` of a failed request.
+fn status_kind(code: u16) -> ErrorKind {
+    match code {
+        401 | 403 => ErrorKind::PermissionDenied,
+        408 | 429 | 500..=599 => ErrorKind::Transient,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Convert the `result` of a request to the given `service` into a store result.
+///
+/// This returns `None` if the server responded that the resource does not exist. Errors don't
+/// include the URL of the request, because it may contain credentials. Errors for responses which
+/// may succeed if retried, like server errors, are `ErrorKind::Transient`.
+pub fn check_response(
+    service: &str,
+    result: Result<Response, ureq::Error>,
+) -> super::Result<Option<Response>> {
+    match result {
+        Ok(response) => Ok(Some(response)),
+        Err(ureq::Error::Status(NOT_FOUND_CODE, _)) => Ok(None),
+        Err(ureq::Error::Status(code, response)) => Err(super::Error::msg(format!(
+            "{} request failed with status {}: {}",
+            service,
+            code,
+            response.status_text()
+        ))
+        .with_kind(status_kind(code))),
+        Err(ureq::Error::Transport(transport)) => Err(super::Error::msg(format!(
+            "{} request failed: {}: {}",
+            service,
+            transport.kind(),
+            transport.message().unwrap_or("no details")
+        ))
+        .with_kind(ErrorKind::Transient)),
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::hash_map::RandomState;
+use std::error::Error as StdError;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// A class of errors which a [`RetryingStore`] can retry differently.
+///
+/// [`RetryingStore`]: crate::store::RetryingStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// The connection to the data store was refused, reset, or lost.
+    Connection,
+
+    /// An operation timed out or was interrupted.
+    Timeout,
+
+    /// Any other error.
+    Other,
+}
+
+impl ErrorClass {
+    /// Return the class of the given `error`.
+    ///
+    /// An error is classified by the first [`std::io::Error`] in its chain of sources, including
+    /// one wrapped by `Error::Io`. Errors which aren't caused by an I/O error are
+    /// `ErrorClass::Connection` if their kind is `ErrorKind::Transient` and `ErrorClass::Other`
+    /// otherwise.
+    pub fn of(error: &super::Error) -> Self {
+        let store_error = error;
+        let mut current: Option<&(dyn StdError + 'static)> = Some(&**error);
+        while let Some(error) = current {
+            if let Some(io_error) = error.downcast_ref::<io::Error>() {
+                return Self::of_io(io_error);
+            }
+            // This type doesn't expose the errors it wraps as sources.
+            match error.downcast_ref::<crate::Error>() {
+                Some(crate::Error::Io(io_error)) => return Self::of_io(io_error),
+                Some(crate::Error::Store(store_error)) => return Self::of(store_error),
+                _ => {}
+            }
+            current = error.source();
+        }
+        if store_error.is_transient() {
+            ErrorClass::Connection
+        } else {
+            ErrorClass::Other
+        }
+    }
+
+    /// Return the class of the given I/O `error`.
+    fn of_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::AddrNotAvailable
+            | io::ErrorKind::UnexpectedEof => ErrorClass::Connection,
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => {
+                ErrorClass::Timeout
+            }
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+/// How a [`RetryingStore`] retries operations which fail with a class of errors.
+///
+/// The delay before the first retry is `initial_delay`, and it doubles with each retry up to
+/// `max_delay`. A random amount of jitter of up to half the delay is subtracted from each delay so
+/// that clients which fail at the same time don't retry in lockstep.
+///
+/// [`RetryingStore`]: crate::store::RetryingStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of times to retry an operation before returning the error.
+    pub max_retries: u32,
+
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+
+    /// The maximum delay between retries.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy which never retries.
+    pub fn never() -> Self {
+        Self {
+            max_retries: 0,
+            initial_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    /// Return how long to wait before the given `retry`, which starts at 0.
+    fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .checked_mul(2u32.saturating_pow(retry))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let jitter_range = delay.as_nanos() as u64 / 2;
+        if jitter_range == 0 {
+            return delay;
+        }
+        let random = RandomState::new().build_hasher().finish();
+        delay - Duration::from_nanos(random % (jitter_range + 1))
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Retry up to 5 times, starting with a delay of 100 milliseconds and waiting at most 30
+    /// seconds between retries.
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The configuration for opening a [`RetryingStore`].
+///
+/// [`RetryingStore`]: crate::store::RetryingStore
+#[derive(Debug, Clone)]
+pub struct RetryingConfig<C: OpenStore> {
+    /// The configuration for the data store to retry operations on.
+    pub inner: C,
+
+    /// How to retry operations which fail with `ErrorClass::Connection`.
+    ///
+    /// The default is `RetryPolicy::default()`.
+    pub connection: RetryPolicy,
+
+    /// How to retry operations which fail with `ErrorClass::Timeout`.
+    ///
+    /// The default is `RetryPolicy::default()`.
+    pub timeout: RetryPolicy,
+
+    /// How to retry operations which fail with `ErrorClass::Other`.
+    ///
+    /// Many of these errors are permanent, so the default is `RetryPolicy::never()`.
+    pub other: RetryPolicy,
+}
+
+impl<C: OpenStore> RetryingConfig<C> {
+    /// Create a new `RetryingConfig` for the store opened by `inner` with the default policies.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            connection: RetryPolicy::default(),
+            timeout: RetryPolicy::default(),
+            other: RetryPolicy::never(),
+        }
+    }
+}
+
+impl<C: OpenStore> OpenStore for RetryingConfig<C> {
+    type Store = RetryingStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let mut store = RetryingStore::new(self.inner.open()?);
+        store.set_policy(ErrorClass::Connection, self.connection);
+        store.set_policy(ErrorClass::Timeout, self.timeout);
+        store.set_policy(ErrorClass::Other, self.other);
+        Ok(store)
+    }
+}
+
+/// A `DataStore` which retries failed operations on another data store.
+///
+/// When an operation on the inner data store fails, the error is classified with
+/// [`ErrorClass::of`] and the operation is retried with exponential backoff according to the
+/// [`RetryPolicy`] for that class. If the operation still fails after the maximum number of
+/// retries, the last error is returned. This keeps transient network failures from aborting long
+/// operations like committing a large repository.
+///
+/// Every `DataStore` operation is safe to retry, because writing or removing a block twice has the
+/// same effect as doing it once.
+///
+/// You can use [`RetryingConfig`] to open a data store of this type.
+///
+/// [`ErrorClass::of`]: crate::store::ErrorClass::of
+/// [`RetryPolicy`]: crate::store::RetryPolicy
+/// [`RetryingConfig`]: crate::store::RetryingConfig
+#[derive(Debug)]
+pub struct RetryingStore<S: DataStore> {
+    inner: S,
+    connection: RetryPolicy,
+    timeout: RetryPolicy,
+    other: RetryPolicy,
+}
+
+impl<S: DataStore> RetryingStore<S> {
+    /// Retry operations on the given `inner` data store with the default policies.
+    ///
+    /// The defaults are the same as for [`RetryingConfig::new`].
+    ///
+    /// [`RetryingConfig::new`]: crate::store::RetryingConfig::new
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            connection: RetryPolicy::default(),
+            timeout: RetryPolicy::default(),
+            other: RetryPolicy::never(),
+        }
+    }
+
+    /// The data store which operations are retried on.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Return how operations which fail with the given `class` of error are retried.
+    pub fn policy(&self, class: ErrorClass) -> RetryPolicy {
+        match class {
+            ErrorClass::Connection => self.connection,
+            ErrorClass::Timeout => self.timeout,
+            ErrorClass::Other => self.other,
+        }
+    }
+
+    /// Change how operations which fail with the given `class` of error are retried.
+    pub fn set_policy(&mut self, class: ErrorClass, policy: RetryPolicy) {
+        match class {
+            ErrorClass::Connection => self.connection = policy,
+            ErrorClass::Timeout => self.timeout = policy,
+            ErrorClass::Other => self.other = policy,
+        }
+    }
+
+    /// Perform `operation` on the inner data store, retrying it according to the policies.
+    fn retry<T>(
+        &mut self,
+        mut operation: impl FnMut(&mut S) -> super::Result<T>,
+    ) -> super::Result<T> {
+        // Each class of error has its own count of retries, so an operation which fails with
+        // different classes of errors is retried according to each of their policies.
+        let mut connection_retries = 0;
+        let mut timeout_retries = 0;
+        let mut other_retries = 0;
+
+        loop {
+            let error = match operation(&mut self.inner) {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            let class = ErrorClass::of(&error);
+            let policy = self.policy(class);
+            let retries = match class {
+                ErrorClass::Connection => &mut connection_retries,
+                ErrorClass::Timeout => &mut timeout_retries,
+                ErrorClass::Other => &mut other_retries,
+            };
+            if *retries >= policy.max_retries {
+                return Err(error);
+            }
+
+            thread::sleep(policy.delay(*retries));
+            *retries += 1;
+        }
+    }
+}
+
+impl<S: DataStore> DataStore for RetryingStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.retry(|store| store.write_block(key, data))
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.retry(|store| store.read_block(key))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.retry(|store| store.remove_block(key))
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.retry(|store| store.list_blocks(kind))
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.retry(|store| store.block_modified_time(key))
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.retry(|store| store.block_size(key))
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        self.inner.probe()
+    }
+}
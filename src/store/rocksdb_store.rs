@@ -0,0 +1,235 @@
+#![cfg(feature = "store-rocksdb")]
+
+use std::path::PathBuf;
+
+use rocksdb::{ColumnFamily, IteratorMode, Options, WriteBatch, WriteOptions, DB};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, LatencyClass, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("c1d7a3e4-8b2f-4f6a-b5e9-0d3c7a9f2e61");
+
+/// The name of the column family which contains data blocks.
+const DATA_FAMILY: &str = "data";
+
+/// The name of the column family which contains locks.
+const LOCKS_FAMILY: &str = "locks";
+
+/// The name of the column family which contains headers.
+const HEADERS_FAMILY: &str = "headers";
+
+/// The name of the column family which contains the superblock and the version block.
+const BLOCKS_FAMILY: &str = "blocks";
+
+/// The name of the column family which contains metadata about the store.
+const METADATA_FAMILY: &str = "metadata";
+
+/// The key of the version ID in the metadata column family.
+const VERSION_KEY: &[u8] = b"version";
+
+/// The key of the superblock in the blocks column family.
+const SUPER_KEY: &[u8] = b"super";
+
+/// The key of the version block in the blocks column family.
+const VERSION_BLOCK_KEY: &[u8] = b"version";
+
+/// The configuration for opening a [`RocksdbStore`].
+///
+/// [`RocksdbStore`]: crate::store::RocksdbStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-rocksdb")))]
+pub struct RocksdbConfig {
+    /// The path of the RocksDB database.
+    pub path: PathBuf,
+}
+
+impl OpenStore for RocksdbConfig {
+    type Store = RocksdbStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        let db = DB::open_cf(
+            &options,
+            &self.path,
+            [
+                DATA_FAMILY,
+                LOCKS_FAMILY,
+                HEADERS_FAMILY,
+                BLOCKS_FAMILY,
+                METADATA_FAMILY,
+            ],
+        )
+        .map_err(|error| crate::Error::Store(error.into()))?;
+
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(true);
+
+        let store = RocksdbStore { db, write_options };
+
+        let version_bytes = store
+            .db
+            .get_cf(store.family(METADATA_FAMILY), VERSION_KEY)
+            .map_err(|error| crate::Error::Store(error.into()))?;
+
+        match version_bytes {
+            Some(bytes) => {
+                let version =
+                    Uuid::from_slice(&bytes).map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+            None => {
+                store
+                    .db
+                    .put_cf_opt(
+                        store.family(METADATA_FAMILY),
+                        VERSION_KEY,
+                        CURRENT_VERSION.as_bytes(),
+                        &store.write_options,
+                    )
+                    .map_err(|error| crate::Error::Store(error.into()))?;
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// A `DataStore` which stores data in a RocksDB database.
+///
+/// This is suited for repositories with a very large number of small blocks, which can be slow to
+/// store as separate files. Each kind of block is stored in a separate column family, and every
+/// write is synced to disk before it returns.
+///
+/// A RocksDB database can only be open in one process at a time, so opening this store fails if
+/// another process has it open.
+///
+/// You can use [`RocksdbConfig`] to open a data store of this type.
+///
+/// [`RocksdbConfig`]: crate::store::RocksdbConfig
+#[cfg_attr(docsrs, doc(cfg(feature = "store-rocksdb")))]
+pub struct RocksdbStore {
+    /// The RocksDB database.
+    db: DB,
+
+    /// The options used for every write, which make writes durable.
+    write_options: WriteOptions,
+}
+
+impl std::fmt::Debug for RocksdbStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RocksdbStore")
+            .field("path", &self.db.path())
+            .finish_non_exhaustive()
+    }
+}
+
+impl RocksdbStore {
+    /// Return the column family with the given `name`.
+    fn family(&self, name: &str) -> &ColumnFamily {
+        // Every column family is created when the database is opened.
+        self.db
+            .cf_handle(name)
+            .expect("A column family of the RocksDB store is missing.")
+    }
+
+    /// Return the column family and key which the block with the given `key` is stored at.
+    fn location(&self, key: BlockKey) -> (&ColumnFamily, Vec<u8>) {
+        match key {
+            BlockKey::Data(id) => (self.family(DATA_FAMILY), id.as_ref().as_bytes().to_vec()),
+            BlockKey::Lock(id) => (self.family(LOCKS_FAMILY), id.as_ref().as_bytes().to_vec()),
+            BlockKey::Header(id) => (self.family(HEADERS_FAMILY), id.as_ref().as_bytes().to_vec()),
+            BlockKey::Super => (self.family(BLOCKS_FAMILY), SUPER_KEY.to_vec()),
+            BlockKey::Version => (self.family(BLOCKS_FAMILY), VERSION_BLOCK_KEY.to_vec()),
+        }
+    }
+}
+
+impl DataStore for RocksdbStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let (family, family_key) = self.location(key);
+        self.db
+            .put_cf_opt(family, family_key, data, &self.write_options)?;
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let (family, family_key) = self.location(key);
+        Ok(self.db.get_cf(family, family_key)?)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let (family, family_key) = self.location(key);
+        self.db
+            .delete_cf_opt(family, family_key, &self.write_options)?;
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        let mut batch = WriteBatch::default();
+        for (key, data) in blocks {
+            let (family, family_key) = self.location(*key);
+            batch.put_cf(family, family_key, data);
+        }
+        self.db.write_opt(batch, &self.write_options)?;
+        Ok(())
+    }
+
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        let locations = keys
+            .iter()
+            .map(|key| self.location(*key))
+            .collect::<Vec<_>>();
+        self.db
+            .multi_get_cf(locations)
+            .into_iter()
+            .map(|result| result.map_err(super::Error::from))
+            .collect()
+    }
+
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        let mut batch = WriteBatch::default();
+        for key in keys {
+            let (family, family_key) = self.location(*key);
+            batch.delete_cf(family, family_key);
+        }
+        self.db.write_opt(batch, &self.write_options)?;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        // Writes made through this store are already synced, but the write-ahead log may also
+        // contain writes which were made without syncing, like by another process.
+        self.db.flush_wal(true)?;
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let family = match kind {
+            BlockType::Data => self.family(DATA_FAMILY),
+            BlockType::Lock => self.family(LOCKS_FAMILY),
+            BlockType::Header => self.family(HEADERS_FAMILY),
+        };
+
+        let mut ids = Vec::new();
+        for entry in self.db.iterator_cf(family, IteratorMode::Start) {
+            let (key, _) = entry?;
+            ids.push(Uuid::from_slice(&key)?.into());
+        }
+
+        Ok(ids)
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            latency: LatencyClass::Local,
+            ..StoreCapabilities::default()
+        }
+    }
+}
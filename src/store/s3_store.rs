@@ -1,13 +1,14 @@
 #![cfg(feature = "store-s3")]
 
 use std::env;
+use std::time::{Duration, SystemTime};
 
 use s3::bucket::Bucket;
 use s3::creds::Credentials;
 use s3::region::Region;
 use uuid::{uuid, Uuid};
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, StoreCapabilities};
 use super::open_store::OpenStore;
 
 /// The separator to use in S3 object keys.
@@ -302,9 +303,14 @@ impl S3Credentials {
 
 /// The configuration for opening an [`S3Store`].
 ///
+/// This struct is non-exhaustive so that options can be added without breaking changes. Create it
+/// with [`S3Config::new`] and then set any other fields you need.
+///
 /// [`S3Store`]: crate::store::S3Store
+/// [`S3Config::new`]: crate::store::S3Config::new
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(docsrs, doc(cfg(feature = "store-s3")))]
+#[non_exhaustive]
 pub struct S3Config {
     /// The name of the S3 bucket.
     pub bucket: String,
@@ -320,9 +326,33 @@ pub struct S3Config {
     /// While keys in S3 are a flat namespace, you can think of this like the directory of the
     /// bucket to create the store in. To create the store in the bucket root, use an empty string.
     pub prefix: String,
+
+    /// The default retention period of the bucket, if it has S3 Object Lock enabled.
+    ///
+    /// Objects in a bucket with Object Lock enabled can't be removed until their retention period
+    /// expires, so repositories only remove blocks which are older than this period. The S3 client
+    /// this store uses can't read the Object Lock configuration of a bucket, so this must match
+    /// the default retention period configured for the bucket. If the bucket doesn't have Object
+    /// Lock enabled, use `None`.
+    ///
+    /// See [`DataStore::retention`] for details.
+    ///
+    /// [`DataStore::retention`]: crate::store::DataStore::retention
+    pub retention: Option<Duration>,
 }
 
 impl S3Config {
+    /// Return a new config for the root of the given `bucket` without a retention period.
+    pub fn new(bucket: impl Into<String>, region: S3Region, credentials: S3Credentials) -> Self {
+        S3Config {
+            bucket: bucket.into(),
+            region,
+            credentials,
+            prefix: String::new(),
+            retention: None,
+        }
+    }
+
     fn into_bucket(self) -> Bucket {
         Bucket::new(
             self.bucket.as_str(),
@@ -383,7 +413,11 @@ impl OpenStore for S3Config {
             Err(error) => return Err(crate::Error::Store(super::Error::from(error))),
         };
 
-        Ok(S3Store { bucket, prefix })
+        Ok(S3Store {
+            bucket,
+            prefix,
+            retention: self.retention,
+        })
     }
 }
 
@@ -397,6 +431,7 @@ impl OpenStore for S3Config {
 pub struct S3Store {
     bucket: Bucket,
     prefix: String,
+    retention: Option<Duration>,
 }
 
 impl S3Store {
@@ -470,4 +505,37 @@ impl DataStore for S3Store {
             .collect::<Result<Vec<BlockId>, _>>()?;
         Ok(block_ids)
     }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        let block_path = self.block_path(key);
+        let (head, status_code) = self.bucket.head_object(block_path)?;
+        if status_code == NOT_FOUND_CODE {
+            return Ok(None);
+        }
+        match head.last_modified {
+            Some(last_modified) => Ok(Some(httpdate::parse_http_date(&last_modified)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let block_path = self.block_path(key);
+        let (head, status_code) = self.bucket.head_object(block_path)?;
+        if status_code == NOT_FOUND_CODE {
+            return Ok(None);
+        }
+        Ok(head.content_length.map(|length| length as u64))
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.retention
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            // This is the maximum size of an object uploaded in a single request.
+            max_block_size: Some(5 * 1024 * 1024 * 1024),
+            ..StoreCapabilities::default()
+        }
+    }
 }
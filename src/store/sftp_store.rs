@@ -1,11 +1,16 @@
 #![cfg(feature = "store-sftp")]
 
+use std::collections::HashMap;
 use std::fmt::{self, Debug, Formatter};
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::net::{SocketAddr, TcpStream};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::sleep;
+use std::time::Duration;
 
-use ssh2::{self, RenameFlags, Session, Sftp};
+use once_cell::sync::Lazy;
+use ssh2::{self, ErrorCode, RenameFlags, Session, Sftp};
 use uuid::Uuid;
 
 use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
@@ -19,6 +24,30 @@ const STORE_DIRECTORY: &str = "store";
 const STAGING_DIRECTORY: &str = "stage";
 const VERSION_FILE: &str = "version";
 
+// Error codes from libssh2 which indicate that the connection was lost or timed out.
+const LIBSSH2_ERROR_SOCKET_SEND: i32 = -7;
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+const LIBSSH2_ERROR_SOCKET_DISCONNECT: i32 = -13;
+const LIBSSH2_ERROR_CHANNEL_CLOSED: i32 = -26;
+const LIBSSH2_ERROR_SOCKET_TIMEOUT: i32 = -30;
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+const LIBSSH2_ERROR_SOCKET_RECV: i32 = -43;
+
+// SFTP status codes.
+const LIBSSH2_FX_NO_SUCH_FILE: i32 = 2;
+const LIBSSH2_FX_NO_CONNECTION: i32 = 6;
+const LIBSSH2_FX_CONNECTION_LOST: i32 = 7;
+
+/// The default number of times an operation is retried after a transient failure.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// The default amount of time to wait before retrying an operation for the first time.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The SSH sessions which are shared between stores, indexed by the server and authentication.
+static SESSIONS: Lazy<Mutex<HashMap<(SocketAddr, SftpAuth), Weak<Session>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
 fn type_path(kind: BlockType) -> PathBuf {
     match kind {
         BlockType::Data => [STORE_DIRECTORY, "data"].iter().collect(),
@@ -49,7 +78,7 @@ fn block_path(key: BlockKey) -> PathBuf {
 }
 
 /// The authentication for an SSH connection.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 #[cfg_attr(docsrs, doc(cfg(feature = "store-sftp")))]
 pub enum SftpAuth {
     /// Authenticate with a password.
@@ -91,6 +120,10 @@ pub enum SftpAuth {
 
 /// The configuration for opening an [`SftpStore`].
 ///
+/// Operations which fail because the connection was lost or timed out are retried after
+/// reconnecting to the server. The delay between attempts starts at `retry_delay` and doubles with
+/// each attempt.
+///
 /// [`SftpStore`]: crate::store::SftpStore
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(docsrs, doc(cfg(feature = "store-sftp")))]
@@ -103,28 +136,54 @@ pub struct SftpConfig {
 
     /// The path of the store on the server.
     pub path: PathBuf,
+
+    /// Whether stores share a connection to the server.
+    ///
+    /// If this is `true`, stores which are opened while another store with the same `addr` and
+    /// `auth` is open use the same SSH connection rather than connecting and authenticating again.
+    /// Each store still uses its own SFTP channel. The connection is closed once every store using
+    /// it is dropped.
+    ///
+    /// This is `true` by default.
+    pub reuse_connection: bool,
+
+    /// The maximum number of times to retry an operation which fails because of a transient
+    /// error.
+    ///
+    /// If this is `0`, operations are never retried. This is `3` by default.
+    pub max_retries: u32,
+
+    /// The amount of time to wait before retrying an operation for the first time.
+    ///
+    /// This is 500 milliseconds by default.
+    pub retry_delay: Duration,
 }
 
-impl OpenStore for SftpConfig {
-    type Store = SftpStore;
+impl SftpConfig {
+    /// Create a new config for the store at `path` on the server at `addr`.
+    ///
+    /// This uses the default values for the other options.
+    pub fn new(addr: SocketAddr, auth: SftpAuth, path: impl Into<PathBuf>) -> Self {
+        Self {
+            addr,
+            auth,
+            path: path.into(),
+            reuse_connection: true,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
 
-    fn open(&self) -> crate::Result<Self::Store> {
-        // Connect to the SSH server.
-        let stream = TcpStream::connect(self.addr)
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-        let mut session =
-            Session::new().map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+    /// Connect and authenticate to the SSH server.
+    fn connect(&self) -> super::Result<Session> {
+        let stream = TcpStream::connect(self.addr)?;
+        let mut session = Session::new()?;
         session.set_tcp_stream(stream);
-        session
-            .handshake()
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+        session.handshake()?;
 
-        // Perform authentication.
         match &self.auth {
             SftpAuth::Password { username, password } => {
-                session
-                    .userauth_password(username, password)
-                    .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+                session.userauth_password(username, password)?;
             }
             SftpAuth::Key {
                 username,
@@ -132,63 +191,94 @@ impl OpenStore for SftpConfig {
                 private_key,
                 password,
             } => {
-                session
-                    .userauth_pubkey_file(
-                        username,
-                        public_key.as_ref().map(|path| path.as_path()),
-                        private_key,
-                        password.as_ref().map(|str| str.as_str()),
-                    )
-                    .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+                session.userauth_pubkey_file(
+                    username,
+                    public_key.as_ref().map(|path| path.as_path()),
+                    private_key,
+                    password.as_ref().map(|str| str.as_str()),
+                )?;
             }
             SftpAuth::Agent { username, comment } => match comment {
                 Some(comment) => {
-                    let mut agent = session
-                        .agent()
-                        .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-                    agent
-                        .connect()
-                        .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-                    agent
-                        .list_identities()
-                        .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
-                    let identities = agent
-                        .identities()
-                        .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+                    let mut agent = session.agent()?;
+                    agent.connect()?;
+                    agent.list_identities()?;
+                    let identities = agent.identities()?;
                     let key = identities
                         .iter()
                         .find(|key| key.comment() == comment)
                         .ok_or_else(|| {
                             super::Error::msg("No key with matching comment found in agent.")
-                        })
-                        .map_err(crate::Error::Store)?;
-                    agent
-                        .userauth(username, key)
-                        .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+                        })?;
+                    agent.userauth(username, key)?;
                 }
                 None => {
-                    session
-                        .userauth_agent(username)
-                        .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+                    session.userauth_agent(username)?;
                 }
             },
         }
 
-        let sftp = session
-            .sftp()
-            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+        Ok(session)
+    }
+
+    /// Return an authenticated session with the SSH server.
+    ///
+    /// If connections are reused, this returns the session shared with other stores unless it is
+    /// the `failed` session, in which case a new session replaces it.
+    fn session(&self, failed: Option<&Arc<Session>>) -> super::Result<Arc<Session>> {
+        if !self.reuse_connection {
+            return Ok(Arc::new(self.connect()?));
+        }
+
+        let key = (self.addr, self.auth.clone());
+        let mut sessions = SESSIONS.lock().unwrap();
+
+        // Clean up sessions which are no longer in use.
+        sessions.retain(|_, session| session.strong_count() > 0);
+
+        if let Some(session) = sessions.get(&key).and_then(Weak::upgrade) {
+            if !failed.is_some_and(|failed| Arc::ptr_eq(failed, &session)) {
+                return Ok(session);
+            }
+        }
+
+        let session = Arc::new(self.connect()?);
+        sessions.insert(key, Arc::downgrade(&session));
+        Ok(session)
+    }
+
+    /// Open a new SFTP channel, retrying if the connection fails because of a transient error.
+    fn channel(&self, mut failed: Option<Arc<Session>>) -> super::Result<(Arc<Session>, Sftp)> {
+        retry(self, || {
+            let session = self.session(failed.as_ref())?;
+            match session.sftp() {
+                Ok(sftp) => Ok((session, sftp)),
+                Err(error) => {
+                    failed = Some(session);
+                    Err(error.into())
+                }
+            }
+        })
+    }
+}
+
+impl OpenStore for SftpConfig {
+    type Store = SftpStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let (session, sftp) = self.channel(None).map_err(crate::Error::Store)?;
 
         // Create the directories if they don't exist.
         let directories = &[
             self.path.to_owned(),
             self.path.join(STAGING_DIRECTORY),
             self.path.join(STORE_DIRECTORY),
-            type_path(BlockType::Data),
-            type_path(BlockType::Lock),
-            type_path(BlockType::Header),
+            self.path.join(type_path(BlockType::Data)),
+            self.path.join(type_path(BlockType::Lock)),
+            self.path.join(type_path(BlockType::Header)),
         ];
         for directory in directories {
-            if sftp.stat(directory).is_err() {
+            if !exists(&sftp, directory).map_err(crate::Error::Store)? {
                 sftp.mkdir(directory, 0o755)
                     .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
             }
@@ -196,7 +286,7 @@ impl OpenStore for SftpConfig {
 
         let version_path = self.path.join(VERSION_FILE);
 
-        if sftp.stat(&version_path).is_ok() {
+        if exists(&sftp, &version_path).map_err(crate::Error::Store)? {
             // Read the version ID file.
             let mut version_file = sftp
                 .open(&version_path)
@@ -217,12 +307,85 @@ impl OpenStore for SftpConfig {
         }
 
         Ok(SftpStore {
+            config: self.clone(),
+            session,
             sftp,
-            path: self.path.clone(),
         })
     }
 }
 
+/// Return whether the given error was caused by a lost or timed out connection.
+fn is_transient(error: &super::Error) -> bool {
+    fn is_transient_ssh(error: &ssh2::Error) -> bool {
+        matches!(
+            error.code(),
+            ErrorCode::Session(
+                LIBSSH2_ERROR_SOCKET_SEND
+                    | LIBSSH2_ERROR_TIMEOUT
+                    | LIBSSH2_ERROR_SOCKET_DISCONNECT
+                    | LIBSSH2_ERROR_CHANNEL_CLOSED
+                    | LIBSSH2_ERROR_SOCKET_TIMEOUT
+                    | LIBSSH2_ERROR_EAGAIN
+                    | LIBSSH2_ERROR_SOCKET_RECV
+            ) | ErrorCode::SFTP(LIBSSH2_FX_NO_CONNECTION | LIBSSH2_FX_CONNECTION_LOST)
+        )
+    }
+
+    if let Some(error) = error.downcast_ref::<ssh2::Error>() {
+        return is_transient_ssh(error);
+    }
+
+    if let Some(error) = error.downcast_ref::<io::Error>() {
+        let transient_kind = matches!(
+            error.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::UnexpectedEof
+        );
+        let transient_inner = error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<ssh2::Error>())
+            .is_some_and(is_transient_ssh);
+        return transient_kind || transient_inner;
+    }
+
+    false
+}
+
+/// Call `operation` until it succeeds or fails with an error which isn't transient, up to the
+/// number of retries in `config`.
+fn retry<T>(
+    config: &SftpConfig,
+    mut operation: impl FnMut() -> super::Result<T>,
+) -> super::Result<T> {
+    let mut delay = config.retry_delay;
+    let mut retries = 0;
+    loop {
+        match operation() {
+            Err(error) if retries < config.max_retries && is_transient(&error) => {
+                sleep(delay);
+                delay *= 2;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Return whether the given remote `path` exists.
+fn exists(sftp: &Sftp, path: &Path) -> super::Result<bool> {
+    match sftp.stat(path) {
+        Ok(_) => Ok(true),
+        Err(error) if error.code() == ErrorCode::SFTP(LIBSSH2_FX_NO_SUCH_FILE) => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
 /// A `DataStore` which stores data on an SFTP server.
 ///
 /// You can use [`SftpConfig`] to open a data store of this type.
@@ -230,126 +393,168 @@ impl OpenStore for SftpConfig {
 /// [`SftpConfig`]: crate::store::SftpConfig
 #[cfg_attr(docsrs, doc(cfg(feature = "store-sftp")))]
 pub struct SftpStore {
+    config: SftpConfig,
+    session: Arc<Session>,
     sftp: Sftp,
-    path: PathBuf,
 }
 
 impl SftpStore {
-    /// Return the path where a block with the given `id` will be stored.
-    fn block_path(&self, key: BlockKey) -> PathBuf {
-        self.path.join(block_path(key))
-    }
+    /// Call `operation` with the SFTP channel and the path of the store.
+    ///
+    /// If the operation fails because of a transient error, this reconnects to the server and
+    /// tries again, up to the maximum number of retries. Operations must be safe to repeat.
+    fn with_retry<T>(
+        &mut self,
+        mut operation: impl FnMut(&Sftp, &Path) -> super::Result<T>,
+    ) -> super::Result<T> {
+        let mut delay = self.config.retry_delay;
+        let mut retries = 0;
+        loop {
+            match operation(&self.sftp, &self.config.path) {
+                Err(error) if retries < self.config.max_retries && is_transient(&error) => {}
+                result => return result,
+            }
 
-    /// Return the path where a block with the given `id` will be staged.
-    fn staging_path(&self) -> PathBuf {
-        let uuid_str = Uuid::new_v4().as_hyphenated().to_string();
-        self.path.join(STAGING_DIRECTORY).join(uuid_str)
+            sleep(delay);
+            delay *= 2;
+            retries += 1;
+
+            // The connection was most likely lost, so we open a new channel on a new connection.
+            // If that fails too, the operation fails again on the old channel and we wait before
+            // trying to reconnect again.
+            let channel = self
+                .config
+                .session(Some(&self.session))
+                .and_then(|session| {
+                    let sftp = session.sftp()?;
+                    Ok((session, sftp))
+                });
+            match channel {
+                Ok((session, sftp)) => {
+                    self.sftp = sftp;
+                    self.session = session;
+                }
+                Err(error) if !is_transient(&error) => return Err(error),
+                Err(_) => {}
+            }
+        }
     }
+}
 
-    /// Return whether the given remote `path` exists.
-    fn exists(&self, path: &Path) -> bool {
-        self.sftp.stat(path).is_ok()
-    }
+/// Return the path where a block with the given `key` will be stored in the store at `root`.
+fn remote_block_path(root: &Path, key: BlockKey) -> PathBuf {
+    root.join(block_path(key))
+}
+
+/// Return the path where a block will be staged in the store at `root`.
+fn staging_path(root: &Path) -> PathBuf {
+    let uuid_str = Uuid::new_v4().as_hyphenated().to_string();
+    root.join(STAGING_DIRECTORY).join(uuid_str)
+}
+
+/// Parse the ID of a block from the path of its file.
+fn parse_block_id(block_path: &Path) -> super::Result<BlockId> {
+    let file_name = block_path
+        .file_name()
+        .unwrap()
+        .to_str()
+        .ok_or_else(|| super::Error::msg("Block file name is invalid."))?;
+    let id = Uuid::parse_str(file_name)
+        .map_err(|_| super::Error::msg("Block file name is invalid."))?
+        .into();
+    Ok(id)
 }
 
 impl DataStore for SftpStore {
     fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
-        let staging_path = self.staging_path();
-        let block_path = self.block_path(key);
-
-        // If this is the first block its sub-directory, the directory needs to be created.
-        let parent = block_path.parent().unwrap();
-        if !self.exists(parent) {
-            self.sftp.mkdir(parent, 0o755)?;
-        }
+        self.with_retry(|sftp, root| {
+            let staging_path = staging_path(root);
+            let block_path = remote_block_path(root, key);
+
+            // If this is the first block its sub-directory, the directory needs to be created.
+            let parent = block_path.parent().unwrap();
+            if !exists(sftp, parent)? {
+                sftp.mkdir(parent, 0o755)?;
+            }
 
-        // Write to a staging file and then atomically move it to its final destination.
-        let mut staging_file = self.sftp.create(&staging_path)?;
-        staging_file.write_all(data)?;
-        staging_file.flush()?;
-        self.sftp.rename(
-            &staging_path,
-            &block_path,
-            Some(RenameFlags::ATOMIC | RenameFlags::OVERWRITE),
-        )?;
-
-        // Remove any unused staging files.
-        for (path, _) in self.sftp.readdir(&self.path.join(STAGING_DIRECTORY))? {
-            self.sftp.unlink(&path)?;
-        }
+            // Write to a staging file and then atomically move it to its final destination.
+            let mut staging_file = sftp.create(&staging_path)?;
+            staging_file.write_all(data)?;
+            staging_file.flush()?;
+            sftp.rename(
+                &staging_path,
+                &block_path,
+                Some(RenameFlags::ATOMIC | RenameFlags::OVERWRITE),
+            )?;
+
+            // Remove any unused staging files.
+            for (path, _) in sftp.readdir(&root.join(STAGING_DIRECTORY))? {
+                sftp.unlink(&path)?;
+            }
 
-        Ok(())
+            Ok(())
+        })
     }
 
     fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
-        let block_path = self.block_path(key);
+        self.with_retry(|sftp, root| {
+            let block_path = remote_block_path(root, key);
 
-        if !self.exists(&block_path) {
-            return Ok(None);
-        }
+            if !exists(sftp, &block_path)? {
+                return Ok(None);
+            }
 
-        let mut file = self.sftp.open(&block_path)?;
+            let mut file = sftp.open(&block_path)?;
 
-        let mut buffer = Vec::with_capacity(file.stat()?.size.unwrap_or(0) as usize);
-        file.read_to_end(&mut buffer)?;
-        Ok(Some(buffer))
+            let mut buffer = Vec::with_capacity(file.stat()?.size.unwrap_or(0) as usize);
+            file.read_to_end(&mut buffer)?;
+            Ok(Some(buffer))
+        })
     }
 
     fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
-        let block_path = self.block_path(key);
+        self.with_retry(|sftp, root| {
+            let block_path = remote_block_path(root, key);
 
-        if !self.exists(&block_path) {
-            return Ok(());
-        }
+            if !exists(sftp, &block_path)? {
+                return Ok(());
+            }
 
-        self.sftp.unlink(&block_path)?;
+            sftp.unlink(&block_path)?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
-        let mut block_ids = Vec::new();
-
-        match kind {
-            BlockType::Data => {
-                let block_directories = self.sftp.readdir(&self.path.join(type_path(kind)))?;
-                for (block_directory, _) in block_directories {
-                    for (block_path, _) in self.sftp.readdir(&block_directory)? {
-                        let file_name = block_path
-                            .file_name()
-                            .unwrap()
-                            .to_str()
-                            .ok_or_else(|| super::Error::msg("Block file name is invalid."))?;
-                        let id = Uuid::parse_str(file_name)
-                            .map_err(|_| super::Error::msg("Block file name is invalid."))?
-                            .into();
-                        block_ids.push(id);
+        self.with_retry(|sftp, root| {
+            let mut block_ids = Vec::new();
+
+            match kind {
+                BlockType::Data => {
+                    let block_directories = sftp.readdir(&root.join(type_path(kind)))?;
+                    for (block_directory, _) in block_directories {
+                        for (block_path, _) in sftp.readdir(&block_directory)? {
+                            block_ids.push(parse_block_id(&block_path)?);
+                        }
                     }
                 }
-            }
-            BlockType::Lock | BlockType::Header => {
-                for (block_path, _) in self.sftp.readdir(&self.path.join(type_path(kind)))? {
-                    let file_name = block_path
-                        .file_name()
-                        .unwrap()
-                        .to_str()
-                        .ok_or_else(|| super::Error::msg("Block file name is invalid."))?;
-                    let id = Uuid::parse_str(file_name)
-                        .map_err(|_| super::Error::msg("Block file name is invalid."))?
-                        .into();
-                    block_ids.push(id);
+                BlockType::Lock | BlockType::Header => {
+                    for (block_path, _) in sftp.readdir(&root.join(type_path(kind)))? {
+                        block_ids.push(parse_block_id(&block_path)?);
+                    }
                 }
             }
-        }
 
-        Ok(block_ids)
+            Ok(block_ids)
+        })
     }
 }
 
 impl Debug for SftpStore {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("SftpStore")
-            .field("path", &self.path)
+            .field("path", &self.config.path)
             .finish_non_exhaustive()
     }
 }
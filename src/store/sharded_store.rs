@@ -0,0 +1,345 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// The configuration for opening a [`ShardedStore`].
+///
+/// Opening the store fails if any of the shards can't be opened.
+///
+/// [`ShardedStore`]: crate::store::ShardedStore
+#[derive(Debug, Clone)]
+pub struct ShardedConfig<C: OpenStore> {
+    /// The configurations for the data stores to distribute blocks across.
+    ///
+    /// Which shard a block is stored in depends on the position of each shard, so shards must
+    /// always be in the same order. New shards must be added to the end.
+    pub shards: Vec<C>,
+}
+
+impl<C: OpenStore> ShardedConfig<C> {
+    /// Create a new `ShardedConfig` which distributes blocks across the stores opened by `shards`.
+    pub fn new(shards: Vec<C>) -> Self {
+        Self { shards }
+    }
+}
+
+impl<C: OpenStore> OpenStore for ShardedConfig<C> {
+    type Store = ShardedStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        if self.shards.is_empty() {
+            return Err(crate::Error::UnsupportedStore);
+        }
+        let shards = self
+            .shards
+            .iter()
+            .map(|config| config.open())
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(ShardedStore::new(shards))
+    }
+}
+
+/// A `DataStore` which distributes blocks across several other data stores.
+///
+/// Each block is stored in one shard, which is chosen by hashing its ID. This allows a repository
+/// to span several disks or buckets which are each too small to hold it on their own. The
+/// superblock and the version block are always stored in the first shard.
+///
+/// Shards are chosen using rendezvous hashing, so adding a shard only moves the blocks which now
+/// belong in the new shard. After adding a shard, either with [`add_shard`] or by adding it to the
+/// [`ShardedConfig`], use [`rebalance`] to move those blocks. Blocks which haven't been moved yet
+/// are still found when they're read, because a block which is missing from its shard is looked
+/// for in every other shard. Blocks are removed from every shard for the same reason.
+///
+/// You can use [`ShardedConfig`] to open a data store of this type.
+///
+/// [`add_shard`]: crate::store::ShardedStore::add_shard
+/// [`rebalance`]: crate::store::ShardedStore::rebalance
+/// [`ShardedConfig`]: crate::store::ShardedConfig
+#[derive(Debug)]
+pub struct ShardedStore<S: DataStore> {
+    shards: Vec<S>,
+}
+
+/// Return the weight of the shard at `index` for the block with the given `id`.
+fn shard_weight(id: BlockId, index: usize) -> u64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(id.as_ref().as_bytes());
+    hasher.update(&(index as u64).to_le_bytes());
+    let hash = hasher.finalize();
+    u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap())
+}
+
+impl<S: DataStore> ShardedStore<S> {
+    /// Distribute blocks across the given `shards`.
+    ///
+    /// # Panics
+    /// - `shards` is empty.
+    pub fn new(shards: Vec<S>) -> Self {
+        assert!(
+            !shards.is_empty(),
+            "A sharded store needs at least one shard."
+        );
+        Self { shards }
+    }
+
+    /// The data stores which blocks are distributed across.
+    pub fn shards(&self) -> &[S] {
+        &self.shards
+    }
+
+    /// Consume this store and return the data stores which blocks are distributed across.
+    pub fn into_shards(self) -> Vec<S> {
+        self.shards
+    }
+
+    /// Add a new `shard` to the end of the shards to distribute blocks across.
+    ///
+    /// Existing blocks aren't moved into the new shard until [`rebalance`] is called.
+    ///
+    /// [`rebalance`]: crate::store::ShardedStore::rebalance
+    pub fn add_shard(&mut self, shard: S) {
+        self.shards.push(shard);
+    }
+
+    /// Move every block which isn't in the shard it belongs in.
+    ///
+    /// If a block is in both the shard it belongs in and another shard, the copy in the shard it
+    /// belongs in is the most recent one, so the other copy is removed. This returns the number of
+    /// blocks which were moved or removed.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with one of the shards.
+    pub fn rebalance(&mut self) -> crate::Result<u64> {
+        let mut count = 0;
+
+        for source in 0..self.shards.len() {
+            for kind in [BlockType::Data, BlockType::Header, BlockType::Lock] {
+                let ids = self.shards[source]
+                    .list_blocks(kind)
+                    .map_err(crate::Error::Store)?;
+                for id in ids {
+                    let key = block_key(kind, id);
+                    let target = self.shard_index(key);
+                    if target == source {
+                        continue;
+                    }
+
+                    let target_has_block = self.shards[target]
+                        .read_block(key)
+                        .map_err(crate::Error::Store)?
+                        .is_some();
+                    if !target_has_block {
+                        // The block may have been removed since it was listed.
+                        let data = match self.shards[source]
+                            .read_block(key)
+                            .map_err(crate::Error::Store)?
+                        {
+                            Some(data) => data,
+                            None => continue,
+                        };
+                        self.shards[target]
+                            .write_block(key, &data)
+                            .map_err(crate::Error::Store)?;
+                    }
+                    self.shards[source]
+                        .remove_block(key)
+                        .map_err(crate::Error::Store)?;
+                    count += 1;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Return the index of the shard the block with the given `key` belongs in.
+    fn shard_index(&self, key: BlockKey) -> usize {
+        match key {
+            BlockKey::Data(id) | BlockKey::Lock(id) | BlockKey::Header(id) => {
+                (0..self.shards.len())
+                    .max_by_key(|&index| shard_weight(id, index))
+                    .unwrap()
+            }
+            BlockKey::Super | BlockKey::Version => 0,
+        }
+    }
+
+    /// Read the block with the given `key` from every shard except the one at `home`.
+    fn read_elsewhere(&mut self, key: BlockKey, home: usize) -> super::Result<Option<Vec<u8>>> {
+        for (index, shard) in self.shards.iter_mut().enumerate() {
+            if index != home {
+                if let Some(data) = shard.read_block(key)? {
+                    return Ok(Some(data));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Return the key of the block of the given `kind` with the given `id`.
+fn block_key(kind: BlockType, id: BlockId) -> BlockKey {
+    match kind {
+        BlockType::Data => BlockKey::Data(id),
+        BlockType::Lock => BlockKey::Lock(id),
+        BlockType::Header => BlockKey::Header(id),
+    }
+}
+
+impl<S: DataStore> DataStore for ShardedStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let index = self.shard_index(key);
+        self.shards[index].write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let index = self.shard_index(key);
+        match self.shards[index].read_block(key)? {
+            Some(data) => Ok(Some(data)),
+            None => self.read_elsewhere(key, index),
+        }
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        for shard in self.shards.iter_mut() {
+            shard.remove_block(key)?;
+        }
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let mut ids = HashSet::new();
+        for shard in self.shards.iter_mut() {
+            ids.extend(shard.list_blocks(kind)?);
+        }
+        Ok(ids.into_iter().collect())
+    }
+
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        let mut batches = vec![Vec::new(); self.shards.len()];
+        for &(key, data) in blocks {
+            batches[self.shard_index(key)].push((key, data));
+        }
+        for (shard, batch) in self.shards.iter_mut().zip(batches) {
+            if !batch.is_empty() {
+                shard.write_blocks(&batch)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        let mut batches = vec![Vec::new(); self.shards.len()];
+        let mut positions = vec![Vec::new(); self.shards.len()];
+        for (position, &key) in keys.iter().enumerate() {
+            let index = self.shard_index(key);
+            batches[index].push(key);
+            positions[index].push(position);
+        }
+
+        let mut results = vec![None; keys.len()];
+        for (index, (batch, positions)) in batches.into_iter().zip(positions).enumerate() {
+            if batch.is_empty() {
+                continue;
+            }
+            let blocks = self.shards[index].read_blocks(&batch)?;
+            for ((key, position), data) in batch.into_iter().zip(positions).zip(blocks) {
+                results[position] = match data {
+                    Some(data) => Some(data),
+                    None => self.read_elsewhere(key, index)?,
+                };
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        for shard in self.shards.iter_mut() {
+            shard.remove_blocks(keys)?;
+        }
+        Ok(())
+    }
+
+    fn consistency(&self) -> Consistency {
+        if self
+            .shards
+            .iter()
+            .all(|shard| shard.consistency() == Consistency::Strong)
+        {
+            Consistency::Strong
+        } else {
+            Consistency::Eventual
+        }
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        let index = self.shard_index(key);
+        match self.shards[index].block_modified_time(key)? {
+            Some(time) => Ok(Some(time)),
+            None => {
+                for (other, shard) in self.shards.iter_mut().enumerate() {
+                    if other != index {
+                        if let Some(time) = shard.block_modified_time(key)? {
+                            return Ok(Some(time));
+                        }
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let index = self.shard_index(key);
+        match self.shards[index].block_size(key)? {
+            Some(size) => Ok(Some(size)),
+            None => {
+                for (other, shard) in self.shards.iter_mut().enumerate() {
+                    if other != index {
+                        if let Some(size) = shard.block_size(key)? {
+                            return Ok(Some(size));
+                        }
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.shards
+            .iter()
+            .filter_map(|shard| shard.retention())
+            .max()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        for shard in self.shards.iter_mut() {
+            shard.sync()?;
+        }
+        Ok(())
+    }
+
+    fn is_read_only(&self) -> bool {
+        // Blocks may be written to any shard, so one read-only shard makes the whole store
+        // read-only.
+        self.shards.iter().any(|shard| shard.is_read_only())
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        let capabilities = self
+            .shards
+            .iter()
+            .map(|shard| shard.probe())
+            .reduce(StoreCapabilities::combine)
+            .unwrap();
+        StoreCapabilities {
+            writable: !self.is_read_only(),
+            ..capabilities
+        }
+    }
+}
@@ -0,0 +1,165 @@
+#![cfg(feature = "store-sled")]
+
+use std::path::PathBuf;
+
+use sled::{Db, Tree};
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, LatencyClass, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("5b0e7d2a-3c1f-4e8b-9a6d-2f4c8e1b7a90");
+
+/// The key of the version ID in the metadata tree.
+const VERSION_KEY: &[u8] = b"version";
+
+/// The key of the superblock in the blocks tree.
+const SUPER_KEY: &[u8] = b"super";
+
+/// The key of the version block in the blocks tree.
+const VERSION_BLOCK_KEY: &[u8] = b"version";
+
+/// The configuration for opening a [`SledStore`].
+///
+/// [`SledStore`]: crate::store::SledStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-sled")))]
+pub struct SledConfig {
+    /// The path of the sled database.
+    pub path: PathBuf,
+}
+
+impl OpenStore for SledConfig {
+    type Store = SledStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let db = sled::open(&self.path).map_err(|error| crate::Error::Store(error.into()))?;
+        let open_tree = |name: &str| {
+            db.open_tree(name)
+                .map_err(|error| crate::Error::Store(error.into()))
+        };
+        let data = open_tree("data")?;
+        let locks = open_tree("locks")?;
+        let headers = open_tree("headers")?;
+        let blocks = open_tree("blocks")?;
+        let metadata = open_tree("metadata")?;
+
+        let version_bytes = metadata
+            .get(VERSION_KEY)
+            .map_err(|error| crate::Error::Store(error.into()))?;
+
+        match version_bytes {
+            Some(bytes) => {
+                let version =
+                    Uuid::from_slice(&bytes).map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+            None => {
+                metadata
+                    .insert(VERSION_KEY, &CURRENT_VERSION.as_bytes()[..])
+                    .map_err(|error| crate::Error::Store(error.into()))?;
+                db.flush()
+                    .map_err(|error| crate::Error::Store(error.into()))?;
+            }
+        }
+
+        Ok(SledStore {
+            db,
+            data,
+            locks,
+            headers,
+            blocks,
+        })
+    }
+}
+
+/// A `DataStore` which stores data in a sled database.
+///
+/// [sled] is an embedded database written in pure Rust, so this store doesn't require any native
+/// dependencies. Each kind of block is stored in a separate tree, and the database is flushed to
+/// disk after every write so that blocks are stored persistently once a write returns.
+///
+/// A sled database can only be open in one process at a time, so opening this store fails if
+/// another process has it open.
+///
+/// You can use [`SledConfig`] to open a data store of this type.
+///
+/// [sled]: https://sled.rs/
+/// [`SledConfig`]: crate::store::SledConfig
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-sled")))]
+pub struct SledStore {
+    /// The sled database.
+    db: Db,
+
+    /// The tree which contains data blocks.
+    data: Tree,
+
+    /// The tree which contains locks.
+    locks: Tree,
+
+    /// The tree which contains headers.
+    headers: Tree,
+
+    /// The tree which contains the superblock and the version block.
+    blocks: Tree,
+}
+
+impl SledStore {
+    /// Return the tree and key which the block with the given `key` is stored at.
+    fn location(&self, key: BlockKey) -> (&Tree, Vec<u8>) {
+        match key {
+            BlockKey::Data(id) => (&self.data, id.as_ref().as_bytes().to_vec()),
+            BlockKey::Lock(id) => (&self.locks, id.as_ref().as_bytes().to_vec()),
+            BlockKey::Header(id) => (&self.headers, id.as_ref().as_bytes().to_vec()),
+            BlockKey::Super => (&self.blocks, SUPER_KEY.to_vec()),
+            BlockKey::Version => (&self.blocks, VERSION_BLOCK_KEY.to_vec()),
+        }
+    }
+}
+
+impl DataStore for SledStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let (tree, tree_key) = self.location(key);
+        tree.insert(tree_key, data)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let (tree, tree_key) = self.location(key);
+        Ok(tree.get(tree_key)?.map(|data| data.to_vec()))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let (tree, tree_key) = self.location(key);
+        tree.remove(tree_key)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let tree = match kind {
+            BlockType::Data => &self.data,
+            BlockType::Lock => &self.locks,
+            BlockType::Header => &self.headers,
+        };
+
+        let mut ids = Vec::new();
+        for key in tree.iter().keys() {
+            ids.push(Uuid::from_slice(&key?)?.into());
+        }
+
+        Ok(ids)
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            latency: LatencyClass::Local,
+            ..StoreCapabilities::default()
+        }
+    }
+}
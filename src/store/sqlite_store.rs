@@ -5,7 +5,7 @@ use std::path::PathBuf;
 use rusqlite::{params, Connection, OptionalExtension, NO_PARAMS};
 use uuid::{uuid, Uuid};
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, DataStore, LatencyClass, StoreCapabilities};
 use super::open_store::OpenStore;
 
 /// A UUID which acts as the version ID of the store format.
@@ -108,6 +108,27 @@ pub struct SqliteStore {
     connection: Connection,
 }
 
+impl SqliteStore {
+    /// Call `f` with this store inside a transaction, rolling it back if `f` returns `Err`.
+    ///
+    /// This makes batches much faster, because SQLite only syncs changes to disk once per
+    /// transaction.
+    fn transaction(&mut self, f: impl FnOnce(&mut Self) -> super::Result<()>) -> super::Result<()> {
+        self.connection.execute_batch("BEGIN;")?;
+        match f(self) {
+            Ok(()) => {
+                self.connection.execute_batch("COMMIT;")?;
+                Ok(())
+            }
+            Err(error) => {
+                // The original error is more useful than an error rolling back.
+                let _ = self.connection.execute_batch("ROLLBACK;");
+                Err(error)
+            }
+        }
+    }
+}
+
 impl DataStore for SqliteStore {
     fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
         match key {
@@ -289,4 +310,31 @@ impl DataStore for SqliteStore {
 
         Ok(result)
     }
+
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        self.transaction(|store| {
+            for (key, data) in blocks {
+                store.write_block(*key, data)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        self.transaction(|store| {
+            for key in keys {
+                store.remove_block(*key)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            // This is the default maximum size of a BLOB in SQLite.
+            max_block_size: Some(1_000_000_000),
+            latency: LatencyClass::Local,
+            ..StoreCapabilities::default()
+        }
+    }
 }
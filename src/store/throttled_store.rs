@@ -0,0 +1,221 @@
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// Limits on how quickly a [`ThrottledStore`] uses another data store.
+///
+/// Each limit allows a burst of up to one second's worth of usage after the store has been idle.
+/// A limit of `None` means that usage is unlimited.
+///
+/// [`ThrottledStore`]: crate::store::ThrottledStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ThrottleLimits {
+    /// The maximum number of bytes to read from blocks per second.
+    ///
+    /// The default is `None`.
+    pub read_bytes_per_second: Option<u64>,
+
+    /// The maximum number of bytes to write to blocks per second.
+    ///
+    /// The default is `None`.
+    pub write_bytes_per_second: Option<u64>,
+
+    /// The maximum number of operations to perform per second.
+    ///
+    /// Every method of `DataStore` which accesses the data store counts as an operation.
+    ///
+    /// The default is `None`.
+    pub operations_per_second: Option<u64>,
+}
+
+/// A token bucket which limits how quickly a resource is used.
+#[derive(Debug)]
+struct TokenBucket {
+    /// The number of tokens added per second, which is also the capacity of the bucket.
+    rate: Option<u64>,
+
+    /// The number of tokens in the bucket, which is negative if tokens were borrowed.
+    tokens: f64,
+
+    /// The time the tokens were last refilled.
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    /// Create a new full bucket which adds `rate` tokens per second.
+    fn new(rate: Option<u64>) -> Self {
+        Self {
+            rate,
+            tokens: rate.unwrap_or_default() as f64,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Take `amount` tokens from the bucket, blocking until they're available.
+    ///
+    /// If `amount` is larger than the capacity of the bucket, the tokens are borrowed from the
+    /// future so that large blocks can still be transferred, and later calls block until the debt
+    /// is paid off.
+    fn take(&mut self, amount: u64) {
+        let rate = match self.rate {
+            Some(rate) if rate > 0 => rate as f64,
+            _ => return,
+        };
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.refilled = now;
+
+        if self.tokens < 0.0 {
+            // Wait until previously borrowed tokens are paid off.
+            thread::sleep(Duration::from_secs_f64(-self.tokens / rate));
+            self.tokens = 0.0;
+            self.refilled = Instant::now();
+        }
+
+        self.tokens -= amount as f64;
+        if self.tokens < 0.0 && (amount as f64) <= rate {
+            // These tokens will be available before we've waited longer than a second.
+            thread::sleep(Duration::from_secs_f64(-self.tokens / rate));
+            self.tokens = 0.0;
+            self.refilled = Instant::now();
+        }
+    }
+}
+
+/// The configuration for opening a [`ThrottledStore`].
+///
+/// [`ThrottledStore`]: crate::store::ThrottledStore
+#[derive(Debug, Clone)]
+pub struct ThrottledConfig<C: OpenStore> {
+    /// The configuration for the data store to throttle.
+    pub inner: C,
+
+    /// The limits on how quickly the data store is used.
+    pub limits: ThrottleLimits,
+}
+
+impl<C: OpenStore> OpenStore for ThrottledConfig<C> {
+    type Store = ThrottledStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(ThrottledStore::new(self.inner.open()?, self.limits))
+    }
+}
+
+/// A `DataStore` which limits the bandwidth and rate of operations used by another data store.
+///
+/// Operations on this store block until they can be performed without exceeding the configured
+/// [`ThrottleLimits`]. This is useful for staying under the rate limits of a storage provider or
+/// leaving bandwidth for other programs.
+///
+/// Reads are throttled after the block is read, because its size isn't known beforehand. A block
+/// which is larger than a second's worth of bandwidth is transferred immediately, and the
+/// operations after it wait until the bandwidth it used is made up.
+///
+/// Limits only apply to a single instance of this store. Multiple instances opened from the same
+/// [`ThrottledConfig`] are throttled independently.
+///
+/// You can use [`ThrottledConfig`] to open a data store of this type.
+///
+/// [`ThrottleLimits`]: crate::store::ThrottleLimits
+/// [`ThrottledConfig`]: crate::store::ThrottledConfig
+#[derive(Debug)]
+pub struct ThrottledStore<S: DataStore> {
+    inner: S,
+    limits: ThrottleLimits,
+    read_bytes: TokenBucket,
+    write_bytes: TokenBucket,
+    operations: TokenBucket,
+}
+
+impl<S: DataStore> ThrottledStore<S> {
+    /// Throttle the given `inner` data store according to `limits`.
+    pub fn new(inner: S, limits: ThrottleLimits) -> Self {
+        Self {
+            inner,
+            limits,
+            read_bytes: TokenBucket::new(limits.read_bytes_per_second),
+            write_bytes: TokenBucket::new(limits.write_bytes_per_second),
+            operations: TokenBucket::new(limits.operations_per_second),
+        }
+    }
+
+    /// The data store which is being throttled.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// The limits on how quickly the data store is used.
+    pub fn limits(&self) -> ThrottleLimits {
+        self.limits
+    }
+
+    /// Change the limits on how quickly the data store is used.
+    pub fn set_limits(&mut self, limits: ThrottleLimits) {
+        self.limits = limits;
+        self.read_bytes = TokenBucket::new(limits.read_bytes_per_second);
+        self.write_bytes = TokenBucket::new(limits.write_bytes_per_second);
+        self.operations = TokenBucket::new(limits.operations_per_second);
+    }
+}
+
+impl<S: DataStore> DataStore for ThrottledStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.operations.take(1);
+        self.write_bytes.take(data.len() as u64);
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.operations.take(1);
+        let data = self.inner.read_block(key)?;
+        if let Some(data) = &data {
+            self.read_bytes.take(data.len() as u64);
+        }
+        Ok(data)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.operations.take(1);
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.operations.take(1);
+        self.inner.list_blocks(kind)
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.operations.take(1);
+        self.inner.block_modified_time(key)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.operations.take(1);
+        self.inner.block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        self.inner.probe()
+    }
+}
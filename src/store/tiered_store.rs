@@ -0,0 +1,375 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+
+/// A tier of a [`TieredStore`].
+///
+/// [`TieredStore`]: crate::store::TieredStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tier {
+    /// The fast tier, like a local directory.
+    Fast,
+
+    /// The cold tier, like cheap cloud storage.
+    Cold,
+}
+
+/// A policy for which blocks a [`TieredStore`] stores in each tier.
+///
+/// [`TieredStore`]: crate::store::TieredStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierPolicy {
+    /// The tier to write headers, locks, the superblock, and the version block to.
+    ///
+    /// The default is `Tier::Fast`.
+    pub metadata: Tier,
+
+    /// The tier to write new data blocks to.
+    ///
+    /// The default is `Tier::Fast`.
+    pub new_data: Tier,
+
+    /// How long data blocks stay in the fast tier before [`TieredStore::migrate`] moves them to
+    /// the cold tier.
+    ///
+    /// If this is `None`, data blocks are only moved between tiers explicitly. Data blocks whose
+    /// modification time isn't known by the fast tier are never moved by `migrate`.
+    ///
+    /// The default is `None`.
+    ///
+    /// [`TieredStore::migrate`]: crate::store::TieredStore::migrate
+    pub fast_data_lifetime: Option<Duration>,
+}
+
+impl Default for TierPolicy {
+    fn default() -> Self {
+        Self {
+            metadata: Tier::Fast,
+            new_data: Tier::Fast,
+            fast_data_lifetime: None,
+        }
+    }
+}
+
+/// The configuration for opening a [`TieredStore`].
+///
+/// [`TieredStore`]: crate::store::TieredStore
+#[derive(Debug, Clone)]
+pub struct TieredConfig<F: OpenStore, C: OpenStore> {
+    /// The configuration for the fast tier.
+    pub fast: F,
+
+    /// The configuration for the cold tier.
+    pub cold: C,
+
+    /// The policy for which blocks are stored in each tier.
+    pub policy: TierPolicy,
+}
+
+impl<F: OpenStore, C: OpenStore> OpenStore for TieredConfig<F, C> {
+    type Store = TieredStore<F::Store, C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(TieredStore::new(
+            self.fast.open()?,
+            self.cold.open()?,
+            self.policy,
+        ))
+    }
+}
+
+/// A `DataStore` which stores blocks in a fast tier and a cold tier.
+///
+/// Each block is stored in one of two data stores according to a [`TierPolicy`]. For example, the
+/// repository metadata and recently written data can be kept in a local directory while older
+/// data is moved to cloud storage. Blocks are read from whichever tier contains them, so blocks can
+/// be moved between tiers without affecting repositories, and changing the policy doesn't require
+/// moving any blocks.
+///
+/// Data blocks are moved to the cold tier once they're old enough by calling [`migrate`], and
+/// blocks can be moved explicitly with [`move_block`] and [`move_all`].
+///
+/// You can use [`TieredConfig`] to open a data store of this type.
+///
+/// [`TierPolicy`]: crate::store::TierPolicy
+/// [`migrate`]: crate::store::TieredStore::migrate
+/// [`move_block`]: crate::store::TieredStore::move_block
+/// [`move_all`]: crate::store::TieredStore::move_all
+/// [`TieredConfig`]: crate::store::TieredConfig
+#[derive(Debug)]
+pub struct TieredStore<F: DataStore, C: DataStore> {
+    fast: F,
+    cold: C,
+    policy: TierPolicy,
+}
+
+impl<F: DataStore, C: DataStore> TieredStore<F, C> {
+    /// Store blocks in the given `fast` and `cold` tiers according to `policy`.
+    pub fn new(fast: F, cold: C, policy: TierPolicy) -> Self {
+        Self { fast, cold, policy }
+    }
+
+    /// The policy for which blocks are stored in each tier.
+    pub fn policy(&self) -> TierPolicy {
+        self.policy
+    }
+
+    /// Change the policy for which blocks are stored in each tier.
+    ///
+    /// This doesn't move any blocks.
+    pub fn set_policy(&mut self, policy: TierPolicy) {
+        self.policy = policy;
+    }
+
+    /// Return the tier which contains the block with the given `key`.
+    ///
+    /// If the block is in both tiers, like when it was being moved when the process was
+    /// interrupted, this returns the tier it's read from. If there is no block with the given
+    /// `key`, this returns `None`.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with either tier.
+    pub fn tier_of(&mut self, key: BlockKey) -> crate::Result<Option<Tier>> {
+        for tier in self.read_order(key) {
+            if self
+                .read_from(tier, key)
+                .map_err(crate::Error::Store)?
+                .is_some()
+            {
+                return Ok(Some(tier));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Move the block with the given `key` to the given `tier`.
+    ///
+    /// The block is copied to `tier` before it's removed from the other tier, so it's never
+    /// missing from both. This returns `true` if the block was moved or `false` if it was already
+    /// in `tier` or doesn't exist.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with either tier.
+    pub fn move_block(&mut self, key: BlockKey, tier: Tier) -> crate::Result<bool> {
+        self.move_to(key, tier).map_err(crate::Error::Store)
+    }
+
+    /// Move all blocks of the given `kind` to the given `tier`.
+    ///
+    /// This returns the number of blocks which were moved.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with either tier.
+    pub fn move_all(&mut self, kind: BlockType, tier: Tier) -> crate::Result<u64> {
+        let source = other(tier);
+        let ids = self.list_in(source, kind).map_err(crate::Error::Store)?;
+
+        let mut moved = 0;
+        for id in ids {
+            if self
+                .move_to(block_key(kind, id), tier)
+                .map_err(crate::Error::Store)?
+            {
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Move data blocks which have been in the fast tier longer than the policy allows to the cold
+    /// tier.
+    ///
+    /// This does nothing if [`TierPolicy::fast_data_lifetime`] is `None`. This returns the number
+    /// of blocks which were moved.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with either tier.
+    ///
+    /// [`TierPolicy::fast_data_lifetime`]: crate::store::TierPolicy::fast_data_lifetime
+    pub fn migrate(&mut self) -> crate::Result<u64> {
+        let lifetime = match self.policy.fast_data_lifetime {
+            Some(lifetime) => lifetime,
+            None => return Ok(0),
+        };
+        self.move_older_than(lifetime).map_err(crate::Error::Store)
+    }
+
+    /// Move data blocks which are older than `lifetime` from the fast tier to the cold tier.
+    fn move_older_than(&mut self, lifetime: Duration) -> super::Result<u64> {
+        let now = SystemTime::now();
+
+        let mut moved = 0;
+        for id in self.fast.list_blocks(BlockType::Data)? {
+            let key = BlockKey::Data(id);
+            let modified = match self.fast.block_modified_time(key)? {
+                Some(modified) => modified,
+                None => continue,
+            };
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age >= lifetime && self.move_to(key, Tier::Cold)? {
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Return the tier which blocks with the given `key` are written to.
+    fn write_tier(&self, key: BlockKey) -> Tier {
+        match key {
+            BlockKey::Data(_) => self.policy.new_data,
+            _ => self.policy.metadata,
+        }
+    }
+
+    /// Return the order in which to check the tiers for the block with the given `key`.
+    ///
+    /// The tier which the block would be written to is checked first, so that a stale copy in the
+    /// other tier is never read after the policy changes.
+    fn read_order(&self, key: BlockKey) -> [Tier; 2] {
+        let first = self.write_tier(key);
+        [first, other(first)]
+    }
+
+    /// Read the block with the given `key` from `tier`.
+    fn read_from(&mut self, tier: Tier, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        match tier {
+            Tier::Fast => self.fast.read_block(key),
+            Tier::Cold => self.cold.read_block(key),
+        }
+    }
+
+    /// Write `data` as the block with the given `key` to `tier`.
+    fn write_to(&mut self, tier: Tier, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        match tier {
+            Tier::Fast => self.fast.write_block(key, data),
+            Tier::Cold => self.cold.write_block(key, data),
+        }
+    }
+
+    /// Remove the block with the given `key` from `tier`.
+    fn remove_from(&mut self, tier: Tier, key: BlockKey) -> super::Result<()> {
+        match tier {
+            Tier::Fast => self.fast.remove_block(key),
+            Tier::Cold => self.cold.remove_block(key),
+        }
+    }
+
+    /// List the blocks of the given `kind` in `tier`.
+    fn list_in(&mut self, tier: Tier, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        match tier {
+            Tier::Fast => self.fast.list_blocks(kind),
+            Tier::Cold => self.cold.list_blocks(kind),
+        }
+    }
+
+    /// Move the block with the given `key` from the other tier to `tier`.
+    fn move_to(&mut self, key: BlockKey, tier: Tier) -> super::Result<bool> {
+        let source = other(tier);
+        let data = match self.read_from(source, key)? {
+            Some(data) => data,
+            None => return Ok(false),
+        };
+        self.write_to(tier, key, &data)?;
+        self.remove_from(source, key)?;
+        Ok(true)
+    }
+}
+
+/// Return the tier which isn't `tier`.
+fn other(tier: Tier) -> Tier {
+    match tier {
+        Tier::Fast => Tier::Cold,
+        Tier::Cold => Tier::Fast,
+    }
+}
+
+/// Return the key of the block of the given `kind` with the given `id`.
+fn block_key(kind: BlockType, id: BlockId) -> BlockKey {
+    match kind {
+        BlockType::Data => BlockKey::Data(id),
+        BlockType::Lock => BlockKey::Lock(id),
+        BlockType::Header => BlockKey::Header(id),
+    }
+}
+
+impl<F: DataStore, C: DataStore> DataStore for TieredStore<F, C> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let tier = self.write_tier(key);
+        self.write_to(tier, key, data)?;
+
+        // These blocks are overwritten, so don't leave an old copy in the other tier.
+        if matches!(key, BlockKey::Super | BlockKey::Version) {
+            self.remove_from(other(tier), key)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        for tier in self.read_order(key) {
+            if let Some(data) = self.read_from(tier, key)? {
+                return Ok(Some(data));
+            }
+        }
+        Ok(None)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.fast.remove_block(key)?;
+        self.cold.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let mut ids = self
+            .fast
+            .list_blocks(kind)?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        ids.extend(self.cold.list_blocks(kind)?);
+        Ok(ids.into_iter().collect())
+    }
+
+    fn consistency(&self) -> Consistency {
+        match (self.fast.consistency(), self.cold.consistency()) {
+            (Consistency::Strong, Consistency::Strong) => Consistency::Strong,
+            _ => Consistency::Eventual,
+        }
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        // Moving a block between tiers resets its modification time, so use the latest time so
+        // that blocks aren't removed before they're old enough.
+        let fast_time = self.fast.block_modified_time(key)?;
+        let cold_time = self.cold.block_modified_time(key)?;
+        Ok(fast_time.max(cold_time))
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        match self.fast.block_size(key)? {
+            Some(size) => Ok(Some(size)),
+            None => self.cold.block_size(key),
+        }
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.fast.retention().max(self.cold.retention())
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.fast.sync()?;
+        self.cold.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.fast.is_read_only() || self.cold.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            writable: !self.is_read_only(),
+            ..self.fast.probe().combine(self.cold.probe())
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::time::{Duration, SystemTime};
+
+use super::data_store::{BlockId, BlockKey, BlockType, Consistency, DataStore, StoreCapabilities};
+use super::open_store::OpenStore;
+use super::ErrorKind;
+
+/// The size of the checksum at the end of each block.
+const CHECKSUM_SIZE: usize = blake3::OUT_LEN;
+
+/// The configuration for opening a [`VerifyingStore`].
+///
+/// [`VerifyingStore`]: crate::store::VerifyingStore
+#[derive(Debug, Clone)]
+pub struct VerifyingConfig<C: OpenStore> {
+    /// The configuration for the data store to verify blocks from.
+    pub inner: C,
+}
+
+impl<C: OpenStore> VerifyingConfig<C> {
+    /// Create a new `VerifyingConfig` which verifies blocks in the store opened by `inner`.
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+impl<C: OpenStore> OpenStore for VerifyingConfig<C> {
+    type Store = VerifyingStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(VerifyingStore::new(self.inner.open()?))
+    }
+}
+
+/// A `DataStore` which checks the integrity of blocks in another data store.
+///
+/// A checksum of each block is appended to it when it's written, and it's checked whenever the
+/// block is read. If a block doesn't match its checksum, reading it fails with an error wrapping
+/// `Error::ChecksumMismatch` whose kind is `ErrorKind::Corrupt`. This catches data which is
+/// silently corrupted by the underlying storage even when the repository isn't encrypted.
+///
+/// The checksum also covers the key of the block, so a block which is stored under the wrong key
+/// is detected as well. Every block in the inner data store must have been written by a
+/// `VerifyingStore`.
+///
+/// You can use [`VerifyingConfig`] to open a data store of this type.
+///
+/// [`VerifyingConfig`]: crate::store::VerifyingConfig
+#[derive(Debug)]
+pub struct VerifyingStore<S: DataStore> {
+    inner: S,
+}
+
+impl<S: DataStore> VerifyingStore<S> {
+    /// Verify blocks written to and read from the given `inner` data store.
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    /// The data store which blocks are written to.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume this store and return the inner data store.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+/// Return the checksum of the block with the given `key` and `data`.
+fn checksum(key: BlockKey, data: &[u8]) -> blake3::Hash {
+    let (tag, id) = match key {
+        BlockKey::Data(id) => (0u8, Some(id)),
+        BlockKey::Lock(id) => (1, Some(id)),
+        BlockKey::Header(id) => (2, Some(id)),
+        BlockKey::Super => (3, None),
+        BlockKey::Version => (4, None),
+    };
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[tag]);
+    if let Some(id) = id {
+        hasher.update(id.as_ref().as_bytes());
+    }
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Return the contents of the block with the given `key` and `data`, including its checksum.
+fn encode(key: BlockKey, data: &[u8]) -> Vec<u8> {
+    let mut contents = Vec::with_capacity(data.len() + CHECKSUM_SIZE);
+    contents.extend_from_slice(data);
+    contents.extend_from_slice(checksum(key, data).as_bytes());
+    contents
+}
+
+/// Return the data in the block with the given `key` and `contents` if it matches its checksum.
+fn decode(key: BlockKey, mut contents: Vec<u8>) -> super::Result<Vec<u8>> {
+    let mismatch =
+        || super::Error::new(crate::Error::ChecksumMismatch { key }).with_kind(ErrorKind::Corrupt);
+
+    let data_len = contents
+        .len()
+        .checked_sub(CHECKSUM_SIZE)
+        .ok_or_else(mismatch)?;
+    let expected =
+        blake3::Hash::from(<[u8; CHECKSUM_SIZE]>::try_from(&contents[data_len..]).unwrap());
+
+    if checksum(key, &contents[..data_len]) != expected {
+        return Err(mismatch());
+    }
+
+    contents.truncate(data_len);
+    Ok(contents)
+}
+
+impl<S: DataStore> DataStore for VerifyingStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.inner.write_block(key, &encode(key, data))
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.inner
+            .read_block(key)?
+            .map(|contents| decode(key, contents))
+            .transpose()
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+
+    fn write_blocks(&mut self, blocks: &[(BlockKey, &[u8])]) -> super::Result<()> {
+        let contents = blocks
+            .iter()
+            .map(|(key, data)| (*key, encode(*key, data)))
+            .collect::<Vec<_>>();
+        let inner_blocks = contents
+            .iter()
+            .map(|(key, contents)| (*key, contents.as_slice()))
+            .collect::<Vec<_>>();
+        self.inner.write_blocks(&inner_blocks)
+    }
+
+    fn read_blocks(&mut self, keys: &[BlockKey]) -> super::Result<Vec<Option<Vec<u8>>>> {
+        self.inner
+            .read_blocks(keys)?
+            .into_iter()
+            .zip(keys)
+            .map(|(contents, key)| contents.map(|contents| decode(*key, contents)).transpose())
+            .collect()
+    }
+
+    fn remove_blocks(&mut self, keys: &[BlockKey]) -> super::Result<()> {
+        self.inner.remove_blocks(keys)
+    }
+
+    fn consistency(&self) -> Consistency {
+        self.inner.consistency()
+    }
+
+    fn block_modified_time(&mut self, key: BlockKey) -> super::Result<Option<SystemTime>> {
+        self.inner.block_modified_time(key)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.inner.block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.inner.retention()
+    }
+
+    fn sync(&mut self) -> super::Result<()> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn probe(&self) -> StoreCapabilities {
+        let capabilities = self.inner.probe();
+        StoreCapabilities {
+            max_block_size: capabilities
+                .max_block_size
+                .map(|size| size.saturating_sub(CHECKSUM_SIZE as u64)),
+            ..capabilities
+        }
+    }
+}
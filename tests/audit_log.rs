@@ -0,0 +1,200 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::time::SystemTime;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{
+    AuditEntry, AuditOperation, Commit, DestructivePolicy, Encryption, OpenMode, OpenOptions,
+    ResourceLimit,
+};
+use acid_store::store::MemoryConfig;
+use common::*;
+
+mod common;
+
+/// Return the operations in the given audit `log`.
+fn operations(log: &[AuditEntry]) -> Vec<AuditOperation> {
+    log.iter().map(|entry| entry.operation.clone()).collect()
+}
+
+/// Return the sequence numbers of the entries in the given audit `log`.
+fn sequences(log: &[AuditEntry]) -> Vec<u64> {
+    log.iter().map(|entry| entry.sequence).collect()
+}
+
+/// The operation recorded when the password is changed with interactive resource limits.
+fn password_changed() -> AuditOperation {
+    AuditOperation::PasswordChanged {
+        memory_limit: ResourceLimit::Interactive,
+        operations_limit: ResourceLimit::Interactive,
+    }
+}
+
+/// Change the password of `repo` to `new_password` and commit.
+fn change_password(repo: &mut KeyRepo<String>, new_password: &[u8]) -> anyhow::Result<()> {
+    repo.change_password(
+        new_password,
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    repo.commit()?;
+    Ok(())
+}
+
+#[rstest]
+fn new_repo_records_creation() -> anyhow::Result<()> {
+    let repo: KeyRepo<String> = create_repo(encoding_config())?;
+    let log = repo.audit_log();
+
+    assert_that!(operations(&log)).is_equal_to(vec![AuditOperation::Created]);
+    assert_that!(sequences(&log)).is_equal_to(vec![0]);
+    assert_that!(log[0].writer).is_none();
+    assert_that!(log[0].time).is_less_than_or_equal_to(SystemTime::now());
+
+    Ok(())
+}
+
+#[rstest]
+fn password_changes_are_persisted_in_order(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    change_password(&mut repo, b"first password")?;
+    change_password(&mut repo, b"second password")?;
+    drop(repo);
+
+    repo_store.password = String::from("second password");
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    change_password(&mut repo, b"third password")?;
+    drop(repo);
+
+    repo_store.password = String::from("third password");
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let log = repo.audit_log();
+
+    assert_that!(operations(&log)).is_equal_to(vec![
+        AuditOperation::Created,
+        password_changed(),
+        password_changed(),
+        password_changed(),
+    ]);
+    assert_that!(sequences(&log)).is_equal_to(vec![0, 1, 2, 3]);
+    assert_that!(log.windows(2).all(|pair| pair[0].time <= pair[1].time)).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn uncommitted_operations_are_not_persisted(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.change_password(
+        b"new password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    assert_that!(repo.audit_log()).has_length(2);
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(operations(&repo.audit_log())).is_equal_to(vec![AuditOperation::Created]);
+
+    Ok(())
+}
+
+#[rstest]
+fn change_password_without_encryption_is_not_recorded(
+    mut repo: KeyRepo<String>,
+) -> anyhow::Result<()> {
+    change_password(&mut repo, b"new password")?;
+    assert_that!(operations(&repo.audit_log())).is_equal_to(vec![AuditOperation::Created]);
+    Ok(())
+}
+
+#[rstest]
+fn destructive_policy_changes_are_recorded(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut policy = DestructivePolicy::default();
+    policy.enabled = true;
+    policy.max_removed_objects = Some(10);
+    repo.set_destructive_policy(repo_store.password.as_bytes(), policy)?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(operations(&repo.audit_log())).is_equal_to(vec![
+        AuditOperation::Created,
+        AuditOperation::DestructivePolicyChanged { policy },
+    ]);
+
+    Ok(())
+}
+
+#[rstest]
+fn audit_writer_is_recorded() -> anyhow::Result<()> {
+    let store_config = MemoryConfig::new();
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(encoding_config())
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .audit_writer("creator")
+        .open(&store_config)?;
+    repo.commit()?;
+    drop(repo);
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .audit_writer("maintainer")
+        .open(&store_config)?;
+    change_password(&mut repo, b"Password")?;
+
+    let writers = repo
+        .audit_log()
+        .into_iter()
+        .map(|entry| entry.writer)
+        .collect::<Vec<_>>();
+    assert_that!(writers).is_equal_to(vec![
+        Some(String::from("creator")),
+        Some(String::from("maintainer")),
+    ]);
+
+    Ok(())
+}
+
+#[rstest]
+fn metadata_handle_appends_to_audit_log(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    change_password(&mut repo, b"first password")?;
+    drop(repo);
+
+    let mut handle = OpenOptions::new()
+        .password(b"first password")
+        .open_metadata(&repo_store.store)?;
+    handle.change_password(
+        b"second password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    handle.commit_metadata()?;
+    handle.change_password(
+        b"third password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    handle.commit_metadata()?;
+    drop(handle);
+
+    repo_store.password = String::from("third password");
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let log = repo.audit_log();
+
+    assert_that!(operations(&log)).is_equal_to(vec![
+        AuditOperation::Created,
+        password_changed(),
+        password_changed(),
+        password_changed(),
+    ]);
+    assert_that!(sequences(&log)).is_equal_to(vec![0, 1, 2, 3]);
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Write;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::Commit;
+use common::*;
+
+mod common;
+
+#[rstest]
+fn applied_batch_changes_are_visible(
+    mut repo: KeyRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo.insert("old".into());
+    object.write_all(b"old")?;
+    object.commit()?;
+    drop(object);
+
+    let mut batch = repo.batch();
+    batch.insert("data".into(), &buffer)?;
+    batch.insert("index".into(), b"data")?;
+    batch.copy("old".into(), "backup".into());
+    batch.remove("old".into());
+    batch.apply()?;
+
+    assert_that!(read_object(&repo, "data")?).is_equal_to(&buffer);
+    assert_that!(read_object(&repo, "index")?).is_equal_to(b"data".to_vec());
+    assert_that!(read_object(&repo, "backup")?).is_equal_to(b"old".to_vec());
+    assert_that!(repo.contains("old")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn unapplied_batch_changes_are_not_visible(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("old".into());
+
+    let mut batch = repo.batch();
+    batch.insert("new".into(), b"data")?;
+    batch.remove("old".into());
+    drop(batch);
+
+    assert_that!(repo.contains("new")).is_false();
+    assert_that!(repo.contains("old")).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn batch_can_copy_object_inserted_in_batch(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    let mut batch = repo.batch();
+    batch.insert("source".into(), b"data")?;
+    batch.copy("source".into(), "dest".into());
+    batch.apply()?;
+
+    assert_that!(read_object(&repo, "dest")?).is_equal_to(b"data".to_vec());
+
+    Ok(())
+}
+
+#[rstest]
+fn failed_batch_applies_no_changes(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("old".into());
+
+    let mut batch = repo.batch();
+    batch.insert("new".into(), b"data")?;
+    batch.remove("old".into());
+    batch.copy("old".into(), "dest".into());
+
+    assert_that!(batch.apply()).is_err_variant(acid_store::Error::NotFound);
+    assert_that!(repo.contains("new")).is_false();
+    assert_that!(repo.contains("old")).is_true();
+    assert_that!(repo.contains("dest")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn discarded_batch_data_is_cleaned(
+    mut repo: KeyRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let size_before = repo.stats().repo_size();
+
+    let mut batch = repo.batch();
+    batch.insert("test".into(), &buffer)?;
+    drop(batch);
+
+    assert_that!(repo.stats().repo_size()).is_equal_to(size_before);
+
+    Ok(())
+}
+
+#[rstest]
+fn batch_changes_persist_after_commit(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut batch = repo.batch();
+    batch.insert("index".into(), b"index")?;
+    batch.insert("data".into(), b"data")?;
+    batch.apply()?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(read_object(&repo, "index")?).is_equal_to(b"index".to_vec());
+    assert_that!(read_object(&repo, "data")?).is_equal_to(b"data".to_vec());
+
+    Ok(())
+}
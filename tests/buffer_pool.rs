@@ -0,0 +1,117 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::sync::Arc;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{BufferPool, OpenMode, OpenOptions, RepoConfig};
+use acid_store::store::MemoryConfig;
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The number of bytes of buffers retained by the pool in these tests.
+const POOL_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Return a copy of `data` with every byte changed so it shares no chunks with `data`.
+fn distinct(data: &[u8], seed: u8) -> Vec<u8> {
+    data.iter().map(|byte| byte ^ seed).collect()
+}
+
+#[apply(config)]
+fn data_round_trips_with_pooled_buffers(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut config = config;
+    config.buffer_pool_size = POOL_SIZE;
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+
+    let expected = (1..=4u8)
+        .map(|seed| distinct(&buffer, seed))
+        .collect::<Vec<_>>();
+    for (i, data) in expected.iter().enumerate() {
+        write_object(&mut repo, &format!("object{}", i), data)?;
+    }
+    for (i, data) in expected.iter().enumerate() {
+        assert_that!(read_object(&repo, &format!("object{}", i))?).is_equal_to(data);
+    }
+
+    let stats = repo.pool_stats();
+    assert_that!(stats.rented()).is_greater_than(0);
+    assert_that!(stats.reused()).is_greater_than(0);
+    assert_that!(stats.retained()).is_less_than_or_equal_to(POOL_SIZE);
+    assert_that!(stats.high_water_mark()).is_less_than_or_equal_to(POOL_SIZE);
+    assert_that!(stats.high_water_mark()).is_greater_than_or_equal_to(stats.retained());
+
+    Ok(())
+}
+
+#[apply(config)]
+fn disabled_pool_retains_nothing(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut config = config;
+    config.buffer_pool_size = 0;
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+
+    write_object(&mut repo, "first", &distinct(&buffer, 1))?;
+    write_object(&mut repo, "second", &distinct(&buffer, 2))?;
+    assert_that!(read_object(&repo, "first")?).is_equal_to(distinct(&buffer, 1));
+
+    let stats = repo.pool_stats();
+    assert_that!(stats.reused()).is_equal_to(0);
+    assert_that!(stats.retained()).is_equal_to(0);
+    assert_that!(stats.high_water_mark()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn pool_is_shared_between_repositories(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let pool = Arc::new(BufferPool::new(POOL_SIZE));
+
+    let mut first: KeyRepo<String> = OpenOptions::new()
+        .buffer_pool(Arc::clone(&pool))
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())?;
+    let mut second: KeyRepo<String> = OpenOptions::new()
+        .buffer_pool(Arc::clone(&pool))
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())?;
+
+    write_object(&mut first, "test", &buffer)?;
+    let rented = pool.stats().rented();
+
+    // Buffers released by the first repository are reused by the second.
+    write_object(&mut second, "test", &distinct(&buffer, 1))?;
+
+    assert_that!(pool.stats().rented()).is_greater_than(rented);
+    assert_that!(pool.stats().reused()).is_greater_than(0);
+    assert_that!(first.pool_stats()).is_equal_to(pool.stats());
+    assert_that!(second.pool_stats()).is_equal_to(pool.stats());
+    assert_that!(read_object(&first, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn pool_size_is_ignored_with_shared_pool(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let pool = Arc::new(BufferPool::new(0));
+    let mut config = RepoConfig::default();
+    config.buffer_pool_size = POOL_SIZE;
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(config)
+        .buffer_pool(Arc::clone(&pool))
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())?;
+    write_object(&mut repo, "first", &distinct(&buffer, 1))?;
+    write_object(&mut repo, "second", &distinct(&buffer, 2))?;
+
+    assert_that!(repo.pool_stats().rented()).is_greater_than(0);
+    assert_that!(repo.pool_stats().retained()).is_equal_to(0);
+
+    Ok(())
+}
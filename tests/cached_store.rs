@@ -0,0 +1,162 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Write;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{BlockId, BlockKey, BlockType, CachedConfig, DataStore, OpenStore};
+use acid_store::uuid::Uuid;
+use common::*;
+use tempfile::TempDir;
+
+mod common;
+
+/// Return a config for a cache of `inner` in `directory`.
+fn cached_config(
+    inner: &CountingConfig,
+    directory: &TempDir,
+    capacity: u64,
+    write_back: bool,
+) -> CachedConfig<CountingConfig> {
+    CachedConfig {
+        inner: inner.clone(),
+        path: directory.path().join("cache"),
+        capacity,
+        write_back,
+    }
+}
+
+#[rstest]
+fn reads_are_served_from_cache(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = CountingConfig::new();
+    let config = cached_config(&inner, &temp_dir, 64 * 1024 * 1024, false);
+    create_store_repo(&config, encoding_config(), &buffer)?;
+    assert_that!(inner.data_writes()).is_greater_than(0);
+
+    // The cache persists when the store is reopened.
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+    assert_that!(inner.data_reads()).is_equal_to(0);
+
+    // The inner store contains the same repository.
+    assert_that!(read_store_object(&inner, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn cache_stays_within_capacity(temp_dir: TempDir, larger_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = CountingConfig::new();
+    let capacity = larger_buffer.len() as u64 / 4;
+    let config = cached_config(&inner, &temp_dir, capacity, false);
+    create_store_repo(&config, encoding_config(), &larger_buffer)?;
+
+    let store = config.open()?;
+    assert_that!(store.cached_size()).is_less_than_or_equal_to(capacity);
+    drop(store);
+
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&larger_buffer);
+    assert_that!(inner.data_reads()).is_greater_than(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn write_back_defers_data_until_commit(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = CountingConfig::new();
+    let config = cached_config(&inner, &temp_dir, 64 * 1024 * 1024, true);
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(encoding_config())
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    assert_that!(inner.data_writes()).is_equal_to(0);
+
+    repo.commit()?;
+    assert_that!(inner.data_writes()).is_greater_than(0);
+    drop(repo);
+
+    assert_that!(read_store_object(&inner, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn pending_blocks_are_listed_and_flushed(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = CountingConfig::new();
+    let config = cached_config(&inner, &temp_dir, 64 * 1024 * 1024, true);
+    let mut store = config.open()?;
+    let id = BlockId::from(Uuid::new_v4());
+
+    store.write_block(BlockKey::Data(id), &buffer)?;
+
+    assert_that!(store.list_blocks(BlockType::Data)?).contains(&id);
+    assert_that!(store.read_block(BlockKey::Data(id))?).is_equal_to(Some(buffer.clone()));
+    assert_that!(inner.open()?.read_block(BlockKey::Data(id))?).is_none();
+
+    store.flush()?;
+
+    assert_that!(inner.open()?.read_block(BlockKey::Data(id))?).is_equal_to(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn sync_writes_pending_blocks(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = CountingConfig::new();
+    let config = cached_config(&inner, &temp_dir, 64 * 1024 * 1024, true);
+    let mut store = config.open()?;
+    let id = BlockId::from(Uuid::new_v4());
+
+    store.write_block(BlockKey::Data(id), &buffer)?;
+    store.sync()?;
+
+    assert_that!(inner.open()?.read_block(BlockKey::Data(id))?).is_equal_to(Some(buffer));
+    assert_that!(inner.syncs()).is_equal_to(1);
+
+    Ok(())
+}
+
+#[rstest]
+fn object_sync_data_writes_through_cache(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = CountingConfig::new();
+    let config = cached_config(&inner, &temp_dir, 64 * 1024 * 1024, true);
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(encoding_config())
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    assert_that!(inner.data_writes()).is_equal_to(0);
+    assert_that!(inner.syncs()).is_equal_to(0);
+
+    object.sync_data()?;
+
+    assert_that!(inner.data_writes()).is_greater_than(0);
+    assert_that!(inner.syncs()).is_greater_than(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn removed_blocks_are_not_cached(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = CountingConfig::new();
+    let config = cached_config(&inner, &temp_dir, 64 * 1024 * 1024, false);
+    let mut store = config.open()?;
+    let id = BlockId::from(Uuid::new_v4());
+
+    store.write_block(BlockKey::Data(id), &buffer)?;
+    store.remove_block(BlockKey::Data(id))?;
+
+    assert_that!(store.read_block(BlockKey::Data(id))?).is_none();
+    assert_that!(store.cached_size()).is_equal_to(0);
+    assert_that!(inner.open()?.read_block(BlockKey::Data(id))?).is_none();
+
+    Ok(())
+}
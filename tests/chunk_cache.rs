@@ -0,0 +1,125 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Write;
+use std::sync::Arc;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{ChunkCache, Commit, Encryption, OpenMode, OpenOptions, RepoConfig};
+use acid_store::store::{BlockKey, BlockType, DataStore, OpenStore};
+use common::*;
+
+mod common;
+
+/// Create a repository which uses the given `cache` and write `data` to an object in it.
+fn create_repo_with_cache(
+    store_config: &CountingConfig,
+    repo_config: RepoConfig,
+    cache: &Arc<ChunkCache>,
+    data: &[u8],
+) -> anyhow::Result<KeyRepo<String>> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(repo_config)
+        .password(b"Password")
+        .chunk_cache(Arc::clone(cache))
+        .mode(OpenMode::CreateNew)
+        .open(store_config)?;
+
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(data)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    Ok(repo)
+}
+
+#[rstest]
+fn shared_cache_is_hit_by_second_repo(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let cache = Arc::new(ChunkCache::new(1024 * 1024));
+    let first_store = CountingConfig::new();
+    let second_store = CountingConfig::new();
+
+    // The repositories use different encryption keys, but they still share cached chunks.
+    let mut repo_config = fixed_config();
+    repo_config.encryption = Encryption::XChaCha20Poly1305;
+    let first_repo = create_repo_with_cache(&first_store, repo_config.clone(), &cache, &buffer)?;
+    let second_repo = create_repo_with_cache(&second_store, repo_config, &cache, &buffer)?;
+
+    assert_that!(read_object(&first_repo, "test")?).is_equal_to(&buffer);
+
+    let stats_after_first = cache.stats();
+    assert_that!(stats_after_first.misses()).is_greater_than(0);
+    assert_that!(stats_after_first.entries()).is_greater_than(0);
+    assert_that!(first_store.data_reads()).is_greater_than(0);
+
+    let second_reads_before = second_store.data_reads();
+    assert_that!(read_object(&second_repo, "test")?).is_equal_to(&buffer);
+
+    let stats_after_second = cache.stats();
+    assert_that!(second_store.data_reads()).is_equal_to(second_reads_before);
+    assert_that!(stats_after_second.misses()).is_equal_to(stats_after_first.misses());
+    assert_that!(stats_after_second.hits()).is_greater_than(stats_after_first.hits());
+
+    Ok(())
+}
+
+#[rstest]
+fn cache_does_not_exceed_capacity(larger_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let capacity = (larger_buffer.len() / 4) as u64;
+    let cache = Arc::new(ChunkCache::new(capacity));
+    let store_config = CountingConfig::new();
+    let repo = create_repo_with_cache(&store_config, fixed_config(), &cache, &larger_buffer)?;
+
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&larger_buffer);
+
+    let stats = cache.stats();
+    assert_that!(stats.entries()).is_greater_than(0);
+    assert_that!(stats.size()).is_less_than_or_equal_to(capacity);
+
+    Ok(())
+}
+
+#[rstest]
+fn clearing_cache_removes_entries(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let cache = Arc::new(ChunkCache::new(1024 * 1024));
+    let store_config = CountingConfig::new();
+    let repo = create_repo_with_cache(&store_config, fixed_config(), &cache, &buffer)?;
+    read_object(&repo, "test")?;
+
+    cache.clear();
+
+    assert_that!(cache.stats().entries()).is_equal_to(0);
+    assert_that!(cache.stats().size()).is_equal_to(0);
+
+    let reads_before = store_config.data_reads();
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&buffer);
+    assert_that!(store_config.data_reads()).is_greater_than(reads_before);
+
+    Ok(())
+}
+
+#[rstest]
+fn verify_bypasses_cache(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let cache = Arc::new(ChunkCache::new(1024 * 1024));
+    let store_config = CountingConfig::new();
+    let repo = create_repo_with_cache(&store_config, fixed_config(), &cache, &buffer)?;
+    read_object(&repo, "test")?;
+
+    // Corrupt the data in the store after it has been cached.
+    let mut store = store_config.inner.open()?;
+    for block_id in store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+    {
+        let key = BlockKey::Data(block_id);
+        let data = store.read_block(key).map_err(anyhow::Error::msg)?.unwrap();
+        let corrupt_data = data.iter().map(|byte| !byte).collect::<Vec<_>>();
+        store
+            .write_block(key, &corrupt_data)
+            .map_err(anyhow::Error::msg)?;
+    }
+
+    assert_that!(repo.verify()?.contains(&String::from("test"))).is_true();
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, Encryption, OpenMode, OpenOptions, ResourceLimit};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore,
+};
+use common::*;
+
+mod common;
+
+/// A data store config which records the keys of every block written to the store.
+#[derive(Debug, Clone)]
+struct RecordingWritesConfig {
+    inner: MemoryConfig,
+    writes: Arc<Mutex<Vec<BlockKey>>>,
+}
+
+impl RecordingWritesConfig {
+    fn new() -> Self {
+        RecordingWritesConfig {
+            inner: MemoryConfig::new(),
+            writes: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Return the keys of the blocks written since this was last called.
+    fn take_writes(&self) -> Vec<BlockKey> {
+        std::mem::take(&mut *self.writes.lock().unwrap())
+    }
+}
+
+impl OpenStore for RecordingWritesConfig {
+    type Store = RecordingWritesStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(RecordingWritesStore {
+            inner: self.inner.open()?,
+            writes: Arc::clone(&self.writes),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct RecordingWritesStore {
+    inner: MemoryStore,
+    writes: Arc<Mutex<Vec<BlockKey>>>,
+}
+
+impl DataStore for RecordingWritesStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.writes.lock().unwrap().push(key);
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+}
+
+#[fixture]
+fn store_config() -> RecordingWritesConfig {
+    RecordingWritesConfig::new()
+}
+
+#[fixture]
+fn recording_repo(store_config: RecordingWritesConfig) -> (KeyRepo<String>, RecordingWritesConfig) {
+    let mut config = fixed_config();
+    config.encryption = Encryption::XChaCha20Poly1305;
+    let repo = OpenOptions::new()
+        .config(config)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&store_config)
+        .unwrap();
+    (repo, store_config)
+}
+
+#[rstest]
+fn commit_without_changes_writes_nothing(
+    recording_repo: (KeyRepo<String>, RecordingWritesConfig),
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let (mut repo, store_config) = recording_repo;
+
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let first_report = repo.commit_with_report()?;
+    store_config.take_writes();
+
+    let second_report = repo.commit_with_report()?;
+
+    assert_that!(first_report.header_written()).is_true();
+    assert_that!(first_report.metadata_written()).is_true();
+    assert_that!(second_report.is_empty()).is_true();
+    assert_that!(store_config.take_writes()).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn commit_with_changes_writes_header(
+    recording_repo: (KeyRepo<String>, RecordingWritesConfig),
+) -> anyhow::Result<()> {
+    let (mut repo, store_config) = recording_repo;
+
+    repo.insert(String::from("first"));
+    repo.commit()?;
+    store_config.take_writes();
+
+    repo.insert(String::from("second"));
+    let report = repo.commit_with_report()?;
+    let writes = store_config.take_writes();
+
+    assert_that!(report.header_written()).is_true();
+    assert_that!(report.metadata_written()).is_true();
+    assert_that!(writes.iter().any(|key| matches!(key, BlockKey::Header(_)))).is_true();
+    assert_that!(writes).contains(&BlockKey::Super);
+
+    Ok(())
+}
+
+#[rstest]
+fn changing_password_only_writes_metadata(
+    recording_repo: (KeyRepo<String>, RecordingWritesConfig),
+) -> anyhow::Result<()> {
+    let (mut repo, store_config) = recording_repo;
+
+    repo.insert(String::from("test"));
+    repo.commit()?;
+    store_config.take_writes();
+
+    repo.change_password(
+        b"New password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    let report = repo.commit_with_report()?;
+
+    assert_that!(report.header_written()).is_false();
+    assert_that!(report.metadata_written()).is_true();
+    assert_that!(store_config.take_writes()).is_equal_to(vec![BlockKey::Super]);
+
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"New password")
+        .open(&store_config)?;
+
+    assert_that!(repo.contains("test")).is_true();
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+#[cfg(feature = "repo-file")]
+use acid_store::repo::file::{Entry, FileRepo};
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, RepoConfig};
+use acid_store::store::{BlockType, DataStore, OpenStore};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// Return a `RepoStore` which keeps the given number of previous `commits`.
+fn history_store(mut config: RepoConfig, commits: u32) -> RepoStore {
+    config.commit_history = commits;
+    RepoStore::new(config)
+}
+
+#[rstest]
+fn no_history_is_kept_by_default(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    repo.commit()?;
+
+    assert_that!(repo.list_commits()).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn commits_are_listed_oldest_first() -> anyhow::Result<()> {
+    let repo_store = history_store(fixed_config(), 2);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    let mut commit_ids = Vec::new();
+    for key in ["a", "b", "c", "d"] {
+        repo.insert(key.into());
+        repo.commit()?;
+        commit_ids.push(repo.current_commit_id());
+    }
+
+    let listed_ids = repo
+        .list_commits()
+        .iter()
+        .map(|info| info.id())
+        .collect::<Vec<_>>();
+
+    assert_that!(listed_ids).is_equal_to(commit_ids[1..].to_vec());
+
+    Ok(())
+}
+
+#[apply(config)]
+fn rollback_to_commit_restores_objects(
+    #[case] config: RepoConfig,
+    smaller_buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo_store = history_store(config, 1);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_object(&mut repo, "test", &smaller_buffer)?;
+    repo.commit()?;
+    let old_commit = repo.current_commit_id();
+
+    write_object(&mut repo, "test", &larger_buffer)?;
+    repo.insert("new".into());
+    repo.commit()?;
+    repo.clean()?;
+
+    repo.rollback_to_commit(old_commit)?;
+
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&smaller_buffer);
+    assert_that!(repo.contains("new")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn rollback_to_commit_persists_after_reopen(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let repo_store = history_store(fixed_config(), 1);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_object(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+    let old_commit = repo.current_commit_id();
+    repo.remove("test");
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    repo.rollback_to_commit(old_commit)?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn rollback_to_commit_can_be_rolled_back() -> anyhow::Result<()> {
+    let repo_store = history_store(fixed_config(), 1);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert("old".into());
+    repo.commit()?;
+    let old_commit = repo.current_commit_id();
+    repo.insert("new".into());
+    repo.commit()?;
+
+    repo.rollback_to_commit(old_commit)?;
+    repo.rollback()?;
+
+    assert_that!(repo.contains("new")).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+#[cfg(feature = "repo-file")]
+fn file_repo_can_roll_back_to_commit() -> anyhow::Result<()> {
+    let repo_store = history_store(fixed_config(), 1);
+    let mut repo: FileRepo = repo_store.create()?;
+    repo.create("old", &Entry::file())?;
+    repo.commit()?;
+    let old_commit = repo.current_commit_id();
+    repo.remove("old")?;
+    repo.create("new", &Entry::file())?;
+    repo.commit()?;
+
+    assert_that!(repo.list_commits()).has_length(2);
+
+    repo.rollback_to_commit(old_commit)?;
+
+    assert_that!(repo.exists("old")).is_true();
+    assert_that!(repo.exists("new")).is_false();
+
+    repo.commit()?;
+    drop(repo);
+    let repo: FileRepo = repo_store.open()?;
+
+    assert_that!(repo.exists("old")).is_true();
+    assert_that!(repo.exists("new")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn rollback_to_discarded_commit_errs() -> anyhow::Result<()> {
+    let repo_store = history_store(fixed_config(), 1);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    let old_commit = repo.current_commit_id();
+    repo.insert("a".into());
+    repo.commit()?;
+    repo.insert("b".into());
+    repo.commit()?;
+
+    assert_that!(repo.rollback_to_commit(old_commit)).is_err_variant(acid_store::Error::NotFound);
+
+    Ok(())
+}
+
+#[rstest]
+fn discarded_commits_are_cleaned(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let repo_store = history_store(fixed_config(), 1);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_object(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+    repo.remove("test");
+    repo.commit()?;
+    repo.clean()?;
+    let kept_blocks = repo_store.store.open()?.list_blocks(BlockType::Data)?.len();
+
+    repo.set_commit_history(0);
+    repo.commit()?;
+    repo.clean()?;
+    let remaining_blocks = repo_store.store.open()?.list_blocks(BlockType::Data)?.len();
+
+    assert_that!(repo.list_commits()).is_empty();
+    assert_that!(remaining_blocks).is_less_than(kept_blocks);
+
+    Ok(())
+}
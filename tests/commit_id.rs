@@ -0,0 +1,298 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::state::StateRepo;
+use acid_store::repo::{
+    peek_commit_id, Commit, FormatVersion, OpenMode, OpenOptions, RepoConfig, ResourceLimit,
+};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore,
+};
+use common::*;
+
+mod common;
+
+/// A data store config whose stores fail to write the superblock while `fail` is set.
+#[derive(Debug, Clone)]
+struct FailingConfig {
+    inner: MemoryConfig,
+    fail: Arc<AtomicBool>,
+}
+
+impl FailingConfig {
+    fn new() -> Self {
+        FailingConfig {
+            inner: MemoryConfig::new(),
+            fail: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn set_failing(&self, fail: bool) {
+        self.fail.store(fail, Ordering::SeqCst);
+    }
+}
+
+impl OpenStore for FailingConfig {
+    type Store = FailingStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(FailingStore {
+            inner: self.inner.open()?,
+            fail: Arc::clone(&self.fail),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct FailingStore {
+    inner: MemoryStore,
+    fail: Arc<AtomicBool>,
+}
+
+impl DataStore for FailingStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        if key == BlockKey::Super && self.fail.load(Ordering::SeqCst) {
+            return Err(acid_store::store::Error::new(io::Error::new(
+                io::ErrorKind::Other,
+                "Writing the superblock failed.",
+            )));
+        }
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+}
+
+/// Create a new repository in the store with the given `store_config`.
+fn create<S: OpenStore>(
+    store_config: &S,
+    config: RepoConfig,
+) -> acid_store::Result<KeyRepo<String>> {
+    OpenOptions::new()
+        .config(config)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(store_config)
+}
+
+/// Open the repository in the store with the given `store_config`.
+fn open<S: OpenStore>(store_config: &S) -> acid_store::Result<KeyRepo<String>> {
+    OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(store_config)
+}
+
+/// Insert an object with the given `key` and `data` into `repo`.
+fn insert(repo: &mut KeyRepo<String>, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    let mut object = repo.insert(key.to_string());
+    object.write_all(data)?;
+    object.commit()?;
+    Ok(())
+}
+
+#[rstest]
+fn commit_ids_increase_with_each_commit(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create(&store, fixed_config())?;
+    let mut previous = repo.current_commit_id();
+
+    for i in 0..3 {
+        insert(&mut repo, &i.to_string(), &buffer)?;
+        let report = repo.commit_with_report()?;
+
+        assert_that!(report.commit_id()).is_greater_than(previous);
+        assert_that!(report.commit_id().sequence()).is_equal_to(previous.sequence() + 1);
+        assert_that!(report.commit_id().uuid()).is_not_equal_to(previous.uuid());
+        assert_that!(repo.current_commit_id()).is_equal_to(report.commit_id());
+        previous = report.commit_id();
+    }
+
+    // Metadata-only changes are also commits.
+    repo.change_password(
+        b"New password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    let report = repo.commit_with_report()?;
+    assert_that!(report.commit_id()).is_greater_than(previous);
+
+    Ok(())
+}
+
+#[rstest]
+fn empty_commit_does_not_advance_id(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create(&store, fixed_config())?;
+    insert(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+    let commit_id = repo.current_commit_id();
+
+    let report = repo.commit_with_report()?;
+
+    assert_that!(report.is_empty()).is_true();
+    assert_that!(report.commit_id()).is_equal_to(commit_id);
+    assert_that!(repo.current_commit_id()).is_equal_to(commit_id);
+
+    Ok(())
+}
+
+#[rstest]
+fn commit_id_can_be_peeked_without_password(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create(&store, fixed_config())?;
+    assert_that!(peek_commit_id(&store)?).is_equal_to(repo.current_commit_id());
+
+    insert(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+    assert_that!(peek_commit_id(&store)?).is_equal_to(repo.current_commit_id());
+    assert_that!(repo.info().commit_id()).is_equal_to(repo.current_commit_id());
+
+    // Uncommitted changes don't change the commit ID.
+    insert(&mut repo, "uncommitted", &buffer)?;
+    let commit_id = repo.current_commit_id();
+    drop(repo);
+
+    assert_that!(peek_commit_id(&store)?).is_equal_to(commit_id);
+    assert_that!(open(&store)?.current_commit_id()).is_equal_to(commit_id);
+
+    Ok(())
+}
+
+#[rstest]
+fn peeking_empty_store_fails() {
+    assert_that!(peek_commit_id(&MemoryConfig::new())).is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn payload_round_trips_across_reopen(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create(&store, fixed_config())?;
+    assert_that!(repo.committed_payload()).is_empty();
+
+    insert(&mut repo, "test", &buffer)?;
+    let report = repo.commit_with_payload(b"checkpoint 1")?;
+    assert_that!(repo.committed_payload()).is_equal_to(b"checkpoint 1".to_vec());
+    drop(repo);
+
+    let mut repo = open(&store)?;
+    assert_that!(repo.committed_payload()).is_equal_to(b"checkpoint 1".to_vec());
+    assert_that!(repo.current_commit_id()).is_equal_to(report.commit_id());
+
+    // A commit without a payload keeps the previous payload.
+    insert(&mut repo, "other", &buffer)?;
+    repo.commit()?;
+    drop(repo);
+
+    let mut repo = open(&store)?;
+    assert_that!(repo.committed_payload()).is_equal_to(b"checkpoint 1".to_vec());
+
+    // Changing only the payload is a commit.
+    let previous = repo.current_commit_id();
+    let report = repo.commit_with_payload(b"checkpoint 2")?;
+    assert_that!(report.metadata_written()).is_true();
+    assert_that!(report.commit_id()).is_greater_than(previous);
+    drop(repo);
+
+    assert_that!(open(&store)?.committed_payload()).is_equal_to(b"checkpoint 2".to_vec());
+
+    Ok(())
+}
+
+#[rstest]
+fn failed_commit_does_not_advance_id(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = FailingConfig::new();
+    let mut repo = create(&store, fixed_config())?;
+    repo.commit_with_payload(b"checkpoint 1")?;
+    let commit_id = repo.current_commit_id();
+
+    insert(&mut repo, "test", &buffer)?;
+    store.set_failing(true);
+
+    assert_that!(repo.commit_with_payload(b"checkpoint 2").is_err()).is_true();
+    assert_that!(repo.current_commit_id()).is_equal_to(commit_id);
+    assert_that!(repo.committed_payload()).is_equal_to(b"checkpoint 1".to_vec());
+    assert_that!(peek_commit_id(&store)?).is_equal_to(commit_id);
+
+    // The next successful commit follows the last successful one.
+    store.set_failing(false);
+    let report = repo.commit_with_report()?;
+    assert_that!(report.commit_id().sequence()).is_equal_to(commit_id.sequence() + 1);
+    assert_that!(repo.committed_payload()).is_equal_to(b"checkpoint 1".to_vec());
+
+    Ok(())
+}
+
+#[rstest]
+fn cleaning_does_not_change_commit_id(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create(&store, fixed_packing_small_config())?;
+    insert(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+    repo.remove("test");
+    repo.commit()?;
+    let commit_id = repo.current_commit_id();
+
+    repo.clean()?;
+
+    assert_that!(repo.current_commit_id()).is_equal_to(commit_id);
+    assert_that!(peek_commit_id(&store)?).is_equal_to(commit_id);
+
+    Ok(())
+}
+
+#[rstest]
+fn state_repo_commits_state_with_payload() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo: StateRepo<Vec<u32>> = OpenOptions::new()
+        .config(fixed_config())
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&store)?;
+
+    repo.state_mut().push(42);
+    repo.commit_with_payload(b"checkpoint")?;
+    drop(repo);
+
+    let repo: StateRepo<Vec<u32>> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(&store)?;
+    assert_that!(repo.state()).is_equal_to(&vec![42]);
+    assert_that!(repo.committed_payload()).is_equal_to(b"checkpoint".to_vec());
+
+    Ok(())
+}
+
+#[rstest]
+fn payload_is_incompatible_with_old_versions() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut config = fixed_config();
+    config.compatibility_target = Some(FormatVersion::V0_14);
+    let mut repo = create(&store, config)?;
+
+    assert!(matches!(
+        repo.commit_with_payload(b"checkpoint"),
+        Err(acid_store::Error::Incompatible {
+            option: "commit_payload",
+            ..
+        })
+    ));
+    assert_that!(repo.committed_payload()).is_empty();
+
+    Ok(())
+}
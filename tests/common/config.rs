@@ -22,6 +22,13 @@ pub fn encoding_config() -> RepoConfig {
     config
 }
 
+/// The repository config used for testing Zstandard compression.
+pub fn zstd_config() -> RepoConfig {
+    let mut config = fixed_config();
+    config.compression = Compression::Zstd { level: 3 };
+    config
+}
+
 /// The repository config used for testing ZPAQ chunking.
 pub fn zpaq_config() -> RepoConfig {
     let mut config = fixed_config();
@@ -29,6 +36,17 @@ pub fn zpaq_config() -> RepoConfig {
     config
 }
 
+/// The repository config used for testing FastCDC chunking.
+pub fn fastcdc_config() -> RepoConfig {
+    let mut config = fixed_config();
+    config.chunking = Chunking::FastCdc {
+        min_size: 64,
+        avg_size: 256,
+        max_size: 1024,
+    };
+    config
+}
+
 /// The repository config used for testing packing with a size smaller than the chunk size.
 pub fn fixed_packing_small_config() -> RepoConfig {
     let mut config = fixed_config();
@@ -57,7 +75,9 @@ pub fn zpaq_packing_config() -> RepoConfig {
 #[rstest]
 #[case::fixed_size_chunking(fixed_config())]
 #[case::encoding(encoding_config())]
+#[case::zstd_compression(zstd_config())]
 #[case::zpaq_chunking(zpaq_config())]
+#[case::fastcdc_chunking(fastcdc_config())]
 #[case::small_pack_size(fixed_packing_small_config())]
 #[case::large_pack_size(fixed_packing_large_config())]
 #[case::zpaq_packing(zpaq_packing_config())]
@@ -68,7 +88,9 @@ pub fn config(#[case] config: RepoConfig) {}
 #[rstest]
 #[case::fixed_size_chunking(create_repo(fixed_config()).unwrap())]
 #[case::encoding(create_repo(encoding_config()).unwrap())]
+#[case::zstd_compression(create_repo(zstd_config()).unwrap())]
 #[case::zpaq_chunking(create_repo(zpaq_config()).unwrap())]
+#[case::fastcdc_chunking(create_repo(fastcdc_config()).unwrap())]
 #[case::small_pack_size(create_repo(fixed_packing_small_config()).unwrap())]
 #[case::large_pack_size(create_repo(fixed_packing_large_config()).unwrap())]
 #[case::zpaq_packing(create_repo(zpaq_packing_config()).unwrap())]
@@ -79,7 +101,9 @@ pub fn repo_config(#[case] repo: KeyRepo<String>) {}
 #[rstest]
 #[case::fixed_size_chunking(RepoObject::new(fixed_config()).unwrap())]
 #[case::encoding(RepoObject::new(encoding_config()).unwrap())]
+#[case::zstd_compression(RepoObject::new(zstd_config()).unwrap())]
 #[case::zpaq_chunking(RepoObject::new(zpaq_config()).unwrap())]
+#[case::fastcdc_chunking(RepoObject::new(fastcdc_config()).unwrap())]
 #[case::small_pack_size(RepoObject::new(fixed_packing_small_config()).unwrap())]
 #[case::large_pack_size(RepoObject::new(fixed_packing_large_config()).unwrap())]
 #[case::zpaq_packing(RepoObject::new(zpaq_packing_config()).unwrap())]
@@ -90,7 +114,9 @@ pub fn object_config(#[case] repo_object: RepoObject) {}
 #[rstest]
 #[case::fixed_size_chunking(RepoStore::new(fixed_config()))]
 #[case::encoding(RepoStore::new(encoding_config()))]
+#[case::zstd_compression(RepoStore::new(zstd_config()))]
 #[case::zpaq_chunking(RepoStore::new(zpaq_config()))]
+#[case::fastcdc_chunking(RepoStore::new(fastcdc_config()))]
 #[case::small_pack_size(RepoStore::new(fixed_packing_small_config()))]
 #[case::large_pack_size(RepoStore::new(fixed_packing_large_config()))]
 #[case::zpaq_packing(RepoStore::new(zpaq_packing_config()))]
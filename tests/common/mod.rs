@@ -9,16 +9,19 @@ mod store;
 
 pub use assertions::ErrorVariantAssertions;
 pub use config::{
-    encoding_config, fixed_config, fixed_packing_large_config, fixed_packing_small_config,
-    zpaq_config, zpaq_packing_config,
+    encoding_config, fastcdc_config, fixed_config, fixed_packing_large_config,
+    fixed_packing_small_config, zpaq_config, zpaq_packing_config, zstd_config,
 };
 pub use data::{buffer, fixed_buffer, larger_buffer, smaller_buffer, temp_dir};
-pub use repository::{create_repo, repo, repo_object, repo_store, RepoObject, RepoStore};
+pub use repository::{
+    create_repo, create_store_repo, read_object, read_store_object, repo, repo_object, repo_store,
+    write_object, RepoObject, RepoStore,
+};
 pub use rstest::*;
 pub use spectral::prelude::*;
 #[cfg(feature = "store-directory")]
 pub use store::{directory_config, directory_store};
-pub use store::{memory_config, memory_store};
+pub use store::{memory_config, memory_store, CountingConfig, CountingStore};
 #[cfg(feature = "store-rclone")]
 pub use store::{rclone_config, rclone_store};
 #[cfg(feature = "store-redis")]
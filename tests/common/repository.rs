@@ -1,10 +1,13 @@
+use std::io::{Read, Write};
+
 use rand::prelude::*;
 use rstest::*;
 
 use acid_store::repo::{
-    key::KeyRepo, InstanceId, Object, OpenMode, OpenOptions, OpenRepo, RepoConfig, DEFAULT_INSTANCE,
+    key::KeyRepo, Commit, InstanceId, Object, OpenMode, OpenOptions, OpenRepo, RepoConfig,
+    DEFAULT_INSTANCE,
 };
-use acid_store::store::MemoryConfig;
+use acid_store::store::{MemoryConfig, OpenStore};
 use rand::distributions::{Alphanumeric, DistString};
 
 const KEY_LEN: usize = 16;
@@ -86,6 +89,51 @@ pub fn create_repo<R: OpenRepo>(config: RepoConfig) -> anyhow::Result<R> {
         .open(&store_config)?)
 }
 
+/// Create a repository in `store` containing an object named "test" with the given `data`.
+///
+/// The repository is committed before it is returned.
+pub fn create_store_repo<S: OpenStore>(
+    store: &S,
+    config: RepoConfig,
+    data: &[u8],
+) -> anyhow::Result<KeyRepo<String>> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(config)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(store)?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(data)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    Ok(repo)
+}
+
+/// Return the contents of the object with the given `key` in the repository in `store`.
+pub fn read_store_object<S: OpenStore>(store: &S, key: &str) -> anyhow::Result<Vec<u8>> {
+    let repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(store)?;
+    read_object(&repo, key)
+}
+
+/// Write `data` to the object with the given `key`, replacing it if it already exists.
+pub fn write_object(repo: &mut KeyRepo<String>, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    let mut object = repo.insert(key.to_string());
+    object.write_all(data)?;
+    object.commit()?;
+    Ok(())
+}
+
+/// Return the contents of the object with the given `key`.
+pub fn read_object(repo: &KeyRepo<String>, key: &str) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    repo.object(key).unwrap().read_to_end(&mut data)?;
+    Ok(data)
+}
+
 /// A test fixture which provides a new empty repository.
 #[fixture]
 pub fn repo<R: OpenRepo>() -> R {
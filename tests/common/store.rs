@@ -1,19 +1,49 @@
 #![macro_use]
 
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
 use rstest_reuse::{self, *};
 use tempfile::TempDir;
 
+#[cfg(any(feature = "store-dropbox", feature = "store-google-drive"))]
+use acid_store::store::OAuthCredentials;
+#[cfg(feature = "store-azure")]
+use acid_store::store::{AzureConfig, AzureCredentials, AzureStore};
+#[cfg(feature = "store-b2")]
+use acid_store::store::{B2Config, B2Store};
 use acid_store::store::{
     BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore,
 };
+#[cfg(feature = "store-device")]
+use acid_store::store::{DeviceConfig, DeviceFormat, DeviceStore};
 #[cfg(feature = "store-directory")]
 use acid_store::store::{DirectoryConfig, DirectoryStore};
+#[cfg(feature = "store-dropbox")]
+use acid_store::store::{DropboxConfig, DropboxStore};
+#[cfg(feature = "store-file")]
+use acid_store::store::{FileConfig, FileStore};
+#[cfg(feature = "store-ftp")]
+use acid_store::store::{FtpConfig, FtpStore};
+#[cfg(feature = "store-gcs")]
+use acid_store::store::{GcsConfig, GcsCredentials, GcsStore};
+#[cfg(feature = "store-google-drive")]
+use acid_store::store::{GoogleDriveConfig, GoogleDriveStore};
+#[cfg(feature = "store-ipfs")]
+use acid_store::store::{IpfsConfig, IpfsStore};
+#[cfg(feature = "store-postgres")]
+use acid_store::store::{PostgresConfig, PostgresStore};
 #[cfg(feature = "store-rclone")]
 use acid_store::store::{RcloneConfig, RcloneStore};
 #[cfg(feature = "store-redis")]
 use acid_store::store::{RedisConfig, RedisStore};
+#[cfg(feature = "store-rocksdb")]
+use acid_store::store::{RocksdbConfig, RocksdbStore};
 #[cfg(feature = "store-s3")]
 use acid_store::store::{S3Config, S3Credentials, S3Region, S3Store};
+#[cfg(feature = "store-sled")]
+use acid_store::store::{SledConfig, SledStore};
 #[cfg(feature = "store-sqlite")]
 use acid_store::store::{SqliteConfig, SqliteStore};
 #[cfg(feature = "store-sftp")]
@@ -54,6 +84,13 @@ impl<T: DataStore> DataStore for WithTempDir<T> {
         self.value.read_block(key)
     }
 
+    fn read_block_streaming(
+        &mut self,
+        key: BlockKey,
+    ) -> acid_store::store::Result<Option<Box<dyn Read + '_>>> {
+        self.value.read_block_streaming(key)
+    }
+
     fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
         self.value.remove_block(key)
     }
@@ -71,6 +108,118 @@ impl<T: OpenStore> OpenStore for WithTempDir<T> {
     }
 }
 
+/// A data store config which counts the operations performed on a `MemoryStore`.
+#[derive(Debug, Clone)]
+pub struct CountingConfig {
+    pub inner: MemoryConfig,
+    writes: Arc<AtomicUsize>,
+    data_writes: Arc<AtomicUsize>,
+    data_reads: Arc<AtomicUsize>,
+    header_reads: Arc<AtomicUsize>,
+    syncs: Arc<AtomicUsize>,
+}
+
+impl CountingConfig {
+    /// Return a new config for an empty store.
+    pub fn new() -> Self {
+        Self::with_inner(MemoryConfig::new())
+    }
+
+    /// Return a new config which counts the operations performed on the store for `inner`.
+    pub fn with_inner(inner: MemoryConfig) -> Self {
+        CountingConfig {
+            inner,
+            writes: Arc::new(AtomicUsize::new(0)),
+            data_writes: Arc::new(AtomicUsize::new(0)),
+            data_reads: Arc::new(AtomicUsize::new(0)),
+            header_reads: Arc::new(AtomicUsize::new(0)),
+            syncs: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Return the number of blocks of any type which have been written or removed.
+    pub fn writes(&self) -> usize {
+        self.writes.load(Ordering::SeqCst)
+    }
+
+    /// Return the number of data blocks which have been written.
+    pub fn data_writes(&self) -> usize {
+        self.data_writes.load(Ordering::SeqCst)
+    }
+
+    /// Return the number of data blocks which have been read.
+    pub fn data_reads(&self) -> usize {
+        self.data_reads.load(Ordering::SeqCst)
+    }
+
+    /// Return the number of data blocks read since this was last called.
+    pub fn take_data_reads(&self) -> usize {
+        self.data_reads.swap(0, Ordering::SeqCst)
+    }
+
+    /// Return the number of header blocks which have been read.
+    pub fn header_reads(&self) -> usize {
+        self.header_reads.load(Ordering::SeqCst)
+    }
+
+    /// Return the number of times the store has been synced.
+    pub fn syncs(&self) -> usize {
+        self.syncs.load(Ordering::SeqCst)
+    }
+}
+
+impl OpenStore for CountingConfig {
+    type Store = CountingStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(CountingStore {
+            inner: self.inner.open()?,
+            counts: self.clone(),
+        })
+    }
+}
+
+/// A data store opened from a `CountingConfig`.
+#[derive(Debug)]
+pub struct CountingStore {
+    inner: MemoryStore,
+    counts: CountingConfig,
+}
+
+impl DataStore for CountingStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.counts.writes.fetch_add(1, Ordering::SeqCst);
+        if let BlockKey::Data(_) = key {
+            self.counts.data_writes.fetch_add(1, Ordering::SeqCst);
+        }
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        if let BlockKey::Data(_) = key {
+            self.counts.data_reads.fetch_add(1, Ordering::SeqCst);
+        }
+        if let BlockKey::Header(_) = key {
+            self.counts.header_reads.fetch_add(1, Ordering::SeqCst);
+        }
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.counts.writes.fetch_add(1, Ordering::SeqCst);
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+
+    fn sync(&mut self) -> acid_store::store::Result<()> {
+        self.counts.syncs.fetch_add(1, Ordering::SeqCst);
+        self.inner.sync()
+    }
+}
+
 pub fn memory_config() -> Box<dyn OpenStore<Store = MemoryStore>> {
     Box::new(MemoryConfig::new())
 }
@@ -82,9 +231,7 @@ pub fn memory_store() -> Box<dyn DataStore> {
 #[cfg(feature = "store-directory")]
 pub fn directory_config() -> Box<dyn OpenStore<Store = DirectoryStore>> {
     let directory = tempfile::tempdir().unwrap();
-    let config = DirectoryConfig {
-        path: directory.as_ref().join("store"),
-    };
+    let config = DirectoryConfig::new(directory.as_ref().join("store"));
     Box::new(WithTempDir {
         directory,
         value: config,
@@ -94,7 +241,31 @@ pub fn directory_config() -> Box<dyn OpenStore<Store = DirectoryStore>> {
 #[cfg(feature = "store-directory")]
 pub fn directory_store() -> Box<dyn DataStore> {
     let directory = tempfile::tempdir().unwrap();
-    let config = DirectoryConfig {
+    let config = DirectoryConfig::new(directory.as_ref().join("store"));
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(WithTempDir {
+        directory,
+        value: store,
+    })
+}
+
+#[cfg(feature = "store-sled")]
+pub fn sled_config() -> Box<dyn OpenStore<Store = SledStore>> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = SledConfig {
+        path: directory.as_ref().join("store"),
+    };
+    Box::new(WithTempDir {
+        directory,
+        value: config,
+    })
+}
+
+#[cfg(feature = "store-sled")]
+pub fn sled_store() -> Box<dyn DataStore> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = SledConfig {
         path: directory.as_ref().join("store"),
     };
     let mut store = config.open().unwrap();
@@ -105,6 +276,95 @@ pub fn directory_store() -> Box<dyn DataStore> {
     })
 }
 
+#[cfg(feature = "store-rocksdb")]
+pub fn rocksdb_config() -> Box<dyn OpenStore<Store = RocksdbStore>> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = RocksdbConfig {
+        path: directory.as_ref().join("store"),
+    };
+    Box::new(WithTempDir {
+        directory,
+        value: config,
+    })
+}
+
+#[cfg(feature = "store-rocksdb")]
+pub fn rocksdb_store() -> Box<dyn DataStore> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = RocksdbConfig {
+        path: directory.as_ref().join("store"),
+    };
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(WithTempDir {
+        directory,
+        value: store,
+    })
+}
+
+#[cfg(feature = "store-file")]
+pub fn file_config() -> Box<dyn OpenStore<Store = FileStore>> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = FileConfig {
+        path: directory.as_ref().join("store.acid"),
+    };
+    Box::new(WithTempDir {
+        directory,
+        value: config,
+    })
+}
+
+#[cfg(feature = "store-file")]
+pub fn file_store() -> Box<dyn DataStore> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = FileConfig {
+        path: directory.as_ref().join("store.acid"),
+    };
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(WithTempDir {
+        directory,
+        value: store,
+    })
+}
+
+#[cfg(feature = "store-device")]
+fn device_path(directory: &TempDir) -> std::path::PathBuf {
+    let path = directory.as_ref().join("device.img");
+    std::fs::File::create(&path)
+        .unwrap()
+        .set_len(64 * 1024 * 1024)
+        .unwrap();
+    DeviceStore::format(&path, &DeviceFormat::default()).unwrap();
+    path
+}
+
+#[cfg(feature = "store-device")]
+pub fn device_config() -> Box<dyn OpenStore<Store = DeviceStore>> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = DeviceConfig {
+        path: device_path(&directory),
+    };
+    Box::new(WithTempDir {
+        directory,
+        value: config,
+    })
+}
+
+#[cfg(feature = "store-device")]
+pub fn device_store() -> Box<dyn DataStore> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = DeviceConfig {
+        path: device_path(&directory),
+    };
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(WithTempDir {
+        directory,
+        value: store,
+    })
+}
+
 #[cfg(feature = "store-sqlite")]
 pub fn sqlite_config() -> Box<dyn OpenStore<Store = SqliteStore>> {
     let directory = tempfile::tempdir().unwrap();
@@ -131,6 +391,19 @@ pub fn sqlite_store() -> Box<dyn DataStore> {
     })
 }
 
+#[cfg(feature = "store-postgres")]
+pub fn postgres_config() -> Box<dyn OpenStore<Store = PostgresStore>> {
+    Box::new(PostgresConfig::new(dotenv::var("POSTGRES_URL").unwrap()))
+}
+
+#[cfg(feature = "store-postgres")]
+pub fn postgres_store() -> Box<dyn DataStore> {
+    let config = postgres_config();
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(store)
+}
+
 #[cfg(feature = "store-redis")]
 pub fn redis_config() -> Box<dyn OpenStore<Store = RedisStore>> {
     let url = dotenv::var("REDIS_URL").unwrap();
@@ -147,15 +420,16 @@ pub fn redis_store() -> Box<dyn DataStore> {
 
 #[cfg(feature = "store-s3")]
 pub fn s3_config() -> Box<dyn OpenStore<Store = S3Store>> {
-    Box::new(S3Config {
-        bucket: dotenv::var("S3_BUCKET").unwrap(),
-        region: S3Region::from_name(&dotenv::var("S3_REGION").unwrap()).unwrap(),
-        credentials: S3Credentials::Basic {
+    let mut config = S3Config::new(
+        dotenv::var("S3_BUCKET").unwrap(),
+        S3Region::from_name(&dotenv::var("S3_REGION").unwrap()).unwrap(),
+        S3Credentials::Basic {
             access_key: dotenv::var("S3_ACCESS_KEY").unwrap(),
             secret_key: dotenv::var("S3_SECRET_KEY").unwrap(),
         },
-        prefix: String::from("test"),
-    })
+    );
+    config.prefix = String::from("test");
+    Box::new(config)
 }
 
 #[cfg(feature = "store-s3")]
@@ -166,6 +440,125 @@ pub fn s3_store() -> Box<dyn DataStore> {
     Box::new(store)
 }
 
+#[cfg(feature = "store-azure")]
+pub fn azure_config() -> Box<dyn OpenStore<Store = AzureStore>> {
+    Box::new(AzureConfig {
+        account: dotenv::var("AZURE_ACCOUNT").unwrap(),
+        container: dotenv::var("AZURE_CONTAINER").unwrap(),
+        credentials: AzureCredentials::AccountKey {
+            key: dotenv::var("AZURE_ACCOUNT_KEY").unwrap(),
+        },
+        prefix: String::from("test"),
+        endpoint: dotenv::var("AZURE_ENDPOINT").ok(),
+    })
+}
+
+#[cfg(feature = "store-azure")]
+pub fn azure_store() -> Box<dyn DataStore> {
+    let config = azure_config();
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(store)
+}
+
+#[cfg(feature = "store-gcs")]
+pub fn gcs_config() -> Box<dyn OpenStore<Store = GcsStore>> {
+    let credentials = match dotenv::var("GCS_CREDENTIALS_FILE") {
+        Ok(path) => GcsCredentials::from_file(path).unwrap(),
+        Err(_) => GcsCredentials::Anonymous,
+    };
+    Box::new(GcsConfig {
+        bucket: dotenv::var("GCS_BUCKET").unwrap(),
+        credentials,
+        prefix: String::from("test"),
+        endpoint: dotenv::var("GCS_ENDPOINT").ok(),
+    })
+}
+
+#[cfg(feature = "store-gcs")]
+pub fn gcs_store() -> Box<dyn DataStore> {
+    let config = gcs_config();
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(store)
+}
+
+#[cfg(feature = "store-b2")]
+pub fn b2_config() -> Box<dyn OpenStore<Store = B2Store>> {
+    Box::new(B2Config {
+        key_id: dotenv::var("B2_KEY_ID").unwrap(),
+        application_key: dotenv::var("B2_APPLICATION_KEY").unwrap(),
+        bucket: dotenv::var("B2_BUCKET").unwrap(),
+        prefix: String::from("test"),
+        endpoint: None,
+    })
+}
+
+#[cfg(feature = "store-b2")]
+pub fn b2_store() -> Box<dyn DataStore> {
+    let config = b2_config();
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(store)
+}
+
+#[cfg(feature = "store-dropbox")]
+pub fn dropbox_config() -> Box<dyn OpenStore<Store = DropboxStore>> {
+    Box::new(DropboxConfig {
+        credentials: OAuthCredentials {
+            client_id: dotenv::var("DROPBOX_CLIENT_ID").unwrap(),
+            client_secret: dotenv::var("DROPBOX_CLIENT_SECRET").ok(),
+            refresh_token: dotenv::var("DROPBOX_REFRESH_TOKEN").unwrap(),
+        },
+        root: dotenv::var("DROPBOX_ROOT").unwrap(),
+    })
+}
+
+#[cfg(feature = "store-dropbox")]
+pub fn dropbox_store() -> Box<dyn DataStore> {
+    let config = dropbox_config();
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(store)
+}
+
+#[cfg(feature = "store-google-drive")]
+pub fn google_drive_config() -> Box<dyn OpenStore<Store = GoogleDriveStore>> {
+    Box::new(GoogleDriveConfig {
+        credentials: OAuthCredentials {
+            client_id: dotenv::var("GDRIVE_CLIENT_ID").unwrap(),
+            client_secret: dotenv::var("GDRIVE_CLIENT_SECRET").ok(),
+            refresh_token: dotenv::var("GDRIVE_REFRESH_TOKEN").unwrap(),
+        },
+        folder_id: dotenv::var("GDRIVE_FOLDER_ID").unwrap(),
+    })
+}
+
+#[cfg(feature = "store-google-drive")]
+pub fn google_drive_store() -> Box<dyn DataStore> {
+    let config = google_drive_config();
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(store)
+}
+
+#[cfg(feature = "store-ipfs")]
+pub fn ipfs_config() -> Box<dyn OpenStore<Store = IpfsStore>> {
+    let mut config = IpfsConfig::new(dotenv::var("IPFS_ROOT").unwrap());
+    if let Ok(api_url) = dotenv::var("IPFS_API_URL") {
+        config.api_url = api_url;
+    }
+    Box::new(config)
+}
+
+#[cfg(feature = "store-ipfs")]
+pub fn ipfs_store() -> Box<dyn DataStore> {
+    let config = ipfs_config();
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(store)
+}
+
 #[cfg(feature = "store-sftp")]
 pub fn sftp_config() -> Box<dyn OpenStore<Store = SftpStore>> {
     let sftp_server: String = dotenv::var("SFTP_SERVER").unwrap();
@@ -173,14 +566,14 @@ pub fn sftp_config() -> Box<dyn OpenStore<Store = SftpStore>> {
     let sftp_username: String = dotenv::var("SFTP_USERNAME").unwrap();
     let sftp_password: String = dotenv::var("SFTP_PASSWORD").unwrap();
 
-    Box::new(SftpConfig {
-        addr: sftp_server.parse().unwrap(),
-        auth: SftpAuth::Password {
+    Box::new(SftpConfig::new(
+        sftp_server.parse().unwrap(),
+        SftpAuth::Password {
             username: sftp_username,
             password: sftp_password,
         },
-        path: PathBuf::from(sftp_path),
-    })
+        PathBuf::from(sftp_path),
+    ))
 }
 #[cfg(feature = "store-sftp")]
 pub fn sftp_store() -> Box<dyn DataStore> {
@@ -190,6 +583,29 @@ pub fn sftp_store() -> Box<dyn DataStore> {
     Box::new(store)
 }
 
+#[cfg(feature = "store-ftp")]
+pub fn ftp_config() -> Box<dyn OpenStore<Store = FtpStore>> {
+    let ftp_host: String = dotenv::var("FTP_HOST").unwrap();
+    let ftp_path: String = dotenv::var("FTP_PATH").unwrap();
+    let ftp_username: String = dotenv::var("FTP_USERNAME").unwrap();
+    let ftp_password: String = dotenv::var("FTP_PASSWORD").unwrap();
+
+    Box::new(FtpConfig::new(
+        ftp_host,
+        ftp_username,
+        ftp_password,
+        ftp_path,
+    ))
+}
+
+#[cfg(feature = "store-ftp")]
+pub fn ftp_store() -> Box<dyn DataStore> {
+    let config = ftp_config();
+    let mut store = config.open().unwrap();
+    truncate_store(&mut store).unwrap();
+    Box::new(store)
+}
+
 #[cfg(feature = "store-rclone")]
 pub fn rclone_config() -> Box<dyn OpenStore<Store = RcloneStore>> {
     Box::new(RcloneConfig {
@@ -213,10 +629,25 @@ pub fn rclone_store() -> Box<dyn DataStore> {
 #[rstest]
 #[case::store_memory(memory_config())]
 #[cfg_attr(feature = "store-directory", case::store_directory(directory_config()))]
+#[cfg_attr(feature = "store-file", case::store_file(file_config()))]
+#[cfg_attr(feature = "store-device", case::store_device(device_config()))]
 #[cfg_attr(feature = "store-sqlite", case::store_sqlilte(sqlite_config()))]
+#[cfg_attr(feature = "store-sled", case::store_sled(sled_config()))]
+#[cfg_attr(feature = "store-rocksdb", case::store_rocksdb(rocksdb_config()))]
+#[cfg_attr(feature = "store-postgres", case::store_postgres(postgres_config()))]
 #[cfg_attr(feature = "store-redis", case::store_redis(redis_config()))]
 #[cfg_attr(feature = "store-s3", case::store_s3(s3_config()))]
+#[cfg_attr(feature = "store-azure", case::store_azure(azure_config()))]
+#[cfg_attr(feature = "store-gcs", case::store_gcs(gcs_config()))]
+#[cfg_attr(feature = "store-b2", case::store_b2(b2_config()))]
+#[cfg_attr(feature = "store-dropbox", case::store_dropbox(dropbox_config()))]
+#[cfg_attr(
+    feature = "store-google-drive",
+    case::store_google_drive(google_drive_config())
+)]
+#[cfg_attr(feature = "store-ipfs", case::store_ipfs(ipfs_config()))]
 #[cfg_attr(feature = "store-sftp", case::store_sftp(sftp_config()))]
+#[cfg_attr(feature = "store-ftp", case::store_ftp(ftp_config()))]
 #[cfg_attr(feature = "store-rclone", case::store_rclone(rclone_config()))]
 pub fn data_configs(#[case] config: Box<dyn OpenStore>) {}
 
@@ -228,9 +659,24 @@ pub fn data_configs(#[case] config: Box<dyn OpenStore>) {}
 #[rstest]
 #[case::store_memory(memory_store())]
 #[cfg_attr(feature = "store-directory", case::store_directory(directory_store()))]
+#[cfg_attr(feature = "store-file", case::store_file(file_store()))]
+#[cfg_attr(feature = "store-device", case::store_device(device_store()))]
 #[cfg_attr(feature = "store-sqlite", case::store_sqlilte(sqlite_store()))]
+#[cfg_attr(feature = "store-sled", case::store_sled(sled_store()))]
+#[cfg_attr(feature = "store-rocksdb", case::store_rocksdb(rocksdb_store()))]
+#[cfg_attr(feature = "store-postgres", case::store_postgres(postgres_store()))]
 #[cfg_attr(feature = "store-redis", case::store_redis(redis_store()))]
 #[cfg_attr(feature = "store-s3", case::store_s3(s3_store()))]
+#[cfg_attr(feature = "store-azure", case::store_azure(azure_store()))]
+#[cfg_attr(feature = "store-gcs", case::store_gcs(gcs_store()))]
+#[cfg_attr(feature = "store-b2", case::store_b2(b2_store()))]
+#[cfg_attr(feature = "store-dropbox", case::store_dropbox(dropbox_store()))]
+#[cfg_attr(
+    feature = "store-google-drive",
+    case::store_google_drive(google_drive_store())
+)]
+#[cfg_attr(feature = "store-ipfs", case::store_ipfs(ipfs_store()))]
 #[cfg_attr(feature = "store-sftp", case::store_sftp(sftp_store()))]
+#[cfg_attr(feature = "store-ftp", case::store_ftp(ftp_store()))]
 #[cfg_attr(feature = "store-rclone", case::store_rclone(rclone_store()))]
 pub fn data_stores(#[case] store: Box<dyn DataStore>) {}
@@ -0,0 +1,341 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Write;
+use std::time::Duration;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{
+    Chunking, Commit, Compression, DestructivePolicy, FormatVersion, ObjectOptions, OpenMode,
+    OpenOptions, RechunkOptions, RepoConfig,
+};
+use acid_store::store::{BlockKey, BlockType, DataStore, MemoryConfig, OpenStore};
+use common::*;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+mod common;
+
+/// The repository metadata as version 0.14 deserializes it.
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct MetadataV0_14 {
+    id: IgnoredAny,
+    config: ConfigV0_14,
+    master_key: IgnoredAny,
+    salt: IgnoredAny,
+    header_id: IgnoredAny,
+}
+
+/// The repository config as version 0.14 deserializes it.
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct ConfigV0_14 {
+    chunking: IgnoredAny,
+    packing: IgnoredAny,
+    compression: IgnoredAny,
+    encryption: IgnoredAny,
+    memory_limit: IgnoredAny,
+    operations_limit: IgnoredAny,
+}
+
+/// Return the config for a repository which can be read by version 0.14.
+fn legacy_config() -> RepoConfig {
+    let mut config = encoding_config();
+    config.compatibility_target = Some(FormatVersion::V0_14);
+    config
+}
+
+/// Create a repository in `store` which can be read by version 0.14.
+fn create_legacy_repo(store: &MemoryConfig) -> acid_store::Result<KeyRepo<String>> {
+    OpenOptions::new()
+        .config(legacy_config())
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(store)
+}
+
+/// Open the repository in `store`.
+fn open_repo(store: &MemoryConfig) -> acid_store::Result<KeyRepo<String>> {
+    OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(store)
+}
+
+/// Return whether version 0.14 could deserialize the metadata in `store`.
+fn is_readable_by_v0_14(store: &MemoryConfig) -> anyhow::Result<bool> {
+    let superblock = store.open()?.read_block(BlockKey::Super)?.unwrap();
+    Ok(rmp_serde::from_slice::<MetadataV0_14>(&superblock).is_ok())
+}
+
+#[rstest]
+#[case::max_header_size("max_header_size", |config: &mut RepoConfig| config.max_header_size = Some(1024))]
+#[case::verify_reads("verify_reads", |config: &mut RepoConfig| config.verify_reads = true)]
+#[case::gc_grace_period("gc_grace_period", |config: &mut RepoConfig| config.gc_grace_period = Duration::from_secs(1))]
+#[case::buffer_pool_size("buffer_pool_size", |config: &mut RepoConfig| config.buffer_pool_size = 1024)]
+#[case::commit_history("commit_history", |config: &mut RepoConfig| config.commit_history = 1)]
+#[case::fastcdc("chunking", |config: &mut RepoConfig| config.chunking = Chunking::FASTCDC)]
+#[case::min_chunk_size("min_chunk_size", |config: &mut RepoConfig| config.min_chunk_size = Some(64))]
+#[case::max_chunk_size("max_chunk_size", |config: &mut RepoConfig| config.max_chunk_size = Some(1024))]
+#[case::zstd("compression", |config: &mut RepoConfig| config.compression = Compression::Zstd { level: 3 })]
+fn incompatible_options_are_rejected_at_create(
+    #[case] option: &str,
+    #[case] set_option: fn(&mut RepoConfig),
+) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut config = legacy_config();
+    set_option(&mut config);
+
+    let result: acid_store::Result<KeyRepo<String>> = OpenOptions::new()
+        .config(config)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&store);
+    let error = result.unwrap_err();
+
+    assert!(matches!(
+        error,
+        acid_store::Error::Incompatible {
+            target: FormatVersion::V0_14,
+            ..
+        }
+    ));
+    assert_that!(error.to_string()).contains(option);
+    assert_that!(store.open()?.list_blocks(BlockType::Data)?).is_empty();
+    assert_that!(store.open()?.read_block(BlockKey::Super)?).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn compatible_options_are_accepted_without_target() -> anyhow::Result<()> {
+    let mut config = encoding_config();
+    config.max_header_size = Some(1024 * 1024);
+    config.verify_reads = true;
+
+    assert_that!(create_repo::<KeyRepo<String>>(config)).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn legacy_repo_can_be_read_by_old_version(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    assert_that!(is_readable_by_v0_14(&store)?).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn current_repo_cannot_be_read_by_old_version() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let _: KeyRepo<String> = OpenOptions::new()
+        .config(encoding_config())
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&store)?;
+
+    assert_that!(is_readable_by_v0_14(&store)?).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn target_is_kept_when_reopened(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let repo = open_repo(&store)?;
+
+    assert_that!(repo.info().config().compatibility_target).is_equal_to(Some(FormatVersion::V0_14));
+    assert_that!(repo.contains("test")).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn legacy_repo_keeps_no_audit_log() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let repo = create_legacy_repo(&store)?;
+
+    assert_that!(repo.audit_log()).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn destructive_policy_is_rejected() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+
+    let result = repo.set_destructive_policy(b"Password", DestructivePolicy::default());
+
+    assert!(matches!(
+        result,
+        Err(acid_store::Error::Incompatible {
+            option: "destructive_policy",
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+#[rstest]
+fn rechunk_is_rejected() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+
+    let result = repo.rechunk(Chunking::Fixed { size: 512 }, &RechunkOptions::new());
+
+    assert!(matches!(
+        result,
+        Err(acid_store::Error::Incompatible {
+            option: "rechunk",
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+#[rstest]
+fn dictionary_is_rejected() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+
+    let result = repo.train_dictionary(1024);
+
+    assert!(matches!(
+        result,
+        Err(acid_store::Error::Incompatible {
+            option: "zstd_dictionary",
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+#[rstest]
+fn object_compression_is_rejected() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+    let mut options = ObjectOptions::new();
+    options.compression(Compression::None);
+
+    let result = repo.insert_with_options(String::from("test"), &options);
+
+    assert!(matches!(
+        result,
+        Err(acid_store::Error::Incompatible {
+            option: "object_compression",
+            ..
+        })
+    ));
+    assert_that!(repo.contains("test")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_compression_is_allowed_per_object(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+    let mut options = ObjectOptions::new();
+    options
+        .compression(legacy_config().compression)
+        .chunking(Chunking::Fixed { size: 100 });
+
+    let mut object = repo.insert_with_options(String::from("test"), &options)?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    assert_that!(is_readable_by_v0_14(&store)?).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn trash_is_rejected_up_front(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    assert!(matches!(
+        repo.remove_to_trash("test"),
+        Err(acid_store::Error::Incompatible {
+            option: "trash",
+            ..
+        })
+    ));
+
+    // The object is left in place, so the repository can still be committed.
+    assert_that!(repo.contains("test")).is_true();
+    repo.commit()?;
+    drop(repo);
+    let repo = open_repo(&store)?;
+    assert_that!(repo.contains("test")).is_true();
+    assert_that!(is_readable_by_v0_14(&store)?).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn tags_are_rejected_at_commit() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+    repo.insert(String::from("test"));
+    repo.commit()?;
+
+    repo.add_tag("test", "tag")?;
+
+    assert!(matches!(
+        repo.commit(),
+        Err(acid_store::Error::Incompatible { option: "tags", .. })
+    ));
+    assert_that!(is_readable_by_v0_14(&store)?).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn removing_target_upgrades_repo() -> anyhow::Result<()> {
+    let store = MemoryConfig::new();
+    let mut repo = create_legacy_repo(&store)?;
+
+    repo.set_compatibility_target(None);
+    repo.set_verify_reads(true);
+    repo.commit()?;
+    repo.rechunk(Chunking::Fixed { size: 512 }, &RechunkOptions::new())?;
+    drop(repo);
+
+    assert_that!(is_readable_by_v0_14(&store)?).is_false();
+
+    let repo = open_repo(&store)?;
+    assert_that!(repo.info().config().compatibility_target).is_none();
+    assert_that!(repo.info().config().verify_reads).is_true();
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::repo::{Compression, Encryption, RepoConfig};
+use acid_store::store::{
+    BlockKey, CompressedConfig, CompressedStore, DataStore, MemoryConfig, OpenStore,
+};
+use common::*;
+
+mod common;
+
+#[rstest]
+fn repo_can_be_read_through_compressed_store(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo_config = RepoConfig::default();
+    repo_config.compression = Compression::None;
+    repo_config.encryption = Encryption::None;
+    let config = CompressedConfig {
+        inner: MemoryConfig::new(),
+        compression: Compression::Lz4 { level: 1 },
+    };
+
+    let repo = create_store_repo(&config, repo_config, &buffer)?;
+    assert_that!(repo.verify()?).is_empty();
+    drop(repo);
+
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn compressible_blocks_are_smaller() -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = CompressedStore::new(inner.open()?, Compression::Lz4 { level: 1 });
+    let data = vec![0u8; 64 * 1024];
+
+    store.write_block(BlockKey::Super, &data)?;
+
+    let contents = inner.open()?.read_block(BlockKey::Super)?.unwrap();
+    assert_that!(contents.len()).is_less_than(data.len());
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(data));
+
+    Ok(())
+}
+
+#[rstest]
+fn incompressible_blocks_are_stored_uncompressed(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = CompressedStore::new(inner.open()?, Compression::Lz4 { level: 1 });
+
+    store.write_block(BlockKey::Super, &buffer)?;
+
+    let contents = inner.open()?.read_block(BlockKey::Super)?.unwrap();
+    assert_that!(contents.len()).is_equal_to(buffer.len() + 1);
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn compression_can_be_changed() -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let data = vec![0u8; 64 * 1024];
+    CompressedStore::new(inner.open()?, Compression::Lz4 { level: 9 })
+        .write_block(BlockKey::Super, &data)?;
+
+    let mut store = CompressedStore::new(inner.open()?, Compression::None);
+
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(data));
+
+    Ok(())
+}
+
+#[rstest]
+fn zstd_blocks_can_be_read_with_other_compression() -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let data = vec![0u8; 64 * 1024];
+    let mut store = CompressedStore::new(inner.open()?, Compression::Zstd { level: 3 });
+    store.write_block(BlockKey::Super, &data)?;
+
+    let contents = inner.open()?.read_block(BlockKey::Super)?.unwrap();
+    assert_that!(contents.len()).is_less_than(data.len());
+
+    let mut store = CompressedStore::new(inner.open()?, Compression::Lz4 { level: 1 });
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(data));
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+#![cfg(all(
+    feature = "repo-content",
+    feature = "encryption",
+    feature = "compression"
+))]
+
+use std::collections::HashSet;
+use std::io::Read;
+
+use acid_store::repo::content::{ContentRepo, HashAlgorithm, HashId};
+use acid_store::repo::Commit;
+use common::*;
+use sha2::{Digest, Sha256};
+
+mod common;
+
+/// Read the data with the given `hash` from `repo`.
+fn read_data(repo: &ContentRepo, hash: &HashId) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    repo.get(hash).unwrap().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[rstest]
+fn put_and_get_data(mut repo: ContentRepo, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let hash = repo.put(buffer.as_slice())?;
+
+    assert_that!(repo.contains(&hash)).is_true();
+    assert_that!(read_data(&repo, &hash)?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn default_algorithm_is_blake3(mut repo: ContentRepo, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let hash = repo.put(buffer.as_slice())?;
+
+    assert_that!(repo.algorithm()).is_equal_to(HashAlgorithm::Blake3);
+    assert_that!(hash.algorithm()).is_equal_to(HashAlgorithm::Blake3);
+    assert_that!(hash.as_bytes()).is_equal_to(blake3::hash(&buffer).as_bytes().as_slice());
+    assert_that!(hash.to_string()).is_equal_to(blake3::hash(&buffer).to_hex().to_string());
+
+    Ok(())
+}
+
+#[rstest]
+fn putting_same_data_twice_stores_it_once(
+    mut repo: ContentRepo,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let first = repo.put(buffer.as_slice())?;
+    let second = repo.put(buffer.as_slice())?;
+
+    assert_that!(first).is_equal_to(&second);
+    assert_that!(repo.hashes().count()).is_equal_to(1);
+
+    Ok(())
+}
+
+#[rstest]
+fn remove_data(mut repo: ContentRepo, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let hash = repo.put(buffer.as_slice())?;
+
+    assert_that!(repo.remove(&hash)).is_true();
+    assert_that!(repo.remove(&hash)).is_false();
+    assert_that!(repo.contains(&hash)).is_false();
+    assert_that!(repo.get(&hash).is_none()).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn change_algorithm_rehashes_data(mut repo: ContentRepo, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let old_hash = repo.put(buffer.as_slice())?;
+
+    repo.change_algorithm(HashAlgorithm::Sha256)?;
+
+    let expected = HashId::new(HashAlgorithm::Sha256, Sha256::digest(&buffer).to_vec());
+    assert_that!(repo.algorithm()).is_equal_to(HashAlgorithm::Sha256);
+    assert_that!(repo.contains(&old_hash)).is_false();
+    assert_that!(repo.contains(&expected)).is_true();
+    assert_that!(read_data(&repo, &expected)?).is_equal_to(&buffer);
+    assert_that!(repo.put(buffer.as_slice())).is_ok_containing(expected);
+
+    Ok(())
+}
+
+#[rstest]
+fn data_persists_after_commit(repo_store: RepoStore, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: ContentRepo = repo_store.create()?;
+    repo.change_algorithm(HashAlgorithm::Sha256)?;
+    let hash = repo.put(buffer.as_slice())?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: ContentRepo = repo_store.open()?;
+
+    assert_that!(repo.algorithm()).is_equal_to(HashAlgorithm::Sha256);
+    assert_that!(read_data(&repo, &hash)?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn data_removed_on_rollback(mut repo: ContentRepo, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let hash = repo.put(buffer.as_slice())?;
+
+    repo.rollback()?;
+
+    assert_that!(repo.contains(&hash)).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn verify_valid_repository_is_valid(mut repo: ContentRepo, buffer: Vec<u8>) -> anyhow::Result<()> {
+    repo.put(buffer.as_slice())?;
+
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 #![cfg(all(feature = "encryption", feature = "compression"))]
 
 use std::fmt::Debug;
+use std::io::Read;
 
 use acid_store::store::{BlockKey, BlockType, DataStore, OpenStore};
 use rstest_reuse::{self, *};
@@ -30,6 +31,27 @@ fn read_data_block(#[case] mut store: Box<dyn DataStore>, buffer: Vec<u8>) {
     assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer));
 }
 
+#[apply(data_stores)]
+#[serial(data_store)]
+fn read_data_block_streaming(
+    #[case] mut store: Box<dyn DataStore>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.read_block_streaming(BlockKey::Data(id))?.is_none()).is_true();
+    store.write_block(BlockKey::Data(id), &buffer)?;
+
+    let mut data = Vec::new();
+    store
+        .read_block_streaming(BlockKey::Data(id))?
+        .unwrap()
+        .read_to_end(&mut data)?;
+    assert_that!(data).is_equal_to(buffer);
+
+    Ok(())
+}
+
 #[apply(data_stores)]
 #[serial(data_store)]
 fn read_lock_block(#[case] mut store: Box<dyn DataStore>, buffer: Vec<u8>) {
@@ -255,3 +277,52 @@ fn list_header_blocks(#[case] mut store: Box<dyn DataStore>, buffer: Vec<u8>) {
         .is_ok()
         .contains_all_of(&[&id1, &id2, &id3]);
 }
+
+#[apply(data_stores)]
+#[serial(data_store)]
+fn read_and_write_blocks_in_batch(
+    #[case] mut store: Box<dyn DataStore>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let data_key = BlockKey::Data(Uuid::new_v4().into());
+    let header_key = BlockKey::Header(Uuid::new_v4().into());
+    let missing_key = BlockKey::Data(Uuid::new_v4().into());
+
+    store.write_blocks(&[
+        (data_key, buffer.as_slice()),
+        (header_key, &[1, 2, 3][..]),
+        (BlockKey::Super, &[4][..]),
+    ])?;
+
+    assert_that!(store.read_blocks(&[header_key, missing_key, data_key, BlockKey::Super])?)
+        .is_equal_to(vec![Some(vec![1, 2, 3]), None, Some(buffer), Some(vec![4])]);
+    assert_that!(store.read_blocks(&[])?).is_empty();
+
+    Ok(())
+}
+
+#[apply(data_stores)]
+#[serial(data_store)]
+fn remove_blocks_in_batch(
+    #[case] mut store: Box<dyn DataStore>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let id1 = Uuid::new_v4().into();
+    let id2 = Uuid::new_v4().into();
+    let id3 = Uuid::new_v4().into();
+
+    store.write_blocks(&[
+        (BlockKey::Data(id1), buffer.as_slice()),
+        (BlockKey::Data(id2), buffer.as_slice()),
+        (BlockKey::Data(id3), buffer.as_slice()),
+    ])?;
+    store.remove_blocks(&[
+        BlockKey::Data(id1),
+        BlockKey::Data(id3),
+        BlockKey::Data(Uuid::new_v4().into()),
+    ])?;
+
+    assert_that!(store.list_blocks(BlockType::Data)?).is_equal_to(vec![id2]);
+
+    Ok(())
+}
@@ -0,0 +1,218 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, DestructivePolicy, DestructiveScope, Encryption, RepoConfig};
+use common::*;
+
+mod common;
+
+const NUM_OBJECTS: usize = 10;
+
+const AUTHORIZATION_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Return a policy which allows removing at most `max_removed_objects` objects between commits.
+fn policy(max_removed_objects: u64) -> DestructivePolicy {
+    let mut policy = DestructivePolicy::default();
+    policy.enabled = true;
+    policy.max_removed_objects = Some(max_removed_objects);
+    policy
+}
+
+/// Return a committed repository with `NUM_OBJECTS` objects and the given `policy`.
+fn repo_with_policy(policy: DestructivePolicy) -> anyhow::Result<KeyRepo<String>> {
+    let mut repo: KeyRepo<String> = create_repo(RepoConfig::default())?;
+    for i in 0..NUM_OBJECTS {
+        repo.insert(format!("{}", i));
+    }
+    repo.set_destructive_policy(b"Password", policy)?;
+    repo.commit()?;
+    Ok(repo)
+}
+
+#[rstest]
+fn removing_objects_over_threshold_requires_authorization() -> anyhow::Result<()> {
+    let mut repo = repo_with_policy(policy(2))?;
+
+    for i in 0..3 {
+        repo.remove(&format!("{}", i));
+    }
+
+    assert_that!(repo.commit()).is_err_variant(acid_store::Error::DestructiveNotAuthorized {
+        scope: DestructiveScope::Remove,
+        objects: 0,
+        bytes: 0,
+    });
+
+    repo.authorize_destructive(DestructiveScope::Remove, AUTHORIZATION_TTL);
+    assert_that!(repo.commit()).is_ok();
+    assert_that!(repo.keys().count()).is_equal_to(NUM_OBJECTS - 3);
+
+    Ok(())
+}
+
+#[rstest]
+fn removing_objects_under_threshold_is_unaffected() -> anyhow::Result<()> {
+    let mut repo = repo_with_policy(policy(2))?;
+
+    repo.remove("0");
+    repo.remove("1");
+    assert_that!(repo.commit()).is_ok();
+
+    // The threshold applies to the removals between commits.
+    repo.remove("2");
+    repo.remove("3");
+    assert_that!(repo.commit()).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn removing_bytes_over_threshold_requires_authorization(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut policy = DestructivePolicy::default();
+    policy.enabled = true;
+    policy.max_removed_bytes = Some(buffer.len() as u64 - 1);
+    let mut repo = repo_with_policy(policy)?;
+
+    let mut object = repo.insert(String::from("Data"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    repo.remove("Data");
+    assert_that!(repo.commit()).is_err_variant(acid_store::Error::DestructiveNotAuthorized {
+        scope: DestructiveScope::Remove,
+        objects: 0,
+        bytes: 0,
+    });
+
+    Ok(())
+}
+
+#[rstest]
+fn replacing_objects_is_not_counted() -> anyhow::Result<()> {
+    let mut repo = repo_with_policy(policy(0))?;
+
+    for i in 0..NUM_OBJECTS {
+        repo.insert(format!("{}", i));
+    }
+    assert_that!(repo.commit()).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn clearing_instance_requires_authorization() -> anyhow::Result<()> {
+    let mut repo = repo_with_policy(policy(u64::MAX))?;
+
+    repo.clear_instance();
+    assert_that!(repo.commit()).is_err_variant(acid_store::Error::DestructiveNotAuthorized {
+        scope: DestructiveScope::Clear,
+        objects: 0,
+        bytes: 0,
+    });
+
+    repo.authorize_destructive(DestructiveScope::Clear, AUTHORIZATION_TTL);
+    assert_that!(repo.commit()).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn purging_trash_requires_authorization() -> anyhow::Result<()> {
+    let mut repo = repo_with_policy(policy(u64::MAX))?;
+
+    repo.remove_to_trash("0")?;
+    assert_that!(repo.commit()).is_ok();
+
+    repo.purge_trash(SystemTime::now());
+    assert_that!(repo.commit()).is_err_variant(acid_store::Error::DestructiveNotAuthorized {
+        scope: DestructiveScope::PurgeTrash,
+        objects: 0,
+        bytes: 0,
+    });
+
+    // Authorizing a different scope is not enough.
+    repo.authorize_destructive(DestructiveScope::Remove, AUTHORIZATION_TTL);
+    assert_that!(repo.commit()).is_err_variant(acid_store::Error::DestructiveNotAuthorized {
+        scope: DestructiveScope::PurgeTrash,
+        objects: 0,
+        bytes: 0,
+    });
+
+    repo.authorize_destructive(DestructiveScope::PurgeTrash, AUTHORIZATION_TTL);
+    assert_that!(repo.commit()).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn expired_authorization_is_rejected() -> anyhow::Result<()> {
+    let mut repo = repo_with_policy(policy(0))?;
+
+    repo.remove("0");
+    repo.authorize_destructive(DestructiveScope::Remove, Duration::ZERO);
+    assert_that!(repo.commit()).is_err_variant(acid_store::Error::DestructiveNotAuthorized {
+        scope: DestructiveScope::Remove,
+        objects: 0,
+        bytes: 0,
+    });
+
+    Ok(())
+}
+
+#[rstest]
+fn rollback_forgets_removals() -> anyhow::Result<()> {
+    let mut repo = repo_with_policy(policy(0))?;
+
+    repo.remove("0");
+    repo.rollback()?;
+    assert_that!(repo.commit()).is_ok();
+    assert_that!(repo.contains("0")).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn disabled_policy_allows_destructive_operations() -> anyhow::Result<()> {
+    let mut repo = repo_with_policy(DestructivePolicy::default())?;
+
+    repo.remove("0");
+    repo.clear_instance();
+    assert_that!(repo.commit()).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn policy_is_persisted(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.set_destructive_policy(repo_store.password.as_bytes(), policy(5))?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.destructive_policy()).is_equal_to(policy(5));
+
+    Ok(())
+}
+
+#[rstest]
+fn setting_policy_with_wrong_password_errs() -> anyhow::Result<()> {
+    let mut config = RepoConfig::default();
+    config.encryption = Encryption::XChaCha20Poly1305;
+    let repo_store = RepoStore::new(config);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    assert_that!(repo.set_destructive_policy(b"Wrong password", policy(5)))
+        .is_err_variant(acid_store::Error::Password);
+    assert_that!(repo.destructive_policy()).is_equal_to(DestructivePolicy::default());
+
+    assert_that!(repo.set_destructive_policy(repo_store.password.as_bytes(), policy(5))).is_ok();
+    assert_that!(repo.destructive_policy()).is_equal_to(policy(5));
+
+    Ok(())
+}
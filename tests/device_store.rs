@@ -0,0 +1,145 @@
+#![cfg(feature = "store-device")]
+
+use std::fs::{read, write, File};
+use std::path::PathBuf;
+
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, DeviceConfig, DeviceFormat, DeviceStore, OpenStore,
+};
+use common::*;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+mod common;
+
+/// A format which fits on a small device.
+const SMALL_FORMAT: DeviceFormat = DeviceFormat {
+    unit_size: 512,
+    max_blocks: 16,
+    journal_entries: 4,
+};
+
+/// Return the path of a file of `len` bytes in `temp_dir` which has not been formatted.
+fn device_path(temp_dir: &TempDir, len: u64) -> anyhow::Result<PathBuf> {
+    let path = temp_dir.path().join("device.img");
+    File::create(&path)?.set_len(len)?;
+    Ok(path)
+}
+
+/// Return the path of a small device in `temp_dir` which has been formatted.
+fn formatted_path(temp_dir: &TempDir) -> anyhow::Result<PathBuf> {
+    let path = device_path(temp_dir, 64 * 1024)?;
+    DeviceStore::format(&path, &SMALL_FORMAT)?;
+    Ok(path)
+}
+
+#[rstest]
+fn open_unformatted_device_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = device_path(&temp_dir, 64 * 1024)?;
+    assert_that!(DeviceStore::open(&path)).is_err_variant(acid_store::Error::InvalidStore);
+    Ok(())
+}
+
+#[rstest]
+fn format_small_device_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = device_path(&temp_dir, 1024)?;
+    assert_that!(DeviceStore::format(&path, &SMALL_FORMAT)).is_err();
+    Ok(())
+}
+
+#[rstest]
+fn blocks_persist_after_reopening(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let path = formatted_path(&temp_dir)?;
+    let ids = (0..10)
+        .map(|_| BlockId::from(Uuid::new_v4()))
+        .collect::<Vec<_>>();
+
+    // Write more changes than fit in the journal so that the block table is rewritten.
+    let mut store = DeviceStore::open(&path)?;
+    for id in &ids {
+        store.write_block(BlockKey::Data(*id), &buffer)?;
+    }
+    store.remove_block(BlockKey::Data(ids[0]))?;
+    drop(store);
+
+    let mut store = DeviceStore::open(&path)?;
+    assert_that!(store.read_block(BlockKey::Data(ids[0]))?).is_none();
+    for id in &ids[1..] {
+        assert_that!(store.read_block(BlockKey::Data(*id))?).is_equal_to(Some(buffer.clone()));
+    }
+    assert_that!(store.list_blocks(BlockType::Data)?).has_length(ids.len() - 1);
+
+    Ok(())
+}
+
+#[rstest]
+fn removing_blocks_frees_space(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let path = formatted_path(&temp_dir)?;
+    let mut store = DeviceStore::open(&path)?;
+    let id = BlockId::from(Uuid::new_v4());
+    let initial_space = store.free_space();
+
+    store.write_block(BlockKey::Data(id), &buffer)?;
+    assert_that!(store.free_space()).is_less_than(initial_space);
+
+    store.remove_block(BlockKey::Data(id))?;
+    assert_that!(store.free_space()).is_equal_to(initial_space);
+
+    Ok(())
+}
+
+#[rstest]
+fn writing_to_full_device_errs(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let path = formatted_path(&temp_dir)?;
+    let mut store = DeviceStore::open(&path)?;
+    let data = vec![0u8; store.capacity() as usize];
+
+    store.write_block(BlockKey::Super, &data)?;
+
+    assert_that!(store.write_block(BlockKey::Version, &buffer).is_err()).is_true();
+    assert_that!(store.read_block(BlockKey::Version)?).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn check_and_repair_corrupt_block(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let path = formatted_path(&temp_dir)?;
+    let corrupt_id = BlockId::from(Uuid::new_v4());
+    let intact_id = BlockId::from(Uuid::new_v4());
+    let mut store = DeviceStore::open(&path)?;
+    store.write_block(BlockKey::Data(corrupt_id), &buffer)?;
+    store.write_block(BlockKey::Data(intact_id), &[1, 2, 3])?;
+    drop(store);
+
+    assert_that!(DeviceStore::check(&path)?.is_consistent()).is_true();
+
+    // Flip a byte in the contents of the block.
+    let mut contents = read(&path)?;
+    let offset = contents
+        .windows(16)
+        .position(|window| window == &buffer[..16])
+        .unwrap();
+    contents[offset] ^= 0xff;
+    write(&path, &contents)?;
+
+    let report = DeviceStore::check(&path)?;
+    assert_that!(report.corrupt_blocks.contains(&BlockKey::Data(corrupt_id))).is_true();
+    assert_that!(report.corrupt_blocks.len()).is_equal_to(1);
+
+    assert_that!(DeviceStore::repair(&path)?).is_equal_to(&report);
+    assert_that!(DeviceStore::check(&path)?.is_consistent()).is_true();
+
+    let mut store = DeviceStore::open(&path)?;
+    assert_that!(store.read_block(BlockKey::Data(corrupt_id))?).is_none();
+    assert_that!(store.read_block(BlockKey::Data(intact_id))?).is_equal_to(Some(vec![1, 2, 3]));
+
+    Ok(())
+}
+
+#[rstest]
+fn config_opens_formatted_device(temp_dir: TempDir) -> anyhow::Result<()> {
+    let config = DeviceConfig::new(formatted_path(&temp_dir)?);
+    assert_that!(config.open()).is_ok();
+    Ok(())
+}
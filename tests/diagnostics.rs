@@ -0,0 +1,92 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::diagnostics::{open_repositories, LockKind, OpenRepoInfo};
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{OpenMode, OpenOptions, Unlock};
+use acid_store::store::MemoryConfig;
+use common::*;
+
+mod common;
+
+/// Open a new repository with the given `label`.
+fn open_labeled(label: &str) -> acid_store::Result<KeyRepo<String>> {
+    OpenOptions::new()
+        .mode(OpenMode::CreateNew)
+        .label(label)
+        .open(&MemoryConfig::new())
+}
+
+/// Return the open repositories with the given `label`.
+///
+/// Other tests may open repositories concurrently, so we filter by label.
+fn with_label(label: &str) -> Vec<OpenRepoInfo> {
+    open_repositories()
+        .into_iter()
+        .filter(|info| info.label() == Some(label))
+        .collect()
+}
+
+#[rstest]
+fn open_repositories_are_listed() -> anyhow::Result<()> {
+    let first = open_labeled("diagnostics-listed-first")?;
+    let second = open_labeled("diagnostics-listed-second")?;
+
+    let first_infos = with_label("diagnostics-listed-first");
+    let second_infos = with_label("diagnostics-listed-second");
+
+    assert_that!(first_infos).has_length(1);
+    assert_that!(second_infos).has_length(1);
+    assert_that!(first_infos[0].id()).is_equal_to(first.info().id());
+    assert_that!(second_infos[0].id()).is_equal_to(second.info().id());
+    assert_that!(first_infos[0].lock_kind()).is_equal_to(Some(LockKind::Exclusive));
+    assert_that!(second_infos[0].lock_kind()).is_equal_to(Some(LockKind::Exclusive));
+
+    Ok(())
+}
+
+#[rstest]
+fn dropped_repositories_are_not_listed() -> anyhow::Result<()> {
+    let first = open_labeled("diagnostics-dropped-first")?;
+    let second = open_labeled("diagnostics-dropped-second")?;
+
+    drop(first);
+
+    assert_that!(with_label("diagnostics-dropped-first")).has_length(0);
+    assert_that!(with_label("diagnostics-dropped-second")).has_length(1);
+
+    drop(second);
+
+    assert_that!(with_label("diagnostics-dropped-second")).has_length(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn unlocked_repositories_have_no_lock_kind() -> anyhow::Result<()> {
+    let repo = open_labeled("diagnostics-unlocked")?;
+    repo.unlock()?;
+
+    let infos = with_label("diagnostics-unlocked");
+
+    assert_that!(infos).has_length(1);
+    assert_that!(infos[0].lock_kind()).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn failed_open_is_not_listed() {
+    let store_config = MemoryConfig::new();
+    let _repo: KeyRepo<String> = OpenOptions::new()
+        .mode(OpenMode::CreateNew)
+        .open(&store_config)
+        .unwrap();
+
+    let result: acid_store::Result<KeyRepo<String>> = OpenOptions::new()
+        .mode(OpenMode::Open)
+        .label("diagnostics-failed")
+        .open(&store_config);
+
+    assert_that!(result).is_err_variant(acid_store::Error::Locked);
+    assert_that!(with_label("diagnostics-failed")).has_length(0);
+}
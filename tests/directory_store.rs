@@ -0,0 +1,210 @@
+#![cfg(all(
+    feature = "encryption",
+    feature = "compression",
+    feature = "store-directory"
+))]
+
+use std::fs::{create_dir, write};
+use std::path::PathBuf;
+
+use acid_store::store::{BlockId, BlockKey, DataStore, DirectoryConfig, DirectoryStore, OpenStore};
+use common::*;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+mod common;
+
+/// Return the path of a directory which does not exist.
+fn missing_path(temp_dir: &TempDir) -> PathBuf {
+    temp_dir.path().join("store")
+}
+
+/// Return the path of an empty directory.
+fn empty_path(temp_dir: &TempDir) -> anyhow::Result<PathBuf> {
+    let path = temp_dir.path().join("store");
+    create_dir(&path)?;
+    Ok(path)
+}
+
+/// Return the path of a directory which contains a valid store.
+fn valid_path(temp_dir: &TempDir) -> anyhow::Result<PathBuf> {
+    let path = temp_dir.path().join("store");
+    DirectoryStore::create_new(&path)?;
+    Ok(path)
+}
+
+/// Return the path of a directory which contains files which are not a store.
+fn junk_path(temp_dir: &TempDir) -> anyhow::Result<PathBuf> {
+    let path = temp_dir.path().join("store");
+    create_dir(&path)?;
+    write(
+        path.join("notes.txt"),
+        b"These are not the blocks you're looking for.",
+    )?;
+    Ok(path)
+}
+
+#[rstest]
+fn open_missing_store_errs(temp_dir: TempDir) {
+    assert_that!(DirectoryStore::open(missing_path(&temp_dir)))
+        .is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn open_empty_directory_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    assert_that!(DirectoryStore::open(empty_path(&temp_dir)?))
+        .is_err_variant(acid_store::Error::InvalidStore);
+    Ok(())
+}
+
+#[rstest]
+fn open_valid_store_succeeds(temp_dir: TempDir) -> anyhow::Result<()> {
+    assert_that!(DirectoryStore::open(valid_path(&temp_dir)?)).is_ok();
+    Ok(())
+}
+
+#[rstest]
+fn open_junk_directory_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    assert_that!(DirectoryStore::open(junk_path(&temp_dir)?))
+        .is_err_variant(acid_store::Error::InvalidStore);
+    Ok(())
+}
+
+#[rstest]
+fn open_store_with_incompatible_version_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = valid_path(&temp_dir)?;
+    write(
+        path.join("version"),
+        b"00000000-0000-0000-0000-000000000000",
+    )?;
+
+    assert_that!(DirectoryStore::open(&path)).is_err_variant(acid_store::Error::UnsupportedStore);
+
+    Ok(())
+}
+
+#[rstest]
+fn create_new_missing_store_succeeds(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = missing_path(&temp_dir);
+
+    assert_that!(DirectoryStore::create_new(&path)).is_ok();
+    assert_that!(DirectoryStore::open(&path)).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn create_new_in_empty_directory_succeeds(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = empty_path(&temp_dir)?;
+
+    assert_that!(DirectoryStore::create_new(&path)).is_ok();
+    assert_that!(DirectoryStore::open(&path)).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn create_new_over_valid_store_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    assert_that!(DirectoryStore::create_new(valid_path(&temp_dir)?))
+        .is_err_variant(acid_store::Error::AlreadyExists);
+    Ok(())
+}
+
+#[rstest]
+fn create_new_over_junk_directory_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    assert_that!(DirectoryStore::create_new(junk_path(&temp_dir)?))
+        .is_err_variant(acid_store::Error::AlreadyExists);
+    Ok(())
+}
+
+#[rstest]
+fn open_or_create_missing_store_succeeds(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = missing_path(&temp_dir);
+
+    assert_that!(DirectoryStore::open_or_create(&path)).is_ok();
+    assert_that!(DirectoryStore::open(&path)).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn open_or_create_empty_directory_succeeds(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = empty_path(&temp_dir)?;
+
+    assert_that!(DirectoryStore::open_or_create(&path)).is_ok();
+    assert_that!(DirectoryStore::open(&path)).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn open_or_create_valid_store_succeeds(temp_dir: TempDir) -> anyhow::Result<()> {
+    assert_that!(DirectoryStore::open_or_create(valid_path(&temp_dir)?)).is_ok();
+    Ok(())
+}
+
+#[rstest]
+fn open_or_create_junk_directory_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    assert_that!(DirectoryStore::open_or_create(junk_path(&temp_dir)?))
+        .is_err_variant(acid_store::Error::InvalidStore);
+    Ok(())
+}
+
+#[rstest]
+fn config_opens_or_creates_store(temp_dir: TempDir) -> anyhow::Result<()> {
+    let config = DirectoryConfig::new(missing_path(&temp_dir));
+
+    assert_that!(config.open()).is_ok();
+    assert_that!(config.open()).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn sync_persists_written_and_removed_blocks(temp_dir: TempDir) -> anyhow::Result<()> {
+    let mut store = DirectoryStore::create_new(missing_path(&temp_dir))?;
+    let kept_id = BlockId::from(Uuid::new_v4());
+    let removed_id = BlockId::from(Uuid::new_v4());
+
+    store.write_block(BlockKey::Data(kept_id), b"kept")?;
+    store.write_block(BlockKey::Data(removed_id), b"removed")?;
+    store.remove_block(BlockKey::Data(removed_id))?;
+    store.sync()?;
+
+    // Syncing again when nothing has changed does nothing.
+    store.sync()?;
+
+    let mut store = DirectoryStore::open(missing_path(&temp_dir))?;
+    assert_that!(store.read_block(BlockKey::Data(kept_id))?).is_equal_to(Some(b"kept".to_vec()));
+    assert_that!(store.read_block(BlockKey::Data(removed_id))?).is_none();
+
+    Ok(())
+}
+
+#[cfg(feature = "store-directory-mmap")]
+#[rstest]
+fn memory_mapped_blocks_can_be_read(temp_dir: TempDir) -> anyhow::Result<()> {
+    use std::io::Read;
+
+    let mut config = DirectoryConfig::new(missing_path(&temp_dir));
+    config.memory_map = true;
+    let mut store = config.open()?;
+    let small_block = vec![1u8; 16];
+    let large_block = vec![2u8; 1024 * 1024];
+
+    store.write_block(BlockKey::Version, &small_block)?;
+    store.write_block(BlockKey::Super, &large_block)?;
+
+    assert_that!(store.memory_map()).is_true();
+    assert_that!(store.read_block(BlockKey::Version)?).is_equal_to(Some(small_block));
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(large_block.clone()));
+
+    let mut streamed = Vec::new();
+    store
+        .read_block_streaming(BlockKey::Super)?
+        .unwrap()
+        .read_to_end(&mut streamed)?;
+    assert_that!(streamed).is_equal_to(large_block);
+
+    Ok(())
+}
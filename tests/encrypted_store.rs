@@ -0,0 +1,99 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, EncryptedConfig, EncryptedStore, MemoryConfig,
+    OpenStore, StoreKey,
+};
+use acid_store::uuid::Uuid;
+use common::*;
+
+mod common;
+
+#[rstest]
+#[case(false)]
+#[case(true)]
+fn repo_can_be_read_through_encrypted_store(
+    #[case] obfuscate_ids: bool,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let config = EncryptedConfig {
+        inner: MemoryConfig::new(),
+        key: StoreKey::generate(),
+        obfuscate_ids,
+    };
+
+    let repo = create_store_repo(&config, encoding_config(), &buffer)?;
+    assert_that!(repo.verify()?).is_empty();
+    drop(repo);
+
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn blocks_are_encrypted_in_inner_store(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = EncryptedStore::new(inner.open()?, StoreKey::generate(), false);
+    let id = BlockId::from(Uuid::new_v4());
+
+    store.write_block(BlockKey::Data(id), &buffer)?;
+
+    let ciphertext = inner.open()?.read_block(BlockKey::Data(id))?.unwrap();
+    assert_that!(ciphertext).is_not_equal_to(&buffer);
+    assert_that!(store.read_block(BlockKey::Data(id))?).is_equal_to(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn block_ids_are_obfuscated(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = EncryptedStore::new(inner.open()?, StoreKey::generate(), true);
+    let id = BlockId::from(Uuid::new_v4());
+
+    store.write_block(BlockKey::Data(id), &buffer)?;
+
+    let inner_ids = inner.open()?.list_blocks(BlockType::Data)?;
+    assert_that!(inner_ids).has_length(1);
+    assert_that!(inner_ids).does_not_contain(&id);
+    assert_that!(store.list_blocks(BlockType::Data)?).is_equal_to(vec![id]);
+    assert_that!(store.read_block(BlockKey::Data(id))?).is_equal_to(Some(buffer));
+
+    store.remove_block(BlockKey::Data(id))?;
+    assert_that!(inner.open()?.list_blocks(BlockType::Data)?).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn wrong_key_fails_to_read(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    EncryptedStore::new(inner.open()?, StoreKey::generate(), false)
+        .write_block(BlockKey::Super, &buffer)?;
+
+    let mut store = EncryptedStore::new(inner.open()?, StoreKey::generate(), false);
+
+    assert_that!(store.read_block(BlockKey::Super).is_err()).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn swapped_blocks_fail_to_read(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let key = StoreKey::generate();
+    let mut store = EncryptedStore::new(inner.open()?, key.clone(), false);
+    let first = BlockId::from(Uuid::new_v4());
+    let second = BlockId::from(Uuid::new_v4());
+    store.write_block(BlockKey::Data(first), &buffer)?;
+
+    let mut inner_store = inner.open()?;
+    let ciphertext = inner_store.read_block(BlockKey::Data(first))?.unwrap();
+    inner_store.write_block(BlockKey::Data(second), &ciphertext)?;
+
+    let mut store = EncryptedStore::new(inner.open()?, key, false);
+    assert_that!(store.read_block(BlockKey::Data(second)).is_err()).is_true();
+
+    Ok(())
+}
@@ -0,0 +1,306 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions, RepoConfig};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, Consistency, DataStore, MemoryConfig, MemoryStore, OpenStore,
+};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The grace period used by repositories in these tests.
+const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// How long it takes for a block to become visible to `list_blocks` in these tests.
+const VISIBILITY_DELAY: Duration = Duration::from_secs(60);
+
+/// The shared state of an `EventualStore`.
+#[derive(Debug)]
+struct EventualState {
+    /// The time each block in the store was written.
+    written: HashMap<BlockKey, SystemTime>,
+
+    /// The keys of blocks which were removed and their age when they were removed.
+    removed: Vec<(BlockKey, Option<Duration>)>,
+
+    /// Whether the store reports the modification times of blocks.
+    report_times: bool,
+}
+
+/// The configuration for opening an `EventualStore`.
+#[derive(Debug, Clone)]
+struct EventualConfig {
+    inner: MemoryConfig,
+    state: Arc<Mutex<EventualState>>,
+}
+
+impl EventualConfig {
+    fn new(report_times: bool) -> Self {
+        Self {
+            inner: MemoryConfig::new(),
+            state: Arc::new(Mutex::new(EventualState {
+                written: HashMap::new(),
+                removed: Vec::new(),
+                report_times,
+            })),
+        }
+    }
+
+    /// Simulate the passage of time by making every block in the store older by `duration`.
+    fn age_blocks(&self, duration: Duration) {
+        for time in self.state.lock().unwrap().written.values_mut() {
+            *time -= duration;
+        }
+    }
+
+    /// Return the data and header blocks which were removed and their ages.
+    fn removed_blocks(&self) -> Vec<(BlockKey, Option<Duration>)> {
+        self.state
+            .lock()
+            .unwrap()
+            .removed
+            .iter()
+            .copied()
+            .filter(|(key, _)| matches!(key, BlockKey::Data(_) | BlockKey::Header(_)))
+            .collect()
+    }
+}
+
+impl OpenStore for EventualConfig {
+    type Store = EventualStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(EventualStore {
+            inner: self.inner.open()?,
+            state: Arc::clone(&self.state),
+        })
+    }
+}
+
+/// A data store which simulates a backend with eventually consistent listing.
+///
+/// Blocks are not listed until `VISIBILITY_DELAY` after they are written.
+#[derive(Debug)]
+struct EventualStore {
+    inner: MemoryStore,
+    state: Arc<Mutex<EventualState>>,
+}
+
+impl DataStore for EventualStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.inner.write_block(key, data)?;
+        let mut state = self.state.lock().unwrap();
+        state.written.insert(key, SystemTime::now());
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.inner.remove_block(key)?;
+        let mut state = self.state.lock().unwrap();
+        let age = state
+            .written
+            .remove(&key)
+            .and_then(|time| time.elapsed().ok());
+        state.removed.push((key, age));
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        let state = self.state.lock().unwrap();
+        let is_visible = |key: BlockKey| match state.written.get(&key) {
+            Some(time) => time.elapsed().map_or(false, |age| age >= VISIBILITY_DELAY),
+            None => true,
+        };
+        Ok(self
+            .inner
+            .list_blocks(kind)?
+            .into_iter()
+            .filter(|&id| {
+                is_visible(match kind {
+                    BlockType::Data => BlockKey::Data(id),
+                    BlockType::Lock => BlockKey::Lock(id),
+                    BlockType::Header => BlockKey::Header(id),
+                })
+            })
+            .collect())
+    }
+
+    fn consistency(&self) -> Consistency {
+        Consistency::Eventual
+    }
+
+    fn block_modified_time(
+        &mut self,
+        key: BlockKey,
+    ) -> acid_store::store::Result<Option<SystemTime>> {
+        let state = self.state.lock().unwrap();
+        if state.report_times {
+            Ok(state.written.get(&key).copied())
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Create a repository in the given store, write an object to it, and then remove it.
+///
+/// This leaves the data which was written to the object unreferenced.
+fn write_unreferenced_data(
+    store_config: &EventualConfig,
+    repo_config: RepoConfig,
+    buffer: &[u8],
+) -> anyhow::Result<KeyRepo<String>> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(repo_config)
+        .gc_grace_period(GRACE_PERIOD)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(store_config)?;
+
+    let mut object = repo.insert(String::from("unreferenced"));
+    object.write_all(buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    repo.remove("unreferenced");
+    repo.commit()?;
+
+    Ok(repo)
+}
+
+/// Assert that none of the `removed` blocks were younger than the grace period.
+fn assert_no_fresh_blocks_removed(removed: &[(BlockKey, Option<Duration>)]) {
+    for (key, age) in removed {
+        assert!(
+            matches!(age, Some(age) if *age >= GRACE_PERIOD),
+            "{:?} was removed before the grace period passed.",
+            key
+        );
+    }
+}
+
+#[apply(config)]
+fn clean_does_not_remove_fresh_blocks(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = EventualConfig::new(true);
+    let mut repo = write_unreferenced_data(&store_config, config, &buffer)?;
+
+    // Make every block visible to `list_blocks` without making any old enough to be removed.
+    store_config.age_blocks(VISIBILITY_DELAY);
+    repo.clean()?;
+
+    assert_that!(store_config.removed_blocks()).has_length(0);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn clean_removes_blocks_after_grace_period(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = EventualConfig::new(true);
+    let mut repo = write_unreferenced_data(&store_config, config, &buffer)?;
+
+    store_config.age_blocks(GRACE_PERIOD);
+    repo.clean()?;
+
+    let removed = store_config.removed_blocks();
+    assert_that!(removed
+        .iter()
+        .any(|(key, _)| matches!(key, BlockKey::Data(_))))
+    .is_true();
+    assert_no_fresh_blocks_removed(&removed);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn clean_does_not_remove_blocks_written_after_grace_period(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = EventualConfig::new(true);
+    let mut repo = write_unreferenced_data(&store_config, config, &buffer)?;
+    store_config.age_blocks(GRACE_PERIOD);
+
+    // Write more unreferenced data which is still within the grace period.
+    let mut object = repo.insert(String::from("fresh"));
+    object.write_all(&larger_buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    repo.remove("fresh");
+    repo.commit()?;
+
+    store_config.age_blocks(VISIBILITY_DELAY);
+    repo.clean()?;
+
+    assert_no_fresh_blocks_removed(&store_config.removed_blocks());
+
+    Ok(())
+}
+
+#[apply(config)]
+fn clean_does_not_remove_blocks_with_unknown_modified_time(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = EventualConfig::new(false);
+    let mut repo = write_unreferenced_data(&store_config, config, &buffer)?;
+
+    store_config.age_blocks(GRACE_PERIOD);
+    repo.clean()?;
+
+    assert_that!(store_config.removed_blocks()).has_length(0);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn clean_preserves_referenced_data_with_eventual_consistency(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = EventualConfig::new(true);
+    let mut repo = write_unreferenced_data(&store_config, config, &larger_buffer)?;
+
+    let mut object = repo.insert(String::from("referenced"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    store_config.age_blocks(GRACE_PERIOD);
+    repo.clean()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(&store_config)?;
+    let mut actual_data = Vec::new();
+    repo.object("referenced")
+        .unwrap()
+        .read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
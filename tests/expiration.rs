@@ -0,0 +1,138 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, DestructivePolicy};
+use common::*;
+use uuid::Uuid;
+
+mod common;
+
+#[rstest]
+fn expired_objects_are_removed(mut repo: KeyRepo<String>) {
+    repo.insert_with_ttl("expired".into(), Duration::ZERO);
+    repo.insert_with_ttl("live".into(), Duration::from_secs(3600));
+    repo.insert("permanent".into());
+
+    assert_that!(repo.expire()).is_equal_to(1);
+
+    assert_that!(repo.contains("expired")).is_false();
+    assert_that!(repo.contains("live")).is_true();
+    assert_that!(repo.contains("permanent")).is_true();
+}
+
+#[rstest]
+fn expired_objects_are_accessible_until_expired(mut repo: KeyRepo<String>) {
+    repo.insert_with_ttl("test".into(), Duration::ZERO);
+
+    assert_that!(repo.object("test")).is_some();
+}
+
+#[rstest]
+fn expiration_time_is_reported(mut repo: KeyRepo<String>) {
+    let before = SystemTime::now();
+    repo.insert_with_ttl("test".into(), Duration::from_secs(60));
+    repo.insert("permanent".into());
+
+    let expires = repo.expires_at("test").unwrap();
+
+    assert_that!(expires).is_greater_than_or_equal_to(before + Duration::from_secs(60));
+    assert_that!(repo.expires_at("permanent")).is_none();
+    assert_that!(repo.expires_at("missing")).is_none();
+}
+
+#[rstest]
+fn huge_ttl_never_expires(mut repo: KeyRepo<String>) {
+    repo.insert_with_ttl("test".into(), Duration::MAX);
+
+    assert_that!(repo.expires_at("test")).is_none();
+    assert_that!(repo.expire()).is_equal_to(0);
+    assert_that!(repo.contains("test")).is_true();
+}
+
+#[rstest]
+fn replacing_object_clears_expiration(mut repo: KeyRepo<String>) {
+    repo.insert_with_ttl("test".into(), Duration::ZERO);
+
+    repo.insert("test".into());
+
+    assert_that!(repo.expires_at("test")).is_none();
+    assert_that!(repo.expire()).is_equal_to(0);
+    assert_that!(repo.contains("test")).is_true();
+}
+
+#[rstest]
+fn removing_object_clears_expiration(mut repo: KeyRepo<String>) {
+    repo.insert_with_ttl("test".into(), Duration::ZERO);
+    repo.remove("test");
+
+    assert_that!(repo.expires_at("test")).is_none();
+    assert_that!(repo.expire()).is_equal_to(0);
+}
+
+#[rstest]
+fn expired_objects_are_removed_on_commit(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert_with_ttl("expired".into(), Duration::ZERO);
+    repo.insert_with_ttl("live".into(), Duration::from_secs(3600));
+    repo.commit()?;
+
+    assert_that!(repo.contains("expired")).is_false();
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(repo.contains("expired")).is_false();
+    assert_that!(repo.expires_at("live")).is_some();
+
+    Ok(())
+}
+
+#[rstest]
+fn expiring_objects_is_not_destructive(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    let mut policy = DestructivePolicy::default();
+    policy.enabled = true;
+    policy.max_removed_objects = Some(0);
+    repo.set_destructive_policy(b"Password", policy)?;
+    repo.insert_with_ttl("test".into(), Duration::ZERO);
+
+    assert_that!(repo.commit()).is_ok();
+    assert_that!(repo.contains("test")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn expired_objects_are_kept_when_commit_fails(
+    mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config.max_header_size = Some(4096);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    for i in 0..200 {
+        let mut object = repo.insert(format!("object-{}", i));
+        object.write_all(Uuid::new_v4().as_bytes())?;
+        object.commit()?;
+    }
+    let mut object = repo.insert_with_ttl("expired".into(), Duration::ZERO);
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    assert_that!(repo.commit())
+        .is_err_variant(acid_store::Error::HeaderTooLarge { size: 0, limit: 0 });
+
+    let mut actual_data = Vec::new();
+    repo.object("expired")
+        .unwrap()
+        .read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(buffer);
+    assert_that!(repo.expires_at("expired")).is_some();
+    assert_that!(repo.verify()?).is_empty();
+
+    Ok(())
+}
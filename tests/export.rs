@@ -0,0 +1,315 @@
+#![cfg(all(feature = "export", feature = "encryption", feature = "compression"))]
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, ExportOptions, OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore,
+};
+use common::*;
+use serde_json::Value;
+#[cfg(feature = "repo-file")]
+use {
+    acid_store::repo::file::{Entry, FileRepo, RelativePath},
+    tar::EntryType,
+};
+
+mod common;
+
+/// The size of the object used to test that exports are streamed.
+const LARGE_OBJECT_SIZE: usize = 256 * 1024;
+
+/// An event which is recorded while exporting a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    /// A data block was read from the data store.
+    DataRead,
+
+    /// Bytes were written to the archive.
+    ArchiveWrite,
+}
+
+/// A data store config which records when data blocks are read.
+#[derive(Debug, Clone)]
+struct RecordingConfig {
+    inner: MemoryConfig,
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl OpenStore for RecordingConfig {
+    type Store = RecordingStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(RecordingStore {
+            inner: self.inner.open()?,
+            events: Arc::clone(&self.events),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct RecordingStore {
+    inner: MemoryStore,
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl DataStore for RecordingStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        if let BlockKey::Data(_) = key {
+            self.events.lock().unwrap().push(Event::DataRead);
+        }
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+}
+
+/// An archive destination which records when bytes are written to it.
+struct RecordingWriter {
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl Write for RecordingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.events.lock().unwrap().push(Event::ArchiveWrite);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Return the path in the archive for an object with the given `key`.
+fn key_to_path(key: &str) -> PathBuf {
+    PathBuf::from("objects").join(key)
+}
+
+/// Read the files in the given tar `archive` into a map of paths to their contents.
+fn read_archive(archive: &[u8]) -> anyhow::Result<HashMap<PathBuf, Vec<u8>>> {
+    let mut files = HashMap::new();
+    for entry in tar::Archive::new(archive).entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        files.insert(path, contents);
+    }
+    Ok(files)
+}
+
+/// Parse the manifest in the given archive `files` into a map of paths to their entries.
+fn read_manifest(files: &HashMap<PathBuf, Vec<u8>>) -> anyhow::Result<HashMap<String, Value>> {
+    let manifest: Vec<Value> = serde_json::from_slice(&files[&PathBuf::from("MANIFEST.json")])?;
+    Ok(manifest
+        .into_iter()
+        .map(|entry| (entry["path"].as_str().unwrap().to_string(), entry))
+        .collect())
+}
+
+#[rstest]
+fn export_encrypted_repo_is_readable(
+    #[from(buffer)] first: Vec<u8>,
+    #[from(buffer)] second: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(encoding_config())?;
+    write_object(&mut repo, "first", &first)?;
+    write_object(&mut repo, "second", &second)?;
+    repo.insert(String::from("empty"));
+    repo.commit()?;
+
+    let archive = repo.export_plaintext(
+        Vec::new(),
+        |key| key_to_path(key),
+        &mut ExportOptions::new(),
+    )?;
+    let files = read_archive(&archive)?;
+
+    assert_that!(files.len()).is_equal_to(4);
+    assert_that!(files[&PathBuf::from("objects/first")]).is_equal_to(&first);
+    assert_that!(files[&PathBuf::from("objects/second")]).is_equal_to(&second);
+    assert_that!(files[&PathBuf::from("objects/empty")]).is_empty();
+
+    let manifest = read_manifest(&files)?;
+    let entry = &manifest["objects/first"];
+    assert_that!(entry["key"].as_str()).is_equal_to(Some("first"));
+    assert_that!(entry["size"].as_u64()).is_equal_to(Some(first.len() as u64));
+    assert_that!(entry["blake3"].as_str().map(String::from))
+        .is_equal_to(Some(blake3::hash(&first).to_hex().to_string()));
+
+    Ok(())
+}
+
+#[rstest]
+fn export_filter_excludes_objects(
+    mut repo: KeyRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    write_object(&mut repo, "keep", &buffer)?;
+    write_object(&mut repo, "skip", &buffer)?;
+
+    let mut options = ExportOptions::new();
+    options.filter(|key: &String| key != "skip");
+    let archive = repo.export_plaintext(Vec::new(), |key| key_to_path(key), &mut options)?;
+    let files = read_archive(&archive)?;
+
+    assert_that!(files.contains_key(&PathBuf::from("objects/keep"))).is_true();
+    assert_that!(files.contains_key(&PathBuf::from("objects/skip"))).is_false();
+    assert_that!(read_manifest(&files)?.len()).is_equal_to(1);
+
+    Ok(())
+}
+
+#[rstest]
+fn export_without_manifest(mut repo: KeyRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    write_object(&mut repo, "object", &buffer)?;
+
+    let mut options = ExportOptions::new();
+    options.no_manifest();
+    let archive = repo.export_plaintext(Vec::new(), |key| key_to_path(key), &mut options)?;
+    let files = read_archive(&archive)?;
+
+    assert_that!(files.keys().collect::<Vec<_>>())
+        .is_equal_to(vec![&PathBuf::from("objects/object")]);
+
+    Ok(())
+}
+
+#[rstest]
+fn export_reports_progress(mut repo: KeyRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    for i in 0..3 {
+        write_object(&mut repo, &i.to_string(), &buffer)?;
+    }
+
+    let mut reports = Vec::new();
+    let mut options = ExportOptions::new();
+    options.progress(|progress| {
+        reports.push(progress);
+        true
+    });
+    repo.export_plaintext(io::sink(), |key| key_to_path(key), &mut options)?;
+    drop(options);
+
+    assert_that!(reports.len()).is_equal_to(3);
+    let last = reports.last().unwrap();
+    assert_that!(last.entries).is_equal_to(3);
+    assert_that!(last.total_entries).is_equal_to(3);
+    assert_that!(last.bytes).is_equal_to(3 * buffer.len() as u64);
+
+    Ok(())
+}
+
+#[rstest]
+fn export_can_be_cancelled(mut repo: KeyRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    for i in 0..3 {
+        write_object(&mut repo, &i.to_string(), &buffer)?;
+    }
+
+    let mut options = ExportOptions::new();
+    options.progress(|progress| progress.entries < 2);
+
+    assert_that!(repo.export_plaintext(io::sink(), |key| key_to_path(key), &mut options))
+        .is_err_variant(acid_store::Error::Cancelled);
+
+    Ok(())
+}
+
+#[rstest]
+fn export_streams_large_objects(
+    #[with(LARGE_OBJECT_SIZE)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let store_config = RecordingConfig {
+        inner: MemoryConfig::new(),
+        events: Arc::clone(&events),
+    };
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(encoding_config())
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&store_config)?;
+    write_object(&mut repo, "large", &fixed_buffer)?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .open(&store_config)?;
+    events.lock().unwrap().clear();
+
+    let mut options = ExportOptions::new();
+    options.no_manifest();
+    repo.export_plaintext(
+        RecordingWriter {
+            events: Arc::clone(&events),
+        },
+        |key| key_to_path(key),
+        &mut options,
+    )?;
+
+    // If the object was read into memory before it was written to the archive, every read would
+    // come before the first write.
+    let events = events.lock().unwrap();
+    let last_read = events
+        .iter()
+        .rposition(|event| *event == Event::DataRead)
+        .unwrap();
+    let first_write = events
+        .iter()
+        .position(|event| *event == Event::ArchiveWrite)
+        .unwrap();
+    assert_that!(first_write).is_less_than(last_read);
+
+    Ok(())
+}
+
+#[cfg(feature = "repo-file")]
+#[rstest]
+fn export_file_repo_uses_paths(mut repo: FileRepo, buffer: Vec<u8>) -> anyhow::Result<()> {
+    repo.create("directory", &Entry::directory())?;
+    repo.create("directory/file", &Entry::file())?;
+    repo.create("skipped", &Entry::file())?;
+    let mut object = repo.open("directory/file")?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let mut options = ExportOptions::new();
+    options.filter(|path: &RelativePath| path.as_str() != "skipped");
+    let archive = repo.export_plaintext(Vec::new(), &mut options)?;
+
+    let mut entry_types = HashMap::new();
+    for entry in tar::Archive::new(archive.as_slice()).entries()? {
+        let entry = entry?;
+        entry_types.insert(entry.path()?.into_owned(), entry.header().entry_type());
+    }
+    assert_that!(entry_types.get(&PathBuf::from("directory")))
+        .is_equal_to(Some(&EntryType::Directory));
+    assert_that!(entry_types.get(&PathBuf::from("directory/file")))
+        .is_equal_to(Some(&EntryType::Regular));
+    assert_that!(entry_types.contains_key(&PathBuf::from("skipped"))).is_false();
+
+    let files = read_archive(&archive)?;
+    assert_that!(files[&PathBuf::from("directory/file")]).is_equal_to(&buffer);
+
+    let manifest = read_manifest(&files)?;
+    assert_that!(manifest["directory"]["attributes"]["kind"].as_str())
+        .is_equal_to(Some("Directory"));
+    assert_that!(manifest["directory/file"]["key"].as_str()).is_equal_to(Some("directory/file"));
+
+    Ok(())
+}
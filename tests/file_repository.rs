@@ -7,10 +7,13 @@ use std::iter::FromIterator;
 
 #[cfg(all(target_os = "linux", feature = "file-metadata"))]
 use exacl::{AclEntry, AclEntryKind, AclOption, Flag, Perm};
+use rand::rngs::SmallRng;
+use rand::{RngCore, SeedableRng};
 use relative_path::RelativePathBuf;
 use tempfile::TempDir;
 
 use acid_store::repo::file::{Entry, FileMode, FileRepo, WalkPredicate};
+use acid_store::repo::key::KeyRepo;
 use acid_store::repo::{Commit, SwitchInstance, DEFAULT_INSTANCE};
 
 use acid_store::uuid::Uuid;
@@ -1309,3 +1312,36 @@ fn verify_valid_repository_is_valid(mut repo: FileRepo) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[rstest]
+fn content_is_deduplicated_between_file_and_key_repos(
+    mut repo: FileRepo,
+    temp_dir: TempDir,
+) -> anyhow::Result<()> {
+    // Use content which spans many chunks.
+    const CONTENT_SIZE: usize = 16 * 1024 * 1024;
+
+    let mut content = vec![0u8; CONTENT_SIZE];
+    SmallRng::from_entropy().fill_bytes(&mut content);
+
+    let source_path = temp_dir.as_ref().join("source");
+    File::create(&source_path)?.write_all(&content)?;
+
+    repo.archive(&source_path, "file")?;
+    let file_repo_size = repo.stats().repo_size();
+
+    assert_that!(file_repo_size).is_greater_than_or_equal_to(CONTENT_SIZE as u64);
+
+    let mut repo: KeyRepo<String> = repo.switch_instance(Uuid::new_v4().into())?;
+    let mut object = repo.insert(String::from("object"));
+    object.write_all(&content)?;
+    object.commit()?;
+    drop(object);
+
+    // Storing the same content again should only add metadata.
+    let combined_repo_size = repo.stats().repo_size();
+
+    assert_that!(combined_repo_size - file_repo_size).is_less_than(CONTENT_SIZE as u64 / 100);
+
+    Ok(())
+}
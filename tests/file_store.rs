@@ -0,0 +1,107 @@
+#![cfg(feature = "store-file")]
+
+use std::fs::{metadata, write, OpenOptions};
+use std::path::PathBuf;
+
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, FileConfig, FileStore, OpenStore,
+};
+use common::*;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+mod common;
+
+/// Return the path of the archive file in `temp_dir`.
+fn store_path(temp_dir: &TempDir) -> PathBuf {
+    temp_dir.path().join("store.acid")
+}
+
+#[rstest]
+fn open_junk_file_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = store_path(&temp_dir);
+    write(&path, b"junk")?;
+
+    assert_that!(FileStore::open(&path)).is_err_variant(acid_store::Error::InvalidStore);
+
+    Ok(())
+}
+
+#[rstest]
+fn open_store_with_incompatible_version_errs(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = store_path(&temp_dir);
+    write(&path, [0u8; 16])?;
+
+    assert_that!(FileStore::open(&path)).is_err_variant(acid_store::Error::UnsupportedStore);
+
+    Ok(())
+}
+
+#[rstest]
+fn blocks_persist_after_reopening(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let config = FileConfig::new(store_path(&temp_dir));
+    let id = BlockId::from(Uuid::new_v4());
+
+    let mut store = config.open()?;
+    store.write_block(BlockKey::Data(id), &buffer)?;
+    store.write_block(BlockKey::Super, b"super")?;
+    drop(store);
+
+    let mut store = config.open()?;
+    assert_that!(store.read_block(BlockKey::Data(id))?).is_equal_to(Some(buffer));
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(b"super".to_vec()));
+    assert_that!(store.list_blocks(BlockType::Data)?).is_equal_to(vec![id]);
+
+    Ok(())
+}
+
+#[rstest]
+fn store_recovers_from_partial_write(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let path = store_path(&temp_dir);
+    let first_id = BlockId::from(Uuid::new_v4());
+    let second_id = BlockId::from(Uuid::new_v4());
+
+    let mut store = FileStore::open(&path)?;
+    store.write_block(BlockKey::Data(first_id), &buffer)?;
+    let len_after_first = metadata(&path)?.len();
+    store.write_block(BlockKey::Data(second_id), &buffer)?;
+    let len_after_second = metadata(&path)?.len();
+
+    // Simulate the process being interrupted while writing the second block.
+    std::mem::forget(store);
+    OpenOptions::new()
+        .write(true)
+        .open(&path)?
+        .set_len(len_after_second - 1)?;
+
+    let mut store = FileStore::open(&path)?;
+    assert_that!(store.read_block(BlockKey::Data(first_id))?).is_equal_to(Some(buffer));
+    assert_that!(store.read_block(BlockKey::Data(second_id))?).is_none();
+    assert_that!(metadata(&path)?.len()).is_equal_to(len_after_first);
+
+    Ok(())
+}
+
+#[rstest]
+fn compact_reclaims_space(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let path = store_path(&temp_dir);
+    let kept_id = BlockId::from(Uuid::new_v4());
+    let removed_id = BlockId::from(Uuid::new_v4());
+
+    let mut store = FileStore::open(&path)?;
+    store.write_block(BlockKey::Data(kept_id), &buffer)?;
+    store.write_block(BlockKey::Data(removed_id), &buffer)?;
+    store.remove_block(BlockKey::Data(removed_id))?;
+    let len_before = metadata(&path)?.len();
+
+    assert_that!(store.compact()?).is_greater_than(0);
+    assert_that!(metadata(&path)?.len()).is_less_than(len_before);
+    assert_that!(store.read_block(BlockKey::Data(kept_id))?).is_equal_to(Some(buffer.clone()));
+    drop(store);
+
+    let mut store = FileStore::open(&path)?;
+    assert_that!(store.read_block(BlockKey::Data(kept_id))?).is_equal_to(Some(buffer));
+    assert_that!(store.read_block(BlockKey::Data(removed_id))?).is_none();
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+#![cfg(all(feature = "repo-fork", feature = "encryption", feature = "compression"))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::fork::ForkRepo;
+use acid_store::repo::Commit;
+use common::*;
+
+mod common;
+
+/// Replace the contents of the fork `name` of the given `key` with `data`.
+fn write_fork(
+    repo: &mut ForkRepo<String>,
+    key: &str,
+    name: &str,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    let mut object = repo.insert(key.to_string(), name);
+    object.write_all(data)?;
+    object.commit()?;
+    Ok(())
+}
+
+/// Read the contents of the fork `name` of the given `key`.
+fn read_fork(repo: &ForkRepo<String>, key: &str, name: &str) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    repo.object(key, name).unwrap().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[rstest]
+fn forks_are_independent(
+    mut repo: ForkRepo<String>,
+    smaller_buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    write_fork(&mut repo, "image", "data", &larger_buffer)?;
+    write_fork(&mut repo, "image", "thumbnail", &smaller_buffer)?;
+
+    assert_that!(read_fork(&repo, "image", "data")?).is_equal_to(&larger_buffer);
+    assert_that!(read_fork(&repo, "image", "thumbnail")?).is_equal_to(&smaller_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn forks_are_listed(mut repo: ForkRepo<String>) -> anyhow::Result<()> {
+    write_fork(&mut repo, "image", "data", b"data")?;
+    write_fork(&mut repo, "image", "thumbnail", b"thumbnail")?;
+
+    let forks = repo.forks("image").unwrap().collect::<HashSet<_>>();
+
+    assert_that!(forks).is_equal_to(HashSet::from(["data", "thumbnail"]));
+    assert_that!(repo.forks("missing")).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn inserting_fork_replaces_only_that_fork(
+    mut repo: ForkRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    write_fork(&mut repo, "image", "data", &buffer)?;
+    write_fork(&mut repo, "image", "thumbnail", b"old")?;
+
+    write_fork(&mut repo, "image", "thumbnail", b"new")?;
+
+    assert_that!(read_fork(&repo, "image", "data")?).is_equal_to(&buffer);
+    assert_that!(read_fork(&repo, "image", "thumbnail")?).is_equal_to(b"new".to_vec());
+
+    Ok(())
+}
+
+#[rstest]
+fn removing_last_fork_removes_key(mut repo: ForkRepo<String>) -> anyhow::Result<()> {
+    write_fork(&mut repo, "image", "data", b"data")?;
+    write_fork(&mut repo, "image", "thumbnail", b"thumbnail")?;
+
+    assert_that!(repo.remove_fork("image", "thumbnail")).is_true();
+    assert_that!(repo.contains_fork("image", "thumbnail")).is_false();
+    assert_that!(repo.contains("image")).is_true();
+
+    assert_that!(repo.remove_fork("image", "data")).is_true();
+    assert_that!(repo.contains("image")).is_false();
+    assert_that!(repo.remove_fork("image", "data")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn removing_key_removes_all_forks(mut repo: ForkRepo<String>) -> anyhow::Result<()> {
+    write_fork(&mut repo, "image", "data", b"data")?;
+    write_fork(&mut repo, "image", "thumbnail", b"thumbnail")?;
+
+    assert_that!(repo.remove("image")).is_true();
+
+    assert_that!(repo.contains("image")).is_false();
+    assert_that!(repo.object("image", "data")).is_none();
+    assert_that!(repo.keys().count()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn copy_copies_all_forks(mut repo: ForkRepo<String>) -> anyhow::Result<()> {
+    write_fork(&mut repo, "source", "data", b"data")?;
+    write_fork(&mut repo, "source", "thumbnail", b"thumbnail")?;
+    write_fork(&mut repo, "dest", "other", b"other")?;
+
+    assert_that!(repo.copy("source", "dest".into())).is_true();
+
+    assert_that!(read_fork(&repo, "dest", "data")?).is_equal_to(b"data".to_vec());
+    assert_that!(read_fork(&repo, "dest", "thumbnail")?).is_equal_to(b"thumbnail".to_vec());
+    assert_that!(repo.contains_fork("dest", "other")).is_false();
+    assert_that!(repo.copy("missing", "dest".into())).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn forks_persist_after_commit(repo_store: RepoStore, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: ForkRepo<String> = repo_store.create()?;
+    write_fork(&mut repo, "image", "data", &buffer)?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: ForkRepo<String> = repo_store.open()?;
+
+    assert_that!(read_fork(&repo, "image", "data")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn forks_removed_on_rollback(mut repo: ForkRepo<String>) -> anyhow::Result<()> {
+    write_fork(&mut repo, "image", "data", b"data")?;
+    repo.commit()?;
+    write_fork(&mut repo, "image", "thumbnail", b"thumbnail")?;
+
+    repo.rollback()?;
+
+    assert_that!(repo.contains_fork("image", "data")).is_true();
+    assert_that!(repo.contains_fork("image", "thumbnail")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn verify_valid_repository_is_valid(mut repo: ForkRepo<String>) -> anyhow::Result<()> {
+    write_fork(&mut repo, "image", "data", b"data")?;
+    write_fork(&mut repo, "image", "thumbnail", b"thumbnail")?;
+
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+#![cfg(all(
+    unix,
+    feature = "encryption",
+    feature = "compression",
+    feature = "fuse-mount"
+))]
+
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use acid_store::repo::file::{Entry, FileRepo, UnixMetadata, UnixSpecial};
+use acid_store::repo::Commit;
+use common::*;
+use tempfile::TempDir;
+
+mod common;
+
+/// The maximum amount of time to wait for the file system to be mounted.
+const MOUNT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait until a file system other than the one containing its parent is mounted at `mountpoint`.
+fn wait_for_mount(mountpoint: &Path) -> anyhow::Result<()> {
+    let parent_device = fs::metadata(mountpoint.parent().unwrap())?.dev();
+    let start = Instant::now();
+    while fs::metadata(mountpoint)?.dev() == parent_device {
+        if start.elapsed() > MOUNT_TIMEOUT {
+            anyhow::bail!("The file system was not mounted in time.");
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    Ok(())
+}
+
+#[rstest]
+fn buffered_writes_are_committed_on_unmount(
+    repo_store: RepoStore,
+    temp_dir: TempDir,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: FileRepo<UnixSpecial, UnixMetadata> = repo_store.create()?;
+    repo.create("root", &Entry::directory())?;
+    repo.commit()?;
+
+    let mountpoint = temp_dir.path().join("mnt");
+    fs::create_dir(&mountpoint)?;
+    let mount_path = mountpoint.clone();
+    let session = thread::spawn(move || repo.mount(&mount_path, "root", &[]));
+    wait_for_mount(&mountpoint)?;
+
+    fs::write(mountpoint.join("file"), &buffer)?;
+
+    let status = Command::new("fusermount")
+        .arg("-u")
+        .arg(&mountpoint)
+        .status()?;
+    assert_that!(status.success()).is_true();
+    session.join().unwrap()?;
+
+    let repo: FileRepo<UnixSpecial, UnixMetadata> = repo_store.open()?;
+    let mut actual = Vec::new();
+    repo.open("root/file")?.read_to_end(&mut actual)?;
+
+    assert_that!(actual).is_equal_to(buffer);
+
+    Ok(())
+}
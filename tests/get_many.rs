@@ -0,0 +1,130 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Seek, SeekFrom, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Chunking, Commit, OpenMode, OpenOptions, RepoConfig};
+use common::*;
+
+mod common;
+
+/// The number of objects which share the same contents.
+const NUM_COPIES: usize = 20;
+
+fn open_repo(
+    store_config: &CountingConfig,
+    repo_config: RepoConfig,
+    mode: OpenMode,
+) -> acid_store::Result<KeyRepo<String>> {
+    OpenOptions::new()
+        .config(repo_config)
+        .password(b"Password")
+        .mode(mode)
+        .open(store_config)
+}
+
+#[rstest]
+fn get_many_returns_contents_in_order(
+    mut repo: KeyRepo<String>,
+    #[from(buffer)] first: Vec<u8>,
+    #[from(buffer)] second: Vec<u8>,
+) -> anyhow::Result<()> {
+    write_object(&mut repo, "first", &first)?;
+    write_object(&mut repo, "second", &second)?;
+    repo.insert(String::from("empty"));
+
+    let contents = repo.get_many(&["second", "missing", "first", "empty", "second"])?;
+
+    assert_that!(contents).is_equal_to(vec![
+        Some(second.clone()),
+        None,
+        Some(first),
+        Some(Vec::new()),
+        Some(second),
+    ]);
+
+    Ok(())
+}
+
+#[rstest]
+fn get_many_with_no_keys_returns_nothing(repo: KeyRepo<String>) -> anyhow::Result<()> {
+    let keys: [&str; 0] = [];
+    assert_that!(repo.get_many(&keys)?).is_empty();
+    Ok(())
+}
+
+#[rstest]
+fn get_many_includes_holes(mut repo: KeyRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("sparse"));
+    object.set_len(buffer.len() as u64)?;
+    object.seek(SeekFrom::End(0))?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let expected = read_object(&repo, "sparse")?;
+    let contents = repo.get_many(&["sparse"])?;
+
+    assert_that!(contents).is_equal_to(vec![Some(expected)]);
+
+    Ok(())
+}
+
+#[rstest]
+#[case(fixed_config())]
+#[case(encoding_config())]
+#[case(fixed_packing_small_config())]
+#[case(fixed_packing_large_config())]
+fn get_many_matches_individual_reads(
+    #[case] config: RepoConfig,
+    #[from(buffer)] first: Vec<u8>,
+    #[from(buffer)] second: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+    let keys = (0..NUM_COPIES).map(|i| i.to_string()).collect::<Vec<_>>();
+    for (i, key) in keys.iter().enumerate() {
+        let data = if i % 2 == 0 { &first } else { &second };
+        write_object(&mut repo, key, data)?;
+    }
+
+    let key_refs = keys.iter().map(String::as_str).collect::<Vec<_>>();
+    let contents = repo.get_many(&key_refs)?;
+
+    for (key, data) in keys.iter().zip(contents) {
+        assert_that!(data).is_equal_to(Some(read_object(&repo, key)?));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn get_many_reads_each_chunk_once() -> anyhow::Result<()> {
+    let store_config = CountingConfig::new();
+    let mut repo_config = fixed_config();
+    repo_config.chunking = Chunking::Fixed { size: 1024 };
+
+    // Each object is smaller than the chunk size, so each distinct object is a single chunk.
+    let first = b"first".repeat(10);
+    let second = b"second".repeat(10);
+
+    let mut repo = open_repo(&store_config, repo_config.clone(), OpenMode::CreateNew)?;
+    let keys = (0..NUM_COPIES).map(|i| i.to_string()).collect::<Vec<_>>();
+    for (i, key) in keys.iter().enumerate() {
+        let data = if i % 2 == 0 { &first } else { &second };
+        write_object(&mut repo, key, data)?;
+    }
+    repo.commit()?;
+    drop(repo);
+
+    let repo = open_repo(&store_config, repo_config, OpenMode::Open)?;
+    store_config.take_data_reads();
+
+    let key_refs = keys.iter().map(String::as_str).collect::<Vec<_>>();
+    let contents = repo.get_many(&key_refs)?;
+
+    // There are only two distinct chunks, no matter how many objects were requested.
+    assert_that!(store_config.take_data_reads()).is_equal_to(2);
+    assert_that!(contents.iter().flatten().count()).is_equal_to(NUM_COPIES);
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+#![cfg(all(
+    feature = "expensive-tests",
+    feature = "store-directory",
+    feature = "encryption",
+    feature = "compression"
+))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Chunking, Commit, Compression, OpenMode, OpenOptions, RepoConfig};
+use acid_store::store::DirectoryConfig;
+use serial_test::serial;
+use tempfile::{tempdir, TempDir};
+
+use common::*;
+
+mod common;
+
+/// The size of the chunk used for testing, which is larger than the streaming threshold.
+const HUGE_CHUNK_SIZE: usize = 200 * 1024 * 1024;
+
+/// The maximum number of additional bytes which may be allocated while streaming a huge chunk.
+const STREAMING_MEMORY_LIMIT: usize = 40 * 1024 * 1024;
+
+/// A global allocator which tracks the peak number of bytes allocated at once.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK_ALLOCATED.fetch_max(allocated, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Return the number of bytes allocated at the peak since `f` started, beyond what was allocated
+/// before it was called.
+fn peak_allocation<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let baseline = ALLOCATED.load(Ordering::SeqCst);
+    PEAK_ALLOCATED.store(baseline, Ordering::SeqCst);
+    let result = f();
+    let peak = PEAK_ALLOCATED.load(Ordering::SeqCst);
+    (result, peak.saturating_sub(baseline))
+}
+
+/// Return the contents of the huge object used for testing.
+fn huge_data() -> Vec<u8> {
+    (0..HUGE_CHUNK_SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+/// Return a repository config which stores `HUGE_CHUNK_SIZE` bytes in a single chunk.
+fn huge_chunk_config(mut config: RepoConfig) -> RepoConfig {
+    config.chunking = Chunking::Fixed {
+        size: HUGE_CHUNK_SIZE as u32,
+    };
+    config
+}
+
+/// Return a repository in a directory store containing a single huge object at "huge".
+///
+/// This also returns the temporary directory containing the store, which must outlive the repo.
+fn huge_repo(config: RepoConfig) -> anyhow::Result<(TempDir, KeyRepo<String>)> {
+    let directory = tempdir()?;
+    let store_config = DirectoryConfig::new(directory.path().join("store"));
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(huge_chunk_config(config))
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&store_config)?;
+    let mut object = repo.insert(String::from("huge"));
+    object.write_all(&huge_data())?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    Ok((directory, repo))
+}
+
+/// Return a config with compression but no encryption.
+fn compression_config() -> RepoConfig {
+    let mut config = fixed_config();
+    config.compression = Compression::Lz4 { level: 1 };
+    config
+}
+
+#[rstest]
+#[case(fixed_config())]
+#[case(compression_config())]
+#[serial(huge_chunks)]
+fn verifying_huge_chunk_has_bounded_memory(#[case] config: RepoConfig) -> anyhow::Result<()> {
+    let (_directory, repo) = huge_repo(config)?;
+
+    let (corrupt_keys, peak) = peak_allocation(|| repo.verify());
+
+    assert_that!(corrupt_keys?).is_empty();
+    assert_that!(peak).is_less_than(STREAMING_MEMORY_LIMIT);
+
+    Ok(())
+}
+
+#[rstest]
+#[serial(huge_chunks)]
+fn reading_huge_chunk_partially_has_bounded_memory() -> anyhow::Result<()> {
+    let (_directory, repo) = huge_repo(fixed_config())?;
+    let expected_hash = blake3::hash(&huge_data());
+
+    let mut object = repo.object("huge").unwrap();
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+
+    let (result, peak) = peak_allocation(|| -> anyhow::Result<()> {
+        loop {
+            let bytes_read = object.read(&mut buffer)?;
+            if bytes_read == 0 {
+                return Ok(());
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+    });
+    result?;
+
+    assert_that!(hasher.finalize()).is_equal_to(expected_hash);
+    assert_that!(peak).is_less_than(STREAMING_MEMORY_LIMIT);
+
+    Ok(())
+}
+
+#[rstest]
+#[case(compression_config())]
+#[case(encoding_config())]
+#[serial(huge_chunks)]
+fn reading_huge_encoded_chunk_is_correct(#[case] config: RepoConfig) -> anyhow::Result<()> {
+    let (_directory, repo) = huge_repo(config)?;
+
+    let mut object = repo.object("huge").unwrap();
+    // Leave room at the end so that `read_to_end` never has to grow the buffer.
+    let mut data = Vec::with_capacity(HUGE_CHUNK_SIZE + 1024 * 1024);
+
+    let (result, peak) = peak_allocation(|| object.read_to_end(&mut data));
+    result?;
+
+    assert_that!(data == huge_data()).is_true();
+
+    // Decoding the chunk never holds more than two copies of it in memory at once.
+    assert_that!(peak).is_less_than(2 * HUGE_CHUNK_SIZE + STREAMING_MEMORY_LIMIT);
+
+    Ok(())
+}
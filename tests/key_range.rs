@@ -0,0 +1,123 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, RestoreSavepoint};
+use common::*;
+
+mod common;
+
+/// Insert an empty object for each of the given `keys`.
+fn insert_keys(repo: &mut KeyRepo<String>, keys: &[&str]) {
+    for key in keys {
+        repo.insert(key.to_string());
+    }
+}
+
+#[rstest]
+fn range_returns_sorted_keys(mut repo: KeyRepo<String>) {
+    insert_keys(&mut repo, &["d", "a", "c", "e", "b"]);
+
+    let keys = repo
+        .range(String::from("b")..String::from("e"))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    assert_that!(keys).is_equal_to(vec!["b".to_string(), "c".into(), "d".into()]);
+}
+
+#[rstest]
+fn unbounded_range_returns_all_keys(mut repo: KeyRepo<String>) {
+    insert_keys(&mut repo, &["c", "a", "b"]);
+
+    let keys = repo.range::<String, _>(..).cloned().collect::<Vec<_>>();
+
+    assert_that!(keys).is_equal_to(vec!["a".to_string(), "b".into(), "c".into()]);
+}
+
+#[rstest]
+fn keys_with_prefix_lists_directory(mut repo: KeyRepo<String>) {
+    insert_keys(
+        &mut repo,
+        &[
+            "docs/b.txt",
+            "docs/a.txt",
+            "doc",
+            "images/c.png",
+            "docs2/d.txt",
+        ],
+    );
+
+    let keys = repo.keys_with_prefix("docs/").cloned().collect::<Vec<_>>();
+
+    assert_that!(keys).is_equal_to(vec!["docs/a.txt".to_string(), "docs/b.txt".into()]);
+}
+
+#[rstest]
+fn index_reflects_changes_after_query(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    insert_keys(&mut repo, &["a/1", "a/2"]);
+    assert_that!(repo.keys_with_prefix("a/").count()).is_equal_to(2);
+
+    repo.remove("a/1");
+    repo.insert("a/3".into());
+    repo.copy("a/2", "a/4".into());
+    repo.remove_to_trash("a/2")?;
+
+    let keys = repo.keys_with_prefix("a/").cloned().collect::<Vec<_>>();
+
+    assert_that!(keys).is_equal_to(vec!["a/3".to_string(), "a/4".into()]);
+
+    Ok(())
+}
+
+#[rstest]
+fn index_reflects_restored_trash(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    insert_keys(&mut repo, &["a", "b"]);
+    repo.remove_to_trash("a")?;
+    assert_that!(repo.range::<str, _>(..).count()).is_equal_to(1);
+
+    repo.restore_from_trash("a")?;
+
+    assert_that!(repo.range::<str, _>(..).count()).is_equal_to(2);
+
+    Ok(())
+}
+
+#[rstest]
+fn index_reflects_rollback(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    insert_keys(&mut repo, &["a"]);
+    repo.commit()?;
+    insert_keys(&mut repo, &["b"]);
+    assert_that!(repo.range::<str, _>(..).count()).is_equal_to(2);
+
+    repo.rollback()?;
+
+    let keys = repo.range::<str, _>(..).cloned().collect::<Vec<_>>();
+    assert_that!(keys).is_equal_to(vec!["a".to_string()]);
+
+    Ok(())
+}
+
+#[rstest]
+fn index_reflects_restored_savepoint(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    insert_keys(&mut repo, &["a"]);
+    let savepoint = repo.savepoint()?;
+    insert_keys(&mut repo, &["b"]);
+    assert_that!(repo.range::<str, _>(..).count()).is_equal_to(2);
+
+    repo.restore(&savepoint)?;
+
+    let keys = repo.range::<str, _>(..).cloned().collect::<Vec<_>>();
+    assert_that!(keys).is_equal_to(vec!["a".to_string()]);
+
+    Ok(())
+}
+
+#[rstest]
+fn index_reflects_cleared_instance(mut repo: KeyRepo<String>) {
+    insert_keys(&mut repo, &["a", "b"]);
+    assert_that!(repo.range::<str, _>(..).count()).is_equal_to(2);
+
+    repo.clear_instance();
+
+    assert_that!(repo.range::<str, _>(..).count()).is_equal_to(0);
+}
@@ -2,14 +2,16 @@
 
 use std::io::{Read, Write};
 
-use acid_store::repo::key::KeyRepo;
+use acid_store::repo::key::{Entry, KeyRepo};
 use acid_store::repo::{
-    peek_info, Commit, Encryption, ResourceLimit, RestoreSavepoint, SwitchInstance, Unlock,
+    audit_encryption, peek_info, Commit, Encryption, ResourceLimit, RestoreSavepoint,
+    SuspectReason, SwitchInstance, Unlock,
 };
-use acid_store::store::{BlockType, DataStore, OpenStore};
+use acid_store::store::{BlockKey, BlockType, DataStore, OpenStore};
 use common::*;
 use rstest_reuse::{self, *};
 use std::collections::HashSet;
+use std::time::SystemTime;
 use uuid::Uuid;
 
 mod common;
@@ -55,6 +57,70 @@ fn list_keys(mut repo: KeyRepo<String>) {
     ]);
 }
 
+#[rstest]
+fn len_counts_objects(mut repo: KeyRepo<String>) {
+    assert_that!(repo.is_empty()).is_true();
+
+    repo.insert(String::from("test1"));
+    repo.insert(String::from("test2"));
+    repo.remove("test1");
+
+    assert_that!(repo.len()).is_equal_to(1);
+    assert_that!(repo.is_empty()).is_false();
+}
+
+#[rstest]
+fn object_stats_match_object(mut repo: KeyRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    object.set_len(buffer.len() as u64 * 2)?;
+    let expected = object.stats()?;
+    drop(object);
+
+    let stats = repo.object_stats("test").unwrap();
+
+    assert_that!(stats.apparent_size()).is_equal_to(expected.apparent_size());
+    assert_that!(stats.actual_size()).is_equal_to(buffer.len() as u64);
+    assert_that!(stats.chunk_count()).is_greater_than(0);
+    assert_that!(stats.holes()).is_equal_to(expected.holes());
+    assert_that!(repo.object_stats("nonexistent")).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn vacant_entry_inserts_object(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    match repo.entry(String::from("test")) {
+        Entry::Vacant(entry) => {
+            let mut object = entry.insert();
+            object.write_all(b"data")?;
+            object.commit()?;
+        }
+        Entry::Occupied(_) => panic!("The entry should be vacant."),
+    }
+
+    assert_that!(repo.contains("test")).is_true();
+    assert_that!(repo.object("test").unwrap().size()?).is_equal_to(4);
+
+    Ok(())
+}
+
+#[rstest]
+fn occupied_entry_returns_existing_object(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(b"data")?;
+    object.commit()?;
+    drop(object);
+
+    let object = repo.entry(String::from("test")).or_insert();
+
+    assert_that!(object.size()?).is_equal_to(4);
+    assert_that!(repo.len()).is_equal_to(1);
+
+    Ok(())
+}
+
 #[rstest]
 fn can_not_get_object_from_removed_key(mut repo: KeyRepo<String>) {
     repo.insert(String::from("test"));
@@ -158,6 +224,133 @@ fn copy_nonexistent_object(mut repo: KeyRepo<String>) {
     assert_that!(repo.copy("nonexistent1", String::from("nonexistent2"))).is_false();
 }
 
+#[rstest]
+fn renamed_object_has_same_contents(
+    repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let RepoObject {
+        mut repo,
+        mut object,
+        key,
+    } = repo_object;
+
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    repo.rename(&key, String::from("renamed"))?;
+
+    let mut object = repo.object("renamed").unwrap();
+    let mut actual_contents = Vec::new();
+    object.read_to_end(&mut actual_contents)?;
+    drop(object);
+
+    assert_that!(actual_contents).is_equal_to(&buffer);
+    assert_that!(repo.contains(&key)).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn renamed_object_keeps_attributes(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert(String::from("source"));
+    repo.set_metadata("source", &1u32)?;
+    repo.add_tag("source", "tag")?;
+
+    repo.rename("source", String::from("dest"))?;
+
+    assert_that!(repo.get_metadata::<_, u32>("dest")).is_ok_containing(Some(1));
+    assert_that!(repo.keys_with_tag("tag").cloned().collect::<Vec<_>>())
+        .is_equal_to(vec![String::from("dest")]);
+
+    Ok(())
+}
+
+#[rstest]
+fn renaming_nonexistent_object_errs(mut repo: KeyRepo<String>) {
+    assert_that!(repo.rename("nonexistent", String::from("dest")))
+        .is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn renaming_to_existing_key_errs(mut repo: KeyRepo<String>) {
+    repo.insert(String::from("source"));
+    repo.insert(String::from("dest"));
+
+    assert_that!(repo.rename("source", String::from("dest")))
+        .is_err_variant(acid_store::Error::AlreadyExists);
+    assert_that!(repo.contains("source")).is_true();
+}
+
+#[rstest]
+fn copies_share_chunks_until_written(
+    mut repo: KeyRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    write_object(&mut repo, "source", &buffer)?;
+    repo.copy("source", String::from("dest"));
+    let source_chunks = repo.object_stats("source").unwrap().chunk_count();
+
+    assert_that!(repo.shared_chunk_count("source", "dest")).is_equal_to(Some(source_chunks));
+
+    write_object(&mut repo, "dest", b"different")?;
+
+    assert_that!(repo.shared_chunk_count("source", "dest")).is_equal_to(Some(0));
+    assert_that!(read_object(&repo, "source")?).is_equal_to(&buffer);
+    assert_that!(repo.shared_chunk_count("source", "nonexistent")).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn swapped_objects_exchange_contents_and_attributes(
+    mut repo: KeyRepo<String>,
+) -> anyhow::Result<()> {
+    write_object(&mut repo, "first", b"first")?;
+    write_object(&mut repo, "second", b"second")?;
+    repo.add_tag("first", "tag")?;
+
+    repo.swap("first", "second")?;
+
+    assert_that!(read_object(&repo, "first")?).is_equal_to(b"second".to_vec());
+    assert_that!(read_object(&repo, "second")?).is_equal_to(b"first".to_vec());
+    assert_that!(repo.keys_with_tag("tag").collect::<Vec<_>>())
+        .is_equal_to(vec![&String::from("second")]);
+
+    Ok(())
+}
+
+#[rstest]
+fn swapping_nonexistent_object_errs(mut repo: KeyRepo<String>) {
+    repo.insert(String::from("first"));
+
+    assert_that!(repo.swap("first", "second")).is_err_variant(acid_store::Error::NotFound);
+    assert_that!(repo.contains("first")).is_true();
+}
+
+#[rstest]
+fn replaced_object_has_source_contents(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    write_object(&mut repo, "real", b"old")?;
+    write_object(&mut repo, "temp", b"new")?;
+
+    repo.replace("real", "temp")?;
+
+    assert_that!(read_object(&repo, "real")?).is_equal_to(b"new".to_vec());
+    assert_that!(repo.contains("temp")).is_false();
+    assert_that!(repo.len()).is_equal_to(1);
+
+    Ok(())
+}
+
+#[rstest]
+fn replacing_with_nonexistent_object_errs(mut repo: KeyRepo<String>) {
+    repo.insert(String::from("real"));
+
+    assert_that!(repo.replace("real", "temp")).is_err_variant(acid_store::Error::NotFound);
+    assert_that!(repo.contains("real")).is_true();
+}
+
 #[rstest]
 fn object_is_not_accessible_from_another_instance(repo_object: RepoObject) -> anyhow::Result<()> {
     let RepoObject { repo, key, .. } = repo_object;
@@ -230,6 +423,168 @@ fn peek_info_succeeds(repo_store: RepoStore) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[rstest]
+fn commit_fails_when_header_is_too_large(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.max_header_size = Some(4096);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    for i in 0..200 {
+        let mut object = repo.insert(format!("object-{}", i));
+        object.write_all(Uuid::new_v4().as_bytes())?;
+        object.commit()?;
+    }
+
+    assert_that!(repo.estimated_header_size()).is_greater_than(4096);
+    assert_that!(repo.commit())
+        .is_err_variant(acid_store::Error::HeaderTooLarge { size: 0, limit: 0 });
+
+    // The staged changes should still be intact.
+    assert_that!(repo.keys().count()).is_equal_to(200);
+
+    for i in 10..200 {
+        repo.remove(&format!("object-{}", i));
+    }
+
+    assert_that!(repo.commit()).is_ok();
+
+    drop(repo);
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.keys().count()).is_equal_to(10);
+
+    Ok(())
+}
+
+#[rstest]
+fn raising_max_header_size_allows_commit(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.max_header_size = Some(4096);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    for i in 0..200 {
+        let mut object = repo.insert(format!("object-{}", i));
+        object.write_all(Uuid::new_v4().as_bytes())?;
+        object.commit()?;
+    }
+
+    assert_that!(repo.commit())
+        .is_err_variant(acid_store::Error::HeaderTooLarge { size: 0, limit: 0 });
+
+    repo.set_max_header_size(None);
+
+    assert_that!(repo.commit()).is_ok();
+    assert_that!(repo.info().config().max_header_size).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn audit_encryption_of_encrypted_repo_is_clean(
+    mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.flush()?;
+    drop(object);
+    repo.commit()?;
+
+    let audit = audit_encryption(&repo_store.store, 1.0)?;
+
+    assert_that!(audit.encryption()).is_equal_to(&Encryption::XChaCha20Poly1305);
+    assert_that!(audit.scanned_blocks()).is_equal_to(audit.total_blocks());
+    assert_that!(audit.suspect_blocks().to_vec()).is_empty();
+    assert_that!(audit.is_clean()).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn audit_encryption_detects_plaintext_block(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+
+    let plaintext_key = BlockKey::Data(Uuid::new_v4().into());
+    let mut store = repo_store.store.open()?;
+    assert_that!(store.write_block(plaintext_key, &[b'a'; 4096])).is_ok();
+
+    let audit = audit_encryption(&repo_store.store, 1.0)?;
+
+    assert_that!(audit.suspect_blocks().to_vec()).has_length(1);
+    assert_that!(audit.suspect_blocks()[0].key()).is_equal_to(plaintext_key);
+    assert_that!(audit.suspect_blocks()[0].reason()).is_equal_to(SuspectReason::NonUniform);
+
+    Ok(())
+}
+
+#[rstest]
+fn audit_encryption_detects_messagepack_map(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+
+    // A MessagePack map with one entry, which is too short for its byte distribution to be checked.
+    let mut map = vec![0x81, 0xa4];
+    map.extend_from_slice(b"data");
+    map.extend_from_slice(&[0xc4, 64]);
+    map.extend_from_slice(&[0xff; 64]);
+
+    let plaintext_key = BlockKey::Data(Uuid::new_v4().into());
+    let mut store = repo_store.store.open()?;
+    assert_that!(store.write_block(plaintext_key, &map)).is_ok();
+
+    let audit = audit_encryption(&repo_store.store, 1.0)?;
+
+    assert_that!(audit.suspect_blocks().to_vec()).has_length(1);
+    assert_that!(audit.suspect_blocks()[0].key()).is_equal_to(plaintext_key);
+    assert_that!(audit.suspect_blocks()[0].reason()).is_equal_to(SuspectReason::PlaintextMarker);
+
+    Ok(())
+}
+
+#[rstest]
+fn audit_encryption_detects_zstd_frame(
+    mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+
+    // A Zstandard frame magic number followed by bytes which otherwise look like ciphertext.
+    let mut frame = vec![0x28, 0xb5, 0x2f, 0xfd];
+    frame.extend_from_slice(&buffer);
+
+    let plaintext_key = BlockKey::Data(Uuid::new_v4().into());
+    let mut store = repo_store.store.open()?;
+    assert_that!(store.write_block(plaintext_key, &frame)).is_ok();
+
+    let audit = audit_encryption(&repo_store.store, 1.0)?;
+
+    assert_that!(audit.suspect_blocks().to_vec()).has_length(1);
+    assert_that!(audit.suspect_blocks()[0].key()).is_equal_to(plaintext_key);
+    assert_that!(audit.suspect_blocks()[0].reason()).is_equal_to(SuspectReason::PlaintextMarker);
+
+    Ok(())
+}
+
+#[rstest]
+fn audit_encryption_of_unencrypted_repo_reports_all_blocks(
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::None;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+
+    let audit = audit_encryption(&repo_store.store, 1.0)?;
+
+    assert_that!(audit.suspect_blocks().len() as u64).is_equal_to(audit.scanned_blocks());
+    assert_that!(audit.is_clean()).is_false();
+
+    Ok(())
+}
+
 #[apply(store_config)]
 fn committed_changes_are_persisted(
     #[case] repo_store: RepoStore,
@@ -721,3 +1076,481 @@ fn update_lock_context(mut repo_store: RepoStore) -> anyhow::Result<()> {
     assert_that!(repo_store.open::<KeyRepo<String>>()).is_ok();
     Ok(())
 }
+
+#[rstest]
+fn trashed_object_can_be_restored(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let RepoObject {
+        mut repo,
+        mut object,
+        key,
+    } = repo_object;
+
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    assert_that!(repo.remove_to_trash(&key)?).is_true();
+    assert_that!(repo.contains(&key)).is_false();
+    let trashed_keys = repo
+        .list_trash()
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    assert_that!(trashed_keys).is_equal_to(vec![key.clone()]);
+
+    repo.restore_from_trash(&key)?;
+
+    assert_that!(repo.contains(&key)).is_true();
+    assert_that!(repo.list_trash().count()).is_equal_to(0);
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object(&key).unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn trashing_nonexistent_object_returns_false(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    assert_that!(repo.remove_to_trash("test")?).is_false();
+    assert_that!(repo.list_trash().count()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn restoring_object_not_in_trash_errs(mut repo: KeyRepo<String>) {
+    assert_that!(repo.restore_from_trash("test")).is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn restoring_object_over_existing_key_errs(repo_object: RepoObject) -> anyhow::Result<()> {
+    let RepoObject { mut repo, key, .. } = repo_object;
+
+    repo.remove_to_trash(&key)?;
+    repo.insert(key.clone());
+
+    assert_that!(repo.restore_from_trash(&key)).is_err_variant(acid_store::Error::AlreadyExists);
+    assert_that!(repo.list_trash().count()).is_equal_to(1);
+
+    Ok(())
+}
+
+#[rstest]
+fn purge_trash_only_removes_older_objects(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert(String::from("test"));
+    repo.remove_to_trash("test")?;
+
+    assert_that!(repo.purge_trash(SystemTime::UNIX_EPOCH)).is_equal_to(0);
+    assert_that!(repo.list_trash().count()).is_equal_to(1);
+
+    assert_that!(repo.purge_trash(SystemTime::now())).is_equal_to(1);
+    assert_that!(repo.list_trash().count()).is_equal_to(0);
+    assert_that!(repo.restore_from_trash("test")).is_err_variant(acid_store::Error::NotFound);
+
+    Ok(())
+}
+
+#[rstest]
+fn trashed_objects_count_towards_repo_size(
+    repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let RepoObject {
+        mut repo,
+        mut object,
+        key,
+    } = repo_object;
+
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    repo.remove_to_trash(&key)?;
+    let stats = repo.stats();
+
+    assert_that!(stats.apparent_size()).is_equal_to(0);
+    assert_that!(stats.trash_size()).is_equal_to(buffer.len() as u64);
+    assert_that!(stats.repo_size()).is_equal_to(buffer.len() as u64);
+
+    repo.purge_trash(SystemTime::now());
+    let stats = repo.stats();
+
+    assert_that!(stats.trash_size()).is_equal_to(0);
+    assert_that!(stats.repo_size()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn trashed_data_is_reclaimed_after_purge(
+    #[case] repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    let mut store = repo_store.store.open()?;
+    let original_blocks = store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+        .len();
+    drop(store);
+
+    // Trashed objects are retained when the repository is cleaned.
+    repo.remove_to_trash("test")?;
+    repo.commit()?;
+    repo.clean()?;
+
+    let mut store = repo_store.store.open()?;
+    let trashed_blocks = store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+        .len();
+    drop(store);
+
+    assert_that!(trashed_blocks).is_greater_than_or_equal_to(original_blocks);
+
+    repo.purge_trash(SystemTime::now());
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let mut store = repo_store.store.open()?;
+    let purged_blocks = store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+        .len();
+
+    assert_that!(purged_blocks).is_less_than(original_blocks);
+
+    Ok(())
+}
+
+#[rstest]
+fn trash_is_persisted(repo_store: RepoStore, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    repo.remove_to_trash("test")?;
+    repo.commit()?;
+    drop(repo);
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(repo.contains("test")).is_false();
+    assert_that!(repo.list_trash().count()).is_equal_to(1);
+
+    repo.restore_from_trash("test")?;
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object("test").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn rollback_restores_trash(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert(String::from("test"));
+    repo.commit()?;
+
+    repo.remove_to_trash("test")?;
+    repo.rollback()?;
+
+    assert_that!(repo.contains("test")).is_true();
+    assert_that!(repo.list_trash().count()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[apply(object_config)]
+fn update_shrinking_object_truncates(
+    #[case] repo_object: RepoObject,
+    larger_buffer: Vec<u8>,
+    smaller_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let RepoObject {
+        mut repo,
+        mut object,
+        key,
+    } = repo_object;
+
+    object.write_all(&larger_buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let new_data = smaller_buffer.clone();
+    repo.update(&key, |old_data| {
+        assert_that!(old_data).is_equal_to(&larger_buffer);
+        Ok(new_data)
+    })?;
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object(&key).unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&smaller_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn update_nonexistent_object_errs(mut repo: KeyRepo<String>) {
+    assert_that!(repo.update("test", Ok)).is_err_variant(acid_store::Error::NotFound);
+    assert_that!(repo.contains("test")).is_false();
+}
+
+#[rstest]
+fn failed_update_leaves_object_unchanged(
+    repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let RepoObject {
+        mut repo,
+        mut object,
+        key,
+    } = repo_object;
+
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let actual_size = repo.stats().actual_size();
+
+    assert_that!(repo.update(&key, |_| Err(acid_store::Error::InvalidData)))
+        .is_err_variant(acid_store::Error::InvalidData);
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object(&key).unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+    assert_that!(repo.stats().actual_size()).is_equal_to(actual_size);
+
+    Ok(())
+}
+
+#[rstest]
+fn update_with_transaction_in_progress_errs(repo_object: RepoObject) -> anyhow::Result<()> {
+    let RepoObject {
+        mut repo,
+        mut object,
+        key,
+    } = repo_object;
+
+    object.write_all(b"uncommitted")?;
+
+    assert_that!(repo.update(&key, Ok)).is_err_variant(acid_store::Error::TransactionInProgress);
+
+    Ok(())
+}
+
+#[rstest]
+fn update_is_visible_to_existing_objects(
+    repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let RepoObject {
+        mut repo,
+        mut object,
+        key,
+    } = repo_object;
+
+    let new_data = buffer.clone();
+    repo.update(&key, |_| Ok(new_data))?;
+
+    let mut actual_data = Vec::new();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn upsert_creates_missing_object(mut repo: KeyRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let new_data = buffer.clone();
+    repo.upsert(String::from("test"), |old_data| {
+        assert_that!(old_data).is_none();
+        Ok(new_data)
+    })?;
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object("test").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn upsert_updates_existing_object(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert(String::from("test"));
+
+    repo.upsert(String::from("test"), |old_data| {
+        assert_that!(old_data).is_some().is_equal_to(Vec::new());
+        Ok(b"new data".to_vec())
+    })?;
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object("test").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(b"new data".to_vec());
+
+    Ok(())
+}
+
+#[rstest]
+fn failed_upsert_does_not_create_object(mut repo: KeyRepo<String>) {
+    let result = repo.upsert(String::from("test"), |_| {
+        Err(acid_store::Error::InvalidData)
+    });
+    assert_that!(result).is_err_variant(acid_store::Error::InvalidData);
+    assert_that!(repo.contains("test")).is_false();
+}
+
+#[rstest]
+fn update_value_modifies_serialized_value(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("test"));
+    object.serialize(&vec![String::from("a long first value"), String::from("b")])?;
+    drop(object);
+
+    repo.update_value("test", |mut value: Vec<String>| {
+        value.remove(0);
+        Ok(value)
+    })?;
+
+    let mut object = repo.object("test").unwrap();
+
+    assert_that!(object.deserialize::<Vec<String>>()).is_ok_containing(vec![String::from("b")]);
+
+    Ok(())
+}
+
+#[rstest]
+fn into_store_releases_lock(repo_store: RepoStore) -> anyhow::Result<()> {
+    let repo: KeyRepo<String> = repo_store.create()?;
+    let mut store = repo.into_store()?;
+
+    let lock_blocks = store
+        .list_blocks(BlockType::Lock)
+        .map_err(anyhow::Error::msg)?;
+
+    assert_that!(lock_blocks).is_empty();
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn into_store_invalidates_live_objects(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let object = repo.insert(String::from("test"));
+
+    assert_that!(repo.into_store()).is_ok();
+    assert_that!(object.is_valid()).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn into_store_discards_uncommitted_changes(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert(String::from("committed"));
+    repo.commit()?;
+
+    let mut store = repo_store.store.open()?;
+    let original_blocks = store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    drop(store);
+
+    // Write data to the data store without committing the repository.
+    let mut object = repo.insert(String::from("uncommitted"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let mut store = repo.into_store()?;
+    let uncommitted_blocks = store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    drop(store);
+
+    // The uncommitted data is left in the data store as garbage.
+    assert_that!(uncommitted_blocks.is_superset(&original_blocks)).is_true();
+    assert_that!(uncommitted_blocks.len()).is_greater_than(original_blocks.len());
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(repo.contains("committed")).is_true();
+    assert_that!(repo.contains("uncommitted")).is_false();
+    assert_that!(repo.verify()?.is_empty()).is_true();
+
+    // Cleaning the repository removes the garbage.
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let mut store = repo_store.store.open()?;
+    let cleaned_blocks = store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    assert_that!(cleaned_blocks).is_equal_to(original_blocks);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn removing_object_does_not_remove_shared_chunks(
+    #[case] repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    // Write the same data to two objects so that they share chunks.
+    for key in ["first", "second"] {
+        let mut object = repo.insert(String::from(key));
+        object.write_all(&buffer)?;
+        object.commit()?;
+    }
+
+    repo.remove("first");
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let mut actual_data = Vec::new();
+    let mut object = repo.object("second").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+    assert_that!(repo.verify()?.is_empty()).is_true();
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, Manifest, ManifestDiff, ManifestSource, RepoConfig};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// Return the given `keys` as a sorted list of strings.
+fn sorted(keys: &[&str]) -> Vec<String> {
+    let mut keys = keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+/// Sort each list of keys in `diff` so it can be compared.
+fn sort_diff(mut diff: ManifestDiff<String>) -> ManifestDiff<String> {
+    diff.added.sort();
+    diff.removed.sort();
+    diff.changed.sort();
+    diff
+}
+
+#[apply(config)]
+fn diff_matches_mutations(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+    smaller_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+    write_object(&mut repo, "removed", &buffer)?;
+    write_object(&mut repo, "modified", &buffer)?;
+    write_object(&mut repo, "rewritten", &smaller_buffer)?;
+    write_object(&mut repo, "resized", &buffer)?;
+    write_object(&mut repo, "unchanged", &larger_buffer)?;
+    let before = repo.manifest(ManifestSource::Staged)?;
+
+    repo.remove("removed");
+    write_object(&mut repo, "modified", &larger_buffer)?;
+    write_object(&mut repo, "rewritten", &smaller_buffer)?;
+    repo.object("resized")
+        .unwrap()
+        .set_len(buffer.len() as u64 * 2)?;
+    write_object(&mut repo, "added", &buffer)?;
+    repo.copy("unchanged", String::from("copied"));
+    let after = repo.manifest(ManifestSource::Staged)?;
+
+    let diff = sort_diff(before.diff(&after));
+
+    assert_that!(diff.added).is_equal_to(sorted(&["added", "copied"]));
+    assert_that!(diff.removed).is_equal_to(sorted(&["removed"]));
+    assert_that!(diff.changed).is_equal_to(sorted(&["modified", "resized"]));
+
+    // Diffing in the other direction swaps the added and removed keys.
+    let reverse_diff = sort_diff(after.diff(&before));
+
+    assert_that!(reverse_diff.added).is_equal_to(diff.removed);
+    assert_that!(reverse_diff.removed).is_equal_to(diff.added);
+    assert_that!(reverse_diff.changed).is_equal_to(diff.changed);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn manifest_describes_objects(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+    write_object(&mut repo, "first", &buffer)?;
+    write_object(&mut repo, "duplicate", &buffer)?;
+    write_object(&mut repo, "sparse", &larger_buffer)?;
+    repo.object("sparse")
+        .unwrap()
+        .set_len(larger_buffer.len() as u64 * 2)?;
+    repo.remove_to_trash("duplicate")?;
+    write_object(&mut repo, "duplicate", &buffer)?;
+
+    let manifest = repo.manifest(ManifestSource::Staged)?;
+
+    assert_that!(manifest.repo_id()).is_equal_to(repo.info().id());
+    assert_that!(manifest.instance()).is_equal_to(repo.instance());
+    assert_that!(manifest.source()).is_equal_to(ManifestSource::Staged);
+    assert_that!(manifest.chunking()).is_equal_to(&repo.info().config().chunking);
+    assert_that!(manifest.len()).is_equal_to(3);
+
+    let first = *manifest.get("first").unwrap();
+    let duplicate = *manifest.get("duplicate").unwrap();
+    let sparse = *manifest.get("sparse").unwrap();
+
+    assert_that!(first).is_equal_to(duplicate);
+    assert_that!(first.size).is_equal_to(buffer.len() as u64);
+    assert_that!(sparse.size).is_equal_to(larger_buffer.len() as u64 * 2);
+    assert_that!(sparse.actual_size).is_equal_to(larger_buffer.len() as u64);
+    assert_that!(sparse.content).is_not_equal_to(first.content);
+
+    assert_that!(manifest.apparent_size())
+        .is_equal_to((buffer.len() * 2 + larger_buffer.len() * 2) as u64);
+    assert_that!(manifest.actual_size()).is_equal_to((buffer.len() + larger_buffer.len()) as u64);
+    assert_that!(manifest.actual_size()).is_equal_to(repo.stats().actual_size());
+
+    Ok(())
+}
+
+#[apply(config)]
+fn committed_manifest_excludes_staged_changes(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+
+    // Nothing has been committed yet.
+    write_object(&mut repo, "first", &buffer)?;
+    assert_that!(repo.manifest(ManifestSource::Committed)?.is_empty()).is_true();
+
+    repo.commit()?;
+    let committed = repo.manifest(ManifestSource::Committed)?;
+
+    write_object(&mut repo, "first", &larger_buffer)?;
+    write_object(&mut repo, "second", &buffer)?;
+
+    let still_committed = repo.manifest(ManifestSource::Committed)?;
+    let staged = repo.manifest(ManifestSource::Staged)?;
+
+    assert_that!(still_committed.source()).is_equal_to(ManifestSource::Committed);
+    assert_that!(committed.diff(&still_committed).is_empty()).is_true();
+
+    let diff = sort_diff(still_committed.diff(&staged));
+    assert_that!(diff.added).is_equal_to(sorted(&["second"]));
+    assert_that!(diff.removed).is_empty();
+    assert_that!(diff.changed).is_equal_to(sorted(&["first"]));
+
+    repo.commit()?;
+    let committed = repo.manifest(ManifestSource::Committed)?;
+
+    assert_that!(committed.diff(&staged).is_empty()).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn manifest_can_be_serialized(mut repo: KeyRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    write_object(&mut repo, "first", &buffer)?;
+    write_object(&mut repo, "second", &buffer)?;
+    let manifest = repo.manifest(ManifestSource::Staged)?;
+
+    let serialized = rmp_serde::to_vec(&manifest)?;
+    let deserialized: Manifest<String> = rmp_serde::from_slice(&serialized)?;
+
+    assert_that!(deserialized).is_equal_to(&manifest);
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions, ReadOnlyObject, RepoConfig};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// A reader which returns at most `limit` bytes at a time and counts how many bytes were read.
+struct CountingReader<'a> {
+    data: &'a [u8],
+    limit: usize,
+    bytes_read: usize,
+}
+
+impl<'a> CountingReader<'a> {
+    fn new(data: &'a [u8], limit: usize) -> Self {
+        CountingReader {
+            data,
+            limit,
+            bytes_read: 0,
+        }
+    }
+}
+
+impl<'a> Read for CountingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = &self.data[self.bytes_read..];
+        let size = buf.len().min(self.limit).min(remaining.len());
+        buf[..size].copy_from_slice(&remaining[..size]);
+        self.bytes_read += size;
+        Ok(size)
+    }
+}
+
+/// Create a repository containing an object named "test" with the given `data`.
+fn create_repo_with_object(
+    store_config: &CountingConfig,
+    repo_config: RepoConfig,
+    data: &[u8],
+) -> anyhow::Result<KeyRepo<String>> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(repo_config)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(store_config)?;
+
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(data)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    Ok(repo)
+}
+
+#[apply(config)]
+fn matching_reader_touches_no_data(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = CountingConfig::new();
+    let repo = create_repo_with_object(&store_config, config, &buffer)?;
+    let writes = store_config.writes();
+    let data_reads = store_config.data_reads();
+
+    let mut object = repo.object("test").unwrap();
+
+    assert_that!(&object.matches_reader(buffer.as_slice())).is_ok_containing(true);
+    assert_that!(store_config.writes()).is_equal_to(writes);
+    assert_that!(store_config.data_reads()).is_equal_to(data_reads);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn different_readers_do_not_match(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = CountingConfig::new();
+    let repo = create_repo_with_object(&store_config, config, &buffer)?;
+    let writes = store_config.writes();
+    let data_reads = store_config.data_reads();
+
+    let mut object = repo.object("test").unwrap();
+    let mut longer = buffer.clone();
+    longer.extend_from_slice(&larger_buffer);
+
+    assert_that!(&object.matches_reader(larger_buffer.as_slice())).is_ok_containing(false);
+    assert_that!(&object.matches_reader(longer.as_slice())).is_ok_containing(false);
+    assert_that!(&object.matches_reader(&buffer[..buffer.len() - 1])).is_ok_containing(false);
+    assert_that!(&object.matches_reader(&buffer[..buffer.len() / 2])).is_ok_containing(false);
+    assert_that!(&object.matches_reader(io::empty())).is_ok_containing(false);
+
+    assert_that!(store_config.writes()).is_equal_to(writes);
+    assert_that!(store_config.data_reads()).is_equal_to(data_reads);
+
+    Ok(())
+}
+
+#[rstest]
+fn divergence_is_detected_at_chunk(#[with(2048)] fixed_buffer: Vec<u8>) -> anyhow::Result<()> {
+    // The chunk size for this config is 256 bytes.
+    let store_config = CountingConfig::new();
+    let repo = create_repo_with_object(&store_config, fixed_config(), &fixed_buffer)?;
+    let mut object = repo.object("test").unwrap();
+
+    // The data matches until the fourth chunk, which ends at byte 1024.
+    let mut diverging = fixed_buffer.clone();
+    diverging[1000] ^= 0xff;
+    let mut reader = CountingReader::new(&diverging, 16);
+
+    assert_that!(&object.matches_reader(&mut reader)).is_ok_containing(false);
+    assert_that!(reader.bytes_read).is_equal_to(1024);
+
+    // A reader which ends on a chunk boundary is still shorter than the object.
+    let mut reader = CountingReader::new(&fixed_buffer[..1024], 16);
+
+    assert_that!(&object.matches_reader(&mut reader)).is_ok_containing(false);
+    assert_that!(reader.bytes_read).is_equal_to(1024);
+
+    // A reader which is longer than the object doesn't need to be read in its entirety.
+    let mut longer = fixed_buffer.clone();
+    longer.extend_from_slice(&fixed_buffer);
+    let mut reader = CountingReader::new(&longer, 16);
+
+    assert_that!(&object.matches_reader(&mut reader)).is_ok_containing(false);
+    assert_that!(reader.bytes_read).is_equal_to(2048 + 256);
+
+    Ok(())
+}
+
+#[rstest]
+fn empty_object_matches_empty_reader(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("test"));
+
+    assert_that!(&object.matches_reader(io::empty())).is_ok_containing(true);
+    assert_that!(&object.matches_reader(&b"data"[..])).is_ok_containing(false);
+
+    Ok(())
+}
+
+#[rstest]
+fn sparse_hole_does_not_match_null_bytes(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("test"));
+    object.set_len(1024)?;
+
+    assert_that!(&object.matches_reader(&[0u8; 1024][..])).is_ok_containing(false);
+
+    Ok(())
+}
+
+#[rstest]
+fn matches_reader_with_read_only_object(
+    mut repo: KeyRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    let mut object = ReadOnlyObject::try_from(object)?;
+
+    assert_that!(&object.matches_reader(buffer.as_slice())).is_ok_containing(true);
+
+    Ok(())
+}
+
+#[rstest]
+fn matches_reader_fails_during_transaction(
+    mut repo: KeyRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+
+    assert_that!(&object.matches_reader(buffer.as_slice()))
+        .is_err_variant(acid_store::Error::TransactionInProgress);
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{BlockKey, DataStore, MemoryConfig, OpenStore};
+use common::*;
+
+mod common;
+
+#[rstest]
+fn dumped_repo_can_be_loaded(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let config = MemoryConfig::new();
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(encoding_config())
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let mut dump = Vec::new();
+    config.dump(&mut dump)?;
+    let loaded_config = MemoryConfig::load(dump.as_slice())?;
+
+    let repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(&loaded_config)?;
+    let mut data = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut data)?;
+    assert_that!(data).is_equal_to(buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn store_and_config_dumps_are_equivalent(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let config = MemoryConfig::new();
+    let mut store = config.open()?;
+    store.write_block(BlockKey::Super, &buffer)?;
+
+    let mut dump = Vec::new();
+    store.dump(&mut dump)?;
+    let mut loaded_store = MemoryConfig::load(dump.as_slice())?.open()?;
+
+    assert_that!(loaded_store.read_block(BlockKey::Super)?).is_equal_to(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn load_truncated_dump_errs(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut store = MemoryConfig::new().open()?;
+    store.write_block(BlockKey::Super, &buffer)?;
+    let mut dump = Vec::new();
+    store.dump(&mut dump)?;
+    dump.truncate(dump.len() - 1);
+
+    assert_that!(MemoryConfig::load(dump.as_slice()))
+        .is_err_variant(acid_store::Error::InvalidData);
+
+    Ok(())
+}
+
+#[rstest]
+fn load_junk_errs() {
+    assert_that!(MemoryConfig::load(&[0u8; 32][..]))
+        .is_err_variant(acid_store::Error::UnsupportedStore);
+}
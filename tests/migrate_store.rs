@@ -0,0 +1,131 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Write;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{BlockKey, DataStore, MemoryConfig, OpenStore};
+use common::*;
+
+mod common;
+
+#[rstest]
+fn migrated_repo_can_be_opened_from_new_store(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let old_store = MemoryConfig::new();
+    let new_store = MemoryConfig::new();
+    let mut repo = create_store_repo(&old_store, encoding_config(), &buffer)?;
+
+    repo.migrate_store(new_store.open()?, |_| true)?;
+    drop(repo);
+
+    assert_that!(read_store_object(&new_store, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn uncommitted_changes_are_migrated(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let old_store = MemoryConfig::new();
+    let new_store = MemoryConfig::new();
+    let mut repo = create_store_repo(&old_store, encoding_config(), b"")?;
+    let mut object = repo.insert(String::from("uncommitted"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    repo.migrate_store(new_store.open()?, |_| true)?;
+    repo.commit()?;
+    drop(repo);
+
+    assert_that!(read_store_object(&new_store, "uncommitted")?).is_equal_to(&buffer);
+    assert_that!(read_store_object(&old_store, "uncommitted")).is_err();
+
+    Ok(())
+}
+
+#[rstest]
+fn new_store_is_locked(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let old_store = MemoryConfig::new();
+    let new_store = MemoryConfig::new();
+    let mut repo = create_store_repo(&old_store, encoding_config(), &buffer)?;
+
+    repo.migrate_store(new_store.open()?, |_| true)?;
+
+    let result = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open::<KeyRepo<String>, _>(&new_store);
+    assert_that!(result.map(|_| ())).is_err_variant(acid_store::Error::Locked);
+
+    Ok(())
+}
+
+#[rstest]
+fn progress_is_reported(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let old_store = MemoryConfig::new();
+    let mut repo = create_store_repo(&old_store, encoding_config(), &buffer)?;
+    let mut reports = Vec::new();
+
+    repo.migrate_store(MemoryConfig::new().open()?, |progress| {
+        reports.push(progress);
+        true
+    })?;
+
+    let last = reports.last().unwrap();
+    assert_that!(reports.len() as u64).is_equal_to(last.total_blocks);
+    assert_that!(last.blocks).is_equal_to(last.total_blocks);
+    assert_that!(last.bytes).is_greater_than(buffer.len() as u64 / 2);
+
+    Ok(())
+}
+
+#[rstest]
+fn cancelled_migration_leaves_no_repo(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let old_store = MemoryConfig::new();
+    let new_store = MemoryConfig::new();
+    let mut repo = create_store_repo(&old_store, encoding_config(), &buffer)?;
+
+    let result = repo.migrate_store(new_store.open()?, |progress| progress.blocks < 2);
+
+    assert_that!(result.map(|_| ())).is_err_variant(acid_store::Error::Cancelled);
+    assert_that!(new_store.open()?.read_block(BlockKey::Super)?).is_none();
+
+    // The repository still uses the old data store.
+    repo.commit()?;
+    drop(repo);
+    assert_that!(read_store_object(&old_store, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn migrating_to_store_with_repo_fails(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let old_store = MemoryConfig::new();
+    let new_store = MemoryConfig::new();
+    let mut repo = create_store_repo(&old_store, encoding_config(), &buffer)?;
+    drop(create_store_repo(&new_store, encoding_config(), b"")?);
+
+    let result = repo.migrate_store(new_store.open()?, |_| true);
+
+    assert_that!(result.map(|_| ())).is_err_variant(acid_store::Error::AlreadyExists);
+
+    Ok(())
+}
+
+#[rstest]
+fn migrating_read_only_repo_fails(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let old_store = MemoryConfig::new();
+    let new_store = MemoryConfig::new();
+    drop(create_store_repo(&old_store, encoding_config(), &buffer)?);
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::ReadOnly)
+        .open(&old_store)?;
+
+    let result = repo.migrate_store(new_store.open()?, |_| true);
+
+    assert_that!(result.map(|_| ())).is_err_variant(acid_store::Error::ReadOnly);
+    assert_that!(new_store.open()?.read_block(BlockKey::Super)?).is_none();
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, MirrorSide, MirroredConfig,
+    MirroredStore, OpenStore,
+};
+use common::*;
+
+mod common;
+
+/// A data store config whose stores fail every operation while they are offline.
+#[derive(Debug, Clone)]
+struct FlakyConfig {
+    inner: MemoryConfig,
+    offline: Arc<AtomicBool>,
+}
+
+impl FlakyConfig {
+    fn new() -> Self {
+        FlakyConfig {
+            inner: MemoryConfig::new(),
+            offline: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+}
+
+impl OpenStore for FlakyConfig {
+    type Store = FlakyStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(FlakyStore {
+            inner: self.inner.open()?,
+            offline: Arc::clone(&self.offline),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct FlakyStore {
+    inner: MemoryStore,
+    offline: Arc<AtomicBool>,
+}
+
+impl FlakyStore {
+    fn check(&self) -> acid_store::store::Result<()> {
+        if self.offline.load(Ordering::SeqCst) {
+            Err(acid_store::store::Error::new(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "The store is offline.",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl DataStore for FlakyStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.check()?;
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.check()?;
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.check()?;
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.check()?;
+        self.inner.list_blocks(kind)
+    }
+}
+
+#[rstest]
+fn blocks_are_written_to_both_stores(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let primary = MemoryConfig::new();
+    let secondary = MemoryConfig::new();
+    let config = MirroredConfig {
+        primary: primary.clone(),
+        secondary: secondary.clone(),
+    };
+
+    create_store_repo(&config, encoding_config(), &buffer)?;
+
+    assert_that!(read_store_object(&primary, "test")?).is_equal_to(&buffer);
+    assert_that!(read_store_object(&secondary, "test")?).is_equal_to(&buffer);
+    assert_that!(config.open()?.stale_side()).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn reads_fall_back_to_secondary(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let primary = FlakyConfig::new();
+    let secondary = MemoryConfig::new();
+    let config = MirroredConfig {
+        primary: primary.clone(),
+        secondary,
+    };
+    create_store_repo(&config, encoding_config(), &buffer)?;
+
+    primary.set_offline(true);
+
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn writes_succeed_while_one_store_is_offline(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let primary = MemoryConfig::new();
+    let secondary = FlakyConfig::new();
+    let mut store = MirroredStore::new(primary.open()?, secondary.open()?);
+
+    secondary.set_offline(true);
+    store.write_block(BlockKey::Super, &buffer)?;
+
+    assert_that!(store.stale_side()).is_equal_to(Some(MirrorSide::Secondary));
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn writes_fail_when_both_stores_are_offline(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let primary = FlakyConfig::new();
+    let secondary = FlakyConfig::new();
+    let mut store = MirroredStore::new(primary.open()?, secondary.open()?);
+
+    primary.set_offline(true);
+    secondary.set_offline(true);
+
+    assert_that!(store.write_block(BlockKey::Super, &buffer).is_err()).is_true();
+    assert_that!(store.read_block(BlockKey::Super).is_err()).is_true();
+    assert_that!(store.list_blocks(BlockType::Data).is_err()).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn repair_resyncs_stale_store(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let primary = MemoryConfig::new();
+    let secondary = FlakyConfig::new();
+    let mut store = MirroredStore::new(primary.open()?, secondary.open()?);
+
+    secondary.set_offline(true);
+    write_repo_blocks(&mut store, &buffer)?;
+    assert_that!(store.stale_side()).is_equal_to(Some(MirrorSide::Secondary));
+
+    secondary.set_offline(false);
+    assert_that!(store.repair()?).is_greater_than(0);
+
+    assert_that!(store.stale_side()).is_none();
+    assert_that!(read_store_object(&secondary.inner, "test")?).is_equal_to(&buffer);
+    assert_that!(store.repair()?).is_equal_to(0);
+
+    Ok(())
+}
+
+/// Write every block of a new repository containing an object with the given `data` to `store`.
+fn write_repo_blocks(store: &mut impl DataStore, data: &[u8]) -> anyhow::Result<()> {
+    let source = MemoryConfig::new();
+    create_store_repo(&source, encoding_config(), data)?;
+    let mut source = source.open()?;
+
+    let mut keys = vec![BlockKey::Version, BlockKey::Super];
+    keys.extend(
+        source
+            .list_blocks(BlockType::Data)?
+            .into_iter()
+            .map(BlockKey::Data),
+    );
+    keys.extend(
+        source
+            .list_blocks(BlockType::Header)?
+            .into_iter()
+            .map(BlockKey::Header),
+    );
+    for key in keys {
+        store.write_block(key, &source.read_block(key)?.unwrap())?;
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn repair_from_removes_extra_blocks(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let primary = MemoryConfig::new();
+    let secondary = MemoryConfig::new();
+    create_store_repo(&secondary, encoding_config(), &buffer)?;
+    let mut store = MirroredStore::new(primary.open()?, secondary.open()?);
+
+    store.repair_from(MirrorSide::Primary)?;
+
+    let mut secondary_store = secondary.open()?;
+    assert_that!(secondary_store.list_blocks(BlockType::Data)?).is_empty();
+    assert_that!(secondary_store.read_block(BlockKey::Super)?).is_none();
+
+    Ok(())
+}
@@ -1,10 +1,11 @@
 #![cfg(all(feature = "encryption", feature = "compression"))]
 
 use std::convert::TryFrom;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write};
 
 use acid_store::repo::key::KeyRepo;
 use acid_store::repo::{Chunking, Commit, ReadOnlyObject, RepoConfig, RestoreSavepoint};
+use acid_store::store::{BlockKey, BlockType, DataStore, OpenStore};
 use common::*;
 use rstest_reuse::{self, *};
 
@@ -55,6 +56,240 @@ fn append_to_object(
     Ok(())
 }
 
+#[apply(object_config)]
+fn write_from_reader(#[case] repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+
+    assert_that!(object.write_from(buffer.as_slice(), None)?).is_equal_to(buffer.len() as u64);
+    object.commit()?;
+
+    let mut actual_data = Vec::new();
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn write_from_reader_with_length(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    let len = buffer.len() as u64 / 2;
+
+    assert_that!(object.write_from(buffer.as_slice(), Some(len))?).is_equal_to(len);
+    object.commit()?;
+
+    let mut actual_data = Vec::new();
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data[..]).is_equal_to(&buffer[..len as usize]);
+
+    Ok(())
+}
+
+#[apply(object_config)]
+fn read_into_writer(#[case] repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    object.seek(SeekFrom::Start(0))?;
+
+    let mut actual_data = Vec::new();
+
+    assert_that!(object.read_into(&mut actual_data)?).is_equal_to(buffer.len() as u64);
+    assert_that!(&actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn read_into_starts_at_current_position(
+    repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    object.seek(SeekFrom::Start(10))?;
+
+    let mut actual_data = Vec::new();
+    object.read_into(&mut actual_data)?;
+
+    assert_that!(&actual_data[..]).is_equal_to(&buffer[10..]);
+
+    Ok(())
+}
+
+#[apply(object_config)]
+fn flushed_data_is_committed(
+    #[case] repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    let (first_half, second_half) = buffer.split_at(buffer.len() / 2);
+
+    object.write_all(first_half)?;
+    object.flush()?;
+    object.write_all(second_half)?;
+    object.sync_data()?;
+    object.commit()?;
+
+    let mut actual_data = Vec::new();
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn flushed_data_is_not_visible_before_commit(repo_object: RepoObject) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    let other_object = repo_object.repo.object(&repo_object.key).unwrap();
+
+    object.write_all(b"data")?;
+    object.sync_data()?;
+
+    assert_that!(other_object.size()?).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn read_at_does_not_move_position(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    object.seek(SeekFrom::Start(5))?;
+
+    let mut actual_data = vec![0u8; 10];
+    let bytes_read = object.read_at(20, &mut actual_data)?;
+
+    assert_that!(bytes_read).is_equal_to(10);
+    assert_that!(&actual_data[..]).is_equal_to(&buffer[20..30]);
+    assert_that!(object.seek(SeekFrom::Current(0))?).is_equal_to(5);
+
+    Ok(())
+}
+
+#[rstest]
+fn read_at_does_not_disturb_sequential_reads(
+    repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    object.seek(SeekFrom::Start(0))?;
+
+    let mut first_half = vec![0u8; buffer.len() / 2];
+    object.read_exact(&mut first_half)?;
+
+    let shared_object = &object;
+    let mut positional_data = vec![0u8; 10];
+    shared_object.read_at(0, &mut positional_data)?;
+
+    let mut second_half = Vec::new();
+    object.read_to_end(&mut second_half)?;
+
+    assert_that!(&positional_data[..]).is_equal_to(&buffer[..10]);
+    assert_that!(&first_half[..]).is_equal_to(&buffer[..buffer.len() / 2]);
+    assert_that!(&second_half[..]).is_equal_to(&buffer[buffer.len() / 2..]);
+
+    Ok(())
+}
+
+#[rstest]
+fn read_at_past_end_reads_nothing(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+
+    let mut actual_data = vec![0u8; 10];
+
+    assert_that!(object.read_at(buffer.len() as u64 + 10, &mut actual_data)?).is_equal_to(0);
+
+    Ok(())
+}
+
+#[apply(object_config)]
+fn write_at_does_not_move_position(
+    #[case] repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    object.seek(SeekFrom::Start(5))?;
+
+    object.write_at(20, b"new data")?;
+
+    let mut expected_data = buffer.clone();
+    expected_data[20..28].copy_from_slice(b"new data");
+    let position = object.seek(SeekFrom::Current(0))?;
+    let mut actual_data = Vec::new();
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(position).is_equal_to(5);
+    assert_that!(&actual_data).is_equal_to(&expected_data);
+
+    Ok(())
+}
+
+#[rstest]
+fn write_at_past_end_creates_hole(repo_object: RepoObject) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+
+    object.write_at(10, b"data")?;
+
+    let mut actual_data = Vec::new();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data[..]).is_equal_to(&b"\0\0\0\0\0\0\0\0\0\0data"[..]);
+    assert_that!(object.stats()?.holes()).is_equal_to(&[0..10][..]);
+
+    Ok(())
+}
+
+#[rstest]
+fn write_at_during_transaction_errs(repo_object: RepoObject) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(b"data")?;
+
+    assert_that!(object.write_at(0, b"new"))
+        .is_err_variant(acid_store::Error::TransactionInProgress);
+    assert_that!(object.read_at(0, &mut [0u8; 4]))
+        .is_err_variant(acid_store::Error::TransactionInProgress);
+
+    Ok(())
+}
+
+#[rstest]
+fn vectored_write_and_read(repo_object: RepoObject) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+
+    let bytes_written =
+        object.write_vectored(&[IoSlice::new(b"first "), IoSlice::new(b"second")])?;
+    object.commit()?;
+    object.seek(SeekFrom::Start(0))?;
+
+    let mut first = [0u8; 6];
+    let mut second = [0u8; 6];
+    let bytes_read =
+        object.read_vectored(&mut [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)])?;
+
+    assert_that!(bytes_written).is_equal_to(12);
+    assert_that!(bytes_read).is_equal_to(12);
+    assert_that!(&first).is_equal_to(b"first ");
+    assert_that!(&second).is_equal_to(b"second");
+
+    Ok(())
+}
+
 #[apply(object_config)]
 fn seek_and_read_data(#[case] repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
     let mut object = repo_object.object;
@@ -380,6 +615,94 @@ fn check_file_stats_with_holes(
     Ok(())
 }
 
+#[apply(object_config)]
+fn punch_hole_in_middle_reads_as_zeros(
+    #[case] repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+
+    let hole_start = buffer.len() / 4;
+    let hole_end = hole_start + buffer.len() / 2;
+    object.punch_hole(hole_start as u64, (hole_end - hole_start) as u64)?;
+
+    let mut expected_data = buffer.clone();
+    expected_data[hole_start..hole_end].fill(0);
+
+    let mut actual_data = Vec::new();
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&expected_data);
+    assert_that!(object.size()?).is_equal_to(buffer.len() as u64);
+
+    Ok(())
+}
+
+#[rstest]
+fn punch_hole_updates_stats(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+
+    let size = buffer.len() as u64;
+    let hole_start = size / 4;
+    let hole_end = hole_start + size / 2;
+    object.punch_hole(hole_start, hole_end - hole_start)?;
+
+    let stats = object.stats()?;
+
+    assert_that!(stats.apparent_size()).is_equal_to(size);
+    assert_that!(stats.actual_size()).is_equal_to(size - (hole_end - hole_start));
+    assert_that!(stats.holes()).is_equal_to(&[hole_start..hole_end][..]);
+    assert_that!(stats.allocated_ranges()).is_equal_to(vec![0..hole_start, hole_end..size]);
+
+    Ok(())
+}
+
+#[rstest]
+fn punch_hole_is_clamped_to_object_size(
+    repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+
+    let size = buffer.len() as u64;
+    object.punch_hole(size / 2, u64::MAX)?;
+    object.punch_hole(size * 2, 10)?;
+
+    let stats = object.stats()?;
+
+    assert_that!(object.size()?).is_equal_to(size);
+    assert_that!(stats.holes()).is_equal_to(&[(size / 2)..size][..]);
+    assert_that!(stats.allocated_ranges()).is_equal_to(vec![0..(size / 2)]);
+
+    Ok(())
+}
+
+#[rstest]
+fn adjacent_holes_are_merged(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = repo_object.object;
+    object.write_all(&buffer)?;
+    object.commit()?;
+
+    let size = buffer.len() as u64;
+    object.punch_hole(0, size / 2)?;
+    object.punch_hole(size / 2, size / 4)?;
+    object.set_len(size * 2)?;
+
+    let stats = object.stats()?;
+
+    assert_that!(stats.holes()).is_equal_to(&[0..(size * 3 / 4), size..(size * 2)][..]);
+    assert_that!(stats.allocated_ranges()).is_equal_to(vec![(size * 3 / 4)..size]);
+
+    Ok(())
+}
+
 #[apply(repo_config)]
 fn compare_content_ids(
     #[case] mut repo: KeyRepo<String>,
@@ -555,6 +878,72 @@ fn write_buffer_with_same_size_as_fixed_chunk_size(
     Ok(())
 }
 
+#[rstest]
+fn max_chunk_size_splits_large_chunks(#[with(1000)] fixed_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut config = fixed_config();
+    config.max_chunk_size = Some(100);
+    let repo_object = RepoObject::new(config)?;
+    let mut object = repo_object.object;
+
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+
+    let mut actual_data = Vec::new();
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(object.stats()?.chunk_count()).is_equal_to(10);
+    assert_that!(&actual_data).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn min_chunk_size_merges_small_chunks(#[with(4096)] fixed_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut config = fixed_config();
+    config.min_chunk_size = Some(1000);
+    let repo_object = RepoObject::new(config)?;
+    let mut object = repo_object.object;
+
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+
+    let mut actual_data = Vec::new();
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    // Boundaries from the 256-byte fixed chunker are ignored until a chunk is 1000 bytes.
+    assert_that!(object.stats()?.chunk_count()).is_equal_to(4);
+    assert_that!(&actual_data).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn chunk_size_bounds_apply_to_content_defined_chunking(
+    #[with(64 * 1024)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut config = zpaq_config();
+    config.min_chunk_size = Some(128);
+    config.max_chunk_size = Some(512);
+    let repo_object = RepoObject::new(config)?;
+    let mut object = repo_object.object;
+
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+
+    let mut actual_data = Vec::new();
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+    let chunk_count = object.stats()?.chunk_count();
+
+    assert_that!(chunk_count).is_greater_than_or_equal_to(fixed_buffer.len() / 512);
+    assert_that!(chunk_count).is_less_than_or_equal_to(fixed_buffer.len() / 128 + 1);
+    assert_that!(&actual_data).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
+
 #[rstest]
 fn reading_seeking_with_uncommitted_changes_errs(repo_object: RepoObject) -> anyhow::Result<()> {
     let mut object = repo_object.object;
@@ -814,3 +1203,146 @@ fn restoring_to_savepoint_invalidates_objects(repo_object: RepoObject) -> anyhow
 
     Ok(())
 }
+
+/// Invert every byte of every data block in the given `repo_store`.
+fn corrupt_data_blocks(repo_store: &RepoStore) -> anyhow::Result<()> {
+    let mut store = repo_store.store.open()?;
+    for block_id in store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+    {
+        let key = BlockKey::Data(block_id);
+        let data = store.read_block(key).map_err(anyhow::Error::msg)?.unwrap();
+        let corrupt_data = data.iter().map(|byte| !byte).collect::<Vec<_>>();
+        store
+            .write_block(key, &corrupt_data)
+            .map_err(anyhow::Error::msg)?;
+    }
+    Ok(())
+}
+
+#[rstest]
+fn read_corrupt_data_without_verify_reads_succeeds(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    corrupt_data_blocks(&repo_store)?;
+
+    // Without encryption, corrupt data can't be detected without hashing it.
+    let mut actual_data = Vec::new();
+    let mut object = repo.object("test").unwrap();
+
+    assert_that!(object.read_to_end(&mut actual_data)).is_ok();
+    assert_that!(actual_data).is_not_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn read_corrupt_data_with_verify_reads_errs(
+    mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config.verify_reads = true;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    corrupt_data_blocks(&repo_store)?;
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object("test").unwrap();
+    let result = object
+        .read_to_end(&mut actual_data)
+        .map_err(acid_store::Error::from);
+
+    assert_that!(matches!(
+        result,
+        Err(acid_store::Error::ChunkMismatch { .. })
+    ))
+    .is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn verify_reads_can_be_enabled_at_runtime(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    corrupt_data_blocks(&repo_store)?;
+    repo.set_verify_reads(true);
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object("test").unwrap();
+    let result = object
+        .read_to_end(&mut actual_data)
+        .map_err(acid_store::Error::from);
+
+    assert_that!(matches!(
+        result,
+        Err(acid_store::Error::ChunkMismatch { .. })
+    ))
+    .is_true();
+
+    Ok(())
+}
+
+#[apply(repo_config)]
+fn objects_can_be_read_from_multiple_threads(
+    #[case] mut repo: KeyRepo<String>,
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let buffers = [first_buffer, second_buffer];
+    for (index, buffer) in buffers.iter().enumerate() {
+        let mut object = repo.insert(index.to_string());
+        object.write_all(buffer)?;
+        object.commit()?;
+    }
+
+    // Read each object, and the first object a second time, from separate threads.
+    let mut objects = vec![
+        (repo.object("0").unwrap(), &buffers[0]),
+        (repo.object("1").unwrap(), &buffers[1]),
+        (repo.object("0").unwrap(), &buffers[0]),
+    ];
+    let results = std::thread::scope(|scope| {
+        let handles = objects
+            .iter_mut()
+            .map(|(object, _)| {
+                scope.spawn(move || {
+                    let mut actual_data = Vec::new();
+                    object.read_to_end(&mut actual_data).map(|_| actual_data)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    for ((_, expected_data), actual_data) in objects.iter().zip(results) {
+        assert_that!(&actual_data?).is_equal_to(*expected_data);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::cmp::min;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError, TestRunner};
+
+use acid_store::repo::{Object, RepoConfig};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The maximum number of bytes to write or read in a single operation.
+const MAX_BUFFER_SIZE: usize = 1024;
+
+/// The maximum offset to seek to or length to set.
+const MAX_OFFSET: u64 = 2048;
+
+/// The maximum number of operations in a sequence.
+const MAX_OPERATIONS: usize = 32;
+
+/// The number of operation sequences to test for each repository configuration.
+const CASES: u32 = 64;
+
+/// An operation to perform on an object.
+#[derive(Debug, Clone)]
+enum Operation {
+    Write(Vec<u8>),
+    Seek(SeekFrom),
+    Read(usize),
+    Flush,
+    Commit,
+    SetLen(u64),
+    Reopen,
+}
+
+/// An error which is expected to be returned by an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExpectedError {
+    TransactionInProgress,
+    InvalidInput,
+}
+
+/// The value returned by an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Unit,
+    Position(u64),
+    Bytes(Vec<u8>),
+    Written(usize),
+}
+
+type OperationResult = Result<Outcome, ExpectedError>;
+
+/// A write transaction which has not been committed.
+#[derive(Debug)]
+struct Transaction {
+    start: u64,
+    data: Vec<u8>,
+}
+
+/// An in-memory reference model of the intended semantics of an `Object`.
+#[derive(Debug, Default)]
+struct Model {
+    data: Vec<u8>,
+    position: u64,
+    transaction: Option<Transaction>,
+}
+
+impl Model {
+    fn size(&self) -> Result<u64, ExpectedError> {
+        match self.transaction {
+            Some(_) => Err(ExpectedError::TransactionInProgress),
+            None => Ok(self.data.len() as u64),
+        }
+    }
+
+    fn apply(&mut self, operation: &Operation) -> OperationResult {
+        match operation {
+            Operation::Write(buffer) => {
+                let position = self.position;
+                self.transaction
+                    .get_or_insert_with(|| Transaction {
+                        start: position,
+                        data: Vec::new(),
+                    })
+                    .data
+                    .extend_from_slice(buffer);
+                self.position += buffer.len() as u64;
+                Ok(Outcome::Written(buffer.len()))
+            }
+            Operation::Seek(pos) => {
+                let size = self.size()? as i64;
+                let new_position = match *pos {
+                    SeekFrom::Start(offset) => min(size as u64, offset),
+                    SeekFrom::End(offset) => {
+                        if offset > size {
+                            return Err(ExpectedError::InvalidInput);
+                        }
+                        min(size, size - offset) as u64
+                    }
+                    SeekFrom::Current(offset) => {
+                        if self.position as i64 + offset < 0 {
+                            return Err(ExpectedError::InvalidInput);
+                        }
+                        min(size, self.position as i64 + offset) as u64
+                    }
+                };
+                self.position = new_position;
+                Ok(Outcome::Position(new_position))
+            }
+            Operation::Read(size) => {
+                self.size()?;
+                let start = self.position as usize;
+                let end = min(start + size, self.data.len());
+                self.position = end as u64;
+                Ok(Outcome::Bytes(self.data[start..end].to_vec()))
+            }
+            Operation::Flush => Ok(Outcome::Unit),
+            Operation::Commit => {
+                if let Some(Transaction { start, data }) = self.transaction.take() {
+                    let start = start as usize;
+                    let end = start + data.len();
+                    if end > self.data.len() {
+                        self.data.resize(end, 0);
+                    }
+                    self.data[start..end].copy_from_slice(&data);
+                }
+                Ok(Outcome::Unit)
+            }
+            Operation::SetLen(len) => {
+                self.size()?;
+                self.data.resize(*len as usize, 0);
+                self.position = min(self.position, *len);
+                Ok(Outcome::Unit)
+            }
+            Operation::Reopen => {
+                self.transaction = None;
+                self.position = 0;
+                Ok(Outcome::Unit)
+            }
+        }
+    }
+}
+
+/// Convert an error returned by an `Object` into the error the model would return.
+fn expected_error(error: impl Into<acid_store::Error>) -> Result<ExpectedError, TestCaseError> {
+    match error.into() {
+        acid_store::Error::TransactionInProgress => Ok(ExpectedError::TransactionInProgress),
+        acid_store::Error::Io(error) if error.kind() == io::ErrorKind::InvalidInput => {
+            Ok(ExpectedError::InvalidInput)
+        }
+        error => Err(TestCaseError::fail(format!(
+            "Unexpected error: {:?}",
+            error
+        ))),
+    }
+}
+
+/// Read up to `size` bytes from the `object`, stopping early only at the end of the object.
+fn read_up_to(object: &mut Object, size: usize) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0u8; size];
+    let mut bytes_read = 0;
+    while bytes_read < size {
+        match object.read(&mut buffer[bytes_read..])? {
+            0 => break,
+            n => bytes_read += n,
+        }
+    }
+    buffer.truncate(bytes_read);
+    Ok(buffer)
+}
+
+/// Apply the given `operation` to the `object` in the `repo_object`.
+fn apply(
+    repo_object: &mut RepoObject,
+    operation: &Operation,
+) -> Result<OperationResult, TestCaseError> {
+    let object = &mut repo_object.object;
+    let result = match operation {
+        Operation::Write(buffer) => object
+            .write(buffer)
+            .map(Outcome::Written)
+            .map_err(Into::into),
+        Operation::Seek(pos) => object.seek(*pos).map(Outcome::Position).map_err(Into::into),
+        Operation::Read(size) => read_up_to(object, *size)
+            .map(Outcome::Bytes)
+            .map_err(Into::into),
+        Operation::Flush => object.flush().map(|_| Outcome::Unit).map_err(Into::into),
+        Operation::Commit => object.commit().map(|_| Outcome::Unit),
+        Operation::SetLen(len) => object.set_len(*len).map(|_| Outcome::Unit),
+        Operation::Reopen => {
+            repo_object.object = repo_object
+                .repo
+                .object(&repo_object.key)
+                .ok_or_else(|| TestCaseError::fail("The object no longer exists."))?;
+            Ok(Outcome::Unit)
+        }
+    };
+
+    match result {
+        Ok(outcome) => Ok(Ok(outcome)),
+        Err(error) => Ok(Err(expected_error::<acid_store::Error>(error)?)),
+    }
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    let offset = 0..=MAX_OFFSET;
+    let relative_offset = -(MAX_OFFSET as i64)..=(MAX_OFFSET as i64);
+    prop_oneof![
+        4 => vec(any::<u8>(), 0..MAX_BUFFER_SIZE).prop_map(Operation::Write),
+        1 => offset.clone().prop_map(|offset| Operation::Seek(SeekFrom::Start(offset))),
+        1 => relative_offset
+            .clone()
+            .prop_map(|offset| Operation::Seek(SeekFrom::Current(offset))),
+        1 => relative_offset.prop_map(|offset| Operation::Seek(SeekFrom::End(offset))),
+        2 => (0..MAX_BUFFER_SIZE).prop_map(Operation::Read),
+        1 => Just(Operation::Flush),
+        3 => Just(Operation::Commit),
+        1 => offset.prop_map(Operation::SetLen),
+        1 => Just(Operation::Reopen),
+    ]
+}
+
+/// Run the `operations` against both a new object and the model, comparing them at every step.
+fn check_operations(config: &RepoConfig, operations: Vec<Operation>) -> Result<(), TestCaseError> {
+    let mut repo_object = RepoObject::new(config.clone())
+        .map_err(|error| TestCaseError::fail(format!("Could not create repo: {:?}", error)))?;
+    let mut model = Model::default();
+
+    for (index, operation) in operations.iter().enumerate() {
+        let expected = model.apply(operation);
+        let actual = apply(&mut repo_object, operation)?;
+        prop_assert_eq!(
+            &actual,
+            &expected,
+            "Operation {} ({:?}) diverged from the model.",
+            index,
+            operation
+        );
+
+        let expected_size = model.size();
+        let actual_size = match repo_object.object.size() {
+            Ok(size) => Ok(size),
+            Err(error) => Err(expected_error(error)?),
+        };
+        prop_assert_eq!(
+            actual_size,
+            expected_size,
+            "Size after operation {} ({:?}) diverged from the model.",
+            index,
+            operation
+        );
+    }
+
+    // Check the final contents of the object.
+    for operation in [Operation::Commit, Operation::Seek(SeekFrom::Start(0))] {
+        model.apply(&operation).ok();
+        apply(&mut repo_object, &operation)?.ok();
+    }
+    let expected = model.apply(&Operation::Read(model.data.len()));
+    let actual = apply(&mut repo_object, &Operation::Read(model.data.len()))?;
+    prop_assert_eq!(actual, expected, "Final contents diverged from the model.");
+
+    Ok(())
+}
+
+#[apply(config)]
+fn object_matches_model(#[case] config: RepoConfig) {
+    let mut runner = TestRunner::new(Config::with_cases(CASES));
+    let result = runner.run(&vec(operation(), 0..MAX_OPERATIONS), |operations| {
+        check_operations(&config, operations)
+    });
+    if let Err(error) = result {
+        panic!("{}", error);
+    }
+}
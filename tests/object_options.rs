@@ -0,0 +1,194 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Chunking, Commit, Compression, ObjectOptions, RepoConfig};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// Return options which compress objects differently than a repository with the given `config`.
+fn different_compression(config: &RepoConfig) -> ObjectOptions {
+    let compression = match config.compression {
+        Compression::None => Compression::Lz4 { level: 1 },
+        _ => Compression::None,
+    };
+    let mut options = ObjectOptions::new();
+    options.compression(compression);
+    options
+}
+
+#[apply(store_config)]
+fn object_with_different_compression_can_be_read(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let options = different_compression(&repo_store.config);
+
+    let mut object = repo.insert_with_options("test".into(), &options)?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let mut actual_data = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+    assert_that!(repo.verify()?).is_empty();
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn object_compression_persists_after_reopen(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let options = different_compression(&repo_store.config);
+
+    let mut object = repo.insert_with_options("test".into(), &options)?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let mut actual_data = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn object_compression_survives_clean(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let options = different_compression(&repo_store.config);
+
+    // Write both objects so that their chunks may share packs, which must be repacked once the
+    // first object is removed.
+    let mut object = repo.insert("removed".into());
+    object.write_all(&larger_buffer)?;
+    object.commit()?;
+    let mut object = repo.insert_with_options("kept".into(), &options)?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    assert_that!(repo.remove("removed")).is_true();
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let mut actual_data = Vec::new();
+    repo.object("kept").unwrap().read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+    assert_that!(repo.verify()?).is_empty();
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn committed_object_compression_survives_clean(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let options = different_compression(&repo_store.config);
+
+    let mut object = repo.insert("removed".into());
+    object.write_all(&larger_buffer)?;
+    object.commit()?;
+    let mut object = repo.insert_with_options("kept".into(), &options)?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    assert_that!(repo.remove("removed")).is_true();
+    repo.commit()?;
+
+    // The chunks of this object are now only referenced by the previous commit, but they still
+    // need to be repacked with the object's compression method.
+    assert_that!(repo.remove("kept")).is_true();
+    repo.clean()?;
+    repo.rollback()?;
+
+    let mut actual_data = Vec::new();
+    repo.object("kept").unwrap().read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+    assert_that!(repo.verify()?).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn object_chunking_overrides_repo_chunking(
+    #[with(1000)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(fixed_config())?;
+    let mut options = ObjectOptions::new();
+    options.chunking(Chunking::Fixed { size: 100 });
+
+    let mut object = repo.insert_with_options("test".into(), &options)?;
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let mut actual_data = Vec::new();
+    let mut object = repo.object("test").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(object.stats()?.chunk_count()).is_equal_to(10);
+    assert_that!(&actual_data).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn other_instances_use_repo_settings(#[with(1000)] fixed_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(fixed_config())?;
+    let mut options = ObjectOptions::new();
+    options.chunking(Chunking::Fixed { size: 100 });
+
+    drop(repo.insert_with_options("test".into(), &options)?);
+    let mut object = repo.object("test").unwrap();
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+
+    // The repository's chunk size is 256 bytes.
+    assert_that!(object.stats()?.chunk_count()).is_equal_to(4);
+
+    Ok(())
+}
+
+#[rstest]
+fn insert_with_options_replaces_existing_object(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(encoding_config())?;
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let object = repo.insert_with_options("test".into(), &ObjectOptions::new())?;
+
+    assert_that!(object.size()?).is_equal_to(0);
+    assert_that!(repo.len()).is_equal_to(1);
+
+    Ok(())
+}
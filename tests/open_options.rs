@@ -4,12 +4,15 @@
     feature = "compression"
 ))]
 
+use std::time::Duration;
+
 use acid_store::repo::key::KeyRepo;
 use acid_store::repo::value::ValueRepo;
 use acid_store::repo::{
-    Chunking, Commit, Compression, Encryption, OpenMode, OpenOptions, RepoConfig, ResourceLimit,
+    peek_commit_id, Chunking, Commit, Compression, Encryption, FormatVersion, OpenMode,
+    OpenOptions, Packing, RepoConfig, ResourceLimit,
 };
-use acid_store::store::MemoryConfig;
+use acid_store::store::{BlockKey, BlockType, DataStore, MemoryConfig, OpenStore};
 use common::*;
 
 mod common;
@@ -30,6 +33,34 @@ fn set_existing_config_and_create_new_repo(mut repo_store: RepoStore) -> anyhow:
     Ok(())
 }
 
+#[rstest]
+fn every_config_option_persists_after_reopen(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    // Every option is set to a value other than its default so that an option which isn't
+    // persisted is caught.
+    repo_store.config.chunking = Chunking::Fixed { size: 1024 * 16 };
+    repo_store.config.packing = Packing::Fixed(1024 * 64);
+    repo_store.config.compression = Compression::Lz4 { level: 2 };
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    repo_store.config.memory_limit = ResourceLimit::Moderate;
+    repo_store.config.operations_limit = ResourceLimit::Moderate;
+    repo_store.config.max_header_size = Some(1024 * 1024);
+    repo_store.config.verify_reads = true;
+    repo_store.config.gc_grace_period = Duration::from_secs(60);
+    repo_store.config.buffer_pool_size = 1024 * 1024;
+    repo_store.config.compatibility_target = Some(FormatVersion::CURRENT);
+    repo_store.config.commit_history = 3;
+    repo_store.config.min_chunk_size = Some(1024);
+    repo_store.config.max_chunk_size = Some(1024 * 32);
+    assert_that!(repo_store.config).is_not_equal_to(RepoConfig::default());
+
+    repo_store.create::<KeyRepo<String>>()?;
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(repo.info().config()).is_equal_to(&repo_store.config);
+
+    Ok(())
+}
+
 #[rstest]
 fn configure_and_create_new_repo() -> anyhow::Result<()> {
     // These are random config values for testing. This should not be used as an example config.
@@ -179,3 +210,178 @@ fn lock_handler_is_passed_context_of_existing_lock(
     assert_that!(repo_store.open::<KeyRepo<String>>()).is_ok();
     Ok(())
 }
+
+#[rstest]
+fn change_password_via_metadata_handle_without_reading_header(
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    for i in 0..1000 {
+        repo.insert(format!("object-{}", i));
+    }
+    repo.commit()?;
+    drop(repo);
+
+    let store_config = CountingConfig::with_inner(repo_store.store.clone());
+    let mut handle = OpenOptions::new()
+        .password(repo_store.password.as_bytes())
+        .open_metadata(&store_config)?;
+    handle.change_password(
+        b"new password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    handle.commit_metadata()?;
+    drop(handle);
+
+    assert_that!(store_config.header_reads()).is_equal_to(0);
+
+    repo_store.password = String::from("new password");
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.keys().count()).is_equal_to(1000);
+
+    Ok(())
+}
+
+#[rstest]
+fn commit_metadata_keeps_changes_committed_by_other_clients(
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+
+    let mut handle = OpenOptions::new()
+        .password(repo_store.password.as_bytes())
+        .open_metadata(&repo_store.store)?;
+
+    // Commit changes to the repository while the handle is open.
+    repo_store.handler = Box::new(|_| true);
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    repo.insert(String::from("test"));
+    repo.commit()?;
+    drop(repo);
+    let commit_id = peek_commit_id(&repo_store.store)?;
+
+    handle.change_password(
+        b"new password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    handle.commit_metadata()?;
+    drop(handle);
+
+    assert_that!(peek_commit_id(&repo_store.store)?).is_equal_to(commit_id);
+
+    repo_store.password = String::from("new password");
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.contains("test")).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn open_metadata_with_wrong_password_errs(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+
+    assert_that!(OpenOptions::new()
+        .password(b"wrong password")
+        .open_metadata(&repo_store.store))
+    .is_err_variant(acid_store::Error::Password);
+
+    Ok(())
+}
+
+#[rstest]
+fn open_metadata_acquires_lock(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+
+    let handle = OpenOptions::new()
+        .password(repo_store.password.as_bytes())
+        .open_metadata(&repo_store.store)?;
+
+    assert_that!(handle.is_locked()).is_ok().is_true();
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Locked);
+
+    drop(handle);
+
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_ok();
+
+    Ok(())
+}
+
+/// Overwrite every header block in the given `store` with `data`.
+fn overwrite_headers(store: &MemoryConfig, data: &[u8]) -> anyhow::Result<()> {
+    let mut store = store.open()?;
+    for header_id in store
+        .list_blocks(BlockType::Header)
+        .map_err(anyhow::Error::msg)?
+    {
+        store
+            .write_block(BlockKey::Header(header_id), data)
+            .map_err(anyhow::Error::msg)?;
+    }
+    Ok(())
+}
+
+#[rstest]
+fn opening_with_garbage_metadata_errs(repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.create::<KeyRepo<String>>()?;
+
+    let mut store = repo_store.store.open()?;
+    store
+        .write_block(BlockKey::Super, b"garbage")
+        .map_err(anyhow::Error::msg)?;
+
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Corrupt);
+
+    Ok(())
+}
+
+#[rstest]
+#[case::empty(Vec::new())]
+#[case::truncated(vec![0x94, 0x80])]
+// A MessagePack `bin 32` with a length prefix of nearly 4 GiB and no data.
+#[case::huge_length_prefix(vec![0xc6, 0xff, 0xff, 0xff, 0xf0])]
+fn opening_with_garbage_header_errs(
+    mut repo_store: RepoStore,
+    #[case] header: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config.compression = Compression::Lz4 { level: 1 };
+    repo_store.create::<KeyRepo<String>>()?;
+    overwrite_headers(&repo_store.store, &header)?;
+
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Corrupt);
+
+    repo_store.config.compression = Compression::None;
+    repo_store.store = MemoryConfig::new();
+    repo_store.create::<KeyRepo<String>>()?;
+    overwrite_headers(&repo_store.store, &header)?;
+
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Corrupt);
+
+    Ok(())
+}
+
+#[rstest]
+#[case::empty(Vec::new())]
+#[case::shorter_than_nonce(vec![0x00; 8])]
+fn opening_with_truncated_encrypted_header_errs(
+    mut repo_store: RepoStore,
+    #[case] header: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    repo_store.create::<KeyRepo<String>>()?;
+    overwrite_headers(&repo_store.store, &header)?;
+
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Corrupt);
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, ResourceLimit};
+use common::*;
+
+mod common;
+
+#[rstest]
+fn new_repo_is_not_dirty(repo: KeyRepo<String>) -> anyhow::Result<()> {
+    assert_that!(repo.is_dirty()?).is_false();
+    assert_that!(repo.pending_changes()?.is_empty()).is_true();
+    Ok(())
+}
+
+#[rstest]
+fn pending_changes_are_reported(
+    mut repo: KeyRepo<String>,
+    smaller_buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    write_object(&mut repo, "modified", &smaller_buffer)?;
+    write_object(&mut repo, "removed", &smaller_buffer)?;
+    write_object(&mut repo, "unchanged", &smaller_buffer)?;
+    repo.commit()?;
+
+    write_object(&mut repo, "modified", &larger_buffer)?;
+    repo.remove("removed");
+    write_object(&mut repo, "inserted", &smaller_buffer)?;
+
+    let changes = repo.pending_changes()?;
+
+    assert_that!(changes.added).is_equal_to(vec![String::from("inserted")]);
+    assert_that!(changes.removed).is_equal_to(vec![String::from("removed")]);
+    assert_that!(changes.changed).is_equal_to(vec![String::from("modified")]);
+    assert_that!(repo.is_dirty()?).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn rewriting_same_contents_is_not_a_change(
+    mut repo: KeyRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    write_object(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+
+    write_object(&mut repo, "test", &buffer)?;
+
+    assert_that!(repo.is_dirty()?).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn committing_clears_pending_changes(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    write_object(&mut repo, "test", b"data")?;
+    assert_that!(repo.is_dirty()?).is_true();
+
+    repo.commit()?;
+
+    assert_that!(repo.is_dirty()?).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn rolling_back_clears_pending_changes(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    write_object(&mut repo, "test", b"data")?;
+
+    repo.rollback()?;
+
+    assert_that!(repo.is_dirty()?).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn metadata_changes_are_dirty(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.change_password(
+        b"new password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+
+    assert_that!(repo.pending_changes()?.is_empty()).is_true();
+    assert_that!(repo.is_dirty()?).is_true();
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::key::{KeyRepo, RawKey};
+use acid_store::repo::Commit;
+use common::*;
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+/// A structured key type which replaced `String` keys.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+enum NewKey {
+    Named(String),
+    Numbered(u32),
+}
+
+#[rstest]
+fn opening_with_raw_keys_succeeds(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert(String::from("Test"));
+    repo.commit()?;
+    drop(repo);
+
+    assert_that!(repo_store.open::<KeyRepo<isize>>())
+        .is_err_variant(acid_store::Error::Deserialize);
+    assert_that!(repo_store.open::<KeyRepo<RawKey>>()).is_ok();
+
+    Ok(())
+}
+
+#[rstest]
+fn raw_keys_can_be_enumerated(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert(String::from("first"));
+    repo.insert(String::from("second"));
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<RawKey> = repo_store.open()?;
+    let keys = repo
+        .keys()
+        .map(|key| key.deserialize::<String>())
+        .collect::<acid_store::Result<HashSet<_>>>()?;
+
+    assert_that!(keys).is_equal_to(
+        [String::from("first"), String::from("second")]
+            .into_iter()
+            .collect::<HashSet<_>>(),
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn raw_key_from_key_matches_stored_key(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<(String, i32, Vec<u8>)> = repo_store.create()?;
+    let key = (String::from("test"), -42, vec![1, 2, 3]);
+    repo.insert(key.clone());
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<RawKey> = repo_store.open()?;
+    let raw_key = RawKey::from_key(&key)?;
+
+    assert_that!(repo.contains(&raw_key)).is_true();
+    assert_that!(raw_key.deserialize::<(String, i32, Vec<u8>)>()?).is_equal_to(&key);
+    assert_that!(raw_key.deserialize::<NewKey>()).is_err_variant(acid_store::Error::Deserialize);
+
+    Ok(())
+}
+
+#[rstest]
+fn read_objects_with_raw_keys(repo_store: RepoStore, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_object(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<RawKey> = repo_store.open()?;
+    let mut object = repo.object(&RawKey::from_key(&"test")?).unwrap();
+    let mut actual_data = Vec::new();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn changes_with_raw_keys_can_be_committed(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert(String::from("keep"));
+    repo.insert(String::from("remove"));
+    repo.commit()?;
+    drop(repo);
+
+    let mut repo: KeyRepo<RawKey> = repo_store.open()?;
+    repo.remove(&RawKey::from_key(&"remove")?);
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(repo.contains("keep")).is_true();
+    assert_that!(repo.contains("remove")).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn migrate_objects_to_new_key_type(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    smaller_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut old_repo: KeyRepo<String> = repo_store.create()?;
+    write_object(&mut old_repo, "first", &buffer)?;
+    write_object(&mut old_repo, "second", &smaller_buffer)?;
+    old_repo.commit()?;
+    drop(old_repo);
+
+    let new_store = RepoStore::new(repo_store.config.clone());
+    let mut new_repo: KeyRepo<NewKey> = new_store.create()?;
+
+    let raw_repo: KeyRepo<RawKey> = repo_store.open()?;
+    for raw_key in raw_repo.keys() {
+        let old_key: String = raw_key.deserialize()?;
+
+        let mut data = Vec::new();
+        raw_repo.object(raw_key).unwrap().read_to_end(&mut data)?;
+
+        let mut object = new_repo.insert(NewKey::Named(old_key));
+        object.write_all(&data)?;
+        object.commit()?;
+    }
+    new_repo.commit()?;
+    drop(new_repo);
+
+    let new_repo: KeyRepo<NewKey> = new_store.open()?;
+
+    let mut first_data = Vec::new();
+    new_repo
+        .object(&NewKey::Named(String::from("first")))
+        .unwrap()
+        .read_to_end(&mut first_data)?;
+    let mut second_data = Vec::new();
+    new_repo
+        .object(&NewKey::Named(String::from("second")))
+        .unwrap()
+        .read_to_end(&mut second_data)?;
+
+    assert_that!(new_repo.keys().len()).is_equal_to(2);
+    assert_that!(first_data).is_equal_to(&buffer);
+    assert_that!(second_data).is_equal_to(&smaller_buffer);
+
+    Ok(())
+}
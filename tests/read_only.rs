@@ -0,0 +1,315 @@
+#![cfg(all(
+    unix,
+    feature = "store-directory",
+    feature = "export",
+    feature = "encryption",
+    feature = "compression"
+))]
+
+use std::collections::BTreeMap;
+use std::fs::{self, Permissions};
+use std::io::{self, Cursor, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use acid_store::diagnostics::open_repositories;
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{
+    Commit, ExportOptions, ManifestSource, OpenMode, OpenOptions, RepoConfig, Unlock,
+};
+use acid_store::store::{DataStore, DirectoryConfig, DirectoryStore, OpenStore};
+use acid_store::uuid::Uuid;
+use common::*;
+use rstest_reuse::{self, *};
+use tempfile::TempDir;
+
+mod common;
+
+/// A snapshot of every file and directory in a directory tree.
+///
+/// This maps each path to its modification time and, for files, its contents.
+type Snapshot = BTreeMap<PathBuf, (SystemTime, Option<Vec<u8>>)>;
+
+/// Return a snapshot of the directory tree at `path`.
+fn snapshot(path: &Path) -> io::Result<Snapshot> {
+    let mut snapshot = BTreeMap::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let metadata = fs::metadata(&path)?;
+        let contents = if metadata.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                pending.push(entry?.path());
+            }
+            None
+        } else {
+            Some(fs::read(&path)?)
+        };
+        snapshot.insert(path, (metadata.modified()?, contents));
+    }
+    Ok(snapshot)
+}
+
+/// Remove or restore write permissions on every file and directory in the tree at `path`.
+fn set_writable(path: &Path, writable: bool) -> io::Result<()> {
+    let is_dir = fs::metadata(path)?.is_dir();
+    let mode = match (is_dir, writable) {
+        (true, true) => 0o755,
+        (true, false) => 0o555,
+        (false, true) => 0o644,
+        (false, false) => 0o444,
+    };
+
+    // Directories must be writable while we change the permissions of their children, and they
+    // must be readable for us to list them.
+    if writable || !is_dir {
+        fs::set_permissions(path, Permissions::from_mode(mode))?;
+    }
+    if is_dir {
+        for entry in fs::read_dir(path)? {
+            set_writable(&entry?.path(), writable)?;
+        }
+    }
+    if !writable && is_dir {
+        fs::set_permissions(path, Permissions::from_mode(mode))?;
+    }
+
+    Ok(())
+}
+
+/// A directory store which is made read-only and made writable again when this is dropped.
+///
+/// Permissions aren't enforced for privileged users, so tests should also compare snapshots of
+/// the store to check that nothing was written.
+struct ReadOnlyStore {
+    directory: TempDir,
+}
+
+impl ReadOnlyStore {
+    fn path(&self) -> PathBuf {
+        self.directory.path().join("store")
+    }
+
+    fn config(&self) -> DirectoryConfig {
+        DirectoryConfig::new_read_only(self.path())
+    }
+}
+
+impl Drop for ReadOnlyStore {
+    fn drop(&mut self) {
+        // Otherwise, the temporary directory can't be removed.
+        set_writable(&self.path(), true).ok();
+    }
+}
+
+/// Return the objects written to every read-only store.
+fn objects(buffer: &[u8], larger_buffer: &[u8]) -> BTreeMap<String, Vec<u8>> {
+    let mut objects = BTreeMap::new();
+    objects.insert(String::from("first"), buffer.to_vec());
+    objects.insert(String::from("second"), larger_buffer.to_vec());
+    objects.insert(String::from("empty"), Vec::new());
+    objects
+}
+
+/// Create a repository containing `objects` in a directory store and make the store read-only.
+fn read_only_store(
+    config: RepoConfig,
+    objects: &BTreeMap<String, Vec<u8>>,
+) -> anyhow::Result<ReadOnlyStore> {
+    let store = ReadOnlyStore {
+        directory: temp_dir(),
+    };
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(config)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&DirectoryConfig::new(store.path()))?;
+    for (key, data) in objects {
+        let mut object = repo.insert(key.clone());
+        object.write_all(data)?;
+        object.commit()?;
+    }
+    repo.commit()?;
+    drop(repo);
+
+    // Make sure that any write would change the modification time of the store's directories.
+    std::thread::sleep(Duration::from_millis(10));
+
+    set_writable(&store.path(), false)?;
+    Ok(store)
+}
+
+/// Open the repository in the given read-only `store`.
+fn open_read_only(store: &ReadOnlyStore) -> acid_store::Result<KeyRepo<String>> {
+    OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::ReadOnly)
+        .open(&store.config())
+}
+
+#[apply(config)]
+fn read_only_repo_supports_reads(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let objects = objects(&buffer, &larger_buffer);
+    let store = read_only_store(config, &objects)?;
+    let before = snapshot(&store.path())?;
+
+    let repo = open_read_only(&store)?;
+
+    for (key, expected) in &objects {
+        let mut data = Vec::new();
+        repo.object(key).unwrap().read_to_end(&mut data)?;
+        assert_that!(data).is_equal_to(expected);
+    }
+
+    assert_that!(repo.verify()?).is_empty();
+    assert_that!(repo.stats().apparent_size())
+        .is_equal_to((buffer.len() + larger_buffer.len()) as u64);
+
+    let committed = repo.manifest(ManifestSource::Committed)?;
+    let staged = repo.manifest(ManifestSource::Staged)?;
+    assert_that!(committed.len()).is_equal_to(objects.len());
+    assert_that!(committed.diff(&staged).is_empty()).is_true();
+
+    let archive = repo.export_plaintext(
+        Vec::new(),
+        |key| PathBuf::from(key),
+        &mut ExportOptions::new(),
+    )?;
+    let mut archive = tar::Archive::new(Cursor::new(archive));
+    let mut exported = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        exported.insert(path, data);
+    }
+    for (key, expected) in &objects {
+        assert_that!(exported.get(key)).is_equal_to(Some(expected));
+    }
+
+    // The repository doesn't hold a lock, so it doesn't show up as locked anywhere.
+    assert_that!(repo.is_locked()?).is_false();
+    let info = open_repositories()
+        .into_iter()
+        .find(|info| info.id() == repo.info().id())
+        .unwrap();
+    assert_that!(info.lock_kind()).is_none();
+
+    repo.unlock()?;
+    drop(repo);
+
+    assert_that!(snapshot(&store.path())?).is_equal_to(before);
+
+    Ok(())
+}
+
+#[rstest]
+fn writes_fail_early(buffer: Vec<u8>, larger_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let objects = objects(&buffer, &larger_buffer);
+    let store = read_only_store(encoding_config(), &objects)?;
+    let before = snapshot(&store.path())?;
+
+    let mut repo = open_read_only(&store)?;
+
+    let mut object = repo.insert(String::from("new"));
+    assert_that!(object.write(&buffer).map_err(acid_store::Error::from))
+        .is_err_variant(acid_store::Error::ReadOnly);
+    assert_that!(object.set_len(10)).is_err_variant(acid_store::Error::ReadOnly);
+    drop(object);
+
+    let mut object = repo.object("first").unwrap();
+    assert_that!(object.write(&buffer).map_err(acid_store::Error::from))
+        .is_err_variant(acid_store::Error::ReadOnly);
+    drop(object);
+
+    repo.remove("second");
+    assert_that!(repo.commit()).is_err_variant(acid_store::Error::ReadOnly);
+    assert_that!(repo.clean()).is_err_variant(acid_store::Error::ReadOnly);
+    assert_that!(repo.update_context(b"context")).is_err_variant(acid_store::Error::ReadOnly);
+
+    // Uncommitted changes can still be rolled back.
+    repo.rollback()?;
+    assert_that!(repo.contains("second")).is_true();
+
+    drop(repo);
+
+    assert_that!(snapshot(&store.path())?).is_equal_to(before);
+
+    Ok(())
+}
+
+#[rstest]
+fn opening_requires_read_only_mode(buffer: Vec<u8>, larger_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let objects = objects(&buffer, &larger_buffer);
+    let store = read_only_store(encoding_config(), &objects)?;
+    let before = snapshot(&store.path())?;
+
+    for mode in [OpenMode::Open, OpenMode::Create, OpenMode::CreateNew] {
+        let result: acid_store::Result<KeyRepo<String>> = OpenOptions::new()
+            .password(b"Password")
+            .mode(mode)
+            .open(&store.config());
+        assert_that!(result).is_err_variant(acid_store::Error::ReadOnly);
+    }
+
+    assert_that!(OpenOptions::new()
+        .password(b"Password")
+        .open_metadata(&store.config()))
+    .is_err_variant(acid_store::Error::ReadOnly);
+
+    // Opening an instance which doesn't exist would create it.
+    let result: acid_store::Result<KeyRepo<String>> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::ReadOnly)
+        .instance(Uuid::new_v4().into())
+        .open(&store.config());
+    assert_that!(result).is_err_variant(acid_store::Error::ReadOnly);
+
+    assert_that!(snapshot(&store.path())?).is_equal_to(before);
+
+    Ok(())
+}
+
+#[rstest]
+fn read_only_directory_store_refuses_writes(
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let objects = objects(&buffer, &larger_buffer);
+    let store = read_only_store(encoding_config(), &objects)?;
+
+    // Remove an empty directory, like a tool which doesn't preserve empty directories would.
+    set_writable(&store.path(), true)?;
+    fs::remove_dir(store.path().join("stage"))?;
+    set_writable(&store.path(), false)?;
+    let before = snapshot(&store.path())?;
+
+    let mut directory_store = store.config().open()?;
+
+    assert_that!(directory_store.is_read_only()).is_true();
+    assert_that!(directory_store
+        .write_block(acid_store::store::BlockKey::Super, &buffer)
+        .is_err())
+    .is_true();
+    assert_that!(directory_store
+        .remove_block(acid_store::store::BlockKey::Super)
+        .is_err())
+    .is_true();
+    assert_that!(directory_store.read_block(acid_store::store::BlockKey::Super)?).is_some();
+
+    drop(directory_store);
+    assert_that!(snapshot(&store.path())?).is_equal_to(before);
+
+    // Opening the store normally restores the missing directory.
+    set_writable(&store.path(), true)?;
+    DirectoryStore::open(store.path())?;
+    assert_that!(store.path().join("stage").is_dir()).is_true();
+
+    Ok(())
+}
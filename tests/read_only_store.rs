@@ -0,0 +1,82 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Read;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockKey, BlockType, DataStore, LatencyClass, MemoryConfig, OpenStore, ReadOnlyConfig,
+};
+use common::*;
+
+mod common;
+
+#[rstest]
+fn read_only_repo_can_be_opened(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    create_store_repo(&inner, encoding_config(), &buffer)?;
+    let config = ReadOnlyConfig::new(inner);
+
+    let repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::ReadOnly)
+        .open(&config)?;
+    let mut data = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut data)?;
+
+    assert_that!(data).is_equal_to(&buffer);
+    assert_that!(repo.verify()?).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn writable_repo_can_not_be_opened(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    create_store_repo(&inner, encoding_config(), &buffer)?;
+    let config = ReadOnlyConfig::new(inner.clone());
+
+    let result: acid_store::Result<KeyRepo<String>> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(&config);
+
+    assert_that!(result).is_err_variant(acid_store::Error::ReadOnly);
+    assert_that!(inner.open()?.list_blocks(BlockType::Lock)?).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn writes_are_rejected(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    create_store_repo(&inner, encoding_config(), &buffer)?;
+    let mut store = ReadOnlyConfig::new(inner.clone()).open()?;
+    let superblock = store.read_block(BlockKey::Super)?;
+
+    assert_that!(store.is_read_only()).is_true();
+    assert_that!(store.probe().writable).is_false();
+    assert_that!(store.write_block(BlockKey::Super, &buffer).is_err()).is_true();
+    assert_that!(store.remove_block(BlockKey::Super).is_err()).is_true();
+    assert_that!(inner.open()?.read_block(BlockKey::Super)?).is_equal_to(superblock);
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_info_reports_store_capabilities(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    create_store_repo(&inner, encoding_config(), &buffer)?;
+
+    let repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::ReadOnly)
+        .open(&ReadOnlyConfig::new(inner.clone()))?;
+    let capabilities = repo.info().store_capabilities();
+
+    assert_that!(capabilities.writable).is_false();
+    assert_that!(capabilities.latency).is_equal_to(LatencyClass::Memory);
+    assert_that!(capabilities.max_block_size).is_none();
+
+    Ok(())
+}
@@ -0,0 +1,311 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "repo-file")]
+use acid_store::repo::file::{Entry, FileRepo};
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Chunking, Commit, OpenMode, OpenOptions, RechunkOptions, RepoConfig};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore,
+};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The chunking method objects are rechunked with, which is different from every test config.
+const NEW_CHUNKING: Chunking = Chunking::Fixed { size: 100 };
+
+/// The number of data blocks which can be written before an interrupted rechunk fails.
+///
+/// This is enough to rechunk and commit at least one object, but not all of them.
+const WRITES_BEFORE_FAILURE: usize = 100;
+
+/// The configuration for opening a `FailingStore`.
+#[derive(Debug, Clone)]
+struct FailingConfig {
+    inner: MemoryConfig,
+    remaining_writes: Arc<Mutex<Option<usize>>>,
+}
+
+impl FailingConfig {
+    fn new() -> Self {
+        Self {
+            inner: MemoryConfig::new(),
+            remaining_writes: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Make writing data blocks fail once `writes` more data blocks have been written.
+    fn fail_after(&self, writes: usize) {
+        *self.remaining_writes.lock().unwrap() = Some(writes);
+    }
+
+    /// Stop failing to write data blocks.
+    fn stop_failing(&self) {
+        *self.remaining_writes.lock().unwrap() = None;
+    }
+}
+
+impl OpenStore for FailingConfig {
+    type Store = FailingStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(FailingStore {
+            inner: self.inner.open()?,
+            remaining_writes: Arc::clone(&self.remaining_writes),
+        })
+    }
+}
+
+/// A data store which simulates the process being interrupted by failing to write data blocks.
+#[derive(Debug)]
+struct FailingStore {
+    inner: MemoryStore,
+    remaining_writes: Arc<Mutex<Option<usize>>>,
+}
+
+impl DataStore for FailingStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        if let BlockKey::Data(_) = key {
+            if let Some(remaining) = self.remaining_writes.lock().unwrap().as_mut() {
+                if *remaining == 0 {
+                    return Err(acid_store::store::Error::msg("The write was interrupted."));
+                }
+                *remaining -= 1;
+            }
+        }
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+}
+
+/// Assert that every object in `repo` has the contents in `expected`.
+fn assert_contents(
+    repo: &KeyRepo<String>,
+    expected: &HashMap<String, Vec<u8>>,
+) -> anyhow::Result<()> {
+    assert_that!(repo.keys().count()).is_equal_to(expected.len());
+    for (key, data) in expected {
+        assert_that!(read_object(repo, key)?).is_equal_to(data);
+    }
+    Ok(())
+}
+
+#[apply(config)]
+fn rechunk_preserves_contents(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+    smaller_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+    write_object(&mut repo, "first", &buffer)?;
+    write_object(&mut repo, "second", &larger_buffer)?;
+    write_object(&mut repo, "trashed", &smaller_buffer)?;
+    repo.remove_to_trash("trashed")?;
+
+    // Write an object with a hole in the middle.
+    let mut object = repo.insert(String::from("sparse"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    object.set_len(buffer.len() as u64 * 2)?;
+    object.seek(SeekFrom::End(0))?;
+    object.write_all(&smaller_buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    let report = repo.rechunk(NEW_CHUNKING, &RechunkOptions::new())?;
+
+    assert_that!(report.objects_rechunked()).is_equal_to(4);
+    assert_that!(report.objects_skipped()).is_equal_to(0);
+    assert_that!(repo.info().config().chunking).is_equal_to(NEW_CHUNKING);
+    assert_that!(repo.verify()?).is_empty();
+
+    let mut sparse_data = buffer.clone();
+    sparse_data.resize(buffer.len() * 2, 0);
+    sparse_data.extend_from_slice(&smaller_buffer);
+
+    let mut expected = HashMap::new();
+    expected.insert(String::from("first"), buffer);
+    expected.insert(String::from("second"), larger_buffer);
+    expected.insert(String::from("sparse"), sparse_data);
+    assert_contents(&repo, &expected)?;
+
+    repo.restore_from_trash("trashed")?;
+    assert_that!(read_object(&repo, "trashed")?).is_equal_to(&smaller_buffer);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn rechunk_deduplicates_with_new_chunking(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+    write_object(&mut repo, "original", &buffer)?;
+    write_object(&mut repo, "duplicate", &buffer)?;
+    repo.commit()?;
+
+    let report = repo.rechunk(NEW_CHUNKING, &RechunkOptions::new())?;
+
+    // The chunks of the duplicate object are deduplicated against the rechunked original.
+    assert_that!(report.objects_rechunked()).is_equal_to(2);
+    assert_that!(report.bytes_written()).is_greater_than(0);
+    assert_that!(report.bytes_written()).is_less_than_or_equal_to(buffer.len() as u64);
+    assert_that!(report.bytes_superseded()).is_less_than_or_equal_to(buffer.len() as u64);
+
+    // New objects are written with the new chunking method, so they're deduplicated against the
+    // rechunked objects.
+    let actual_size = repo.stats().actual_size();
+    write_object(&mut repo, "new", &buffer)?;
+    assert_that!(repo.stats().actual_size()).is_equal_to(actual_size);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn rechunk_with_same_chunking_does_nothing(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let chunking = config.chunking.clone();
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+    write_object(&mut repo, "object", &buffer)?;
+    repo.commit()?;
+
+    let report = repo.rechunk(chunking, &RechunkOptions::new())?;
+
+    assert_that!(report.objects_rechunked()).is_equal_to(0);
+    assert_that!(report.bytes_written()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn interrupted_rechunk_resumes(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    #[with(2048)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let old_chunking = config.chunking.clone();
+    let store_config = FailingConfig::new();
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(config)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&store_config)?;
+
+    let mut expected = HashMap::new();
+    for i in 0..6u8 {
+        // Every byte is different, so none of these objects share any chunks.
+        let data = fixed_buffer.iter().map(|byte| byte ^ i).collect::<Vec<_>>();
+        expected.insert(format!("unique{}", i), data);
+    }
+    expected.insert(String::from("shared1"), buffer.clone());
+    expected.insert(String::from("shared2"), buffer.clone());
+    for (key, data) in &expected {
+        write_object(&mut repo, key, data)?;
+    }
+    repo.commit()?;
+
+    // Interrupt the rechunk partway through, after at least one checkpoint has been committed.
+    store_config.fail_after(WRITES_BEFORE_FAILURE);
+    let mut options = RechunkOptions::new();
+    options.checkpoint_interval(1);
+    assert_that!(repo.rechunk(NEW_CHUNKING, &options)).is_err();
+    drop(repo);
+    store_config.stop_failing();
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .open(&store_config)?;
+
+    // The chunking method isn't changed until every object has been rechunked.
+    assert_that!(repo.info().config().chunking).is_equal_to(&old_chunking);
+    assert_contents(&repo, &expected)?;
+
+    let report = repo.rechunk(NEW_CHUNKING, &options)?;
+
+    assert_that!(report.objects_skipped()).is_greater_than(0);
+    assert_that!(report.objects_rechunked()).is_greater_than(0);
+    assert_that!(report.objects_skipped() + report.objects_rechunked())
+        .is_equal_to(expected.len() as u64);
+    drop(repo);
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .open(&store_config)?;
+
+    assert_that!(repo.info().config().chunking).is_equal_to(NEW_CHUNKING);
+    assert_that!(repo.verify()?).is_empty();
+    assert_contents(&repo, &expected)?;
+
+    // Deduplication still works under the new chunking method.
+    let actual_size = repo.stats().actual_size();
+    write_object(&mut repo, "shared3", &buffer)?;
+    assert_that!(repo.stats().actual_size()).is_equal_to(actual_size);
+
+    // Once the old chunks are cleaned up, the contents are still intact.
+    repo.clean()?;
+    expected.insert(String::from("shared3"), buffer);
+    assert_contents(&repo, &expected)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "repo-file")]
+#[rstest]
+fn rechunk_file_repo_with_uncommitted_changes(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: FileRepo = repo_store.create()?;
+    repo.create("removed", &Entry::file())?;
+    repo.create("kept", &Entry::file())?;
+    let mut object = repo.open("kept")?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    repo.remove("removed")?;
+    repo.create("added", &Entry::file())?;
+    let mut object = repo.open("added")?;
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    // Rechunking with a checkpoint interval commits the uncommitted changes.
+    let mut options = RechunkOptions::new();
+    options.checkpoint_interval(1);
+    repo.rechunk(NEW_CHUNKING, &options)?;
+    drop(repo);
+
+    let repo: FileRepo = repo_store.open()?;
+
+    assert_that!(repo.exists("removed")).is_false();
+    for path in ["kept", "added"] {
+        let mut actual_data = Vec::new();
+        repo.open(path)?.read_to_end(&mut actual_data)?;
+        assert_that!(actual_data).is_equal_to(&buffer);
+    }
+
+    Ok(())
+}
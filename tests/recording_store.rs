@@ -0,0 +1,157 @@
+#![cfg(all(
+    feature = "encryption",
+    feature = "compression",
+    feature = "store-recording"
+))]
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    read_operation_log, BlockKey, DataStore, MemoryConfig, OpenStore, RecordingConfig, ReplayStore,
+    StoreOperation,
+};
+use common::*;
+use tempfile::TempDir;
+
+mod common;
+
+/// Create a repository through a `RecordingStore`, write an object, and commit.
+fn record_session(log_path: &Path, redact: bool, buffer: &[u8]) -> anyhow::Result<MemoryConfig> {
+    let inner = MemoryConfig::new();
+    let config = RecordingConfig {
+        inner: inner.clone(),
+        log_path: log_path.to_path_buf(),
+        redact,
+    };
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    Ok(inner)
+}
+
+fn open_repo(config: &MemoryConfig) -> acid_store::Result<KeyRepo<String>> {
+    OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(config)
+}
+
+#[rstest]
+fn recorded_operations_are_logged(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let log_path = temp_dir.path().join("store.log");
+    record_session(&log_path, false, &buffer)?;
+
+    let operations = read_operation_log(&log_path)?;
+    let super_write = operations
+        .iter()
+        .find(|entry| entry.operation() == StoreOperation::Write(BlockKey::Super))
+        .expect("The super block write was not recorded.");
+
+    assert_that!(super_write.error()).is_none();
+    assert_that!(super_write.payload()).is_some();
+    assert_that!(super_write.size()).is_equal_to(super_write.payload().map(|p| p.len() as u64));
+
+    Ok(())
+}
+
+#[rstest]
+fn replayed_store_matches_original(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let log_path = temp_dir.path().join("store.log");
+    let original_config = record_session(&log_path, false, &buffer)?;
+
+    let replayed_config = MemoryConfig::new();
+    let mut replay = ReplayStore::new(replayed_config.open()?, &log_path)?;
+    replay.replay_all()?;
+    drop(replay);
+
+    let original_repo = open_repo(&original_config)?;
+    let replayed_repo = open_repo(&replayed_config)?;
+
+    assert_that!(replayed_repo.verify()?.len()).is_equal_to(original_repo.verify()?.len());
+    assert_that!(replayed_repo.verify()?.is_empty()).is_true();
+
+    let mut original_data = Vec::new();
+    original_repo
+        .object("test")
+        .unwrap()
+        .read_to_end(&mut original_data)?;
+    let mut replayed_data = Vec::new();
+    replayed_repo
+        .object("test")
+        .unwrap()
+        .read_to_end(&mut replayed_data)?;
+
+    assert_that!(replayed_data).is_equal_to(&original_data);
+    assert_that!(replayed_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn replay_to_start_leaves_store_empty(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let log_path = temp_dir.path().join("store.log");
+    record_session(&log_path, false, &buffer)?;
+
+    let mut replay = ReplayStore::new(MemoryConfig::new().open()?, &log_path)?;
+    replay.replay_to(0)?;
+
+    assert_that!(replay.position()).is_equal_to(0);
+    assert_that!(replay.read_block(BlockKey::Super)).is_ok_containing(None);
+
+    Ok(())
+}
+
+#[rstest]
+fn redacted_log_omits_payloads(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let log_path = temp_dir.path().join("store.log");
+    record_session(&log_path, true, &buffer)?;
+
+    let operations = read_operation_log(&log_path)?;
+    let writes = operations
+        .iter()
+        .filter(|entry| matches!(entry.operation(), StoreOperation::Write(_)))
+        .collect::<Vec<_>>();
+
+    assert_that!(writes.is_empty()).is_false();
+    for entry in writes {
+        assert_that!(entry.payload()).is_none();
+        assert_that!(entry.size()).is_some();
+        assert_that!(entry.hash()).is_some();
+    }
+
+    let mut replay = ReplayStore::new(MemoryConfig::new().open()?, &log_path)?;
+    assert_that!(replay.replay_all()).is_err_variant(acid_store::Error::InvalidData);
+
+    Ok(())
+}
+
+#[rstest]
+#[cfg(target_os = "linux")]
+fn log_errors_do_not_change_operation_results(buffer: Vec<u8>) -> anyhow::Result<()> {
+    // Every write to `/dev/full` fails.
+    let inner = MemoryConfig::new();
+    let mut store = RecordingConfig {
+        inner: inner.clone(),
+        log_path: "/dev/full".into(),
+        redact: false,
+    }
+    .open()?;
+
+    assert_that!(store.write_block(BlockKey::Super, &buffer)).is_ok();
+    assert_that!(store.read_block(BlockKey::Super)).is_ok_containing(Some(buffer.clone()));
+    assert_that!(store.flush()).is_err();
+    assert_that!(inner.open()?.read_block(BlockKey::Super)).is_ok_containing(Some(buffer));
+
+    Ok(())
+}
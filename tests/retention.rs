@@ -0,0 +1,330 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions, RepoConfig};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore,
+};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The retention period of the data store in these tests.
+const RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The shared state of a `LockedStore`.
+#[derive(Debug)]
+struct LockedState {
+    /// The time each block in the store was written.
+    written: HashMap<BlockKey, SystemTime>,
+
+    /// The keys of blocks which were removed and their age when they were removed.
+    removed: Vec<(BlockKey, Option<Duration>)>,
+
+    /// The keys of blocks which could not be removed because they were too young.
+    rejected: Vec<BlockKey>,
+
+    /// Whether the store reports the modification times of blocks.
+    report_times: bool,
+
+    /// The number of times a data block was read.
+    data_reads: usize,
+}
+
+/// The configuration for opening a `LockedStore`.
+#[derive(Debug, Clone)]
+struct LockedConfig {
+    inner: MemoryConfig,
+    state: Arc<Mutex<LockedState>>,
+}
+
+impl LockedConfig {
+    fn new(report_times: bool) -> Self {
+        Self {
+            inner: MemoryConfig::new(),
+            state: Arc::new(Mutex::new(LockedState {
+                written: HashMap::new(),
+                removed: Vec::new(),
+                rejected: Vec::new(),
+                report_times,
+                data_reads: 0,
+            })),
+        }
+    }
+
+    /// Simulate the passage of time by making every block in the store older by `duration`.
+    fn age_blocks(&self, duration: Duration) {
+        for time in self.state.lock().unwrap().written.values_mut() {
+            *time -= duration;
+        }
+    }
+
+    /// Return the data and header blocks which were removed and their ages, and then forget them.
+    fn take_removed_blocks(&self) -> Vec<(BlockKey, Option<Duration>)> {
+        std::mem::take(&mut self.state.lock().unwrap().removed)
+            .into_iter()
+            .filter(|(key, _)| matches!(key, BlockKey::Data(_) | BlockKey::Header(_)))
+            .collect()
+    }
+
+    /// Return the blocks which the store refused to remove.
+    fn rejected_blocks(&self) -> Vec<BlockKey> {
+        self.state.lock().unwrap().rejected.clone()
+    }
+
+    /// Return the number of times a data block was read, and then reset it.
+    fn take_data_reads(&self) -> usize {
+        std::mem::take(&mut self.state.lock().unwrap().data_reads)
+    }
+}
+
+impl OpenStore for LockedConfig {
+    type Store = LockedStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(LockedStore {
+            inner: self.inner.open()?,
+            state: Arc::clone(&self.state),
+        })
+    }
+}
+
+/// A data store which simulates a backend with an object lock.
+///
+/// Data and header blocks can't be removed until `RETENTION` after they are written. Locks are
+/// exempt so that the repository can be unlocked.
+#[derive(Debug)]
+struct LockedStore {
+    inner: MemoryStore,
+    state: Arc<Mutex<LockedState>>,
+}
+
+impl DataStore for LockedStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.inner.write_block(key, data)?;
+        let mut state = self.state.lock().unwrap();
+        state.written.insert(key, SystemTime::now());
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        if let BlockKey::Data(_) = key {
+            self.state.lock().unwrap().data_reads += 1;
+        }
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let age = state.written.get(&key).and_then(|time| time.elapsed().ok());
+        let is_locked = matches!(key, BlockKey::Data(_) | BlockKey::Header(_))
+            && age.map_or(true, |age| age < RETENTION);
+        if is_locked {
+            state.rejected.push(key);
+            return Err(acid_store::store::Error::msg("The block is locked."));
+        }
+
+        self.inner.remove_block(key)?;
+        state.written.remove(&key);
+        state.removed.push((key, age));
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+
+    fn block_modified_time(
+        &mut self,
+        key: BlockKey,
+    ) -> acid_store::store::Result<Option<SystemTime>> {
+        let state = self.state.lock().unwrap();
+        if state.report_times {
+            Ok(state.written.get(&key).copied())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> acid_store::store::Result<Option<u64>> {
+        self.inner.block_size(key)
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        Some(RETENTION)
+    }
+}
+
+/// Create a repository in the given store, write an object to it, and then remove it.
+///
+/// This leaves the data which was written to the object unreferenced.
+fn write_unreferenced_data(
+    store_config: &LockedConfig,
+    repo_config: RepoConfig,
+    buffer: &[u8],
+) -> anyhow::Result<KeyRepo<String>> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(repo_config)
+        .password(b"Password")
+        .mode(OpenMode::CreateNew)
+        .open(store_config)?;
+    remove_new_object(&mut repo, "unreferenced", buffer)?;
+    Ok(repo)
+}
+
+/// Write `data` to a new object with the given `key`, commit, and then remove it and commit.
+fn remove_new_object(repo: &mut KeyRepo<String>, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    let mut object = repo.insert(key.to_string());
+    object.write_all(data)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    repo.remove(key);
+    repo.commit()?;
+
+    Ok(())
+}
+
+/// Assert that none of the `removed` blocks were younger than the retention period.
+fn assert_no_retained_blocks_removed(removed: &[(BlockKey, Option<Duration>)]) {
+    for (key, age) in removed {
+        assert!(
+            matches!(age, Some(age) if *age >= RETENTION),
+            "{:?} was removed before the retention period expired.",
+            key
+        );
+    }
+}
+
+/// Return whether any of the `removed` blocks are data blocks.
+fn contains_data_blocks(removed: &[(BlockKey, Option<Duration>)]) -> bool {
+    removed
+        .iter()
+        .any(|(key, _)| matches!(key, BlockKey::Data(_)))
+}
+
+#[apply(config)]
+fn clean_does_not_remove_retained_blocks(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = LockedConfig::new(true);
+    let mut repo = write_unreferenced_data(&store_config, config, &buffer)?;
+
+    repo.clean()?;
+
+    assert_that!(store_config.take_removed_blocks()).has_length(0);
+    assert_that!(store_config.rejected_blocks()).has_length(0);
+    assert_that!(repo.stats().retained_size()).is_greater_than(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn clean_does_not_read_retained_blocks(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let store_config = LockedConfig::new(true);
+    let mut repo = write_unreferenced_data(&store_config, fixed_config(), &buffer)?;
+    store_config.take_data_reads();
+
+    repo.clean()?;
+
+    assert_that!(store_config.take_data_reads()).is_equal_to(0);
+    assert_that!(repo.stats().retained_size()).is_greater_than(0);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn clean_removes_exactly_the_expired_blocks(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = LockedConfig::new(true);
+    let mut repo = write_unreferenced_data(&store_config, config, &buffer)?;
+    store_config.age_blocks(RETENTION);
+
+    // Write more unreferenced data which is still within the retention period.
+    remove_new_object(&mut repo, "fresh", &larger_buffer)?;
+
+    repo.clean()?;
+
+    let removed = store_config.take_removed_blocks();
+    assert_that!(contains_data_blocks(&removed)).is_true();
+    assert_no_retained_blocks_removed(&removed);
+    assert_that!(store_config.rejected_blocks()).has_length(0);
+    assert_that!(repo.stats().retained_size()).is_greater_than(0);
+
+    // Once the retention period expires, the remaining blocks are removed.
+    store_config.age_blocks(RETENTION);
+    repo.clean()?;
+
+    let removed = store_config.take_removed_blocks();
+    assert_that!(contains_data_blocks(&removed)).is_true();
+    assert_no_retained_blocks_removed(&removed);
+    assert_that!(store_config.rejected_blocks()).has_length(0);
+
+    // There are no unreferenced data blocks left to remove.
+    repo.clean()?;
+
+    assert_that!(contains_data_blocks(&store_config.take_removed_blocks())).is_false();
+
+    Ok(())
+}
+
+#[apply(config)]
+fn clean_does_not_remove_blocks_with_unknown_modified_time(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = LockedConfig::new(false);
+    let mut repo = write_unreferenced_data(&store_config, config, &buffer)?;
+
+    store_config.age_blocks(RETENTION);
+    repo.clean()?;
+
+    assert_that!(store_config.take_removed_blocks()).has_length(0);
+    assert_that!(store_config.rejected_blocks()).has_length(0);
+
+    Ok(())
+}
+
+#[apply(config)]
+fn clean_preserves_referenced_data_with_retention(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store_config = LockedConfig::new(true);
+    let mut repo = write_unreferenced_data(&store_config, config, &larger_buffer)?;
+
+    let mut object = repo.insert(String::from("referenced"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    store_config.age_blocks(RETENTION);
+    repo.clean()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new()
+        .password(b"Password")
+        .mode(OpenMode::Open)
+        .open(&store_config)?;
+    let mut actual_data = Vec::new();
+    repo.object("referenced")
+        .unwrap()
+        .read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, ErrorClass, ErrorKind, MemoryConfig, MemoryStore,
+    OpenStore, RetryPolicy, RetryingConfig, RetryingStore,
+};
+use common::*;
+
+mod common;
+
+/// The failures which a `FailingStore` has left to return and how many operations it has seen.
+#[derive(Debug, Default)]
+struct Failures {
+    remaining: Vec<io::ErrorKind>,
+    attempts: u32,
+}
+
+/// A data store config whose stores fail operations with a queue of I/O errors.
+#[derive(Debug, Clone)]
+struct FailingConfig {
+    inner: MemoryConfig,
+    failures: Arc<Mutex<Failures>>,
+}
+
+impl FailingConfig {
+    fn new() -> Self {
+        FailingConfig {
+            inner: MemoryConfig::new(),
+            failures: Arc::new(Mutex::new(Failures::default())),
+        }
+    }
+
+    /// Fail the next operations with errors of the given `kinds`, in order.
+    fn fail_with(&self, kinds: &[io::ErrorKind]) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.remaining = kinds.iter().rev().copied().collect();
+        failures.attempts = 0;
+    }
+
+    /// The number of operations since `fail_with` was last called.
+    fn attempts(&self) -> u32 {
+        self.failures.lock().unwrap().attempts
+    }
+}
+
+impl OpenStore for FailingConfig {
+    type Store = FailingStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(FailingStore {
+            inner: self.inner.open()?,
+            failures: Arc::clone(&self.failures),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct FailingStore {
+    inner: MemoryStore,
+    failures: Arc<Mutex<Failures>>,
+}
+
+impl FailingStore {
+    fn check(&self) -> acid_store::store::Result<()> {
+        let mut failures = self.failures.lock().unwrap();
+        failures.attempts += 1;
+        match failures.remaining.pop() {
+            Some(kind) => Err(acid_store::store::Error::new(io::Error::new(
+                kind,
+                "The operation failed.",
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl DataStore for FailingStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.check()?;
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.check()?;
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.check()?;
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.check()?;
+        self.inner.list_blocks(kind)
+    }
+}
+
+/// A policy which retries `max_retries` times without waiting.
+fn immediate(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        initial_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+    }
+}
+
+#[rstest]
+fn errors_are_classified_by_io_error_kind() {
+    let error = |kind| acid_store::store::Error::new(io::Error::new(kind, "Error."));
+
+    assert_that!(ErrorClass::of(&error(io::ErrorKind::ConnectionReset)))
+        .is_equal_to(ErrorClass::Connection);
+    assert_that!(ErrorClass::of(&error(io::ErrorKind::TimedOut))).is_equal_to(ErrorClass::Timeout);
+    assert_that!(ErrorClass::of(&error(io::ErrorKind::PermissionDenied)))
+        .is_equal_to(ErrorClass::Other);
+    assert_that!(ErrorClass::of(&acid_store::store::Error::new(
+        acid_store::Error::Io(io::Error::from(io::ErrorKind::BrokenPipe))
+    )))
+    .is_equal_to(ErrorClass::Connection);
+    assert_that!(ErrorClass::of(&acid_store::store::Error::msg("Error.")))
+        .is_equal_to(ErrorClass::Other);
+}
+
+#[rstest]
+fn transient_errors_are_classified_as_connection_errors() {
+    let error = acid_store::store::Error::msg("Error.").with_kind(ErrorKind::Transient);
+    assert_that!(ErrorClass::of(&error)).is_equal_to(ErrorClass::Connection);
+}
+
+#[rstest]
+fn transient_errors_are_retried(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = FailingConfig::new();
+    let mut store = RetryingStore::new(inner.open()?);
+    store.set_policy(ErrorClass::Connection, immediate(3));
+    store.set_policy(ErrorClass::Timeout, immediate(3));
+
+    inner.fail_with(&[io::ErrorKind::ConnectionReset, io::ErrorKind::TimedOut]);
+    store.write_block(BlockKey::Super, &buffer)?;
+    assert_that!(inner.attempts()).is_equal_to(3);
+
+    inner.fail_with(&[io::ErrorKind::NotConnected]);
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(buffer));
+    assert_that!(inner.attempts()).is_equal_to(2);
+
+    Ok(())
+}
+
+#[rstest]
+fn retries_are_limited_per_class(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = FailingConfig::new();
+    let mut store = RetryingStore::new(inner.open()?);
+    store.set_policy(ErrorClass::Connection, immediate(2));
+
+    inner.fail_with(&[io::ErrorKind::ConnectionRefused; 3]);
+    assert_that!(store.write_block(BlockKey::Super, &buffer).is_err()).is_true();
+    assert_that!(inner.attempts()).is_equal_to(3);
+
+    Ok(())
+}
+
+#[rstest]
+fn other_errors_are_not_retried_by_default(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = FailingConfig::new();
+    let mut store = RetryingConfig::new(inner.clone()).open()?;
+
+    inner.fail_with(&[io::ErrorKind::PermissionDenied]);
+    assert_that!(store.write_block(BlockKey::Super, &buffer).is_err()).is_true();
+    assert_that!(inner.attempts()).is_equal_to(1);
+    assert_that!(store.policy(ErrorClass::Other)).is_equal_to(RetryPolicy::never());
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_survives_transient_errors(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = FailingConfig::new();
+    let config = RetryingConfig {
+        connection: immediate(1),
+        ..RetryingConfig::new(inner.clone())
+    };
+
+    inner.fail_with(&[io::ErrorKind::ConnectionReset]);
+    create_store_repo(&config, encoding_config(), &buffer)?;
+
+    inner.fail_with(&[io::ErrorKind::ConnectionReset]);
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
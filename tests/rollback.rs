@@ -0,0 +1,390 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::time::SystemTime;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{
+    Commit, DestructivePolicy, Encryption, Manifest, ManifestSource, OpenOptions, RepoInfo,
+    ResourceLimit,
+};
+use acid_store::store::{BlockId, BlockKey, BlockType, DataStore, OpenStore};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The committed state of a repository, as seen by a newly opened repository.
+#[derive(Debug, PartialEq, Eq)]
+struct CommittedState {
+    contents: HashMap<String, Vec<u8>>,
+    trash: HashSet<String>,
+    stats: [u64; 5],
+    info: RepoInfo,
+    manifest: Manifest<String>,
+}
+
+/// How uncommitted changes are discarded.
+#[derive(Debug, Clone, Copy)]
+enum Discard {
+    /// Drop the repository without committing.
+    Drop,
+
+    /// Roll back the changes and then commit.
+    Rollback,
+
+    /// Clean the repository and then drop it without committing.
+    Clean,
+}
+
+/// Create a repository in `repo_store` with some committed objects.
+fn create_populated_repo(
+    repo_store: &RepoStore,
+    buffer: &[u8],
+    larger_buffer: &[u8],
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_object(&mut repo, "first", buffer)?;
+    write_object(&mut repo, "second", larger_buffer)?;
+    write_object(&mut repo, "sparse", buffer)?;
+    repo.object("sparse")
+        .unwrap()
+        .set_len(buffer.len() as u64 * 2)?;
+    write_object(&mut repo, "trashed", larger_buffer)?;
+    repo.remove_to_trash("trashed")?;
+    repo.commit()?;
+    Ok(())
+}
+
+/// Open the repository in `repo_store`, check its integrity, and return its committed state.
+fn committed_state(repo_store: &RepoStore) -> anyhow::Result<CommittedState> {
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.verify()?).is_empty();
+
+    let mut contents = HashMap::new();
+    for key in repo.keys() {
+        let mut data = Vec::new();
+        repo.object(key).unwrap().read_to_end(&mut data)?;
+        contents.insert(key.clone(), data);
+    }
+
+    let stats = repo.stats();
+
+    Ok(CommittedState {
+        contents,
+        trash: repo.list_trash().map(|(key, _)| key.clone()).collect(),
+        stats: [
+            stats.apparent_size(),
+            stats.actual_size(),
+            stats.repo_size(),
+            stats.trash_size(),
+            stats.retained_size(),
+        ],
+        info: repo.info(),
+        manifest: repo.manifest(ManifestSource::Committed)?,
+    })
+}
+
+/// Return the contents of every block in the data store in `repo_store` other than data blocks.
+///
+/// Lock blocks are excluded as well because they only exist while the repository is open.
+fn metadata_blocks(repo_store: &RepoStore) -> anyhow::Result<HashMap<BlockKey, Vec<u8>>> {
+    let mut store = repo_store.store.open()?;
+    let mut keys = vec![BlockKey::Super, BlockKey::Version];
+    keys.extend(
+        store
+            .list_blocks(BlockType::Header)?
+            .into_iter()
+            .map(BlockKey::Header),
+    );
+
+    let mut blocks = HashMap::new();
+    for key in keys {
+        blocks.insert(key, store.read_block(key)?.unwrap());
+    }
+    Ok(blocks)
+}
+
+/// Return the IDs of the data blocks in the data store in `repo_store`.
+fn data_blocks(repo_store: &RepoStore) -> anyhow::Result<HashSet<BlockId>> {
+    let mut store = repo_store.store.open()?;
+    Ok(store.list_blocks(BlockType::Data)?.into_iter().collect())
+}
+
+/// Make uncommitted changes to the repository in `repo_store` with `mutation`, discard them, and
+/// then assert that the committed state of the repository is unchanged.
+fn assert_discarded(
+    repo_store: &RepoStore,
+    discard: Discard,
+    mutation: impl FnOnce(&mut KeyRepo<String>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let state_before = committed_state(repo_store)?;
+    let blocks_before = metadata_blocks(repo_store)?;
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    mutation(&mut repo)?;
+    match discard {
+        Discard::Drop => {}
+        Discard::Rollback => {
+            repo.rollback()?;
+            repo.commit()?;
+        }
+        Discard::Clean => repo.clean()?,
+    }
+    drop(repo);
+
+    assert_that!(committed_state(repo_store)?).is_equal_to(&state_before);
+
+    // Committing or cleaning the repository may write a new header, but dropping it without
+    // committing must not write anything but data.
+    if let Discard::Drop = discard {
+        assert_that!(metadata_blocks(repo_store)?).is_equal_to(&blocks_before);
+    }
+
+    Ok(())
+}
+
+/// Assert that the changes made by `mutation` are discarded in every way they can be discarded.
+fn assert_always_discarded(
+    repo_store: &RepoStore,
+    mutation: impl Fn(&mut KeyRepo<String>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    for discard in [Discard::Drop, Discard::Rollback, Discard::Clean] {
+        assert_discarded(repo_store, discard, &mutation)?;
+    }
+    Ok(())
+}
+
+#[apply(store_config)]
+fn inserted_object_is_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+    smaller_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        write_object(repo, "new", &smaller_buffer)
+    })
+}
+
+#[apply(store_config)]
+fn overwritten_object_is_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+    smaller_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        write_object(repo, "first", &smaller_buffer)
+    })
+}
+
+#[apply(store_config)]
+fn removed_object_is_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        repo.remove("first");
+        Ok(())
+    })
+}
+
+#[apply(store_config)]
+fn copied_object_is_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        repo.copy("second", String::from("copy"));
+        Ok(())
+    })
+}
+
+#[apply(store_config)]
+fn resized_object_is_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        repo.object("second")
+            .unwrap()
+            .set_len(larger_buffer.len() as u64 / 2)?;
+        repo.object("first")
+            .unwrap()
+            .set_len(buffer.len() as u64 * 3)?;
+        Ok(())
+    })
+}
+
+#[apply(store_config)]
+fn trash_changes_are_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        repo.restore_from_trash("trashed")?;
+        repo.remove_to_trash("first")?;
+        repo.purge_trash(SystemTime::now());
+        Ok(())
+    })
+}
+
+#[apply(store_config)]
+fn cleared_instance_is_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        repo.clear_instance();
+        Ok(())
+    })
+}
+
+#[apply(store_config)]
+fn metadata_changes_are_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        let mut policy = DestructivePolicy::default();
+        policy.enabled = true;
+        repo.set_destructive_policy(repo_store.password.as_bytes(), policy)?;
+        repo.change_password(
+            b"new password",
+            ResourceLimit::Interactive,
+            ResourceLimit::Interactive,
+        );
+        repo.set_verify_reads(false);
+        repo.set_max_header_size(Some(1024 * 1024));
+        Ok(())
+    })
+}
+
+#[apply(store_config)]
+fn combined_changes_are_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+    smaller_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    assert_always_discarded(&repo_store, |repo| {
+        write_object(repo, "new", &larger_buffer)?;
+        write_object(repo, "first", &smaller_buffer)?;
+        repo.copy("new", String::from("copy"));
+        repo.remove("second");
+        repo.object("sparse").unwrap().set_len(0)?;
+        repo.restore_from_trash("trashed")?;
+        repo.change_password(
+            b"new password",
+            ResourceLimit::Interactive,
+            ResourceLimit::Interactive,
+        );
+        Ok(())
+    })
+}
+
+#[rstest]
+fn old_password_works_after_discarding_password_change(
+    mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+
+    for discard in [Discard::Drop, Discard::Rollback, Discard::Clean] {
+        assert_discarded(&repo_store, discard, |repo| {
+            repo.change_password(
+                b"new password",
+                ResourceLimit::Interactive,
+                ResourceLimit::Interactive,
+            );
+            Ok(())
+        })?;
+
+        let result: acid_store::Result<KeyRepo<String>> = OpenOptions::new()
+            .password(b"new password")
+            .open(&repo_store.store);
+        assert_that!(result).is_err_variant(acid_store::Error::Password);
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn password_change_can_be_committed_after_clean(
+    mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    let state_before = committed_state(&repo_store)?;
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    repo.change_password(
+        b"new password",
+        ResourceLimit::Interactive,
+        ResourceLimit::Interactive,
+    );
+    repo.clean()?;
+    repo.commit()?;
+    drop(repo);
+
+    repo_store.password = String::from("new password");
+    let state_after = committed_state(&repo_store)?;
+
+    assert_that!(state_after.contents).is_equal_to(&state_before.contents);
+    assert_that!(state_after.manifest).is_equal_to(&state_before.manifest);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn discarded_data_is_reclaimed_by_next_commit(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+    smaller_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    create_populated_repo(&repo_store, &buffer, &larger_buffer)?;
+    let committed_blocks = data_blocks(&repo_store)?;
+    let state_before = committed_state(&repo_store)?;
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    write_object(&mut repo, "new", &larger_buffer)?;
+    write_object(&mut repo, "first", &smaller_buffer)?;
+    drop(repo);
+
+    // The data written by the discarded changes is left behind as garbage.
+    let garbage_blocks = data_blocks(&repo_store)?;
+    assert_that!(garbage_blocks.is_superset(&committed_blocks)).is_true();
+    assert_that!(garbage_blocks.len()).is_greater_than(committed_blocks.len());
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    assert_that!(data_blocks(&repo_store)?).is_equal_to(&committed_blocks);
+    assert_that!(committed_state(&repo_store)?).is_equal_to(&state_before);
+
+    Ok(())
+}
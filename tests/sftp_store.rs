@@ -0,0 +1,94 @@
+#![cfg(feature = "store-sftp")]
+
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::time::{Duration, Instant};
+
+use acid_store::store::{BlockKey, DataStore, OpenStore, SftpAuth, SftpConfig};
+use serial_test::serial;
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// Return the address of a local port which no server is listening on.
+fn closed_port() -> SocketAddr {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    listener.local_addr().unwrap()
+}
+
+/// Return a config for the SFTP server used for testing.
+fn server_config() -> SftpConfig {
+    SftpConfig::new(
+        dotenv::var("SFTP_SERVER").unwrap().parse().unwrap(),
+        SftpAuth::Password {
+            username: dotenv::var("SFTP_USERNAME").unwrap(),
+            password: dotenv::var("SFTP_PASSWORD").unwrap(),
+        },
+        dotenv::var("SFTP_PATH").unwrap(),
+    )
+}
+
+/// Return a config for a server which refuses connections.
+fn unreachable_config() -> SftpConfig {
+    SftpConfig::new(
+        closed_port(),
+        SftpAuth::Password {
+            username: String::from("username"),
+            password: String::from("password"),
+        },
+        "store",
+    )
+}
+
+#[rstest]
+fn new_config_has_defaults() {
+    let config = unreachable_config();
+    assert_that!(config.reuse_connection).is_true();
+    assert_that!(config.max_retries).is_equal_to(3);
+    assert_that!(config.retry_delay).is_equal_to(Duration::from_millis(500));
+}
+
+#[rstest]
+fn refused_connections_are_retried() {
+    let mut config = unreachable_config();
+    config.max_retries = 2;
+    config.retry_delay = Duration::from_millis(50);
+
+    let start = Instant::now();
+    assert_that!(config.open()).is_err();
+
+    // We wait 50 milliseconds before the first retry and 100 milliseconds before the second.
+    assert_that!(start.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(150));
+}
+
+#[rstest]
+fn connections_are_not_retried_without_retries() {
+    let mut config = unreachable_config();
+    config.max_retries = 0;
+    config.retry_delay = Duration::from_secs(10);
+
+    let start = Instant::now();
+    assert_that!(config.open()).is_err();
+    assert_that!(start.elapsed()).is_less_than(Duration::from_secs(10));
+}
+
+#[rstest]
+#[case::shared(true)]
+#[case::unshared(false)]
+#[serial(data_store)]
+fn stores_with_same_server_can_be_used_together(#[case] reuse_connection: bool, buffer: Vec<u8>) {
+    let mut config = server_config();
+    config.reuse_connection = reuse_connection;
+    let mut first = config.open().unwrap();
+    let mut second = config.open().unwrap();
+    let id = Uuid::new_v4().into();
+
+    assert_that!(first.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(second.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer.clone()));
+
+    // The connection stays open as long as any store is using it.
+    drop(first);
+    assert_that!(second.remove_block(BlockKey::Data(id))).is_ok();
+    assert_that!(second.read_block(BlockKey::Data(id))).is_ok_containing(None);
+}
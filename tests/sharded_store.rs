@@ -0,0 +1,113 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::store::{
+    BlockKey, BlockType, DataStore, MemoryConfig, OpenStore, ShardedConfig, ShardedStore,
+};
+use common::*;
+use uuid::Uuid;
+
+mod common;
+
+/// Return the number of data blocks in the store opened by `config`.
+fn count_data_blocks(config: &MemoryConfig) -> anyhow::Result<usize> {
+    Ok(config.open()?.list_blocks(BlockType::Data)?.len())
+}
+
+#[rstest]
+fn blocks_are_distributed_across_shards(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let shards = vec![
+        MemoryConfig::new(),
+        MemoryConfig::new(),
+        MemoryConfig::new(),
+    ];
+    let mut store = ShardedConfig::new(shards.clone()).open()?;
+
+    let ids = (0..64).map(|_| Uuid::new_v4().into()).collect::<Vec<_>>();
+    for id in &ids {
+        store.write_block(BlockKey::Data(*id), &buffer)?;
+    }
+
+    for shard in &shards {
+        assert_that!(count_data_blocks(shard)?).is_greater_than(0);
+    }
+    assert_that!(store.list_blocks(BlockType::Data)?).has_length(ids.len());
+    for id in &ids {
+        assert_that!(store.read_block(BlockKey::Data(*id))?).is_equal_to(Some(buffer.clone()));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn superblock_is_stored_in_first_shard(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let first = MemoryConfig::new();
+    let mut store = ShardedStore::new(vec![first.open()?, MemoryConfig::new().open()?]);
+
+    store.write_block(BlockKey::Super, &buffer)?;
+
+    assert_that!(first.open()?.read_block(BlockKey::Super)?).is_equal_to(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn rebalance_moves_blocks_to_new_shard(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let first = MemoryConfig::new();
+    let second = MemoryConfig::new();
+    let mut store = ShardedStore::new(vec![first.open()?]);
+
+    let ids = (0..64).map(|_| Uuid::new_v4().into()).collect::<Vec<_>>();
+    for id in &ids {
+        store.write_block(BlockKey::Data(*id), &buffer)?;
+    }
+
+    store.add_shard(second.open()?);
+
+    // Blocks which haven't been moved yet can still be read.
+    for id in &ids {
+        assert_that!(store.read_block(BlockKey::Data(*id))?).is_equal_to(Some(buffer.clone()));
+    }
+
+    let moved = store.rebalance()?;
+
+    assert_that!(moved).is_greater_than(0);
+    assert_that!(count_data_blocks(&second)? as u64).is_equal_to(moved);
+    assert_that!(count_data_blocks(&first)? + count_data_blocks(&second)?).is_equal_to(ids.len());
+    assert_that!(store.rebalance()?).is_equal_to(0);
+    for id in &ids {
+        assert_that!(store.read_block(BlockKey::Data(*id))?).is_equal_to(Some(buffer.clone()));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn removed_blocks_are_removed_from_every_shard(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let first = MemoryConfig::new();
+    let mut store = ShardedStore::new(vec![first.open()?]);
+    let ids = (0..16).map(|_| Uuid::new_v4().into()).collect::<Vec<_>>();
+    for id in &ids {
+        store.write_block(BlockKey::Data(*id), &buffer)?;
+    }
+    store.add_shard(MemoryConfig::new().open()?);
+
+    for id in &ids {
+        store.remove_block(BlockKey::Data(*id))?;
+    }
+
+    assert_that!(count_data_blocks(&first)?).is_equal_to(0);
+    assert_that!(store.list_blocks(BlockType::Data)?).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn repository_spans_shards(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let config = ShardedConfig::new(vec![MemoryConfig::new(), MemoryConfig::new()]);
+
+    create_store_repo(&config, encoding_config(), &buffer)?;
+
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(buffer);
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::Write;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::Commit;
+use common::*;
+
+mod common;
+
+/// Replace the contents of the object with the given `key` with `data`.
+fn overwrite_object(repo: &mut KeyRepo<String>, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    let mut object = repo.object(key).unwrap();
+    object.set_len(0)?;
+    object.write_all(data)?;
+    object.commit()?;
+    Ok(())
+}
+
+#[rstest]
+fn restoring_snapshot_reverts_changes(
+    mut repo: KeyRepo<String>,
+    smaller_buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    overwrite_object(&mut repo, "test", &smaller_buffer)?;
+    let snapshot = repo.snapshot("test")?;
+    overwrite_object(&mut repo, "test", &larger_buffer)?;
+
+    repo.restore_snapshot("test", snapshot)?;
+
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&smaller_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn snapshot_can_be_restored_more_than_once(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    overwrite_object(&mut repo, "test", b"original")?;
+    let snapshot = repo.snapshot("test")?;
+
+    overwrite_object(&mut repo, "test", b"first")?;
+    repo.restore_snapshot("test", snapshot)?;
+    overwrite_object(&mut repo, "test", b"second")?;
+    repo.restore_snapshot("test", snapshot)?;
+
+    assert_that!(read_object(&repo, "test")?).is_equal_to(b"original".to_vec());
+
+    Ok(())
+}
+
+#[rstest]
+fn snapshots_are_listed_oldest_first(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    let first = repo.snapshot("test")?;
+    let second = repo.snapshot("test")?;
+
+    assert_that!(repo.snapshots("test").collect::<Vec<_>>()).is_equal_to(vec![first, second]);
+
+    Ok(())
+}
+
+#[rstest]
+fn snapshot_of_nonexistent_object_errs(mut repo: KeyRepo<String>) {
+    assert_that!(repo.snapshot("test")).is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn restoring_removed_snapshot_errs(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    let snapshot = repo.snapshot("test")?;
+
+    assert_that!(repo.remove_snapshot("test", snapshot)).is_true();
+    assert_that!(repo.remove_snapshot("test", snapshot)).is_false();
+    assert_that!(repo.restore_snapshot("test", snapshot))
+        .is_err_variant(acid_store::Error::NotFound);
+
+    Ok(())
+}
+
+#[rstest]
+fn removing_object_removes_snapshots(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    let snapshot = repo.snapshot("test")?;
+
+    repo.remove("test");
+    repo.insert("test".into());
+
+    assert_that!(repo.snapshots("test").count()).is_equal_to(0);
+    assert_that!(repo.restore_snapshot("test", snapshot))
+        .is_err_variant(acid_store::Error::NotFound);
+
+    Ok(())
+}
+
+#[rstest]
+fn removed_snapshots_are_cleaned(mut repo: KeyRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    overwrite_object(&mut repo, "test", &buffer)?;
+    let snapshot = repo.snapshot("test")?;
+    overwrite_object(&mut repo, "test", b"")?;
+    assert_that!(repo.stats().repo_size()).is_greater_than(0);
+
+    repo.remove_snapshot("test", snapshot);
+
+    assert_that!(repo.stats().repo_size()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn snapshots_persist_after_commit(repo_store: RepoStore, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert("test".into());
+    overwrite_object(&mut repo, "test", &buffer)?;
+    let snapshot = repo.snapshot("test")?;
+    overwrite_object(&mut repo, "test", b"")?;
+    repo.commit()?;
+    drop(repo);
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    repo.restore_snapshot("test", snapshot)?;
+
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn clearing_instance_removes_snapshots(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    overwrite_object(&mut repo, "test", b"data")?;
+    repo.snapshot("test")?;
+
+    repo.clear_instance();
+
+    assert_that!(repo.stats().repo_size()).is_equal_to(0);
+    assert_that!(repo.snapshots("test").count()).is_equal_to(0);
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io;
+
+use acid_store::store::{
+    BlockKey, DataStore, Error, ErrorKind, MemoryConfig, OpenStore, ReadOnlyConfig,
+};
+use common::*;
+
+mod common;
+
+#[rstest]
+#[case(io::ErrorKind::NotFound, ErrorKind::NotFound)]
+#[case(io::ErrorKind::PermissionDenied, ErrorKind::PermissionDenied)]
+#[case(io::ErrorKind::ConnectionReset, ErrorKind::Transient)]
+#[case(io::ErrorKind::TimedOut, ErrorKind::Transient)]
+#[case(io::ErrorKind::InvalidData, ErrorKind::Corrupt)]
+#[case(io::ErrorKind::Unsupported, ErrorKind::Unsupported)]
+#[case(io::ErrorKind::Other, ErrorKind::Other)]
+fn io_errors_are_classified_by_kind(#[case] io_kind: io::ErrorKind, #[case] kind: ErrorKind) {
+    assert_that!(Error::new(io::Error::new(io_kind, "Error.")).kind()).is_equal_to(kind);
+    assert_that!(Error::new(acid_store::Error::Io(io::Error::from(io_kind))).kind())
+        .is_equal_to(kind);
+}
+
+#[rstest]
+#[case(acid_store::Error::ReadOnly, ErrorKind::PermissionDenied)]
+#[case(acid_store::Error::Corrupt, ErrorKind::Corrupt)]
+#[case(acid_store::Error::InvalidData, ErrorKind::Corrupt)]
+#[case(acid_store::Error::UnsupportedStore, ErrorKind::Unsupported)]
+#[case(acid_store::Error::AlreadyExists, ErrorKind::Other)]
+fn library_errors_are_classified_by_variant(
+    #[case] error: acid_store::Error,
+    #[case] kind: ErrorKind,
+) {
+    assert_that!(Error::new(error).kind()).is_equal_to(kind);
+}
+
+#[rstest]
+fn wrapped_store_errors_keep_their_kind() {
+    let inner = Error::msg("Error.").with_kind(ErrorKind::Transient);
+    let error = Error::new(acid_store::Error::Store(inner));
+
+    assert_that!(error.kind()).is_equal_to(ErrorKind::Transient);
+    assert_that!(error.is_transient()).is_true();
+}
+
+#[rstest]
+fn messages_are_other_errors() {
+    let error = Error::msg("Error.");
+
+    assert_that!(error.kind()).is_equal_to(ErrorKind::Other);
+    assert_that!(error.is_transient()).is_false();
+}
+
+#[rstest]
+fn read_only_store_errors_are_permission_denied(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut store = ReadOnlyConfig::new(MemoryConfig::new()).open()?;
+    let error = store.write_block(BlockKey::Super, &buffer).unwrap_err();
+
+    assert_that!(error.kind()).is_equal_to(ErrorKind::PermissionDenied);
+
+    Ok(())
+}
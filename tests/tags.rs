@@ -0,0 +1,194 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashSet;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, RestoreSavepoint, SwitchInstance, DEFAULT_INSTANCE};
+use acid_store::uuid::Uuid;
+use common::*;
+
+mod common;
+
+type TestMetadata = (String, u32);
+
+#[rstest]
+fn set_and_get_metadata(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    let metadata: TestMetadata = ("owner".into(), 42);
+
+    repo.set_metadata("test", &metadata)?;
+
+    assert_that!(repo.get_metadata::<_, TestMetadata>("test")).is_ok_containing(Some(metadata));
+
+    Ok(())
+}
+
+#[rstest]
+fn object_without_metadata_has_none(mut repo: KeyRepo<String>) {
+    repo.insert("test".into());
+
+    assert_that!(repo.get_metadata::<_, TestMetadata>("test")).is_ok_containing(None);
+}
+
+#[rstest]
+fn metadata_of_nonexistent_object_errs(mut repo: KeyRepo<String>) {
+    assert_that!(repo.set_metadata("test", &1u32)).is_err_variant(acid_store::Error::NotFound);
+    assert_that!(repo.get_metadata::<_, u32>("test")).is_err_variant(acid_store::Error::NotFound);
+    assert_that!(repo.add_tag("test", "tag")).is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn deserializing_metadata_to_wrong_type_errs(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    repo.set_metadata("test", &1u32)?;
+
+    assert_that!(repo.get_metadata::<_, String>("test"))
+        .is_err_variant(acid_store::Error::Deserialize);
+
+    Ok(())
+}
+
+#[rstest]
+fn remove_metadata(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    repo.set_metadata("test", &1u32)?;
+
+    assert_that!(repo.remove_metadata("test")).is_true();
+    assert_that!(repo.remove_metadata("test")).is_false();
+    assert_that!(repo.get_metadata::<_, u32>("test")).is_ok_containing(None);
+
+    Ok(())
+}
+
+#[rstest]
+fn keys_with_tag_returns_tagged_objects(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("first".into());
+    repo.insert("second".into());
+    repo.insert("third".into());
+
+    assert_that!(repo.add_tag("first", "red")).is_ok_containing(true);
+    assert_that!(repo.add_tag("first", "red")).is_ok_containing(false);
+    repo.add_tag("second", "red")?;
+    repo.add_tag("second", "blue")?;
+
+    assert_that!(repo.keys_with_tag("red").cloned().collect::<HashSet<_>>())
+        .is_equal_to(HashSet::from(["first".to_string(), "second".to_string()]));
+    assert_that!(repo.keys_with_tag("blue").cloned().collect::<Vec<_>>())
+        .is_equal_to(vec!["second".to_string()]);
+    assert_that!(repo.keys_with_tag("green").count()).is_equal_to(0);
+    assert_that!(repo.tags("second").collect::<Vec<_>>()).is_equal_to(vec!["blue", "red"]);
+
+    Ok(())
+}
+
+#[rstest]
+fn remove_tag(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    repo.add_tag("test", "tag")?;
+
+    assert_that!(repo.remove_tag("test", "tag")).is_true();
+    assert_that!(repo.remove_tag("test", "tag")).is_false();
+    assert_that!(repo.keys_with_tag("tag").count()).is_equal_to(0);
+    assert_that!(repo.tags("test").count()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn removing_object_removes_attributes(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("removed".into());
+    repo.insert("trashed".into());
+    for key in ["removed", "trashed"] {
+        repo.set_metadata(key, &1u32)?;
+        repo.add_tag(key, "tag")?;
+    }
+
+    repo.remove("removed");
+    repo.remove_to_trash("trashed")?;
+    repo.insert("removed".into());
+
+    assert_that!(repo.keys_with_tag("tag").count()).is_equal_to(0);
+    assert_that!(repo.get_metadata::<_, u32>("removed")).is_ok_containing(None);
+
+    Ok(())
+}
+
+#[rstest]
+fn replacing_object_keeps_attributes(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    repo.set_metadata("test", &1u32)?;
+    repo.add_tag("test", "tag")?;
+
+    repo.insert("test".into());
+
+    assert_that!(repo.get_metadata::<_, u32>("test")).is_ok_containing(Some(1));
+    assert_that!(repo.tags("test").collect::<Vec<_>>()).is_equal_to(vec!["tag"]);
+
+    Ok(())
+}
+
+#[rstest]
+fn attributes_persist_after_commit(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert("test".into());
+    repo.set_metadata("test", &1u32)?;
+    repo.add_tag("test", "tag")?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(repo.get_metadata::<_, u32>("test")).is_ok_containing(Some(1));
+    assert_that!(repo.keys_with_tag("tag").cloned().collect::<Vec<_>>())
+        .is_equal_to(vec!["test".to_string()]);
+
+    Ok(())
+}
+
+#[rstest]
+fn attributes_are_rolled_back(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    repo.add_tag("test", "committed")?;
+    repo.commit()?;
+
+    repo.add_tag("test", "uncommitted")?;
+    repo.set_metadata("test", &1u32)?;
+    repo.rollback()?;
+
+    assert_that!(repo.tags("test").collect::<Vec<_>>()).is_equal_to(vec!["committed"]);
+    assert_that!(repo.keys_with_tag("uncommitted").count()).is_equal_to(0);
+    assert_that!(repo.get_metadata::<_, u32>("test")).is_ok_containing(None);
+
+    Ok(())
+}
+
+#[rstest]
+fn attributes_are_restored_from_savepoint(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    let savepoint = repo.savepoint()?;
+
+    repo.add_tag("test", "tag")?;
+    repo.restore(&savepoint)?;
+
+    assert_that!(repo.tags("test").count()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn attributes_are_separate_between_instances(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert("test".into());
+    repo.add_tag("test", "tag")?;
+
+    let instance = Uuid::new_v4().into();
+    let mut repo: KeyRepo<String> = repo.switch_instance(instance)?;
+    repo.insert("test".into());
+
+    assert_that!(repo.tags("test").count()).is_equal_to(0);
+
+    let repo: KeyRepo<String> = repo.switch_instance(DEFAULT_INSTANCE)?;
+
+    assert_that!(repo.tags("test").collect::<Vec<_>>()).is_equal_to(vec!["tag"]);
+
+    Ok(())
+}
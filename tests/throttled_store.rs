@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+use acid_store::store::{
+    BlockKey, BlockType, DataStore, MemoryConfig, OpenStore, ThrottleLimits, ThrottledConfig,
+};
+use common::*;
+
+mod common;
+
+#[rstest]
+fn operations_are_rate_limited() -> anyhow::Result<()> {
+    let mut store = ThrottledConfig {
+        inner: MemoryConfig::new(),
+        limits: ThrottleLimits {
+            operations_per_second: Some(20),
+            ..ThrottleLimits::default()
+        },
+    }
+    .open()?;
+
+    // The first 20 operations are allowed as a burst.
+    let start = Instant::now();
+    for _ in 0..30 {
+        store.list_blocks(BlockType::Data)?;
+    }
+
+    assert_that!(start.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(400));
+
+    Ok(())
+}
+
+#[rstest]
+fn write_bandwidth_is_limited(#[with(5000)] fixed_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut store = ThrottledConfig {
+        inner: MemoryConfig::new(),
+        limits: ThrottleLimits {
+            write_bytes_per_second: Some(10_000),
+            ..ThrottleLimits::default()
+        },
+    }
+    .open()?;
+
+    let start = Instant::now();
+    for _ in 0..3 {
+        store.write_block(BlockKey::Super, &fixed_buffer)?;
+    }
+
+    assert_that!(start.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(400));
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(fixed_buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn large_reads_delay_later_reads(#[with(20_000)] fixed_buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    inner.open()?.write_block(BlockKey::Super, &fixed_buffer)?;
+    let mut store = ThrottledConfig {
+        inner,
+        limits: ThrottleLimits {
+            read_bytes_per_second: Some(10_000),
+            ..ThrottleLimits::default()
+        },
+    }
+    .open()?;
+
+    // A block larger than the limit is still read immediately.
+    let start = Instant::now();
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(fixed_buffer));
+    assert_that!(start.elapsed()).is_less_than(Duration::from_millis(500));
+
+    store.read_block(BlockKey::Super)?;
+    assert_that!(start.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(900));
+
+    Ok(())
+}
+
+#[rstest]
+fn unlimited_store_is_not_throttled(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut store = ThrottledConfig {
+        inner: MemoryConfig::new(),
+        limits: ThrottleLimits::default(),
+    }
+    .open()?;
+
+    let start = Instant::now();
+    for _ in 0..100 {
+        store.write_block(BlockKey::Super, &buffer)?;
+    }
+
+    assert_that!(start.elapsed()).is_less_than(Duration::from_secs(1));
+
+    Ok(())
+}
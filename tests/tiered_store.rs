@@ -0,0 +1,160 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::store::{
+    BlockKey, BlockType, DataStore, MemoryConfig, OpenStore, Tier, TierPolicy, TieredConfig,
+    TieredStore,
+};
+use common::*;
+
+mod common;
+
+#[rstest]
+fn default_policy_keeps_everything_in_fast_tier(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let fast = MemoryConfig::new();
+    let cold = MemoryConfig::new();
+    let config = TieredConfig {
+        fast: fast.clone(),
+        cold: cold.clone(),
+        policy: TierPolicy::default(),
+    };
+
+    create_store_repo(&config, encoding_config(), &buffer)?;
+
+    assert_that!(read_store_object(&fast, "test")?).is_equal_to(&buffer);
+    assert_that!(cold.open()?.list_blocks(BlockType::Data)?).is_empty();
+    assert_that!(cold.open()?.read_block(BlockKey::Super)?).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn new_data_can_be_written_to_cold_tier(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let fast = MemoryConfig::new();
+    let cold = MemoryConfig::new();
+    let config = TieredConfig {
+        fast: fast.clone(),
+        cold: cold.clone(),
+        policy: TierPolicy {
+            new_data: Tier::Cold,
+            ..TierPolicy::default()
+        },
+    };
+
+    create_store_repo(&config, encoding_config(), &buffer)?;
+
+    assert_that!(fast.open()?.list_blocks(BlockType::Data)?).is_empty();
+    assert_that!(cold.open()?.list_blocks(BlockType::Data)?).is_not_empty();
+    assert_that!(fast.open()?.read_block(BlockKey::Super)?).is_some();
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn blocks_can_be_moved_between_tiers(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let fast = MemoryConfig::new();
+    let cold = MemoryConfig::new();
+    let config = TieredConfig {
+        fast: fast.clone(),
+        cold: cold.clone(),
+        policy: TierPolicy::default(),
+    };
+    create_store_repo(&config, encoding_config(), &buffer)?;
+    let mut store = config.open()?;
+    let data_blocks = store.list_blocks(BlockType::Data)?;
+
+    let moved = store.move_all(BlockType::Data, Tier::Cold)?;
+
+    assert_that!(moved).is_equal_to(data_blocks.len() as u64);
+    assert_that!(fast.open()?.list_blocks(BlockType::Data)?).is_empty();
+    for id in &data_blocks {
+        assert_that!(store.tier_of(BlockKey::Data(*id))?).is_equal_to(Some(Tier::Cold));
+    }
+    assert_that!(store.list_blocks(BlockType::Data)?.len()).is_equal_to(data_blocks.len());
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+
+    // Moving a block to the tier it's already in does nothing.
+    assert_that!(store.move_block(BlockKey::Data(data_blocks[0]), Tier::Cold)?).is_false();
+    assert_that!(store.move_block(BlockKey::Data(data_blocks[0]), Tier::Fast)?).is_true();
+    assert_that!(store.tier_of(BlockKey::Data(data_blocks[0]))?).is_equal_to(Some(Tier::Fast));
+
+    Ok(())
+}
+
+#[rstest]
+fn changing_policy_never_reads_stale_metadata() -> anyhow::Result<()> {
+    let mut store = TieredStore::new(
+        MemoryConfig::new().open()?,
+        MemoryConfig::new().open()?,
+        TierPolicy::default(),
+    );
+    let cold_metadata = TierPolicy {
+        metadata: Tier::Cold,
+        ..TierPolicy::default()
+    };
+
+    store.write_block(BlockKey::Super, b"first")?;
+    store.set_policy(cold_metadata);
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(b"first".to_vec()));
+
+    store.write_block(BlockKey::Super, b"second")?;
+    store.set_policy(TierPolicy::default());
+
+    assert_that!(store.read_block(BlockKey::Super)?).is_equal_to(Some(b"second".to_vec()));
+    assert_that!(store.tier_of(BlockKey::Super)?).is_equal_to(Some(Tier::Cold));
+
+    Ok(())
+}
+
+#[rstest]
+fn migrate_does_nothing_without_lifetime(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let config = TieredConfig {
+        fast: MemoryConfig::new(),
+        cold: MemoryConfig::new(),
+        policy: TierPolicy::default(),
+    };
+    create_store_repo(&config, encoding_config(), &buffer)?;
+
+    assert_that!(config.open()?.migrate()?).is_equal_to(0);
+
+    Ok(())
+}
+
+#[cfg(feature = "store-directory")]
+#[rstest]
+fn migrate_moves_old_data_to_cold_tier(
+    temp_dir: tempfile::TempDir,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use acid_store::store::DirectoryConfig;
+
+    let fast = DirectoryConfig::new(temp_dir.path().join("fast"));
+    let cold = MemoryConfig::new();
+    let config = TieredConfig {
+        fast: fast.clone(),
+        cold: cold.clone(),
+        policy: TierPolicy {
+            fast_data_lifetime: Some(Duration::from_secs(60 * 60)),
+            ..TierPolicy::default()
+        },
+    };
+    create_store_repo(&config, encoding_config(), &buffer)?;
+
+    // No blocks are old enough yet.
+    let mut store = config.open()?;
+    assert_that!(store.migrate()?).is_equal_to(0);
+
+    store.set_policy(TierPolicy {
+        fast_data_lifetime: Some(Duration::ZERO),
+        ..TierPolicy::default()
+    });
+    assert_that!(store.migrate()?).is_greater_than(0);
+
+    assert_that!(fast.open()?.list_blocks(BlockType::Data)?).is_empty();
+    assert_that!(fast.open()?.read_block(BlockKey::Super)?).is_some();
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::repo::{Compression, Encryption, RepoConfig};
+use acid_store::store::{
+    BlockKey, DataStore, ErrorKind, MemoryConfig, OpenStore, VerifyingConfig, VerifyingStore,
+};
+use common::*;
+use uuid::Uuid;
+
+mod common;
+
+/// Return whether `error` is a checksum mismatch for the block with the given `key`.
+fn is_mismatch(error: &acid_store::store::Error, key: BlockKey) -> bool {
+    error.kind() == ErrorKind::Corrupt
+        && matches!(
+            error.downcast_ref::<acid_store::Error>(),
+            Some(acid_store::Error::ChecksumMismatch { key: error_key }) if *error_key == key
+        )
+}
+
+#[rstest]
+fn repo_can_be_read_through_verifying_store(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo_config = RepoConfig::default();
+    repo_config.compression = Compression::None;
+    repo_config.encryption = Encryption::None;
+    let config = VerifyingConfig::new(MemoryConfig::new());
+
+    let repo = create_store_repo(&config, repo_config, &buffer)?;
+    assert_that!(repo.verify()?).is_empty();
+    drop(repo);
+
+    assert_that!(read_store_object(&config, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn corrupt_block_errs(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = VerifyingStore::new(inner.open()?);
+    store.write_block(BlockKey::Super, &buffer)?;
+
+    let mut contents = inner.open()?.read_block(BlockKey::Super)?.unwrap();
+    contents[0] ^= 0xff;
+    inner.open()?.write_block(BlockKey::Super, &contents)?;
+
+    let error = store.read_block(BlockKey::Super).unwrap_err();
+    assert_that!(is_mismatch(&error, BlockKey::Super)).is_true();
+
+    let error = store.read_blocks(&[BlockKey::Super]).unwrap_err();
+    assert_that!(is_mismatch(&error, BlockKey::Super)).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn truncated_block_errs() -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = VerifyingStore::new(inner.open()?);
+    inner.open()?.write_block(BlockKey::Super, &[0u8; 8])?;
+
+    let error = store.read_block(BlockKey::Super).unwrap_err();
+    assert_that!(is_mismatch(&error, BlockKey::Super)).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn misplaced_block_errs(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = VerifyingStore::new(inner.open()?);
+    let key = BlockKey::Data(Uuid::new_v4().into());
+    let other_key = BlockKey::Data(Uuid::new_v4().into());
+    store.write_block(key, &buffer)?;
+
+    let contents = inner.open()?.read_block(key)?.unwrap();
+    inner.open()?.write_block(other_key, &contents)?;
+
+    let error = store.read_block(other_key).unwrap_err();
+    assert_that!(is_mismatch(&error, other_key)).is_true();
+    assert_that!(store.read_block(key)?).is_equal_to(Some(buffer));
+
+    Ok(())
+}
@@ -0,0 +1,198 @@
+#![cfg(all(
+    feature = "repo-version",
+    feature = "encryption",
+    feature = "compression"
+))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::version::VersionRepo;
+use acid_store::repo::Commit;
+use common::*;
+
+mod common;
+
+/// Replace the contents of the object with the given `key` with `data`.
+fn write_version(repo: &mut VersionRepo<String>, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    let mut object = repo.insert(key.to_string());
+    object.write_all(data)?;
+    object.commit()?;
+    Ok(())
+}
+
+/// Read the contents of the given `object`.
+fn read_all(mut object: impl Read) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    object.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[rstest]
+fn insert_and_read_object(mut repo: VersionRepo<String>, buffer: Vec<u8>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", &buffer)?;
+
+    assert_that!(repo.contains("test")).is_true();
+    assert_that!(read_all(repo.object("test").unwrap())?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn old_versions_remain_readable(
+    mut repo: VersionRepo<String>,
+    smaller_buffer: Vec<u8>,
+    larger_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", &smaller_buffer)?;
+    let first = repo.create_version("test")?;
+    write_version(&mut repo, "test", &larger_buffer)?;
+    let second = repo.create_version("test")?;
+
+    assert_that!(first.id).is_less_than(second.id);
+    assert_that!(first.size).is_equal_to(smaller_buffer.len() as u64);
+    assert_that!(read_all(repo.version_object("test", first.id).unwrap())?)
+        .is_equal_to(&smaller_buffer);
+    assert_that!(read_all(repo.version_object("test", second.id).unwrap())?)
+        .is_equal_to(&larger_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn versions_are_listed_oldest_first(mut repo: VersionRepo<String>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", b"first")?;
+    let first = repo.create_version("test")?;
+    write_version(&mut repo, "test", b"second")?;
+    let second = repo.create_version("test")?;
+
+    assert_that!(repo.versions("test").unwrap().cloned().collect::<Vec<_>>())
+        .is_equal_to(vec![first, second]);
+    assert_that!(repo.versions("nonexistent")).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn creating_version_of_nonexistent_key_errs(mut repo: VersionRepo<String>) {
+    assert_that!(repo.create_version("test")).is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn remove_version(mut repo: VersionRepo<String>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", b"data")?;
+    let version = repo.create_version("test")?;
+
+    assert_that!(repo.remove_version("test", version.id)).is_true();
+    assert_that!(repo.remove_version("test", version.id)).is_false();
+    assert_that!(repo.get_version("test", version.id)).is_none();
+    assert_that!(repo.version_object("test", version.id).is_none()).is_true();
+
+    Ok(())
+}
+
+#[rstest]
+fn version_ids_are_not_reused(mut repo: VersionRepo<String>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", b"data")?;
+    let first = repo.create_version("test")?;
+    repo.remove_version("test", first.id);
+    let second = repo.create_version("test")?;
+
+    assert_that!(second.id).is_not_equal_to(first.id);
+
+    Ok(())
+}
+
+#[rstest]
+fn prune_versions_keeps_newest(mut repo: VersionRepo<String>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", b"data")?;
+    for _ in 0..5 {
+        repo.create_version("test")?;
+    }
+    let newest = repo
+        .versions("test")
+        .unwrap()
+        .rev()
+        .take(2)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    assert_that!(repo.prune_versions("test", 2)).is_ok_containing(3);
+    assert_that!(repo
+        .versions("test")
+        .unwrap()
+        .rev()
+        .cloned()
+        .collect::<Vec<_>>())
+    .is_equal_to(newest);
+
+    Ok(())
+}
+
+#[rstest]
+fn restore_version_replaces_current_object(mut repo: VersionRepo<String>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", b"old")?;
+    let version = repo.create_version("test")?;
+    write_version(&mut repo, "test", b"new")?;
+
+    repo.restore_version("test", version.id)?;
+
+    assert_that!(read_all(repo.object("test").unwrap())?).is_equal_to(b"old".to_vec());
+    assert_that!(repo.get_version("test", version.id)).is_some();
+    assert_that!(repo.restore_version("test", version.id + 1))
+        .is_err_variant(acid_store::Error::NotFound);
+
+    Ok(())
+}
+
+#[rstest]
+fn remove_object_removes_versions(mut repo: VersionRepo<String>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", b"data")?;
+    let version = repo.create_version("test")?;
+
+    assert_that!(repo.remove("test")).is_true();
+    assert_that!(repo.contains("test")).is_false();
+    assert_that!(repo.get_version("test", version.id)).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn versions_persist_after_commit(repo_store: RepoStore, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: VersionRepo<String> = repo_store.create()?;
+    write_version(&mut repo, "test", &buffer)?;
+    let version = repo.create_version("test")?;
+    write_version(&mut repo, "test", b"")?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: VersionRepo<String> = repo_store.open()?;
+
+    assert_that!(repo.get_version("test", version.id)).is_some_with_value(&version);
+    assert_that!(read_all(repo.version_object("test", version.id).unwrap())?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn versions_removed_on_rollback(mut repo: VersionRepo<String>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", b"data")?;
+    repo.commit()?;
+    let version = repo.create_version("test")?;
+
+    repo.rollback()?;
+
+    assert_that!(repo.get_version("test", version.id)).is_none();
+
+    Ok(())
+}
+
+#[rstest]
+fn verify_valid_repository_is_valid(mut repo: VersionRepo<String>) -> anyhow::Result<()> {
+    write_version(&mut repo, "test", b"data")?;
+    repo.create_version("test")?;
+
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+
+    Ok(())
+}
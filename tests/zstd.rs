@@ -0,0 +1,220 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Read, Write};
+use std::ops::Range;
+
+#[cfg(feature = "repo-file")]
+use acid_store::repo::file::{Entry, FileRepo};
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, Compression};
+use acid_store::store::{BlockKey, BlockType, DataStore, MemoryConfig, OpenStore};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The size of the dictionaries trained in these tests.
+const DICTIONARY_SIZE: usize = 4096;
+
+/// Return a small record which is similar to every other record.
+fn record(index: usize) -> Vec<u8> {
+    format!(
+        "{{\"id\": {index}, \"name\": \"user-{index}\", \"email\": \"user-{index}@example.com\", \
+         \"active\": {}, \"roles\": [\"reader\", \"writer\"]}}",
+        index % 2 == 0
+    )
+    .into_bytes()
+}
+
+/// Write a record to a new object for each index in `indices`.
+fn write_records(repo: &mut KeyRepo<String>, indices: Range<usize>) -> anyhow::Result<()> {
+    for index in indices {
+        let mut object = repo.insert(index.to_string());
+        object.write_all(&record(index))?;
+        object.commit()?;
+    }
+    Ok(())
+}
+
+/// Assert that the object for each index in `indices` contains its record.
+fn assert_records(repo: &KeyRepo<String>, indices: Range<usize>) -> anyhow::Result<()> {
+    for index in indices {
+        let mut data = Vec::new();
+        repo.object(&index.to_string())
+            .unwrap()
+            .read_to_end(&mut data)?;
+        assert_that!(data).is_equal_to(record(index));
+    }
+    Ok(())
+}
+
+/// Return the total size of the data blocks in `store`.
+fn data_size(store: &MemoryConfig) -> anyhow::Result<usize> {
+    let mut store = store.open()?;
+    let mut size = 0;
+    for id in store.list_blocks(BlockType::Data)? {
+        size += store
+            .read_block(BlockKey::Data(id))?
+            .map_or(0, |data| data.len());
+    }
+    Ok(size)
+}
+
+#[apply(store_config)]
+fn dictionary_compressed_data_can_be_read(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.compression = Compression::Zstd { level: 3 };
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_records(&mut repo, 0..1000)?;
+
+    repo.train_dictionary(DICTIONARY_SIZE)?;
+    write_records(&mut repo, 1000..1100)?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_records(&repo, 0..1100)?;
+    assert_that!(repo.verify()?).is_empty();
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn data_compressed_with_old_dictionaries_can_be_read(
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    repo_store.config.compression = Compression::Zstd { level: 3 };
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_records(&mut repo, 0..1000)?;
+
+    repo.train_dictionary(DICTIONARY_SIZE)?;
+    write_records(&mut repo, 1000..1100)?;
+    repo.train_dictionary(DICTIONARY_SIZE)?;
+    write_records(&mut repo, 1100..1200)?;
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_records(&repo, 0..1200)?;
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn dictionary_compressed_data_survives_clean(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    repo_store.config.compression = Compression::Zstd { level: 3 };
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_records(&mut repo, 0..1000)?;
+    repo.train_dictionary(DICTIONARY_SIZE)?;
+    write_records(&mut repo, 1000..1100)?;
+    repo.commit()?;
+
+    // Remove every other object so that packs need to be repacked.
+    for index in (0..1100).step_by(2) {
+        repo.remove(&index.to_string());
+    }
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    for index in (1..1100).step_by(2) {
+        assert_records(&repo, index..index + 1)?;
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn dictionary_makes_similar_chunks_smaller() -> anyhow::Result<()> {
+    let without_dictionary = RepoStore::new(zstd_config());
+    let with_dictionary = RepoStore::new(zstd_config());
+
+    let mut repo: KeyRepo<String> = without_dictionary.create()?;
+    write_records(&mut repo, 0..1000)?;
+    let size_before = data_size(&without_dictionary.store)?;
+    write_records(&mut repo, 1000..2000)?;
+    let size_without_dictionary = data_size(&without_dictionary.store)? - size_before;
+
+    let mut repo: KeyRepo<String> = with_dictionary.create()?;
+    write_records(&mut repo, 0..1000)?;
+    repo.train_dictionary(DICTIONARY_SIZE)?;
+    let size_before = data_size(&with_dictionary.store)?;
+    write_records(&mut repo, 1000..2000)?;
+    let size_with_dictionary = data_size(&with_dictionary.store)? - size_before;
+
+    assert_that!(size_with_dictionary).is_less_than(size_without_dictionary);
+
+    Ok(())
+}
+
+#[rstest]
+#[cfg(feature = "repo-file")]
+fn file_repo_can_train_dictionary() -> anyhow::Result<()> {
+    let repo_store = RepoStore::new(zstd_config());
+    let mut repo: FileRepo = repo_store.create()?;
+    for index in 0..1100 {
+        let path = index.to_string();
+        repo.create(&path, &Entry::file())?;
+        let mut object = repo.open(&path)?;
+        object.write_all(&record(index))?;
+        object.commit()?;
+        if index == 1000 {
+            repo.train_dictionary(DICTIONARY_SIZE)?;
+        }
+    }
+    repo.commit()?;
+    drop(repo);
+
+    let repo: FileRepo = repo_store.open()?;
+
+    for index in 0..1100 {
+        let mut data = Vec::new();
+        repo.open(index.to_string())?.read_to_end(&mut data)?;
+        assert_that!(data).is_equal_to(record(index));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn training_without_enough_data_errs() -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(zstd_config())?;
+
+    assert_that!(repo.train_dictionary(DICTIONARY_SIZE)).is_err();
+
+    Ok(())
+}
+
+#[rstest]
+fn training_without_zstd_compression_errs() -> anyhow::Result<()> {
+    let mut config = zstd_config();
+    config.compression = Compression::Lz4 { level: 1 };
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+    write_records(&mut repo, 0..1000)?;
+
+    assert_that!(repo.train_dictionary(DICTIONARY_SIZE))
+        .is_err_variant(acid_store::Error::UnsupportedRepo);
+
+    Ok(())
+}
+
+#[rstest]
+fn rollback_discards_dictionary() -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(zstd_config())?;
+    write_records(&mut repo, 0..1000)?;
+    repo.commit()?;
+    let header_size = repo.estimated_header_size();
+
+    repo.train_dictionary(DICTIONARY_SIZE)?;
+    assert_that!(repo.estimated_header_size()).is_greater_than(header_size);
+
+    repo.rollback()?;
+
+    assert_that!(repo.estimated_header_size()).is_equal_to(header_size);
+    assert_records(&repo, 0..1000)?;
+
+    Ok(())
+}